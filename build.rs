@@ -34,6 +34,7 @@ fn main() {
         ("src/conf/composing.rs", "ComposingSettings"),
         ("src/conf/tags.rs", "TagsSettings"),
         ("src/conf/pgp.rs", "PGPSettings"),
+        ("src/conf/smime.rs", "SmimeSettings"),
     ]);
     #[cfg(feature = "cli-docs")]
     {
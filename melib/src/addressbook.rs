@@ -22,6 +22,9 @@
 #[cfg(feature = "vcard")]
 pub mod vcard;
 
+#[cfg(feature = "carddav")]
+pub mod carddav;
+
 pub mod mutt;
 
 use std::{collections::HashMap, ops::Deref};
@@ -0,0 +1,667 @@
+/*
+ * meli - addressbook module
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # CardDAV address book synchronization
+//!
+//! Syncs the cards of an [`AddressBook`] with an address book collection on a
+//! CardDAV server ([RFC 6352](https://datatracker.ietf.org/doc/rfc6352/)).
+//!
+//! The vCards of the collection are listed with their ETags. Cards that are
+//! new or changed on the server are downloaded, local edits are uploaded with
+//! an `If-Match` precondition and cards deleted on one side are deleted on the
+//! other. When a card changed on both sides the server's version is kept and
+//! the card is reported as a conflict.
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+};
+
+use isahc::{
+    auth::{Authentication, Credentials},
+    config::{Configurable, RedirectPolicy},
+    AsyncBody, AsyncReadResponseExt, HttpClient, Request, Response,
+};
+use uuid::Uuid;
+
+use super::{
+    vcard::{self, CardDeserializer},
+    Card, CardId,
+};
+use crate::error::{Error, NetworkErrorKind, Result};
+
+const PROPFIND_ETAGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:resourcetype/>
+    <d:getetag/>
+  </d:prop>
+</d:propfind>"#;
+
+#[derive(Debug, Clone)]
+pub struct CardDavConf {
+    /// URL of the address book collection.
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
+/// A card as it was on the server after the last sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedCard {
+    pub etag: String,
+    pub card_id: CardId,
+    /// The card as [`vcard::to_vcard`] wrote it, to detect local edits.
+    pub vcard: String,
+}
+
+/// Cards synced with the server, by their path on the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncState {
+    pub cards: HashMap<String, SyncedCard>,
+}
+
+#[derive(Debug, Default)]
+pub struct SyncResult {
+    pub state: SyncState,
+    /// Cards that are new or changed on the server.
+    pub updated: Vec<Card>,
+    /// Cards deleted on the server.
+    pub removed: Vec<CardId>,
+    /// Number of cards uploaded to or deleted from the server.
+    pub pushed: usize,
+    /// Names of cards that changed both locally and on the server.
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SyncAction {
+    /// Download the card at `href`.
+    Fetch(String),
+    /// Download the card at `href`, discarding its local edits.
+    Conflict { href: String, card_id: CardId },
+    /// Upload a local card, as a new resource if there is no `etag`.
+    Push {
+        href: String,
+        card_id: CardId,
+        etag: Option<String>,
+    },
+    /// Delete a card deleted locally from the server.
+    DeleteRemote {
+        href: String,
+        card_id: CardId,
+        etag: String,
+    },
+    /// Delete a card deleted on the server locally.
+    DeleteLocal { href: String, card_id: CardId },
+}
+
+impl SyncAction {
+    fn href(&self) -> &str {
+        match self {
+            SyncAction::Fetch(href)
+            | SyncAction::Conflict { href, .. }
+            | SyncAction::Push { href, .. }
+            | SyncAction::DeleteRemote { href, .. }
+            | SyncAction::DeleteLocal { href, .. } => href,
+        }
+    }
+}
+
+/// Outcome of a conditional `PUT` or `DELETE`.
+enum Written {
+    /// The request succeeded, with the new ETag of the resource if the server
+    /// returned one.
+    Done(Option<String>),
+    /// The resource changed on the server since it was last synced.
+    Conflict,
+}
+
+#[derive(Debug)]
+pub struct CardDavClient {
+    client: HttpClient,
+    /// Scheme and authority of the server, e.g. `https://dav.example.com`.
+    origin: String,
+    /// Path of the address book collection, ending in `/`.
+    collection: String,
+}
+
+impl CardDavClient {
+    pub fn new(conf: &CardDavConf) -> Result<Self> {
+        let uri = conf
+            .url
+            .parse::<isahc::http::Uri>()
+            .map_err(|err| Error::new(format!("Invalid CardDAV url {}: {}", conf.url, err)))?;
+        let authority = uri
+            .authority()
+            .ok_or_else(|| Error::new(format!("Invalid CardDAV url {}: no host", conf.url)))?;
+        let origin = format!("{}://{}", uri.scheme_str().unwrap_or("https"), authority);
+        let mut collection = uri.path().to_string();
+        if !collection.ends_with('/') {
+            collection.push('/');
+        }
+        let client = HttpClient::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .redirect_policy(RedirectPolicy::Limit(10))
+            .authentication(Authentication::basic())
+            .credentials(Credentials::new(&conf.username, &conf.password))
+            .build()?;
+        Ok(CardDavClient {
+            client,
+            origin,
+            collection,
+        })
+    }
+
+    /// Syncs the `local` cards of the address book with the server, starting
+    /// from the `state` of the last sync.
+    pub async fn sync(&self, local: HashMap<CardId, Card>, state: SyncState) -> Result<SyncResult> {
+        let remote = self.list().await?;
+        let actions = plan(&self.collection, &remote, &local, &state);
+        let mut ret = SyncResult {
+            state,
+            ..SyncResult::default()
+        };
+        for action in actions {
+            match action {
+                SyncAction::Fetch(href) => {
+                    self.fetch(&href, None, &mut ret).await?;
+                }
+                SyncAction::Conflict { href, card_id } => {
+                    ret.conflicts.push(card_name(&local, card_id, &href));
+                    self.fetch(&href, Some(card_id), &mut ret).await?;
+                }
+                SyncAction::Push {
+                    href,
+                    card_id,
+                    etag,
+                } => {
+                    let vcard = vcard::to_vcard(&local[&card_id]);
+                    match self.put(&href, &vcard, etag.as_deref()).await? {
+                        Written::Done(Some(etag)) => {
+                            ret.state.cards.insert(
+                                href,
+                                SyncedCard {
+                                    etag,
+                                    card_id,
+                                    vcard,
+                                },
+                            );
+                        }
+                        Written::Done(None) => {
+                            /* The server didn't return the new ETag, it might
+                             * have changed the card when storing it. */
+                            self.fetch(&href, Some(card_id), &mut ret).await?;
+                        }
+                        Written::Conflict => {
+                            ret.conflicts.push(card_name(&local, card_id, &href));
+                            self.fetch(&href, Some(card_id), &mut ret).await?;
+                            continue;
+                        }
+                    }
+                    ret.pushed += 1;
+                }
+                SyncAction::DeleteRemote {
+                    href,
+                    card_id,
+                    etag,
+                } => match self.delete(&href, &etag).await? {
+                    Written::Done(_) => {
+                        ret.state.cards.remove(&href);
+                        ret.pushed += 1;
+                    }
+                    Written::Conflict => {
+                        ret.conflicts.push(card_name(&local, card_id, &href));
+                        self.fetch(&href, Some(card_id), &mut ret).await?;
+                    }
+                },
+                SyncAction::DeleteLocal { href, card_id } => {
+                    ret.state.cards.remove(&href);
+                    ret.removed.push(card_id);
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    fn url(&self, href: &str) -> String {
+        format!("{}{}", self.origin, href)
+    }
+
+    /// Lists the path and ETag of every vCard in the collection.
+    async fn list(&self) -> Result<Vec<(String, String)>> {
+        let req = Request::builder()
+            .method("PROPFIND")
+            .uri(self.url(&self.collection))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(PROPFIND_ETAGS)
+            .map_err(|err| Error::new(err.to_string()))?;
+        let mut res = self.client.send_async(req).await?;
+        check_status(&res, "Could not list the CardDAV address book")?;
+        let text = res.text().await?;
+        Ok(parse_multistatus(&text, &self.origin, &self.collection))
+    }
+
+    /// Downloads the card at `href` and stores it in `ret` with `card_id`, or
+    /// the id it was synced with before.
+    async fn fetch(&self, href: &str, card_id: Option<CardId>, ret: &mut SyncResult) -> Result<()> {
+        let mut res = self.client.get_async(self.url(href)).await?;
+        check_status(&res, &format!("Could not download {}", href))?;
+        let etag = header(&res, "ETag").unwrap_or_default();
+        let text = res.text().await?;
+        let mut card = match parse_vcard(&text) {
+            Ok(card) => card,
+            Err(err) => {
+                log::warn!("Could not parse vCard {}: {}", href, err);
+                return Ok(());
+            }
+        };
+        let card_id = card_id
+            .or_else(|| ret.state.cards.get(href).map(|c| c.card_id))
+            .unwrap_or_else(|| CardId::Uuid(Uuid::new_v5(&Uuid::NAMESPACE_URL, href.as_bytes())));
+        card.set_id(card_id);
+        ret.state.cards.insert(
+            href.to_string(),
+            SyncedCard {
+                etag,
+                card_id,
+                vcard: vcard::to_vcard(&card),
+            },
+        );
+        ret.updated.push(card);
+        Ok(())
+    }
+
+    /// Uploads `vcard` to `href`, if it still has `etag` on the server or, if
+    /// `etag` is `None`, if it doesn't exist yet.
+    async fn put(&self, href: &str, vcard: &str, etag: Option<&str>) -> Result<Written> {
+        let req = Request::builder()
+            .method("PUT")
+            .uri(self.url(href))
+            .header("Content-Type", "text/vcard; charset=utf-8");
+        let req = match etag {
+            Some(etag) => req.header("If-Match", etag),
+            None => req.header("If-None-Match", "*"),
+        }
+        .body(vcard.to_string())
+        .map_err(|err| Error::new(err.to_string()))?;
+        let res = self.client.send_async(req).await?;
+        if res.status() == isahc::http::StatusCode::PRECONDITION_FAILED {
+            return Ok(Written::Conflict);
+        }
+        check_status(&res, &format!("Could not upload {}", href))?;
+        Ok(Written::Done(header(&res, "ETag")))
+    }
+
+    /// Deletes `href` from the server, if it still has `etag`.
+    async fn delete(&self, href: &str, etag: &str) -> Result<Written> {
+        let req = Request::builder()
+            .method("DELETE")
+            .uri(self.url(href))
+            .header("If-Match", etag)
+            .body(())
+            .map_err(|err| Error::new(err.to_string()))?;
+        let res = self.client.send_async(req).await?;
+        match res.status() {
+            isahc::http::StatusCode::PRECONDITION_FAILED => Ok(Written::Conflict),
+            isahc::http::StatusCode::NOT_FOUND => Ok(Written::Done(None)),
+            _ => {
+                check_status(&res, &format!("Could not delete {}", href))?;
+                Ok(Written::Done(None))
+            }
+        }
+    }
+}
+
+fn check_status(res: &Response<AsyncBody>, summary: &str) -> Result<()> {
+    if res.status().is_success() {
+        return Ok(());
+    }
+    let kind: NetworkErrorKind = res.status().into();
+    Err(Error::new(format!("{}: server replied {}", summary, res.status())).set_kind(kind.into()))
+}
+
+fn header(res: &Response<AsyncBody>, name: &str) -> Option<String> {
+    res.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+fn card_name(local: &HashMap<CardId, Card>, card_id: CardId, href: &str) -> String {
+    local
+        .get(&card_id)
+        .map(|c| c.name())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(href)
+        .to_string()
+}
+
+/// Parses a vCard downloaded from the server.
+fn parse_vcard(text: &str) -> Result<Card> {
+    /* Unfold long lines, see RFC 6350 section 3.2. */
+    let text = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let text = format!("{}\n", text.trim());
+    let mut card: Card = CardDeserializer::from_str(&text).and_then(TryInto::try_into)?;
+    card.set_external_resource(false);
+    Ok(card)
+}
+
+/// Decides what to do with each card, given the path and ETag of each card
+/// on the server.
+fn plan(
+    collection: &str,
+    remote: &[(String, String)],
+    local: &HashMap<CardId, Card>,
+    state: &SyncState,
+) -> Vec<SyncAction> {
+    let edited = |synced: &SyncedCard| {
+        local
+            .get(&synced.card_id)
+            .map_or(false, |card| vcard::to_vcard(card) != synced.vcard)
+    };
+    let mut ret = vec![];
+    for (href, etag) in remote {
+        let synced = match state.cards.get(href) {
+            Some(synced) => synced,
+            None => {
+                ret.push(SyncAction::Fetch(href.clone()));
+                continue;
+            }
+        };
+        let card_id = synced.card_id;
+        if synced.etag != *etag {
+            ret.push(if edited(synced) {
+                SyncAction::Conflict {
+                    href: href.clone(),
+                    card_id,
+                }
+            } else {
+                SyncAction::Fetch(href.clone())
+            });
+        } else if !local.contains_key(&card_id) {
+            ret.push(SyncAction::DeleteRemote {
+                href: href.clone(),
+                card_id,
+                etag: etag.clone(),
+            });
+        } else if edited(synced) {
+            ret.push(SyncAction::Push {
+                href: href.clone(),
+                card_id,
+                etag: Some(etag.clone()),
+            });
+        }
+    }
+    let remote_hrefs = remote
+        .iter()
+        .map(|(href, _)| href.as_str())
+        .collect::<HashSet<&str>>();
+    for (href, synced) in state.cards.iter() {
+        if remote_hrefs.contains(href.as_str()) {
+            continue;
+        }
+        /* Deleted on the server. Upload the card again if it was edited
+         * locally meanwhile. */
+        ret.push(if edited(synced) {
+            SyncAction::Push {
+                href: href.clone(),
+                card_id: synced.card_id,
+                etag: None,
+            }
+        } else {
+            SyncAction::DeleteLocal {
+                href: href.clone(),
+                card_id: synced.card_id,
+            }
+        });
+    }
+    let synced_ids = state
+        .cards
+        .values()
+        .map(|synced| synced.card_id)
+        .collect::<HashSet<CardId>>();
+    for (card_id, card) in local.iter() {
+        if card.external_resource() || synced_ids.contains(card_id) {
+            continue;
+        }
+        let uuid = match card_id {
+            CardId::Uuid(uuid) => *uuid,
+            CardId::Hash(hash) => Uuid::new_v5(&Uuid::NAMESPACE_OID, &hash.to_be_bytes()),
+        };
+        ret.push(SyncAction::Push {
+            href: format!("{}{}.vcf", collection, uuid),
+            card_id: *card_id,
+            etag: None,
+        });
+    }
+    ret.sort_by(|a, b| a.href().cmp(b.href()));
+    ret
+}
+
+/// Returns the contents of the elements with local name `name`, in any
+/// namespace.
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut ret = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let qname = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        if qname.rsplit(':').next() != Some(name) {
+            continue;
+        }
+        if tag.ends_with('/') {
+            ret.push("");
+            continue;
+        }
+        let closing = format!("</{}>", qname);
+        match rest.find(&closing) {
+            Some(close) => {
+                ret.push(&rest[..close]);
+                rest = &rest[close + closing.len()..];
+            }
+            None => break,
+        }
+    }
+    ret
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Parses the reply of a `PROPFIND` request into the path and ETag of each
+/// vCard in `collection`.
+fn parse_multistatus(xml: &str, origin: &str, collection: &str) -> Vec<(String, String)> {
+    xml_elements(xml, "response")
+        .into_iter()
+        .filter_map(|response| {
+            let href = xml_unescape(xml_elements(response, "href").first()?.trim());
+            let href = href
+                .strip_prefix(origin)
+                .map(str::to_string)
+                .unwrap_or(href);
+            if href.trim_end_matches('/') == collection.trim_end_matches('/')
+                || !xml_elements(response, "collection").is_empty()
+            {
+                return None;
+            }
+            let etag = xml_unescape(xml_elements(response, "getetag").first()?.trim());
+            if etag.is_empty() {
+                return None;
+            }
+            Some((href, etag))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_carddav_parse_multistatus() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+  <d:response>
+    <d:href>/dav/contacts/</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype><d:collection/><card:addressbook/></d:resourcetype>
+        <d:getetag>"collection"</d:getetag>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/dav/contacts/a.vcf</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:resourcetype/>
+        <d:getetag>&quot;1&quot;</d:getetag>
+      </d:prop>
+    </d:propstat>
+  </d:response>
+  <response xmlns="DAV:">
+    <href>https://dav.example.com/dav/contacts/b.vcf</href>
+    <propstat><prop><getetag>"2"</getetag></prop></propstat>
+  </response>
+</d:multistatus>"#;
+        assert_eq!(
+            parse_multistatus(xml, "https://dav.example.com", "/dav/contacts/"),
+            vec![
+                ("/dav/contacts/a.vcf".to_string(), "\"1\"".to_string()),
+                ("/dav/contacts/b.vcf".to_string(), "\"2\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_carddav_plan() {
+        let card = |name: &str| {
+            let mut card = Card::new();
+            card.set_name(name.to_string());
+            card
+        };
+        let synced = |card: &Card, etag: &str| SyncedCard {
+            etag: etag.to_string(),
+            card_id: *card.id(),
+            vcard: vcard::to_vcard(card),
+        };
+        let unchanged = card("unchanged");
+        let mut edited = card("edited");
+        let changed_remotely = card("changed remotely");
+        let mut conflict = card("conflict");
+        let deleted_locally = card("deleted locally");
+        let deleted_remotely = card("deleted remotely");
+        let new = card("new");
+        let state = SyncState {
+            cards: [
+                ("/c/1.vcf", synced(&unchanged, "1")),
+                ("/c/2.vcf", synced(&edited, "1")),
+                ("/c/3.vcf", synced(&changed_remotely, "1")),
+                ("/c/4.vcf", synced(&conflict, "1")),
+                ("/c/5.vcf", synced(&deleted_locally, "1")),
+                ("/c/6.vcf", synced(&deleted_remotely, "1")),
+            ]
+            .iter()
+            .map(|(href, synced)| (href.to_string(), synced.clone()))
+            .collect(),
+        };
+        edited.set_email("edited@example.com".to_string());
+        conflict.set_email("conflict@example.com".to_string());
+        let local = [
+            &unchanged,
+            &edited,
+            &changed_remotely,
+            &conflict,
+            &deleted_remotely,
+            &new,
+        ]
+        .iter()
+        .map(|c| (*c.id(), (*c).clone()))
+        .collect::<HashMap<CardId, Card>>();
+        let remote = [
+            ("/c/1.vcf", "1"),
+            ("/c/2.vcf", "1"),
+            ("/c/3.vcf", "2"),
+            ("/c/4.vcf", "2"),
+            ("/c/5.vcf", "1"),
+            ("/c/7.vcf", "1"),
+        ]
+        .iter()
+        .map(|(href, etag)| (href.to_string(), etag.to_string()))
+        .collect::<Vec<_>>();
+        let new_uuid = match new.id() {
+            CardId::Uuid(uuid) => *uuid,
+            CardId::Hash(_) => unreachable!(),
+        };
+        let mut expected = vec![
+            SyncAction::Push {
+                href: "/c/2.vcf".to_string(),
+                card_id: *edited.id(),
+                etag: Some("1".to_string()),
+            },
+            SyncAction::Fetch("/c/3.vcf".to_string()),
+            SyncAction::Conflict {
+                href: "/c/4.vcf".to_string(),
+                card_id: *conflict.id(),
+            },
+            SyncAction::DeleteRemote {
+                href: "/c/5.vcf".to_string(),
+                card_id: *deleted_locally.id(),
+                etag: "1".to_string(),
+            },
+            SyncAction::DeleteLocal {
+                href: "/c/6.vcf".to_string(),
+                card_id: *deleted_remotely.id(),
+            },
+            SyncAction::Fetch("/c/7.vcf".to_string()),
+            SyncAction::Push {
+                href: format!("/c/{}.vcf", new_uuid),
+                card_id: *new.id(),
+                etag: None,
+            },
+        ];
+        expected.sort_by(|a, b| a.href().cmp(b.href()));
+        assert_eq!(plan("/c/", &remote, &local, &state), expected);
+    }
+}
@@ -245,6 +245,59 @@ impl<V: VCardVersion> TryInto<Card> for VCard<V> {
     }
 }
 
+/// Serializes `card` as a version 4 vCard. Properties are written in a fixed
+/// order, so an unchanged card always gives the same output.
+pub fn to_vcard(card: &Card) -> String {
+    let mut ret = String::from("BEGIN:VCARD\r\nVERSION:4.0\r\n");
+    let mut push = |name: &str, value: &str| {
+        if !value.is_empty() {
+            ret.push_str(name);
+            ret.push(':');
+            ret.push_str(&value.replace("\r\n", "\\n").replace('\n', "\\n"));
+            ret.push_str("\r\n");
+        }
+    };
+    let uid = match (card.extra_property("UID"), card.id()) {
+        (Some(uid), _) => uid.to_string(),
+        (None, CardId::Uuid(uuid)) => format!("urn:uuid:{}", uuid),
+        (None, CardId::Hash(hash)) => format!(
+            "urn:uuid:{}",
+            Uuid::new_v5(&Uuid::NAMESPACE_OID, &hash.to_be_bytes())
+        ),
+    };
+    push("UID", &uid);
+    push("FN", card.name());
+    push("NICKNAME", card.additionalname());
+    push("TITLE", card.title());
+    if let Some(birthday) = card.birthday {
+        push(
+            "BDAY",
+            &crate::datetime::timestamp_to_string(birthday, Some("%Y%m%d"), true),
+        );
+    }
+    push("EMAIL", card.email());
+    push("URL", card.url());
+    push("KEY", card.key());
+    let mut extra = card
+        .extra_properties()
+        .iter()
+        .filter(|(k, _)| {
+            ![
+                "UID", "VERSION", "FN", "N", "NICKNAME", "BDAY", "EMAIL", "URL", "KEY",
+            ]
+            .iter()
+            .any(|p| k.eq_ignore_ascii_case(p))
+                && (!k.eq_ignore_ascii_case("TITLE") || card.title().is_empty())
+        })
+        .collect::<Vec<_>>();
+    extra.sort();
+    for (k, v) in extra {
+        push(k, v);
+    }
+    ret.push_str(FOOTER_CRLF);
+    ret
+}
+
 fn parse_card<'a>() -> impl Parser<'a, Vec<&'a str>> {
     move |input| {
         one_or_more(prefix(
@@ -329,3 +382,29 @@ fn test_card() {
     let j = "BEGIN:VCARD\nVERSION:4.0\nN:Gump;Forrest;;Mr.;\nFN:Forrest Gump\nORG:Bubba Gump Shrimp Co.\nTITLE:Shrimp Man\nPHOTO;MEDIATYPE=image/gif:http://www.example.com/dir_photos/my_photo.gif\nTEL;TYPE=work,voice;VALUE=uri:tel:+1-111-555-1212\nTEL;TYPE=home,voice;VALUE=uri:tel:+1-404-555-1212\nADR;TYPE=WORK;PREF=1;LABEL=\"100 Waters Edge\\nBaytown\\, LA 30314\\nUnited States of America\":;;100 Waters Edge;Baytown;LA;30314;United States of America\nADR;TYPE=HOME;LABEL=\"42 Plantation St.\\nBaytown\\, LA 30314\\nUnited States of America\":;;42 Plantation St.;Baytown;LA;30314;United States of America\nEMAIL:forrestgump@example.com\nREV:20080424T195243Z\nx-qq:21588891\nEND:VCARD\n";
     println!("results = {:#?}", CardDeserializer::from_str(j).unwrap());
 }
+
+#[test]
+fn test_to_vcard() {
+    let mut card = Card::new();
+    card.set_name("Forrest Gump".to_string())
+        .set_email("forrestgump@example.com".to_string())
+        .set_extra_property(
+            "UID",
+            "urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1".to_string(),
+        )
+        .set_extra_property("ORG", "Bubba Gump Shrimp Co.".to_string())
+        .set_extra_property("NOTE", "line\nbreak".to_string());
+    let vcard = to_vcard(&card);
+    assert_eq!(
+        vcard,
+        "BEGIN:VCARD\r\nVERSION:4.0\r\nUID:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1\r\nFN:\
+         Forrest Gump\r\nEMAIL:forrestgump@example.com\r\nNOTE:line\\nbreak\r\nORG:Bubba Gump \
+         Shrimp Co.\r\nEND:VCARD\r\n"
+    );
+    let parsed: Card = CardDeserializer::from_str(&vcard)
+        .and_then(TryInto::try_into)
+        .unwrap();
+    assert_eq!(parsed.name(), "Forrest Gump");
+    assert_eq!(parsed.email(), "forrestgump@example.com");
+    assert_eq!(parsed.extra_property("ORG"), Some("Bubba Gump Shrimp Co."));
+}
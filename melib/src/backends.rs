@@ -36,6 +36,9 @@ pub mod jmap;
 pub mod maildir;
 #[cfg(feature = "mbox_backend")]
 pub mod mbox;
+#[cfg(feature = "pop3_backend")]
+pub mod pop3;
+pub mod virtual_mailbox;
 use std::{
     any::Any,
     borrow::Cow,
@@ -58,6 +61,9 @@ use self::maildir::MaildirType;
 use self::mbox::MboxType;
 #[cfg(feature = "imap_backend")]
 pub use self::nntp::NntpType;
+#[cfg(feature = "pop3_backend")]
+pub use self::pop3::Pop3Type;
+pub use self::virtual_mailbox::VirtualMailbox;
 use super::email::{Envelope, EnvelopeHash, Flag};
 use crate::{
     conf::AccountSettings,
@@ -189,6 +195,16 @@ impl Backends {
                 },
             );
         }
+        #[cfg(feature = "pop3_backend")]
+        {
+            b.register(
+                "pop3".to_string(),
+                Backend {
+                    create_fn: Box::new(|| Box::new(|f, i, ev| pop3::Pop3Type::new(f, i, ev))),
+                    validate_conf_fn: Box::new(pop3::Pop3Type::validate_config),
+                },
+            );
+        }
         #[cfg(feature = "notmuch_backend")]
         {
             b.register(
@@ -360,8 +376,33 @@ pub trait MailBackend: ::std::fmt::Debug + Send + Sync {
         mailbox_hash: MailboxHash,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>>;
 
+    /// Fetches the next page of envelopes older than those `fetch` loaded, for
+    /// a mailbox that was too large to fetch whole. See
+    /// [`BackendMailbox::older_envelopes`].
+    fn fetch_older(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        Err(
+            Error::new("Loading older messages is not supported in this backend.")
+                .set_kind(ErrorKind::NotSupported),
+        )
+    }
+
     fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()>;
     fn watch(&self) -> ResultFuture<()>;
+
+    /// Drops connections that may have died in the meantime, for example
+    /// while the machine was suspended, and connects again.
+    fn reconnect(&self) -> ResultFuture<()> {
+        self.is_online()
+    }
+
+    /// Closes the connections to the server cleanly before exiting.
+    fn logout(&self) -> ResultFuture<()> {
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
     fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>>;
     fn operation(&self, hash: EnvelopeHash) -> Result<Box<dyn BackendOp>>;
 
@@ -393,6 +434,13 @@ pub trait MailBackend: ::std::fmt::Debug + Send + Sync {
         mailbox_hash: MailboxHash,
     ) -> ResultFuture<()>;
 
+    /// Permanently removes messages that have been marked for deletion in
+    /// `mailbox_hash` but are still kept by the backend.
+    fn expunge(&mut self, _mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        Err(Error::new("Expunging is not supported in this backend.")
+            .set_kind(ErrorKind::NotSupported))
+    }
+
     fn collection(&self) -> crate::Collection;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
@@ -580,6 +628,16 @@ pub trait BackendMailbox: Debug {
     fn special_usage(&self) -> SpecialUsageMailbox;
     fn permissions(&self) -> MailboxPermissions;
     fn count(&self) -> Result<(usize, usize)>;
+    /// Describes how the mailbox's messages were last loaded, if the backend
+    /// loads some mailboxes differently than others.
+    fn fetch_strategy(&self) -> Option<String> {
+        None
+    }
+    /// How many of the mailbox's messages were left out by the last fetch and
+    /// can be loaded with [`MailBackend::fetch_older`].
+    fn older_envelopes(&self) -> usize {
+        0
+    }
 }
 
 crate::declare_u64_hash!(AccountHash);
@@ -720,6 +778,12 @@ impl LazyCountSet {
         self.set.len() + self.not_yet_seen
     }
 
+    /// How many of the counted envelopes have not been inserted yet.
+    #[inline(always)]
+    pub fn not_yet_seen(&self) -> usize {
+        self.not_yet_seen
+    }
+
     #[inline(always)]
     pub fn clear(&mut self) {
         self.set.clear();
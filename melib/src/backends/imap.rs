@@ -55,7 +55,7 @@ use crate::{
     },
     collection::Collection,
     conf::AccountSettings,
-    connections::timeout,
+    connections::{proxy::Proxy, timeout, tls::TlsOptions},
     email::{parser::BytesExt, *},
     error::{Error, Result, ResultIntoError},
 };
@@ -79,10 +79,49 @@ pub static SUPPORTED_CAPABILITIES: &[&str] = &[
     "LOGIN",
     "LOGINDISABLED",
     "MOVE",
+    "SORT",
     "SPECIAL-USE",
+    "THREAD=REFERENCES",
+    "UIDPLUS",
     "UNSELECT",
+    "UTF8=ACCEPT",
 ];
 
+/// When messages marked as `\Deleted` are permanently removed from the
+/// server.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ExpungePolicy {
+    /// Expunge right after deleting messages.
+    #[default]
+    Immediately,
+    /// Expunge a mailbox when it is closed, i.e. when another mailbox is
+    /// selected.
+    OnClose,
+    /// Expunge mailboxes with deleted messages every `expunge_interval`
+    /// seconds.
+    Periodic,
+    /// Expunge only on explicit request.
+    Manual,
+}
+
+impl FromStr for ExpungePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "immediately" => Ok(Self::Immediately),
+            "on_close" | "on-close" => Ok(Self::OnClose),
+            "periodic" => Ok(Self::Periodic),
+            "manual" => Ok(Self::Manual),
+            other => Err(Error::new(format!(
+                "Unrecognized expunge policy `{}`, expected one of: immediately, on_close, \
+                 periodic, manual",
+                other
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct EnvelopeCache {
     bytes: Option<Vec<u8>>,
@@ -97,9 +136,26 @@ pub struct ImapServerConf {
     pub server_port: u16,
     pub use_starttls: bool,
     pub use_tls: bool,
-    pub danger_accept_invalid_certs: bool,
+    pub tls: TlsOptions,
+    /// Connect through this proxy.
+    pub proxy: Option<Proxy>,
+    /// Connect to the target of the `_imaps._tcp` or `_imap._tcp` SRV record
+    /// of `server_hostname`, if there is one.
+    pub use_srv: bool,
     pub protocol: ImapProtocol,
+    /// Timeout of each command and response.
     pub timeout: Option<Duration>,
+    /// Timeout of establishing a connection, including TLS negotiation and
+    /// authentication.
+    pub connect_timeout: Option<Duration>,
+    /// How long a connection is assumed to be alive without activity before
+    /// it is reestablished.
+    pub protocol_timeout: Duration,
+    /// How many times to retry a connection that failed with a network error
+    /// or timed out.
+    pub connect_retries: u32,
+    /// Delay before the first retry, doubled on each following one.
+    pub retry_backoff: Duration,
 }
 
 type Capabilities = HashSet<Vec<u8>>;
@@ -154,8 +210,25 @@ pub struct UIDStore {
     highestmodseqs: Arc<Mutex<HashMap<MailboxHash, std::result::Result<ModSequence, ()>>>>,
     mailboxes: Arc<FutureMutex<HashMap<MailboxHash, ImapMailbox>>>,
     is_online: Arc<Mutex<(SystemTime, Result<()>)>>,
+    /// Whether `UTF8=ACCEPT` is enabled for the current connection, in which
+    /// case mailbox names are not encoded in modified UTF-7.
+    utf8_accept: Arc<Mutex<bool>>,
+    expunge_policy: ExpungePolicy,
+    expunge_interval: Duration,
+    /// Mailboxes with messages that have been marked as `\Deleted` but not
+    /// expunged yet.
+    pending_expunge: Arc<Mutex<HashSet<MailboxHash>>>,
     event_consumer: BackendEventConsumer,
     timeout: Option<Duration>,
+    /// How many search results to request and fetch envelope data for at a
+    /// time, newest first, or all of them at once if `0`.
+    search_page_size: usize,
+    /// Mailboxes with more messages than this are sorted and threaded by the
+    /// server, if it supports it, and only their newest messages are fetched.
+    /// `0` disables this.
+    large_mailbox_threshold: usize,
+    /// FETCH batch size measured from the throughput of previous batches.
+    fetch_chunk_size: Arc<Mutex<usize>>,
 }
 
 impl UIDStore {
@@ -185,10 +258,27 @@ impl UIDStore {
                 SystemTime::now(),
                 Err(Error::new("Account is uninitialised.")),
             ))),
+            utf8_accept: Default::default(),
+            expunge_policy: ExpungePolicy::default(),
+            expunge_interval: Duration::from_secs(5 * 60),
+            search_page_size: 500,
+            large_mailbox_threshold: 10000,
+            fetch_chunk_size: Arc::new(Mutex::new(250)),
+            pending_expunge: Default::default(),
             event_consumer,
             timeout,
         }
     }
+
+    /// Encode a mailbox name for use in commands, depending on whether
+    /// `UTF8=ACCEPT` is enabled.
+    fn encode_mailbox_name(&self, name: &str) -> String {
+        if *self.utf8_accept.lock().unwrap() {
+            name.to_string()
+        } else {
+            crate::backends::utf7::encode_utf7_imap(name)
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -222,6 +312,9 @@ impl MailBackend for ImapType {
                     deflate,
                     condstore,
                     oauth2,
+                    utf8_accept,
+                    sort,
+                    thread,
                 },
         } = self.server_conf.protocol
         {
@@ -263,6 +356,27 @@ impl MailBackend for ImapType {
                             };
                         }
                     }
+                    "UTF8=ACCEPT" => {
+                        if utf8_accept {
+                            *status = MailBackendExtensionStatus::Enabled { comment: None };
+                        } else {
+                            *status = MailBackendExtensionStatus::Supported {
+                                comment: Some("Disabled by user configuration"),
+                            };
+                        }
+                    }
+                    "SORT" | "THREAD=REFERENCES" => {
+                        let enabled = if name == "SORT" { sort } else { sort && thread };
+                        if enabled && self.uid_store.large_mailbox_threshold > 0 {
+                            *status = MailBackendExtensionStatus::Enabled {
+                                comment: Some("Used for large mailboxes"),
+                            };
+                        } else {
+                            *status = MailBackendExtensionStatus::Supported {
+                                comment: Some("Disabled by user configuration"),
+                            };
+                        }
+                    }
                     "AUTH=OAUTH2" => {
                         if oauth2 {
                             *status = MailBackendExtensionStatus::Enabled { comment: None };
@@ -298,39 +412,7 @@ impl MailBackend for ImapType {
         &mut self,
         mailbox_hash: MailboxHash,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
-        let cache_handle = {
-            #[cfg(feature = "sqlite3")]
-            if self.uid_store.keep_offline_cache {
-                match cache::Sqlite3Cache::get(self.uid_store.clone()).chain_err_summary(|| {
-                    format!(
-                        "Could not initialize cache for IMAP account {}. Resetting database.",
-                        self.uid_store.account_name
-                    )
-                }) {
-                    Ok(v) => Some(v),
-                    Err(err) => {
-                        (self.uid_store.event_consumer)(self.uid_store.account_hash, err.into());
-                        match cache::Sqlite3Cache::reset_db(&self.uid_store)
-                            .and_then(|()| cache::Sqlite3Cache::get(self.uid_store.clone()))
-                            .chain_err_summary(|| "Could not reset IMAP cache database.")
-                        {
-                            Ok(v) => Some(v),
-                            Err(err) => {
-                                (self.uid_store.event_consumer)(
-                                    self.uid_store.account_hash,
-                                    err.into(),
-                                );
-                                None
-                            }
-                        }
-                    }
-                }
-            } else {
-                None
-            }
-            #[cfg(not(feature = "sqlite3"))]
-            None
-        };
+        let cache_handle = self.cache_handle();
         let mut state = FetchState {
             stage: if self.uid_store.keep_offline_cache && cache_handle.is_some() {
                 FetchStage::InitialCache
@@ -341,6 +423,8 @@ impl MailBackend for ImapType {
             mailbox_hash,
             uid_store: self.uid_store.clone(),
             cache_handle,
+            window: vec![],
+            limit: None,
         };
 
         /* do this in a closure to prevent recursion limit error in async_stream
@@ -380,6 +464,37 @@ impl MailBackend for ImapType {
         }))
     }
 
+    fn fetch_older(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let mut state = FetchState {
+            stage: FetchStage::Finished,
+            connection: self.connection.clone(),
+            mailbox_hash,
+            uid_store: self.uid_store.clone(),
+            cache_handle: self.cache_handle(),
+            window: vec![],
+            limit: Some(self.uid_store.large_mailbox_threshold),
+        };
+        Ok(Box::pin(async_stream::try_stream! {
+            {
+                let f = &state.uid_store.mailboxes.lock().await[&mailbox_hash];
+                let mut older = f.older.lock().unwrap();
+                if let Some(page) = older.take() {
+                    let (stage, window, rest) =
+                        next_older_page(page, state.uid_store.large_mailbox_threshold);
+                    state.stage = stage;
+                    state.window = window;
+                    *older = rest;
+                }
+            };
+            while state.stage != FetchStage::Finished {
+                yield fetch_hlpr(&mut state).await?;
+            }
+        }))
+    }
+
     fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
         let main_conn = self.connection.clone();
         let uid_store = self.uid_store.clone();
@@ -451,24 +566,39 @@ impl MailBackend for ImapType {
             match timeout(timeout_dur, connection.lock()).await {
                 Ok(mut conn) => {
                     debug!("is_online");
-                    match timeout(timeout_dur, conn.connect()).await {
-                        Ok(Ok(())) => Ok(()),
-                        Err(err) | Ok(Err(err)) => {
-                            conn.stream = Err(err.clone());
-                            conn.connect().await
-                        }
+                    /* connect() applies the connect timeout and retries by itself. */
+                    let ret = conn.connect().await;
+                    if let Err(err) = ret.as_ref() {
+                        conn.stream = Err(err.clone());
                     }
+                    ret
                 }
                 Err(err) => Err(err),
             }
         }))
     }
 
+    fn reconnect(&self) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        let timeout_dur = self.server_conf.timeout;
+        Ok(Box::pin(async move {
+            let mut conn = timeout(timeout_dur, connection.lock()).await?;
+            /* Don't wait for a NOOP on a socket that is most likely dead. */
+            conn.stream = Err(Error::new("Reconnecting after resume.")
+                .set_kind(ErrorKind::Network(crate::error::NetworkErrorKind::None)));
+            let ret = conn.connect().await;
+            if let Err(err) = ret.as_ref() {
+                conn.stream = Err(err.clone());
+            }
+            ret
+        }))
+    }
+
     fn watch(&self) -> ResultFuture<()> {
         let server_conf = self.server_conf.clone();
         let main_conn = self.connection.clone();
         let uid_store = self.uid_store.clone();
-        Ok(Box::pin(async move {
+        let watch = Box::pin(async move {
             let has_idle: bool = match server_conf.protocol {
                 ImapProtocol::IMAP {
                     extension_use: ImapExtensionUse { idle, .. },
@@ -526,6 +656,22 @@ impl MailBackend for ImapType {
             }
             debug!("watch future returning");
             Ok(())
+        });
+        if self.uid_store.expunge_policy == ExpungePolicy::Periodic {
+            let expunge = Box::pin(periodic_expunge(
+                self.connection.clone(),
+                self.uid_store.clone(),
+            ));
+            return Ok(Box::pin(watch_with_expunge(watch, expunge)));
+        }
+        Ok(watch)
+    }
+
+    fn logout(&self) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.logout().await
         }))
     }
 
@@ -866,21 +1012,40 @@ impl MailBackend for ImapType {
         env_hashes: EnvelopeHashBatch,
         mailbox_hash: MailboxHash,
     ) -> ResultFuture<()> {
+        let uids: SmallVec<[UID; 64]> = {
+            let hash_index_lck = self.uid_store.hash_index.lock().unwrap();
+            env_hashes
+                .iter()
+                .filter_map(|env_hash| hash_index_lck.get(&env_hash).cloned().map(|(uid, _)| uid))
+                .collect()
+        };
         let flag_future = self.set_flags(
             env_hashes,
             mailbox_hash,
             smallvec::smallvec![(Ok(Flag::TRASHED), true)],
         )?;
         let connection = self.connection.clone();
+        let uid_store = self.uid_store.clone();
         Ok(Box::pin(async move {
             flag_future.await?;
-            let mut response = Vec::with_capacity(8 * 1024);
+            if uid_store.expunge_policy != ExpungePolicy::Immediately {
+                uid_store
+                    .pending_expunge
+                    .lock()
+                    .unwrap()
+                    .insert(mailbox_hash);
+                return Ok(());
+            }
             let mut conn = connection.lock().await;
-            conn.send_command("EXPUNGE".as_bytes()).await?;
-            conn.read_response(&mut response, RequiredResponses::empty())
-                .await?;
-            debug!("EXPUNGE response: {}", &String::from_utf8_lossy(&response));
-            Ok(())
+            conn.expunge(mailbox_hash, Some(&uids)).await
+        }))
+    }
+
+    fn expunge(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = connection.lock().await;
+            conn.expunge(mailbox_hash, None).await
         }))
     }
 
@@ -942,6 +1107,7 @@ impl MailBackend for ImapType {
                 /* FIXME  Do not try to CREATE a sub-mailbox in a mailbox
                  * that has the \Noinferiors flag set. */
             }
+            let path = uid_store.encode_mailbox_name(&path);
 
             let mut response = Vec::with_capacity(8 * 1024);
             {
@@ -1115,6 +1281,7 @@ impl MailBackend for ImapType {
                         (mailboxes[&mailbox_hash].separator as char).encode_utf8(&mut [0; 4]),
                     );
                 }
+                new_path = uid_store.encode_mailbox_name(&new_path);
                 command = format!(
                     "RENAME \"{}\" \"{}\"",
                     mailboxes[&mailbox_hash].imap_path(),
@@ -1181,7 +1348,7 @@ impl MailBackend for ImapType {
         }
         let mailbox_hash = mailbox_hash.unwrap();
         fn rec(q: &crate::search::Query, s: &mut String) {
-            use crate::search::{escape_double_quote, Query::*};
+            use crate::search::{escape_double_quote, FlagQuery, Query::*};
             match q {
                 Subject(t) => {
                     s.push_str(" SUBJECT \"");
@@ -1213,39 +1380,57 @@ impl MailBackend for ImapType {
                     s.extend(escape_double_quote(t).chars());
                     s.push('"');
                 }
+                Body(t) => {
+                    s.push_str(" BODY \"");
+                    s.extend(escape_double_quote(t).chars());
+                    s.push('"');
+                }
+                Before(t) => {
+                    s.push_str(" BEFORE ");
+                    s.push_str(&crate::datetime::timestamp_to_string(
+                        *t,
+                        Some("%d-%b-%Y"),
+                        true,
+                    ));
+                }
+                After(t) => {
+                    s.push_str(" SINCE ");
+                    s.push_str(&crate::datetime::timestamp_to_string(
+                        *t,
+                        Some("%d-%b-%Y"),
+                        true,
+                    ));
+                }
                 Flags(v) => {
                     for f in v {
-                        match f.as_str() {
-                            "draft" => {
-                                s.push_str(" DRAFT ");
-                            }
-                            "deleted" => {
-                                s.push_str(" DELETED ");
-                            }
-                            "flagged" => {
-                                s.push_str(" FLAGGED ");
-                            }
-                            "recent" => {
-                                s.push_str(" RECENT ");
-                            }
-                            "seen" | "read" => {
-                                s.push_str(" SEEN ");
-                            }
-                            "unseen" | "unread" => {
-                                s.push_str(" UNSEEN ");
-                            }
-                            "answered" => {
-                                s.push_str(" ANSWERED ");
-                            }
-                            "unanswered" => {
-                                s.push_str(" UNANSWERED ");
-                            }
-                            keyword => {
+                        let (flag, set) = match FlagQuery::new(f) {
+                            FlagQuery::Set(flag) => (flag, true),
+                            FlagQuery::Unset(flag) => (flag, false),
+                            FlagQuery::Tag(keyword) => {
                                 s.push_str(" KEYWORD ");
                                 s.push_str(keyword);
                                 s.push(' ');
+                                continue;
+                            }
+                        };
+                        let key = match flag {
+                            Flag::SEEN => "SEEN",
+                            Flag::FLAGGED => "FLAGGED",
+                            Flag::REPLIED => "ANSWERED",
+                            Flag::DRAFT => "DRAFT",
+                            Flag::TRASHED => "DELETED",
+                            _ => {
+                                s.push_str(if set { " KEYWORD " } else { " UNKEYWORD " });
+                                s.push_str("$Forwarded ");
+                                continue;
                             }
+                        };
+                        s.push(' ');
+                        if !set {
+                            s.push_str("UN");
                         }
+                        s.push_str(key);
+                        s.push(' ');
                     }
                 }
                 And(q1, q2) => {
@@ -1272,35 +1457,87 @@ impl MailBackend for ImapType {
         let uid_store = self.uid_store.clone();
 
         Ok(Box::pin(async move {
+            /* `0` fetches every result in one page. */
+            let page_size = match uid_store.search_page_size {
+                0 => usize::MAX,
+                n => n,
+            };
+            let (has_esearch, has_partial) = {
+                let capabilities = uid_store.capabilities.lock().unwrap();
+                (
+                    capabilities
+                        .iter()
+                        .any(|cap| cap.eq_ignore_ascii_case(b"ESEARCH")),
+                    capabilities
+                        .iter()
+                        .any(|cap| cap.eq_ignore_ascii_case(b"PARTIAL")),
+                )
+            };
+            let paged = has_partial && page_size != usize::MAX;
             let mut response = Vec::with_capacity(8 * 1024);
             let mut conn = connection.lock().await;
-            conn.examine_mailbox(mailbox_hash, &mut response, false)
-                .await?;
-            conn.send_command(format!("UID SEARCH CHARSET UTF-8 {}", query_str.trim()).as_bytes())
-                .await?;
-            conn.read_response(&mut response, RequiredResponses::SEARCH)
+            /* A search cannot match more messages than the mailbox holds, so
+             * re-examine it for a fresh EXISTS to bound the results by. */
+            let exists = conn
+                .examine_mailbox(mailbox_hash, &mut response, true)
+                .await?
+                .map_or(0, |select| select.exists);
+            let mut uids: Vec<UID> = vec![];
+            let mut offset: usize = 0;
+            loop {
+                let return_opts = search_return_opts(paged, has_esearch, offset, page_size);
+                response.clear();
+                conn.send_command(
+                    format!(
+                        "UID SEARCH {}CHARSET UTF-8 {}",
+                        return_opts,
+                        query_str.trim()
+                    )
+                    .as_bytes(),
+                )
                 .await?;
-            debug!(
-                "searching for {} returned: {}",
-                query_str,
-                String::from_utf8_lossy(&response)
-            );
-
-            for l in response.split_rn() {
-                if l.starts_with(b"* SEARCH") {
-                    use std::iter::FromIterator;
-                    let uid_index = uid_store.uid_index.lock()?;
-                    return Ok(SmallVec::from_iter(
-                        String::from_utf8_lossy(l[b"* SEARCH".len()..].trim())
-                            .split_whitespace()
-                            .map(UID::from_str)
-                            .filter_map(std::result::Result::ok)
-                            .filter_map(|uid| uid_index.get(&(mailbox_hash, uid)))
-                            .copied(),
-                    ));
+                conn.read_response(&mut response, RequiredResponses::SEARCH)
+                    .await?;
+                debug!(
+                    "searching for {} returned: {}",
+                    query_str,
+                    String::from_utf8_lossy(&response)
+                );
+
+                let (page, count) = search_response_page(&response, exists)?;
+                let page_is_empty = page.is_empty();
+                uids.extend(page);
+                offset = offset.saturating_add(page_size);
+                if !paged || page_is_empty || offset >= count {
+                    break;
                 }
             }
-            Err(Error::new(String::from_utf8_lossy(&response).to_string()))
+            uids.sort_unstable();
+            uids.dedup();
+            /* Fetch envelope data only for the results that are not loaded yet,
+             * one page at a time and newest first. */
+            let missing = {
+                let uid_index = uid_store.uid_index.lock()?;
+                uids.iter()
+                    .filter(|uid| !uid_index.contains_key(&(mailbox_hash, **uid)))
+                    .copied()
+                    .collect::<Vec<UID>>()
+            };
+            for chunk in missing.rchunks(page_size) {
+                let chunk = chunk
+                    .iter()
+                    .map(|uid| uid.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",");
+                conn.fetch_new_envelopes(mailbox_hash, &chunk).await?;
+            }
+            let uid_index = uid_store.uid_index.lock()?;
+            Ok(uids
+                .iter()
+                .rev()
+                .filter_map(|uid| uid_index.get(&(mailbox_hash, *uid)))
+                .copied()
+                .collect())
         }))
     }
 }
@@ -1327,8 +1564,8 @@ impl ImapType {
         let server_port = get_conf_val!(s["server_port"], 143)?;
         let use_tls = get_conf_val!(s["use_tls"], true)?;
         let use_starttls = use_tls && get_conf_val!(s["use_starttls"], server_port != 993)?;
-        let danger_accept_invalid_certs: bool =
-            get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        let proxy = Proxy::new(s)?;
+        let tls = TlsOptions::new(s)?;
         #[cfg(feature = "sqlite3")]
         let keep_offline_cache = get_conf_val!(s["offline_cache"], true)?;
         #[cfg(not(feature = "sqlite3"))]
@@ -1341,11 +1578,15 @@ impl ImapType {
             )));
         }
         let timeout = get_conf_val!(s["timeout"], 16_u64)?;
-        let timeout = if timeout == 0 {
-            None
-        } else {
-            Some(Duration::from_secs(timeout))
+        let to_timeout = |secs: u64| {
+            if secs == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(secs))
+            }
         };
+        let connect_timeout = to_timeout(get_conf_val!(s["connect_timeout"], timeout)?);
+        let timeout = to_timeout(get_conf_val!(s["command_timeout"], timeout)?);
         let server_conf = ImapServerConf {
             server_hostname: server_hostname.to_string(),
             server_username: server_username.to_string(),
@@ -1353,7 +1594,9 @@ impl ImapType {
             server_port,
             use_tls,
             use_starttls,
-            danger_accept_invalid_certs,
+            proxy,
+            use_srv: get_conf_val!(s["use_srv"], false)?,
+            tls,
             protocol: ImapProtocol::IMAP {
                 extension_use: ImapExtensionUse {
                     idle: get_conf_val!(s["use_idle"], true)?,
@@ -1361,14 +1604,28 @@ impl ImapType {
                     #[cfg(feature = "deflate_compression")]
                     deflate: get_conf_val!(s["use_deflate"], true)?,
                     oauth2: use_oauth2,
+                    utf8_accept: get_conf_val!(s["use_utf8_accept"], true)?,
+                    sort: get_conf_val!(s["use_sort"], true)?,
+                    thread: get_conf_val!(s["use_thread"], true)?,
                 },
             },
             timeout,
+            connect_timeout,
+            protocol_timeout: Duration::from_secs(get_conf_val!(
+                s["protocol_timeout"],
+                IMAP_PROTOCOL_TIMEOUT.as_secs()
+            )?),
+            connect_retries: get_conf_val!(s["connect_retries"], 2_u32)?,
+            retry_backoff: Duration::from_secs(get_conf_val!(s["retry_backoff"], 1_u64)?),
         };
         let account_hash = AccountHash::from_bytes(s.name.as_bytes());
         let account_name = Arc::new(s.name.to_string());
         let uid_store: Arc<UIDStore> = Arc::new(UIDStore {
             keep_offline_cache,
+            expunge_policy: get_conf_val!(s["expunge_policy"], ExpungePolicy::default())?,
+            expunge_interval: Duration::from_secs(get_conf_val!(s["expunge_interval"], 300_u64)?),
+            search_page_size: get_conf_val!(s["search_page_size"], 500_usize)?,
+            large_mailbox_threshold: get_conf_val!(s["large_mailbox_threshold"], 10000_usize)?,
             ..UIDStore::new(
                 account_hash,
                 account_name,
@@ -1386,6 +1643,42 @@ impl ImapType {
         }))
     }
 
+    /// Opens the offline cache, resetting it if it cannot be read, if the
+    /// account keeps one.
+    fn cache_handle(&self) -> Option<Box<dyn cache::ImapCache>> {
+        #[cfg(feature = "sqlite3")]
+        if self.uid_store.keep_offline_cache {
+            match cache::Sqlite3Cache::get(self.uid_store.clone()).chain_err_summary(|| {
+                format!(
+                    "Could not initialize cache for IMAP account {}. Resetting database.",
+                    self.uid_store.account_name
+                )
+            }) {
+                Ok(v) => Some(v),
+                Err(err) => {
+                    (self.uid_store.event_consumer)(self.uid_store.account_hash, err.into());
+                    match cache::Sqlite3Cache::reset_db(&self.uid_store)
+                        .and_then(|()| cache::Sqlite3Cache::get(self.uid_store.clone()))
+                        .chain_err_summary(|| "Could not reset IMAP cache database.")
+                    {
+                        Ok(v) => Some(v),
+                        Err(err) => {
+                            (self.uid_store.event_consumer)(
+                                self.uid_store.account_hash,
+                                err.into(),
+                            );
+                            None
+                        }
+                    }
+                }
+            }
+        } else {
+            None
+        }
+        #[cfg(not(feature = "sqlite3"))]
+        None
+    }
+
     pub fn shell(&mut self) {
         let mut conn = ImapConnection::new_connection(&self.server_conf, self.uid_store.clone());
 
@@ -1450,6 +1743,7 @@ impl ImapType {
         let mut mailboxes: HashMap<MailboxHash, ImapMailbox> = Default::default();
         let mut res = Vec::with_capacity(8 * 1024);
         let mut conn = connection.lock().await;
+        let utf8_accept = *conn.uid_store.utf8_accept.lock().unwrap();
         let has_list_status: bool = conn
             .uid_store
             .capabilities
@@ -1475,7 +1769,9 @@ impl ImapType {
             if !l.starts_with(b"*") {
                 continue;
             }
-            if let Ok(mut mailbox) = protocol_parser::list_mailbox_result(l).map(|(_, v)| v) {
+            if let Ok(mut mailbox) =
+                protocol_parser::list_mailbox_result(l, utf8_accept).map(|(_, v)| v)
+            {
                 if let Some(parent) = mailbox.parent {
                     if mailboxes.contains_key(&parent) {
                         mailboxes
@@ -1526,7 +1822,9 @@ impl ImapType {
             if !l.starts_with(b"*") {
                 continue;
             }
-            if let Ok(subscription) = protocol_parser::list_mailbox_result(l).map(|(_, v)| v) {
+            if let Ok(subscription) =
+                protocol_parser::list_mailbox_result(l, utf8_accept).map(|(_, v)| v)
+            {
                 if let Some(f) = mailboxes.get_mut(&subscription.hash()) {
                     if f.special_usage() == SpecialUsageMailbox::Normal
                         && subscription.special_usage() != SpecialUsageMailbox::Normal
@@ -1604,7 +1902,13 @@ impl ImapType {
                 s.name.as_str(),
             )));
         }
-        get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        TlsOptions::new(s)?;
+        for key in TlsOptions::KEYS {
+            s.extra.remove(*key);
+        }
+        Proxy::new(s)?;
+        s.extra.remove("proxy");
+        get_conf_val!(s["use_srv"], false)?;
         #[cfg(feature = "sqlite3")]
         get_conf_val!(s["offline_cache"], true)?;
         #[cfg(not(feature = "sqlite3"))]
@@ -1619,6 +1923,13 @@ impl ImapType {
         }
         get_conf_val!(s["use_idle"], true)?;
         get_conf_val!(s["use_condstore"], true)?;
+        get_conf_val!(s["use_utf8_accept"], true)?;
+        get_conf_val!(s["use_sort"], true)?;
+        get_conf_val!(s["use_thread"], true)?;
+        get_conf_val!(s["expunge_policy"], ExpungePolicy::default())?;
+        get_conf_val!(s["expunge_interval"], 300_u64)?;
+        get_conf_val!(s["search_page_size"], 500_usize)?;
+        get_conf_val!(s["large_mailbox_threshold"], 10000_usize)?;
         #[cfg(feature = "deflate_compression")]
         get_conf_val!(s["use_deflate"], true)?;
         #[cfg(not(feature = "deflate_compression"))]
@@ -1629,7 +1940,12 @@ impl ImapType {
                 s.name.as_str(),
             )));
         }
-        let _timeout = get_conf_val!(s["timeout"], 16_u64)?;
+        let timeout = get_conf_val!(s["timeout"], 16_u64)?;
+        get_conf_val!(s["connect_timeout"], timeout)?;
+        get_conf_val!(s["command_timeout"], timeout)?;
+        get_conf_val!(s["protocol_timeout"], IMAP_PROTOCOL_TIMEOUT.as_secs())?;
+        get_conf_val!(s["connect_retries"], 2_u32)?;
+        get_conf_val!(s["retry_backoff"], 1_u64)?;
         let extra_keys = s
             .extra
             .keys()
@@ -1663,10 +1979,160 @@ enum FetchStage {
     InitialFresh,
     InitialCache,
     ResyncCache,
-    FreshFetch { max_uid: UID },
+    /// `chunk_size` is `None` for the first batch of the mailbox.
+    FreshFetch {
+        max_uid: UID,
+        chunk_size: Option<usize>,
+    },
+    /// Fetches `FetchState::window` from `offset` onwards.
+    SortedFetch {
+        offset: usize,
+        chunk_size: Option<usize>,
+    },
     Finished,
 }
 
+/// Size of the first FETCH batch of a mailbox, kept small so that the first
+/// page of the listing is shown quickly.
+const FIRST_FETCH_CHUNK_SIZE: usize = 50;
+const MIN_FETCH_CHUNK_SIZE: usize = 50;
+const MAX_FETCH_CHUNK_SIZE: usize = 2000;
+/// How long a FETCH batch should take after the first one.
+const TARGET_FETCH_DURATION: Duration = Duration::from_secs(2);
+
+/// Returns the size of the next FETCH batch, given that the last batch of
+/// `fetched` messages took `elapsed` to complete. The size follows the
+/// measured throughput so that a batch takes about `TARGET_FETCH_DURATION`,
+/// but grows at most four times and shrinks at most by half each time.
+fn next_fetch_chunk_size(current: usize, fetched: usize, elapsed: Duration) -> usize {
+    if fetched == 0 {
+        return current;
+    }
+    let elapsed = std::cmp::max(elapsed, Duration::from_millis(1));
+    let ideal =
+        (fetched as f64 * TARGET_FETCH_DURATION.as_secs_f64() / elapsed.as_secs_f64()) as usize;
+    ideal
+        .clamp(current / 2, current.saturating_mul(4))
+        .clamp(MIN_FETCH_CHUNK_SIZE, MAX_FETCH_CHUNK_SIZE)
+}
+
+/// Returns the `RETURN` options of a `UID SEARCH` for the page of results
+/// starting `offset` results from the newest. With `PARTIAL` the results are
+/// requested one page at a time; with `ESEARCH` they are returned as compact
+/// sequence sets.
+fn search_return_opts(paged: bool, has_esearch: bool, offset: usize, page_size: usize) -> String {
+    if paged {
+        format!(
+            "RETURN (PARTIAL -{}:-{} COUNT) ",
+            offset + 1,
+            offset.saturating_add(page_size)
+        )
+    } else if has_esearch {
+        "RETURN (ALL COUNT) ".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Returns the UIDs of a `UID SEARCH` response and the total count of
+/// results, or an error if they are more than the `exists` messages of the
+/// mailbox.
+fn search_response_page(response: &[u8], exists: usize) -> Result<(Vec<UID>, usize)> {
+    for l in response.split_rn() {
+        if l.starts_with(b"* ESEARCH") {
+            let (_, esearch) = protocol_parser::esearch_results(l)?;
+            let len = protocol_parser::sequence_set_len(&esearch.results);
+            let count = esearch.count.unwrap_or(len);
+            if len > exists || count > exists {
+                return Err(Error::new(format!(
+                    "IMAP search returned {} results but the mailbox has only {} \
+                     messages",
+                    std::cmp::max(len, count),
+                    exists
+                ))
+                .set_kind(ErrorKind::External));
+            }
+            return Ok((esearch.results.into_iter().flatten().collect(), count));
+        } else if l.starts_with(b"* SEARCH") {
+            let uids = String::from_utf8_lossy(l[b"* SEARCH".len()..].trim())
+                .split_whitespace()
+                .map(UID::from_str)
+                .filter_map(std::result::Result::ok)
+                .collect::<Vec<UID>>();
+            let count = uids.len();
+            return Ok((uids, count));
+        }
+    }
+    Err(Error::new(String::from_utf8_lossy(response).to_string()))
+}
+
+#[test]
+fn test_imap_search_pages() {
+    assert_eq!(
+        search_return_opts(true, true, 0, 500),
+        "RETURN (PARTIAL -1:-500 COUNT) "
+    );
+    assert_eq!(
+        search_return_opts(true, true, 500, 500),
+        "RETURN (PARTIAL -501:-1000 COUNT) "
+    );
+    assert_eq!(
+        search_return_opts(false, true, 0, usize::MAX),
+        "RETURN (ALL COUNT) "
+    );
+    assert_eq!(search_return_opts(false, false, 0, usize::MAX), "");
+
+    assert_eq!(
+        search_response_page(
+            b"* ESEARCH (TAG \"a\") UID PARTIAL (-1:-3 10:11,7) COUNT 5\r\na OK done\r\n",
+            20
+        )
+        .unwrap(),
+        (vec![7, 10, 11], 5)
+    );
+    assert_eq!(
+        search_response_page(b"* SEARCH 3 1 2\r\na OK done\r\n", 20).unwrap(),
+        (vec![3, 1, 2], 3)
+    );
+    /* A huge range is rejected without being expanded. */
+    assert!(search_response_page(
+        b"* ESEARCH (TAG \"a\") UID ALL 1:4294967295\r\na OK done\r\n",
+        20
+    )
+    .is_err());
+    assert!(search_response_page(
+        b"* ESEARCH (TAG \"a\") UID COUNT 4294967295\r\na OK done\r\n",
+        20
+    )
+    .is_err());
+    assert!(search_response_page(b"a OK done\r\n", 20).is_err());
+}
+
+#[test]
+fn test_imap_next_fetch_chunk_size() {
+    /* Fast connection: grows at most four times. */
+    assert_eq!(
+        next_fetch_chunk_size(50, 50, Duration::from_millis(100)),
+        200
+    );
+    /* A batch that takes about the target duration keeps its size. */
+    assert_eq!(next_fetch_chunk_size(500, 500, Duration::from_secs(2)), 500);
+    /* Slow connection: shrinks at most by half, never below the minimum. */
+    assert_eq!(
+        next_fetch_chunk_size(500, 500, Duration::from_secs(60)),
+        250
+    );
+    assert_eq!(
+        next_fetch_chunk_size(60, 60, Duration::from_secs(60)),
+        MIN_FETCH_CHUNK_SIZE
+    );
+    assert_eq!(
+        next_fetch_chunk_size(1500, 1500, Duration::from_millis(10)),
+        MAX_FETCH_CHUNK_SIZE
+    );
+    assert_eq!(next_fetch_chunk_size(300, 0, Duration::from_secs(1)), 300);
+}
+
 #[derive(Debug)]
 struct FetchState {
     stage: FetchStage,
@@ -1674,6 +2140,284 @@ struct FetchState {
     mailbox_hash: MailboxHash,
     uid_store: Arc<UIDStore>,
     cache_handle: Option<Box<dyn cache::ImapCache>>,
+    /// UIDs of a large mailbox chosen by the server, newest first.
+    window: Vec<UID>,
+    /// How many more messages a `FreshFetch` fetches before it leaves the
+    /// rest of the mailbox for `MailBackend::fetch_older`.
+    limit: Option<usize>,
+}
+
+/// Chooses the messages of a large mailbox to fetch: the first `limit` UIDs
+/// of `sorted`, which is ordered newest first, or if `threads` is given,
+/// whole threads in the order of their newest message until at least `limit`
+/// messages are chosen. The result keeps the order of `sorted`.
+fn large_mailbox_window(sorted: &[UID], threads: Option<&[Vec<UID>]>, limit: usize) -> Vec<UID> {
+    let threads = match threads {
+        Some(threads) => threads,
+        None => return sorted.iter().take(limit).copied().collect(),
+    };
+    let thread_of: HashMap<UID, usize> = threads
+        .iter()
+        .enumerate()
+        .flat_map(|(i, thread)| thread.iter().map(move |uid| (*uid, i)))
+        .collect();
+    let mut chosen_threads: HashSet<usize> = HashSet::default();
+    let mut window: HashSet<UID> = HashSet::default();
+    for uid in sorted {
+        if window.len() >= limit {
+            break;
+        }
+        match thread_of.get(uid) {
+            Some(i) => {
+                if chosen_threads.insert(*i) {
+                    window.extend(threads[*i].iter().copied());
+                }
+            }
+            None => {
+                window.insert(*uid);
+            }
+        }
+    }
+    sorted
+        .iter()
+        .filter(|uid| window.contains(uid))
+        .copied()
+        .collect()
+}
+
+#[test]
+fn test_imap_large_mailbox_window() {
+    let sorted = [9, 8, 7, 6, 5, 4, 3, 2, 1];
+    let threads = [vec![1, 9], vec![2, 3], vec![4, 5, 6, 7, 8]];
+    assert_eq!(large_mailbox_window(&sorted, None, 2), vec![9, 8]);
+    assert_eq!(
+        large_mailbox_window(&sorted, Some(&threads[..]), 2),
+        vec![9, 1]
+    );
+    assert_eq!(
+        large_mailbox_window(&sorted, Some(&threads[..]), 3),
+        vec![9, 8, 7, 6, 5, 4, 1]
+    );
+    assert_eq!(large_mailbox_window(&sorted, None, 20), sorted.to_vec());
+    assert_eq!(
+        large_mailbox_window(&[], Some(&threads[..]), 2),
+        Vec::<UID>::new()
+    );
+}
+
+/// Splits the next page of about `limit` messages off the older messages of
+/// a large mailbox. Returns the stage that fetches the page, the window of a
+/// `SortedFetch` and the older messages left after the page.
+fn next_older_page(
+    older: OlderEnvelopes,
+    limit: usize,
+) -> (FetchStage, Vec<UID>, Option<OlderEnvelopes>) {
+    match older {
+        OlderEnvelopes::UpTo { max_uid } => (
+            FetchStage::FreshFetch {
+                max_uid,
+                chunk_size: None,
+            },
+            vec![],
+            None,
+        ),
+        OlderEnvelopes::Sorted { rest, threads } => {
+            let window = large_mailbox_window(&rest, threads.as_deref(), limit);
+            let chosen: HashSet<UID> = window.iter().copied().collect();
+            let rest: Vec<UID> = rest
+                .into_iter()
+                .filter(|uid| !chosen.contains(uid))
+                .collect();
+            (
+                FetchStage::SortedFetch {
+                    offset: 0,
+                    chunk_size: None,
+                },
+                window,
+                if rest.is_empty() {
+                    None
+                } else {
+                    Some(OlderEnvelopes::Sorted { rest, threads })
+                },
+            )
+        }
+    }
+}
+
+#[test]
+fn test_imap_next_older_page() {
+    assert_eq!(
+        next_older_page(OlderEnvelopes::UpTo { max_uid: 70 }, 20),
+        (
+            FetchStage::FreshFetch {
+                max_uid: 70,
+                chunk_size: None
+            },
+            vec![],
+            None
+        )
+    );
+    let older = OlderEnvelopes::Sorted {
+        rest: vec![7, 6, 5, 4, 3, 2],
+        threads: Some(vec![vec![1, 9], vec![2, 3], vec![4, 5, 6, 7, 8]]),
+    };
+    let (stage, window, rest) = next_older_page(older, 2);
+    assert_eq!(
+        stage,
+        FetchStage::SortedFetch {
+            offset: 0,
+            chunk_size: None
+        }
+    );
+    /* The thread of 7 is taken whole, except for 8 which was fetched before. */
+    assert_eq!(window, vec![7, 6, 5, 4]);
+    assert_eq!(
+        rest,
+        Some(OlderEnvelopes::Sorted {
+            rest: vec![3, 2],
+            threads: Some(vec![vec![1, 9], vec![2, 3], vec![4, 5, 6, 7, 8]]),
+        })
+    );
+    let (_, window, rest) = next_older_page(rest.unwrap(), 2);
+    assert_eq!(window, vec![3, 2]);
+    assert_eq!(rest, None);
+}
+
+/// Prepares the fetch of a mailbox with more than `large_mailbox_threshold`
+/// messages on the server, of which only the newest are fetched and the
+/// rest are left for `MailBackend::fetch_older`. The newest messages are
+/// chosen with server-side SORT, and if possible THREAD, or else by UID.
+/// Returns the stage to continue with, or `None` if the whole mailbox should
+/// be fetched. Which of these is used is recorded in the mailbox's
+/// `fetch_strategy`.
+async fn large_mailbox_first_page(
+    state: &mut FetchState,
+    exists: usize,
+    max_uid: UID,
+) -> Result<Option<FetchStage>> {
+    let threshold = state.uid_store.large_mailbox_threshold;
+    let (fetch_strategy, older, mailbox_exists, unseen) = {
+        let f = &state.uid_store.mailboxes.lock().await[&state.mailbox_hash];
+        (
+            f.fetch_strategy.clone(),
+            f.older.clone(),
+            f.exists.clone(),
+            f.unseen.clone(),
+        )
+    };
+    *older.lock().unwrap() = None;
+    if threshold == 0 || exists <= threshold {
+        *fetch_strategy.lock().unwrap() = None;
+        return Ok(None);
+    }
+    let mut conn = state.connection.lock().await;
+    let (use_sort, use_thread) = match conn.server_conf.protocol {
+        ImapProtocol::IMAP {
+            extension_use: ImapExtensionUse { sort, thread, .. },
+        } => (sort, thread),
+        ImapProtocol::ManageSieve => (false, false),
+    };
+    let (has_sort, has_thread) = {
+        let capabilities = state.uid_store.capabilities.lock().unwrap();
+        (
+            use_sort
+                && capabilities
+                    .iter()
+                    .any(|cap| cap.eq_ignore_ascii_case(b"SORT")),
+            use_thread
+                && capabilities
+                    .iter()
+                    .any(|cap| cap.eq_ignore_ascii_case(b"THREAD=REFERENCES")),
+        )
+    };
+    /* Only part of the mailbox is fetched, so count the unseen messages on the
+     * server. */
+    let mut response = Vec::with_capacity(8 * 1024);
+    conn.send_command(b"UID SEARCH UNSEEN").await?;
+    conn.read_response(&mut response, RequiredResponses::SEARCH)
+        .await?;
+    for l in response.split_rn() {
+        if l.starts_with(b"* SEARCH") {
+            let mut unseen = unseen.lock().unwrap();
+            unseen.clear();
+            unseen.set_not_yet_seen(protocol_parser::search_results(l)?.1.len());
+        }
+    }
+    /* Messages that are not fetched stay counted as not yet seen. */
+    {
+        let mut mailbox_exists = mailbox_exists.lock().unwrap();
+        mailbox_exists.clear();
+        mailbox_exists.set_not_yet_seen(exists);
+    }
+    let by_uid = || {
+        Some(format!(
+            "pages of {} of {} messages by descending UID, sorted and threaded locally",
+            threshold, exists
+        ))
+    };
+    if !has_sort {
+        *fetch_strategy.lock().unwrap() = by_uid();
+        state.limit = Some(threshold);
+        return Ok(Some(FetchStage::FreshFetch {
+            max_uid,
+            chunk_size: None,
+        }));
+    }
+    response.clear();
+    conn.send_command(b"UID SORT (REVERSE DATE) UTF-8 ALL")
+        .await?;
+    conn.read_response(&mut response, RequiredResponses::SORT)
+        .await?;
+    let mut sorted = vec![];
+    for l in response.split_rn() {
+        if l.starts_with(b"* SORT") {
+            sorted = protocol_parser::sort_results(l)?.1;
+        }
+    }
+    let threads = if has_thread {
+        response.clear();
+        conn.send_command(b"UID THREAD REFERENCES UTF-8 ALL")
+            .await?;
+        conn.read_response(&mut response, RequiredResponses::THREAD)
+            .await?;
+        let mut threads = vec![];
+        for l in response.split_rn() {
+            if l.starts_with(b"* THREAD") {
+                threads = protocol_parser::thread_results(l)?.1;
+            }
+        }
+        Some(threads)
+    } else {
+        None
+    };
+    if sorted.is_empty() {
+        *fetch_strategy.lock().unwrap() = by_uid();
+        state.limit = Some(threshold);
+        return Ok(Some(FetchStage::FreshFetch {
+            max_uid,
+            chunk_size: None,
+        }));
+    }
+    *fetch_strategy.lock().unwrap() = Some(format!(
+        "pages of {} of {} messages, newest first, chosen with server-side {}",
+        threshold,
+        exists,
+        if threads.is_some() {
+            "SORT and THREAD"
+        } else {
+            "SORT"
+        }
+    ));
+    let (stage, window, rest) = next_older_page(
+        OlderEnvelopes::Sorted {
+            rest: sorted,
+            threads,
+        },
+        threshold,
+    );
+    state.window = window;
+    *older.lock().unwrap() = rest;
+    Ok(Some(stage))
 }
 
 async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
@@ -1702,8 +2446,19 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                     state.stage = FetchStage::Finished;
                     return Ok(Vec::new());
                 }
+                if let Some(stage) = large_mailbox_first_page(
+                    state,
+                    select_response.exists,
+                    select_response.uidnext - 1,
+                )
+                .await?
+                {
+                    state.stage = stage;
+                    continue;
+                }
                 state.stage = FetchStage::FreshFetch {
                     max_uid: select_response.uidnext - 1,
+                    chunk_size: None,
                 };
                 continue;
             }
@@ -1773,23 +2528,27 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                 state.stage = FetchStage::InitialFresh;
                 continue;
             }
-            FetchStage::FreshFetch { max_uid } => {
+            FetchStage::FreshFetch { chunk_size, .. }
+            | FetchStage::SortedFetch { chunk_size, .. } => {
                 let FetchState {
                     ref mut stage,
                     ref connection,
                     mailbox_hash,
                     ref uid_store,
                     ref mut cache_handle,
+                    ref window,
+                    ref mut limit,
                 } = state;
                 let mailbox_hash = *mailbox_hash;
                 let mut our_unseen: BTreeSet<EnvelopeHash> = BTreeSet::default();
-                let (mailbox_path, mailbox_exists, no_select, unseen) = {
+                let (mailbox_path, mailbox_exists, no_select, unseen, older) = {
                     let f = &uid_store.mailboxes.lock().await[&mailbox_hash];
                     (
                         f.imap_path().to_string(),
                         f.exists.clone(),
                         f.no_select,
                         f.unseen.clone(),
+                        f.older.clone(),
                     )
                 };
                 if no_select {
@@ -1798,27 +2557,41 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                 }
                 let mut conn = connection.lock().await;
                 let mut response = Vec::with_capacity(8 * 1024);
-                let max_uid_left = max_uid;
-                let chunk_size = 250;
+                let first_batch = chunk_size.is_none();
+                let chunk_size = chunk_size.unwrap_or(FIRST_FETCH_CHUNK_SIZE);
+                let mut next_chunk_size = chunk_size;
+                let uid_set = match *stage {
+                    FetchStage::FreshFetch { max_uid: 0, .. } => None,
+                    FetchStage::FreshFetch { max_uid: 1, .. } => Some("1".to_string()),
+                    FetchStage::FreshFetch { max_uid, .. } => Some(format!(
+                        "{}:{}",
+                        std::cmp::max(max_uid.saturating_sub(chunk_size), 1),
+                        max_uid
+                    )),
+                    FetchStage::SortedFetch { offset, .. } => Some(
+                        window
+                            .iter()
+                            .skip(offset)
+                            .take(chunk_size)
+                            .map(|uid| uid.to_string())
+                            .collect::<Vec<String>>()
+                            .join(","),
+                    )
+                    .filter(|set| !set.is_empty()),
+                    _ => None,
+                };
 
                 let mut envelopes = Vec::with_capacity(chunk_size);
                 conn.examine_mailbox(mailbox_hash, &mut response, false)
                     .await?;
-                if max_uid_left > 0 {
-                    debug!("{} max_uid_left= {}", mailbox_hash, max_uid_left);
-                    let command = if max_uid_left == 1 {
-                        "UID FETCH 1 (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] \
-                         BODYSTRUCTURE)"
-                            .to_string()
-                    } else {
-                        format!(
-                            "UID FETCH {}:{} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS \
-                             (REFERENCES)] BODYSTRUCTURE)",
-                            std::cmp::max(max_uid_left.saturating_sub(chunk_size), 1),
-                            max_uid_left
-                        )
-                    };
+                if let Some(uid_set) = uid_set {
+                    let command = format!(
+                        "UID FETCH {} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] \
+                         BODYSTRUCTURE)",
+                        uid_set
+                    );
                     debug!("sending {:?}", &command);
+                    let started = std::time::Instant::now();
                     conn.send_command(command.as_bytes()).await?;
                     conn.read_response(&mut response, RequiredResponses::FETCH_REQUIRED)
                         .await
@@ -1829,6 +2602,17 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                             )
                         })?;
                     let (_, mut v, _) = protocol_parser::fetch_responses(&response)?;
+                    {
+                        let mut learned = uid_store.fetch_chunk_size.lock().unwrap();
+                        next_chunk_size =
+                            next_fetch_chunk_size(chunk_size, v.len(), started.elapsed());
+                        if first_batch {
+                            /* The small first batch is dominated by latency; continue from
+                             * what previous fetches on this connection measured. */
+                            next_chunk_size = std::cmp::max(next_chunk_size, *learned);
+                        }
+                        *learned = next_chunk_size;
+                    }
                     debug!(
                         "fetch response is {} bytes and {} lines and has {} parsed Envelopes",
                         response.len(),
@@ -1935,15 +2719,41 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                         .insert_existing_set(envelopes.iter().map(|env| env.hash()).collect::<_>());
                     drop(conn);
                 }
-                if max_uid_left <= 1 {
-                    unseen.lock().unwrap().set_not_yet_seen(0);
-                    mailbox_exists.lock().unwrap().set_not_yet_seen(0);
-                    *stage = FetchStage::Finished;
-                } else {
-                    *stage = FetchStage::FreshFetch {
-                        max_uid: std::cmp::max(max_uid_left.saturating_sub(chunk_size + 1), 1),
-                    };
+                if let (FetchStage::FreshFetch { .. }, Some(limit)) = (*stage, limit.as_mut()) {
+                    *limit = limit.saturating_sub(envelopes.len());
                 }
+                *stage = match *stage {
+                    FetchStage::FreshFetch { max_uid, .. } if max_uid <= 1 => {
+                        unseen.lock().unwrap().set_not_yet_seen(0);
+                        mailbox_exists.lock().unwrap().set_not_yet_seen(0);
+                        FetchStage::Finished
+                    }
+                    /* Leave the rest of a large mailbox for `fetch_older`. */
+                    FetchStage::FreshFetch { max_uid, .. }
+                        if *limit == Some(0) && max_uid > chunk_size + 1 =>
+                    {
+                        *older.lock().unwrap() = Some(OlderEnvelopes::UpTo {
+                            max_uid: max_uid - chunk_size - 1,
+                        });
+                        FetchStage::Finished
+                    }
+                    FetchStage::FreshFetch { max_uid, .. } => FetchStage::FreshFetch {
+                        max_uid: std::cmp::max(max_uid.saturating_sub(chunk_size + 1), 1),
+                        chunk_size: Some(next_chunk_size),
+                    },
+                    /* Messages outside of the window stay counted as not yet seen, see
+                     * `large_mailbox_first_page`. */
+                    FetchStage::SortedFetch { offset, .. }
+                        if offset + chunk_size >= window.len() =>
+                    {
+                        FetchStage::Finished
+                    }
+                    FetchStage::SortedFetch { offset, .. } => FetchStage::SortedFetch {
+                        offset: offset + chunk_size,
+                        chunk_size: Some(next_chunk_size),
+                    },
+                    other => other,
+                };
                 return Ok(envelopes);
             }
             FetchStage::Finished => {
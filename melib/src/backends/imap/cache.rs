@@ -148,7 +148,7 @@ mod sqlite3_m {
     CREATE INDEX IF NOT EXISTS envelope_idx ON envelopes(hash);
     CREATE INDEX IF NOT EXISTS mailbox_idx ON mailbox(mailbox_hash);",
         ),
-        version: 2,
+        version: 3,
     };
 
     impl ToSql for ModSequence {
@@ -692,6 +692,8 @@ pub(super) async fn fetch_cached_envs(state: &mut FetchState) -> Result<Option<V
         mailbox_hash,
         ref uid_store,
         cache_handle: _,
+        window: _,
+        limit: _,
     } = state;
     let mailbox_hash = *mailbox_hash;
     if !uid_store.keep_offline_cache {
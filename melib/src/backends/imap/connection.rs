@@ -22,7 +22,7 @@
 use super::protocol_parser::{ImapLineSplit, ImapResponse, RequiredResponses, SelectResponse};
 use crate::{
     backends::{MailboxHash, RefreshEvent},
-    connections::{lookup_ipv4, timeout, Connection},
+    connections::{connect_tcp, dns, timeout, Connection},
     email::parser::BytesExt,
     error::*,
     LogLevel,
@@ -39,12 +39,13 @@ use std::{
 };
 
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 pub use smol::Async as AsyncWrapper;
 
-const IMAP_PROTOCOL_TIMEOUT: Duration = Duration::from_secs(60 * 28);
+/// Default of the `protocol_timeout` setting: servers may drop connections
+/// that were inactive for more than 30 minutes (RFC 3501).
+pub const IMAP_PROTOCOL_TIMEOUT: Duration = Duration::from_secs(60 * 28);
 
-use super::{protocol_parser, Capabilities, ImapServerConf, UIDStore};
+use super::{protocol_parser, Capabilities, ExpungePolicy, ImapServerConf, UIDStore, UID};
 
 #[derive(Debug, Clone, Copy)]
 pub enum SyncPolicy {
@@ -70,6 +71,9 @@ pub struct ImapExtensionUse {
     #[cfg(feature = "deflate_compression")]
     pub deflate: bool,
     pub oauth2: bool,
+    pub utf8_accept: bool,
+    pub sort: bool,
+    pub thread: bool,
 }
 
 impl Default for ImapExtensionUse {
@@ -80,6 +84,9 @@ impl Default for ImapExtensionUse {
             #[cfg(feature = "deflate_compression")]
             deflate: true,
             oauth2: false,
+            utf8_accept: true,
+            sort: true,
+            thread: true,
         }
     }
 }
@@ -118,13 +125,43 @@ pub struct ImapConnection {
     pub uid_store: Arc<UIDStore>,
 }
 
+/// Opens a TCP connection to `host:port` within the connect timeout, naming
+/// the server in the error if it fails.
+fn tcp_connect(host: &str, port: u16, server_conf: &ImapServerConf) -> Result<std::net::TcpStream> {
+    connect_tcp(
+        host,
+        port,
+        server_conf.proxy.as_ref(),
+        server_conf.connect_timeout,
+    )
+}
+
 impl ImapStream {
     pub async fn new_connection(
         server_conf: &ImapServerConf,
         uid_store: &UIDStore,
     ) -> Result<(Capabilities, ImapStream)> {
-        use std::net::TcpStream;
-        let path = &server_conf.server_hostname;
+        let endpoint = if server_conf.use_srv && server_conf.proxy.is_none() {
+            let services: &[&str] = match server_conf.protocol {
+                ImapProtocol::IMAP { .. } if server_conf.use_tls && !server_conf.use_starttls => {
+                    &["_imaps._tcp"]
+                }
+                ImapProtocol::IMAP { .. } => &["_imap._tcp"],
+                ImapProtocol::ManageSieve => &[],
+            };
+            dns::resolve_service(
+                services,
+                &server_conf.server_hostname,
+                server_conf.server_port,
+            )
+        } else {
+            dns::ServiceEndpoint {
+                host: server_conf.server_hostname.clone(),
+                port: server_conf.server_port,
+                tls_domain: server_conf.server_hostname.clone(),
+            }
+        };
+        let (path, port) = (&endpoint.host, endpoint.port);
 
         let cmd_id = 1;
         let stream = if server_conf.use_tls {
@@ -134,28 +171,13 @@ impl ImapStream {
                     message: "Establishing TLS connection.".into(),
                 },
             );
-            let mut connector = TlsConnector::builder();
-            if server_conf.danger_accept_invalid_certs {
-                connector.danger_accept_invalid_certs(true);
-            }
-            let connector = connector
-                .build()
-                .chain_err_kind(crate::error::ErrorKind::Network(
-                    crate::error::NetworkErrorKind::InvalidTLSConnection,
-                ))?;
-
-            let addr = lookup_ipv4(path, server_conf.server_port)?;
+            let connector = server_conf.tls.connector()?;
 
-            let mut socket = AsyncWrapper::new(Connection::Tcp(
-                if let Some(timeout) = server_conf.timeout {
-                    TcpStream::connect_timeout(&addr, timeout)?
-                } else {
-                    TcpStream::connect(addr)?
-                },
-            ))?;
+            let mut socket =
+                AsyncWrapper::new(Connection::Tcp(tcp_connect(path, port, server_conf)?))?;
             if server_conf.use_starttls {
                 let err_fn = || {
-                    if server_conf.server_port == 993 {
+                    if port == 993 {
                         "STARTTLS failed. Server port is set to 993, which normally uses TLS. \
                          Maybe try disabling use_starttls."
                     } else {
@@ -216,7 +238,7 @@ impl ImapStream {
             {
                 // FIXME: This is blocking
                 let socket = socket.into_inner()?;
-                let mut conn_result = connector.connect(path, socket);
+                let mut conn_result = connector.connect(&endpoint.tls_domain, socket);
                 if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
                     conn_result
                 {
@@ -238,24 +260,18 @@ impl ImapStream {
                         }
                     }
                 }
-                AsyncWrapper::new(Connection::Tls(conn_result.chain_err_summary(|| {
+                let tls_stream = conn_result.chain_err_summary(|| {
                     format!("Could not initiate TLS negotiation to {}.", path)
-                })?))
-                .chain_err_summary(|| format!("Could not initiate TLS negotiation to {}.", path))?
+                })?;
+                server_conf
+                    .tls
+                    .verify_peer(&endpoint.tls_domain, &tls_stream)?;
+                AsyncWrapper::new(Connection::Tls(tls_stream)).chain_err_summary(|| {
+                    format!("Could not initiate TLS negotiation to {}.", path)
+                })?
             }
         } else {
-            let addr = if let Ok(a) = lookup_ipv4(path, server_conf.server_port) {
-                a
-            } else {
-                return Err(Error::new(format!("Could not lookup address {}", &path)));
-            };
-            AsyncWrapper::new(Connection::Tcp(
-                if let Some(timeout) = server_conf.timeout {
-                    TcpStream::connect_timeout(&addr, timeout)?
-                } else {
-                    TcpStream::connect(addr)?
-                },
-            ))?
+            AsyncWrapper::new(Connection::Tcp(tcp_connect(path, port, server_conf)?))?
         };
         if let Err(err) = stream
             .get_ref()
@@ -454,8 +470,17 @@ impl ImapStream {
         let mut buf: Vec<u8> = vec![0; Connection::IO_BUF_SIZE];
         ret.clear();
         let mut last_line_idx: usize = 0;
+        let dur = self.timeout;
         loop {
-            match timeout(self.timeout, self.stream.read(&mut buf)).await? {
+            match timeout(dur, self.stream.read(&mut buf))
+                .await
+                .map_err(|err| {
+                    Error::new(format!(
+                        "The server did not respond within {} seconds.",
+                        dur.map(|d| d.as_secs()).unwrap_or_default()
+                    ))
+                    .set_kind(err.kind)
+                })? {
                 Ok(0) => break,
                 Ok(b) => {
                     ret.extend_from_slice(&buf[0..b]);
@@ -575,11 +600,11 @@ impl ImapConnection {
         Box::pin(async move {
             if let (time, ref mut status @ Ok(())) = *self.uid_store.is_online.lock().unwrap() {
                 if SystemTime::now().duration_since(time).unwrap_or_default()
-                    >= IMAP_PROTOCOL_TIMEOUT
+                    >= self.server_conf.protocol_timeout
                 {
                     let err = Error::new(format!(
                         "Connection timed out after {} seconds",
-                        IMAP_PROTOCOL_TIMEOUT.as_secs()
+                        self.server_conf.protocol_timeout.as_secs()
                     ))
                     .set_kind(ErrorKind::Timeout);
                     *status = Err(err.clone());
@@ -604,7 +629,53 @@ impl ImapConnection {
                     return Ok(());
                 }
             }
-            let new_stream = ImapStream::new_connection(&self.server_conf, &self.uid_store).await;
+            let mut attempt = 0;
+            let new_stream = loop {
+                let res = match timeout(
+                    self.server_conf.connect_timeout,
+                    ImapStream::new_connection(&self.server_conf, &self.uid_store),
+                )
+                .await
+                {
+                    Ok(res) => res,
+                    Err(err) => Err(Error::new(format!(
+                        "Could not connect to {}:{} within {} seconds.",
+                        self.server_conf.server_hostname,
+                        self.server_conf.server_port,
+                        self.server_conf
+                            .connect_timeout
+                            .map(|d| d.as_secs())
+                            .unwrap_or_default()
+                    ))
+                    .set_kind(err.kind)),
+                };
+                match res {
+                    Err(err)
+                        if attempt < self.server_conf.connect_retries
+                            && (err.kind.is_network() || err.kind.is_timeout()) =>
+                    {
+                        attempt += 1;
+                        let backoff = self
+                            .server_conf
+                            .retry_backoff
+                            .saturating_mul(2_u32.saturating_pow(attempt - 1));
+                        (self.uid_store.event_consumer)(
+                            self.uid_store.account_hash,
+                            crate::backends::BackendEvent::AccountStateChange {
+                                message: format!(
+                                    "Connection attempt {} failed, retrying in {} seconds: {}",
+                                    attempt,
+                                    backoff.as_secs(),
+                                    err.summary
+                                )
+                                .into(),
+                            },
+                        );
+                        crate::connections::sleep(backoff).await;
+                    }
+                    res => break res,
+                }
+            };
             if let Err(err) = new_stream.as_ref() {
                 self.uid_store.is_online.lock().unwrap().1 = Err(err.clone());
             } else {
@@ -621,6 +692,9 @@ impl ImapConnection {
                             deflate,
                             idle: _idle,
                             oauth2: _,
+                            utf8_accept,
+                            sort: _,
+                            thread: _,
                         },
                 } => {
                     if capabilities.contains(&b"CONDSTORE"[..]) && condstore {
@@ -645,6 +719,23 @@ impl ImapConnection {
                             }
                         }
                     }
+                    *self.uid_store.utf8_accept.lock().unwrap() = false;
+                    if utf8_accept
+                        && capabilities.contains(&b"UTF8=ACCEPT"[..])
+                        && capabilities.contains(&b"ENABLE"[..])
+                    {
+                        /* RFC 6855: mailbox names are sent and received as UTF-8 instead of
+                         * modified UTF-7 once enabled. */
+                        let mut ret = Vec::new();
+                        self.send_command(b"ENABLE UTF8=ACCEPT").await?;
+                        self.read_response(&mut ret, RequiredResponses::empty())
+                            .await?;
+                        if let ImapResponse::Ok(_) = ImapResponse::try_from(ret.as_slice())? {
+                            *self.uid_store.utf8_accept.lock().unwrap() = ret.split_rn().any(|l| {
+                                l.starts_with(b"* ENABLED") && l.find(b"UTF8=ACCEPT").is_some()
+                            });
+                        }
+                    }
                     #[cfg(feature = "deflate_compression")]
                     if capabilities.contains(&b"COMPRESS=DEFLATE"[..]) && deflate {
                         let mut ret = Vec::new();
@@ -863,6 +954,7 @@ impl ImapConnection {
             ))
             .set_kind(crate::error::ErrorKind::Bug));
         }
+        self.close_for_expunge(Some(mailbox_hash)).await?;
         self.send_command(format!("SELECT \"{}\"", imap_path).as_bytes())
             .await?;
         self.read_response(ret, RequiredResponses::SELECT_REQUIRED)
@@ -949,6 +1041,7 @@ impl ImapConnection {
             ))
             .set_kind(crate::error::ErrorKind::Bug));
         }
+        self.close_for_expunge(None).await?;
         self.send_command(format!("EXAMINE \"{}\"", &imap_path).as_bytes())
             .await?;
         self.read_response(ret, RequiredResponses::EXAMINE_REQUIRED)
@@ -973,6 +1066,95 @@ impl ImapConnection {
         Ok(Some(select_response))
     }
 
+    /// Permanently removes messages marked as `\Deleted` in `mailbox_hash`.
+    ///
+    /// If `uids` is given and the server supports `UIDPLUS`, only these
+    /// messages are removed with `UID EXPUNGE`.
+    pub async fn expunge(&mut self, mailbox_hash: MailboxHash, uids: Option<&[UID]>) -> Result<()> {
+        let mut response = Vec::with_capacity(8 * 1024);
+        self.select_mailbox(mailbox_hash, &mut response, false)
+            .await?;
+        let has_uidplus = self
+            .uid_store
+            .capabilities
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|cap| cap.eq_ignore_ascii_case(b"UIDPLUS"));
+        match uids {
+            Some([]) => return Ok(()),
+            Some(uids) if has_uidplus => {
+                let cmd = format!(
+                    "UID EXPUNGE {}",
+                    uids.iter()
+                        .map(|uid| uid.to_string())
+                        .collect::<Vec<String>>()
+                        .join(",")
+                );
+                self.send_command(cmd.as_bytes()).await?;
+            }
+            _ => {
+                self.send_command(b"EXPUNGE").await?;
+            }
+        }
+        self.read_response(&mut response, RequiredResponses::empty())
+            .await?;
+        debug!("EXPUNGE response: {}", &String::from_utf8_lossy(&response));
+        if uids.is_none() || !has_uidplus {
+            self.uid_store
+                .pending_expunge
+                .lock()
+                .unwrap()
+                .remove(&mailbox_hash);
+        }
+        Ok(())
+    }
+
+    /// With the `on_close` expunge policy, sends `CLOSE` (which implicitly
+    /// expunges) if the currently selected mailbox has pending deletions and
+    /// is about to be deselected in favor of `next`.
+    async fn close_for_expunge(&mut self, next: Option<MailboxHash>) -> Result<()> {
+        if self.uid_store.expunge_policy != ExpungePolicy::OnClose {
+            return Ok(());
+        }
+        let current = match self.stream.as_ref()?.current_mailbox {
+            MailboxSelection::Select(current) if Some(current) != next => current,
+            _ => return Ok(()),
+        };
+        if !self
+            .uid_store
+            .pending_expunge
+            .lock()
+            .unwrap()
+            .remove(&current)
+        {
+            return Ok(());
+        }
+        let mut response = Vec::with_capacity(8 * 1024);
+        self.send_command(b"CLOSE").await?;
+        self.read_response(&mut response, RequiredResponses::empty())
+            .await?;
+        self.stream.as_mut()?.current_mailbox = MailboxSelection::None;
+        Ok(())
+    }
+
+    /// Closes the selected mailbox according to the expunge policy and logs
+    /// out.
+    pub async fn logout(&mut self) -> Result<()> {
+        if self.stream.is_err() {
+            return Ok(());
+        }
+        self.close_for_expunge(None).await?;
+        self.send_command(b"LOGOUT").await?;
+        let mut response = Vec::with_capacity(8 * 1024);
+        /* The server replies with BYE, which ends the stream. */
+        let _ = self
+            .read_response(&mut response, RequiredResponses::LOGOUT_REQUIRED)
+            .await;
+        self.stream = Err(Error::new("Logged out."));
+        Ok(())
+    }
+
     pub async fn unselect(&mut self) -> Result<()> {
         match self.stream.as_mut()?.current_mailbox.take() {
             MailboxSelection::Examine(_) | MailboxSelection::Select(_) => {
@@ -21,7 +21,7 @@
 
 use std::sync::{Arc, Mutex, RwLock};
 
-use super::protocol_parser::SelectResponse;
+use super::{protocol_parser::SelectResponse, UID};
 use crate::{
     backends::{
         BackendMailbox, LazyCountSet, Mailbox, MailboxHash, MailboxPermissions, SpecialUsageMailbox,
@@ -29,6 +29,19 @@ use crate::{
     error::*,
 };
 
+/// Where the next page of a large mailbox's older messages starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OlderEnvelopes {
+    /// The messages with UIDs up to `max_uid`, fetched by descending UID.
+    UpTo { max_uid: UID },
+    /// The messages server-side SORT put after the fetched ones, newest
+    /// first, and the THREAD results of the mailbox if there are any.
+    Sorted {
+        rest: Vec<UID>,
+        threads: Option<Vec<Vec<UID>>>,
+    },
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ImapMailbox {
     pub hash: MailboxHash,
@@ -47,6 +60,10 @@ pub struct ImapMailbox {
     pub exists: Arc<Mutex<LazyCountSet>>,
     pub unseen: Arc<Mutex<LazyCountSet>>,
     pub warm: Arc<Mutex<bool>>,
+    /// How the last fetch loaded the mailbox, if it is large.
+    pub fetch_strategy: Arc<Mutex<Option<String>>>,
+    /// The messages the last fetch left out, if it is large.
+    pub older: Arc<Mutex<Option<OlderEnvelopes>>>,
 }
 
 impl ImapMailbox {
@@ -122,4 +139,16 @@ impl BackendMailbox for ImapMailbox {
     fn count(&self) -> Result<(usize, usize)> {
         Ok((self.unseen.lock()?.len(), self.exists.lock()?.len()))
     }
+
+    fn fetch_strategy(&self) -> Option<String> {
+        self.fetch_strategy.lock().unwrap().clone()
+    }
+
+    fn older_envelopes(&self) -> usize {
+        if self.older.lock().unwrap().is_some() {
+            self.exists.lock().unwrap().not_yet_seen()
+        } else {
+            0
+        }
+    }
 }
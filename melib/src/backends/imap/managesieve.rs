@@ -30,9 +30,10 @@ use nom::{
     sequence::separated_pair,
 };
 
-use super::{ImapConnection, ImapProtocol, ImapServerConf, UIDStore};
+use super::{ImapConnection, ImapProtocol, ImapServerConf, UIDStore, IMAP_PROTOCOL_TIMEOUT};
 use crate::{
     conf::AccountSettings,
+    connections::{proxy::Proxy, tls::TlsOptions},
     email::parser::IResult,
     error::{Error, Result},
     get_conf_val,
@@ -302,8 +303,8 @@ impl ManageSieveConnection {
         let server_username = get_conf_val!(s["server_username"])?;
         let server_password = get_conf_val!(s["server_password"])?;
         let server_port = get_conf_val!(s["server_port"], 4190)?;
-        let danger_accept_invalid_certs: bool =
-            get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        let proxy = Proxy::new(s)?;
+        let tls = TlsOptions::new(s)?;
         let timeout = get_conf_val!(s["timeout"], 16_u64)?;
         let timeout = if timeout == 0 {
             None
@@ -317,9 +318,15 @@ impl ManageSieveConnection {
             server_port,
             use_starttls: true,
             use_tls: true,
-            danger_accept_invalid_certs,
+            proxy,
+            use_srv: false,
+            tls,
             protocol: ImapProtocol::ManageSieve,
             timeout,
+            connect_timeout: timeout,
+            protocol_timeout: IMAP_PROTOCOL_TIMEOUT,
+            connect_retries: 0,
+            retry_backoff: std::time::Duration::from_secs(1),
         };
         let uid_store = Arc::new(UIDStore {
             is_online: Arc::new(Mutex::new((
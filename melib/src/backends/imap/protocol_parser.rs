@@ -19,7 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::{convert::TryFrom, str::FromStr};
+use std::{convert::TryFrom, ops::RangeInclusive, str::FromStr};
 
 use nom::{
     branch::{alt, permutation},
@@ -32,6 +32,7 @@ use nom::{
 
 use super::*;
 use crate::{
+    backends::utf7::decode_utf7_imap,
     email::{
         address::{Address, MailboxAddress},
         parser::{
@@ -61,6 +62,8 @@ bitflags! {
         const SEARCH              = 0b0010_0000_0000_0000;
         const FETCH               = 0b0100_0000_0000_0000;
         const NO_REQUIRED         = 0b1000_0000_0000_0000;
+        const SORT                = 0b0001_0000_0000_0000_0000;
+        const THREAD              = 0b0010_0000_0000_0000_0000;
         const CAPABILITY_REQUIRED = Self::CAPABILITY.bits;
         const LOGOUT_REQUIRED     = Self::BYE.bits;
         const SELECT_REQUIRED     = Self::FLAGS.bits | Self::EXISTS.bits | Self::RECENT.bits | Self::UNSEEN.bits | Self::PERMANENTFLAGS.bits | Self::UIDNEXT.bits | Self::UIDVALIDITY.bits;
@@ -118,7 +121,13 @@ impl RequiredResponses {
             ret |= line.ends_with(b"EXPUNGE\r\n");
         }
         if self.intersects(RequiredResponses::SEARCH) {
-            ret |= line.starts_with(b"SEARCH");
+            ret |= line.starts_with(b"SEARCH") || line.starts_with(b"ESEARCH");
+        }
+        if self.intersects(RequiredResponses::SORT) {
+            ret |= line.starts_with(b"SORT");
+        }
+        if self.intersects(RequiredResponses::THREAD) {
+            ret |= line.starts_with(b"THREAD");
         }
         if self.intersects(RequiredResponses::FETCH) {
             let mut ptr = 0;
@@ -473,7 +482,10 @@ fn test_imap_line_iterator() {
  * LIST (\HasChildren) "." INBOX
  */
 
-pub fn list_mailbox_result(input: &[u8]) -> IResult<&[u8], ImapMailbox> {
+/// Parses a `LIST` or `LSUB` response. Mailbox names are decoded from
+/// modified UTF-7 unless `utf8_accept` is set, in which case they are UTF-8
+/// already and may contain `&` literally.
+pub fn list_mailbox_result(input: &[u8], utf8_accept: bool) -> IResult<&[u8], ImapMailbox> {
     let (input, _) = alt((tag("* LIST ("), tag("* LSUB (")))(input.ltrim())?;
     let (input, properties) = take_until(&b")"[0..])(input)?;
     let (input, _) = tag(b") ")(input)?;
@@ -516,17 +528,25 @@ pub fn list_mailbox_result(input: &[u8]) -> IResult<&[u8], ImapMailbox> {
             }
             f.imap_path = path.to_string();
             f.hash = MailboxHash::from_bytes(f.imap_path.as_bytes());
+            let decode = |name: &str| {
+                if utf8_accept {
+                    name.to_string()
+                } else {
+                    decode_utf7_imap(name)
+                }
+            };
+            let decoded_path = decode(&f.imap_path);
             f.path = if separator == b'/' {
-                f.imap_path.clone()
+                decoded_path.clone()
             } else {
-                f.imap_path.replace(separator as char, "/")
+                decoded_path.replace(separator as char, "/")
             };
             f.name = if let Some(pos) = f.imap_path.as_bytes().iter().rposition(|&c| c == separator)
             {
                 f.parent = Some(MailboxHash::from_bytes(f.imap_path[..pos].as_bytes()));
-                f.imap_path[pos + 1..].to_string()
+                decode(&f.imap_path[pos + 1..])
             } else {
-                f.imap_path.clone()
+                decoded_path
             };
             f.separator = separator;
 
@@ -1109,6 +1129,265 @@ fn test_imap_search() {
     );
 }
 
+/// Parses the response of a `SORT` command (RFC 5256), which lists message
+/// numbers in the requested order.
+pub fn sort_results<'a>(input: &'a [u8]) -> IResult<&'a [u8], Vec<ImapNum>> {
+    alt((
+        |input: &'a [u8]| -> IResult<&'a [u8], Vec<ImapNum>> {
+            let (input, _) = tag("* SORT ")(input)?;
+            let (input, list) = separated_list1(
+                tag(b" "),
+                map_res(is_not(" \r\n"), |s: &[u8]| {
+                    ImapNum::from_str(unsafe { std::str::from_utf8_unchecked(s) })
+                }),
+            )(input)?;
+            let (input, _) = tag("\r\n")(input)?;
+            Ok((input, list))
+        },
+        |input: &'a [u8]| -> IResult<&'a [u8], Vec<ImapNum>> {
+            let (input, _) = tag("* SORT\r\n")(input)?;
+            Ok((input, vec![]))
+        },
+    ))(input)
+}
+
+/// Parses the response of a `THREAD` command (RFC 5256). Each thread is
+/// returned as the flat list of its message numbers, parents before their
+/// children.
+pub fn thread_results(input: &[u8]) -> IResult<&[u8], Vec<Vec<ImapNum>>> {
+    let (input, _) = tag("* THREAD")(input)?;
+    let (input, list) = take_until("\r\n")(input)?;
+    let (input, _) = tag("\r\n")(input)?;
+    let mut threads: Vec<Vec<ImapNum>> = vec![];
+    let mut depth = 0_usize;
+    let mut num: Option<ImapNum> = None;
+    for &b in list {
+        if b.is_ascii_digit() {
+            if depth == 0 {
+                return Err(nom::Err::Error(
+                    (input, "thread_results(): number outside of a thread.").into(),
+                ));
+            }
+            num = Some(
+                num.unwrap_or(0)
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add((b - b'0') as ImapNum))
+                    .ok_or_else(|| {
+                        nom::Err::Error((input, "thread_results(): number out of range.").into())
+                    })?,
+            );
+            continue;
+        }
+        if let Some(n) = num.take() {
+            threads.last_mut().unwrap().push(n);
+        }
+        match b {
+            b'(' => {
+                if depth == 0 {
+                    threads.push(vec![]);
+                }
+                depth += 1;
+            }
+            b')' if depth > 0 => {
+                depth -= 1;
+            }
+            b' ' => {}
+            _ => {
+                return Err(nom::Err::Error(
+                    (input, "thread_results(): invalid thread list.").into(),
+                ));
+            }
+        }
+    }
+    if depth != 0 || num.is_some() {
+        return Err(nom::Err::Error(
+            (input, "thread_results(): unbalanced thread list.").into(),
+        ));
+    }
+    Ok((input, threads))
+}
+
+#[test]
+fn test_imap_sort_thread() {
+    assert_eq!(sort_results(b"* SORT\r\n").map(|(_, v)| v), Ok(vec![]));
+    assert_eq!(
+        sort_results(b"* SORT 5 3 4 1 2\r\n").map(|(_, v)| v),
+        Ok(vec![5, 3, 4, 1, 2])
+    );
+    assert_eq!(thread_results(b"* THREAD\r\n").map(|(_, v)| v), Ok(vec![]));
+    assert_eq!(
+        thread_results(b"* THREAD (2)(3 6 (4 23)(44 7 96))\r\n").map(|(_, v)| v),
+        Ok(vec![vec![2], vec![3, 6, 4, 23, 44, 7, 96]])
+    );
+    assert_eq!(
+        thread_results(b"* THREAD ((3)(5))(166 167)\r\n").map(|(_, v)| v),
+        Ok(vec![vec![3, 5], vec![166, 167]])
+    );
+    thread_results(b"* THREAD (2)(3 6\r\n").unwrap_err();
+    thread_results(b"* THREAD (2)(99999999999999999999999)\r\n").unwrap_err();
+}
+
+/// Parses a sequence set such as `1:3,7` into its ranges, in ascending order
+/// and merged where they overlap or touch. The ranges are not expanded, since
+/// a short set can cover billions of numbers. Open ranges (`*`) are not
+/// allowed.
+pub fn sequence_set(input: &[u8]) -> Result<Vec<RangeInclusive<ImapNum>>> {
+    let mut ret: Vec<RangeInclusive<ImapNum>> = vec![];
+    for item in input.split(|b| *b == b',') {
+        let mut ends = item.splitn(2, |b| *b == b':').map(|n| {
+            ImapNum::from_str(&String::from_utf8_lossy(n)).map_err(|_| {
+                Error::new(format!(
+                    "Invalid sequence set `{}`",
+                    String::from_utf8_lossy(input)
+                ))
+            })
+        });
+        let start = ends.next().unwrap_or_else(|| Ok(0))?;
+        let end = ends.next().transpose()?.unwrap_or(start);
+        ret.push(std::cmp::min(start, end)..=std::cmp::max(start, end));
+    }
+    ret.sort_unstable_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<ImapNum>> = Vec::with_capacity(ret.len());
+    for range in ret {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                if range.end() > last.end() {
+                    *last = *last.start()..=*range.end();
+                }
+            }
+            _ => merged.push(range),
+        }
+    }
+    Ok(merged)
+}
+
+/// Returns how many numbers the ranges of [`sequence_set`] cover.
+pub fn sequence_set_len(ranges: &[RangeInclusive<ImapNum>]) -> usize {
+    ranges
+        .iter()
+        .map(|range| (range.end() - range.start()).saturating_add(1))
+        .fold(0, usize::saturating_add)
+}
+
+/// Return data of an `ESEARCH` response (RFC 4731), including `PARTIAL`
+/// results (RFC 9394).
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct EsearchResponse {
+    pub uid: bool,
+    /// Total number of matching messages, if `COUNT` was requested.
+    pub count: Option<usize>,
+    /// Numbers returned with `ALL` or `PARTIAL`, as ranges in ascending
+    /// order, see [`sequence_set`].
+    pub results: Vec<RangeInclusive<ImapNum>>,
+}
+
+pub fn esearch_results(input: &[u8]) -> IResult<&[u8], EsearchResponse> {
+    let (input, _) = tag("* ESEARCH")(input)?;
+    let (input, line) = take_until("\r\n")(input)?;
+    let (input, _) = tag("\r\n")(input)?;
+    let mut ret = EsearchResponse::default();
+    let mut tokens = line.split(|b| *b == b' ').filter(|t| !t.is_empty());
+    while let Some(token) = tokens.next() {
+        if token.starts_with(b"(") {
+            /* Search correlator, `(TAG "A01")` */
+            let mut token = token;
+            while !token.ends_with(b")") {
+                token = match tokens.next() {
+                    Some(t) => t,
+                    None => {
+                        return Err(nom::Err::Error(
+                            (line, "esearch_results(): unterminated search correlator").into(),
+                        ))
+                    }
+                };
+            }
+            continue;
+        }
+        match token.to_ascii_uppercase().as_slice() {
+            b"UID" => {
+                ret.uid = true;
+            }
+            b"COUNT" => {
+                ret.count = tokens
+                    .next()
+                    .and_then(|n| usize::from_str(&String::from_utf8_lossy(n)).ok());
+            }
+            b"ALL" => {
+                let set = tokens.next().unwrap_or_default();
+                ret.results = sequence_set(set).map_err(|_| {
+                    nom::Err::Error((set, "esearch_results(): invalid ALL sequence set").into())
+                })?;
+            }
+            b"PARTIAL" => {
+                /* `PARTIAL (<range> <sequence set or NIL>)` */
+                let _range = tokens.next();
+                let set = tokens.next().unwrap_or_default();
+                let set = set.strip_suffix(b")").unwrap_or(set);
+                if set != b"NIL" {
+                    ret.results = sequence_set(set).map_err(|_| {
+                        nom::Err::Error(
+                            (set, "esearch_results(): invalid PARTIAL sequence set").into(),
+                        )
+                    })?;
+                }
+            }
+            _ => {
+                /* MIN, MAX and other return data take a single value. */
+                tokens.next();
+            }
+        }
+    }
+    Ok((input, ret))
+}
+
+#[test]
+fn test_imap_esearch() {
+    assert_eq!(
+        sequence_set(b"7,1:3,10").unwrap(),
+        vec![1..=3, 7..=7, 10..=10]
+    );
+    assert_eq!(sequence_set(b"5:3,6,2:4").unwrap(), vec![2..=6]);
+    assert!(sequence_set(b"1:*").is_err());
+    /* Ranges are not expanded. */
+    let huge = sequence_set(b"1:4294967295,2:10").unwrap();
+    assert_eq!(huge, vec![1..=4294967295]);
+    assert_eq!(sequence_set_len(&huge), 4294967295);
+    assert_eq!(sequence_set_len(&[1..=3, 7..=7]), 4);
+    assert_eq!(
+        esearch_results(b"* ESEARCH (TAG \"M5\") UID COUNT 5 ALL 4:5,7,10:11\r\n").map(|(_, v)| v),
+        Ok(EsearchResponse {
+            uid: true,
+            count: Some(5),
+            results: vec![4..=5, 7..=7, 10..=11],
+        })
+    );
+    assert_eq!(
+        esearch_results(b"* ESEARCH (TAG \"M6\") UID PARTIAL (-1:-3 200:201,250) COUNT 12000\r\n")
+            .map(|(_, v)| v),
+        Ok(EsearchResponse {
+            uid: true,
+            count: Some(12000),
+            results: vec![200..=201, 250..=250],
+        })
+    );
+    assert_eq!(
+        esearch_results(b"* ESEARCH (TAG \"M7\") UID PARTIAL (1:100 NIL) COUNT 0\r\n")
+            .map(|(_, v)| v),
+        Ok(EsearchResponse {
+            uid: true,
+            count: Some(0),
+            results: vec![],
+        })
+    );
+    assert_eq!(
+        esearch_results(b"* ESEARCH (TAG \"M8\") UID\r\n").map(|(_, v)| v),
+        Ok(EsearchResponse {
+            uid: true,
+            ..EsearchResponse::default()
+        })
+    );
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct SelectResponse {
     pub exists: ImapNum,
@@ -1491,6 +1770,7 @@ pub fn envelope_address(input: &[u8]) -> IResult<&[u8], Address> {
                     to_str!(host_name)
                 )
                 .into_bytes()
+                .into()
             } else {
                 format!(
                     "{}{}{}",
@@ -1499,6 +1779,7 @@ pub fn envelope_address(input: &[u8]) -> IResult<&[u8], Address> {
                     to_str!(&mailbox_name),
                 )
                 .into_bytes()
+                .into()
             },
             display_name: str_builder!(0, name.len()),
             address_spec: if let Some(host_name) = host_name.as_ref() {
@@ -1699,6 +1980,32 @@ pub fn status_response(input: &[u8]) -> IResult<&[u8], StatusResponse> {
     ))
 }
 
+#[test]
+fn test_imap_list_mailbox_utf7() {
+    let (_, mailbox) = list_mailbox_result(
+        b"* LIST (\\HasNoChildren) \".\" \"INBOX.&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-\"\r\n",
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        mailbox.imap_path(),
+        "INBOX.&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-"
+    );
+    assert_eq!(mailbox.path, "INBOX/Отправленные");
+    assert_eq!(mailbox.name, "Отправленные");
+    assert_eq!(mailbox.parent, Some(MailboxHash::from_bytes(b"INBOX")));
+
+    /* With UTF8=ACCEPT names are UTF-8 and `&` is not a shift character. */
+    let (_, mailbox) = list_mailbox_result(
+        "* LIST (\\HasNoChildren) \".\" \"INBOX.Tom &AOk- Отправленные\"\r\n".as_bytes(),
+        true,
+    )
+    .unwrap();
+    assert_eq!(mailbox.imap_path(), "INBOX.Tom &AOk- Отправленные");
+    assert_eq!(mailbox.path, "INBOX/Tom &AOk- Отправленные");
+    assert_eq!(mailbox.name, "Tom &AOk- Отправленные");
+}
+
 // mailbox = "INBOX" / astring
 //           ; INBOX is case-insensitive. All case variants of
 //           ; INBOX (e.g., "iNbOx") MUST be interpreted as INBOX
@@ -28,7 +28,7 @@ use crate::{
             generate_envelope_hash, FetchResponse, ImapLineSplit, RequiredResponses,
             UntaggedResponse,
         },
-        BackendMailbox, RefreshEvent,
+        BackendMailbox, MailboxHash, RefreshEvent,
         RefreshEventKind::{self, *},
         TagHash,
     },
@@ -318,7 +318,7 @@ impl ImapConnection {
                         debug!("UID SEARCH RECENT returned no results");
                     }
                     Ok(v) => {
-                        let command = {
+                        let uids = {
                             let mut iter = v.split(u8::is_ascii_whitespace);
                             let first = iter.next().unwrap_or(v);
                             let mut accum = to_str!(first).trim().to_string();
@@ -326,119 +326,12 @@ impl ImapConnection {
                                 accum.push(',');
                                 accum.push_str(to_str!(ms).trim());
                             }
-                            format!(
-                                "UID FETCH {} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS \
-                                 (REFERENCES)] BODYSTRUCTURE)",
-                                accum
-                            )
+                            accum
                         };
                         try_fail!(
                             mailbox_hash,
-                            self.send_command(command.as_bytes()).await
-                            self.read_response(&mut response, RequiredResponses::FETCH_REQUIRED).await
+                            self.fetch_new_envelopes(mailbox_hash, &uids).await
                         );
-                        let mut v = match super::protocol_parser::fetch_responses(&response) {
-                            Ok((_, v, _)) => v,
-                            Err(err) => {
-                                debug!(
-                                    "Error when parsing FETCH response after untagged recent {:?}",
-                                    err
-                                );
-                                return Ok(true);
-                            }
-                        };
-                        debug!("responses len is {}", v.len());
-                        for FetchResponse {
-                            ref uid,
-                            ref mut envelope,
-                            ref mut flags,
-                            ref references,
-                            ..
-                        } in &mut v
-                        {
-                            if uid.is_none() || flags.is_none() || envelope.is_none() {
-                                continue;
-                            }
-                            let uid = uid.unwrap();
-                            let env = envelope.as_mut().unwrap();
-                            env.set_hash(generate_envelope_hash(mailbox.imap_path(), &uid));
-                            if let Some(value) = references {
-                                env.set_references(value);
-                            }
-                            let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
-                            if let Some((flags, keywords)) = flags {
-                                env.set_flags(*flags);
-                                if !env.is_seen() {
-                                    mailbox.unseen.lock().unwrap().insert_new(env.hash());
-                                }
-                                for f in keywords {
-                                    let hash = TagHash::from_bytes(f.as_bytes());
-                                    if !tag_lck.contains_key(&hash) {
-                                        tag_lck.insert(hash, f.to_string());
-                                    }
-                                    env.tags_mut().push(hash);
-                                }
-                            }
-                            mailbox.exists.lock().unwrap().insert_new(env.hash());
-                        }
-                        if self.uid_store.keep_offline_cache {
-                            if let Err(err) = cache_handle
-                                .insert_envelopes(mailbox_hash, &v)
-                                .chain_err_summary(|| {
-                                    format!(
-                                        "Could not save envelopes in cache for mailbox {}",
-                                        &mailbox.imap_path()
-                                    )
-                                })
-                            {
-                                log::info!("{err}");
-                            }
-                        }
-                        for response in v {
-                            if let FetchResponse {
-                                envelope: Some(envelope),
-                                uid: Some(uid),
-                                ..
-                            } = response
-                            {
-                                if !self
-                                    .uid_store
-                                    .uid_index
-                                    .lock()
-                                    .unwrap()
-                                    .contains_key(&(mailbox_hash, uid))
-                                {
-                                    self.uid_store
-                                        .msn_index
-                                        .lock()
-                                        .unwrap()
-                                        .entry(mailbox_hash)
-                                        .or_default()
-                                        .push(uid);
-                                }
-                                self.uid_store
-                                    .hash_index
-                                    .lock()
-                                    .unwrap()
-                                    .insert(envelope.hash(), (uid, mailbox_hash));
-                                self.uid_store
-                                    .uid_index
-                                    .lock()
-                                    .unwrap()
-                                    .insert((mailbox_hash, uid), envelope.hash());
-                                debug!(
-                                    "Create event {} {} {}",
-                                    envelope.hash(),
-                                    envelope.subject(),
-                                    mailbox.path(),
-                                );
-                                self.add_refresh_event(RefreshEvent {
-                                    account_hash: self.uid_store.account_hash,
-                                    mailbox_hash,
-                                    kind: Create(Box::new(envelope)),
-                                });
-                            }
-                        }
                     }
                     Err(e) => {
                         debug!(
@@ -535,4 +428,132 @@ impl ImapConnection {
         }
         Ok(true)
     }
+
+    /// Fetches the envelopes of `uids`, a comma separated list of UIDs in the
+    /// selected mailbox `mailbox_hash`, adds them to the UID indexes and emits
+    /// `Create` events for them.
+    pub async fn fetch_new_envelopes(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        uids: &str,
+    ) -> Result<()> {
+        let mailbox =
+            std::clone::Clone::clone(&self.uid_store.mailboxes.lock().await[&mailbox_hash]);
+        #[cfg(not(feature = "sqlite3"))]
+        let mut cache_handle = super::cache::DefaultCache::get(self.uid_store.clone())?;
+        #[cfg(feature = "sqlite3")]
+        let mut cache_handle = super::cache::Sqlite3Cache::get(self.uid_store.clone())?;
+        let mut response = Vec::with_capacity(8 * 1024);
+        let command = format!(
+            "UID FETCH {} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] \
+             BODYSTRUCTURE)",
+            uids
+        );
+        self.send_command(command.as_bytes()).await?;
+        self.read_response(&mut response, RequiredResponses::FETCH_REQUIRED)
+            .await?;
+        let mut v = match super::protocol_parser::fetch_responses(&response) {
+            Ok((_, v, _)) => v,
+            Err(err) => {
+                debug!(
+                    "Error when parsing FETCH response of new envelopes {:?}",
+                    err
+                );
+                return Ok(());
+            }
+        };
+        debug!("responses len is {}", v.len());
+        for FetchResponse {
+            ref uid,
+            ref mut envelope,
+            ref mut flags,
+            ref references,
+            ..
+        } in &mut v
+        {
+            if uid.is_none() || flags.is_none() || envelope.is_none() {
+                continue;
+            }
+            let uid = uid.unwrap();
+            let env = envelope.as_mut().unwrap();
+            env.set_hash(generate_envelope_hash(mailbox.imap_path(), &uid));
+            if let Some(value) = references {
+                env.set_references(value);
+            }
+            let mut tag_lck = self.uid_store.collection.tag_index.write().unwrap();
+            if let Some((flags, keywords)) = flags {
+                env.set_flags(*flags);
+                if !env.is_seen() {
+                    mailbox.unseen.lock().unwrap().insert_new(env.hash());
+                }
+                for f in keywords {
+                    let hash = TagHash::from_bytes(f.as_bytes());
+                    if !tag_lck.contains_key(&hash) {
+                        tag_lck.insert(hash, f.to_string());
+                    }
+                    env.tags_mut().push(hash);
+                }
+            }
+            mailbox.exists.lock().unwrap().insert_new(env.hash());
+        }
+        if self.uid_store.keep_offline_cache {
+            if let Err(err) = cache_handle
+                .insert_envelopes(mailbox_hash, &v)
+                .chain_err_summary(|| {
+                    format!(
+                        "Could not save envelopes in cache for mailbox {}",
+                        &mailbox.imap_path()
+                    )
+                })
+            {
+                log::info!("{err}");
+            }
+        }
+        for response in v {
+            if let FetchResponse {
+                envelope: Some(envelope),
+                uid: Some(uid),
+                ..
+            } = response
+            {
+                if !self
+                    .uid_store
+                    .uid_index
+                    .lock()
+                    .unwrap()
+                    .contains_key(&(mailbox_hash, uid))
+                {
+                    self.uid_store
+                        .msn_index
+                        .lock()
+                        .unwrap()
+                        .entry(mailbox_hash)
+                        .or_default()
+                        .push(uid);
+                }
+                self.uid_store
+                    .hash_index
+                    .lock()
+                    .unwrap()
+                    .insert(envelope.hash(), (uid, mailbox_hash));
+                self.uid_store
+                    .uid_index
+                    .lock()
+                    .unwrap()
+                    .insert((mailbox_hash, uid), envelope.hash());
+                debug!(
+                    "Create event {} {} {}",
+                    envelope.hash(),
+                    envelope.subject(),
+                    mailbox.path(),
+                );
+                self.add_refresh_event(RefreshEvent {
+                    account_hash: self.uid_store.account_hash,
+                    mailbox_hash,
+                    kind: Create(Box::new(envelope)),
+                });
+            }
+        }
+        Ok(())
+    }
 }
@@ -18,7 +18,7 @@
  * You should have received a copy of the GNU General Public License
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
 use super::*;
 use crate::backends::SpecialUsageMailbox;
@@ -51,6 +51,63 @@ pub async fn poll_with_examine(kit: ImapWatchKit) -> Result<()> {
     }
 }
 
+/// Expunges mailboxes with pending deletions every `expunge_interval`, for
+/// the `periodic` expunge policy. Failures are logged and retried on the next
+/// interval, so that they do not end the watch it runs alongside.
+pub async fn periodic_expunge(
+    main_conn: Arc<FutureMutex<ImapConnection>>,
+    uid_store: Arc<UIDStore>,
+) {
+    let interval = uid_store.expunge_interval;
+    expunge_every(interval, || {
+        expunge_pending(main_conn.clone(), uid_store.clone())
+    })
+    .await
+}
+
+async fn expunge_pending(
+    main_conn: Arc<FutureMutex<ImapConnection>>,
+    uid_store: Arc<UIDStore>,
+) -> Result<()> {
+    let pending = uid_store
+        .pending_expunge
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .collect::<Vec<MailboxHash>>();
+    for mailbox_hash in pending {
+        let mut conn = timeout(uid_store.timeout, main_conn.lock()).await?;
+        conn.expunge(mailbox_hash, None).await?;
+    }
+    Ok(())
+}
+
+async fn expunge_every<F, Fut>(interval: std::time::Duration, mut expunge: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    loop {
+        smol::Timer::after(interval).await;
+        if let Err(err) = expunge().await {
+            log::warn!("Could not expunge IMAP mailboxes periodically: {}", err);
+        }
+    }
+}
+
+/// Runs `watch` alongside the never ending `expunge` until `watch` returns.
+pub async fn watch_with_expunge<W, E>(watch: W, expunge: E) -> Result<()>
+where
+    W: Future<Output = Result<()>> + Unpin,
+    E: Future<Output = ()> + Unpin,
+{
+    match futures::future::select(watch, expunge).await {
+        futures::future::Either::Left((result, _)) => result,
+        futures::future::Either::Right(((), watch)) => watch.await,
+    }
+}
+
 pub async fn idle(kit: ImapWatchKit) -> Result<()> {
     debug!("IDLE");
     /* IDLE only watches the connection's selected mailbox. We will IDLE on INBOX
@@ -452,3 +509,44 @@ pub async fn examine_updates(
     }
     Ok(())
 }
+
+#[test]
+fn test_imap_periodic_expunge_failures() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let expunge = {
+        let attempts = attempts.clone();
+        Box::pin(expunge_every(
+            std::time::Duration::from_millis(1),
+            move || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err(Error::new("connection lost")) }
+            },
+        ))
+    };
+    /* Failing expunges neither end nor fail the watch. */
+    let watch = {
+        let attempts = attempts.clone();
+        Box::pin(async move {
+            while attempts.load(Ordering::SeqCst) < 3 {
+                smol::Timer::after(std::time::Duration::from_millis(1)).await;
+            }
+            Ok(())
+        })
+    };
+    assert!(smol::block_on(watch_with_expunge(watch, expunge)).is_ok());
+    assert!(attempts.load(Ordering::SeqCst) >= 3);
+
+    /* The watch keeps running if the expunge future ends. */
+    let watch = Box::pin(async {
+        smol::Timer::after(std::time::Duration::from_millis(5)).await;
+        Err(Error::new("watch failed"))
+    });
+    assert_eq!(
+        smol::block_on(watch_with_expunge(watch, Box::pin(async {})))
+            .unwrap_err()
+            .to_string(),
+        "watch failed"
+    );
+}
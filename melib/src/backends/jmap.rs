@@ -34,9 +34,9 @@ use serde_json::Value;
 use crate::{
     backends::*,
     conf::AccountSettings,
-    connections::timeout,
+    connections::{proxy::Proxy, timeout, tls::TlsOptions},
     email::*,
-    error::{Error, Result},
+    error::{Error, ErrorKind, Result},
     Collection,
 };
 
@@ -81,6 +81,9 @@ use objects::*;
 pub mod mailbox;
 use mailbox::*;
 
+pub mod eventsource;
+use eventsource::*;
+
 #[derive(Debug, Default)]
 pub struct EnvelopeCache {
     bytes: Option<String>,
@@ -95,8 +98,13 @@ pub struct JmapServerConf {
     pub server_username: String,
     pub server_password: String,
     pub use_token: bool,
-    pub danger_accept_invalid_certs: bool,
+    /// Certificate validation and client certificate of HTTPS connections.
+    pub tls: TlsOptions,
     pub timeout: Option<Duration>,
+    /// Connect through this proxy.
+    pub proxy: Option<Proxy>,
+    /// Mailboxes are fetched in pages of this many messages, newest first.
+    pub large_mailbox_threshold: usize,
 }
 
 macro_rules! get_conf_val {
@@ -128,6 +136,29 @@ macro_rules! get_conf_val {
 }
 
 impl JmapServerConf {
+    /// Reads the TLS settings of the account, refusing those that the HTTP
+    /// client cannot apply.
+    fn tls_options(s: &AccountSettings) -> Result<TlsOptions> {
+        let tls = TlsOptions::new(s)?;
+        for (key, is_set) in [
+            ("tls_min_version", tls.min_version.is_some()),
+            (
+                "tls_pinned_certificates",
+                !tls.pinned_certificates.is_empty(),
+            ),
+            ("tls_pinned_public_keys", !tls.pinned_public_keys.is_empty()),
+        ] {
+            if is_set {
+                return Err(Error::new(format!(
+                    "Configuration error ({}): `{}` is not supported by JMAP accounts.",
+                    s.name, key
+                ))
+                .set_kind(ErrorKind::Configuration));
+            }
+        }
+        Ok(tls)
+    }
+
     pub fn new(s: &AccountSettings) -> Result<Self> {
         let use_token: bool = get_conf_val!(s["use_token"], false)?;
 
@@ -147,7 +178,7 @@ impl JmapServerConf {
             server_username: get_conf_val!(s["server_username"])?.to_string(),
             server_password: s.server_password()?,
             use_token,
-            danger_accept_invalid_certs: get_conf_val!(s["danger_accept_invalid_certs"], false)?,
+            tls: Self::tls_options(s)?,
             timeout: get_conf_val!(s["timeout"], 16_u64).map(|t| {
                 if t == 0 {
                     None
@@ -155,6 +186,8 @@ impl JmapServerConf {
                     Some(Duration::from_secs(t))
                 }
             })?,
+            proxy: Proxy::new(s)?,
+            large_mailbox_threshold: get_conf_val!(s["large_mailbox_threshold"], 10000_usize)?,
         })
     }
 }
@@ -289,6 +322,21 @@ impl MailBackend for JmapType {
         }))
     }
 
+    fn reconnect(&self) -> ResultFuture<()> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        let timeout_dur = self.server_conf.timeout;
+        Ok(Box::pin(async move {
+            let mut conn = timeout(timeout_dur, connection.lock()).await?;
+            *store.online_status.lock().await = (
+                Instant::now(),
+                Err(Error::new("Reconnecting after resume.")
+                    .set_kind(ErrorKind::Network(crate::error::NetworkErrorKind::None))),
+            );
+            conn.connect().await
+        }))
+    }
+
     fn fetch(
         &mut self,
         mailbox_hash: MailboxHash,
@@ -302,6 +350,40 @@ impl MailBackend for JmapType {
                 &conn,
                 &store,
                 mailbox_hash,
+                0,
+            ).await?;
+            if res.is_empty() {
+                return;
+            }
+            yield res;
+        }))
+    }
+
+    fn fetch_older(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let store = self.store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async_stream::try_stream! {
+            let position = store
+                .mailboxes
+                .read()
+                .unwrap()
+                .get(&mailbox_hash)
+                .and_then(|mbox| *mbox.older_emails.lock().unwrap())
+                .map(|(position, _)| position);
+            let position = match position {
+                Some(position) => position,
+                None => return,
+            };
+            let mut conn = connection.lock().await;
+            conn.connect().await?;
+            let res = protocol::fetch(
+                &conn,
+                &store,
+                mailbox_hash,
+                position,
             ).await?;
             if res.is_empty() {
                 return;
@@ -324,27 +406,81 @@ impl MailBackend for JmapType {
         let connection = self.connection.clone();
         let store = self.store.clone();
         Ok(Box::pin(async move {
-            {
+            let (client, url) = {
                 let mut conn = connection.lock().await;
                 conn.connect().await?;
-            }
+                let url = EventSource::url(&conn.session_guard().event_source_url);
+                (conn.client.clone(), url)
+            };
+            /* Delay before reconnecting to a push stream that closed without delivering
+             * any events, doubled on each consecutive failure. */
+            const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+            const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(300);
+            let mut backoff = MIN_RECONNECT_BACKOFF;
             loop {
-                {
-                    let mailbox_hashes = {
-                        store
-                            .mailboxes
-                            .read()
-                            .unwrap()
-                            .keys()
-                            .cloned()
-                            .collect::<SmallVec<[MailboxHash; 16]>>()
-                    };
-                    let conn = connection.lock().await;
-                    for mailbox_hash in mailbox_hashes {
-                        conn.email_changes(mailbox_hash).await?;
+                /* Listen for pushed state changes, and fall back to polling if the server
+                 * doesn't support push. */
+                let events = match url {
+                    Some(ref url) => match EventSource::connect(&client, url).await {
+                        Ok(events) => Some(events),
+                        Err(err) => {
+                            log::warn!(
+                                "{}: could not connect to JMAP EventSource, polling instead: {}",
+                                store.account_name,
+                                err
+                            );
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                /* Catch up with changes made while not listening. */
+                Self::email_changes(&connection, &store).await?;
+                let mut events = match events {
+                    Some(events) => events,
+                    None => {
+                        crate::connections::sleep(Duration::from_secs(60)).await;
+                        continue;
                     }
+                };
+                let account_id = connection.lock().await.mail_account_id();
+                let mut received = false;
+                loop {
+                    match events.next().await {
+                        Ok(Some(event)) => {
+                            received = true;
+                            if event.event != "state" {
+                                continue;
+                            }
+                            let changed = serde_json::from_str::<StateChange>(&event.data)
+                                .map(|change| change.mail_changed(&account_id))
+                                .unwrap_or(true);
+                            if changed {
+                                Self::email_changes(&connection, &store).await?;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(err) => {
+                            log::debug!(
+                                "{}: JMAP EventSource connection closed: {}",
+                                store.account_name,
+                                err
+                            );
+                            break;
+                        }
+                    }
+                }
+                if received {
+                    backoff = MIN_RECONNECT_BACKOFF;
+                } else {
+                    log::debug!(
+                        "{}: JMAP EventSource closed without events, reconnecting in {}s",
+                        store.account_name,
+                        backoff.as_secs()
+                    );
+                    crate::connections::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, MAX_RECONNECT_BACKOFF);
                 }
-                crate::connections::sleep(Duration::from_secs(60)).await;
             }
         }))
     }
@@ -902,6 +1038,22 @@ impl MailBackend for JmapType {
 }
 
 impl JmapType {
+    /// Fetches the changes of every mailbox since their last known state.
+    async fn email_changes(connection: &FutureMutex<JmapConnection>, store: &Store) -> Result<()> {
+        let mailbox_hashes = store
+            .mailboxes
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<SmallVec<[MailboxHash; 16]>>();
+        let conn = connection.lock().await;
+        for mailbox_hash in mailbox_hashes {
+            conn.email_changes(mailbox_hash).await?;
+        }
+        Ok(())
+    }
+
     pub fn new(
         s: &AccountSettings,
         is_subscribed: Box<dyn Fn(&str) -> bool + Send + Sync>,
@@ -977,7 +1129,13 @@ impl JmapType {
         // either of these two needed
         get_conf_val!(s["server_password"]).or(get_conf_val!(s["server_password_command"]))?;
 
-        get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        JmapServerConf::tls_options(s)?;
+        for key in TlsOptions::KEYS {
+            s.extra.remove(*key);
+        }
+        get_conf_val!(s["large_mailbox_threshold"], 10000_usize)?;
+        Proxy::new(s)?;
+        s.extra.remove("proxy");
         Ok(())
     }
 }
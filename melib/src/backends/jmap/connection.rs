@@ -57,6 +57,41 @@ impl JmapConnection {
                     &server_conf.server_password,
                 ))
         };
+        let client = if server_conf.tls.danger_accept_invalid_certs {
+            client.ssl_options(isahc::config::SslOption::DANGER_ACCEPT_INVALID_CERTS)
+        } else {
+            client
+        };
+        let client = if let Some(cert) = server_conf.tls.client_certificate.as_ref() {
+            use isahc::config::{ClientCertificate, PrivateKey};
+
+            client.ssl_client_certificate(if let Some(key_path) = cert.key_path.as_ref() {
+                ClientCertificate::pem_file(&cert.path, PrivateKey::pem_file(key_path, None))
+            } else {
+                ClientCertificate::pkcs12_file(&cert.path, cert.password.clone())
+            })
+        } else {
+            client
+        };
+        let client = if let Some(proxy) = server_conf.proxy.as_ref() {
+            let uri = proxy
+                .to_string()
+                .parse::<isahc::http::Uri>()
+                .map_err(|err| Error::new(format!("Invalid proxy {}: {}", proxy, err)))?;
+            let client = client.proxy(Some(uri));
+            if let Some(username) = proxy.username.as_ref() {
+                client
+                    .proxy_authentication(isahc::auth::Authentication::basic())
+                    .proxy_credentials(isahc::auth::Credentials::new(
+                        username.as_str(),
+                        proxy.password.as_deref().unwrap_or_default(),
+                    ))
+            } else {
+                client
+            }
+        } else {
+            client
+        };
         let client = client.build()?;
         let server_conf = server_conf.clone();
         Ok(JmapConnection {
@@ -0,0 +1,221 @@
+/*
+ * meli - jmap module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Push notifications over the JMAP EventSource endpoint ([RFC8620 section 7.3](https://www.rfc-editor.org/rfc/rfc8620#section-7.3)).
+//!
+//! The server keeps a `text/event-stream` response open and sends a `state`
+//! event with a [`StateChange`] object whenever data of an account changes.
+
+use futures::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    StreamExt,
+};
+use isahc::{config::Configurable, AsyncBody, Request};
+
+use super::*;
+
+/// Seconds between the server's keep-alive `ping` events.
+const PING_INTERVAL: u64 = 30;
+
+/// The connection is reopened after this long, in case it broke silently.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// An event of a `text/event-stream` response.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+}
+
+/// Accumulates the lines of a `text/event-stream` into events.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    event: String,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    /// Feeds a line without its line terminator, returning an event when the
+    /// line completes one.
+    pub fn feed(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            if self.data.is_empty() {
+                self.event.clear();
+                return None;
+            }
+            let event = std::mem::take(&mut self.event);
+            return Some(SseEvent {
+                event: if event.is_empty() {
+                    "message".to_string()
+                } else {
+                    event
+                },
+                data: std::mem::take(&mut self.data).join("\n"),
+                id: self.id.clone(),
+            });
+        }
+        if line.starts_with(':') {
+            /* Comment */
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => self.data.push(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// The data of a `state` event: the new states of the changed object types
+/// of each account.
+#[derive(Deserialize, Debug)]
+pub struct StateChange {
+    #[serde(rename = "@type")]
+    pub type_: String,
+    pub changed: HashMap<Id<Account>, HashMap<String, String>>,
+}
+
+impl StateChange {
+    /// Whether mail of `account_id` changed.
+    pub fn mail_changed(&self, account_id: &Id<Account>) -> bool {
+        self.changed
+            .get(account_id)
+            .map(|types| {
+                types
+                    .keys()
+                    .any(|t| matches!(t.as_str(), "Email" | "Mailbox" | "Thread"))
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// An open EventSource connection.
+pub struct EventSource {
+    lines: Lines<BufReader<AsyncBody>>,
+    parser: SseParser,
+}
+
+impl std::fmt::Debug for EventSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct(stringify!(EventSource))
+            .field("parser", &self.parser)
+            .finish()
+    }
+}
+
+impl EventSource {
+    /// Expands the `eventSourceUrl` URI template of the session, or returns
+    /// `None` if the server doesn't support push.
+    pub fn url(template: &str) -> Option<String> {
+        if template.is_empty() {
+            return None;
+        }
+        Some(
+            template
+                .replace("{types}", "Email,Mailbox,Thread")
+                .replace("{closeafter}", "no")
+                .replace("{ping}", &PING_INTERVAL.to_string()),
+        )
+    }
+
+    pub async fn connect(client: &HttpClient, url: &str) -> Result<Self> {
+        let req = Request::get(url)
+            .header("Accept", "text/event-stream")
+            .timeout(RECONNECT_INTERVAL)
+            .body(())
+            .map_err(|err| Error::new(format!("Invalid EventSource URL {}: {}", url, err)))?;
+        let res = client.send_async(req).await?;
+        if !res.status().is_success() {
+            let kind: crate::error::NetworkErrorKind = res.status().into();
+            return Err(Error::new(format!(
+                "Could not connect to JMAP EventSource endpoint {}: {}",
+                url,
+                res.status()
+            ))
+            .set_kind(kind.into()));
+        }
+        Ok(EventSource {
+            lines: BufReader::new(res.into_body()).lines(),
+            parser: SseParser::default(),
+        })
+    }
+
+    /// Returns the next event, or `None` if the server closed the connection.
+    pub async fn next(&mut self) -> Result<Option<SseEvent>> {
+        while let Some(line) = self.lines.next().await {
+            let line = line?;
+            if let Some(event) = self.parser.feed(line.trim_end_matches('\r')) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jmap_eventsource() {
+        let mut parser = SseParser::default();
+        let mut events = vec![];
+        for line in [
+            ": keep-alive",
+            "event: ping",
+            "data: {\"interval\":30}",
+            "",
+            "event: state",
+            "id: 42",
+            "data: {\"@type\":\"StateChange\",",
+            "data:\"changed\":{\"u1\":{\"Email\":\"s2\"}}}",
+            "",
+            "",
+        ]
+        .iter()
+        {
+            events.extend(parser.feed(line));
+        }
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "ping");
+        assert_eq!(events[1].event, "state");
+        assert_eq!(events[1].id.as_deref(), Some("42"));
+        let change: StateChange = serde_json::from_str(&events[1].data).unwrap();
+        assert_eq!(change.type_, "StateChange");
+        assert!(change.mail_changed(&Id::from("u1".to_string())));
+        assert!(!change.mail_changed(&Id::from("u2".to_string())));
+
+        assert_eq!(EventSource::url(""), None);
+        assert_eq!(
+            EventSource::url(
+                "https://jmap.example.com/events?types={types}&closeafter={closeafter}&ping={ping}"
+            )
+            .unwrap(),
+            "https://jmap.example.com/events?types=Email,Mailbox,Thread&closeafter=no&ping=30"
+        );
+    }
+}
@@ -44,6 +44,9 @@ pub struct JmapMailbox {
     pub usage: Arc<RwLock<SpecialUsageMailbox>>,
     pub email_state: Arc<Mutex<Option<State<EmailObject>>>>,
     pub email_query_state: Arc<Mutex<Option<String>>>,
+    /// `(position, total)` of the mailbox's query, if the last fetch stopped
+    /// before its end.
+    pub older_emails: Arc<Mutex<Option<(u64, u64)>>>,
 }
 
 impl BackendMailbox for JmapMailbox {
@@ -116,4 +119,12 @@ impl BackendMailbox for JmapMailbox {
             self.total_emails.lock()?.len(),
         ))
     }
+
+    fn older_envelopes(&self) -> usize {
+        self.older_emails
+            .lock()
+            .unwrap()
+            .map(|(position, total)| total.saturating_sub(position) as usize)
+            .unwrap_or(0)
+    }
 }
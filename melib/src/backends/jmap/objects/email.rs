@@ -611,7 +611,7 @@ impl From<crate::search::Query> for Filter<EmailFilterCondition, EmailObject> {
                 AllText(t) => {
                     *f = Filter::Condition(EmailFilterCondition::new().text(t.clone()).into());
                 }
-                Body(t) => {
+                Body(t) | Attachment(t) => {
                     *f = Filter::Condition(EmailFilterCondition::new().body(t.clone()).into());
                 }
                 Before(t) => {
@@ -662,43 +662,30 @@ impl From<crate::search::Query> for Filter<EmailFilterCondition, EmailObject> {
                 }
                 Flags(v) => {
                     fn flag_to_filter(f: &str) -> Filter<EmailFilterCondition, EmailObject> {
-                        match f {
-                            "draft" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .has_keyword("$draft".to_string())
-                                    .into(),
-                            ),
-                            "flagged" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .has_keyword("$flagged".to_string())
-                                    .into(),
-                            ),
-                            "seen" | "read" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .has_keyword("$seen".to_string())
-                                    .into(),
-                            ),
-                            "unseen" | "unread" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .not_keyword("$seen".to_string())
-                                    .into(),
-                            ),
-                            "answered" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .has_keyword("$answered".to_string())
-                                    .into(),
-                            ),
-                            "unanswered" => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .not_keyword("$answered".to_string())
-                                    .into(),
-                            ),
-                            keyword => Filter::Condition(
-                                EmailFilterCondition::new()
-                                    .not_keyword(keyword.to_string())
-                                    .into(),
-                            ),
+                        use crate::search::FlagQuery;
+                        let (flag, set) = match FlagQuery::new(f) {
+                            FlagQuery::Set(flag) => (flag, true),
+                            FlagQuery::Unset(flag) => (flag, false),
+                            FlagQuery::Tag(_) => (Flag::empty(), true),
+                        };
+                        let keyword = match flag {
+                            Flag::SEEN => "$seen",
+                            Flag::FLAGGED => "$flagged",
+                            Flag::REPLIED => "$answered",
+                            Flag::DRAFT => "$draft",
+                            Flag::PASSED => "$forwarded",
+                            Flag::TRASHED => "$junk",
+                            _ => f,
                         }
+                        .to_string();
+                        Filter::Condition(
+                            if set {
+                                EmailFilterCondition::new().has_keyword(keyword)
+                            } else {
+                                EmailFilterCondition::new().not_keyword(keyword)
+                            }
+                            .into(),
+                        )
                     }
                     let mut accum = if let Some(first) = v.first() {
                         flag_to_filter(first.as_str())
@@ -172,6 +172,7 @@ pub async fn get_mailboxes(conn: &JmapConnection) -> Result<HashMap<MailboxHash,
                     unread_threads,
                     email_state: Arc::new(Mutex::new(None)),
                     email_query_state: Arc::new(Mutex::new(None)),
+                    older_emails: Arc::new(Mutex::new(None)),
                 },
             )
         })
@@ -256,12 +257,17 @@ pub async fn get_message(conn: &JmapConnection, ids: &[String]) -> Result<Vec<En
 }
 */
 
+/// Fetches the messages of a mailbox from `position` onwards, newest first.
+/// At most `large_mailbox_threshold` messages are fetched, if it is set; where
+/// the next page starts is kept in the mailbox's `older_emails`.
 pub async fn fetch(
     conn: &JmapConnection,
     store: &Store,
     mailbox_hash: MailboxHash,
+    position: u64,
 ) -> Result<Vec<Envelope>> {
     let mailbox_id = store.mailboxes.read().unwrap()[&mailbox_hash].id.clone();
+    let limit = conn.server_conf.large_mailbox_threshold;
     let email_query_call: EmailQuery = EmailQuery::new(
         Query::new()
             .account_id(conn.mail_account_id().clone())
@@ -270,7 +276,12 @@ pub async fn fetch(
                     .in_mailbox(Some(mailbox_id))
                     .into(),
             )))
-            .position(0),
+            .sort(Some(vec![Comparator::new()
+                .property("receivedAt".to_string())
+                .is_ascending(false)]))
+            .position(position)
+            .limit(if limit == 0 { None } else { Some(limit as u64) })
+            .calculate_total(true),
     )
     .collapse_threads(false);
 
@@ -319,6 +330,13 @@ pub async fn fetch(
         .entry(mailbox_hash)
         .and_modify(|mbox| {
             *mbox.email_query_state.lock().unwrap() = Some(query_response.query_state);
+            let next = query_response.position + query_response.ids.len() as u64;
+            *mbox.older_emails.lock().unwrap() =
+                if !query_response.ids.is_empty() && next < query_response.total {
+                    Some((next, query_response.total))
+                } else {
+                    None
+                };
         });
     let GetResponse::<EmailObject> { list, state, .. } = e;
     {
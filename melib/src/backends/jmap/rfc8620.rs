@@ -456,7 +456,7 @@ where
 {
     pub account_id: Id<Account>,
     pub filter: Option<F>,
-    pub sort: Option<Comparator<OBJ>>,
+    pub sort: Option<Vec<Comparator<OBJ>>>,
     #[serde(default)]
     pub position: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -492,7 +492,7 @@ where
 
     _impl!(account_id: Id<Account>);
     _impl!(filter: Option<F>);
-    _impl!(sort: Option<Comparator<OBJ>>);
+    _impl!(sort: Option<Vec<Comparator<OBJ>>>);
     _impl!(position: u64);
     _impl!(anchor: Option<String>);
     _impl!(anchor_offset: u64);
@@ -1076,7 +1076,7 @@ where
 {
     pub account_id: Id<Account>,
     pub filter: Option<F>,
-    pub sort: Option<Comparator<OBJ>>,
+    pub sort: Option<Vec<Comparator<OBJ>>>,
     ///sinceQueryState: "String"
     ///
     ///The current state of the query in the client.  This is the string
@@ -1131,7 +1131,7 @@ where
         }
     }
     _impl!(filter: Option<F>);
-    _impl!(sort: Option<Comparator<OBJ>>);
+    _impl!(sort: Option<Vec<Comparator<OBJ>>>);
     _impl!(max_changes: Option<usize>);
     _impl!(up_to_id: Option<Id<OBJ>>);
     _impl!(calculate_total: bool);
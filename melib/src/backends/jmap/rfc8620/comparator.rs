@@ -28,8 +28,10 @@ pub struct Comparator<OBJ: Object> {
     #[serde(default = "bool_true")]
     is_ascending: bool,
     //FIXME
+    #[serde(skip_serializing_if = "Option::is_none")]
     collation: Option<String>,
     //#[serde(flatten)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     additional_properties: Vec<String>,
 
     _ph: PhantomData<fn() -> OBJ>,
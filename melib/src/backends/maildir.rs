@@ -23,6 +23,10 @@
 mod backend;
 pub use self::backend::*;
 
+#[cfg(feature = "sqlite3")]
+mod cache;
+mod message_file;
+use message_file::read_envelope_bytes;
 mod stream;
 use std::{
     collections::hash_map::DefaultHasher,
@@ -296,3 +300,42 @@ impl MaildirPathTrait for Path {
         flag
     }
 }
+
+/// Header of envelope cache files. Bump the version whenever the serialized
+/// layout of [`Envelope`] changes, so that cache files written by other
+/// versions are discarded instead of decoded into the wrong fields.
+const ENVELOPE_CACHE_HEADER: &[u8; 8] = b"MELIENV\x02";
+
+/// Reads the cached envelope at `path`. Returns `None` if the file is
+/// unreadable, was written by a different cache version or fails to decode.
+fn read_cached_envelope(path: &Path) -> Option<Envelope> {
+    let f = fs::File::open(path).ok()?;
+    let filesize = f.metadata().ok()?.len();
+    let mut reader = BufReader::new(f);
+    let mut header = [0_u8; ENVELOPE_CACHE_HEADER.len()];
+    reader.read_exact(&mut header).ok()?;
+    if &header != ENVELOPE_CACHE_HEADER {
+        return None;
+    }
+    bincode::Options::deserialize_from(
+        bincode::Options::with_limit(bincode::config::DefaultOptions::new(), 2 * filesize),
+        reader,
+    )
+    .ok()
+}
+
+/// Writes `env` to the envelope cache file at `path`, readable only by its
+/// owner.
+fn write_cached_envelope(path: PathBuf, env: &Envelope) -> Result<()> {
+    use std::{io::Write, os::unix::fs::PermissionsExt};
+
+    let f = fs::File::create(path)?;
+    let mut permissions = f.metadata()?.permissions();
+    permissions.set_mode(0o600); // Read/write for owner only.
+    f.set_permissions(permissions)?;
+    let mut writer = std::io::BufWriter::new(f);
+    writer.write_all(ENVELOPE_CACHE_HEADER)?;
+    bincode::Options::serialize_into(bincode::config::DefaultOptions::new(), &mut writer, env)?;
+    writer.flush()?;
+    Ok(())
+}
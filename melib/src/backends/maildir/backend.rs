@@ -26,7 +26,9 @@
 
 use futures::prelude::Stream;
 
-use super::{MaildirMailbox, MaildirOp, MaildirPathTrait};
+use super::{
+    read_envelope_bytes, write_cached_envelope, MaildirMailbox, MaildirOp, MaildirPathTrait,
+};
 use crate::{
     backends::{RefreshEventKind::*, *},
     conf::AccountSettings,
@@ -44,7 +46,7 @@ use std::{
     hash::{Hash, Hasher},
     io::{self, Read, Write},
     ops::{Deref, DerefMut},
-    os::unix::fs::PermissionsExt,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
     path::{Component, Path, PathBuf},
     sync::{mpsc::channel, Arc, Mutex},
     time::Duration,
@@ -123,6 +125,10 @@ pub struct MaildirType {
     event_consumer: BackendEventConsumer,
     collection: Collection,
     path: PathBuf,
+    /// Load envelopes from an sqlite3 index instead of parsing every file,
+    /// see the `cache` module.
+    #[cfg_attr(not(feature = "sqlite3"), allow(dead_code))]
+    lazy_envelopes: bool,
 }
 
 macro_rules! path_is_new {
@@ -153,9 +159,29 @@ macro_rules! get_path_hash {
     }};
 }
 
+/// Returns the envelope hash of the message file `file`.
+///
+/// Only the mailbox directory and the unique name of the file are hashed,
+/// not the `new` or `cur` subdirectory or the info part of the name after
+/// `:` that holds the flags. A message keeps its hash when it is moved to
+/// `cur` or its flags change, so these renames update the envelope in place.
+/// The Message-ID is not used since copies of a message share it, and hashing
+/// the contents would mean reading the file on every rename.
 pub fn get_file_hash(file: &Path) -> EnvelopeHash {
     let mut hasher = DefaultHasher::default();
-    file.hash(&mut hasher);
+    match (file.file_name(), file.parent().and_then(Path::parent)) {
+        (Some(name), Some(mailbox)) => {
+            let name = name.as_bytes();
+            let unique_name = name
+                .iter()
+                .position(|&c| c == b':')
+                .map(|pos| &name[..pos])
+                .unwrap_or(name);
+            mailbox.hash(&mut hasher);
+            unique_name.hash(&mut hasher);
+        }
+        _ => file.hash(&mut hasher),
+    }
     EnvelopeHash(hasher.finish())
 }
 
@@ -214,6 +240,18 @@ impl MailBackend for MaildirType {
         let root_mailbox = self.path.to_path_buf();
         let map = self.hash_indexes.clone();
         let mailbox_index = self.mailbox_index.clone();
+        #[cfg(feature = "sqlite3")]
+        if self.lazy_envelopes {
+            return super::cache::fetch(
+                self.name.clone(),
+                mailbox_hash,
+                unseen,
+                total,
+                path,
+                map,
+                mailbox_index,
+            );
+        }
         super::stream::MaildirStream::new(
             &self.name,
             mailbox_hash,
@@ -240,7 +278,6 @@ impl MailBackend for MaildirType {
         Ok(Box::pin(async move {
             let thunk = move |sender: &BackendEventConsumer| {
                 debug!("refreshing");
-                let mut buf = Vec::with_capacity(4096);
                 let files = Self::list_mail_in_maildir_fs(path.clone(), false)?;
                 let mut current_hashes = {
                     let mut map = map.lock().unwrap();
@@ -259,10 +296,8 @@ impl MailBackend for MaildirType {
                         }
                         (*map).insert(hash, PathBuf::from(&file).into());
                     }
-                    let mut reader = io::BufReader::new(fs::File::open(&file)?);
-                    buf.clear();
-                    reader.read_to_end(&mut buf)?;
-                    if let Ok(mut env) = Envelope::from_bytes(buf.as_slice(), Some(file.flags())) {
+                    let bytes = read_envelope_bytes(&file)?;
+                    if let Ok(mut env) = Envelope::from_bytes(&bytes, Some(file.flags())) {
                         env.set_hash(hash);
                         mailbox_index
                             .lock()
@@ -271,19 +306,7 @@ impl MailBackend for MaildirType {
                         let file_name = file.strip_prefix(&root_mailbox).unwrap().to_path_buf();
                         if let Ok(cached) = cache_dir.place_cache_file(file_name) {
                             /* place result in cache directory */
-                            let f = fs::File::create(cached)?;
-                            let metadata = f.metadata()?;
-                            let mut permissions = metadata.permissions();
-
-                            permissions.set_mode(0o600); // Read/write for owner only.
-                            f.set_permissions(permissions)?;
-
-                            let writer = io::BufWriter::new(f);
-                            bincode::Options::serialize_into(
-                                bincode::config::DefaultOptions::new(),
-                                writer,
-                                &env,
-                            )?;
+                            write_cached_envelope(cached, &env)?;
                         }
                         (sender)(
                             account_hash,
@@ -354,7 +377,6 @@ impl MailBackend for MaildirType {
         Ok(Box::pin(async move {
             // Move `watcher` in the closure's scope so that it doesn't get dropped.
             let _watcher = watcher;
-            let mut buf = Vec::with_capacity(4096);
             loop {
                 match rx.recv() {
                     /*
@@ -394,7 +416,6 @@ impl MailBackend for MaildirType {
                                 pathbuf.as_path(),
                                 &cache_dir,
                                 file_name,
-                                &mut buf,
                             ) {
                                 mailbox_index
                                     .lock()
@@ -449,7 +470,6 @@ impl MailBackend for MaildirType {
                                         pathbuf.as_path(),
                                         &cache_dir,
                                         file_name,
-                                        &mut buf,
                                     ) {
                                         mailbox_index
                                             .lock()
@@ -468,13 +488,11 @@ impl MailBackend for MaildirType {
                                 }
                             };
                             let new_hash: EnvelopeHash = get_file_hash(pathbuf.as_path());
-                            let mut reader = io::BufReader::new(fs::File::open(&pathbuf)?);
-                            buf.clear();
-                            reader.read_to_end(&mut buf)?;
+                            let bytes = read_envelope_bytes(&pathbuf)?;
                             if index_lock.get_mut(&new_hash).is_none() {
                                 debug!("write notice");
                                 if let Ok(mut env) =
-                                    Envelope::from_bytes(buf.as_slice(), Some(pathbuf.flags()))
+                                    Envelope::from_bytes(&bytes, Some(pathbuf.flags()))
                                 {
                                     env.set_hash(new_hash);
                                     debug!("{}\t{:?}", new_hash, &pathbuf);
@@ -601,7 +619,6 @@ impl MailBackend for MaildirType {
                                         dest.as_path(),
                                         &cache_dir,
                                         file_name,
-                                        &mut buf,
                                     ) {
                                         mailbox_index
                                             .lock()
@@ -627,18 +644,20 @@ impl MailBackend for MaildirType {
                                         );
                                     }
                                 } else {
-                                    index_lock.entry(old_hash).and_modify(|e| {
-                                        debug!(&e.modified);
-                                        e.modified = Some(PathMod::Hash(new_hash));
-                                    });
-                                    (sender)(
-                                        account_hash,
-                                        BackendEvent::Refresh(RefreshEvent {
+                                    if old_hash != new_hash {
+                                        index_lock.entry(old_hash).and_modify(|e| {
+                                            debug!(&e.modified);
+                                            e.modified = Some(PathMod::Hash(new_hash));
+                                        });
+                                        (sender)(
                                             account_hash,
-                                            mailbox_hash,
-                                            kind: Rename(old_hash, new_hash),
-                                        }),
-                                    );
+                                            BackendEvent::Refresh(RefreshEvent {
+                                                account_hash,
+                                                mailbox_hash,
+                                                kind: Rename(old_hash, new_hash),
+                                            }),
+                                        );
+                                    }
                                     if !was_seen && is_seen {
                                         let mut lck =
                                             mailbox_counts[&mailbox_hash].0.lock().unwrap();
@@ -695,7 +714,6 @@ impl MailBackend for MaildirType {
                                     dest.as_path(),
                                     &cache_dir,
                                     file_name,
-                                    &mut buf,
                                 ) {
                                     mailbox_index
                                         .lock()
@@ -742,7 +760,6 @@ impl MailBackend for MaildirType {
                                     dest.as_path(),
                                     &cache_dir,
                                     file_name,
-                                    &mut buf,
                                 ) {
                                     mailbox_index
                                         .lock()
@@ -771,14 +788,16 @@ impl MailBackend for MaildirType {
                                 if was_seen && !is_seen {
                                     *mailbox_counts[&mailbox_hash].0.lock().unwrap() += 1;
                                 }
-                                (sender)(
-                                    account_hash,
-                                    BackendEvent::Refresh(RefreshEvent {
+                                if old_hash != new_hash {
+                                    (sender)(
                                         account_hash,
-                                        mailbox_hash,
-                                        kind: Rename(old_hash, new_hash),
-                                    }),
-                                );
+                                        BackendEvent::Refresh(RefreshEvent {
+                                            account_hash,
+                                            mailbox_hash,
+                                            kind: Rename(old_hash, new_hash),
+                                        }),
+                                    );
+                                }
                                 debug!("contains_new_key");
                                 if old_flags != new_flags {
                                     (sender)(
@@ -1262,6 +1281,7 @@ impl MaildirType {
             event_consumer,
             collection: Default::default(),
             path: root_mailbox,
+            lazy_envelopes: Self::lazy_envelopes(settings)?,
         }))
     }
 
@@ -1334,7 +1354,32 @@ impl MaildirType {
         Ok(())
     }
 
+    /// Parses the `lazy_envelopes` setting, which needs the sqlite3 feature.
+    fn lazy_envelopes(s: &AccountSettings) -> Result<bool> {
+        let lazy_envelopes = match s.extra.get("lazy_envelopes").map(String::as_str) {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(other) => {
+                return Err(Error::new(format!(
+                    "Configuration error ({}): Invalid value for field `lazy_envelopes`: {}",
+                    s.name, other
+                )));
+            }
+        };
+        if lazy_envelopes && !cfg!(feature = "sqlite3") {
+            return Err(Error::new(format!(
+                "Configuration error ({}): `lazy_envelopes` requires meli to be built with the \
+                 sqlite3 feature.",
+                s.name
+            ))
+            .set_kind(ErrorKind::NotSupported));
+        }
+        Ok(lazy_envelopes)
+    }
+
     pub fn validate_config(s: &mut AccountSettings) -> Result<()> {
+        Self::lazy_envelopes(s)?;
+        s.extra.remove("lazy_envelopes");
         let root_mailbox = PathBuf::from(&s.root_mailbox).expand();
         if !root_mailbox.exists() {
             return Err(Error::new(format!(
@@ -1378,7 +1423,6 @@ fn add_path_to_index(
     path: &Path,
     cache_dir: &xdg::BaseDirectories,
     file_name: PathBuf,
-    buf: &mut Vec<u8>,
 ) -> Result<Envelope> {
     debug!("add_path_to_index path {:?} filename{:?}", path, file_name);
     let env_hash = get_file_hash(path);
@@ -1393,10 +1437,8 @@ fn add_path_to_index(
             map.len()
         );
     }
-    let mut reader = io::BufReader::new(fs::File::open(path)?);
-    buf.clear();
-    reader.read_to_end(buf)?;
-    let mut env = Envelope::from_bytes(buf.as_slice(), Some(path.flags()))?;
+    let bytes = read_envelope_bytes(path)?;
+    let mut env = Envelope::from_bytes(&bytes, Some(path.flags()))?;
     env.set_hash(env_hash);
     debug!(
         "add_path_to_index gen {}\t{}",
@@ -1406,14 +1448,37 @@ fn add_path_to_index(
     if let Ok(cached) = cache_dir.place_cache_file(file_name) {
         debug!("putting in cache");
         /* place result in cache directory */
-        let f = fs::File::create(cached)?;
-        let metadata = f.metadata()?;
-        let mut permissions = metadata.permissions();
-
-        permissions.set_mode(0o600); // Read/write for owner only.
-        f.set_permissions(permissions)?;
-        let writer = io::BufWriter::new(f);
-        bincode::Options::serialize_into(bincode::config::DefaultOptions::new(), writer, &env)?;
+        write_cached_envelope(cached, &env)?;
     }
     Ok(env)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maildir_file_hash_stable_across_renames() {
+        let hash = get_file_hash(Path::new("/mail/INBOX/cur/1692.M1P2.host:2,S"));
+        assert_eq!(
+            hash,
+            get_file_hash(Path::new("/mail/INBOX/cur/1692.M1P2.host:2,RS"))
+        );
+        assert_eq!(
+            hash,
+            get_file_hash(Path::new("/mail/INBOX/cur/1692.M1P2.host:2,"))
+        );
+        assert_eq!(
+            hash,
+            get_file_hash(Path::new("/mail/INBOX/new/1692.M1P2.host"))
+        );
+        assert_ne!(
+            hash,
+            get_file_hash(Path::new("/mail/Archive/cur/1692.M1P2.host:2,S"))
+        );
+        assert_ne!(
+            hash,
+            get_file_hash(Path::new("/mail/INBOX/cur/1693.M1P2.host:2,S"))
+        );
+    }
+}
@@ -0,0 +1,265 @@
+/*
+ * meli - maildir
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Envelope index of maildir mailboxes in an sqlite3 database, used instead
+//! of [`MaildirStream`](super::MaildirStream) when the `lazy_envelopes`
+//! account setting is set.
+//!
+//! The content of a maildir message file never changes; only its name does,
+//! when its flags change. So the envelope of a file is stored under its
+//! [`get_file_hash`] and reused as long as a file with that hash exists,
+//! without opening the file again. Of new files only the headers are read,
+//! and bodies are parsed when a message is opened.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::stream::Stream;
+
+use super::{get_file_hash, move_to_cur, HashIndexes, MaildirPathTrait};
+use crate::{
+    backends::MailboxHash,
+    email::{Envelope, EnvelopeHash},
+    error::Result,
+    sqlite3::{self, DatabaseDescription},
+};
+
+const DB_DESCRIPTION: DatabaseDescription = DatabaseDescription {
+    name: "maildir_envelopes.db",
+    init_script: Some(
+        "PRAGMA foreign_keys = true;
+    PRAGMA encoding = 'UTF-8';
+
+    CREATE TABLE IF NOT EXISTS envelopes (
+                    hash                INTEGER PRIMARY KEY,
+                    mailbox_hash        INTEGER NOT NULL,
+                    envelope            BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS envelope_mailbox_idx ON envelopes(mailbox_hash);",
+    ),
+    version: 1,
+};
+
+type EnvelopeStream = Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>;
+
+/// Returns a stream of the envelopes of the maildir mailbox at `path`, from
+/// the index of account `name`.
+pub fn fetch(
+    name: String,
+    mailbox_hash: MailboxHash,
+    unseen: Arc<Mutex<usize>>,
+    total: Arc<Mutex<usize>>,
+    path: PathBuf,
+    map: HashIndexes,
+    mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
+) -> Result<EnvelopeStream> {
+    Ok(Box::pin(futures::stream::once(async move {
+        let envelopes = load(&name, mailbox_hash, &path, &map, &mailbox_index)?;
+        *total.lock().unwrap() += envelopes.len();
+        *unseen.lock().unwrap() += envelopes.iter().filter(|e| !e.is_seen()).count();
+        Ok(envelopes)
+    })))
+}
+
+/// Returns the envelopes of the maildir mailbox at `path` and brings the
+/// index up to date with its files.
+fn load(
+    name: &str,
+    mailbox_hash: MailboxHash,
+    path: &Path,
+    map: &HashIndexes,
+    mailbox_index: &Mutex<HashMap<EnvelopeHash, MailboxHash>>,
+) -> Result<Vec<Envelope>> {
+    for p in path.join("new").read_dir()?.flatten() {
+        let _ = move_to_cur(p.path());
+    }
+    let files = path
+        .join("cur")
+        .read_dir()?
+        .flatten()
+        .map(|e| e.path())
+        .collect::<Vec<PathBuf>>();
+
+    let mut connection = sqlite3::open_or_create_db(&DB_DESCRIPTION, Some(name))?;
+    let mut cached: HashMap<EnvelopeHash, Envelope> = {
+        let mut stmt =
+            connection.prepare("SELECT hash, envelope FROM envelopes WHERE mailbox_hash = ?1;")?;
+        let rows = stmt.query_map(sqlite3::params![mailbox_hash], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect::<std::result::Result<_, _>>()?
+    };
+
+    let mut ret = Vec::with_capacity(files.len());
+    let mut new = vec![];
+    {
+        let mut map = map.lock().unwrap();
+        let map = map.entry(mailbox_hash).or_default();
+        let mut mailbox_index = mailbox_index.lock().unwrap();
+        for file in files {
+            let hash = get_file_hash(&file);
+            let env = if let Some(mut env) = cached.remove(&hash) {
+                env.set_flags(file.flags());
+                env
+            } else {
+                match parse_headers(&file) {
+                    Ok(mut env) => {
+                        env.set_hash(hash);
+                        new.push(env.clone());
+                        env
+                    }
+                    Err(err) => {
+                        debug!(
+                            "DEBUG: hash {}, path: {} couldn't be parsed, {}",
+                            hash,
+                            file.display(),
+                            err,
+                        );
+                        continue;
+                    }
+                }
+            };
+            map.insert(hash, file.into());
+            mailbox_index.insert(hash, mailbox_hash);
+            ret.push(env);
+        }
+    }
+
+    /* What is left in `cached` are messages whose files were removed. */
+    let tx = connection.transaction()?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT OR REPLACE INTO envelopes (hash, mailbox_hash, envelope) VALUES (?1, ?2, ?3);",
+        )?;
+        for env in &new {
+            insert.execute(sqlite3::params![env.hash(), mailbox_hash, env])?;
+        }
+        let mut delete = tx.prepare("DELETE FROM envelopes WHERE hash = ?1;")?;
+        for hash in cached.keys() {
+            delete.execute(sqlite3::params![hash])?;
+        }
+    }
+    tx.commit()?;
+    Ok(ret)
+}
+
+/// Parses the envelope of `file` out of its headers only.
+fn parse_headers(file: &Path) -> Result<Envelope> {
+    let mut reader = BufReader::new(fs::File::open(file)?);
+    let mut headers = vec![];
+    loop {
+        let start = headers.len();
+        if reader.read_until(b'\n', &mut headers)? == 0 {
+            break;
+        }
+        if matches!(&headers[start..], b"\n" | b"\r\n") {
+            break;
+        }
+    }
+    let mut env = Envelope::from_bytes(&headers, Some(file.flags()))?;
+    /* Without the body, a `multipart/mixed` message is assumed to have
+     * attachments. */
+    if env
+        .other_headers()
+        .get("Content-Type")
+        .map(|ct| ct.to_ascii_lowercase().contains("multipart/mixed"))
+        .unwrap_or(false)
+    {
+        env.set_has_attachments(true);
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maildir_cache_load() {
+        let name = format!("meli-maildir-cache-test-{}", std::process::id());
+        let root = std::env::temp_dir().join(&name);
+        let mailbox = root.join("INBOX");
+        for dir in &["cur", "new", "tmp"] {
+            fs::create_dir_all(mailbox.join(dir)).unwrap();
+        }
+        for i in 0..3 {
+            fs::write(
+                mailbox.join("cur").join(format!("{}:2,", i)),
+                format!(
+                    "From: a@example.com\nSubject: message {}\nContent-Type: multipart/mixed; \
+                     boundary=\"b\"\n\n--b\n\nbody\n--b--\n",
+                    i
+                ),
+            )
+            .unwrap();
+        }
+        let mailbox_hash = MailboxHash(1);
+        let map = HashIndexes::default();
+        let mailbox_index = Mutex::new(HashMap::default());
+        let subjects = |envelopes: &[Envelope]| {
+            let mut ret = envelopes
+                .iter()
+                .map(|e| (e.subject().to_string(), e.is_seen()))
+                .collect::<Vec<_>>();
+            ret.sort();
+            ret
+        };
+
+        let envelopes = load(&name, mailbox_hash, &mailbox, &map, &mailbox_index).unwrap();
+        assert_eq!(
+            subjects(&envelopes),
+            vec![
+                ("message 0".to_string(), false),
+                ("message 1".to_string(), false),
+                ("message 2".to_string(), false)
+            ]
+        );
+        assert!(envelopes.iter().all(Envelope::has_attachments));
+
+        /* The second load reads the index: renames only change flags, and
+         * removed files are dropped. */
+        fs::rename(
+            mailbox.join("cur").join("0:2,"),
+            mailbox.join("cur").join("0:2,S"),
+        )
+        .unwrap();
+        fs::write(mailbox.join("cur").join("1:2,"), "not a message").unwrap();
+        fs::remove_file(mailbox.join("cur").join("2:2,")).unwrap();
+        let envelopes = load(&name, mailbox_hash, &mailbox, &map, &mailbox_index).unwrap();
+        assert_eq!(
+            subjects(&envelopes),
+            vec![
+                ("message 0".to_string(), true),
+                ("message 1".to_string(), false)
+            ]
+        );
+        assert_eq!(mailbox_index.lock().unwrap().len(), 3);
+
+        sqlite3::reset_db(&DB_DESCRIPTION, Some(&name)).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * meli - mailbox module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Reading maildir message files to build their envelopes.
+//!
+//! Envelopes are built from the headers of a message, so during a scan only
+//! the header section of a file is read from disk. The body is read too only
+//! for `multipart/mixed` messages, whose parts are checked for attachments.
+//! Files are read instead of memory-mapped: touching a mapping of a file that
+//! another program truncated raises `SIGBUS`.
+
+use std::io::Read;
+
+use super::*;
+use crate::email::parser;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads the message file at `path` as far as building its envelope needs.
+pub fn read_envelope_bytes(path: &Path) -> Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    let mut buf = Vec::with_capacity(CHUNK_SIZE);
+    let mut searched: usize = 0;
+    loop {
+        let len = buf.len();
+        buf.resize(len + CHUNK_SIZE, 0);
+        let read = file.read(&mut buf[len..])?;
+        buf.truncate(len + read);
+        if read == 0 {
+            return Ok(buf);
+        }
+        /* The separator may straddle two chunks. */
+        if let Some(end) = header_section_end(&buf, searched.saturating_sub(2)) {
+            if !needs_body(&buf[..end]) {
+                return Ok(buf);
+            }
+            file.read_to_end(&mut buf)?;
+            return Ok(buf);
+        }
+        searched = buf.len();
+    }
+}
+
+/// Returns the offset after the empty line that ends the header section, if
+/// it is in `bytes`, looking from `from` onwards.
+fn header_section_end(bytes: &[u8], from: usize) -> Option<usize> {
+    bytes[from..].windows(2).enumerate().find_map(|(i, w)| {
+        let i = from + i;
+        match w {
+            b"\n\n" => Some(i + 2),
+            b"\n\r" if bytes.get(i + 2) == Some(&b'\n') => Some(i + 3),
+            _ => None,
+        }
+    })
+}
+
+/// Returns whether building the envelope of a message with this header
+/// section reads its body, i.e. whether it is `multipart/mixed`. Header
+/// sections that don't parse are given the whole message.
+fn needs_body(header_section: &[u8]) -> bool {
+    let headers = match parser::mail(header_section) {
+        Ok((headers, _)) => headers,
+        Err(_) => return true,
+    };
+    headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case(b"content-type")
+            && matches!(
+                parser::attachments::content_type(value),
+                Ok((_, (ct, cst, _)))
+                    if ct.eq_ignore_ascii_case(b"multipart") && cst.eq_ignore_ascii_case(b"mixed")
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maildir_read_envelope_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("message");
+        let body = "body line\n".repeat(4 * CHUNK_SIZE);
+
+        /* Only the header section of plain messages is read. */
+        let message = format!("From: a@example.com\nSubject: plain\n\n{}", body);
+        fs::write(&path, &message).unwrap();
+        let bytes = read_envelope_bytes(&path).unwrap();
+        assert!(bytes.len() < message.len());
+        assert!(message.as_bytes().starts_with(&bytes));
+        let env = Envelope::from_bytes(&bytes, None).unwrap();
+        assert_eq!(env.subject(), "plain");
+
+        /* A separator split between two chunks is found. */
+        let message = format!(
+            "Subject: split\r\nX-Padding: {}\r\n\r\n{}",
+            "x".repeat(CHUNK_SIZE - 29),
+            body
+        );
+        fs::write(&path, &message).unwrap();
+        let bytes = read_envelope_bytes(&path).unwrap();
+        assert_eq!(header_section_end(&bytes, 0), Some(CHUNK_SIZE + 2));
+        assert!(bytes.len() < message.len());
+
+        /* multipart/mixed messages are read whole to look for attachments. */
+        let message = format!(
+            "Subject: mixed\nContent-Type: multipart/mixed; boundary=\"xyzzy\"\n\n--xyzzy\n\
+             Content-Type: text/plain\n\n{}\n--xyzzy\nContent-Type: application/pdf\n\
+             Content-Disposition: attachment; filename=\"a.pdf\"\n\nAAAA\n--xyzzy--\n",
+            body
+        );
+        fs::write(&path, &message).unwrap();
+        let bytes = read_envelope_bytes(&path).unwrap();
+        assert_eq!(bytes, message.as_bytes());
+        assert!(Envelope::from_bytes(&bytes, None).unwrap().has_attachments);
+
+        /* Messages without a body and empty files. */
+        fs::write(&path, b"Subject: headers only\n").unwrap();
+        assert_eq!(
+            read_envelope_bytes(&path).unwrap(),
+            b"Subject: headers only\n"
+        );
+        fs::write(&path, b"").unwrap();
+        assert!(read_envelope_bytes(&path).unwrap().is_empty());
+        assert!(read_envelope_bytes(&dir.path().join("missing")).is_err());
+    }
+}
@@ -19,29 +19,41 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use core::{future::Future, pin::Pin};
+//! Initial scan of a maildir mailbox.
+//!
+//! The message files of a mailbox are split into chunks that a pool of
+//! worker threads parse in parallel. Each chunk is yielded by the stream as
+//! soon as it and every chunk before it are done, so envelopes arrive in the
+//! order of the directory listing no matter which worker finished first.
+
+use core::pin::Pin;
 use std::{
-    io::{self, Read},
-    os::unix::fs::PermissionsExt,
+    collections::BTreeMap,
     path::PathBuf,
-    result,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use futures::{
-    stream::{FuturesUnordered, StreamExt},
+    stream::StreamExt,
     task::{Context, Poll},
 };
 
 use super::*;
 use crate::backends::maildir::backend::move_to_cur;
 
+/// Number of message files parsed by a worker at a time.
+const CHUNK_SIZE: usize = 256;
+/// Upper limit on the number of workers of a single mailbox scan.
+const MAX_WORKERS: usize = 8;
+
 pub struct MaildirStream {
-    payloads: Pin<
-        Box<
-            FuturesUnordered<Pin<Box<dyn Future<Output = Result<Vec<Envelope>>> + Send + 'static>>>,
-        >,
-    >,
+    payloads: smol::channel::Receiver<(usize, Result<Vec<Envelope>>)>,
+    /// Chunks that finished before some chunk preceding them.
+    pending: BTreeMap<usize, Result<Vec<Envelope>>>,
+    next: usize,
 }
 
 impl MaildirStream {
@@ -55,77 +67,99 @@ impl MaildirStream {
         map: HashIndexes,
         mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
-        let chunk_size = 2048;
         path.push("new");
         for p in path.read_dir()?.flatten() {
             move_to_cur(p.path()).ok().take();
         }
         path.pop();
         path.push("cur");
-        let files: Vec<PathBuf> = path
-            .read_dir()?
-            .flatten()
-            .map(|e| e.path())
-            .collect::<Vec<_>>();
-        let payloads = Box::pin(if !files.is_empty() {
-            files
-                .chunks(chunk_size)
-                .map(|chunk| {
-                    let cache_dir = xdg::BaseDirectories::with_profile("meli", name).unwrap();
-                    Box::pin(Self::chunk(
-                        SmallVec::from(chunk),
-                        cache_dir,
+        let files: Arc<Vec<PathBuf>> = Arc::new(
+            path.read_dir()?
+                .flatten()
+                .map(|e| e.path())
+                .collect::<Vec<_>>(),
+        );
+        /* Count every file up front so that the mailbox total can be used to
+         * show the progress of the scan. Files that can't be parsed are
+         * subtracted again by the worker that finds them. */
+        *total.lock().unwrap() += files.len();
+        let chunks = (files.len() + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS)
+            .min(chunks);
+        let (sender, payloads) = smol::channel::bounded(workers.max(1));
+        let next_chunk = Arc::new(AtomicUsize::new(0));
+        for i in 0..workers {
+            let files = files.clone();
+            let next_chunk = next_chunk.clone();
+            let sender = sender.clone();
+            let cache_dir = xdg::BaseDirectories::with_profile("meli", name).unwrap();
+            let unseen = unseen.clone();
+            let total = total.clone();
+            let root_mailbox = root_mailbox.clone();
+            let map = map.clone();
+            let mailbox_index = mailbox_index.clone();
+            std::thread::Builder::new()
+                .name(format!("maildir-scan-{}-{}", mailbox_hash, i))
+                .spawn(move || loop {
+                    let idx = next_chunk.fetch_add(1, Ordering::SeqCst);
+                    if idx >= chunks {
+                        break;
+                    }
+                    let chunk = &files[idx * CHUNK_SIZE..files.len().min((idx + 1) * CHUNK_SIZE)];
+                    let result = Self::chunk(
+                        chunk,
+                        &cache_dir,
                         mailbox_hash,
-                        unseen.clone(),
-                        total.clone(),
-                        root_mailbox.clone(),
-                        map.clone(),
-                        mailbox_index.clone(),
-                    )) as Pin<Box<dyn Future<Output = _> + Send + 'static>>
-                })
-                .collect::<_>()
-        } else {
-            FuturesUnordered::new()
-        });
-        Ok(Self { payloads }.boxed())
+                        &unseen,
+                        &total,
+                        &root_mailbox,
+                        &map,
+                        &mailbox_index,
+                    );
+                    if smol::block_on(sender.send((idx, result))).is_err() {
+                        /* The stream was dropped, stop scanning. */
+                        break;
+                    }
+                })?;
+        }
+        Ok(Self {
+            payloads,
+            pending: BTreeMap::new(),
+            next: 0,
+        }
+        .boxed())
     }
 
-    async fn chunk(
-        chunk: SmallVec<[std::path::PathBuf; 2048]>,
-        cache_dir: xdg::BaseDirectories,
+    /// Parses the message files of `chunk`, in order.
+    fn chunk(
+        chunk: &[PathBuf],
+        cache_dir: &xdg::BaseDirectories,
         mailbox_hash: MailboxHash,
-        unseen: Arc<Mutex<usize>>,
-        total: Arc<Mutex<usize>>,
-        root_mailbox: PathBuf,
-        map: HashIndexes,
-        mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
+        unseen: &Mutex<usize>,
+        total: &Mutex<usize>,
+        root_mailbox: &Path,
+        map: &HashIndexes,
+        mailbox_index: &Mutex<HashMap<EnvelopeHash, MailboxHash>>,
     ) -> Result<Vec<Envelope>> {
         let mut local_r: Vec<Envelope> = Vec::with_capacity(chunk.len());
         let mut unseen_total: usize = 0;
-        let mut buf = Vec::with_capacity(4096);
+        let mut failed: usize = 0;
         for file in chunk {
             /* Check if we have a cache file with this email's
              * filename */
-            let file_name = PathBuf::from(&file)
-                .strip_prefix(&root_mailbox)
-                .unwrap()
-                .to_path_buf();
+            let file_name = file.strip_prefix(root_mailbox).unwrap().to_path_buf();
             if let Some(cached) = cache_dir.find_cache_file(&file_name) {
                 /* Cached struct exists, try to load it */
-                let cached_file = fs::File::open(&cached)?;
-                let filesize = cached_file.metadata()?.len();
-                let reader = io::BufReader::new(cached_file);
-                let result: result::Result<Envelope, _> = bincode::Options::deserialize_from(
-                    bincode::Options::with_limit(
-                        bincode::config::DefaultOptions::new(),
-                        2 * filesize,
-                    ),
-                    reader,
-                );
-                if let Ok(env) = result {
+                if let Some(mut env) = read_cached_envelope(&cached) {
                     let mut map = map.lock().unwrap();
                     let map = map.entry(mailbox_hash).or_default();
-                    let hash = env.hash();
+                    /* Caches written by older versions hold hashes of the
+                     * whole path. */
+                    let hash = get_file_hash(file);
+                    env.set_hash(hash);
                     map.insert(hash, file.clone().into());
                     mailbox_index.lock().unwrap().insert(hash, mailbox_hash);
                     if !env.is_seen() {
@@ -134,37 +168,23 @@ impl MaildirStream {
                     local_r.push(env);
                     continue;
                 }
-                /* Try delete invalid file */
+                /* Try delete invalid or stale file */
                 let _ = fs::remove_file(&cached);
             };
-            let env_hash = get_file_hash(&file);
+            let env_hash = get_file_hash(file);
             {
                 let mut map = map.lock().unwrap();
                 let map = map.entry(mailbox_hash).or_default();
-                map.insert(env_hash, PathBuf::from(&file).into());
+                map.insert(env_hash, file.clone().into());
             }
-            let mut reader = io::BufReader::new(fs::File::open(&file)?);
-            buf.clear();
-            reader.read_to_end(&mut buf)?;
-            match Envelope::from_bytes(buf.as_slice(), Some(file.flags())) {
+            let bytes = read_envelope_bytes(file)?;
+            match Envelope::from_bytes(&bytes, Some(file.flags())) {
                 Ok(mut env) => {
                     env.set_hash(env_hash);
                     mailbox_index.lock().unwrap().insert(env_hash, mailbox_hash);
                     if let Ok(cached) = cache_dir.place_cache_file(file_name) {
                         /* place result in cache directory */
-                        let f = fs::File::create(cached)?;
-                        let metadata = f.metadata()?;
-                        let mut permissions = metadata.permissions();
-
-                        permissions.set_mode(0o600); // Read/write for owner only.
-                        f.set_permissions(permissions)?;
-
-                        let writer = io::BufWriter::new(f);
-                        bincode::Options::serialize_into(
-                            bincode::config::DefaultOptions::new(),
-                            writer,
-                            &env,
-                        )?;
+                        write_cached_envelope(cached, &env)?;
                     }
                     if !env.is_seen() {
                         unseen_total += 1;
@@ -178,11 +198,15 @@ impl MaildirStream {
                         file.as_path().display(),
                         err,
                     );
+                    failed += 1;
                     continue;
                 }
             }
         }
-        *total.lock().unwrap() += local_r.len();
+        if failed > 0 {
+            let mut total = total.lock().unwrap();
+            *total = total.saturating_sub(failed);
+        }
         *unseen.lock().unwrap() += unseen_total;
         Ok(local_r)
     }
@@ -191,7 +215,116 @@ impl MaildirStream {
 impl Stream for MaildirStream {
     type Item = Result<Vec<Envelope>>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let payloads = self.payloads.as_mut();
-        payloads.poll_next(cx)
+        loop {
+            let next = self.next;
+            if let Some(payload) = self.pending.remove(&next) {
+                self.next += 1;
+                return Poll::Ready(Some(payload));
+            }
+            match self.payloads.poll_next_unpin(cx) {
+                Poll::Ready(Some((idx, payload))) => {
+                    self.pending.insert(idx, payload);
+                }
+                Poll::Ready(None) => {
+                    /* All workers have exited; only a panicked worker can
+                     * leave gaps, so yield whatever is left in order. */
+                    let first = self.pending.keys().next().copied();
+                    return Poll::Ready(first.and_then(|idx| {
+                        self.next = idx + 1;
+                        self.pending.remove(&idx)
+                    }));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maildir_stream_order() {
+        let name = format!("meli-maildir-stream-test-{}", std::process::id());
+        let root = std::env::temp_dir().join(&name);
+        let mailbox = root.join("INBOX");
+        for dir in &["cur", "new", "tmp"] {
+            fs::create_dir_all(mailbox.join(dir)).unwrap();
+        }
+        const MESSAGES: usize = 3 * CHUNK_SIZE + 10;
+        for i in 0..MESSAGES {
+            fs::write(
+                mailbox
+                    .join("cur")
+                    .join(format!("{}:2,{}", i, if i % 2 == 0 { "S" } else { "" })),
+                format!("From: a@example.com\nSubject: message {}\n\nbody\n", i),
+            )
+            .unwrap();
+        }
+        let listing = fs::read_dir(mailbox.join("cur"))
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .collect::<Vec<PathBuf>>();
+
+        let mailbox_hash = MailboxHash(1);
+        let unseen = Arc::new(Mutex::new(0));
+        let total = Arc::new(Mutex::new(0));
+        let stream = MaildirStream::new(
+            &name,
+            mailbox_hash,
+            unseen.clone(),
+            total.clone(),
+            mailbox.clone(),
+            root.clone(),
+            HashIndexes::default(),
+            Default::default(),
+        )
+        .unwrap();
+        let payloads = futures::executor::block_on(stream.collect::<Vec<_>>());
+        let envelopes = payloads
+            .into_iter()
+            .flat_map(|payload| payload.unwrap())
+            .collect::<Vec<Envelope>>();
+        /* Envelopes arrive in the order of the directory listing. */
+        assert_eq!(
+            envelopes.iter().map(Envelope::hash).collect::<Vec<_>>(),
+            listing.iter().map(|p| get_file_hash(p)).collect::<Vec<_>>()
+        );
+        assert_eq!(envelopes.len(), MESSAGES);
+        assert_eq!(*total.lock().unwrap(), MESSAGES);
+        assert_eq!(*unseen.lock().unwrap(), MESSAGES / 2);
+
+        let _ = fs::remove_dir_all(
+            xdg::BaseDirectories::with_profile("meli", &name)
+                .unwrap()
+                .get_cache_home(),
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_maildir_envelope_cache_version() {
+        let path = std::env::temp_dir().join(format!(
+            "meli-maildir-envelope-cache-test-{}",
+            std::process::id()
+        ));
+        let env =
+            Envelope::from_bytes(b"From: a@example.com\nSubject: cached\n\nbody\n", None).unwrap();
+        write_cached_envelope(path.clone(), &env).unwrap();
+        assert_eq!(
+            read_cached_envelope(&path).map(|env| env.subject().into_owned()),
+            Some("cached".to_string())
+        );
+        /* Entries without the current header, such as those written before the
+         * cache was versioned, are rejected. */
+        fs::write(
+            &path,
+            bincode::Options::serialize(bincode::config::DefaultOptions::new(), &env).unwrap(),
+        )
+        .unwrap();
+        assert!(read_cached_envelope(&path).is_none());
+        fs::remove_file(&path).unwrap();
     }
 }
@@ -153,7 +153,7 @@ extern crate notify;
 use std::{
     collections::hash_map::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, Read, Seek, SeekFrom, Write},
     os::unix::io::AsRawFd,
     path::{Path, PathBuf},
     str::FromStr,
@@ -201,6 +201,150 @@ fn get_rw_lock_blocking(f: &File, path: &Path) -> Result<()> {
     Ok(())
 }
 
+fn get_flock_blocking(f: &File, path: &Path) -> Result<()> {
+    let ret_val = unsafe { libc::flock(f.as_raw_fd(), libc::LOCK_EX) };
+    if ret_val == -1 {
+        let err = nix::errno::Errno::from_i32(nix::errno::errno());
+        return Err(Error::new(format!(
+            "Could not lock {}: flock() returned {}",
+            path.display(),
+            err.desc()
+        )));
+    }
+    Ok(())
+}
+
+/// Locks taken while writing to an mbox file, see the `write_locks` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MboxLock {
+    /// A `<mbox>.lock` file next to the mbox.
+    DotLock,
+    /// An fcntl(2) lock, which is also taken when reading.
+    Fcntl,
+    /// A flock(2) lock.
+    Flock,
+}
+
+impl FromStr for MboxLock {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "dotlock" => Ok(MboxLock::DotLock),
+            "fcntl" => Ok(MboxLock::Fcntl),
+            "flock" => Ok(MboxLock::Flock),
+            other => Err(Error::new(format!(
+                "invalid mbox lock `{}`, expected `dotlock`, `fcntl` or `flock`",
+                other
+            ))),
+        }
+    }
+}
+
+impl MboxLock {
+    /// Parses a list of locks separated by spaces or commas.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|l| !l.is_empty())
+            .map(Self::from_str)
+            .collect()
+    }
+}
+
+/// A `<mbox>.lock` file, removed when dropped.
+#[derive(Debug)]
+struct DotLock {
+    path: PathBuf,
+}
+
+impl DotLock {
+    /// Creates the dot-lock of the mbox at `path`, waiting for up to 30
+    /// seconds while another program holds it. Dot-locks older than five
+    /// minutes are considered stale and removed. Returns `None` if the
+    /// directory of the mbox isn't writable.
+    fn new(path: &Path) -> Result<Option<Self>> {
+        let mut lock_path = path.as_os_str().to_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        for _ in 0..60 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(_) => return Ok(Some(DotLock { path: lock_path })),
+                Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                    log::warn!(
+                        "Could not create {}, writing without a dot-lock: {}",
+                        lock_path.display(),
+                        err
+                    );
+                    return Ok(None);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&lock_path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|m| m.elapsed().ok())
+                        .map(|age| age > std::time::Duration::from_secs(5 * 60))
+                        .unwrap_or(false);
+                    if stale {
+                        log::warn!("Removing stale dot-lock {}", lock_path.display());
+                        let _ = std::fs::remove_file(&lock_path);
+                    } else {
+                        std::thread::sleep(std::time::Duration::from_millis(500));
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(Error::new(format!(
+            "Could not lock {}: {} is held by another program",
+            path.display(),
+            lock_path.display()
+        )))
+    }
+}
+
+impl Drop for DotLock {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::error!("Could not remove dot-lock {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// An mbox file opened for writing, with its write locks held until it's
+/// dropped.
+#[derive(Debug)]
+struct LockedMbox {
+    file: File,
+    /* Declared after `file`, so that the dot-lock is removed after the file
+     * locks are released by closing it. */
+    _dotlock: Option<DotLock>,
+}
+
+impl LockedMbox {
+    fn open(path: &Path, locks: &[MboxLock]) -> Result<Self> {
+        let _dotlock = if locks.contains(&MboxLock::DotLock) {
+            DotLock::new(path)?
+        } else {
+            None
+        };
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        if locks.contains(&MboxLock::Fcntl) {
+            get_rw_lock_blocking(&file, path)?;
+        }
+        if locks.contains(&MboxLock::Flock) {
+            get_flock_blocking(&file, path)?;
+        }
+        Ok(LockedMbox { file, _dotlock })
+    }
+}
+
 #[derive(Debug)]
 struct MboxMailbox {
     hash: MailboxHash,
@@ -290,6 +434,8 @@ pub struct MboxOp {
     offset: Offset,
     length: Length,
     slice: std::cell::RefCell<Option<Vec<u8>>>,
+    /// Format used to undo the quoting of `From ` lines.
+    format: Option<MboxFormat>,
 }
 
 impl MboxOp {
@@ -300,6 +446,7 @@ impl MboxOp {
             slice: std::cell::RefCell::new(None),
             offset,
             length,
+            format: None,
         }
     }
 }
@@ -317,9 +464,12 @@ impl BackendOp for MboxOp {
             buf_reader.read_to_end(&mut contents)?;
             *self.slice.get_mut() = Some(contents);
         }
-        let ret = Ok(self.slice.get_mut().as_ref().unwrap().as_slice()
-            [self.offset..self.offset + self.length]
-            .to_vec());
+        let message = &self.slice.get_mut().as_ref().unwrap().as_slice()
+            [self.offset..self.offset + self.length];
+        let ret = Ok(match self.format {
+            Some(format) => format.unquote(message),
+            None => message.to_vec(),
+        });
         Ok(Box::pin(async move { ret }))
     }
 
@@ -860,9 +1010,287 @@ pub struct MboxType {
     mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
     mailboxes: Arc<Mutex<HashMap<MailboxHash, MboxMailbox>>>,
     prefer_mbox_type: Option<MboxFormat>,
+    write_locks: Vec<MboxLock>,
+    event_consumer: BackendEventConsumer,
+}
+
+/// What the write operations and the watcher of [`MboxType`] need, so that
+/// they can be moved into their futures.
+#[derive(Debug, Clone)]
+struct MboxWriter {
+    account_hash: AccountHash,
+    mailbox_index: Arc<Mutex<HashMap<EnvelopeHash, MailboxHash>>>,
+    mailboxes: Arc<Mutex<HashMap<MailboxHash, MboxMailbox>>>,
+    prefer_mbox_type: Option<MboxFormat>,
+    write_locks: Vec<MboxLock>,
     event_consumer: BackendEventConsumer,
 }
 
+impl MboxWriter {
+    fn mailbox_path(&self, mailbox_hash: MailboxHash) -> Result<PathBuf> {
+        self.mailboxes
+            .lock()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map(|m| m.fs_path.clone())
+            .ok_or_else(|| {
+                Error::new(format!("Mailbox with hash {} not found.", mailbox_hash))
+                    .set_kind(ErrorKind::Bug)
+            })
+    }
+
+    fn send_event(&self, mailbox_hash: MailboxHash, kind: RefreshEventKind) {
+        (self.event_consumer)(
+            self.account_hash,
+            BackendEvent::Refresh(RefreshEvent {
+                account_hash: self.account_hash,
+                mailbox_hash,
+                kind,
+            }),
+        );
+    }
+
+    /// Returns the messages `env_hashes` of `mailbox_hash` with their flags.
+    fn read_messages(
+        &self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &EnvelopeHashBatch,
+    ) -> Result<Vec<(Vec<u8>, Flag)>> {
+        let path = self.mailbox_path(mailbox_hash)?;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        get_rw_lock_blocking(&file, &path)?;
+        let mut contents = Vec::new();
+        BufReader::new(file).read_to_end(&mut contents)?;
+        let mailboxes_lck = self.mailboxes.lock().unwrap();
+        let index = mailboxes_lck[&mailbox_hash].index.lock().unwrap();
+        let mut ret = vec![];
+        for env_hash in env_hashes.iter() {
+            let (offset, length) = match index.get(&env_hash) {
+                Some(&(offset, length)) if offset + length <= contents.len() => (offset, length),
+                _ => continue,
+            };
+            let message = &contents[offset..offset + length];
+            /* Drop the blank line that separates it from the next message. */
+            let end = message.iter().rposition(|b| *b != b'\n').map(|p| p + 1);
+            let mut bytes = match self.prefer_mbox_type {
+                Some(format) => format.unquote(&message[..end.unwrap_or(0)]),
+                None => message[..end.unwrap_or(0)].to_vec(),
+            };
+            bytes.push(b'\n');
+            ret.push((bytes, write::flags_from_headers(message)));
+        }
+        Ok(ret)
+    }
+
+    /// Appends `messages` with their flags to the mbox of `mailbox_hash`. The
+    /// file is only appended to, never rewritten.
+    fn append(&self, mailbox_hash: MailboxHash, messages: &[(Vec<u8>, Flag)]) -> Result<()> {
+        let path = self.mailbox_path(mailbox_hash)?;
+        let mut mbox = LockedMbox::open(&path, &self.write_locks)?;
+        let file_len = mbox.file.metadata()?.len() as usize;
+        let mut last = [0_u8; 2];
+        let last = &mut last[..file_len.min(2)];
+        mbox.file.seek(SeekFrom::End(-(last.len() as i64)))?;
+        mbox.file.read_exact(last)?;
+        /* A blank line must separate the new messages from the last one. */
+        let mut buf = match last {
+            [] | [b'\n', b'\n'] => vec![],
+            [b'\n'] | [_, b'\n'] => b"\n".to_vec(),
+            _ => b"\n\n".to_vec(),
+        };
+        let first_offset = file_len + buf.len();
+        let format = self.prefer_mbox_type.unwrap_or_default();
+        for (i, (bytes, flags)) in messages.iter().enumerate() {
+            let envelope_from = Envelope::from_bytes(bytes, None)
+                .ok()
+                .and_then(|env| env.from().first().cloned());
+            format.append(
+                &mut buf,
+                bytes,
+                envelope_from.as_ref(),
+                Some(crate::datetime::now()),
+                (*flags, vec![]),
+                MboxMetadata::CClient,
+                i == 0,
+                false,
+            )?;
+        }
+        mbox.file.seek(SeekFrom::End(0))?;
+        mbox.file.write_all(&buf)?;
+        mbox.file.sync_all()?;
+
+        let mut mailboxes_lck = self.mailboxes.lock().unwrap();
+        let mailbox = match mailboxes_lck.get_mut(&mailbox_hash) {
+            Some(m) if m.content.len() == file_len => m,
+            /* The mailbox isn't loaded or another program changed it; the new messages are
+             * picked up when it's reloaded. */
+            _ => return Ok(()),
+        };
+        mailbox.content.extend_from_slice(&buf);
+        let envelopes = mbox_parse(
+            mailbox.index.clone(),
+            &mailbox.content,
+            first_offset,
+            self.prefer_mbox_type,
+        )
+        .map(|(_, envelopes)| envelopes)
+        .unwrap_or_default();
+        let mut mailbox_index_lck = self.mailbox_index.lock().unwrap();
+        for env in envelopes {
+            mailbox_index_lck.insert(env.hash(), mailbox_hash);
+            self.send_event(mailbox_hash, RefreshEventKind::Create(Box::new(env)));
+        }
+        Ok(())
+    }
+
+    /// Picks up the messages appended to the file of `mailbox_hash` since it
+    /// was last read, for example by a mail delivery agent, and sends a
+    /// `Create` event for each. Only the appended part is read and parsed. If
+    /// the file was changed in some other way, it is read again whole and a
+    /// `Rescan` event is sent instead.
+    fn sync_appended(&self, mailbox_hash: MailboxHash) -> Result<()> {
+        let path = self.mailbox_path(mailbox_hash)?;
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        get_rw_lock_blocking(&file, &path)?;
+        let mut mailboxes_lck = self.mailboxes.lock().unwrap();
+        let mailbox = match mailboxes_lck.get_mut(&mailbox_hash) {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        match read_appended(&mut file, &mailbox.content)? {
+            Some(appended) if appended.is_empty() => {}
+            Some(appended) => {
+                let offset = mailbox.content.len();
+                mailbox.content.extend_from_slice(&appended);
+                let envelopes = mbox_parse(
+                    mailbox.index.clone(),
+                    &mailbox.content,
+                    offset,
+                    self.prefer_mbox_type,
+                )
+                .map(|(_, envelopes)| envelopes)
+                .unwrap_or_default();
+                drop(mailboxes_lck);
+                let mut mailbox_index_lck = self.mailbox_index.lock().unwrap();
+                for env in envelopes {
+                    mailbox_index_lck.insert(env.hash(), mailbox_hash);
+                    self.send_event(mailbox_hash, RefreshEventKind::Create(Box::new(env)));
+                }
+            }
+            None => {
+                let mut contents = Vec::new();
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut contents)?;
+                mailbox.content = contents;
+                drop(mailboxes_lck);
+                self.send_event(mailbox_hash, RefreshEventKind::Rescan);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the change `change` returns for each message in `env_hashes`.
+    /// Only the part of the file from the first changed message onwards is
+    /// rewritten.
+    fn rewrite(
+        &self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &EnvelopeHashBatch,
+        change: impl Fn(&[u8]) -> write::MessageChange,
+    ) -> Result<()> {
+        let path = self.mailbox_path(mailbox_hash)?;
+        let mut mbox = LockedMbox::open(&path, &self.write_locks)?;
+        let mut contents = Vec::new();
+        mbox.file.read_to_end(&mut contents)?;
+        let mut mailboxes_lck = self.mailboxes.lock().unwrap();
+        let mailbox = mailboxes_lck.get_mut(&mailbox_hash).unwrap();
+        if mailbox.content != contents {
+            return Err(Error::new(format!(
+                "{} was changed by another program, try again once it's reloaded.",
+                path.display()
+            )));
+        }
+        let mut index = mailbox.index.lock().unwrap();
+        let changes = env_hashes
+            .iter()
+            .filter_map(|env_hash| {
+                let (offset, length) = index.get(&env_hash)?;
+                let message = contents.get(*offset..*offset + *length)?;
+                Some((env_hash, change(message)))
+            })
+            .collect::<HashMap<EnvelopeHash, write::MessageChange>>();
+        let mut new_index = index.clone();
+        let (start, tail) = match write::rewrite(&contents, &mut new_index, &changes) {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        mbox.file.seek(SeekFrom::Start(start as u64))?;
+        mbox.file.write_all(&tail)?;
+        mbox.file.set_len((start + tail.len()) as u64)?;
+        mbox.file.sync_all()?;
+        *index = new_index;
+        contents.truncate(start);
+        contents.extend_from_slice(&tail);
+        mailbox.content = contents;
+        drop(index);
+        drop(mailboxes_lck);
+
+        let mut mailbox_index_lck = self.mailbox_index.lock().unwrap();
+        for (env_hash, change) in changes {
+            if change == write::MessageChange::Delete {
+                mailbox_index_lck.remove(&env_hash);
+                self.send_event(mailbox_hash, RefreshEventKind::Remove(env_hash));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many bytes before the last known offset of an mbox file are compared
+/// with the file to tell whether it was only appended to.
+const APPEND_CHECK_LEN: usize = 4096;
+
+/// Reads what was appended to the mbox file `file` since `known` was read
+/// from it, starting from the last known offset. Returns `None` if the file
+/// was changed in some other way, for example rewritten by another program.
+fn read_appended(file: &mut File, known: &[u8]) -> Result<Option<Vec<u8>>> {
+    let file_len = file.metadata()?.len() as usize;
+    if file_len < known.len() {
+        return Ok(None);
+    }
+    /* Compare the end of the known contents with the file, so that a rewrite
+     * is noticed without reading the whole file. */
+    let check_start = known.len().saturating_sub(APPEND_CHECK_LEN);
+    let mut check = vec![0; known.len() - check_start];
+    file.seek(SeekFrom::Start(check_start as u64))?;
+    file.read_exact(&mut check)?;
+    if check != known[check_start..] {
+        return Ok(None);
+    }
+    let mut appended = Vec::with_capacity(file_len - known.len());
+    file.read_to_end(&mut appended)?;
+    Ok(Some(appended))
+}
+
+impl MboxType {
+    fn writer(&self) -> MboxWriter {
+        MboxWriter {
+            account_hash: AccountHash::from_bytes(self.account_name.as_bytes()),
+            mailbox_index: self.mailbox_index.clone(),
+            mailboxes: self.mailboxes.clone(),
+            prefer_mbox_type: self.prefer_mbox_type,
+            write_locks: self.write_locks.clone(),
+            event_consumer: self.event_consumer.clone(),
+        }
+    }
+}
+
 impl MailBackend for MboxType {
     fn capabilities(&self) -> MailBackendCapabilities {
         const CAPABILITIES: MailBackendCapabilities = MailBackendCapabilities {
@@ -930,14 +1358,18 @@ impl MailBackend for MboxType {
                     }
                 }
                 if done {
-                    if payload.is_empty() {
-                        Ok(None)
-                    } else {
+                    /* Keep the contents even if the last batch is empty, writes and the
+                     * watcher compare them with the file. */
+                    if !self.contents.is_empty() {
                         let mut mailbox_lock = self.mailboxes.lock().unwrap();
-                        let contents = std::mem::replace(&mut self.contents, vec![]);
+                        let contents = std::mem::take(&mut self.contents);
                         mailbox_lock
                             .entry(self.mailbox_hash)
                             .and_modify(|f| f.content = contents);
+                    }
+                    if payload.is_empty() {
+                        Ok(None)
+                    } else {
                         Ok(Some(payload))
                     }
                 } else {
@@ -978,16 +1410,15 @@ impl MailBackend for MboxType {
         }))
     }
 
-    fn refresh(&mut self, _mailbox_hash: MailboxHash) -> ResultFuture<()> {
-        Err(Error::new(
-            "Refreshing is currently unimplemented for mbox backend.",
-        ))
+    fn refresh(&mut self, mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let writer = self.writer();
+        Ok(Box::pin(async move { writer.sync_appended(mailbox_hash) }))
     }
 
     fn watch(&self) -> ResultFuture<()> {
         let sender = self.event_consumer.clone();
         let (tx, rx) = channel();
-        let mut watcher = watcher(tx, std::time::Duration::from_secs(10))
+        let mut watcher = watcher(tx, std::time::Duration::from_secs(2))
             .map_err(|e| e.to_string())
             .map_err(Error::new)?;
         for f in self.mailboxes.lock().unwrap().values() {
@@ -999,9 +1430,10 @@ impl MailBackend for MboxType {
         }
         let account_hash = AccountHash::from_bytes(self.account_name.as_bytes());
         let mailboxes = self.mailboxes.clone();
-        let mailbox_index = self.mailbox_index.clone();
-        let prefer_mbox_type = self.prefer_mbox_type;
+        let writer = self.writer();
         Ok(Box::pin(async move {
+            // Move `watcher` in the closure's scope so that it doesn't get dropped.
+            let _watcher = watcher;
             loop {
                 match rx.recv() {
                     /*
@@ -1018,58 +1450,9 @@ impl MailBackend for MboxType {
                         /* Update */
                         DebouncedEvent::NoticeWrite(pathbuf) | DebouncedEvent::Write(pathbuf) => {
                             let mailbox_hash = MailboxHash(get_path_hash!(&pathbuf));
-                            let file = match std::fs::OpenOptions::new()
-                                .read(true)
-                                .write(true)
-                                .open(&pathbuf)
-                            {
-                                Ok(f) => f,
-                                Err(_) => {
-                                    continue;
-                                }
-                            };
-                            get_rw_lock_blocking(&file, &pathbuf)?;
-                            let mut mailbox_lock = mailboxes.lock().unwrap();
-                            let mut buf_reader = BufReader::new(file);
-                            let mut contents = Vec::new();
-                            if let Err(e) = buf_reader.read_to_end(&mut contents) {
-                                debug!(e);
-                                continue;
-                            };
-                            if contents.starts_with(mailbox_lock[&mailbox_hash].content.as_slice())
-                            {
-                                if let Ok((_, envelopes)) = mbox_parse(
-                                    mailbox_lock[&mailbox_hash].index.clone(),
-                                    &contents,
-                                    mailbox_lock[&mailbox_hash].content.len(),
-                                    prefer_mbox_type,
-                                ) {
-                                    let mut mailbox_index_lck = mailbox_index.lock().unwrap();
-                                    for env in envelopes {
-                                        mailbox_index_lck.insert(env.hash(), mailbox_hash);
-                                        (sender)(
-                                            account_hash,
-                                            BackendEvent::Refresh(RefreshEvent {
-                                                account_hash,
-                                                mailbox_hash,
-                                                kind: RefreshEventKind::Create(Box::new(env)),
-                                            }),
-                                        );
-                                    }
-                                }
-                            } else {
-                                (sender)(
-                                    account_hash,
-                                    BackendEvent::Refresh(RefreshEvent {
-                                        account_hash,
-                                        mailbox_hash,
-                                        kind: RefreshEventKind::Rescan,
-                                    }),
-                                );
+                            if let Err(err) = writer.sync_appended(mailbox_hash) {
+                                debug!(err);
                             }
-                            mailbox_lock
-                                .entry(mailbox_hash)
-                                .and_modify(|f| f.content = contents);
                         }
                         /* Remove */
                         DebouncedEvent::NoticeRemove(pathbuf) | DebouncedEvent::Remove(pathbuf) => {
@@ -1153,56 +1536,79 @@ impl MailBackend for MboxType {
             index[&env_hash]
         };
         let mailbox_path = mailboxes_lck[&mailbox_hash].fs_path.clone();
-        Ok(Box::new(MboxOp::new(
-            env_hash,
-            mailbox_path.as_path(),
-            offset,
-            length,
-        )))
+        let mut op = MboxOp::new(env_hash, mailbox_path.as_path(), offset, length);
+        op.format = self.prefer_mbox_type;
+        Ok(Box::new(op))
     }
 
     fn copy_messages(
         &mut self,
-        _env_hashes: EnvelopeHashBatch,
-        _source_mailbox_hash: MailboxHash,
-        _destination_mailbox_hash: MailboxHash,
-        _move_: bool,
+        env_hashes: EnvelopeHashBatch,
+        source_mailbox_hash: MailboxHash,
+        destination_mailbox_hash: MailboxHash,
+        move_: bool,
     ) -> ResultFuture<()> {
-        Err(Error::new(
-            "Copying messages is currently unimplemented for mbox backend",
-        ))
+        let writer = self.writer();
+        Ok(Box::pin(async move {
+            if source_mailbox_hash == destination_mailbox_hash && move_ {
+                return Ok(());
+            }
+            let messages = writer.read_messages(source_mailbox_hash, &env_hashes)?;
+            writer.append(destination_mailbox_hash, &messages)?;
+            if move_ {
+                writer.rewrite(source_mailbox_hash, &env_hashes, |_| {
+                    write::MessageChange::Delete
+                })?;
+            }
+            Ok(())
+        }))
     }
 
     fn set_flags(
         &mut self,
-        _env_hashes: EnvelopeHashBatch,
-        _mailbox_hash: MailboxHash,
-        _flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
     ) -> ResultFuture<()> {
-        Err(Error::new(
-            "Settings flags is currently unimplemented for mbox backend",
-        ))
+        if flags.iter().any(|(f, _)| f.is_err()) {
+            return Err(Error::new("The mbox backend doesn't support tags.")
+                .set_kind(ErrorKind::NotSupported));
+        }
+        let writer = self.writer();
+        Ok(Box::pin(async move {
+            writer.rewrite(mailbox_hash, &env_hashes, |message| {
+                let mut new_flags = write::flags_from_headers(message);
+                for (f, value) in flags.iter() {
+                    if let Ok(f) = f {
+                        new_flags.set(*f, *value);
+                    }
+                }
+                write::MessageChange::SetFlags(new_flags)
+            })
+        }))
     }
 
     fn delete_messages(
         &mut self,
-        _env_hashes: EnvelopeHashBatch,
-        _mailbox_hash: MailboxHash,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
     ) -> ResultFuture<()> {
-        Err(Error::new(
-            "Deleting messages is currently unimplemented for mbox backend",
-        ))
+        let writer = self.writer();
+        Ok(Box::pin(async move {
+            writer.rewrite(mailbox_hash, &env_hashes, |_| write::MessageChange::Delete)
+        }))
     }
 
     fn save(
         &self,
-        _bytes: Vec<u8>,
-        _mailbox_hash: MailboxHash,
-        _flags: Option<Flag>,
+        bytes: Vec<u8>,
+        mailbox_hash: MailboxHash,
+        flags: Option<Flag>,
     ) -> ResultFuture<()> {
-        Err(Error::new(
-            "Saving messages is currently unimplemented for mbox backend",
-        ))
+        let writer = self.writer();
+        Ok(Box::pin(async move {
+            writer.append(mailbox_hash, &[(bytes, flags.unwrap_or_else(Flag::empty))])
+        }))
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1319,6 +1725,7 @@ impl MboxType {
             )));
         }
         let prefer_mbox_type: String = get_conf_val!(s["prefer_mbox_type"], "auto".to_string())?;
+        let write_locks: String = get_conf_val!(s["write_locks"], "dotlock fcntl".to_string())?;
         let ret = MboxType {
             account_name: s.name.to_string(),
             event_consumer,
@@ -1336,6 +1743,7 @@ impl MboxType {
                     )))
                 }
             },
+            write_locks: MboxLock::parse_list(&write_locks)?,
             collection: Collection::default(),
             mailbox_index: Default::default(),
             mailboxes: Default::default(),
@@ -1474,6 +1882,47 @@ impl MboxType {
         let prefer_mbox_type: Result<String> =
             get_conf_val!(s["prefer_mbox_type"], "auto".to_string());
         prefer_mbox_type?;
+        let write_locks: String = get_conf_val!(s["write_locks"], "dotlock fcntl".to_string())?;
+        MboxLock::parse_list(&write_locks).map_err(|err| {
+            Error::new(format!(
+                "Configuration error ({}): Invalid value for field `write_locks`: {}",
+                s.name, err
+            ))
+        })?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mbox_read_appended() {
+        let path = std::env::temp_dir().join(format!("meli-mbox-append-{}", std::process::id()));
+        let first: &[u8] = b"From a@example.com Fri Jun 23 02:56:55 2000\nSubject: a\n\nfirst\n\n";
+        let second: &[u8] =
+            b"From b@example.com Fri Jun 23 02:57:55 2000\nSubject: b\n\nsecond\n\n";
+        std::fs::write(&path, first).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(read_appended(&mut file, first).unwrap(), Some(vec![]));
+        assert_eq!(read_appended(&mut file, b"").unwrap(), Some(first.to_vec()));
+
+        let mut contents = first.to_vec();
+        contents.extend_from_slice(second);
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(
+            read_appended(&mut file, first).unwrap(),
+            Some(second.to_vec())
+        );
+
+        /* Rewritten by another program, for example to change flags. */
+        let mut rewritten = contents.clone();
+        rewritten[first.len() - b"first\n\n".len()] = b'F';
+        std::fs::write(&path, &rewritten).unwrap();
+        assert_eq!(read_appended(&mut file, &contents).unwrap(), None);
+        std::fs::write(&path, second).unwrap();
+        assert_eq!(read_appended(&mut file, &contents).unwrap(), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
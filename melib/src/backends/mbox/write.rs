@@ -21,7 +21,52 @@
 
 use super::*;
 
+/// Change to a message of an mbox file, see [`rewrite`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageChange {
+    SetFlags(Flag),
+    Delete,
+}
+
 impl MboxFormat {
+    /// Whether `line` of a message body must be quoted with a `>` so that it
+    /// isn't mistaken for a `From ` line.
+    pub fn must_quote(&self, line: &[u8]) -> bool {
+        match self {
+            MboxFormat::MboxO | MboxFormat::MboxCl => line.starts_with(b"From "),
+            MboxFormat::MboxRd => {
+                let unquoted = line
+                    .iter()
+                    .position(|b| *b != b'>')
+                    .map(|p| &line[p..])
+                    .unwrap_or_default();
+                unquoted.starts_with(b"From ")
+            }
+            MboxFormat::MboxCl2 => false,
+        }
+    }
+
+    /// Removes the quoting of `From ` lines from the body of `message`. Only
+    /// mboxrd quoting can be undone, the other formats are returned as is.
+    pub fn unquote(&self, message: &[u8]) -> Vec<u8> {
+        if !matches!(self, MboxFormat::MboxRd) {
+            return message.to_vec();
+        }
+        let mut ret = Vec::with_capacity(message.len());
+        let mut in_body = false;
+        for line in message.split_inclusive(|b| *b == b'\n') {
+            if in_body && line.starts_with(b">") && self.must_quote(line) {
+                ret.extend_from_slice(&line[1..]);
+            } else {
+                ret.extend_from_slice(line);
+            }
+            if line == b"\n" || line == b"\r\n" {
+                in_body = true;
+            }
+        }
+        ret
+    }
+
     pub fn append(
         &self,
         writer: &mut dyn std::io::Write,
@@ -152,7 +197,33 @@ impl MboxFormat {
         };
 
         match self {
-            MboxFormat::MboxO | MboxFormat::MboxRd => Err(Error::new("Unimplemented.")),
+            MboxFormat::MboxO | MboxFormat::MboxRd => {
+                for (h, v) in headers {
+                    writer.write_all(h)?;
+                    writer.write_all(&b": "[..])?;
+                    write_header_val_fn(writer, v)?;
+                    writer.write_all(line_ending)?;
+                }
+                write_metadata_fn(writer)?;
+                writer.write_all(line_ending)?;
+                for line in body.split_inclusive(|b| *b == b'\n') {
+                    let (line, newline) = if let Some(line) = line.strip_suffix(b"\r\n") {
+                        (line, true)
+                    } else if let Some(line) = line.strip_suffix(b"\n") {
+                        (line, true)
+                    } else {
+                        (line, false)
+                    };
+                    if self.must_quote(line) {
+                        writer.write_all(&[b'>'])?;
+                    }
+                    writer.write_all(line)?;
+                    if newline {
+                        writer.write_all(line_ending)?;
+                    }
+                }
+                Ok(())
+            }
             MboxFormat::MboxCl => {
                 let len = (body_len
                     + body
@@ -258,3 +329,252 @@ impl MboxFormat {
         }
     }
 }
+
+/// Returns the flags stored in the `Status` and `X-Status` headers of
+/// `message`.
+pub fn flags_from_headers(message: &[u8]) -> Flag {
+    let mut flags = Flag::empty();
+    for line in message.split_inclusive(|b| *b == b'\n') {
+        if line.trim().is_empty() {
+            break;
+        }
+        let value = if line.len() > b"Status:".len()
+            && line[..b"Status:".len()].eq_ignore_ascii_case(b"Status:")
+        {
+            &line[b"Status:".len()..]
+        } else if line.len() > b"X-Status:".len()
+            && line[..b"X-Status:".len()].eq_ignore_ascii_case(b"X-Status:")
+        {
+            &line[b"X-Status:".len()..]
+        } else {
+            continue;
+        };
+        for (c, flag) in [
+            (b'F', Flag::FLAGGED),
+            (b'A', Flag::REPLIED),
+            (b'R', Flag::SEEN),
+            (b'D', Flag::TRASHED),
+            (b'T', Flag::DRAFT),
+        ] {
+            if value.contains(&c) {
+                flags.set(flag, true);
+            }
+        }
+    }
+    flags
+}
+
+/// Replaces the `Status` and `X-Status` headers of `message` with ones that
+/// store `flags`. The rest of the message is left as is.
+pub fn set_flag_headers(message: &[u8], flags: Flag) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(message.len() + 32);
+    let mut lines = message.split_inclusive(|b| *b == b'\n');
+    let mut removing = false;
+    let mut line_ending: &[u8] = b"\n";
+    let mut rest = None;
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            rest = Some(line);
+            break;
+        }
+        if line.ends_with(b"\r\n") {
+            line_ending = b"\r\n";
+        }
+        if removing && (line.starts_with(b" ") || line.starts_with(b"\t")) {
+            continue;
+        }
+        removing = [&b"Status:"[..], &b"X-Status:"[..]]
+            .iter()
+            .any(|h| line.len() > h.len() && line[..h.len()].eq_ignore_ascii_case(h));
+        if !removing {
+            ret.extend_from_slice(line);
+        }
+    }
+    if flags.is_seen() {
+        ret.extend_from_slice(b"Status: R");
+        ret.extend_from_slice(line_ending);
+    }
+    if flags.is_flagged() || flags.is_replied() || flags.is_draft() || flags.is_trashed() {
+        ret.extend_from_slice(b"X-Status: ");
+        for (c, flag) in [
+            (b'F', Flag::FLAGGED),
+            (b'A', Flag::REPLIED),
+            (b'T', Flag::DRAFT),
+            (b'D', Flag::TRASHED),
+        ] {
+            if flags.contains(flag) {
+                ret.push(c);
+            }
+        }
+        ret.extend_from_slice(line_ending);
+    }
+    if let Some(rest) = rest {
+        ret.extend_from_slice(rest);
+    }
+    for line in lines {
+        ret.extend_from_slice(line);
+    }
+    ret
+}
+
+/// Applies `changes` to the messages of the mbox file `contents`, whose
+/// positions are in `index`. Only the part of the file from the first changed
+/// message onwards has to be rewritten: returns its offset and new bytes, and
+/// updates `index` with the new positions. Returns `None` if none of the
+/// messages in `changes` are in `index`.
+pub fn rewrite(
+    contents: &[u8],
+    index: &mut HashMap<EnvelopeHash, (Offset, Length)>,
+    changes: &HashMap<EnvelopeHash, MessageChange>,
+) -> Option<(Offset, Vec<u8>)> {
+    let mut entries = index
+        .iter()
+        .map(|(hash, (offset, length))| (*hash, *offset, *length))
+        .filter(|(_, offset, length)| offset + length <= contents.len())
+        .collect::<Vec<(EnvelopeHash, Offset, Length)>>();
+    entries.sort_by_key(|(_, offset, _)| *offset);
+    /* Messages are indexed after their `From ` line. */
+    let from_line_start = |offset: Offset| -> Offset {
+        contents[..offset.saturating_sub(1)]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map(|p| p + 1)
+            .unwrap_or(0)
+    };
+    let start = entries
+        .iter()
+        .find_map(|(hash, offset, _)| match changes.get(hash)? {
+            MessageChange::SetFlags(_) => Some(*offset),
+            MessageChange::Delete => Some(from_line_start(*offset)),
+        })?;
+    let mut tail = Vec::with_capacity(contents.len() - start);
+    let mut pos = start;
+    for (hash, offset, length) in entries {
+        if offset < start {
+            continue;
+        }
+        match changes.get(&hash) {
+            None => {
+                index.insert(hash, (start + tail.len() + offset - pos, length));
+            }
+            Some(MessageChange::SetFlags(flags)) => {
+                tail.extend_from_slice(&contents[pos..offset]);
+                let message = set_flag_headers(&contents[offset..offset + length], *flags);
+                index.insert(hash, (start + tail.len(), message.len()));
+                tail.extend_from_slice(&message);
+                pos = offset + length;
+            }
+            Some(MessageChange::Delete) => {
+                tail.extend_from_slice(&contents[pos..from_line_start(offset).max(pos)]);
+                index.remove(&hash);
+                pos = offset + length;
+            }
+        }
+    }
+    tail.extend_from_slice(&contents[pos..]);
+    Some((start, tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mbox_quoting() {
+        let message: &[u8] = b"Subject: test\n\nFrom here\n>From there\nnot From\n";
+        let mut out = vec![];
+        MboxFormat::MboxRd
+            .append(
+                &mut out,
+                message,
+                None,
+                Some(0),
+                (Flag::SEEN, vec![]),
+                MboxMetadata::CClient,
+                true,
+                false,
+            )
+            .unwrap();
+        let (from_line, written) = out.split_at(out.find(b"\n").unwrap() + 1);
+        assert!(from_line.starts_with(b"From MAILER-DAEMON "));
+        assert_eq!(
+            written,
+            &b"Subject: test\nStatus: R\n\n>From here\n>>From there\nnot From\n"[..]
+        );
+        assert_eq!(
+            MboxFormat::MboxRd.unquote(written),
+            b"Subject: test\nStatus: R\n\nFrom here\n>From there\nnot From\n".to_vec()
+        );
+        assert!(MboxFormat::MboxO.must_quote(b"From here"));
+        assert!(!MboxFormat::MboxO.must_quote(b">From here"));
+        assert!(!MboxFormat::MboxCl2.must_quote(b"From here"));
+    }
+
+    #[test]
+    fn test_mbox_rewrite() {
+        let contents: &[u8] = b"From a Thu Jan  1 00:00:00 1970\nSubject: 1\n\none\n\n\
+From b Thu Jan  1 00:00:00 1970\nSubject: 2\nStatus: R\n\ntwo\n\n\
+From c Thu Jan  1 00:00:00 1970\nSubject: 3\n\nthree\n";
+        let index = Arc::new(Mutex::new(HashMap::default()));
+        let envelopes = MessageIterator {
+            index: index.clone(),
+            input: contents,
+            offset: 0,
+            file_offset: 0,
+            format: Some(MboxFormat::MboxRd),
+        }
+        .collect::<Result<Vec<Envelope>>>()
+        .unwrap();
+        assert_eq!(envelopes.len(), 3);
+        let mut index = index.lock().unwrap().clone();
+        let (one, two, three) = (
+            envelopes[0].hash(),
+            envelopes[1].hash(),
+            envelopes[2].hash(),
+        );
+        fn message(
+            index: &HashMap<EnvelopeHash, (Offset, Length)>,
+            contents: &[u8],
+            hash: EnvelopeHash,
+        ) -> Vec<u8> {
+            let (offset, length) = index[&hash];
+            contents[offset..offset + length].to_vec()
+        }
+        assert_eq!(
+            flags_from_headers(&message(&index, contents, two)),
+            Flag::SEEN
+        );
+
+        let changes = [
+            (two, MessageChange::SetFlags(Flag::FLAGGED | Flag::REPLIED)),
+            (three, MessageChange::Delete),
+        ]
+        .iter()
+        .copied()
+        .collect();
+        let one_position = index[&one];
+        let (start, tail) = rewrite(contents, &mut index, &changes).unwrap();
+        let new_contents = [&contents[..start], tail.as_slice()].concat();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[&one], one_position);
+        assert!(new_contents.ends_with(b"two\n\n"));
+        assert_eq!(
+            message(&index, &new_contents, two),
+            b"Subject: 2\nX-Status: FA\n\ntwo\n\n".to_vec()
+        );
+        assert_eq!(
+            flags_from_headers(&message(&index, &new_contents, two)),
+            Flag::FLAGGED | Flag::REPLIED
+        );
+
+        let changes = [(one, MessageChange::Delete)].iter().copied().collect();
+        let (start, tail) = rewrite(&new_contents, &mut index, &changes).unwrap();
+        assert_eq!(start, 0);
+        assert!(tail.starts_with(b"From b "));
+        assert_eq!(
+            message(&index, &tail, two),
+            b"Subject: 2\nX-Status: FA\n\ntwo\n\n".to_vec()
+        );
+        assert!(rewrite(&tail, &mut index, &changes).is_none());
+    }
+}
@@ -37,6 +37,8 @@ pub use mailbox::*;
 mod operations;
 pub use operations::*;
 mod connection;
+mod newsrc;
+pub use newsrc::Newsrc;
 use std::{
     collections::{hash_map::DefaultHasher, BTreeSet, HashMap, HashSet},
     hash::Hasher,
@@ -52,10 +54,10 @@ use futures::{lock::Mutex as FutureMutex, stream::Stream};
 use crate::{
     backends::*,
     conf::AccountSettings,
-    connections::timeout,
+    connections::{proxy::Proxy, timeout, tls::TlsOptions},
     email::*,
     error::{Error, Result, ResultIntoError},
-    Collection,
+    log, Collection,
 };
 pub type UID = usize;
 
@@ -110,7 +112,9 @@ pub struct NntpServerConf {
     pub use_starttls: bool,
     pub use_tls: bool,
     pub require_auth: bool,
-    pub danger_accept_invalid_certs: bool,
+    pub tls: TlsOptions,
+    /// Connect through this proxy.
+    pub proxy: Option<Proxy>,
     pub extension_use: NntpExtensionUse,
 }
 
@@ -124,6 +128,7 @@ pub struct UIDStore {
     message_id_index: Arc<Mutex<HashMap<String, EnvelopeHash>>>,
     hash_index: Arc<Mutex<HashMap<EnvelopeHash, (UID, MailboxHash)>>>,
     uid_index: Arc<Mutex<HashMap<(MailboxHash, UID), EnvelopeHash>>>,
+    newsrc: Arc<Mutex<Newsrc>>,
 
     collection: Collection,
     mailboxes: Arc<FutureMutex<HashMap<MailboxHash, NntpMailbox>>>,
@@ -145,6 +150,7 @@ impl UIDStore {
             message_id_index: Default::default(),
             hash_index: Default::default(),
             uid_index: Default::default(),
+            newsrc: Default::default(),
             mailboxes: Arc::new(FutureMutex::new(Default::default())),
             collection: Collection::new(),
             is_online: Arc::new(Mutex::new((
@@ -308,6 +314,7 @@ impl MailBackend for NntpType {
                         return Ok(());
                     }
                     let mut env_hash_set: BTreeSet<EnvelopeHash> = Default::default();
+                    let mut unseen_hash_set: BTreeSet<EnvelopeHash> = Default::default();
                     for msg_id in message_ids {
                         conn.send_command(format!("OVER {}", msg_id).as_bytes())
                             .await?;
@@ -315,9 +322,15 @@ impl MailBackend for NntpType {
                         let mut message_id_lck = uid_store.message_id_index.lock().unwrap();
                         let mut hash_index_lck = uid_store.hash_index.lock().unwrap();
                         let mut uid_index_lck = uid_store.uid_index.lock().unwrap();
+                        let newsrc_lck = uid_store.newsrc.lock().unwrap();
                         for l in res.split_rn().skip(1) {
-                            let (_, (num, env)) = protocol_parser::over_article(l)?;
+                            let (_, (num, mut env)) = protocol_parser::over_article(l)?;
                             env_hash_set.insert(env.hash());
+                            if newsrc_lck.is_read(&mbox.nntp_path, num) {
+                                env.set_seen();
+                            } else {
+                                unseen_hash_set.insert(env.hash());
+                            }
                             message_id_lck.insert(env.message_id_display().to_string(), env.hash());
                             hash_index_lck.insert(env.hash(), (num, mailbox_hash));
                             uid_index_lck.insert((mailbox_hash, num), env.hash());
@@ -335,11 +348,11 @@ impl MailBackend for NntpType {
                     {
                         let f = &uid_store.mailboxes.lock().await[&mailbox_hash];
                         *f.latest_article.lock().unwrap() = Some(latest_article);
-                        f.exists
+                        f.exists.lock().unwrap().insert_existing_set(env_hash_set);
+                        f.unseen
                             .lock()
                             .unwrap()
-                            .insert_existing_set(env_hash_set.clone());
-                        f.unseen.lock().unwrap().insert_existing_set(env_hash_set);
+                            .insert_existing_set(unseen_hash_set);
                     }
                     return Ok(());
                 }
@@ -429,11 +442,50 @@ impl MailBackend for NntpType {
 
     fn set_flags(
         &mut self,
-        _env_hashes: EnvelopeHashBatch,
-        _mailbox_hash: MailboxHash,
-        _flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
     ) -> ResultFuture<()> {
-        Err(Error::new("NNTP doesn't support flags."))
+        if flags
+            .iter()
+            .any(|(f, _)| !matches!(f, Ok(f) if *f == Flag::SEEN))
+        {
+            return Err(Error::new("NNTP only supports the seen flag."));
+        }
+        let uid_store = self.uid_store.clone();
+        Ok(Box::pin(async move {
+            let (group, unseen) = uid_store
+                .mailboxes
+                .lock()
+                .await
+                .get(&mailbox_hash)
+                .map(|m| (m.nntp_path().to_string(), m.unseen.clone()))
+                .ok_or_else(|| {
+                    Error::new(format!(
+                        "Mailbox with hash {} not found in NNTP connection, this could possibly \
+                         be a bug or it was deleted.",
+                        mailbox_hash
+                    ))
+                })?;
+            let mut newsrc_lck = uid_store.newsrc.lock().unwrap();
+            let hash_index_lck = uid_store.hash_index.lock().unwrap();
+            let mut unseen_lck = unseen.lock().unwrap();
+            for env_hash in env_hashes.iter() {
+                let num = match hash_index_lck.get(&env_hash) {
+                    Some((num, h)) if *h == mailbox_hash => *num,
+                    _ => continue,
+                };
+                for (_, value) in flags.iter() {
+                    newsrc_lck.set_read(&group, num, *value);
+                    if *value {
+                        unseen_lck.remove(env_hash);
+                    } else {
+                        unseen_lck.insert_new(env_hash);
+                    }
+                }
+            }
+            newsrc_lck.save()
+        }))
     }
 
     fn delete_messages(
@@ -581,8 +633,8 @@ impl NntpType {
         let server_port = get_conf_val!(s["server_port"], 119)?;
         let use_tls = get_conf_val!(s["use_tls"], server_port == 563)?;
         let use_starttls = use_tls && get_conf_val!(s["use_starttls"], false)?;
-        let danger_accept_invalid_certs: bool =
-            get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        let proxy = Proxy::new(s)?;
+        let tls = TlsOptions::new(s)?;
         let require_auth = get_conf_val!(s["require_auth"], false)?;
         let server_conf = NntpServerConf {
             server_hostname: server_hostname.to_string(),
@@ -600,7 +652,8 @@ impl NntpType {
             server_port,
             use_tls,
             use_starttls,
-            danger_accept_invalid_certs,
+            proxy,
+            tls,
             extension_use: NntpExtensionUse {
                 #[cfg(feature = "deflate_compression")]
                 deflate: get_conf_val!(s["use_deflate"], false)?,
@@ -609,7 +662,12 @@ impl NntpType {
         let account_hash = AccountHash::from_bytes(s.name.as_bytes());
         let account_name = Arc::new(s.name.to_string());
         let mut mailboxes = HashMap::default();
-        for (k, _f) in s.mailboxes.iter() {
+        /* Entries with a `query` are virtual mailboxes, not newsgroups. */
+        for (k, _f) in s
+            .mailboxes
+            .iter()
+            .filter(|(_, f)| !f.extra.contains_key("query"))
+        {
             let mailbox_hash = MailboxHash(get_path_hash!(&k));
             mailboxes.insert(
                 mailbox_hash,
@@ -630,8 +688,17 @@ impl NntpType {
                 account_name
             )));
         }
+        let newsrc = Newsrc::new(&s.name).unwrap_or_else(|err| {
+            log::warn!(
+                "{}: could not load read state of articles, it will not be saved: {}",
+                s.name,
+                err
+            );
+            Newsrc::default()
+        });
         let uid_store: Arc<UIDStore> = Arc::new(UIDStore {
             mailboxes: Arc::new(FutureMutex::new(mailboxes)),
+            newsrc: Arc::new(Mutex::new(newsrc)),
             ..UIDStore::new(account_hash, account_name, event_consumer)
         });
         let connection = NntpConnection::new_connection(&server_conf, uid_store.clone());
@@ -750,7 +817,12 @@ impl NntpType {
                 s.name.as_str(),
             )));
         }
-        get_conf_val!(s["danger_accept_invalid_certs"], false)?;
+        TlsOptions::new(s)?;
+        for key in TlsOptions::KEYS {
+            s.extra.remove(*key);
+        }
+        Proxy::new(s)?;
+        s.extra.remove("proxy");
         let extra_keys = s
             .extra
             .keys()
@@ -849,12 +921,19 @@ impl FetchState {
         //hash_index: Arc<Mutex<HashMap<EnvelopeHash, (UID, MailboxHash)>>>,
         //uid_index: Arc<Mutex<HashMap<(MailboxHash, UID), EnvelopeHash>>>,
         let mut latest_article: Option<crate::UnixTimestamp> = None;
+        let group = uid_store.mailboxes.lock().await[&mailbox_hash]
+            .nntp_path()
+            .to_string();
         {
             let mut message_id_lck = uid_store.message_id_index.lock().unwrap();
             let mut hash_index_lck = uid_store.hash_index.lock().unwrap();
             let mut uid_index_lck = uid_store.uid_index.lock().unwrap();
+            let newsrc_lck = uid_store.newsrc.lock().unwrap();
             for l in res.split_rn().skip(1) {
-                let (_, (num, env)) = protocol_parser::over_article(l)?;
+                let (_, (num, mut env)) = protocol_parser::over_article(l)?;
+                if newsrc_lck.is_read(&group, num) {
+                    env.set_seen();
+                }
                 message_id_lck.insert(env.message_id_display().to_string(), env.hash());
                 hash_index_lck.insert(env.hash(), (num, mailbox_hash));
                 uid_index_lck.insert((mailbox_hash, num), env.hash());
@@ -868,13 +947,15 @@ impl FetchState {
         }
         {
             let hash_set: BTreeSet<EnvelopeHash> = ret.iter().map(|env| env.hash()).collect();
+            let unseen_set: BTreeSet<EnvelopeHash> = ret
+                .iter()
+                .filter(|env| !env.is_seen())
+                .map(|env| env.hash())
+                .collect();
             let f = &uid_store.mailboxes.lock().await[&mailbox_hash];
             *f.latest_article.lock().unwrap() = latest_article;
-            f.exists
-                .lock()
-                .unwrap()
-                .insert_existing_set(hash_set.clone());
-            f.unseen.lock().unwrap().insert_existing_set(hash_set);
+            f.exists.lock().unwrap().insert_existing_set(hash_set);
+            f.unseen.lock().unwrap().insert_existing_set(unseen_set);
         };
         Ok(Some(ret))
     }
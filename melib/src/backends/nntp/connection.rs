@@ -21,7 +21,7 @@
 
 use crate::{
     backends::{BackendMailbox, MailboxHash},
-    connections::{lookup_ipv4, Connection},
+    connections::{connect_tcp, Connection},
     email::parser::BytesExt,
     error::*,
     log,
@@ -30,7 +30,6 @@ extern crate native_tls;
 use std::{collections::HashSet, future::Future, pin::Pin, sync::Arc, time::Instant};
 
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 pub use smol::Async as AsyncWrapper;
 
 use super::{Capabilities, NntpServerConf, UIDStore};
@@ -85,16 +84,14 @@ impl NntpStream {
     pub async fn new_connection(
         server_conf: &NntpServerConf,
     ) -> Result<(Capabilities, NntpStream)> {
-        use std::net::TcpStream;
         let path = &server_conf.server_hostname;
 
-        let stream = {
-            let addr = lookup_ipv4(path, server_conf.server_port)?;
-            AsyncWrapper::new(Connection::Tcp(TcpStream::connect_timeout(
-                &addr,
-                std::time::Duration::new(16, 0),
-            )?))?
-        };
+        let stream = AsyncWrapper::new(Connection::Tcp(connect_tcp(
+            path,
+            server_conf.server_port,
+            server_conf.proxy.as_ref(),
+            Some(std::time::Duration::new(16, 0)),
+        )?))?;
         let mut res = String::with_capacity(8 * 1024);
         let mut ret = NntpStream {
             stream,
@@ -104,11 +101,7 @@ impl NntpStream {
         };
 
         if server_conf.use_tls {
-            let mut connector = TlsConnector::builder();
-            if server_conf.danger_accept_invalid_certs {
-                connector.danger_accept_invalid_certs(true);
-            }
-            let connector = connector.build()?;
+            let connector = server_conf.tls.connector()?;
 
             if server_conf.use_starttls {
                 ret.read_response(&mut res, false, &["200 ", "201 "])
@@ -181,8 +174,10 @@ impl NntpStream {
                         }
                     }
                 }
+                let tls_stream = conn_result?;
+                server_conf.tls.verify_peer(path, &tls_stream)?;
                 ret.stream =
-                    AsyncWrapper::new(Connection::Tls(conn_result?)).chain_err_summary(|| {
+                    AsyncWrapper::new(Connection::Tls(tls_stream)).chain_err_summary(|| {
                         format!("Could not initiate TLS negotiation to {}.", path)
                     })?;
             }
@@ -79,7 +79,10 @@ impl BackendMailbox for NntpMailbox {
     }
 
     fn permissions(&self) -> MailboxPermissions {
-        MailboxPermissions::default()
+        MailboxPermissions {
+            set_flags: true,
+            ..MailboxPermissions::default()
+        }
     }
 
     fn is_subscribed(&self) -> bool {
@@ -0,0 +1,240 @@
+/*
+ * meli - nntp module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Read state of articles.
+//!
+//! NNTP has no flags, so the numbers of read articles are kept in a
+//! `.newsrc` style file in the data directory of the account. Every line
+//! lists the read articles of a newsgroup as ranges, e.g.
+//! `comp.lang.rust: 1-1204,1210`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    fs,
+    path::PathBuf,
+};
+
+use super::UID;
+use crate::error::{Error, Result};
+
+/// Read articles of a newsgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// Whether the group line ends with `:` (subscribed) or `!`
+    /// (unsubscribed).
+    pub subscribed: bool,
+    /// Disjoint, non-adjacent ranges of read articles, keyed by their first
+    /// article and mapped to their last one.
+    ranges: BTreeMap<UID, UID>,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group {
+            subscribed: true,
+            ranges: BTreeMap::default(),
+        }
+    }
+}
+
+impl Group {
+    pub fn contains(&self, num: UID) -> bool {
+        self.ranges
+            .range(..=num)
+            .next_back()
+            .map(|(_, end)| num <= *end)
+            .unwrap_or(false)
+    }
+
+    /// Marks `start..=end` as read, merging it with the ranges it overlaps or
+    /// touches.
+    pub fn insert_range(&mut self, mut start: UID, mut end: UID) {
+        if let Some((&prev_start, &prev_end)) = self.ranges.range(..=start).next_back() {
+            if prev_end.saturating_add(1) >= start {
+                start = prev_start;
+                end = end.max(prev_end);
+            }
+        }
+        while let Some((&next_start, &next_end)) = self.ranges.range(start..).next() {
+            if next_start > end.saturating_add(1) {
+                break;
+            }
+            self.ranges.remove(&next_start);
+            end = end.max(next_end);
+        }
+        self.ranges.insert(start, end);
+    }
+
+    pub fn insert(&mut self, num: UID) {
+        self.insert_range(num, num);
+    }
+
+    pub fn remove(&mut self, num: UID) {
+        let (start, end) = match self.ranges.range(..=num).next_back() {
+            Some((&start, &end)) if num <= end => (start, end),
+            _ => return,
+        };
+        self.ranges.remove(&start);
+        if start < num {
+            self.ranges.insert(start, num - 1);
+        }
+        if num < end {
+            self.ranges.insert(num + 1, end);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Newsrc {
+    /// File the read state is saved in, if any.
+    path: Option<PathBuf>,
+    groups: BTreeMap<String, Group>,
+}
+
+impl Newsrc {
+    /// Loads the read state of `account_name` from its data directory.
+    pub fn new(account_name: &str) -> Result<Self> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", account_name)
+            .map_err(|err| Error::new(err.to_string()))?;
+        let path = data_dir.place_data_file("newsrc")?;
+        let groups = if path.exists() {
+            Self::parse(&fs::read_to_string(&path)?)
+        } else {
+            BTreeMap::default()
+        };
+        Ok(Newsrc {
+            path: Some(path),
+            groups,
+        })
+    }
+
+    /// Parses `.newsrc` lines. Malformed ranges are skipped.
+    pub fn parse(s: &str) -> BTreeMap<String, Group> {
+        let mut ret: BTreeMap<String, Group> = BTreeMap::default();
+        for line in s.lines() {
+            /* Unsubscribed groups end with `!` instead of `:`. */
+            let pos = match line.find([':', '!']) {
+                Some(pos) if !line[..pos].trim().is_empty() => pos,
+                _ => continue,
+            };
+            let group = ret.entry(line[..pos].trim().to_string()).or_default();
+            group.subscribed = line.as_bytes()[pos] == b':';
+            for range in line[pos + 1..]
+                .split(',')
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+            {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                if let (Ok(start), Ok(end)) =
+                    (start.trim().parse::<UID>(), end.trim().parse::<UID>())
+                {
+                    if start <= end {
+                        group.insert_range(start, end);
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    pub fn is_read(&self, group: &str, num: UID) -> bool {
+        self.groups
+            .get(group)
+            .map(|group| group.contains(num))
+            .unwrap_or(false)
+    }
+
+    pub fn set_read(&mut self, group: &str, num: UID, value: bool) {
+        if value {
+            self.groups
+                .entry(group.to_string())
+                .or_default()
+                .insert(num);
+        } else if let Some(group) = self.groups.get_mut(group) {
+            group.remove(num);
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            fs::write(path, self.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Newsrc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, group) in self.groups.iter() {
+            let mut line = format!("{}{}", name, if group.subscribed { ':' } else { '!' });
+            for (i, (start, end)) in group.ranges.iter().enumerate() {
+                line.push(if i == 0 { ' ' } else { ',' });
+                if start == end {
+                    write!(line, "{}", start)?;
+                } else {
+                    write!(line, "{}-{}", start, end)?;
+                }
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nntp_newsrc() {
+        let mut newsrc = Newsrc {
+            path: None,
+            groups: Newsrc::parse(
+                "comp.lang.rust: 1-4,7, 9-9\nalt.test! 2\nmalformed\nsic.all: x-3,5-2\n",
+            ),
+        };
+        assert!(newsrc.is_read("comp.lang.rust", 3));
+        assert!(newsrc.is_read("comp.lang.rust", 9));
+        assert!(!newsrc.is_read("comp.lang.rust", 5));
+        assert!(newsrc.is_read("alt.test", 2));
+        assert!(!newsrc.is_read("sic.all", 3));
+        newsrc.set_read("comp.lang.rust", 5, true);
+        newsrc.set_read("comp.lang.rust", 7, false);
+        newsrc.set_read("sic.all", 1, true);
+        assert_eq!(
+            newsrc.to_string(),
+            "alt.test! 2\ncomp.lang.rust: 1-5,9\nsic.all: 1\n"
+        );
+        assert_eq!(Newsrc::parse(&newsrc.to_string()), newsrc.groups);
+
+        /* Huge ranges are kept as ranges. */
+        let mut newsrc = Newsrc {
+            path: None,
+            groups: Newsrc::parse("huge: 1-4000000000\n"),
+        };
+        assert!(newsrc.is_read("huge", 3_999_999_999));
+        newsrc.set_read("huge", 2, false);
+        newsrc.set_read("huge", 4_000_000_001, true);
+        assert!(!newsrc.is_read("huge", 2));
+        assert_eq!(newsrc.to_string(), "huge: 1,3-4000000001\n");
+    }
+}
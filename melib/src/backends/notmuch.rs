@@ -135,6 +135,7 @@ impl DbConnection {
                 for (&mailbox_hash, m) in mailboxes_lck.iter() {
                     let query_str = format!("{} id:{}", m.query_str.as_str(), &message_id);
                     let query: Query = Query::new(self, &query_str)?;
+                    query.set_mailbox_options(m)?;
                     if query.count().unwrap_or(0) > 0 {
                         let mut total_lck = m.total.lock().unwrap();
                         let mut unseen_lck = m.unseen.lock().unwrap();
@@ -228,11 +229,53 @@ pub struct NotmuchDb {
     account_hash: AccountHash,
     event_consumer: BackendEventConsumer,
     save_messages_to: Option<PathBuf>,
+    new_command: Option<Arc<NewCommand>>,
 }
 
 unsafe impl Send for NotmuchDb {}
 unsafe impl Sync for NotmuchDb {}
 
+/// The `new_command` account setting: a command such as `notmuch new` that
+/// indexes new files into the database.
+#[derive(Debug)]
+struct NewCommand {
+    command: String,
+    last_run: Mutex<Option<std::time::Instant>>,
+}
+
+impl NewCommand {
+    /// Runs that close together are coalesced: refreshing every mailbox of
+    /// the account should index new files only once.
+    const COALESCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+    fn new(command: String) -> Self {
+        Self {
+            command,
+            last_run: Mutex::new(None),
+        }
+    }
+
+    fn run(&self) -> Result<()> {
+        let mut last_run = self.last_run.lock()?;
+        if matches!(*last_run, Some(t) if t.elapsed() < Self::COALESCE_INTERVAL) {
+            return Ok(());
+        }
+        let output = std::process::Command::new("sh")
+            .args(["-c", &self.command])
+            .stdin(std::process::Stdio::null())
+            .output()?;
+        *last_run = Some(std::time::Instant::now());
+        if !output.status.success() {
+            return Err(Error::new(format!(
+                "notmuch `new_command` `{}` exited with {}.",
+                self.command, output.status
+            ))
+            .set_details(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 struct NotmuchMailbox {
     hash: MailboxHash,
@@ -241,6 +284,10 @@ struct NotmuchMailbox {
     name: String,
     path: String,
     query_str: String,
+    /// Tags whose messages are left out of the mailbox unless the query
+    /// mentions them, like notmuch's `search.exclude_tags`.
+    exclude_tags: Vec<String>,
+    search_order: notmuch_sort_t,
     usage: Arc<RwLock<SpecialUsageMailbox>>,
 
     total: Arc<Mutex<usize>>,
@@ -301,6 +348,71 @@ impl BackendMailbox for NotmuchMailbox {
 unsafe impl Send for NotmuchMailbox {}
 unsafe impl Sync for NotmuchMailbox {}
 
+/// Splits a tag list in notmuch's configuration syntax, e.g.
+/// `"deleted;spam;"`.
+fn parse_tag_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a `search_order` setting value, named like the `--sort` values of
+/// the notmuch CLI.
+fn parse_search_order(value: &str) -> Result<notmuch_sort_t> {
+    match value {
+        "newest-first" => Ok(notmuch_sort_t_NOTMUCH_SORT_NEWEST_FIRST),
+        "oldest-first" => Ok(notmuch_sort_t_NOTMUCH_SORT_OLDEST_FIRST),
+        other => Err(Error::new(format!(
+            "Notmuch `search_order` setting value `{}` is invalid, expected `newest-first` or \
+             `oldest-first`.",
+            other
+        ))
+        .set_kind(ErrorKind::Configuration)),
+    }
+}
+
+/// Reads `search.exclude_tags` from the notmuch configuration file, looked up
+/// the same way the notmuch CLI does.
+fn notmuch_config_exclude_tags() -> Option<Vec<String>> {
+    let profile = std::env::var("NOTMUCH_PROFILE").ok();
+    let config_path = if let Ok(path) = std::env::var("NOTMUCH_CONFIG") {
+        PathBuf::from(path)
+    } else {
+        let xdg_path = xdg::BaseDirectories::with_prefix("notmuch")
+            .ok()
+            .and_then(|dirs| {
+                dirs.find_config_file(
+                    Path::new(profile.as_deref().unwrap_or("default")).join("config"),
+                )
+            });
+        match xdg_path {
+            Some(path) => path,
+            None => Path::new(&match profile {
+                Some(profile) => format!("~/.notmuch-config.{}", profile),
+                None => "~/.notmuch-config".to_string(),
+            })
+            .expand(),
+        }
+    };
+    let contents = std::fs::read_to_string(config_path).ok()?;
+    let mut in_search_section = false;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_search_section = line == "[search]";
+        } else if in_search_section {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "exclude_tags" {
+                    return Some(parse_tag_list(value));
+                }
+            }
+        }
+    }
+    None
+}
+
 impl NotmuchDb {
     pub fn new(
         s: &AccountSettings,
@@ -355,7 +467,17 @@ impl NotmuchDb {
             ))
             .set_kind(ErrorKind::Configuration));
         }
+        let new_command = s
+            .extra
+            .get("new_command")
+            .map(|c| Arc::new(NewCommand::new(c.to_string())));
         path.push(".notmuch");
+        if !path.is_dir() {
+            /* Let `new_command` create and populate the database. */
+            if let Some(ref new_command) = new_command {
+                new_command.run()?;
+            }
+        }
         if !path.exists() || !path.is_dir() {
             return Err(Error::new(format!(
                 "Notmuch `root_mailbox` {} for account {} does not contain a `.notmuch` \
@@ -367,6 +489,16 @@ impl NotmuchDb {
         }
         path.pop();
 
+        let exclude_tags = match s.extra.get("exclude_tags") {
+            Some(value) => parse_tag_list(value),
+            None => notmuch_config_exclude_tags().unwrap_or_default(),
+        };
+        let search_order = s
+            .extra
+            .get("search_order")
+            .map(|v| parse_search_order(v))
+            .transpose()?
+            .unwrap_or(notmuch_sort_t_NOTMUCH_SORT_NEWEST_FIRST);
         let mut mailboxes = HashMap::with_capacity(s.mailboxes.len());
         let mut parents: Vec<(MailboxHash, &str)> = Vec::with_capacity(s.mailboxes.len());
         for (k, f) in s.mailboxes.iter() {
@@ -384,6 +516,17 @@ impl NotmuchDb {
                         children: vec![],
                         parent: None,
                         query_str: query_str.to_string(),
+                        exclude_tags: f
+                            .extra
+                            .get("exclude_tags")
+                            .map(|v| parse_tag_list(v))
+                            .unwrap_or_else(|| exclude_tags.clone()),
+                        search_order: f
+                            .extra
+                            .get("search_order")
+                            .map(|v| parse_search_order(v))
+                            .transpose()?
+                            .unwrap_or(search_order),
                         usage: Arc::new(RwLock::new(SpecialUsageMailbox::Normal)),
                         total: Arc::new(Mutex::new(0)),
                         unseen: Arc::new(Mutex::new(0)),
@@ -435,6 +578,7 @@ impl NotmuchDb {
             _account_name: Arc::new(s.name.to_string()),
             account_hash,
             event_consumer,
+            new_command,
         }))
     }
 
@@ -457,7 +601,9 @@ impl NotmuchDb {
             .set_kind(ErrorKind::Configuration));
         }
         path.push(".notmuch");
-        if !path.exists() || !path.is_dir() {
+        /* If `new_command` is set, the database is created when the account is
+         * loaded. */
+        if (!path.exists() || !path.is_dir()) && !s.extra.contains_key("new_command") {
             return Err(Error::new(format!(
                 "Notmuch `root_mailbox` {} for account {} does not contain a `.notmuch` \
                  subdirectory.",
@@ -479,8 +625,25 @@ impl NotmuchDb {
                 .set_kind(ErrorKind::Configuration));
             }
         }
+        if let Some(new_command) = s.extra.remove("new_command") {
+            if new_command.trim().is_empty() {
+                return Err(Error::new(format!(
+                    "Notmuch `new_command` setting for account {} is empty.",
+                    s.name
+                ))
+                .set_kind(ErrorKind::Configuration));
+            }
+        }
+        s.extra.remove("exclude_tags");
+        if let Some(search_order) = s.extra.remove("search_order") {
+            parse_search_order(&search_order)?;
+        }
         let mut parents: Vec<(String, String)> = Vec::with_capacity(s.mailboxes.len());
         for (k, f) in s.mailboxes.iter_mut() {
+            f.extra.remove("exclude_tags");
+            if let Some(search_order) = f.extra.remove("search_order") {
+                parse_search_order(&search_order)?;
+            }
             if f.extra.remove("query").is_none() {
                 return Err(Error::new(format!(
                     "notmuch mailbox configuration entry `{}` for account {} should have a \
@@ -650,6 +813,7 @@ impl MailBackend for NotmuchDb {
             let mailboxes_lck = mailboxes.read().unwrap();
             let mailbox = mailboxes_lck.get(&mailbox_hash).unwrap();
             let query: Query = Query::new(&database, mailbox.query_str.as_str())?;
+            query.set_mailbox_options(mailbox)?;
             {
                 let mut total_lck = mailbox.total.lock().unwrap();
                 let mut unseen_lck = mailbox.unseen.lock().unwrap();
@@ -685,18 +849,21 @@ impl MailBackend for NotmuchDb {
 
     fn refresh(&mut self, _mailbox_hash: MailboxHash) -> ResultFuture<()> {
         let account_hash = self.account_hash;
-        let mut database = NotmuchDb::new_connection(
-            self.path.as_path(),
-            self.revision_uuid.clone(),
-            self.lib.clone(),
-            false,
-        )?;
+        let path = self.path.clone();
+        let revision_uuid = self.revision_uuid.clone();
+        let lib = self.lib.clone();
         let mailboxes = self.mailboxes.clone();
         let index = self.index.clone();
         let mailbox_index = self.mailbox_index.clone();
         let tag_index = self.collection.tag_index.clone();
         let event_consumer = self.event_consumer.clone();
+        let new_command = self.new_command.clone();
         Ok(Box::pin(async move {
+            if let Some(new_command) = new_command {
+                new_command.run()?;
+            }
+            let mut database =
+                NotmuchDb::new_connection(path.as_path(), revision_uuid, lib, false)?;
             let new_revision_uuid = database.get_revision_uuid();
             if new_revision_uuid > *database.revision_uuid.read().unwrap() {
                 database.refresh(
@@ -716,7 +883,7 @@ impl MailBackend for NotmuchDb {
 
     fn watch(&self) -> ResultFuture<()> {
         extern crate notify;
-        use notify::{watcher, RecursiveMode, Watcher};
+        use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 
         let account_hash = self.account_hash;
         let collection = self.collection.clone();
@@ -727,6 +894,7 @@ impl MailBackend for NotmuchDb {
         let index = self.index.clone();
         let mailbox_index = self.mailbox_index.clone();
         let event_consumer = self.event_consumer.clone();
+        let new_command = self.new_command.clone();
 
         let (tx, rx) = std::sync::mpsc::channel();
         let mut watcher = watcher(tx, std::time::Duration::from_secs(2)).unwrap();
@@ -734,8 +902,24 @@ impl MailBackend for NotmuchDb {
         Ok(Box::pin(async move {
             let _watcher = watcher;
             let rx = rx;
+            let database_path = path.join(".notmuch");
             loop {
-                let _ = rx.recv().map_err(|err| err.to_string())?;
+                let event = rx.recv().map_err(|err| err.to_string())?;
+                if let Some(ref new_command) = new_command {
+                    /* Changes to the database itself, e.g. by `new_command`, must not trigger
+                     * another indexing run. */
+                    let is_new_file = match event {
+                        DebouncedEvent::Create(ref p)
+                        | DebouncedEvent::Write(ref p)
+                        | DebouncedEvent::Rename(_, ref p) => !p.starts_with(&database_path),
+                        _ => false,
+                    };
+                    if is_new_file {
+                        if let Err(err) = new_command.run() {
+                            log::error!("{}", err);
+                        }
+                    }
+                }
                 {
                     let mut database = NotmuchDb::new_connection(
                         path.as_path(),
@@ -951,11 +1135,9 @@ impl MailBackend for NotmuchDb {
         let mailboxes = self.mailboxes.clone();
         Ok(Box::pin(async move {
             let mut ret = SmallVec::new();
-            let mut query_s = if let Some(mailbox_hash) = mailbox_hash {
-                if let Some(m) = mailboxes.read().unwrap().get(&mailbox_hash) {
-                    let mut s = m.query_str.clone();
-                    s.push(' ');
-                    s
+            let mailbox = if let Some(mailbox_hash) = mailbox_hash {
+                if let Some(m) = mailboxes.read().unwrap().get(&mailbox_hash).cloned() {
+                    Some(m)
                 } else {
                     return Err(Error::new(format!(
                         "Mailbox with hash {} not found!",
@@ -963,11 +1145,21 @@ impl MailBackend for NotmuchDb {
                     ))
                     .set_kind(crate::error::ErrorKind::Bug));
                 }
+            } else {
+                None
+            };
+            let mut query_s = if let Some(ref m) = mailbox {
+                let mut s = m.query_str.clone();
+                s.push(' ');
+                s
             } else {
                 String::new()
             };
             melib_query.query_to_string(&mut query_s);
             let query: Query = Query::new(&database, &query_s)?;
+            if let Some(ref m) = mailbox {
+                query.set_mailbox_options(m)?;
+            }
             let iter = query.search()?;
             for message in iter {
                 ret.push(message.env_hash());
@@ -1093,6 +1285,31 @@ impl<'s> Query<'s> {
         })
     }
 
+    /// Applies the excluded tags and search order of `mailbox`.
+    fn set_mailbox_options(&self, mailbox: &NotmuchMailbox) -> Result<()> {
+        for tag in mailbox.exclude_tags.iter() {
+            let tag_cstr = std::ffi::CString::new(tag.as_str())?;
+            let status = unsafe {
+                call!(self.lib, notmuch_query_add_tag_exclude)(self.ptr, tag_cstr.as_ptr())
+            };
+            /* `IGNORED` means the query mentions the tag explicitly, so it is not
+             * excluded. */
+            if status != _notmuch_status_NOTMUCH_STATUS_SUCCESS
+                && status != _notmuch_status_NOTMUCH_STATUS_IGNORED
+            {
+                return Err(Error::new(format!(
+                    "Could not exclude tag {} from query {}: notmuch_query_add_tag_exclude \
+                     returned {}.",
+                    tag, self.query_str, status
+                )));
+            }
+        }
+        unsafe {
+            call!(self.lib, notmuch_query_set_sort)(self.ptr, mailbox.search_order);
+        }
+        Ok(())
+    }
+
     fn count(&self) -> Result<u32> {
         let mut count = 0_u32;
         unsafe {
@@ -1140,7 +1357,7 @@ pub trait MelibQueryToNotmuchQuery {
 
 impl MelibQueryToNotmuchQuery for crate::search::Query {
     fn query_to_string(&self, ret: &mut String) {
-        use crate::search::Query::*;
+        use crate::search::{FlagQuery, Query::*};
         match self {
             Before(timestamp) => {
                 ret.push_str("date:..@");
@@ -1222,23 +1439,52 @@ impl MelibQueryToNotmuchQuery for crate::search::Query {
             /* * * * */
             Flags(v) => {
                 for f in v {
+                    /* Flags are stored as notmuch's standard tags, see `set_flags`. */
+                    let (flag, set) = match FlagQuery::new(f) {
+                        FlagQuery::Set(flag) => (flag, true),
+                        FlagQuery::Unset(flag) => (flag, false),
+                        FlagQuery::Tag(_) => (Flag::empty(), true),
+                    };
+                    let (tag, set) = match flag {
+                        Flag::SEEN => ("unread", !set),
+                        Flag::DRAFT => ("draft", set),
+                        Flag::FLAGGED => ("flagged", set),
+                        Flag::PASSED => ("passed", set),
+                        Flag::REPLIED => ("replied", set),
+                        Flag::TRASHED => ("trashed", set),
+                        _ => (f.as_str(), set),
+                    };
+                    if !set {
+                        ret.push_str("NOT ");
+                    }
                     ret.push_str("tag:\"");
-                    for c in f.chars() {
+                    for c in tag.chars() {
                         if c == '"' {
                             ret.push_str("\\\"");
                         } else {
                             ret.push(c);
                         }
                     }
-                    ret.push_str("\" ");
+                    ret.push_str("\" AND ");
                 }
                 if !v.is_empty() {
-                    ret.pop();
+                    ret.truncate(ret.len() - " AND ".len());
                 }
             }
             HasAttachment => {
                 ret.push_str("tag:attachment");
             }
+            Attachment(s) => {
+                ret.push_str("attachment:\"");
+                for c in s.chars() {
+                    if c == '"' {
+                        ret.push_str("\\\"");
+                    } else {
+                        ret.push(c);
+                    }
+                }
+                ret.push('"');
+            }
             And(q1, q2) => {
                 ret.push('(');
                 q1.query_to_string(ret);
@@ -0,0 +1,652 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # POP3 backend / client
+//!
+//! Implements a POP3 client as specified by [RFC 1939: Post Office Protocol -
+//! Version 3](https://datatracker.ietf.org/doc/html/rfc1939), with the `CAPA`
+//! command of [RFC 2449](https://datatracker.ietf.org/doc/html/rfc2449) and
+//! the `STLS` command of [RFC 2595](https://datatracker.ietf.org/doc/html/rfc2595).
+//!
+//! POP3 servers have a single mailbox, the maildrop. Messages are downloaded
+//! into a local store in the account's data directory, and their `UIDL`
+//! unique ids are used to download each message only once. Unless
+//! `leave_on_server` is set, downloaded messages are deleted from the server.
+
+mod connection;
+pub use connection::*;
+mod mailbox;
+pub use mailbox::*;
+mod operations;
+pub use operations::*;
+mod protocol_parser;
+pub use protocol_parser::*;
+mod store;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{lock::Mutex as FutureMutex, stream::Stream};
+use smallvec::SmallVec;
+pub use store::*;
+
+use crate::{
+    backends::*,
+    conf::AccountSettings,
+    connections::{proxy::Proxy, timeout, tls::TlsOptions},
+    email::*,
+    error::{Error, Result},
+    get_path_hash, log, Collection,
+};
+
+macro_rules! get_conf_val {
+    ($s:ident[$var:literal]) => {
+        $s.extra.get($var).ok_or_else(|| {
+            Error::new(format!(
+                "Configuration error ({}): POP3 connection requires the field `{}` set",
+                $s.name.as_str(),
+                $var
+            ))
+        })
+    };
+    ($s:ident[$var:literal], $default:expr) => {
+        $s.extra
+            .get($var)
+            .map(|v| {
+                <_>::from_str(v).map_err(|e| {
+                    Error::new(format!(
+                        "Configuration error ({}) POP3: Invalid value for field `{}`: {}\n{}",
+                        $s.name.as_str(),
+                        $var,
+                        v,
+                        e
+                    ))
+                })
+            })
+            .unwrap_or_else(|| Ok($default))
+    };
+}
+
+pub static SUPPORTED_CAPABILITIES: &[&str] = &["STLS", "UIDL", "USER"];
+
+/// Path of the maildrop mailbox.
+pub const INBOX: &str = "INBOX";
+
+#[derive(Debug, Clone)]
+pub struct Pop3ServerConf {
+    pub server_hostname: String,
+    pub server_username: String,
+    pub server_password: String,
+    pub server_port: u16,
+    pub use_starttls: bool,
+    pub use_tls: bool,
+    pub tls: TlsOptions,
+    /// Connect through this proxy.
+    pub proxy: Option<Proxy>,
+    /// Keep downloaded messages on the server.
+    pub leave_on_server: bool,
+    /// Interval between checks for new mail.
+    pub poll_interval: Duration,
+}
+
+type Capabilities = HashSet<String>;
+
+#[derive(Debug)]
+pub struct UIDStore {
+    account_hash: AccountHash,
+    account_name: Arc<String>,
+    capabilities: Arc<Mutex<Capabilities>>,
+    /// Envelope hash of every message in the local store, by UIDL.
+    uidl_index: Arc<Mutex<HashMap<String, EnvelopeHash>>>,
+    /// UIDL and local path of every message in the local store.
+    hash_index: Arc<Mutex<HashMap<EnvelopeHash, (String, PathBuf)>>>,
+    store: Pop3Store,
+
+    collection: Collection,
+    mailbox: Pop3Mailbox,
+    is_online: Arc<Mutex<(Instant, Result<()>)>>,
+    event_consumer: BackendEventConsumer,
+}
+
+impl UIDStore {
+    fn new(
+        account_hash: AccountHash,
+        account_name: Arc<String>,
+        store: Pop3Store,
+        event_consumer: BackendEventConsumer,
+    ) -> Self {
+        UIDStore {
+            account_hash,
+            account_name,
+            store,
+            event_consumer,
+            capabilities: Default::default(),
+            uidl_index: Default::default(),
+            hash_index: Default::default(),
+            collection: Collection::new(),
+            mailbox: Pop3Mailbox {
+                hash: MailboxHash(get_path_hash!(INBOX)),
+                path: INBOX.to_string(),
+                exists: Default::default(),
+                unseen: Default::default(),
+            },
+            is_online: Arc::new(Mutex::new((
+                Instant::now(),
+                Err(Error::new("Account is uninitialised.")),
+            ))),
+        }
+    }
+
+    /// Indexes a stored message and returns its envelope, unless it can't be
+    /// parsed.
+    fn insert_envelope(
+        &self,
+        uidl: String,
+        path: PathBuf,
+        bytes: &[u8],
+        flags: Flag,
+    ) -> Option<Envelope> {
+        let env_hash = EnvelopeHash::from_bytes(uidl.as_bytes());
+        self.uidl_index
+            .lock()
+            .unwrap()
+            .insert(uidl.clone(), env_hash);
+        self.hash_index
+            .lock()
+            .unwrap()
+            .insert(env_hash, (uidl, path.clone()));
+        match Envelope::from_bytes(bytes, Some(flags)) {
+            Ok(mut env) => {
+                env.set_hash(env_hash);
+                Some(env)
+            }
+            Err(err) => {
+                log::warn!(
+                    "{}: could not parse POP3 message {}: {}",
+                    self.account_name,
+                    path.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Indexes the messages of the local store and returns their envelopes.
+    fn load_local(&self) -> Result<Vec<Envelope>> {
+        let mut ret = vec![];
+        for (uidl, path, flags) in self.store.list()? {
+            let bytes = std::fs::read(&path)?;
+            if let Some(env) = self.insert_envelope(uidl, path, &bytes, flags) {
+                ret.push(env);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn add_to_counts(&self, envelopes: &[Envelope]) {
+        self.mailbox
+            .exists
+            .lock()
+            .unwrap()
+            .insert_set(envelopes.iter().map(|env| env.hash()).collect());
+        self.mailbox.unseen.lock().unwrap().insert_set(
+            envelopes
+                .iter()
+                .filter(|env| !env.is_seen())
+                .map(|env| env.hash())
+                .collect(),
+        );
+    }
+
+    /// Adds new messages found while the account is open.
+    fn add_new_envelopes(&self, envelopes: Vec<Envelope>) {
+        self.add_to_counts(&envelopes);
+        for env in envelopes {
+            (self.event_consumer)(
+                self.account_hash,
+                BackendEvent::Refresh(RefreshEvent {
+                    mailbox_hash: self.mailbox.hash,
+                    account_hash: self.account_hash,
+                    kind: RefreshEventKind::Create(Box::new(env)),
+                }),
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Pop3Type {
+    connection: Arc<FutureMutex<Pop3Connection>>,
+    server_conf: Pop3ServerConf,
+    uid_store: Arc<UIDStore>,
+}
+
+impl MailBackend for Pop3Type {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        let mut extensions = self
+            .uid_store
+            .capabilities
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|c| {
+                let status = if SUPPORTED_CAPABILITIES
+                    .iter()
+                    .any(|s| c.eq_ignore_ascii_case(s))
+                {
+                    MailBackendExtensionStatus::Enabled { comment: None }
+                } else {
+                    MailBackendExtensionStatus::Unsupported { comment: None }
+                };
+                (c.to_string(), status)
+            })
+            .collect::<Vec<(String, MailBackendExtensionStatus)>>();
+        extensions.sort_by(|a, b| a.0.cmp(&b.0));
+        MailBackendCapabilities {
+            is_async: true,
+            is_remote: true,
+            supports_search: false,
+            extensions: Some(extensions),
+            supports_tags: false,
+            supports_submission: false,
+        }
+    }
+
+    fn fetch(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let uid_store = self.uid_store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async_stream::try_stream! {
+            uid_store.mailbox.exists.lock().unwrap().clear();
+            uid_store.mailbox.unseen.lock().unwrap().clear();
+            let local = uid_store.load_local()?;
+            if !local.is_empty() {
+                uid_store.add_to_counts(&local);
+                yield local;
+            }
+            let new = {
+                let mut conn = timeout(Some(Duration::from_secs(60 * 16)), connection.lock()).await?;
+                conn.sync_maildrop().await?
+            };
+            if !new.is_empty() {
+                uid_store.add_to_counts(&new);
+                yield new;
+            }
+        }))
+    }
+
+    fn refresh(&mut self, _mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        let uid_store = self.uid_store.clone();
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let new = {
+                let mut conn =
+                    timeout(Some(Duration::from_secs(60 * 16)), connection.lock()).await?;
+                conn.sync_maildrop().await?
+            };
+            uid_store.add_new_envelopes(new);
+            Ok(())
+        }))
+    }
+
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let mailbox = Clone::clone(&self.uid_store.mailbox);
+        Ok(Box::pin(async move {
+            let mut ret: HashMap<MailboxHash, Mailbox> = HashMap::default();
+            ret.insert(mailbox.hash, Box::new(mailbox));
+            Ok(ret)
+        }))
+    }
+
+    fn is_online(&self) -> ResultFuture<()> {
+        let connection = self.connection.clone();
+        Ok(Box::pin(async move {
+            let mut conn = timeout(Some(Duration::from_secs(60 * 16)), connection.lock()).await?;
+            timeout(Some(Duration::from_secs(60 * 16)), conn.connect()).await?
+        }))
+    }
+
+    fn watch(&self) -> ResultFuture<()> {
+        let uid_store = self.uid_store.clone();
+        let connection = self.connection.clone();
+        let poll_interval = self.server_conf.poll_interval;
+        Ok(Box::pin(async move {
+            loop {
+                smol::Timer::after(poll_interval).await;
+                let new = {
+                    let mut conn =
+                        timeout(Some(Duration::from_secs(60 * 16)), connection.lock()).await?;
+                    conn.sync_maildrop().await?
+                };
+                uid_store.add_new_envelopes(new);
+            }
+        }))
+    }
+
+    fn operation(&self, env_hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        if !self
+            .uid_store
+            .hash_index
+            .lock()
+            .unwrap()
+            .contains_key(&env_hash)
+        {
+            return Err(Error::new(
+                "Message not found in local store, it might have been deleted before you \
+                 requested it.",
+            ));
+        }
+        Ok(Box::new(Pop3Op::new(env_hash, self.uid_store.clone())))
+    }
+
+    fn save(
+        &self,
+        _bytes: Vec<u8>,
+        _mailbox_hash: MailboxHash,
+        _flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        Err(Error::new("POP3 doesn't support saving."))
+    }
+
+    fn copy_messages(
+        &mut self,
+        _env_hashes: EnvelopeHashBatch,
+        _source_mailbox_hash: MailboxHash,
+        _destination_mailbox_hash: MailboxHash,
+        _move_: bool,
+    ) -> ResultFuture<()> {
+        Err(Error::new("POP3 doesn't support copying/moving."))
+    }
+
+    fn set_flags(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        if flags.iter().any(|(f, _)| f.is_err()) {
+            return Err(Error::new("POP3 doesn't support tags."));
+        }
+        let uid_store = self.uid_store.clone();
+        Ok(Box::pin(async move {
+            let mut hash_index_lck = uid_store.hash_index.lock().unwrap();
+            for env_hash in env_hashes.iter() {
+                let path = match hash_index_lck.get_mut(&env_hash) {
+                    Some((_, path)) => path,
+                    None => continue,
+                };
+                let mut env_flags = path_flags(path);
+                for (f, value) in flags.iter() {
+                    env_flags.set(*f.as_ref().unwrap(), *value);
+                }
+                *path = uid_store.store.set_flags(path, env_flags)?;
+                let mut unseen_lck = uid_store.mailbox.unseen.lock().unwrap();
+                if env_flags.is_seen() {
+                    unseen_lck.remove(env_hash);
+                } else {
+                    unseen_lck.insert_new(env_hash);
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn delete_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        let uid_store = self.uid_store.clone();
+        let connection = self.connection.clone();
+        let leave_on_server = self.server_conf.leave_on_server;
+        Ok(Box::pin(async move {
+            let entries = {
+                let hash_index_lck = uid_store.hash_index.lock().unwrap();
+                env_hashes
+                    .iter()
+                    .filter_map(|env_hash| {
+                        let (uidl, path) = hash_index_lck.get(&env_hash)?;
+                        Some((env_hash, uidl.clone(), path.clone()))
+                    })
+                    .collect::<Vec<(EnvelopeHash, String, PathBuf)>>()
+            };
+            if leave_on_server {
+                let uidls = entries
+                    .iter()
+                    .map(|(_, uidl, _)| uidl.clone())
+                    .collect::<Vec<String>>();
+                let mut conn =
+                    timeout(Some(Duration::from_secs(60 * 16)), connection.lock()).await?;
+                conn.delete_from_server(&uidls).await?;
+            }
+            for (env_hash, uidl, path) in entries {
+                std::fs::remove_file(&path)?;
+                uid_store.hash_index.lock().unwrap().remove(&env_hash);
+                uid_store.uidl_index.lock().unwrap().remove(&uidl);
+                uid_store.mailbox.exists.lock().unwrap().remove(env_hash);
+                uid_store.mailbox.unseen.lock().unwrap().remove(env_hash);
+                (uid_store.event_consumer)(
+                    uid_store.account_hash,
+                    BackendEvent::Refresh(RefreshEvent {
+                        mailbox_hash: uid_store.mailbox.hash,
+                        account_hash: uid_store.account_hash,
+                        kind: RefreshEventKind::Remove(env_hash),
+                    }),
+                );
+            }
+            Ok(())
+        }))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn collection(&self) -> Collection {
+        self.uid_store.collection.clone()
+    }
+
+    fn create_mailbox(
+        &mut self,
+        _path: String,
+    ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
+        Err(Error::new("POP3 doesn't support creating mailboxes."))
+    }
+
+    fn delete_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        Err(Error::new("POP3 doesn't support deleting mailboxes."))
+    }
+
+    fn set_mailbox_subscription(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _new_val: bool,
+    ) -> ResultFuture<()> {
+        Err(Error::new("POP3 doesn't support mailbox subscriptions."))
+    }
+
+    fn rename_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _new_path: String,
+    ) -> ResultFuture<Mailbox> {
+        Err(Error::new("POP3 doesn't support renaming mailboxes."))
+    }
+
+    fn set_mailbox_permissions(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _val: crate::backends::MailboxPermissions,
+    ) -> ResultFuture<()> {
+        Err(Error::new(
+            "POP3 doesn't support setting mailbox permissions.",
+        ))
+    }
+
+    fn search(
+        &self,
+        _query: crate::search::Query,
+        _mailbox_hash: Option<MailboxHash>,
+    ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
+        Err(Error::new(
+            "Searching is currently unimplemented for pop3 backend.",
+        ))
+    }
+}
+
+impl Pop3Type {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        s: &AccountSettings,
+        _is_subscribed: Box<dyn Fn(&str) -> bool + Send + Sync>,
+        event_consumer: BackendEventConsumer,
+    ) -> Result<Box<dyn MailBackend>> {
+        let server_hostname = get_conf_val!(s["server_hostname"])?;
+        let server_username = get_conf_val!(s["server_username"])?;
+        let server_password = s.server_password()?;
+        let server_port = get_conf_val!(s["server_port"], 110)?;
+        let use_tls = get_conf_val!(s["use_tls"], true)?;
+        let use_starttls = get_conf_val!(s["use_starttls"], use_tls && server_port != 995)?;
+        let server_conf = Pop3ServerConf {
+            server_hostname: server_hostname.to_string(),
+            server_username: server_username.to_string(),
+            server_password,
+            server_port,
+            use_tls,
+            use_starttls,
+            tls: TlsOptions::new(s)?,
+            proxy: Proxy::new(s)?,
+            leave_on_server: get_conf_val!(s["leave_on_server"], true)?,
+            poll_interval: Duration::from_secs(get_conf_val!(s["poll_interval"], 300_u64)?),
+        };
+        let account_hash = AccountHash::from_bytes(s.name.as_bytes());
+        let account_name = Arc::new(s.name.to_string());
+        let store = Pop3Store::new(&s.name)?;
+        let uid_store: Arc<UIDStore> = Arc::new(UIDStore::new(
+            account_hash,
+            account_name,
+            store,
+            event_consumer,
+        ));
+        let connection = Pop3Connection::new_connection(&server_conf, uid_store.clone());
+
+        Ok(Box::new(Pop3Type {
+            server_conf,
+            connection: Arc::new(FutureMutex::new(connection)),
+            uid_store,
+        }))
+    }
+
+    pub fn validate_config(s: &mut AccountSettings) -> Result<()> {
+        let mut keys: HashSet<&'static str> = Default::default();
+        macro_rules! get_conf_val {
+            ($s:ident[$var:literal]) => {{
+                keys.insert($var);
+                $s.extra.remove($var).ok_or_else(|| {
+                    Error::new(format!(
+                        "Configuration error ({}): POP3 connection requires the field `{}` set",
+                        $s.name.as_str(),
+                        $var
+                    ))
+                })
+            }};
+            ($s:ident[$var:literal], $default:expr) => {{
+                keys.insert($var);
+                $s.extra
+                    .remove($var)
+                    .map(|v| {
+                        <_>::from_str(&v).map_err(|e| {
+                            Error::new(format!(
+                                "Configuration error ({}) POP3: Invalid value for field `{}`: \
+                                 {}\n{}",
+                                $s.name.as_str(),
+                                $var,
+                                v,
+                                e
+                            ))
+                        })
+                    })
+                    .unwrap_or_else(|| Ok($default))
+            }};
+        }
+        get_conf_val!(s["server_hostname"])?;
+        get_conf_val!(s["server_username"])?;
+        keys.insert("server_password_command");
+        if !s.extra.contains_key("server_password_command") {
+            get_conf_val!(s["server_password"])?;
+        } else if s.extra.contains_key("server_password") {
+            return Err(Error::new(format!(
+                "Configuration error ({}): both server_password and server_password_command are \
+                 set, cannot choose",
+                s.name.as_str(),
+            )));
+        }
+        let _ = get_conf_val!(s["server_password_command"]);
+        let server_port = get_conf_val!(s["server_port"], 110_u16)?;
+        let use_tls = get_conf_val!(s["use_tls"], true)?;
+        let use_starttls = get_conf_val!(s["use_starttls"], use_tls && server_port != 995)?;
+        if !use_tls && use_starttls {
+            return Err(Error::new(format!(
+                "Configuration error ({}): incompatible use_tls and use_starttls values: use_tls \
+                 = false, use_starttls = true",
+                s.name.as_str(),
+            )));
+        }
+        get_conf_val!(s["leave_on_server"], true)?;
+        get_conf_val!(s["poll_interval"], 300_u64)?;
+        TlsOptions::new(s)?;
+        for key in TlsOptions::KEYS {
+            s.extra.remove(*key);
+        }
+        Proxy::new(s)?;
+        s.extra.remove("proxy");
+        let extra_keys = s
+            .extra
+            .keys()
+            .map(String::as_str)
+            .collect::<HashSet<&str>>();
+        let diff = extra_keys.difference(&keys).collect::<Vec<&&str>>();
+        if !diff.is_empty() {
+            return Err(Error::new(format!(
+                "Configuration error ({}) POP3: the following flags are set but are not \
+                 recognized: {:?}.",
+                s.name.as_str(),
+                diff
+            )));
+        }
+        Ok(())
+    }
+}
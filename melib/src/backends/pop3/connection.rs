@@ -0,0 +1,316 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::{
+    connections::{connect_tcp, Connection},
+    email::{parser::BytesExt, Envelope, Flag},
+    error::*,
+    log,
+};
+extern crate native_tls;
+use std::{collections::HashSet, future::Future, pin::Pin, sync::Arc, time::Instant};
+
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+pub use smol::Async as AsyncWrapper;
+
+use super::{protocol_parser, Capabilities, Pop3ServerConf, UIDStore};
+
+#[derive(Debug)]
+pub struct Pop3Stream {
+    pub stream: AsyncWrapper<Connection>,
+}
+
+#[derive(Debug)]
+pub struct Pop3Connection {
+    pub stream: Result<Pop3Stream>,
+    pub server_conf: Pop3ServerConf,
+    pub uid_store: Arc<UIDStore>,
+}
+
+impl Pop3Stream {
+    pub async fn new_connection(
+        server_conf: &Pop3ServerConf,
+    ) -> Result<(Capabilities, Pop3Stream)> {
+        let path = &server_conf.server_hostname;
+
+        let stream = AsyncWrapper::new(Connection::Tcp(connect_tcp(
+            path,
+            server_conf.server_port,
+            server_conf.proxy.as_ref(),
+            Some(std::time::Duration::new(16, 0)),
+        )?))?;
+        let mut res = Vec::with_capacity(8 * 1024);
+        let mut ret = Pop3Stream { stream };
+
+        if server_conf.use_tls {
+            let connector = server_conf.tls.connector()?;
+
+            if server_conf.use_starttls {
+                ret.read_response(&mut res, false).await?;
+                ret.send_command(b"STLS").await?;
+                ret.read_response(&mut res, false)
+                    .await
+                    .chain_err_summary(|| {
+                        format!(
+                            "Could not connect to {}: could not begin TLS negotiation",
+                            path
+                        )
+                    })?;
+            }
+
+            {
+                // FIXME: This is blocking
+                let socket = ret.stream.into_inner()?;
+                let mut conn_result = connector.connect(path, socket);
+                if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
+                    conn_result
+                {
+                    let mut midhandshake_stream = Some(midhandshake_stream);
+                    loop {
+                        match midhandshake_stream.take().unwrap().handshake() {
+                            Ok(r) => {
+                                conn_result = Ok(r);
+                                break;
+                            }
+                            Err(native_tls::HandshakeError::WouldBlock(stream)) => {
+                                midhandshake_stream = Some(stream);
+                            }
+                            p => {
+                                p.chain_err_kind(crate::error::ErrorKind::Network(
+                                    crate::error::NetworkErrorKind::InvalidTLSConnection,
+                                ))?;
+                            }
+                        }
+                    }
+                }
+                let tls_stream = conn_result?;
+                server_conf.tls.verify_peer(path, &tls_stream)?;
+                ret.stream =
+                    AsyncWrapper::new(Connection::Tls(tls_stream)).chain_err_summary(|| {
+                        format!("Could not initiate TLS negotiation to {}.", path)
+                    })?;
+            }
+        }
+        if let Err(err) = ret
+            .stream
+            .get_ref()
+            .set_keepalive(Some(std::time::Duration::new(60 * 9, 0)))
+        {
+            log::warn!("Could not set TCP keepalive in POP3 connection: {}", err);
+        }
+
+        /* There is no new greeting after STLS. */
+        if !(server_conf.use_tls && server_conf.use_starttls) {
+            ret.read_response(&mut res, false).await?;
+        }
+
+        /* CAPA is optional, servers that don't know it reply with -ERR. */
+        ret.send_command(b"CAPA").await?;
+        let capabilities: Capabilities = match ret.read_response(&mut res, true).await {
+            Ok(()) => protocol_parser::capabilities(&res),
+            Err(err) if !err.kind.is_network() => HashSet::default(),
+            Err(err) => return Err(err),
+        };
+
+        ret.send_command(format!("USER {}", server_conf.server_username).as_bytes())
+            .await?;
+        ret.read_response(&mut res, false)
+            .await
+            .chain_err_summary(|| "Authentication failed")
+            .chain_err_kind(ErrorKind::Authentication)?;
+        ret.send_command(format!("PASS {}", server_conf.server_password).as_bytes())
+            .await?;
+        ret.read_response(&mut res, false)
+            .await
+            .chain_err_summary(|| "Authentication failed")
+            .chain_err_kind(ErrorKind::Authentication)?;
+
+        Ok((capabilities, ret))
+    }
+
+    /// Reads a response. Fails if its status indicator is `-ERR`.
+    pub async fn read_response(&mut self, ret: &mut Vec<u8>, is_multiline: bool) -> Result<()> {
+        let mut buf: Vec<u8> = vec![0; Connection::IO_BUF_SIZE];
+        ret.clear();
+        loop {
+            let b = self.stream.read(&mut buf).await?;
+            if b == 0 {
+                return Err(Error::new("Connection closed by server.")
+                    .set_kind(ErrorKind::Network(NetworkErrorKind::ConnectionFailed)));
+            }
+            ret.extend_from_slice(&buf[0..b]);
+            let pos = match ret.find(b"\r\n") {
+                Some(pos) => pos,
+                None => continue,
+            };
+            if ret.starts_with(b"-ERR") {
+                return Err(Error::new(format!(
+                    "Server replied with an error: {}",
+                    String::from_utf8_lossy(&ret[..pos])
+                )));
+            } else if !ret.starts_with(b"+OK") {
+                return Err(Error::new(format!(
+                    "Unexpected reply: {}",
+                    String::from_utf8_lossy(&ret[..pos])
+                ))
+                .set_kind(ErrorKind::Network(NetworkErrorKind::ProtocolViolation)));
+            }
+            if !is_multiline || ret[pos..].ends_with(b"\r\n.\r\n") {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn send_command(&mut self, command: &[u8]) -> Result<()> {
+        self.stream.write_all(command).await?;
+        self.stream.write_all(b"\r\n").await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+}
+
+impl Pop3Connection {
+    pub fn new_connection(
+        server_conf: &Pop3ServerConf,
+        uid_store: Arc<UIDStore>,
+    ) -> Pop3Connection {
+        Pop3Connection {
+            stream: Err(Error::new("Offline".to_string())),
+            server_conf: server_conf.clone(),
+            uid_store,
+        }
+    }
+
+    pub fn connect<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            if self.stream.is_ok() {
+                self.uid_store.is_online.lock().unwrap().0 = Instant::now();
+                return Ok(());
+            }
+            let new_stream = Pop3Stream::new_connection(&self.server_conf).await;
+            if let Err(err) = new_stream.as_ref() {
+                *self.uid_store.is_online.lock().unwrap() = (Instant::now(), Err(err.clone()));
+            } else {
+                *self.uid_store.is_online.lock().unwrap() = (Instant::now(), Ok(()));
+            }
+            let (capabilities, stream) = new_stream?;
+            self.stream = Ok(stream);
+            *self.uid_store.capabilities.lock().unwrap() = capabilities;
+            Ok(())
+        })
+    }
+
+    /// Sends `command` and reads its response into `res`.
+    pub async fn command(
+        &mut self,
+        command: &[u8],
+        res: &mut Vec<u8>,
+        is_multiline: bool,
+    ) -> Result<()> {
+        let stream = self.stream.as_mut()?;
+        let result = match stream.send_command(command).await {
+            Ok(()) => stream.read_response(res, is_multiline).await,
+            Err(err) => Err(err),
+        };
+        if let Err(ref err) = result {
+            if err.kind.is_network() {
+                self.stream = Err(err.clone());
+            }
+        }
+        result
+    }
+
+    /// Ends the session. Messages marked with `DELE` are deleted by the server
+    /// only when the session ends this way.
+    pub async fn quit(&mut self) -> Result<()> {
+        let mut res = Vec::with_capacity(64);
+        let result = self.command(b"QUIT", &mut res, false).await;
+        self.stream = Err(Error::new("Offline".to_string()));
+        result
+    }
+
+    /// Starts a new session and downloads the messages that aren't in the
+    /// local store yet. Unless `leave_on_server` is set, stored messages are
+    /// deleted from the server afterwards. The session is always ended with
+    /// `QUIT`. Returns the envelopes of the new messages.
+    pub async fn sync_maildrop(&mut self) -> Result<Vec<Envelope>> {
+        /* The maildrop doesn't change during a session, a new one is needed to see
+         * new messages. */
+        if self.stream.is_ok() {
+            let _ = self.quit().await;
+        }
+        self.connect().await?;
+        let mut res = Vec::with_capacity(8 * 1024);
+        self.command(b"UIDL", &mut res, true)
+            .await
+            .chain_err_summary(|| {
+                format!(
+                    "{} Could not list messages: the server must support the UIDL command.",
+                    &self.uid_store.account_name
+                )
+            })?;
+        let listing = protocol_parser::uidl_list(&res)?;
+        let mut ret = vec![];
+        for (num, uidl) in listing.iter() {
+            if self.uid_store.uidl_index.lock().unwrap().contains_key(uidl) {
+                continue;
+            }
+            self.command(format!("RETR {}", num).as_bytes(), &mut res, true)
+                .await?;
+            let bytes = protocol_parser::multiline_data(&res);
+            let path = self.uid_store.store.insert(uidl, &bytes, Flag::default())?;
+            if let Some(env) =
+                self.uid_store
+                    .insert_envelope(uidl.to_string(), path, &bytes, Flag::default())
+            {
+                ret.push(env);
+            }
+        }
+        if !self.server_conf.leave_on_server {
+            for (num, uidl) in listing.iter() {
+                if self.uid_store.uidl_index.lock().unwrap().contains_key(uidl) {
+                    self.command(format!("DELE {}", num).as_bytes(), &mut res, false)
+                        .await?;
+                }
+            }
+        }
+        self.quit().await?;
+        Ok(ret)
+    }
+
+    /// Deletes the messages with these UIDLs from the server.
+    pub async fn delete_from_server(&mut self, uidls: &[String]) -> Result<()> {
+        if self.stream.is_ok() {
+            let _ = self.quit().await;
+        }
+        self.connect().await?;
+        let mut res = Vec::with_capacity(8 * 1024);
+        self.command(b"UIDL", &mut res, true).await?;
+        for (num, uidl) in protocol_parser::uidl_list(&res)? {
+            if uidls.contains(&uidl) {
+                self.command(format!("DELE {}", num).as_bytes(), &mut res, false)
+                    .await?;
+            }
+        }
+        self.quit().await
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    backends::{
+        BackendMailbox, LazyCountSet, Mailbox, MailboxHash, MailboxPermissions, SpecialUsageMailbox,
+    },
+    error::*,
+};
+
+/// The maildrop, the only mailbox of a POP3 account.
+#[derive(Debug, Default, Clone)]
+pub struct Pop3Mailbox {
+    pub(super) hash: MailboxHash,
+    pub(super) path: String,
+
+    pub exists: Arc<Mutex<LazyCountSet>>,
+    pub unseen: Arc<Mutex<LazyCountSet>>,
+}
+
+impl BackendMailbox for Pop3Mailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &[]
+    }
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        SpecialUsageMailbox::Inbox
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        None
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        MailboxPermissions {
+            create_messages: false,
+            remove_messages: true,
+            set_flags: true,
+            create_child: false,
+            rename_messages: false,
+            delete_messages: true,
+            delete_mailbox: false,
+            change_permissions: false,
+        }
+    }
+
+    fn is_subscribed(&self) -> bool {
+        true
+    }
+
+    fn set_is_subscribed(&mut self, _new_val: bool) -> Result<()> {
+        Err(Error::new("Cannot set subscription in POP3."))
+    }
+
+    fn set_special_usage(&mut self, _new_val: SpecialUsageMailbox) -> Result<()> {
+        Err(Error::new("Cannot set special usage in POP3."))
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok((self.unseen.lock()?.len(), self.exists.lock()?.len()))
+    }
+}
@@ -0,0 +1,66 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::sync::Arc;
+
+use super::*;
+use crate::{backends::*, email::*, error::Error};
+
+/// `BackendOp` implementor for Pop3. Messages are read from the local store,
+/// since every message is downloaded when it is first seen.
+#[derive(Debug, Clone)]
+pub struct Pop3Op {
+    hash: EnvelopeHash,
+    uid_store: Arc<UIDStore>,
+}
+
+impl Pop3Op {
+    pub fn new(hash: EnvelopeHash, uid_store: Arc<UIDStore>) -> Self {
+        Pop3Op { hash, uid_store }
+    }
+
+    fn path(&self) -> Result<PathBuf> {
+        self.uid_store
+            .hash_index
+            .lock()
+            .unwrap()
+            .get(&self.hash)
+            .map(|(_, path)| path.clone())
+            .ok_or_else(|| {
+                Error::new(
+                    "Message not found in local store, it might have been deleted before you \
+                     requested it.",
+                )
+            })
+    }
+}
+
+impl BackendOp for Pop3Op {
+    fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
+        let path = self.path()?;
+        Ok(Box::pin(async move { Ok(std::fs::read(&path)?) }))
+    }
+
+    fn fetch_flags(&self) -> ResultFuture<Flag> {
+        let path = self.path()?;
+        Ok(Box::pin(async move { Ok(path_flags(&path)) }))
+    }
+}
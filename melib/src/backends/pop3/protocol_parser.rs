@@ -0,0 +1,110 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::str::FromStr;
+
+use super::Capabilities;
+use crate::{email::parser::BytesExt, error::*};
+
+/// Returns the status line of a response, without the line terminator.
+pub fn status_line(response: &[u8]) -> &[u8] {
+    match response.find(b"\r\n") {
+        Some(pos) => &response[..pos],
+        None => response,
+    }
+}
+
+/// Returns the data of a multi-line response: the lines between the status
+/// line and the termination octet, with byte-stuffing removed.
+pub fn multiline_data(response: &[u8]) -> Vec<u8> {
+    let start = match response.find(b"\r\n") {
+        Some(pos) => pos + b"\r\n".len(),
+        None => return vec![],
+    };
+    let mut data = &response[start..];
+    if data == b".\r\n" {
+        return vec![];
+    }
+    if data.ends_with(b"\r\n.\r\n") {
+        data = &data[..data.len() - b".\r\n".len()];
+    }
+    let mut ret = Vec::with_capacity(data.len());
+    for line in data.split_inclusive(|b| *b == b'\n') {
+        if line.starts_with(b"..") {
+            ret.extend_from_slice(&line[1..]);
+        } else {
+            ret.extend_from_slice(line);
+        }
+    }
+    ret
+}
+
+/// Parses the response of `CAPA`.
+pub fn capabilities(response: &[u8]) -> Capabilities {
+    String::from_utf8_lossy(&multiline_data(response))
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Parses the response of `UIDL` into message numbers and their unique ids.
+pub fn uidl_list(response: &[u8]) -> Result<Vec<(usize, String)>> {
+    String::from_utf8_lossy(&multiline_data(response))
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let mut parts = l.split_whitespace();
+            match (parts.next().map(usize::from_str), parts.next()) {
+                (Some(Ok(num)), Some(uid)) => Ok((num, uid.to_string())),
+                _ => Err(Error::new(format!("Invalid UIDL response line: {}", l))
+                    .set_kind(ErrorKind::Network(NetworkErrorKind::ProtocolViolation))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop3_multiline_data() {
+        assert_eq!(status_line(b"+OK 2 messages\r\n"), b"+OK 2 messages");
+        assert_eq!(multiline_data(b"+OK\r\n.\r\n"), b"");
+        assert_eq!(
+            multiline_data(b"+OK 12 octets\r\nSubject: a\r\n\r\n..hidden\r\n.\r\n"),
+            b"Subject: a\r\n\r\n.hidden\r\n"
+        );
+        assert_eq!(
+            uidl_list(b"+OK\r\n1 whqtswO00WBw418f9t5JxYwZ\r\n2 QhdPYR:00WBw1Ph7x7\r\n.\r\n")
+                .unwrap(),
+            vec![
+                (1, "whqtswO00WBw418f9t5JxYwZ".to_string()),
+                (2, "QhdPYR:00WBw1Ph7x7".to_string())
+            ]
+        );
+        assert!(uidl_list(b"+OK\r\nfoo\r\n.\r\n").is_err());
+        let caps = capabilities(b"+OK Capability list follows\r\nTOP\r\nUSER\r\nSTLS\r\n.\r\n");
+        assert!(caps.contains("STLS"));
+        assert!(!caps.contains("UIDL"));
+    }
+}
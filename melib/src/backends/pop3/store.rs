@@ -0,0 +1,165 @@
+/*
+ * meli - pop3 module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Local copies of downloaded messages.
+//!
+//! Every message is stored in its own file, named after the hexadecimal
+//! encoding of its UIDL followed by maildir-style flags, e.g. `6162:2,S`.
+//! Since POP3 has no flags, this is also where flags are kept.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    email::Flag,
+    error::{Error, Result},
+};
+
+const FLAG_CHARS: &[(char, Flag)] = &[
+    ('D', Flag::DRAFT),
+    ('F', Flag::FLAGGED),
+    ('P', Flag::PASSED),
+    ('R', Flag::REPLIED),
+    ('S', Flag::SEEN),
+    ('T', Flag::TRASHED),
+];
+
+#[derive(Debug, Clone)]
+pub struct Pop3Store {
+    path: PathBuf,
+}
+
+impl Pop3Store {
+    /// Opens the store of `account_name` in the data directory, creating it
+    /// if needed.
+    pub fn new(account_name: &str) -> Result<Self> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", account_name)
+            .map_err(|err| Error::new(err.to_string()))?;
+        let path = data_dir.create_data_directory("pop3")?;
+        Ok(Pop3Store { path })
+    }
+
+    /// Returns the UIDL, path and flags of every stored message.
+    pub fn list(&self) -> Result<Vec<(String, PathBuf, Flag)>> {
+        let mut ret = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if let Some((uidl, flags)) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(parse_file_name)
+            {
+                ret.push((uidl, path, flags));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Stores a downloaded message and returns its path.
+    pub fn insert(&self, uidl: &str, bytes: &[u8], flags: Flag) -> Result<PathBuf> {
+        let path = self.path.join(file_name(uidl, flags));
+        /* Write to a temporary file first so that an interrupted download doesn't
+         * leave a truncated message behind. */
+        let tmp_path = self.path.join(format!(".{}.tmp", hex(uidl)));
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(path)
+    }
+
+    /// Renames the file at `path` to reflect `flags` and returns its new path.
+    pub fn set_flags(&self, path: &Path, flags: Flag) -> Result<PathBuf> {
+        let (uidl, _) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(parse_file_name)
+            .ok_or_else(|| Error::new(format!("Invalid POP3 message file name: {:?}", path)))?;
+        let new_path = self.path.join(file_name(&uidl, flags));
+        if new_path != path {
+            fs::rename(path, &new_path)?;
+        }
+        Ok(new_path)
+    }
+}
+
+/// Reads the flags of the message stored at `path`.
+pub fn path_flags(path: &Path) -> Flag {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(parse_file_name)
+        .map(|(_, flags)| flags)
+        .unwrap_or_default()
+}
+
+fn hex(uidl: &str) -> String {
+    uidl.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn file_name(uidl: &str, flags: Flag) -> String {
+    let mut ret = format!("{}:2,", hex(uidl));
+    for (c, f) in FLAG_CHARS {
+        if flags.contains(*f) {
+            ret.push(*c);
+        }
+    }
+    ret
+}
+
+fn parse_file_name(name: &str) -> Option<(String, Flag)> {
+    let (hex, flag_chars) = name.split_once(":2,")?;
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let mut flags = Flag::default();
+    for c in flag_chars.chars() {
+        if let Some((_, f)) = FLAG_CHARS.iter().find(|(fc, _)| *fc == c) {
+            flags |= *f;
+        }
+    }
+    Some((String::from_utf8(bytes).ok()?, flags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop3_store_file_name() {
+        let name = file_name("QhdPYR:00/WBw1", Flag::SEEN | Flag::FLAGGED);
+        assert!(!name[..name.len() - ":2,FS".len()].contains(['/', ':']));
+        assert!(name.ends_with(":2,FS"));
+        assert_eq!(
+            parse_file_name(&name),
+            Some(("QhdPYR:00/WBw1".to_string(), Flag::SEEN | Flag::FLAGGED))
+        );
+        assert_eq!(
+            parse_file_name(&file_name("a", Flag::default())),
+            Some(("a".to_string(), Flag::default()))
+        );
+        assert_eq!(parse_file_name(".61.tmp"), None);
+        assert_eq!(parse_file_name("6:2,S"), None);
+    }
+}
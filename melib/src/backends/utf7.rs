@@ -28,6 +28,7 @@
 //!
 //! Idea is based on Python [mutf7](https://github.com/cheshire-mouse/mutf7) library.
 
+use data_encoding::BASE64_NOPAD;
 use encoding_rs::UTF_16BE;
 use regex::{Captures, Regex};
 
@@ -100,8 +101,7 @@ fn encode_modified_utf7(text: &str) -> String {
     for value in text_u16 {
         input.extend_from_slice(&value.to_be_bytes());
     }
-    let text_u16 = base64::encode(input);
-    let text_u16 = text_u16.trim_end_matches('=');
+    let text_u16 = BASE64_NOPAD.encode(&input);
     let result = text_u16.replace('/', ",");
     format!("&{}-", result)
 }
@@ -128,13 +128,13 @@ fn decode_utf7_part(text: &str) -> String {
     }
 
     let text_mb64 = &text[1..text.len() - 1];
-    let mut text_b64 = text_mb64.replace(',', "/");
+    let text_b64 = text_mb64.replace(',', "/");
 
-    while (text_b64.len() % 4) != 0 {
-        text_b64 += "=";
-    }
-
-    let text_u16 = base64::decode(text_b64).unwrap();
+    /* Leave invalid sequences as they are instead of failing. */
+    let text_u16 = match BASE64_NOPAD.decode(text_b64.as_bytes()) {
+        Ok(v) if v.len() % 2 == 0 => v,
+        _ => return text.to_string(),
+    };
     let (cow, _encoding_used, _had_errors) = UTF_16BE.decode(&text_u16);
     let result = cow.as_ref();
 
@@ -184,4 +184,11 @@ mod tests {
     fn decode_consecutive_accents() {
         assert_eq!(decode_utf7_imap("th&AOkA4g-tre"), "théâtre")
     }
+
+    #[test]
+    fn decode_ampersand_and_invalid() {
+        assert_eq!(decode_utf7_imap("Tom &- Jerry"), "Tom & Jerry");
+        assert_eq!(encode_utf7_imap("Tom & Jerry"), "Tom &- Jerry");
+        assert_eq!(decode_utf7_imap("Invalid &A-"), "Invalid &A-");
+    }
 }
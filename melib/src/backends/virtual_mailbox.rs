@@ -0,0 +1,195 @@
+/*
+ * meli - backends
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Mailboxes defined by a search query in the account configuration instead
+//! of existing in the backend, e.g.
+//!
+//! ```toml
+//! [accounts.work.mailboxes."Unread"]
+//! query = "flags:unread"
+//! ```
+//!
+//! A [`VirtualMailbox`] holds no envelopes of its own: its contents are the
+//! envelopes of the account's other mailboxes that match its query, which
+//! the client keeps up to date.
+
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use super::{BackendMailbox, Mailbox, MailboxHash, MailboxPermissions, SpecialUsageMailbox};
+use crate::{conf::MailboxConf, error::Result, search::Query};
+
+#[derive(Debug, Clone)]
+pub struct VirtualMailbox {
+    hash: MailboxHash,
+    name: String,
+    path: String,
+    query: Query,
+    usage: Arc<RwLock<SpecialUsageMailbox>>,
+    unseen: Arc<Mutex<usize>>,
+    total: Arc<Mutex<usize>>,
+}
+
+impl VirtualMailbox {
+    /// Returns the virtual mailbox of configuration entry `path`, if `conf`
+    /// has a `query` value.
+    pub fn from_conf(path: &str, conf: &MailboxConf) -> Option<Result<Self>> {
+        let query_str = conf.extra.get("query")?;
+        Some(
+            Query::try_from(query_str.as_str()).map(|query| VirtualMailbox {
+                hash: MailboxHash::from_bytes(format!("virtual:{}", path).as_bytes()),
+                name: path.rsplit('/').next().unwrap_or(path).to_string(),
+                path: path.to_string(),
+                query,
+                usage: Arc::new(RwLock::new(conf.usage.unwrap_or_default())),
+                unseen: Default::default(),
+                total: Default::default(),
+            }),
+        )
+    }
+
+    /// Returns the virtual mailbox of the account's saved search `name`.
+    pub fn from_saved_search(name: &str, query: &str) -> Result<Self> {
+        Ok(VirtualMailbox {
+            hash: Self::saved_search_hash(name),
+            name: name.to_string(),
+            path: name.to_string(),
+            query: Query::try_from(query)?,
+            usage: Default::default(),
+            unseen: Default::default(),
+            total: Default::default(),
+        })
+    }
+
+    /// Returns the hash of the virtual mailbox of saved search `name`, which
+    /// can't clash with that of a mailbox of the same path.
+    pub fn saved_search_hash(name: &str) -> MailboxHash {
+        MailboxHash::from_bytes(format!("search:{}", name).as_bytes())
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Sets the message counts returned by [`BackendMailbox::count`].
+    pub fn set_count(&self, unseen: usize, total: usize) {
+        *self.unseen.lock().unwrap() = unseen;
+        *self.total.lock().unwrap() = total;
+    }
+}
+
+impl BackendMailbox for VirtualMailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &[]
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        None
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        *self.usage.read().unwrap()
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        MailboxPermissions {
+            set_flags: true,
+            ..MailboxPermissions::default()
+        }
+    }
+
+    fn is_subscribed(&self) -> bool {
+        true
+    }
+
+    fn set_is_subscribed(&mut self, _new_val: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_special_usage(&mut self, new_val: SpecialUsageMailbox) -> Result<()> {
+        *self.usage.write()? = new_val;
+        Ok(())
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok((*self.unseen.lock()?, *self.total.lock()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_mailbox_from_conf() {
+        let mut conf = MailboxConf::default();
+        assert!(VirtualMailbox::from_conf("Unread", &conf).is_none());
+
+        conf.extra
+            .insert("query".to_string(), "flags:unread".to_string());
+        let mailbox = VirtualMailbox::from_conf("Views/Unread", &conf)
+            .unwrap()
+            .unwrap();
+        assert_eq!(mailbox.name(), "Unread");
+        assert_eq!(mailbox.path(), "Views/Unread");
+        assert_eq!(mailbox.query(), &Query::try_from("flags:unread").unwrap());
+        assert_ne!(
+            mailbox.hash(),
+            MailboxHash::from_bytes("Views/Unread".as_bytes())
+        );
+        mailbox.set_count(1, 2);
+        assert_eq!(mailbox.count().unwrap(), (1, 2));
+
+        conf.extra
+            .insert("query".to_string(), "subject: test and".to_string());
+        assert!(VirtualMailbox::from_conf("Broken", &conf).unwrap().is_err());
+
+        let search = VirtualMailbox::from_saved_search("To do/urgent", "tag:todo").unwrap();
+        assert_eq!(search.name(), "To do/urgent");
+        assert_eq!(
+            search.hash(),
+            VirtualMailbox::saved_search_hash("To do/urgent")
+        );
+        assert_ne!(
+            search.hash(),
+            MailboxHash::from_bytes("To do/urgent".as_bytes())
+        );
+        assert!(VirtualMailbox::from_saved_search("Broken", "subject: test and").is_err());
+    }
+}
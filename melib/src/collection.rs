@@ -30,12 +30,16 @@ use smallvec::SmallVec;
 use super::*;
 use crate::backends::{MailboxHash, TagHash};
 
-pub type EnvelopeRef<'g> = RwRef<'g, EnvelopeHash, Envelope>;
-pub type EnvelopeRefMut<'g> = RwRefMut<'g, EnvelopeHash, Envelope>;
+mod store;
+pub use store::{BTreeStore, EnvelopeStore, MemoryStore, StoreKind};
+
+/// The envelopes of a [`Collection`], shared with the threads of its
+/// mailboxes.
+pub type Envelopes = Arc<RwLock<Box<dyn EnvelopeStore>>>;
 
 #[derive(Debug, Clone)]
 pub struct Collection {
-    pub envelopes: Arc<RwLock<HashMap<EnvelopeHash, Envelope>>>,
+    pub envelopes: Envelopes,
     pub message_id_index: Arc<RwLock<HashMap<Vec<u8>, EnvelopeHash>>>,
     pub threads: Arc<RwLock<HashMap<MailboxHash, Threads>>>,
     pub sent_mailbox: Arc<RwLock<Option<MailboxHash>>>,
@@ -75,6 +79,11 @@ impl Drop for Collection {
 
 impl Collection {
     pub fn new() -> Collection {
+        Self::with_store(Box::<MemoryStore>::default())
+    }
+
+    /// Creates a collection that keeps its envelopes in `store`.
+    pub fn with_store(store: Box<dyn EnvelopeStore>) -> Collection {
         let message_id_index = Arc::new(RwLock::new(HashMap::with_capacity_and_hasher(
             16,
             Default::default(),
@@ -89,7 +98,7 @@ impl Collection {
         )));
 
         Collection {
-            envelopes: Arc::new(RwLock::new(Default::default())),
+            envelopes: Arc::new(RwLock::new(store)),
             tag_index: Arc::new(RwLock::new(BTreeMap::default())),
             message_id_index,
             threads,
@@ -395,7 +404,9 @@ impl Collection {
         }
     }
 
-    pub fn insert(&self, envelope: Envelope, mailbox_hash: MailboxHash) -> bool {
+    /// Inserts `envelope` in `mailbox_hash` and returns the threads of the
+    /// mailbox that changed.
+    pub fn insert(&self, envelope: Envelope, mailbox_hash: MailboxHash) -> ThreadUpdate {
         let hash = envelope.hash();
         self.mailboxes
             .write()
@@ -405,7 +416,8 @@ impl Collection {
                 m.insert(hash);
             });
         self.envelopes.write().unwrap().insert(hash, envelope);
-        self.threads
+        let update = self
+            .threads
             .write()
             .unwrap()
             .entry(mailbox_hash)
@@ -420,7 +432,66 @@ impl Collection {
         {
             self.insert_reply(hash);
         }
-        false
+        update
+    }
+
+    /// Adds envelope `env_hash`, which must already be in the collection, to
+    /// `mailbox_hash` as well, e.g. to a virtual mailbox whose query it
+    /// matches. If it's already there, its thread is updated instead.
+    pub fn add_to_mailbox(&self, env_hash: EnvelopeHash, mailbox_hash: MailboxHash) {
+        debug_assert!(self.envelopes.read().unwrap().contains_key(&env_hash));
+        let is_new = self
+            .mailboxes
+            .write()
+            .unwrap()
+            .entry(mailbox_hash)
+            .or_default()
+            .insert(env_hash);
+        let mut threads_lck = self.threads.write().unwrap();
+        let threads = threads_lck.entry(mailbox_hash).or_default();
+        if is_new
+            || threads
+                .update_envelope(&self.envelopes, env_hash, env_hash)
+                .is_err()
+        {
+            threads.insert(&self.envelopes, env_hash);
+        }
+    }
+
+    /// Removes envelope `env_hash` from `mailbox_hash` only, leaving it in the
+    /// collection and in its other mailboxes.
+    pub fn remove_from_mailbox(&self, env_hash: EnvelopeHash, mailbox_hash: MailboxHash) {
+        if let Some(m) = self.mailboxes.write().unwrap().get_mut(&mailbox_hash) {
+            m.remove(&env_hash);
+        }
+        if let Some(t) = self.threads.write().unwrap().get_mut(&mailbox_hash) {
+            t.remove(env_hash);
+        }
+    }
+
+    /// Replaces the envelopes of `mailbox_hash` with `env_hashes`, which must
+    /// already be in the collection, and rebuilds its threads.
+    pub fn set_mailbox_envelopes(&self, mailbox_hash: MailboxHash, env_hashes: &[EnvelopeHash]) {
+        let mut ordered_hash_set = env_hashes.to_vec();
+        {
+            let envelopes_lck = self.envelopes.read().unwrap();
+            ordered_hash_set.retain(|h| envelopes_lck.contains_key(h));
+            ordered_hash_set.sort_by(|a, b| {
+                envelopes_lck[a]
+                    .date()
+                    .partial_cmp(&envelopes_lck[b].date())
+                    .unwrap()
+            });
+        }
+        let mut threads = Threads::new(ordered_hash_set.len());
+        for h in ordered_hash_set.iter() {
+            threads.insert(&self.envelopes, *h);
+        }
+        self.mailboxes
+            .write()
+            .unwrap()
+            .insert(mailbox_hash, ordered_hash_set.into_iter().collect());
+        self.threads.write().unwrap().insert(mailbox_hash, threads);
     }
 
     pub fn insert_reply(&self, env_hash: EnvelopeHash) {
@@ -430,8 +501,68 @@ impl Collection {
         }
     }
 
+    /// Adopts orphaned replies of `mailbox_hash` into their likely thread.
+    /// Returns `true` if any threads changed. See [`Threads::adopt_orphans`].
+    pub fn adopt_orphans(&self, mailbox_hash: MailboxHash) -> bool {
+        self.threads
+            .write()
+            .unwrap()
+            .get_mut(&mailbox_hash)
+            .map(|t| t.adopt_orphans(&self.envelopes) > 0)
+            .unwrap_or(false)
+    }
+
+    /// Applies the threading corrections of `overlay` to `mailbox_hash`.
+    /// Returns `true` if any threads changed.
+    pub fn apply_overlay(&self, mailbox_hash: MailboxHash, overlay: &ThreadOverlay) -> bool {
+        self.threads
+            .write()
+            .unwrap()
+            .get_mut(&mailbox_hash)
+            .map(|t| t.apply_overlay(&self.envelopes, overlay))
+            .unwrap_or(false)
+    }
+
+    /// Attaches the messages `env_hashes` to the thread of `mailbox_hash` as
+    /// replies to `parent`. Replies among `env_hashes` stay attached to their
+    /// parent message.
+    pub fn graft(
+        &self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &[EnvelopeHash],
+        parent: EnvelopeHash,
+    ) -> Result<()> {
+        let mut threads_lck = self.threads.write().unwrap();
+        let threads = threads_lck
+            .get_mut(&mailbox_hash)
+            .ok_or_else(|| Error::new("Mailbox is not loaded."))?;
+        let parent = threads
+            .envelope_node(parent)
+            .ok_or_else(|| Error::new("Target message is not in this mailbox."))?;
+        let nodes = env_hashes
+            .iter()
+            .filter_map(|h| threads.envelope_node(*h))
+            .filter(|id| *id != parent)
+            .collect::<Vec<ThreadNodeHash>>();
+        let mut topmost = vec![];
+        'nodes: for &id in &nodes {
+            let mut ancestor = threads[&id].parent();
+            while let Some(a) = ancestor {
+                if nodes.contains(&a) {
+                    continue 'nodes;
+                }
+                ancestor = threads[&a].parent();
+            }
+            topmost.push(id);
+        }
+        for id in topmost {
+            threads.reparent(&self.envelopes, id, Some(parent))?;
+        }
+        Ok(())
+    }
+
     pub fn get_env(&'_ self, hash: EnvelopeHash) -> EnvelopeRef<'_> {
-        let guard: RwLockReadGuard<'_, _> = self.envelopes.read().unwrap();
+        let guard = self.envelopes.read().unwrap();
         EnvelopeRef { guard, hash }
     }
 
@@ -467,6 +598,69 @@ impl Collection {
                 .insert(mailbox_hash, Threads::default());
         }
     }
+
+    /// Moves the envelopes into `store` and keeps them there from now on.
+    /// The store is shared by every clone of the collection.
+    pub fn set_store(&self, mut store: Box<dyn EnvelopeStore>) {
+        let mut envelopes = self.envelopes.write().unwrap();
+        let hashes = envelopes.keys().copied().collect::<Vec<EnvelopeHash>>();
+        for hash in hashes {
+            if let Some(envelope) = envelopes.remove(&hash) {
+                store.insert(hash, envelope);
+            }
+        }
+        *envelopes = store;
+    }
+}
+
+pub struct EnvelopeRef<'g> {
+    guard: RwLockReadGuard<'g, Box<dyn EnvelopeStore>>,
+    hash: EnvelopeHash,
+}
+
+impl Deref for EnvelopeRef<'_> {
+    type Target = Envelope;
+
+    fn deref(&self) -> &Envelope {
+        self.guard.get(&self.hash).expect("Hash was not found")
+    }
+}
+
+impl AsRef<Envelope> for EnvelopeRef<'_> {
+    fn as_ref(&self) -> &Envelope {
+        self.guard.get(&self.hash).expect("Hash was not found")
+    }
+}
+
+pub struct EnvelopeRefMut<'g> {
+    guard: RwLockWriteGuard<'g, Box<dyn EnvelopeStore>>,
+    hash: EnvelopeHash,
+}
+
+impl DerefMut for EnvelopeRefMut<'_> {
+    fn deref_mut(&mut self) -> &mut Envelope {
+        self.guard.get_mut(&self.hash).expect("Hash was not found")
+    }
+}
+
+impl Deref for EnvelopeRefMut<'_> {
+    type Target = Envelope;
+
+    fn deref(&self) -> &Envelope {
+        self.guard.get(&self.hash).expect("Hash was not found")
+    }
+}
+
+impl AsRef<Envelope> for EnvelopeRefMut<'_> {
+    fn as_ref(&self) -> &Envelope {
+        self.guard.get(&self.hash).expect("Hash was not found")
+    }
+}
+
+impl AsMut<Envelope> for EnvelopeRefMut<'_> {
+    fn as_mut(&mut self) -> &mut Envelope {
+        self.guard.get_mut(&self.hash).expect("Hash was not found")
+    }
 }
 
 pub struct RwRef<'g, K: std::cmp::Eq + std::hash::Hash, V> {
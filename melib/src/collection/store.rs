@@ -0,0 +1,204 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Storage of the envelopes of a [`Collection`](super::Collection).
+//!
+//! The collection keeps its envelopes in a [`Box<dyn
+//! EnvelopeStore>`](EnvelopeStore) so that accounts can pick a store other
+//! than the default in-memory [`MemoryStore`] with [`StoreKind`], for example
+//! one that keeps large archives on disk.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Index,
+};
+
+use crate::email::{Envelope, EnvelopeHash};
+
+/// The stores an account can keep its envelopes in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// [`MemoryStore`]
+    #[default]
+    Memory,
+    /// [`BTreeStore`]
+    BTree,
+}
+
+impl StoreKind {
+    /// Returns an empty store of this kind.
+    pub fn new_store(self) -> Box<dyn EnvelopeStore> {
+        match self {
+            StoreKind::Memory => Box::<MemoryStore>::default(),
+            StoreKind::BTree => Box::<BTreeStore>::default(),
+        }
+    }
+}
+
+/// A map from envelope hashes to envelopes.
+pub trait EnvelopeStore: std::fmt::Debug + Send + Sync {
+    fn get(&self, hash: &EnvelopeHash) -> Option<&Envelope>;
+    fn get_mut(&mut self, hash: &EnvelopeHash) -> Option<&mut Envelope>;
+    /// Inserts `envelope` under `hash`, returning the envelope it replaced.
+    fn insert(&mut self, hash: EnvelopeHash, envelope: Envelope) -> Option<Envelope>;
+    fn remove(&mut self, hash: &EnvelopeHash) -> Option<Envelope>;
+    fn len(&self) -> usize;
+    fn iter(&self) -> Box<dyn Iterator<Item = (&EnvelopeHash, &Envelope)> + '_>;
+
+    fn contains_key(&self, hash: &EnvelopeHash) -> bool {
+        self.get(hash).is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &EnvelopeHash> + '_> {
+        Box::new(self.iter().map(|(hash, _)| hash))
+    }
+
+    fn values(&self) -> Box<dyn Iterator<Item = &Envelope> + '_> {
+        Box::new(self.iter().map(|(_, envelope)| envelope))
+    }
+}
+
+impl Index<&EnvelopeHash> for dyn EnvelopeStore {
+    type Output = Envelope;
+
+    fn index(&self, hash: &EnvelopeHash) -> &Envelope {
+        self.get(hash).expect("Envelope hash was not found")
+    }
+}
+
+impl Default for Box<dyn EnvelopeStore> {
+    fn default() -> Self {
+        Box::<MemoryStore>::default()
+    }
+}
+
+/// Keeps all envelopes in a [`HashMap`].
+#[derive(Debug, Default)]
+pub struct MemoryStore(HashMap<EnvelopeHash, Envelope>);
+
+impl From<HashMap<EnvelopeHash, Envelope>> for MemoryStore {
+    fn from(map: HashMap<EnvelopeHash, Envelope>) -> Self {
+        MemoryStore(map)
+    }
+}
+
+impl EnvelopeStore for MemoryStore {
+    fn get(&self, hash: &EnvelopeHash) -> Option<&Envelope> {
+        self.0.get(hash)
+    }
+
+    fn get_mut(&mut self, hash: &EnvelopeHash) -> Option<&mut Envelope> {
+        self.0.get_mut(hash)
+    }
+
+    fn insert(&mut self, hash: EnvelopeHash, envelope: Envelope) -> Option<Envelope> {
+        self.0.insert(hash, envelope)
+    }
+
+    fn remove(&mut self, hash: &EnvelopeHash) -> Option<Envelope> {
+        self.0.remove(hash)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&EnvelopeHash, &Envelope)> + '_> {
+        Box::new(self.0.iter())
+    }
+
+    fn contains_key(&self, hash: &EnvelopeHash) -> bool {
+        self.0.contains_key(hash)
+    }
+}
+
+/// Keeps all envelopes in a [`BTreeMap`]. Unlike the table of a
+/// [`MemoryStore`], which is reallocated whole when it grows, it grows a node
+/// at a time, so loading a large archive doesn't briefly need twice the memory
+/// of its envelopes.
+#[derive(Debug, Default)]
+pub struct BTreeStore(BTreeMap<EnvelopeHash, Envelope>);
+
+impl EnvelopeStore for BTreeStore {
+    fn get(&self, hash: &EnvelopeHash) -> Option<&Envelope> {
+        self.0.get(hash)
+    }
+
+    fn get_mut(&mut self, hash: &EnvelopeHash) -> Option<&mut Envelope> {
+        self.0.get_mut(hash)
+    }
+
+    fn insert(&mut self, hash: EnvelopeHash, envelope: Envelope) -> Option<Envelope> {
+        self.0.insert(hash, envelope)
+    }
+
+    fn remove(&mut self, hash: &EnvelopeHash) -> Option<Envelope> {
+        self.0.remove(hash)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&EnvelopeHash, &Envelope)> + '_> {
+        Box::new(self.0.iter())
+    }
+
+    fn contains_key(&self, hash: &EnvelopeHash) -> bool {
+        self.0.contains_key(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collection_memory_store() {
+        for kind in [StoreKind::Memory, StoreKind::BTree] {
+            check_store(kind.new_store());
+        }
+    }
+
+    fn check_store(mut store: Box<dyn EnvelopeStore>) {
+        assert!(store.is_empty());
+        let (a, b) = (EnvelopeHash(1), EnvelopeHash(2));
+        assert!(store.insert(a, Envelope::new(a)).is_none());
+        assert!(store.insert(b, Envelope::new(b)).is_none());
+        assert!(store.insert(b, Envelope::new(b)).is_some());
+        assert_eq!(store.len(), 2);
+        assert!(store.contains_key(&a));
+        assert_eq!(store[&b].hash(), b);
+        let mut keys = store.keys().copied().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![a, b]);
+        store.get_mut(&a).unwrap().set_hash(EnvelopeHash(3));
+        assert_eq!(store.values().filter(|e| e.hash() == a).count(), 0);
+        assert!(store.remove(&a).is_some());
+        assert!(!store.contains_key(&a));
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -38,6 +38,11 @@ use libc::TCP_KEEPALIVE as KEEPALIVE_OPTION;
 use libc::TCP_KEEPIDLE as KEEPALIVE_OPTION;
 use libc::{self, c_int, c_void};
 
+pub mod dns;
+pub mod proxy;
+#[cfg(feature = "tls")]
+pub mod tls;
+
 #[derive(Debug)]
 pub enum Connection {
     Tcp(std::net::TcpStream),
@@ -262,18 +267,52 @@ impl std::os::unix::io::AsRawFd for Connection {
 pub fn lookup_ipv4(host: &str, port: u16) -> crate::Result<std::net::SocketAddr> {
     use std::net::ToSocketAddrs;
 
-    let addrs = (host, port).to_socket_addrs()?;
-    for addr in addrs {
-        if let std::net::SocketAddr::V4(_) = addr {
-            return Ok(addr);
+    dns::ADDRESS_CACHE.resolve((host.to_ascii_lowercase(), port), || {
+        let addrs = (host, port).to_socket_addrs()?;
+        for addr in addrs {
+            if let std::net::SocketAddr::V4(_) = addr {
+                return Ok((addr, dns::ADDRESS_TTL));
+            }
         }
-    }
 
-    Err(
-        crate::error::Error::new(format!("Could not lookup address {}:{}", host, port)).set_kind(
-            crate::error::ErrorKind::Network(crate::error::NetworkErrorKind::HostLookupFailed),
-        ),
-    )
+        Err(
+            crate::error::Error::new(format!("Could not lookup address {}:{}", host, port))
+                .set_kind(crate::error::ErrorKind::Network(
+                    crate::error::NetworkErrorKind::HostLookupFailed,
+                )),
+        )
+    })
+}
+
+/// Opens a TCP connection to `host:port`, through `proxy` if it is set.
+pub fn connect_tcp(
+    host: &str,
+    port: u16,
+    proxy: Option<&proxy::Proxy>,
+    timeout: Option<Duration>,
+) -> crate::Result<std::net::TcpStream> {
+    use crate::error::{Error, ErrorKind, NetworkErrorKind};
+
+    if let Some(proxy) = proxy {
+        return proxy.connect(host, port, timeout);
+    }
+    let addr = lookup_ipv4(host, port)?;
+    let res = if let Some(timeout) = timeout {
+        std::net::TcpStream::connect_timeout(&addr, timeout)
+    } else {
+        std::net::TcpStream::connect(addr)
+    };
+    res.map_err(|err| {
+        let kind = if err.kind() == std::io::ErrorKind::TimedOut {
+            ErrorKind::Timeout
+        } else {
+            ErrorKind::Network(NetworkErrorKind::ConnectionFailed)
+        };
+        Error::new(format!("Could not connect to {}:{}", host, port))
+            .set_details(err.to_string())
+            .set_source(Some(std::sync::Arc::new(err)))
+            .set_kind(kind)
+    })
 }
 
 use futures::future::{self, Either, Future};
@@ -0,0 +1,527 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! DNS SRV record lookups ([RFC 2782](https://www.rfc-editor.org/rfc/rfc2782))
+//! for mail service discovery ([RFC 6186](https://www.rfc-editor.org/rfc/rfc6186))
+//! and an in-process cache of DNS answers.
+//!
+//! The cache serves answers until their TTL expires. When a lookup fails, an
+//! expired answer is served for up to [`STALE_LIMIT`] so that reconnecting
+//! does not depend on a flaky resolver.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash, Hasher},
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, UdpSocket},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::error::{Error, ErrorKind, NetworkErrorKind, Result};
+
+/// How long expired answers are still served when a lookup fails.
+pub const STALE_LIMIT: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long host addresses from the system resolver are cached, since it does
+/// not report TTLs.
+pub const ADDRESS_TTL: Duration = Duration::from_secs(5 * 60);
+/// How long the absence of SRV records is cached.
+const NEGATIVE_TTL: Duration = Duration::from_secs(60);
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// Answers cached by host name and port.
+pub static ADDRESS_CACHE: DnsCache<(String, u16), SocketAddr> = DnsCache::new();
+/// SRV records cached by service name.
+pub static SRV_CACHE: DnsCache<String, Vec<SrvRecord>> = DnsCache::new();
+
+/// A cache of DNS answers with expiry times.
+#[derive(Debug)]
+pub struct DnsCache<K, V> {
+    entries: Mutex<Option<HashMap<K, (V, Instant)>>>,
+}
+
+impl<K, V> DnsCache<K, V> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(None),
+        }
+    }
+}
+
+impl<K, V> Default for DnsCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> DnsCache<K, V> {
+    /// Returns the cached answer for `key` if it has not expired, and calls
+    /// `lookup` otherwise. `lookup` returns the answer and its TTL. If it
+    /// fails, an expired answer is returned if it expired less than
+    /// [`STALE_LIMIT`] ago.
+    pub fn resolve(&self, key: K, lookup: impl FnOnce() -> Result<(V, Duration)>) -> Result<V> {
+        self.resolve_at(key, Instant::now(), lookup)
+    }
+
+    fn resolve_at(
+        &self,
+        key: K,
+        now: Instant,
+        lookup: impl FnOnce() -> Result<(V, Duration)>,
+    ) -> Result<V> {
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|entries| entries.get(&key).cloned());
+        if let Some((value, expires)) = cached.as_ref() {
+            if now < *expires {
+                return Ok(value.clone());
+            }
+        }
+        match lookup() {
+            Ok((value, ttl)) => {
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .get_or_insert_with(HashMap::default)
+                    .insert(key, (value.clone(), now + ttl));
+                Ok(value)
+            }
+            Err(err) => match cached {
+                Some((value, expires)) if now.duration_since(expires) < STALE_LIMIT => {
+                    log::warn!("DNS lookup failed, using expired answer: {}", err);
+                    Ok(value)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Forgets all cached answers.
+    pub fn clear(&self) {
+        *self.entries.lock().unwrap() = None;
+    }
+}
+
+/// A SRV record: `target:port` provides the service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Looks up the SRV records of `name`, e.g. `_imaps._tcp.example.com`, in the
+/// order they should be tried. Resolves to an empty list if the service is
+/// not provided.
+pub fn lookup_srv(name: &str) -> Result<Vec<SrvRecord>> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    SRV_CACHE.resolve(name.clone(), || {
+        let nameservers = nameservers();
+        let mut last_err = None;
+        for nameserver in nameservers {
+            match query_srv(&name, nameserver) {
+                Ok((records, ttl)) => return Ok((records, ttl)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| Error::new("No nameservers configured."))
+            .set_kind(ErrorKind::Network(NetworkErrorKind::HostLookupFailed)))
+    })
+}
+
+/// Returns the host and port of the preferred SRV record of `service` (e.g.
+/// `_submission._tcp`) for `domain`, if any.
+pub fn srv_target(service: &str, domain: &str) -> Result<Option<(String, u16)>> {
+    Ok(lookup_srv(&format!("{}.{}", service, domain))?
+        .into_iter()
+        .next()
+        .map(|r| (r.target, r.port)))
+}
+
+/// Where to connect to for a service located with [`resolve_service`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceEndpoint {
+    /// The host to open the TCP connection to.
+    pub host: String,
+    pub port: u16,
+    /// The name the server certificate must be valid for. SRV answers are
+    /// not authenticated, so this is always the configured domain and never
+    /// the SRV target ([RFC 6186 section 6](https://www.rfc-editor.org/rfc/rfc6186#section-6)).
+    pub tls_domain: String,
+}
+
+/// Returns the target of the first of `services` with SRV records for
+/// `domain`, or `domain:port` if there are none or the lookups fail.
+pub fn resolve_service(services: &[&str], domain: &str, port: u16) -> ServiceEndpoint {
+    for service in services {
+        match srv_target(service, domain) {
+            Ok(Some((host, port))) => {
+                log::trace!("Using {}:{} from {}.{}", host, port, service, domain);
+                return ServiceEndpoint {
+                    host,
+                    port,
+                    tls_domain: domain.to_string(),
+                };
+            }
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("Could not look up {}.{}: {}", service, domain, err);
+            }
+        }
+    }
+    ServiceEndpoint {
+        host: domain.to_string(),
+        port,
+        tls_domain: domain.to_string(),
+    }
+}
+
+/// Reads the nameservers of `/etc/resolv.conf`, defaulting to the local host.
+fn nameservers() -> Vec<SocketAddr> {
+    let ret = std::fs::read_to_string("/etc/resolv.conf")
+        .map(|s| parse_resolv_conf(&s))
+        .unwrap_or_default();
+    if ret.is_empty() {
+        vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53)]
+    } else {
+        ret
+    }
+}
+
+fn parse_resolv_conf(input: &str) -> Vec<SocketAddr> {
+    input
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next()? != "nameserver" {
+                return None;
+            }
+            /* Drop IPv6 zone indices such as `%eth0`. */
+            let addr = fields.next()?.split('%').next()?;
+            Some(SocketAddr::new(addr.parse().ok()?, 53))
+        })
+        .collect()
+}
+
+fn query_srv(name: &str, nameserver: SocketAddr) -> Result<(Vec<SrvRecord>, Duration)> {
+    let id = {
+        let mut hasher = RandomState::new().build_hasher();
+        name.hash(&mut hasher);
+        hasher.finish() as u16
+    };
+    let query = build_query(id, name, TYPE_SRV)?;
+    let socket = UdpSocket::bind(if nameserver.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })?;
+    socket.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    socket.connect(nameserver)?;
+    socket.send(&query)?;
+    let mut buf = vec![0; 4096];
+    let len = socket.recv(&mut buf)?;
+    buf.truncate(len);
+    const TRUNCATED: u8 = 0x02;
+    if buf.get(2).map_or(false, |flags| flags & TRUNCATED != 0) {
+        /* Retry over TCP. */
+        let mut stream = TcpStream::connect_timeout(&nameserver, QUERY_TIMEOUT)?;
+        stream.set_read_timeout(Some(QUERY_TIMEOUT))?;
+        stream.set_write_timeout(Some(QUERY_TIMEOUT))?;
+        let mut request = (query.len() as u16).to_be_bytes().to_vec();
+        request.extend_from_slice(&query);
+        stream.write_all(&request)?;
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        buf = vec![0; usize::from(u16::from_be_bytes(len))];
+        stream.read_exact(&mut buf)?;
+    }
+    parse_srv_response(&buf, id)
+}
+
+fn build_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>> {
+    const RECURSION_DESIRED: u16 = 0x0100;
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&RECURSION_DESIRED.to_be_bytes());
+    /* One question, no answer, authority or additional records. */
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        if label.len() > 63 {
+            return Err(Error::new(format!("Invalid domain name {}", name)));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Reads the possibly compressed domain name at `offset` of `msg`, returning
+/// it and the offset after it.
+fn read_name(msg: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = vec![];
+    let mut end = None;
+    /* Bound the number of compression pointers followed to avoid loops. */
+    for _ in 0..128 {
+        let len = *msg.get(offset)?;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(offset + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let pointer = usize::from(u16::from_be_bytes([len & 0x3f, *msg.get(offset + 1)?]));
+                end.get_or_insert(offset + 2);
+                offset = pointer;
+            }
+            len => {
+                let label = msg.get(offset + 1..offset + 1 + usize::from(len))?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                offset += 1 + usize::from(len);
+            }
+        }
+    }
+    None
+}
+
+fn parse_srv_response(msg: &[u8], id: u16) -> Result<(Vec<SrvRecord>, Duration)> {
+    let invalid = || Error::new("Invalid DNS response.");
+    let u16_at = |offset: usize| -> Result<u16> {
+        msg.get(offset..offset + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(invalid)
+    };
+    if msg.len() < 12 || u16_at(0)? != id {
+        return Err(invalid());
+    }
+    const NXDOMAIN: u16 = 3;
+    match u16_at(2)? & 0x000f {
+        0 => {}
+        NXDOMAIN => return Ok((vec![], NEGATIVE_TTL)),
+        rcode => {
+            return Err(Error::new(format!(
+                "DNS server returned an error (RCODE {}).",
+                rcode
+            )))
+        }
+    }
+    let questions = u16_at(4)?;
+    let answers = u16_at(6)?;
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(msg, offset).ok_or_else(invalid)?.1 + 4;
+    }
+    let mut records = vec![];
+    let mut ttl: Option<u32> = None;
+    for _ in 0..answers {
+        offset = read_name(msg, offset).ok_or_else(invalid)?.1;
+        let rtype = u16_at(offset)?;
+        let record_ttl = u32::from(u16_at(offset + 4)?) << 16 | u32::from(u16_at(offset + 6)?);
+        let rdlength = usize::from(u16_at(offset + 8)?);
+        let rdata = offset + 10;
+        offset = rdata + rdlength;
+        if offset > msg.len() {
+            return Err(invalid());
+        }
+        if rtype != TYPE_SRV {
+            continue;
+        }
+        let target = read_name(msg, rdata + 6).ok_or_else(invalid)?.0;
+        ttl = Some(ttl.map_or(record_ttl, |ttl| ttl.min(record_ttl)));
+        /* A target of "." means the service is decidedly not available. */
+        if target.is_empty() {
+            continue;
+        }
+        records.push(SrvRecord {
+            priority: u16_at(rdata)?,
+            weight: u16_at(rdata + 2)?,
+            port: u16_at(rdata + 4)?,
+            target,
+        });
+    }
+    records.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| b.weight.cmp(&a.weight))
+    });
+    Ok((
+        records,
+        ttl.map_or(NEGATIVE_TTL, |ttl| Duration::from_secs(u64::from(ttl))),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dns_srv_response() {
+        assert_eq!(
+            parse_resolv_conf(
+                "# comment\nsearch example.com\nnameserver 192.0.2.1\nnameserver fe80::1%eth0\n"
+            ),
+            vec![
+                "192.0.2.1:53".parse().unwrap(),
+                "[fe80::1]:53".parse().unwrap()
+            ]
+        );
+
+        let query = build_query(0x1234, "_imaps._tcp.example.com", TYPE_SRV).unwrap();
+        assert_eq!(query.len(), 12 + 25 + 4);
+        assert_eq!(
+            read_name(&query, 12),
+            Some(("_imaps._tcp.example.com".to_string(), 12 + 25))
+        );
+
+        let mut response = query.clone();
+        /* Response flags and two answers. */
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = 2;
+        for (priority, weight, ttl, target) in [
+            (20_u16, 0_u16, 300_u32, &b"\x05imap2\xc0\x18"[..]),
+            (10, 5, 3600, b"\x04imap\xc0\x18"),
+        ] {
+            /* Pointer to the question name. */
+            response.extend_from_slice(&[0xc0, 0x0c]);
+            response.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            response.extend_from_slice(&CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&ttl.to_be_bytes());
+            response.extend_from_slice(&(6 + target.len() as u16).to_be_bytes());
+            response.extend_from_slice(&priority.to_be_bytes());
+            response.extend_from_slice(&weight.to_be_bytes());
+            response.extend_from_slice(&993_u16.to_be_bytes());
+            response.extend_from_slice(target);
+        }
+        let (records, ttl) = parse_srv_response(&response, 0x1234).unwrap();
+        assert_eq!(ttl, Duration::from_secs(300));
+        assert_eq!(
+            records,
+            vec![
+                SrvRecord {
+                    priority: 10,
+                    weight: 5,
+                    port: 993,
+                    target: "imap.example.com".to_string(),
+                },
+                SrvRecord {
+                    priority: 20,
+                    weight: 0,
+                    port: 993,
+                    target: "imap2.example.com".to_string(),
+                },
+            ]
+        );
+        assert!(parse_srv_response(&response, 0x4321).is_err());
+        assert!(parse_srv_response(&response[..response.len() - 3], 0x1234).is_err());
+
+        let mut nxdomain = query;
+        nxdomain[3] = 0x83;
+        assert_eq!(
+            parse_srv_response(&nxdomain, 0x1234).unwrap(),
+            (vec![], NEGATIVE_TTL)
+        );
+
+        /* A pointer loop. */
+        assert_eq!(read_name(&[0xc0, 0x00], 0), None);
+    }
+
+    #[test]
+    fn test_dns_cache() {
+        let cache: DnsCache<&str, u32> = DnsCache::new();
+        let now = Instant::now();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.resolve_at("a", now, || Ok((1, ttl))).unwrap(), 1);
+        /* Fresh answers are served from the cache. */
+        assert_eq!(
+            cache
+                .resolve_at("a", now + ttl / 2, || Ok((2, ttl)))
+                .unwrap(),
+            1
+        );
+        /* Expired answers are refreshed. */
+        assert_eq!(
+            cache.resolve_at("a", now + ttl, || Ok((3, ttl))).unwrap(),
+            3
+        );
+        /* ...or served if the lookup fails. */
+        assert_eq!(
+            cache
+                .resolve_at("a", now + 3 * ttl, || Err(Error::new("timed out")))
+                .unwrap(),
+            3
+        );
+        assert!(cache
+            .resolve_at("a", now + ttl * 2 + STALE_LIMIT, || Err(Error::new(
+                "timed out"
+            )))
+            .is_err());
+        assert!(cache
+            .resolve_at("b", now, || Err(Error::new("timed out")))
+            .is_err());
+        cache.clear();
+        assert_eq!(cache.resolve_at("a", now, || Ok((4, ttl))).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_dns_resolve_service_tls_domain() {
+        let domain = "srv-tls-domain.meli.invalid";
+        SRV_CACHE
+            .resolve(format!("_imaps._tcp.{}", domain), || {
+                Ok((
+                    vec![SrvRecord {
+                        priority: 0,
+                        weight: 0,
+                        port: 1993,
+                        target: "mail.elsewhere.invalid".to_string(),
+                    }],
+                    Duration::from_secs(60),
+                ))
+            })
+            .unwrap();
+        /* The connection goes to the SRV target, but its certificate is
+         * checked against the configured domain. */
+        assert_eq!(
+            resolve_service(&["_imaps._tcp"], domain, 993),
+            ServiceEndpoint {
+                host: "mail.elsewhere.invalid".to_string(),
+                port: 1993,
+                tls_domain: domain.to_string(),
+            }
+        );
+        assert_eq!(
+            resolve_service(&[], domain, 993),
+            ServiceEndpoint {
+                host: domain.to_string(),
+                port: 993,
+                tls_domain: domain.to_string(),
+            }
+        );
+    }
+}
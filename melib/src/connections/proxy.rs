@@ -0,0 +1,572 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! SOCKS5 ([RFC 1928](https://www.rfc-editor.org/rfc/rfc1928)) and HTTP
+//! `CONNECT` proxies for backend connections.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{IpAddr, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use data_encoding::BASE64;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    conf::AccountSettings,
+    error::{Error, ErrorKind, NetworkErrorKind, Result},
+};
+
+/// The address of the SOCKS port of a local Tor daemon.
+pub const TOR_PROXY: &str = "socks5h://127.0.0.1:9050";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// SOCKS5, resolving host names locally.
+    Socks5,
+    /// SOCKS5, resolving host names through the proxy.
+    Socks5h,
+    /// HTTP `CONNECT` tunnel. Host names are resolved by the proxy.
+    Http,
+}
+
+/// A proxy server, set with the `proxy` account setting as a URL of the form
+/// `scheme://[user[:password]@]host[:port]`, or `tor`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("kind", &self.kind)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .finish()
+    }
+}
+
+impl fmt::Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}://{}",
+            self.scheme(),
+            authority(&self.host, self.port)
+        )
+    }
+}
+
+impl FromStr for Proxy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("tor") {
+            return TOR_PROXY.parse();
+        }
+        let (scheme, rest) = s
+            .split_once("://")
+            .ok_or_else(|| Error::new(format!("`{}` is not a proxy URL", s)))?;
+        let (kind, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "socks5" => (ProxyKind::Socks5, 1080),
+            "socks5h" => (ProxyKind::Socks5h, 1080),
+            "http" => (ProxyKind::Http, 8080),
+            other => {
+                return Err(Error::new(format!(
+                    "unsupported proxy scheme `{}`, expected one of `socks5`, `socks5h` or `http`",
+                    other
+                )))
+            }
+        };
+        let rest = rest.trim_end_matches('/');
+        let (userinfo, hostport) = match rest.rsplit_once('@') {
+            Some((userinfo, hostport)) => (Some(userinfo), hostport),
+            None => (None, rest),
+        };
+        let (username, password) = match userinfo.map(|u| u.split_once(':').unwrap_or((u, ""))) {
+            Some((username, password)) => (
+                Some(username.to_string()),
+                Some(password.to_string()).filter(|p| !p.is_empty()),
+            ),
+            None => (None, None),
+        };
+        let (host, port) = if let Some(bracketed) = hostport.strip_prefix('[') {
+            let (host, port) = bracketed
+                .split_once(']')
+                .ok_or_else(|| Error::new(format!("`{}` is not a proxy URL", s)))?;
+            (host, port.strip_prefix(':'))
+        } else {
+            match hostport.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (hostport, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(Error::new(format!("`{}` has no proxy host", s)));
+        }
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|err| Error::new(format!("`{}` has an invalid port: {}", s, err)))?,
+            None => default_port,
+        };
+        Ok(Self {
+            kind,
+            host: host.to_string(),
+            port,
+            username,
+            password,
+        })
+    }
+}
+
+impl Serialize for Proxy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut url = format!("{}://", self.scheme());
+        if let Some(username) = self.username.as_ref() {
+            url.push_str(username);
+            if let Some(password) = self.password.as_ref() {
+                url.push(':');
+                url.push_str(password);
+            }
+            url.push('@');
+        }
+        url.push_str(&authority(&self.host, self.port));
+        serializer.serialize_str(&url)
+    }
+}
+
+impl<'de> Deserialize<'de> for Proxy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = <String>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Proxy {
+    /// Reads the `proxy` setting of an account.
+    pub fn new(s: &AccountSettings) -> Result<Option<Self>> {
+        s.extra
+            .get("proxy")
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| {
+                v.parse().map_err(|err: Error| {
+                    Error::new(format!(
+                        "Configuration error ({}): Invalid value for field `proxy`: {}\n{}",
+                        s.name, v, err
+                    ))
+                    .set_kind(ErrorKind::Configuration)
+                })
+            })
+            .transpose()
+    }
+
+    fn scheme(&self) -> &'static str {
+        match self.kind {
+            ProxyKind::Socks5 => "socks5",
+            ProxyKind::Socks5h => "socks5h",
+            ProxyKind::Http => "http",
+        }
+    }
+
+    /// Opens a connection to `host:port` through the proxy. Unless the proxy
+    /// is a `socks5` proxy, `host` is resolved by the proxy.
+    pub fn connect(&self, host: &str, port: u16, timeout: Option<Duration>) -> Result<TcpStream> {
+        let io_err = |summary: String, err: std::io::Error| {
+            let kind = if matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) {
+                ErrorKind::Timeout
+            } else {
+                ErrorKind::Network(NetworkErrorKind::ConnectionFailed)
+            };
+            Error::new(summary)
+                .set_details(err.to_string())
+                .set_source(Some(Arc::new(err)))
+                .set_kind(kind)
+        };
+        let proxy_err = || format!("Could not connect to proxy {}", self);
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|err| io_err(proxy_err(), err))?
+            .next()
+            .ok_or_else(|| {
+                Error::new(proxy_err())
+                    .set_details(format!("Could not lookup address {}", self.host))
+                    .set_kind(ErrorKind::Network(NetworkErrorKind::HostLookupFailed))
+            })?;
+        let mut stream = if let Some(timeout) = timeout {
+            TcpStream::connect_timeout(&addr, timeout)
+        } else {
+            TcpStream::connect(addr)
+        }
+        .map_err(|err| io_err(proxy_err(), err))?;
+        let tunnel_err = || format!("Could not connect to {}:{} through {}", host, port, self);
+        set_timeouts(&stream, timeout).map_err(|err| io_err(tunnel_err(), err))?;
+        match self.kind {
+            ProxyKind::Socks5 | ProxyKind::Socks5h => {
+                self.socks5_handshake(&mut stream, host, port)
+            }
+            ProxyKind::Http => self.http_connect(&mut stream, host, port),
+        }
+        .map_err(|err| io_err(tunnel_err(), err))?;
+        set_timeouts(&stream, None).map_err(|err| io_err(tunnel_err(), err))?;
+        Ok(stream)
+    }
+
+    fn socks5_handshake<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        host: &str,
+        port: u16,
+    ) -> std::io::Result<()> {
+        const VERSION: u8 = 0x05;
+        const NO_AUTHENTICATION: u8 = 0x00;
+        const USERNAME_PASSWORD: u8 = 0x02;
+        const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+        const CONNECT: u8 = 0x01;
+        const IPV4: u8 = 0x01;
+        const DOMAINNAME: u8 = 0x03;
+        const IPV6: u8 = 0x04;
+
+        if self.username.is_some() {
+            stream.write_all(&[VERSION, 2, NO_AUTHENTICATION, USERNAME_PASSWORD])?;
+        } else {
+            stream.write_all(&[VERSION, 1, NO_AUTHENTICATION])?;
+        }
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(proxy_io_err("the proxy is not a SOCKS5 proxy"));
+        }
+        match reply[1] {
+            NO_AUTHENTICATION => {}
+            USERNAME_PASSWORD => {
+                /* RFC 1929 */
+                let username = self.username.as_deref().unwrap_or_default().as_bytes();
+                let password = self.password.as_deref().unwrap_or_default().as_bytes();
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(proxy_io_err("the proxy username or password is too long"));
+                }
+                let mut request = vec![0x01, username.len() as u8];
+                request.extend_from_slice(username);
+                request.push(password.len() as u8);
+                request.extend_from_slice(password);
+                stream.write_all(&request)?;
+                stream.read_exact(&mut reply)?;
+                if reply[1] != 0x00 {
+                    return Err(proxy_io_err("the proxy rejected the username or password"));
+                }
+            }
+            NO_ACCEPTABLE_METHODS if self.username.is_none() => {
+                return Err(proxy_io_err("the proxy requires authentication"));
+            }
+            _ => {
+                return Err(proxy_io_err(
+                    "the proxy does not support any offered authentication method",
+                ));
+            }
+        }
+
+        let mut request = vec![VERSION, CONNECT, 0x00];
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) if self.kind == ProxyKind::Socks5 => Some(
+                (host, port)
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("could not lookup address {}", host),
+                        )
+                    })?
+                    .ip(),
+            ),
+            Err(_) => None,
+        };
+        match ip {
+            Some(IpAddr::V4(ip)) => {
+                request.push(IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Some(IpAddr::V6(ip)) => {
+                request.push(IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            None => {
+                if host.len() > 255 {
+                    return Err(proxy_io_err("the host name is too long"));
+                }
+                request.push(DOMAINNAME);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request)?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0x00 {
+            return Err(proxy_io_err(match reply[1] {
+                0x02 => "connection not allowed by the proxy ruleset",
+                0x03 => "network unreachable",
+                0x04 => "host unreachable",
+                0x05 => "connection refused",
+                0x06 => "TTL expired",
+                0x07 => "command not supported by the proxy",
+                0x08 => "address type not supported by the proxy",
+                _ => "general SOCKS server failure",
+            }));
+        }
+        /* Skip the bound address and port. */
+        let bound_len = match reply[3] {
+            IPV4 => 4,
+            IPV6 => 16,
+            DOMAINNAME => {
+                let mut len = [0; 1];
+                stream.read_exact(&mut len)?;
+                usize::from(len[0])
+            }
+            _ => return Err(proxy_io_err("invalid SOCKS5 reply")),
+        };
+        let mut bound = vec![0; bound_len + 2];
+        stream.read_exact(&mut bound)?;
+        Ok(())
+    }
+
+    fn http_connect<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        host: &str,
+        port: u16,
+    ) -> std::io::Result<()> {
+        let authority = authority(host, port);
+        let mut request = format!(
+            "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n",
+            authority = authority
+        );
+        if let Some(username) = self.username.as_ref() {
+            request.push_str(&format!(
+                "Proxy-Authorization: Basic {}\r\n",
+                BASE64.encode(
+                    format!(
+                        "{}:{}",
+                        username,
+                        self.password.as_deref().unwrap_or_default()
+                    )
+                    .as_bytes()
+                )
+            ));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.flush()?;
+
+        /* Read the response headers one byte at a time so that none of the
+         * tunneled data is consumed. */
+        let mut response = Vec::with_capacity(256);
+        let mut byte = [0; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() > 8 * 1024 {
+                return Err(proxy_io_err("the proxy response headers are too long"));
+            }
+            stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+        if !status_line.starts_with("HTTP/") || !status.starts_with('2') {
+            return Err(proxy_io_err(&format!(
+                "the proxy refused the connection: {}",
+                status_line
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Formats `host:port`, with IPv6 addresses in brackets.
+fn authority(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn set_timeouts(stream: &TcpStream, timeout: Option<Duration>) -> std::io::Result<()> {
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)
+}
+
+fn proxy_io_err(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A stream that replays `input` and records what is written to it.
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mock(input: &[u8]) -> MockStream {
+        MockStream {
+            input: Cursor::new(input.to_vec()),
+            output: vec![],
+        }
+    }
+
+    #[test]
+    fn test_proxy_parse() {
+        let tor: Proxy = "tor".parse().unwrap();
+        assert_eq!(tor.kind, ProxyKind::Socks5h);
+        assert_eq!(tor.to_string(), TOR_PROXY);
+
+        let proxy: Proxy = "http://user:p@ss@proxy.example.com".parse().unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Http);
+        assert_eq!(proxy.host, "proxy.example.com");
+        assert_eq!(proxy.port, 8080);
+        assert_eq!(proxy.username.as_deref(), Some("user"));
+        assert_eq!(proxy.password.as_deref(), Some("p@ss"));
+        assert_eq!(proxy.to_string(), "http://proxy.example.com:8080");
+        assert!(!format!("{:?}", proxy).contains("p@ss"));
+
+        let proxy: Proxy = "socks5://[::1]:1081".parse().unwrap();
+        assert_eq!(proxy.kind, ProxyKind::Socks5);
+        assert_eq!(proxy.host, "::1");
+        assert_eq!(proxy.port, 1081);
+        assert_eq!(proxy.username, None);
+
+        assert!("ftp://proxy".parse::<Proxy>().is_err());
+        assert!("proxy:1080".parse::<Proxy>().is_err());
+        assert!("socks5://proxy:port".parse::<Proxy>().is_err());
+    }
+
+    #[test]
+    fn test_proxy_socks5_handshake() {
+        let proxy: Proxy = "socks5h://localhost".parse().unwrap();
+        let mut stream = mock(&[
+            0x05, 0x00, /* method selection */
+            0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0x04, 0x38, /* reply */
+            b'*',
+        ]);
+        proxy
+            .socks5_handshake(&mut stream, "imap.example.com", 993)
+            .unwrap();
+        let mut expected = vec![0x05, 0x01, 0x00, 0x05, 0x01, 0x00, 0x03, 16];
+        expected.extend_from_slice(b"imap.example.com");
+        expected.extend_from_slice(&[0x03, 0xe1]);
+        assert_eq!(stream.output, expected);
+        /* Data after the reply is left for the tunneled protocol. */
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"*");
+
+        let proxy: Proxy = "socks5://u:pw@localhost".parse().unwrap();
+        let mut stream = mock(&[
+            0x05, 0x02, /* method selection */
+            0x01, 0x00, /* authentication */
+            0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0, /* reply */
+        ]);
+        proxy
+            .socks5_handshake(&mut stream, "10.0.0.1", 143)
+            .unwrap();
+        assert_eq!(
+            stream.output,
+            [
+                &[0x05, 0x02, 0x00, 0x02][..],
+                &[0x01, 1, b'u', 2, b'p', b'w'],
+                &[0x05, 0x01, 0x00, 0x01, 10, 0, 0, 1, 0x00, 143],
+            ]
+            .concat()
+        );
+
+        let mut stream = mock(&[0x05, 0x00, 0x05, 0x05, 0x00, 0x01]);
+        let err = proxy
+            .socks5_handshake(&mut stream, "10.0.0.1", 143)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "connection refused");
+    }
+
+    #[test]
+    fn test_proxy_http_connect() {
+        let proxy: Proxy = "http://user:pw@localhost:3128".parse().unwrap();
+        let mut stream = mock(b"HTTP/1.1 200 Connection established\r\n\r\n* OK");
+        proxy
+            .http_connect(&mut stream, "imap.example.com", 993)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(stream.output.clone()).unwrap(),
+            format!(
+                "CONNECT imap.example.com:993 HTTP/1.1\r\nHost: imap.example.com:993\r\n\
+                 Proxy-Authorization: Basic {}\r\n\r\n",
+                BASE64.encode(b"user:pw")
+            )
+        );
+        let mut rest = vec![];
+        stream.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"* OK");
+
+        let mut stream = mock(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+        assert!(proxy
+            .http_connect(&mut stream, "imap.example.com", 993)
+            .unwrap_err()
+            .to_string()
+            .contains("407"));
+    }
+}
@@ -0,0 +1,338 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Per-account TLS options: minimum protocol version, certificate and public
+//! key pinning and client certificates.
+
+use std::path::PathBuf;
+
+use data_encoding::{BASE64, HEXLOWER_PERMISSIVE};
+use native_tls::{Protocol, TlsConnector, TlsStream};
+use openssl::{sha::sha256, x509::X509};
+
+use crate::{
+    conf::AccountSettings,
+    error::{Error, ErrorKind, NetworkErrorKind, Result, ResultIntoError},
+    ShellExpandTrait,
+};
+
+/// A client certificate to authenticate with.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// A PEM certificate if `key_path` is set, a PKCS #12 archive otherwise.
+    pub path: PathBuf,
+    /// The PEM PKCS #8 private key of the certificate.
+    pub key_path: Option<PathBuf>,
+    /// The password of the PKCS #12 archive.
+    pub password: String,
+}
+
+/// TLS options of a server connection, read from the account settings.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub danger_accept_invalid_certs: bool,
+    pub min_version: Option<Protocol>,
+    /// SHA-256 digests of accepted server certificates.
+    pub pinned_certificates: Vec<[u8; 32]>,
+    /// SHA-256 digests of the DER SubjectPublicKeyInfo of accepted server
+    /// public keys.
+    pub pinned_public_keys: Vec<[u8; 32]>,
+    pub client_certificate: Option<ClientCertificate>,
+}
+
+impl TlsOptions {
+    /// The account settings read by [`TlsOptions::new`].
+    pub const KEYS: &'static [&'static str] = &[
+        "danger_accept_invalid_certs",
+        "tls_min_version",
+        "tls_pinned_certificates",
+        "tls_pinned_public_keys",
+        "tls_client_certificate",
+        "tls_client_key",
+        "tls_client_certificate_password",
+    ];
+
+    pub fn new(s: &AccountSettings) -> Result<Self> {
+        let conf_err = |key: &str, value: &str, err: &dyn std::fmt::Display| {
+            Error::new(format!(
+                "Configuration error ({}): Invalid value for field `{}`: {}\n{}",
+                s.name, key, value, err
+            ))
+            .set_kind(ErrorKind::Configuration)
+        };
+        let mut ret = Self::default();
+        if let Some(v) = s.extra.get("danger_accept_invalid_certs") {
+            ret.danger_accept_invalid_certs = v
+                .parse()
+                .map_err(|err| conf_err("danger_accept_invalid_certs", v, &err))?;
+        }
+        if let Some(v) = s.extra.get("tls_min_version") {
+            ret.min_version =
+                Some(parse_tls_version(v).map_err(|err| conf_err("tls_min_version", v, &err))?);
+        }
+        if let Some(v) = s.extra.get("tls_pinned_certificates") {
+            ret.pinned_certificates = parse_list(v)
+                .into_iter()
+                .map(parse_certificate_fingerprint)
+                .collect::<Result<_>>()
+                .map_err(|err| conf_err("tls_pinned_certificates", v, &err))?;
+        }
+        if let Some(v) = s.extra.get("tls_pinned_public_keys") {
+            ret.pinned_public_keys = parse_list(v)
+                .into_iter()
+                .map(parse_public_key_pin)
+                .collect::<Result<_>>()
+                .map_err(|err| conf_err("tls_pinned_public_keys", v, &err))?;
+        }
+        if let Some(v) = s.extra.get("tls_client_certificate") {
+            ret.client_certificate = Some(ClientCertificate {
+                path: PathBuf::from(v).expand(),
+                key_path: s
+                    .extra
+                    .get("tls_client_key")
+                    .map(|k| PathBuf::from(k).expand()),
+                password: s
+                    .extra
+                    .get("tls_client_certificate_password")
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        } else if s.extra.contains_key("tls_client_key") {
+            return Err(Error::new(format!(
+                "Configuration error ({}): `tls_client_key` is set but `tls_client_certificate` \
+                 is not.",
+                s.name
+            ))
+            .set_kind(ErrorKind::Configuration));
+        }
+        Ok(ret)
+    }
+
+    fn is_pinned(&self) -> bool {
+        !self.pinned_certificates.is_empty() || !self.pinned_public_keys.is_empty()
+    }
+
+    /// Builds a connector with these options. When certificates or public
+    /// keys are pinned, the certificate chain is not verified;
+    /// [`TlsOptions::verify_peer`] checks the pins instead.
+    pub fn connector(&self) -> Result<TlsConnector> {
+        let mut connector = TlsConnector::builder();
+        if self.danger_accept_invalid_certs || self.is_pinned() {
+            connector.danger_accept_invalid_certs(true);
+        }
+        connector.min_protocol_version(self.min_version);
+        if let Some(client_certificate) = self.client_certificate.as_ref() {
+            let cert = std::fs::read(&client_certificate.path).chain_err_summary(|| {
+                format!(
+                    "Could not read client certificate {}",
+                    client_certificate.path.display()
+                )
+            })?;
+            let identity = if let Some(key_path) = client_certificate.key_path.as_ref() {
+                let key = std::fs::read(key_path).chain_err_summary(|| {
+                    format!("Could not read client key {}", key_path.display())
+                })?;
+                native_tls::Identity::from_pkcs8(&cert, &key)
+            } else {
+                native_tls::Identity::from_pkcs12(&cert, &client_certificate.password)
+            }
+            .chain_err_summary(|| {
+                format!(
+                    "Could not load client certificate {}",
+                    client_certificate.path.display()
+                )
+            })
+            .chain_err_kind(ErrorKind::Network(NetworkErrorKind::BadClientCertificate))?;
+            connector.identity(identity);
+        }
+        connector
+            .build()
+            .chain_err_kind(ErrorKind::Network(NetworkErrorKind::InvalidTLSConnection))
+    }
+
+    /// Checks the server certificate of an established connection against
+    /// the pinned certificates and public keys, if any.
+    pub fn verify_peer<S: std::io::Read + std::io::Write>(
+        &self,
+        host: &str,
+        stream: &TlsStream<S>,
+    ) -> Result<()> {
+        if !self.is_pinned() {
+            return Ok(());
+        }
+        let cert = stream
+            .peer_certificate()?
+            .ok_or_else(|| Error::new(format!("{} did not present a certificate.", host)))?
+            .to_der()?;
+        let (cert_digest, key_digest) = certificate_digests(&cert)?;
+        if self.pinned_certificates.contains(&cert_digest)
+            || self.pinned_public_keys.contains(&key_digest)
+        {
+            return Ok(());
+        }
+        Err(Error::new(format!(
+            "The certificate of {} does not match any pinned certificate or public key.",
+            host
+        ))
+        .set_details(format!(
+            "Its SHA-256 fingerprint is {} and its public key pin is {}.",
+            cert_digest
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+            format!("sha256//{}", BASE64.encode(&key_digest))
+        ))
+        .set_kind(ErrorKind::Network(NetworkErrorKind::BadServerCertificate)))
+    }
+}
+
+/// Splits a list setting, given either as a TOML array or as a comma
+/// separated string.
+fn parse_list(value: &str) -> Vec<&str> {
+    value
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|v| v.trim().trim_matches('"').trim())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn parse_tls_version(value: &str) -> Result<Protocol> {
+    match value
+        .trim()
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+    {
+        "1" | "1.0" => Ok(Protocol::Tlsv10),
+        "1.1" => Ok(Protocol::Tlsv11),
+        "1.2" => Ok(Protocol::Tlsv12),
+        _ => Err(Error::new("expected one of `1.0`, `1.1` or `1.2`")),
+    }
+}
+
+/// Parses a hexadecimal SHA-256 fingerprint, with optional colons, e.g. as
+/// printed by `openssl x509 -noout -fingerprint -sha256`.
+fn parse_certificate_fingerprint(value: &str) -> Result<[u8; 32]> {
+    let hex = value.replace(':', "");
+    let bytes = HEXLOWER_PERMISSIVE
+        .decode(hex.as_bytes())
+        .map_err(|err| Error::new(format!("`{}` is not a hexadecimal digest: {}", value, err)))?;
+    digest_from_slice(value, &bytes)
+}
+
+/// Parses a public key pin in the `sha256//<base64 digest>` format used by
+/// curl's `--pinnedpubkey`.
+fn parse_public_key_pin(value: &str) -> Result<[u8; 32]> {
+    let b64 = value.strip_prefix("sha256//").ok_or_else(|| {
+        Error::new(format!(
+            "`{}` is not a public key pin of the form sha256//<base64>",
+            value
+        ))
+    })?;
+    let bytes = BASE64
+        .decode(b64.as_bytes())
+        .map_err(|err| Error::new(format!("`{}` is not valid base64: {}", value, err)))?;
+    digest_from_slice(value, &bytes)
+}
+
+fn digest_from_slice(value: &str, bytes: &[u8]) -> Result<[u8; 32]> {
+    if bytes.len() != 32 {
+        return Err(Error::new(format!(
+            "`{}` is not a SHA-256 digest: it is {} bytes long instead of 32",
+            value,
+            bytes.len()
+        )));
+    }
+    let mut ret = [0; 32];
+    ret.copy_from_slice(bytes);
+    Ok(ret)
+}
+
+/// Returns the SHA-256 digests of a DER X.509 certificate and of its DER
+/// SubjectPublicKeyInfo.
+fn certificate_digests(cert: &[u8]) -> Result<([u8; 32], [u8; 32])> {
+    let public_key = X509::from_der(cert)?.public_key()?.public_key_to_der()?;
+    Ok((sha256(cert), sha256(&public_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_options_parsing() {
+        let digest = sha256(b"abc");
+        assert_eq!(
+            parse_certificate_fingerprint(
+                "BA:78:16:BF:8F:01:CF:EA:41:41:40:DE:5D:AE:22:23:B0:03:61:A3:96:17:7A:9C:B4:10:\
+                 FF:61:F2:00:15:AD"
+            )
+            .unwrap(),
+            digest
+        );
+        assert!(parse_certificate_fingerprint("BA:78").is_err());
+        assert_eq!(
+            parse_public_key_pin(&format!("sha256//{}", BASE64.encode(&digest))).unwrap(),
+            digest
+        );
+        assert!(parse_public_key_pin(&BASE64.encode(&digest)).is_err());
+        assert_eq!(
+            parse_list(r#"["sha256//a", "sha256//b"]"#),
+            vec!["sha256//a", "sha256//b"]
+        );
+        assert_eq!(parse_list("a,b ,"), vec!["a", "b"]);
+        assert!(matches!(
+            parse_tls_version("TLSv1.2").unwrap(),
+            Protocol::Tlsv12
+        ));
+        assert!(matches!(
+            parse_tls_version("1.1").unwrap(),
+            Protocol::Tlsv11
+        ));
+        assert!(parse_tls_version("1.4").is_err());
+
+        assert!(certificate_digests(b"not a certificate").is_err());
+
+        let key = openssl::pkey::PKey::from_ec_key(
+            openssl::ec::EcKey::generate(
+                &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::X9_62_PRIME256V1)
+                    .unwrap(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let mut cert = X509::builder().unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&openssl::asn1::Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&openssl::asn1::Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, openssl::hash::MessageDigest::sha256())
+            .unwrap();
+        let cert = cert.build().to_der().unwrap();
+        assert_eq!(
+            certificate_digests(&cert).unwrap(),
+            (sha256(&cert), sha256(&key.public_key_to_der().unwrap()))
+        );
+    }
+}
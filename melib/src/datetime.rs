@@ -39,7 +39,7 @@
 //! ```
 use std::{
     borrow::Cow,
-    convert::TryInto,
+    convert::{TryFrom, TryInto},
     ffi::{CStr, CString},
     os::raw::c_int,
 };
@@ -75,6 +75,8 @@ extern "C" {
 
     fn localtime_r(timep: *const libc::time_t, tm: *mut libc::tm) -> *mut libc::tm;
 
+    fn gmtime_r(timep: *const libc::time_t, tm: *mut libc::tm) -> *mut libc::tm;
+
     fn gettimeofday(tv: *mut libc::timeval, tz: *mut libc::timezone) -> i32;
 }
 
@@ -180,6 +182,34 @@ pub fn timestamp_to_string(timestamp: UnixTimestamp, fmt: Option<&str>, posix: b
         let i: i64 = timestamp.try_into().unwrap_or(0);
         localtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
     }
+    tm_to_string(&new_tm, fmt, posix)
+}
+
+/// Like [`timestamp_to_string`], but in the timezone that is `offset` seconds
+/// east of UTC instead of the local timezone.
+pub fn timestamp_to_string_with_offset(
+    timestamp: UnixTimestamp,
+    offset: i64,
+    fmt: Option<&str>,
+    posix: bool,
+) -> String {
+    let mut new_tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let i: i64 = i64::try_from(timestamp).unwrap_or(0) + offset;
+        gmtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
+    }
+    /* `%z` and `%Z` are printed from these fields. */
+    let zone = if offset == 0 {
+        CString::new("UTC").unwrap()
+    } else {
+        CString::new(utc_offset_to_string(offset)).unwrap()
+    };
+    new_tm.tm_gmtoff = offset as _;
+    new_tm.tm_zone = zone.as_ptr() as _;
+    tm_to_string(&new_tm, fmt, posix)
+}
+
+fn tm_to_string(new_tm: &libc::tm, fmt: Option<&str>, posix: bool) -> String {
     let format: Cow<'_, CStr> = if let Some(cs) = fmt
         .map(str::as_bytes)
         .map(CStr::from_bytes_with_nul)
@@ -218,7 +248,7 @@ pub fn timestamp_to_string(timestamp: UnixTimestamp, fmt: Option<&str>, posix: b
                 vec.as_mut_ptr() as *mut _,
                 256,
                 format.as_ptr(),
-                &new_tm as *const _,
+                new_tm as *const _,
             )
         }
     };
@@ -226,6 +256,84 @@ pub fn timestamp_to_string(timestamp: UnixTimestamp, fmt: Option<&str>, posix: b
     String::from_utf8_lossy(&vec[0..ret]).into_owned()
 }
 
+/// Parses a UTC offset such as `+0200`, `-05:30`, `Z` or a timezone
+/// abbreviation such as `UTC` or `CET` into seconds east of UTC.
+pub fn parse_utc_offset(s: &str) -> Option<i64> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+    let sign = match s.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => {
+            let abbr = s.to_ascii_uppercase();
+            let idx = TIMEZONE_ABBR
+                .binary_search_by(|probe| probe.0.cmp(abbr.as_bytes()))
+                .ok()?;
+            let (hr_offset, min_offset) = TIMEZONE_ABBR[idx].1;
+            return Some((hr_offset as i64) * 60 * 60 + (min_offset as i64) * 60);
+        }
+    };
+    let digits = s[1..].replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours = digits[..2].parse::<i64>().ok()?;
+    let minutes = digits[2..].parse::<i64>().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 * 60 + minutes * 60))
+}
+
+/// Formats a UTC offset in seconds east of UTC like `+0200`.
+pub fn utc_offset_to_string(offset: i64) -> String {
+    format!(
+        "{}{:02}{:02}",
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / (60 * 60),
+        (offset.abs() / 60) % 60
+    )
+}
+
+/// Returns the UTC offset of an RFC 822 `Date:` header value, e.g. `-0800` of
+/// `Wed, 8 Jan 2020 10:44:03 -0800 (PST)`.
+pub fn rfc822_utc_offset(date: &str) -> Option<i64> {
+    date.split_whitespace()
+        .rev()
+        .find(|t| !t.starts_with('(') && !t.ends_with(')'))
+        .and_then(parse_utc_offset)
+}
+
+/// Returns the day of the week (`0` is Monday) and the hour of `timestamp` in
+/// local time.
+pub fn timestamp_to_weekday_hour(timestamp: UnixTimestamp) -> (usize, usize) {
+    let mut new_tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let i: i64 = timestamp.try_into().unwrap_or(0);
+        localtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
+    }
+    (
+        (new_tm.tm_wday as usize + 6) % 7,
+        (new_tm.tm_hour as usize) % 24,
+    )
+}
+
+/// Returns the day of the week (`0` is Monday) and the minute of the day of
+/// `timestamp` in local time.
+pub fn timestamp_to_weekday_minute(timestamp: UnixTimestamp) -> (usize, usize) {
+    let mut new_tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let i: i64 = timestamp.try_into().unwrap_or(0);
+        localtime_r(&i as *const i64, &mut new_tm as *mut libc::tm);
+    }
+    (
+        (new_tm.tm_wday as usize + 6) % 7,
+        ((new_tm.tm_hour as usize) % 24) * 60 + (new_tm.tm_min as usize) % 60,
+    )
+}
+
 fn tm_to_secs(tm: libc::tm) -> std::result::Result<i64, ()> {
     let mut is_leap = false;
     let mut year = tm.tm_year;
@@ -474,6 +582,8 @@ where
         if ret.is_null() {
             return Ok(None);
         }
+        /* Let mktime(3) work out whether daylight saving time is in effect. */
+        new_tm.tm_isdst = -1;
         Ok(Some(mktime(&new_tm as *const _) as u64))
     }
 }
@@ -496,6 +606,30 @@ fn test_datetime_timestamp() {
     timestamp_to_string(0, None, false);
 }
 
+#[test]
+fn test_datetime_utc_offset() {
+    assert_eq!(parse_utc_offset("+0200"), Some(2 * 60 * 60));
+    assert_eq!(parse_utc_offset("-05:30"), Some(-(5 * 60 * 60 + 30 * 60)));
+    assert_eq!(parse_utc_offset("UTC"), Some(0));
+    assert_eq!(parse_utc_offset("+2"), None);
+    assert_eq!(parse_utc_offset("CET"), Some(60 * 60));
+    assert_eq!(parse_utc_offset("Mars"), None);
+    assert_eq!(utc_offset_to_string(-(5 * 60 * 60 + 30 * 60)), "-0530");
+    assert_eq!(
+        rfc822_utc_offset("Wed, 8 Jan 2020 10:44:03 -0800 (PST)"),
+        Some(-8 * 60 * 60)
+    );
+    assert_eq!(rfc822_utc_offset("Wed, 8 Jan 2020 10:44:03 GMT"), Some(0));
+    assert_eq!(
+        timestamp_to_string_with_offset(1578509043, -8 * 60 * 60, Some(RFC822_DATE), true),
+        "Wed, 08 Jan 2020 10:44:03 -0800"
+    );
+    assert_eq!(
+        timestamp_to_string_with_offset(1578509043, 0, Some("%H:%M %Z"), true),
+        "18:44 UTC"
+    );
+}
+
 #[test]
 fn test_datetime_rfcs() {
     if unsafe { libc::setlocale(libc::LC_ALL, b"\0".as_ptr() as _) }.is_null() {
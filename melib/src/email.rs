@@ -101,12 +101,14 @@ pub mod attachment_types;
 pub mod attachments;
 pub mod compose;
 pub mod headers;
+pub mod html;
+pub mod interner;
 pub mod list_management;
 pub mod mailto;
 pub mod parser;
 pub mod pgp;
 
-use std::{borrow::Cow, convert::TryInto, ops::Deref};
+use std::{borrow::Cow, convert::TryInto, ops::Deref, sync::Arc};
 
 pub use address::{Address, MessageID, References, StrBuild, StrBuilder};
 pub use attachments::{Attachment, AttachmentBuilder};
@@ -223,7 +225,8 @@ pub struct Envelope {
     pub to: SmallVec<[Address; 1]>,
     pub cc: SmallVec<[Address; 1]>,
     pub bcc: Vec<Address>,
-    pub subject: Option<String>,
+    pub subject: Option<Arc<str>>,
+    pub list_id: Option<Arc<str>>,
     pub message_id: MessageID,
     pub in_reply_to: Option<MessageID>,
     pub references: Option<References>,
@@ -267,6 +270,7 @@ impl Envelope {
             cc: SmallVec::new(),
             bcc: Vec::new(),
             subject: None,
+            list_id: None,
             message_id: MessageID::default(),
             in_reply_to: None,
             references: None,
@@ -341,6 +345,9 @@ impl Envelope {
                 if let Ok((_, value)) = parse_result {
                     self.set_subject(value);
                 };
+            } else if name == "list-id" {
+                self.set_list_id(value);
+                continue;
             } else if name == "message-id" {
                 self.set_message_id(value);
             } else if name == "references" {
@@ -556,11 +563,17 @@ impl Envelope {
 
     pub fn subject(&self) -> Cow<str> {
         match self.subject {
-            Some(ref s) => Cow::from(s),
+            Some(ref s) => Cow::from(&**s),
             _ => Cow::from(String::new()),
         }
     }
 
+    /// The value of the `List-Id` header. It is not kept in
+    /// [`other_headers`](Self::other_headers).
+    pub fn list_id(&self) -> Option<&str> {
+        self.list_id.as_deref()
+    }
+
     pub fn in_reply_to(&self) -> Option<&MessageID> {
         self.in_reply_to
             .as_ref()
@@ -597,22 +610,26 @@ impl Envelope {
         self
     }
 
-    pub fn set_bcc(&mut self, new_val: Vec<Address>) -> &mut Self {
+    pub fn set_bcc(&mut self, mut new_val: Vec<Address>) -> &mut Self {
+        interner::intern_addresses(&mut new_val);
         self.bcc = new_val;
         self
     }
 
-    pub fn set_cc(&mut self, new_val: SmallVec<[Address; 1]>) -> &mut Self {
+    pub fn set_cc(&mut self, mut new_val: SmallVec<[Address; 1]>) -> &mut Self {
+        interner::intern_addresses(&mut new_val);
         self.cc = new_val;
         self
     }
 
-    pub fn set_from(&mut self, new_val: SmallVec<[Address; 1]>) -> &mut Self {
+    pub fn set_from(&mut self, mut new_val: SmallVec<[Address; 1]>) -> &mut Self {
+        interner::intern_addresses(&mut new_val);
         self.from = new_val;
         self
     }
 
-    pub fn set_to(&mut self, new_val: SmallVec<[Address; 1]>) -> &mut Self {
+    pub fn set_to(&mut self, mut new_val: SmallVec<[Address; 1]>) -> &mut Self {
+        interner::intern_addresses(&mut new_val);
         self.to = new_val;
         self
     }
@@ -647,7 +664,18 @@ impl Envelope {
             new_val.pop();
         }
 
-        self.subject = Some(new_val);
+        self.subject = Some(interner::intern_str(&new_val));
+        self
+    }
+
+    pub fn set_list_id(&mut self, new_val: &[u8]) -> &mut Self {
+        let new_val = parser::encodings::phrase(new_val, false)
+            .map(|(_, value)| {
+                String::from_utf8(value)
+                    .unwrap_or_else(|err| String::from_utf8_lossy(&err.into_bytes()).into())
+            })
+            .unwrap_or_else(|_| String::from_utf8_lossy(new_val).into());
+        self.list_id = Some(interner::intern_str(&new_val));
         self
     }
 
@@ -25,13 +25,14 @@ use std::{
     collections::HashSet,
     convert::TryFrom,
     hash::{Hash, Hasher},
+    sync::Arc,
 };
 
 use super::*;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GroupAddress {
-    pub raw: Vec<u8>,
+    pub raw: Arc<[u8]>,
     pub display_name: StrBuilder,
     pub mailbox_list: Vec<Address>,
 }
@@ -41,7 +42,7 @@ pub struct GroupAddress {
  * Container for an address.
  *
  * ```text
- * >           raw: Vec<u8>
+ * >           raw: Arc<[u8]>
  * > ┌──────────┴────────────┐
  * > Name <address@domain.tld>
  * > └─┬┘  └──────────┬─────┘
@@ -50,7 +51,7 @@ pub struct GroupAddress {
  * >            address_spec
  *
  *
- * >           raw: Vec<u8>
+ * >           raw: Arc<[u8]>
  * > ┌──────────┴────────────────────┐
  * > "Name Name2" <address@domain.tld>
  * >  └─────┬──┘   └──────────┬─────┘
@@ -60,7 +61,7 @@ pub struct GroupAddress {
  * ```
  */
 pub struct MailboxAddress {
-    pub raw: Vec<u8>,
+    pub raw: Arc<[u8]>,
     pub display_name: StrBuilder,
     pub address_spec: StrBuilder,
 }
@@ -110,7 +111,7 @@ impl Address {
     pub fn new(display_name: Option<String>, address: String) -> Self {
         Address::Mailbox(if let Some(d) = display_name {
             MailboxAddress {
-                raw: format!("{} <{}>", d, address).into_bytes(),
+                raw: format!("{} <{}>", d, address).into_bytes().into(),
                 display_name: StrBuilder {
                     offset: 0,
                     length: d.len(),
@@ -122,7 +123,7 @@ impl Address {
             }
         } else {
             MailboxAddress {
-                raw: address.to_string().into_bytes(),
+                raw: address.to_string().into_bytes().into(),
                 display_name: StrBuilder {
                     offset: 0,
                     length: 0,
@@ -146,7 +147,8 @@ impl Address {
                     .collect::<Vec<String>>()
                     .join(",")
             )
-            .into_bytes(),
+            .into_bytes()
+            .into(),
             display_name: StrBuilder {
                 offset: 0,
                 length: display_name.len(),
@@ -157,8 +159,8 @@ impl Address {
 
     pub fn raw(&self) -> &[u8] {
         match self {
-            Address::Mailbox(m) => m.raw.as_slice(),
-            Address::Group(g) => g.raw.as_slice(),
+            Address::Mailbox(m) => &m.raw,
+            Address::Group(g) => &g.raw,
         }
     }
 
@@ -495,7 +497,7 @@ macro_rules! make_address {
     ($d:expr, $a:expr) => {
         Address::Mailbox(if $d.is_empty() {
             MailboxAddress {
-                raw: format!("{}", $a).into_bytes(),
+                raw: format!("{}", $a).into_bytes().into(),
                 display_name: StrBuilder {
                     offset: 0,
                     length: 0,
@@ -507,7 +509,7 @@ macro_rules! make_address {
             }
         } else {
             MailboxAddress {
-                raw: format!("{} <{}>", $d, $a).into_bytes(),
+                raw: format!("{} <{}>", $d, $a).into_bytes().into(),
                 display_name: StrBuilder {
                     offset: 0,
                     length: $d.len(),
@@ -56,6 +56,7 @@ pub enum Charset {
     BIG5,
     ISO2022JP,
     EUCJP,
+    ShiftJIS,
     KOI8R,
     KOI8U,
 }
@@ -159,6 +160,15 @@ impl<'a> From<&'a [u8]> for Charset {
             b if b.eq_ignore_ascii_case(b"big5") => Charset::BIG5,
             b if b.eq_ignore_ascii_case(b"iso-2022-jp") => Charset::ISO2022JP,
             b if b.eq_ignore_ascii_case(b"euc-jp") => Charset::EUCJP,
+            b if b.eq_ignore_ascii_case(b"shift_jis")
+                || b.eq_ignore_ascii_case(b"shift-jis")
+                || b.eq_ignore_ascii_case(b"sjis")
+                || b.eq_ignore_ascii_case(b"x-sjis")
+                || b.eq_ignore_ascii_case(b"windows-31j")
+                || b.eq_ignore_ascii_case(b"cp932") =>
+            {
+                Charset::ShiftJIS
+            }
             b if b.eq_ignore_ascii_case(b"koi8-r") => Charset::KOI8R,
             b if b.eq_ignore_ascii_case(b"koi8-u") => Charset::KOI8U,
             _ => {
@@ -169,6 +179,23 @@ impl<'a> From<&'a [u8]> for Charset {
     }
 }
 
+impl str::FromStr for Charset {
+    type Err = crate::error::Error;
+
+    /// Parse a charset name, returning an error instead of falling back to
+    /// `us-ascii` for unknown names.
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        let charset = Charset::from(s.as_bytes());
+        if charset == Charset::Ascii
+            && !s.trim().eq_ignore_ascii_case("us-ascii")
+            && !s.trim().eq_ignore_ascii_case("ascii")
+        {
+            return Err(crate::error::Error::new(format!("Unknown charset `{}`", s)));
+        }
+        Ok(charset)
+    }
+}
+
 impl Display for Charset {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
@@ -198,6 +225,7 @@ impl Display for Charset {
             Charset::BIG5 => write!(f, "big5"),
             Charset::ISO2022JP => write!(f, "iso-2022-jp"),
             Charset::EUCJP => write!(f, "euc-jp"),
+            Charset::ShiftJIS => write!(f, "shift_jis"),
             Charset::KOI8R => write!(f, "koi8-r"),
             Charset::KOI8U => write!(f, "koi8-u"),
         }
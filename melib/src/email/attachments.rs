@@ -155,6 +155,29 @@ impl AttachmentBuilder {
         &self.content_transfer_encoding
     }
 
+    /// The filename presented to recipients, from `Content-Disposition` or
+    /// else from the `name` parameter of `Content-Type`.
+    pub fn filename(&self) -> Option<&str> {
+        self.content_disposition
+            .filename
+            .as_deref()
+            .or_else(|| self.content_type.name())
+    }
+
+    /// Sets the filename in both `Content-Disposition` and the `name`
+    /// parameter of `Content-Type`, where the latter has one.
+    pub fn set_filename(&mut self, val: Option<String>) -> &mut Self {
+        match self.content_type {
+            ContentType::Other { ref mut name, .. }
+            | ContentType::OctetStream { ref mut name, .. } => {
+                *name = val.clone();
+            }
+            _ => {}
+        }
+        self.content_disposition.filename = val;
+        self
+    }
+
     pub fn set_content_type_from_bytes(&mut self, value: &[u8]) -> &mut Self {
         match parser::attachments::content_type(value) {
             Ok((_, (ct, cst, params))) => {
@@ -977,7 +1000,31 @@ impl Attachment {
         };
 
         let mut ret = if self.content_type.is_text() {
-            if let Ok(v) = parser::encodings::decode_charset(&bytes, charset) {
+            /* Fall back to charset detection if the declared (or default) charset does
+             * not match the content or decodes it to implausible text, unless the
+             * user has forced a specific charset. */
+            let declared = if options.force_charset.is_none()
+                && matches!(charset, Charset::UTF8 | Charset::Ascii)
+                && str::from_utf8(&bytes).is_err()
+            {
+                None
+            } else if options.force_charset.is_none() {
+                let charset = parser::encodings::detect_misdeclared_charset(&bytes, charset)
+                    .unwrap_or(charset);
+                parser::encodings::decode_charset(&bytes, charset).ok()
+            } else {
+                parser::encodings::decode_charset(&bytes, charset).ok()
+            };
+            let decoded = declared
+                .or_else(|| {
+                    if options.force_charset.is_some() {
+                        return None;
+                    }
+                    parser::encodings::detect_charset(&bytes)
+                        .and_then(|c| parser::encodings::decode_charset(&bytes, c).ok())
+                })
+                .or_else(|| parser::encodings::decode_charset(&bytes, charset).ok());
+            if let Some(v) = decoded {
                 v.into_bytes()
             } else {
                 self.body().to_vec()
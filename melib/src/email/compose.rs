@@ -33,12 +33,15 @@ use xdg_utils::query_mime_info;
 use super::*;
 use crate::{
     email::{
-        attachment_types::{Charset, ContentTransferEncoding, ContentType, MultipartType},
+        attachment_types::{
+            Charset, ContentDispositionKind, ContentTransferEncoding, ContentType, MultipartType,
+        },
         attachments::AttachmentBuilder,
     },
     shellexpand::ShellExpandTrait,
 };
 
+pub mod lint;
 pub mod mime;
 pub mod random;
 
@@ -292,6 +295,69 @@ impl Draft {
         ret
     }
 
+    /// Returns the headers of the draft as text, one `Name: value` line per
+    /// header, to be edited and passed to [`Draft::update_headers`].
+    pub fn headers_to_edit_string(&self) -> String {
+        let mut ret = String::new();
+        for (k, v) in self.headers.deref() {
+            ret.push_str(&format!("{}: {}\n", k, v));
+        }
+        ret
+    }
+
+    /// Replaces the headers of the draft with the header block `value`.
+    ///
+    /// Lines starting with whitespace continue the previous header and empty
+    /// lines are ignored. Address headers must hold valid address lists and
+    /// `References`/`In-Reply-To` valid message identifiers. If validation
+    /// fails the draft is left unchanged and the error mentions the offending
+    /// line. Returns whether the headers have changed.
+    pub fn update_headers(&mut self, value: &str) -> Result<bool> {
+        let mut headers: Vec<(usize, &str, String)> = vec![];
+        for (i, line) in value.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with([' ', '\t']) {
+                let (_, _, value) = headers.last_mut().ok_or_else(|| {
+                    Error::new(format!(
+                        "line {}: continuation line without a preceding header",
+                        i + 1
+                    ))
+                })?;
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(line.trim());
+                continue;
+            }
+            let (name, value) = line.split_once(':').ok_or_else(|| {
+                Error::new(format!(
+                    "line {}: expected `Name: value`, found {:?}",
+                    i + 1,
+                    line
+                ))
+            })?;
+            if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic()) {
+                return Err(Error::new(format!(
+                    "line {}: invalid header name {:?}",
+                    i + 1,
+                    name
+                )));
+            }
+            headers.push((i + 1, name, value.trim().to_string()));
+        }
+        let mut new_headers = HeaderMap::default();
+        for (line, name, value) in headers {
+            validate_header_value(name, &value)
+                .map_err(|err| Error::new(format!("line {}: {}", line, err)))?;
+            new_headers.insert(name.try_into()?, value);
+        }
+        let changes: bool = self.headers != new_headers;
+        self.headers = new_headers;
+        Ok(changes)
+    }
+
     pub fn finalise(mut self) -> Result<String> {
         let mut ret = String::new();
         let has_from: bool = self.headers.contains_key("From");
@@ -362,6 +428,42 @@ impl Draft {
     }
 }
 
+/// Checks that the value of a header edited with [`Draft::update_headers`]
+/// can be parsed.
+fn validate_header_value(name: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    if ["From", "To", "Cc", "Bcc", "Reply-To", "Sender"]
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(name))
+    {
+        match parser::address::rfc2822address_list(value.as_bytes()) {
+            Ok((rest, _)) if rest.trim().is_empty() => {}
+            _ => {
+                return Err(Error::new(format!(
+                    "invalid address list in `{}`: {:?}",
+                    name, value
+                )))
+            }
+        }
+    } else if ["References", "In-Reply-To"]
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(name))
+    {
+        match parser::address::msg_id_list(value.as_bytes()) {
+            Ok((rest, list)) if rest.trim().is_empty() && !list.is_empty() => {}
+            _ => {
+                return Err(Error::new(format!(
+                    "invalid message identifiers in `{}`: {:?}",
+                    name, value
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
 fn build_multipart(
     ret: &mut String,
     kind: MultipartType,
@@ -466,6 +568,20 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
                 ret.push_str("\r\n");
             }
         }
+        CMSSignature => {
+            ret.push_str(&format!(
+                "Content-Type: {}; name=\"smime.p7s\"\r\n",
+                a.content_type
+            ));
+            ret.push_str("Content-Description: S/MIME cryptographic signature\r\n");
+            ret.push_str("Content-Disposition: attachment; filename=\"smime.p7s\"\r\n");
+            ret.push_str("Content-Transfer-Encoding: base64\r\n");
+            ret.push_str("\r\n");
+            for line in BASE64_MIME.encode(a.raw()).trim().lines() {
+                ret.push_str(line);
+                ret.push_str("\r\n");
+            }
+        }
         _ => {
             let content_transfer_encoding: ContentTransferEncoding = if a.raw().is_ascii() {
                 ContentTransferEncoding::_8Bit
@@ -484,7 +600,18 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
                     a.content_type()
                 ));
             }
-            ret.push_str("Content-Disposition: attachment\r\n");
+            if let Some(filename) = a.filename() {
+                ret.push_str(&format!(
+                    "Content-Disposition: {}; filename=\"{}\"\r\n",
+                    a.content_disposition().kind,
+                    filename
+                ));
+            } else {
+                ret.push_str(&format!(
+                    "Content-Disposition: {}\r\n",
+                    a.content_disposition().kind
+                ));
+            }
             ret.push_str(&format!(
                 "Content-Transfer-Encoding: {}\r\n",
                 content_transfer_encoding
@@ -525,14 +652,16 @@ where
         .set_raw(contents)
         .set_body_to_raw()
         .set_content_type(ContentType::Other {
-            name: path.file_name().map(|s| s.to_string_lossy().into()),
+            name: None,
             tag: if let Ok(mime_type) = query_mime_info(&path) {
                 mime_type
             } else {
                 b"application/octet-stream".to_vec()
             },
             parameters: vec![],
-        });
+        })
+        .set_content_disposition(ContentDispositionKind::Attachment.into())
+        .set_filename(path.file_name().map(|s| s.to_string_lossy().into()));
 
     Ok(attachment)
 }
@@ -624,6 +753,81 @@ mod tests {
         assert_eq!(&original, &default);
     }
 
+    #[test]
+    fn test_draft_update_headers() {
+        let mut draft = Draft::default();
+        draft.set_header("Subject", "test".into());
+        let original = draft.clone();
+        let s = draft.headers_to_edit_string();
+        assert!(!draft.update_headers(&s).unwrap());
+        assert_eq!(&original, &draft);
+
+        let s = format!(
+            "{}Reply-To: Ευγένιος <list@example.com>\nReferences: <a@example.com>\n \
+             <b@example.com>\nX-Custom: value\n",
+            s
+        );
+        assert!(draft.update_headers(&s).unwrap());
+        assert_eq!(
+            draft.headers().get("References"),
+            Some("<a@example.com> <b@example.com>")
+        );
+        assert_eq!(draft.headers().get("x-custom"), Some("value"));
+        assert_eq!(draft.headers().get("Subject"), Some("test"));
+
+        /* Invalid input leaves the draft untouched. */
+        let edited = draft.clone();
+        for invalid in [
+            " continuation\n",
+            "Subject test\n",
+            "Bad Name: value\n",
+            "Subject: a\nTo: <not an address\n",
+            "In-Reply-To: not-a-msg-id\n",
+        ] {
+            assert!(draft.update_headers(invalid).is_err(), "{:?}", invalid);
+            assert_eq!(&edited, &draft);
+        }
+        assert!(draft
+            .update_headers("Subject: a\nTo: <not an address\n")
+            .unwrap_err()
+            .to_string()
+            .contains("line 2"));
+    }
+
+    #[test]
+    fn test_attachment_disposition() {
+        let mut draft = Draft::default();
+        draft.set_body("body".to_string());
+        let mut attachment = AttachmentBuilder::default();
+        attachment
+            .set_raw(b"hello".to_vec())
+            .set_body_to_raw()
+            .set_content_type(ContentType::Other {
+                name: None,
+                tag: b"text/x-log".to_vec(),
+                parameters: vec![],
+            })
+            .set_content_disposition(ContentDispositionKind::Attachment.into())
+            .set_filename(Some("build.log".to_string()));
+        assert_eq!(attachment.filename(), Some("build.log"));
+        assert_eq!(attachment.content_type().name(), Some("build.log"));
+        let mut inline = attachment.clone();
+        inline
+            .set_content_disposition(ContentDispositionKind::Inline.into())
+            .set_filename(Some("renamed.log".to_string()));
+        draft.attachments_mut().push(inline);
+        draft.attachments_mut().push(attachment);
+        let s = draft.finalise().unwrap();
+        let inline_pos = s
+            .find("Content-Disposition: inline; filename=\"renamed.log\"\r\n")
+            .unwrap();
+        let attachment_pos = s
+            .find("Content-Disposition: attachment; filename=\"build.log\"\r\n")
+            .unwrap();
+        assert!(inline_pos < attachment_pos);
+        assert!(s.contains("Content-Type: text/x-log; name=\"renamed.log\"; charset=\"utf-8\"\r\n"));
+    }
+
     /*
     #[test]
     fn test_attachments() {
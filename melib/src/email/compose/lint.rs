@@ -0,0 +1,415 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! MIME conformance checks for outgoing messages.
+//!
+//! [`lint`] inspects the output of [`Draft::finalise`](super::Draft::finalise)
+//! and reports violations of RFC 5322, RFC 2045, RFC 2046 and RFC 2047 that
+//! are likely to make a submission server reject the message.
+use std::fmt;
+
+use crate::email::{
+    attachment_types::{Charset, ContentTransferEncoding, ContentType},
+    attachments::{Attachment, AttachmentBuilder},
+    parser::{self, BytesExt},
+};
+
+/// Maximum line length in octets, excluding CRLF (RFC 5322 section 2.1.1).
+pub const MAX_LINE_LENGTH: usize = 998;
+/// Recommended line length in octets, excluding CRLF (RFC 5322 section 2.1.1).
+pub const RECOMMENDED_LINE_LENGTH: usize = 78;
+/// Maximum length of an `encoded-word` (RFC 2047 section 2).
+pub const MAX_ENCODED_WORD_LENGTH: usize = 75;
+/// Maximum length of a base64 or quoted-printable encoded line (RFC 2045).
+pub const MAX_ENCODED_LINE_LENGTH: usize = 76;
+/// Maximum length of a multipart boundary (RFC 2046 section 5.1.1).
+pub const MAX_BOUNDARY_LENGTH: usize = 70;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    /// The message is valid but may be displayed or relayed badly.
+    Warning,
+    /// The message violates a MUST requirement and is likely to be rejected.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintMessage {
+    pub severity: LintSeverity,
+    pub description: String,
+}
+
+impl LintMessage {
+    fn error(description: String) -> Self {
+        Self {
+            severity: LintSeverity::Error,
+            description,
+        }
+    }
+
+    fn warning(description: String) -> Self {
+        Self {
+            severity: LintSeverity::Warning,
+            description,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == LintSeverity::Error
+    }
+}
+
+impl fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            match self.severity {
+                LintSeverity::Warning => "warning",
+                LintSeverity::Error => "error",
+            },
+            self.description
+        )
+    }
+}
+
+/// Check a finalised message for MIME conformance problems.
+///
+/// Returns an empty `Vec` if no problems were found.
+pub fn lint(message: &[u8]) -> Vec<LintMessage> {
+    let mut ret = vec![];
+    let header_end = header_section_len(message);
+
+    for (i, line) in message.split(|b| *b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.len() > MAX_LINE_LENGTH {
+            ret.push(LintMessage::error(format!(
+                "line {} is {} octets long; lines must not exceed {} octets. Use quoted-printable \
+                 or base64 encoding.",
+                i + 1,
+                line.len(),
+                MAX_LINE_LENGTH
+            )));
+        }
+    }
+
+    lint_headers(&message[..header_end], &mut ret);
+    let root = AttachmentBuilder::new(message).build();
+    lint_part(&root, "1", &mut ret);
+    ret
+}
+
+fn header_section_len(message: &[u8]) -> usize {
+    message
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|p| p + 4)
+        .or_else(|| message.windows(2).position(|w| w == b"\n\n").map(|p| p + 2))
+        .unwrap_or(message.len())
+}
+
+fn lint_headers(headers: &[u8], ret: &mut Vec<LintMessage>) {
+    for (i, line) in headers.split(|b| *b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.len() > RECOMMENDED_LINE_LENGTH && line.len() <= MAX_LINE_LENGTH {
+            ret.push(LintMessage::warning(format!(
+                "header line {} is {} octets long; header lines should be folded to {} octets.",
+                i + 1,
+                line.len(),
+                RECOMMENDED_LINE_LENGTH
+            )));
+        }
+    }
+
+    let headers = match parser::headers::headers(headers) {
+        Ok((_, headers)) => headers,
+        Err(err) => {
+            ret.push(LintMessage::error(format!(
+                "header section could not be parsed: {}",
+                crate::error::Error::from(err)
+            )));
+            return;
+        }
+    };
+    let mut has_mime_version = false;
+    let mut has_content_type = false;
+    for (name, value) in headers {
+        let name = String::from_utf8_lossy(name);
+        has_mime_version |= name.eq_ignore_ascii_case("MIME-Version");
+        has_content_type |= name.eq_ignore_ascii_case("Content-Type");
+        if !value.is_ascii() {
+            ret.push(LintMessage::error(format!(
+                "{} header contains unencoded 8-bit characters; they must be RFC 2047 encoded.",
+                name
+            )));
+            continue;
+        }
+        for word in value
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|w| w.len() > 4 && w.starts_with(b"=?") && w.ends_with(b"?="))
+        {
+            lint_encoded_word(&name, word, ret);
+        }
+    }
+    if has_content_type && !has_mime_version {
+        ret.push(LintMessage::error(
+            "Content-Type is present but MIME-Version header is missing.".to_string(),
+        ));
+    }
+}
+
+fn lint_encoded_word(header: &str, word: &[u8], ret: &mut Vec<LintMessage>) {
+    if word.len() > MAX_ENCODED_WORD_LENGTH {
+        ret.push(LintMessage::error(format!(
+            "{} header contains an encoded-word of {} characters; encoded-words must not exceed \
+             {} characters.",
+            header,
+            word.len(),
+            MAX_ENCODED_WORD_LENGTH
+        )));
+    }
+    let inner = &word[2..word.len() - 2];
+    let fields = inner.splitn(3, |b| *b == b'?').collect::<Vec<&[u8]>>();
+    match fields.as_slice() {
+        [charset, encoding, _text]
+            if !charset.is_empty()
+                && (encoding.eq_ignore_ascii_case(b"B") || encoding.eq_ignore_ascii_case(b"Q")) =>
+        {
+            if let Charset::UTF8 = Charset::from(*charset) {
+                if !charset.eq_ignore_ascii_case(b"utf-8") && !charset.eq_ignore_ascii_case(b"utf8")
+                {
+                    ret.push(LintMessage::warning(format!(
+                        "{} header contains an encoded-word with unknown charset `{}`.",
+                        header,
+                        String::from_utf8_lossy(charset)
+                    )));
+                }
+            }
+        }
+        _ => {
+            ret.push(LintMessage::error(format!(
+                "{} header contains a malformed encoded-word `{}`.",
+                header,
+                String::from_utf8_lossy(word)
+            )));
+        }
+    }
+}
+
+fn lint_part(part: &Attachment, path: &str, ret: &mut Vec<LintMessage>) {
+    match part.content_type() {
+        ContentType::Multipart {
+            ref boundary,
+            ref parts,
+            ..
+        } => {
+            if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LENGTH {
+                ret.push(LintMessage::error(format!(
+                    "part {} has a boundary of {} characters; boundaries must be 1 to {} \
+                     characters long.",
+                    path,
+                    boundary.len(),
+                    MAX_BOUNDARY_LENGTH
+                )));
+            }
+            lint_boundary(part.body(), boundary, path, ret);
+            if !matches!(
+                part.content_transfer_encoding(),
+                ContentTransferEncoding::_7Bit | ContentTransferEncoding::_8Bit
+            ) {
+                ret.push(LintMessage::error(format!(
+                    "multipart part {} has Content-Transfer-Encoding `{}`; only 7bit, 8bit or \
+                     binary are allowed.",
+                    path,
+                    part.content_transfer_encoding()
+                )));
+            }
+            for (i, sub) in parts.iter().enumerate() {
+                lint_part(sub, &format!("{}.{}", path, i + 1), ret);
+            }
+        }
+        ContentType::MessageRfc822 => {}
+        content_type => lint_leaf(part, content_type, path, ret),
+    }
+}
+
+/// Check that the boundary delimiter doesn't appear as a line prefix other
+/// than in actual delimiter lines (RFC 2046 section 5.1.1).
+fn lint_boundary(body: &[u8], boundary: &[u8], path: &str, ret: &mut Vec<LintMessage>) {
+    let mut delimiter = b"--".to_vec();
+    delimiter.extend_from_slice(boundary);
+    let mut has_close_delimiter = false;
+    for (i, line) in body.split(|b| *b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(rest) = line.strip_prefix(delimiter.as_slice()) {
+            let rest = rest.trim();
+            if rest == b"--" {
+                has_close_delimiter = true;
+            } else if !rest.is_empty() {
+                ret.push(LintMessage::error(format!(
+                    "part {}: line {} of the multipart body collides with the boundary delimiter.",
+                    path,
+                    i + 1
+                )));
+            }
+        }
+    }
+    if !has_close_delimiter {
+        ret.push(LintMessage::error(format!(
+            "part {} is missing the closing boundary delimiter.",
+            path
+        )));
+    }
+}
+
+fn lint_leaf(
+    part: &Attachment,
+    content_type: &ContentType,
+    path: &str,
+    ret: &mut Vec<LintMessage>,
+) {
+    let body = part.body();
+    match part.content_transfer_encoding() {
+        ContentTransferEncoding::_7Bit if !body.is_ascii() => {
+            ret.push(LintMessage::error(format!(
+                "part {} ({}) contains 8-bit data but its Content-Transfer-Encoding is 7bit.",
+                path, content_type
+            )));
+        }
+        ContentTransferEncoding::_7Bit | ContentTransferEncoding::_8Bit => {
+            if body.contains(&b'\0') {
+                ret.push(LintMessage::error(format!(
+                    "part {} ({}) contains NUL octets; binary data must be base64 encoded.",
+                    path, content_type
+                )));
+            }
+        }
+        ContentTransferEncoding::Base64 | ContentTransferEncoding::QuotedPrintable => {
+            if let Some((i, line)) = body
+                .split(|b| *b == b'\n')
+                .map(|l| l.strip_suffix(b"\r").unwrap_or(l))
+                .enumerate()
+                .find(|(_, l)| l.len() > MAX_ENCODED_LINE_LENGTH)
+            {
+                ret.push(LintMessage::error(format!(
+                    "part {} ({}): encoded line {} is {} characters long; {} lines must not \
+                     exceed {} characters.",
+                    path,
+                    content_type,
+                    i + 1,
+                    line.len(),
+                    part.content_transfer_encoding(),
+                    MAX_ENCODED_LINE_LENGTH
+                )));
+            }
+        }
+        ContentTransferEncoding::Other { ref tag } => {
+            ret.push(LintMessage::warning(format!(
+                "part {} ({}) has unknown Content-Transfer-Encoding `{}`.",
+                path,
+                content_type,
+                String::from_utf8_lossy(tag)
+            )));
+        }
+    }
+
+    if let ContentType::Text { ref charset, .. } = content_type {
+        let declared_charset = part
+            .parameters()
+            .into_iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(b"charset"));
+        let is_8bit = matches!(
+            part.content_transfer_encoding(),
+            ContentTransferEncoding::_7Bit | ContentTransferEncoding::_8Bit
+        ) && !body.is_ascii();
+        if is_8bit && (!declared_charset || *charset == Charset::Ascii) {
+            ret.push(LintMessage::error(format!(
+                "part {} ({}) contains 8-bit text but declares no charset other than us-ascii.",
+                path, content_type
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::Draft;
+
+    #[test]
+    fn test_lint_finalised_draft() {
+        let mut draft = Draft::default();
+        draft
+            .set_header("From", "user <user@example.com>".into())
+            .set_header("To", "other <other@example.com>".into())
+            .set_header("Subject", "Συμπεριφορά σε flush λόγω misprediction".into())
+            .set_body("αδφαφσαφασ\nascii line\n".to_string());
+        let message = draft.finalise().unwrap();
+        let errors = lint(message.as_bytes())
+            .into_iter()
+            .filter(LintMessage::is_error)
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_lint_errors() {
+        let message = "From: user@example.com\r\nSubject: Καλημέρα\r\nMIME-Version: \
+                       1.0\r\nContent-Type: text/plain\r\nContent-Transfer-Encoding: \
+                       7bit\r\n\r\nκαλημέρα\r\n";
+        let errors = lint(message.as_bytes());
+        assert_eq!(
+            errors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "error: Subject header contains unencoded 8-bit characters; they must be RFC 2047 \
+                 encoded."
+                    .to_string(),
+                "error: part 1 (text/plain) contains 8-bit data but its Content-Transfer-Encoding \
+                 is 7bit."
+                    .to_string(),
+                "error: part 1 (text/plain) contains 8-bit text but declares no charset other \
+                 than us-ascii."
+                    .to_string(),
+            ]
+        );
+
+        let long_line = "a".repeat(MAX_LINE_LENGTH + 1);
+        let message = format!(
+            "From: user@example.com\r\nSubject: =?UTF-8?B?{}?=\r\nMIME-Version: \
+             1.0\r\nContent-Type: multipart/mixed; \
+             boundary=\"bzz\"\r\n\r\n--bzz\r\n\r\n{}\r\n--bzzz\r\n--bzz--\r\n",
+            "z4POtSA".repeat(10),
+            long_line
+        );
+        let errors = lint(message.as_bytes())
+            .into_iter()
+            .filter(LintMessage::is_error)
+            .map(|m| m.description)
+            .collect::<Vec<String>>();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].starts_with("line 8 is 999 octets long"));
+        assert!(errors[1].starts_with("Subject header contains an encoded-word of 82"));
+        assert!(errors[2].contains("collides with the boundary delimiter"));
+    }
+}
@@ -36,20 +36,22 @@ pub fn encode_header(value: &str) -> String {
                     ret.push_str(g);
                 }
                 (true, false) => {
-                    /* If !g.is_whitespace()
+                    /* If !g.is_whitespace() and g starts a word
                      *
                      * Whitespaces inside encoded tokens must be greedily taken,
-                     * instead of splitting each non-ascii word into separate encoded tokens. */
-                    if g.split_whitespace().next().is_some() {
+                     * instead of splitting each non-ascii word into separate encoded tokens.
+                     * ASCII characters inside a word are encoded along with it, since
+                     * whitespace is needed to separate an encoded-word from the text after
+                     * it. */
+                    if g.split_whitespace().next().is_some()
+                        && value[..idx].ends_with(char::is_whitespace)
+                    {
                         ret.push_str(&format!(
                             "=?UTF-8?B?{}?=",
                             BASE64_MIME
                                 .encode(value[current_window_start..idx].as_bytes())
                                 .trim()
                         ));
-                        if idx != value.len() - 1 && (idx == 0 || !value[..idx].ends_with(' ')) {
-                            ret.push(' ');
-                        }
                         is_current_window_ascii = true;
                         current_window_start = idx;
                         ret.push_str(g);
@@ -91,20 +93,20 @@ pub fn encode_header(value: &str) -> String {
                     ret.push(g);
                 }
                 (true, false) => {
-                    /* If !g.is_whitespace()
+                    /* If !g.is_whitespace() and g starts a word
                      *
                      * Whitespaces inside encoded tokens must be greedily taken,
-                     * instead of splitting each non-ascii word into separate encoded tokens. */
-                    if !g.is_whitespace() && value.is_char_boundary(i) {
+                     * instead of splitting each non-ascii word into separate encoded tokens.
+                     * ASCII characters inside a word are encoded along with it, since
+                     * whitespace is needed to separate an encoded-word from the text after
+                     * it. */
+                    if !g.is_whitespace() && value[..i].ends_with(char::is_whitespace) {
                         ret.push_str(&format!(
                             "=?UTF-8?B?{}?=",
                             BASE64_MIME
                                 .encode(value[current_window_start..i].as_bytes())
                                 .trim()
                         ));
-                        if i != value.len() - 1 {
-                            ret.push(' ');
-                        }
                         is_current_window_ascii = true;
                         current_window_start = i;
                         ret.push(g);
@@ -193,4 +195,14 @@ fn test_encode_header() {
         )
         .unwrap(),
     );
+    let words_enc = "Ünïcode plans";
+    assert_eq!(
+        words_enc,
+        std::str::from_utf8(
+            &crate::email::parser::encodings::phrase(encode_header(words_enc).as_bytes(), false)
+                .unwrap()
+                .1
+        )
+        .unwrap(),
+    );
 }
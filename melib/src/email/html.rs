@@ -0,0 +1,480 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Processing of `text/html` parts.
+//!
+//! [`sanitize`] removes tracking pixels, remote images that report back
+//! when a message is read, and rewrites click-tracking links to the
+//! destination they redirect to when it can be decoded from the link.
+//! [`render`] converts html to plain text, so that it can be read without an
+//! external program.
+
+use std::fmt;
+
+use data_encoding::{BASE64URL_NOPAD, BASE64_NOPAD};
+use regex::Regex;
+
+mod render;
+pub use render::*;
+
+/// Substrings of the `src` of remote images that are known to be tracking
+/// pixels regardless of their size.
+const TRACKING_IMAGE_PATTERNS: &[&str] = &[
+    "/track/open",
+    "/tracking/open",
+    "/trk/open",
+    "/wf/open",
+    "/email/open",
+    "/open.aspx",
+    "/open.php",
+    "/e2t/o/",
+    "/pixel.gif",
+    "/pixel.png",
+    "/beacon.gif",
+    "mailtrack.io/trace",
+];
+
+/// Query parameters that click-tracking services put the destination URL in.
+const REDIRECT_PARAMETERS: &[&str] = &[
+    "url",
+    "u",
+    "q",
+    "r",
+    "target",
+    "redirect",
+    "redirect_url",
+    "dest",
+    "destination",
+    "link",
+    "goto",
+];
+
+/// What [`sanitize`] removed from an html part.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SanitizeReport {
+    /// The `src` of every removed tracking image.
+    pub tracking_images: Vec<String>,
+    /// Every rewritten tracking link, with its destination.
+    pub unwrapped_links: Vec<(String, String)>,
+}
+
+impl SanitizeReport {
+    pub fn is_empty(&self) -> bool {
+        self.tracking_images.is_empty() && self.unwrapped_links.is_empty()
+    }
+}
+
+impl fmt::Display for SanitizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Removed {} tracking image{} and unwrapped {} tracking link{}.",
+            self.tracking_images.len(),
+            if self.tracking_images.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            self.unwrapped_links.len(),
+            if self.unwrapped_links.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+        )?;
+        for src in &self.tracking_images {
+            write!(f, "\n  image: {}", src)?;
+        }
+        for (link, destination) in &self.unwrapped_links {
+            write!(f, "\n  link: {} -> {}", link, destination)?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes tracking images from `html` and rewrites click-tracking links to
+/// their destination, returning the sanitized html and what was changed.
+pub fn sanitize(html: &str) -> (String, SanitizeReport) {
+    let tag_re = Regex::new(r#"(?is)<(img|a)\b(?:[^>"']|"[^"]*"|'[^']*')*>"#).unwrap();
+    let attr_re =
+        Regex::new(r#"(?is)([a-z_:][-a-z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .unwrap();
+    let mut report = SanitizeReport::default();
+    let mut ret = String::with_capacity(html.len());
+    let mut last = 0;
+    for tag in tag_re.captures_iter(html) {
+        let whole = tag.get(0).unwrap();
+        let attrs = attr_re
+            .captures_iter(whole.as_str())
+            .map(|attr| {
+                let value = attr
+                    .get(2)
+                    .or_else(|| attr.get(3))
+                    .or_else(|| attr.get(4))
+                    .unwrap();
+                (attr[1].to_ascii_lowercase(), value)
+            })
+            .collect::<Vec<_>>();
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| decode_entities(v.as_str()))
+        };
+        ret.push_str(&html[last..whole.start()]);
+        last = whole.end();
+        if tag[1].eq_ignore_ascii_case("img") {
+            if let Some(src) = attr("src") {
+                if is_tracking_image(&src, attr("width"), attr("height"), attr("style")) {
+                    report.tracking_images.push(src);
+                    continue;
+                }
+            }
+        } else if let Some((href, value)) = attrs
+            .iter()
+            .find(|(n, _)| n == "href")
+            .map(|(_, v)| (decode_entities(v.as_str()), v))
+        {
+            if let Some(destination) = unwrap_tracking_url(&href) {
+                let tag = whole.as_str();
+                /* Replace the value along with its quotes, if any. */
+                let (start, end) = if tag[..value.start()].ends_with(&['"', '\''][..]) {
+                    (value.start() - 1, value.end() + 1)
+                } else {
+                    (value.start(), value.end())
+                };
+                ret.push_str(&tag[..start]);
+                ret.push('"');
+                ret.push_str(&destination.replace('&', "&amp;").replace('"', "&quot;"));
+                ret.push('"');
+                ret.push_str(&tag[end..]);
+                report.unwrapped_links.push((href, destination));
+                continue;
+            }
+        }
+        ret.push_str(whole.as_str());
+    }
+    ret.push_str(&html[last..]);
+    (ret, report)
+}
+
+fn is_remote(url: &str) -> bool {
+    let url = url.trim_start();
+    url.starts_with("//")
+        || url
+            .get(.."https://".len())
+            .map(|s| s.eq_ignore_ascii_case("https://"))
+            .unwrap_or(false)
+        || url
+            .get(.."http://".len())
+            .map(|s| s.eq_ignore_ascii_case("http://"))
+            .unwrap_or(false)
+}
+
+/// Returns the value of `property` in an inline `style` attribute.
+fn style_property(style: &str, property: &str) -> Option<String> {
+    style.split(';').find_map(|declaration| {
+        let (name, value) = declaration.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case(property) {
+            Some(value.trim().to_ascii_lowercase())
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses a pixel length such as `1`, `1px` or `0.5px`.
+fn parse_pixels(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+fn is_tracking_image(
+    src: &str,
+    width: Option<String>,
+    height: Option<String>,
+    style: Option<String>,
+) -> bool {
+    if !is_remote(src) {
+        return false;
+    }
+    let src = src.to_ascii_lowercase();
+    if TRACKING_IMAGE_PATTERNS.iter().any(|p| src.contains(p)) {
+        return true;
+    }
+    let style = style.unwrap_or_default();
+    if style_property(&style, "display").as_deref() == Some("none")
+        || style_property(&style, "visibility").as_deref() == Some("hidden")
+    {
+        return true;
+    }
+    let size = |attribute: Option<String>, property: &str| {
+        style_property(&style, property)
+            .as_deref()
+            .and_then(parse_pixels)
+            .or_else(|| attribute.as_deref().and_then(parse_pixels))
+    };
+    match (size(width, "width"), size(height, "height")) {
+        (Some(w), Some(h)) => w <= 1.0 && h <= 1.0,
+        (Some(l), None) | (None, Some(l)) => l < 1.0,
+        (None, None) => false,
+    }
+}
+
+/// Returns the destination that the click-tracking link `url` redirects to,
+/// if it can be decoded from `url`.
+pub fn unwrap_tracking_url(url: &str) -> Option<String> {
+    let mut ret = None;
+    /* Links can be wrapped by more than one service. */
+    for _ in 0..5 {
+        match unwrap_once(ret.as_deref().unwrap_or(url)) {
+            Some(destination) => ret = Some(destination),
+            None => break,
+        }
+    }
+    ret
+}
+
+fn unwrap_once(url: &str) -> Option<String> {
+    /* Proofpoint URL Defense: https://urldefense.com/v3/__<URL>__;<checksum> */
+    if let Some((_, rest)) = url.split_once("urldefense.com/v3/__") {
+        if let Some((destination, _)) = rest.split_once("__;") {
+            if is_remote(destination) {
+                return Some(destination.to_string());
+            }
+        }
+    }
+    if !is_remote(url) {
+        return None;
+    }
+    let (_, query) = url.split_once('?')?;
+    let query = query.split('#').next().unwrap_or_default();
+    query.split('&').find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        if !REDIRECT_PARAMETERS
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(name))
+        {
+            return None;
+        }
+        let value = percent_decode(value);
+        if is_remote(&value) {
+            return Some(value.trim().to_string());
+        }
+        let bytes = value.trim_end_matches('=').as_bytes();
+        let decoded = BASE64URL_NOPAD
+            .decode(bytes)
+            .or_else(|_| BASE64_NOPAD.decode(bytes))
+            .ok()
+            .and_then(|d| String::from_utf8(d).ok())?;
+        if is_remote(&decoded) {
+            Some(decoded)
+        } else {
+            None
+        }
+    })
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(b) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                ret.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        ret.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&ret).into_owned()
+}
+
+/// Decodes the character references in `s`.
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut ret = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        ret.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let decoded = rest.find(';').filter(|end| *end <= 32).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "nbsp" => Some('\u{a0}'),
+                "shy" => Some('\u{ad}'),
+                "zwnj" => Some('\u{200c}'),
+                "zwj" => Some('\u{200d}'),
+                "copy" => Some('©'),
+                "reg" => Some('®'),
+                "trade" => Some('™'),
+                "hellip" => Some('…'),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "lsquo" => Some('‘'),
+                "rsquo" => Some('’'),
+                "ldquo" => Some('“'),
+                "rdquo" => Some('”'),
+                "laquo" => Some('«'),
+                "raquo" => Some('»'),
+                "bull" => Some('•'),
+                "middot" => Some('·'),
+                "deg" => Some('°'),
+                "times" => Some('×'),
+                "euro" => Some('€'),
+                "pound" => Some('£'),
+                "yen" => Some('¥'),
+                "cent" => Some('¢'),
+                "sect" => Some('§'),
+                "para" => Some('¶'),
+                "larr" => Some('←'),
+                "rarr" => Some('→'),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                }
+                _ if entity.starts_with('#') => {
+                    entity[1..].parse::<u32>().ok().and_then(char::from_u32)
+                }
+                _ => None,
+            }?;
+            Some((c, end + 1))
+        });
+        if let Some((c, len)) = decoded {
+            ret.push(c);
+            rest = &rest[len..];
+        } else {
+            ret.push('&');
+            rest = &rest[1..];
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_sanitize() {
+        let html = r#"<html><body>
+<p>Hello <a href='https://example.com/plain?q=search'>plain</a></p>
+<img src="https://cdn.example.com/logo.png" width="200" height="50" alt="logo">
+<img src="cid:logo@example.com" width="1" height="1">
+<IMG SRC="https://t.example.com/o/abc123" WIDTH="1" HEIGHT="1">
+<img style="display: none" src="https://t.example.com/hidden.gif">
+<img src="https://mail.example.com/track/open?id=42" alt="a > b">
+<a href="https://click.example.com/c?u=https%3A%2F%2Fexample.com%2Fsale%3Fa%3D1%26b%3D2&amp;id=7" class="btn">Sale</a>
+<a href=https://l.example.com/?url=aHR0cHM6Ly9leGFtcGxlLm9yZy8>Docs</a>
+</body></html>"#;
+        let (sanitized, report) = sanitize(html);
+        assert_eq!(
+            report.tracking_images,
+            vec![
+                "https://t.example.com/o/abc123".to_string(),
+                "https://t.example.com/hidden.gif".to_string(),
+                "https://mail.example.com/track/open?id=42".to_string(),
+            ]
+        );
+        assert_eq!(
+            report.unwrapped_links,
+            vec![
+                (
+                    "https://click.example.com/c?u=https%3A%2F%2Fexample.com%2Fsale%3Fa%3D1%26b%3D2&id=7"
+                        .to_string(),
+                    "https://example.com/sale?a=1&b=2".to_string()
+                ),
+                (
+                    "https://l.example.com/?url=aHR0cHM6Ly9leGFtcGxlLm9yZy8".to_string(),
+                    "https://example.org/".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            sanitized,
+            r#"<html><body>
+<p>Hello <a href='https://example.com/plain?q=search'>plain</a></p>
+<img src="https://cdn.example.com/logo.png" width="200" height="50" alt="logo">
+<img src="cid:logo@example.com" width="1" height="1">
+
+
+
+<a href="https://example.com/sale?a=1&amp;b=2" class="btn">Sale</a>
+<a href="https://example.org/">Docs</a>
+</body></html>"#
+        );
+        assert_eq!(
+            report.to_string(),
+            "Removed 3 tracking images and unwrapped 2 tracking links.
+  image: https://t.example.com/o/abc123
+  image: https://t.example.com/hidden.gif
+  image: https://mail.example.com/track/open?id=42
+  link: https://click.example.com/c?u=https%3A%2F%2Fexample.com%2Fsale%3Fa%3D1%26b%3D2&id=7 -> https://example.com/sale?a=1&b=2
+  link: https://l.example.com/?url=aHR0cHM6Ly9leGFtcGxlLm9yZy8 -> https://example.org/"
+        );
+
+        let (unchanged, report) = sanitize("<p>No tracking here.</p>");
+        assert!(report.is_empty());
+        assert_eq!(unchanged, "<p>No tracking here.</p>");
+    }
+
+    #[test]
+    fn test_html_unwrap_tracking_url() {
+        assert_eq!(
+            unwrap_tracking_url("https://urldefense.com/v3/__https://example.com/page__;!!abc$")
+                .as_deref(),
+            Some("https://example.com/page")
+        );
+        assert_eq!(
+            unwrap_tracking_url(
+                "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fwww.google.com%2Furl%3Fq%3Dhttps%253A%252F%252Fexample.com%252F&data=1"
+            )
+            .as_deref(),
+            Some("https://example.com/")
+        );
+        assert_eq!(unwrap_tracking_url("https://example.com/?q=rust"), None);
+        assert_eq!(unwrap_tracking_url("mailto:a@example.com?url=x"), None);
+        assert_eq!(
+            decode_entities("a&amp;b&#39;c&#x41;&bogus;"),
+            "a&b'cA&bogus;"
+        );
+    }
+}
@@ -0,0 +1,1033 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A minimal html to text renderer.
+//!
+//! The html is parsed leniently into a tree, with the implied end tags of
+//! paragraphs, list items and table cells, and rendered block by block:
+//! lists are indented, quotes are prefixed with `> `, tables with one line
+//! per cell are laid out in columns and other tables cell after cell, and
+//! `*bold*`, `_emphasis_` and `` `code` `` are marked inline.
+
+use std::fmt::Write;
+
+use super::{decode_entities, style_property};
+
+/// Html rendered as plain text by [`render`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RenderedHtml {
+    /// The text, followed by the numbered list of link targets.
+    pub text: String,
+    /// The targets of the links, in the order of their `[N]` references.
+    pub links: Vec<String>,
+}
+
+/// Renders `html` as text wrapped at `width` columns. Links are marked with
+/// a `[N]` reference to the list of their targets at the end of the text.
+pub fn render(html: &str, width: usize) -> RenderedHtml {
+    let nodes = parse(html);
+    let mut renderer = Renderer {
+        links: vec![],
+        list_depth: 0,
+    };
+    let mut block = Block::new(width.max(20), false);
+    renderer.walk_all(&nodes, &mut block);
+    let mut text = String::new();
+    let mut blank = false;
+    for line in block.finish() {
+        let line = line.replace('\u{a0}', " ");
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = !text.is_empty();
+            continue;
+        }
+        if blank {
+            text.push('\n');
+            blank = false;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    if !renderer.links.is_empty() {
+        text.push_str("\nLinks:\n");
+        for (i, link) in renderer.links.iter().enumerate() {
+            let _ = writeln!(text, "[{}] {}", i + 1, link);
+        }
+    }
+    RenderedHtml {
+        text,
+        links: renderer.links,
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Element(Element),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn new(name: String, attrs: Vec<(String, String)>) -> Self {
+        Self {
+            name,
+            attrs,
+            children: vec![],
+        }
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.attr("hidden").is_some()
+            || self
+                .attr("style")
+                .and_then(|style| style_property(style, "display"))
+                .map(|display| display == "none")
+                .unwrap_or(false)
+    }
+}
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose content is text, not markup.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Elements whose start tag ends an open paragraph.
+const CLOSES_PARAGRAPH: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "div",
+    "dl",
+    "fieldset",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Elements rendered on lines of their own.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "body",
+    "center",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "header",
+    "html",
+    "legend",
+    "main",
+    "nav",
+    "section",
+    "summary",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+];
+
+/// How deep elements may nest. The start tags of deeper elements are
+/// ignored, so that their content goes to the deepest open element, and the
+/// tree can be walked and dropped recursively.
+const MAX_DEPTH: usize = 128;
+
+/// Elements that are not rendered at all.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "select", "style", "template", "textarea", "title",
+];
+
+/// Parses `html` into a tree, closing unclosed elements where html implies
+/// it.
+fn parse(html: &str) -> Vec<Node> {
+    let mut stack = vec![Element::new(String::new(), vec![])];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let lt = match rest.find('<') {
+            Some(lt) => lt,
+            None => {
+                push_text(&mut stack, rest);
+                break;
+            }
+        };
+        push_text(&mut stack, &rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment
+                .find("-->")
+                .map(|end| &comment[end + "-->".len()..])
+                .unwrap_or_default();
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest
+                .find('>')
+                .map(|end| &rest[end + 1..])
+                .unwrap_or_default();
+        } else if let Some(end_tag) = rest.strip_prefix("</") {
+            let name = end_tag
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            if name.is_empty() {
+                push_text(&mut stack, "<");
+                rest = &rest[1..];
+                continue;
+            }
+            rest = end_tag
+                .find('>')
+                .map(|end| &end_tag[end + 1..])
+                .unwrap_or_default();
+            if let Some(i) = (1..stack.len()).rev().find(|i| stack[*i].name == name) {
+                close_to(&mut stack, i);
+            }
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (element, self_closing, len) = parse_start_tag(&rest[1..]);
+            rest = &rest[1 + len..];
+            let name = element.name.clone();
+            let opened = open(&mut stack, element, self_closing);
+            if RAW_TEXT_ELEMENTS.contains(&name.as_str()) && !self_closing {
+                let end = find_end_tag(rest, &name).unwrap_or(rest.len());
+                if let Some(top) = stack.last_mut().filter(|_| opened) {
+                    top.children.push(Node::Text(rest[..end].to_string()));
+                }
+                rest = &rest[end..];
+            }
+        } else {
+            push_text(&mut stack, "<");
+            rest = &rest[1..];
+        }
+    }
+    close_to(&mut stack, 1);
+    stack.pop().map(|root| root.children).unwrap_or_default()
+}
+
+/// Returns the position of the first end tag of `name` in `s`, ignoring
+/// ASCII case.
+fn find_end_tag(s: &str, name: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut start = 0;
+    while let Some(pos) = s[start..].find("</") {
+        let pos = start + pos;
+        let tag_name = &bytes[pos + 2..];
+        if tag_name.len() >= name.len()
+            && tag_name[..name.len()].eq_ignore_ascii_case(name.as_bytes())
+        {
+            return Some(pos);
+        }
+        start = pos + 2;
+    }
+    None
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(top) = stack.last_mut() {
+        top.children.push(Node::Text(decode_entities(text)));
+    }
+}
+
+/// Closes the elements of `stack` from the top down to index `i`.
+fn close_to(stack: &mut Vec<Element>, i: usize) {
+    while stack.len() > i.max(1) {
+        let element = stack.pop().unwrap();
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Node::Element(element));
+    }
+}
+
+/// Closes the nearest open element named one of `names`, unless one of
+/// `boundaries` is open above it.
+fn close_implied(stack: &mut Vec<Element>, names: &[&str], boundaries: &[&str]) {
+    for i in (1..stack.len()).rev() {
+        if names.contains(&stack[i].name.as_str()) {
+            close_to(stack, i);
+            return;
+        }
+        if boundaries.contains(&stack[i].name.as_str()) {
+            return;
+        }
+    }
+}
+
+/// Adds `element` to the tree, as the new open element unless it is void.
+/// Returns `false` if it is too deep and was dropped, see [`MAX_DEPTH`].
+fn open(stack: &mut Vec<Element>, element: Element, self_closing: bool) -> bool {
+    match element.name.as_str() {
+        "li" => close_implied(stack, &["li"], &["ul", "ol", "menu"]),
+        "dt" | "dd" => close_implied(stack, &["dt", "dd"], &["dl"]),
+        "tr" => close_implied(stack, &["tr"], &["table"]),
+        "td" | "th" => close_implied(stack, &["td", "th"], &["tr", "table"]),
+        "thead" | "tbody" | "tfoot" => {
+            close_implied(stack, &["thead", "tbody", "tfoot"], &["table"])
+        }
+        name if CLOSES_PARAGRAPH.contains(&name) => {
+            close_implied(stack, &["p"], &["div", "td", "th", "li", "blockquote"])
+        }
+        _ => {}
+    }
+    if stack.len() > MAX_DEPTH {
+        return false;
+    }
+    if self_closing || VOID_ELEMENTS.contains(&element.name.as_str()) {
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Node::Element(element));
+    } else {
+        stack.push(element);
+    }
+    true
+}
+
+/// Parses the start tag at the beginning of `s`, which follows its `<`.
+/// Returns the element, whether the tag is self-closing and its length.
+fn parse_start_tag(s: &str) -> (Element, bool, usize) {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+        i += 1;
+    }
+    let name = s[..i].to_ascii_lowercase();
+    let mut attrs = vec![];
+    let mut self_closing = false;
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            self_closing = bytes[i] == b'/';
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+        if bytes[i] == b'>' {
+            i += 1;
+            break;
+        }
+        self_closing = false;
+        let start = i;
+        while i < bytes.len()
+            && !bytes[i].is_ascii_whitespace()
+            && !matches!(bytes[i], b'=' | b'>' | b'/')
+        {
+            i += 1;
+        }
+        let attr_name = s[start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if i < bytes.len() && bytes[i] == b'=' {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i < bytes.len() && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let quote = bytes[i];
+                let start = i + 1;
+                i = start;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+                value = decode_entities(&s[start..i]);
+                i = (i + 1).min(bytes.len());
+            } else {
+                let start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                    i += 1;
+                }
+                value = decode_entities(&s[start..i]);
+            }
+        }
+        if start == i {
+            /* Skip a stray character such as a lone `=`. */
+            i += 1;
+        } else if !attr_name.is_empty() {
+            attrs.push((attr_name, value));
+        }
+    }
+    (Element::new(name, attrs), self_closing, i)
+}
+
+/// Text being laid out in lines of at most `width` columns.
+struct Block {
+    width: usize,
+    /// Keep whitespace and line breaks, as in `<pre>`.
+    pre: bool,
+    lines: Vec<String>,
+    /// The text of the current line, before wrapping.
+    line: String,
+    /// A space is due before the next word.
+    space: bool,
+}
+
+impl Block {
+    fn new(width: usize, pre: bool) -> Self {
+        Self {
+            width,
+            pre,
+            lines: vec![],
+            line: String::new(),
+            space: false,
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.pre {
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.lines.push(std::mem::take(&mut self.line));
+                }
+                self.line.push_str(&part.replace('\t', "    "));
+            }
+            return;
+        }
+        for c in text.chars() {
+            match c {
+                c if c.is_ascii_whitespace() => self.space = true,
+                '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{feff}' | '\u{ad}' | '\u{34f}' => {}
+                c => {
+                    if self.space && !self.line.is_empty() {
+                        self.line.push(' ');
+                    }
+                    self.space = false;
+                    self.line.push(c);
+                }
+            }
+        }
+    }
+
+    /// Appends `word`, after a space if one is due.
+    fn word(&mut self, word: &str) {
+        if self.space && !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.space = false;
+        self.line.push_str(word);
+    }
+
+    /// Ends the current line.
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            if self.pre {
+                self.lines.push(line);
+            } else {
+                self.lines.extend(wrap(&line, self.width));
+            }
+        }
+        self.space = false;
+    }
+
+    /// Ends the current line, or adds an empty line if it is empty, as
+    /// `<br>` does.
+    fn line_break(&mut self) {
+        if self.line.is_empty() {
+            self.lines.push(String::new());
+        } else {
+            self.flush();
+        }
+    }
+
+    /// Ends the current line and separates what follows with an empty line.
+    fn blank(&mut self) {
+        self.flush();
+        if self
+            .lines
+            .last()
+            .map(|l| !l.trim().is_empty())
+            .unwrap_or(false)
+        {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn extend(&mut self, lines: Vec<String>) {
+        self.flush();
+        self.lines.extend(lines);
+    }
+
+    /// Returns the lines, without leading and trailing empty lines.
+    fn finish(mut self) -> Vec<String> {
+        self.flush();
+        while self
+            .lines
+            .last()
+            .map(|l| l.trim().is_empty())
+            .unwrap_or(false)
+        {
+            self.lines.pop();
+        }
+        let leading = self
+            .lines
+            .iter()
+            .take_while(|l| l.trim().is_empty())
+            .count();
+        self.lines.split_off(leading)
+    }
+}
+
+/// Wraps `line` on spaces to lines of at most `width` columns. Words longer
+/// than `width` are not broken.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut ret = vec![];
+    let mut current = String::new();
+    let mut current_len = 0;
+    for word in line.split(' ') {
+        let len = word.chars().count();
+        if current_len > 0 && current_len + 1 + len > width {
+            ret.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if current_len > 0 {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(word);
+        current_len += len;
+    }
+    if !current.is_empty() {
+        ret.push(current);
+    }
+    ret
+}
+
+struct Renderer {
+    links: Vec<String>,
+    list_depth: usize,
+}
+
+impl Renderer {
+    fn walk_all(&mut self, nodes: &[Node], block: &mut Block) {
+        for node in nodes {
+            self.walk(node, block);
+        }
+    }
+
+    fn walk(&mut self, node: &Node, block: &mut Block) {
+        let element = match node {
+            Node::Text(text) => {
+                block.text(text);
+                return;
+            }
+            Node::Element(element) => element,
+        };
+        if element.is_hidden() || SKIPPED_ELEMENTS.contains(&element.name.as_str()) {
+            return;
+        }
+        match element.name.as_str() {
+            "br" => block.line_break(),
+            "hr" => {
+                block.flush();
+                block.lines.push("-".repeat(block.width));
+            }
+            "img" => {
+                if let Some(alt) = element.attr("alt").map(str::trim) {
+                    if !alt.is_empty() {
+                        block.word(&format!("[{}]", alt));
+                    }
+                }
+            }
+            "a" => self.link(element, block),
+            "b" | "strong" => self.styled(element, block, "*"),
+            "i" | "em" | "cite" | "dfn" | "u" => self.styled(element, block, "_"),
+            "code" | "kbd" | "samp" | "tt" => self.styled(element, block, "`"),
+            "q" => self.styled(element, block, "\""),
+            "p" => {
+                block.blank();
+                self.walk_all(&element.children, block);
+                block.blank();
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                block.blank();
+                let mut heading = Block::new(block.width, false);
+                self.walk_all(&element.children, &mut heading);
+                let lines = heading.finish();
+                match element.name.as_bytes()[1] {
+                    b'1' | b'2' => {
+                        let underline = if element.name == "h1" { "=" } else { "-" };
+                        let len = lines.iter().map(|l| l.chars().count()).max();
+                        block.extend(lines);
+                        if let Some(len) = len {
+                            block.lines.push(underline.repeat(len));
+                        }
+                    }
+                    level => {
+                        let prefix = format!("{} ", "#".repeat((level - b'0') as usize));
+                        block.extend(
+                            lines
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, l)| {
+                                    if i == 0 {
+                                        format!("{}{}", prefix, l)
+                                    } else {
+                                        l
+                                    }
+                                })
+                                .collect(),
+                        );
+                    }
+                }
+                block.blank();
+            }
+            "ul" | "ol" | "menu" => self.list(element, block),
+            "li" => self.indented(&element.children, block, "* ", "  "),
+            "dd" => self.indented(&element.children, block, "    ", "    "),
+            "blockquote" => {
+                block.blank();
+                self.indented(&element.children, block, "> ", "> ");
+                block.blank();
+            }
+            "pre" => {
+                block.blank();
+                let mut pre = Block::new(block.width, true);
+                self.walk_all(&element.children, &mut pre);
+                block.extend(pre.finish());
+                block.blank();
+            }
+            "table" => {
+                block.flush();
+                let lines = self.table(element, block.width);
+                block.extend(lines);
+            }
+            name if BLOCK_ELEMENTS.contains(&name) => {
+                block.flush();
+                self.walk_all(&element.children, block);
+                block.flush();
+            }
+            _ => self.walk_all(&element.children, block),
+        }
+    }
+
+    /// Renders the content of `element` between two `marker`s.
+    fn styled(&mut self, element: &Element, block: &mut Block, marker: &str) {
+        if block.pre {
+            self.walk_all(&element.children, block);
+            return;
+        }
+        let (space, len) = (block.space, block.line.len());
+        block.word(marker);
+        let (lines, start) = (block.lines.len(), block.line.len());
+        self.walk_all(&element.children, block);
+        if block.lines.len() == lines && block.line.len() == start {
+            /* Nothing was rendered, drop the opening marker. */
+            block.line.truncate(len);
+            block.space = space || block.space;
+        } else if !block.line.is_empty() {
+            block.line.push_str(marker);
+        }
+    }
+
+    fn link(&mut self, element: &Element, block: &mut Block) {
+        let (lines, start) = (block.lines.len(), block.line.len());
+        self.walk_all(&element.children, block);
+        let href = match element.attr("href").map(str::trim) {
+            Some(href)
+                if !href.is_empty()
+                    && !href.starts_with('#')
+                    && !href.to_ascii_lowercase().starts_with("javascript:") =>
+            {
+                href
+            }
+            _ => return,
+        };
+        let text = if block.lines.len() == lines {
+            block.line[start..].trim()
+        } else {
+            ""
+        };
+        if text == href || href.strip_prefix("mailto:") == Some(text) {
+            return;
+        }
+        let n = match self.links.iter().position(|l| l == href) {
+            Some(i) => i + 1,
+            None => {
+                self.links.push(href.to_string());
+                self.links.len()
+            }
+        };
+        let reference = format!("[{}]", n);
+        if text.is_empty() {
+            block.word(&reference);
+        } else {
+            block.line.push_str(&reference);
+        }
+    }
+
+    fn list(&mut self, element: &Element, block: &mut Block) {
+        let top_level = self.list_depth == 0;
+        if top_level {
+            block.blank();
+        } else {
+            block.flush();
+        }
+        self.list_depth += 1;
+        let ordered = element.name == "ol";
+        let mut n: i64 = element
+            .attr("start")
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(1);
+        for child in &element.children {
+            match child {
+                Node::Element(item) if item.name == "li" => {
+                    if item.is_hidden() {
+                        continue;
+                    }
+                    let marker = if ordered {
+                        format!("{}. ", n)
+                    } else {
+                        "* ".to_string()
+                    };
+                    n += 1;
+                    let indent = " ".repeat(marker.len());
+                    self.indented(&item.children, block, &marker, &indent);
+                }
+                other => self.walk(other, block),
+            }
+        }
+        self.list_depth -= 1;
+        if top_level {
+            block.blank();
+        } else {
+            block.flush();
+        }
+    }
+
+    /// Renders `nodes` with `first` before their first line and `rest` before
+    /// the others.
+    fn indented(&mut self, nodes: &[Node], block: &mut Block, first: &str, rest: &str) {
+        let width = block.width.saturating_sub(first.chars().count()).max(10);
+        let mut inner = Block::new(width, block.pre);
+        self.walk_all(nodes, &mut inner);
+        let lines = inner
+            .finish()
+            .into_iter()
+            .enumerate()
+            .map(|(i, l)| format!("{}{}", if i == 0 { first } else { rest }, l))
+            .collect();
+        block.extend(lines);
+    }
+
+    fn table(&mut self, table: &Element, width: usize) -> Vec<String> {
+        let mut rows = vec![];
+        let mut ret = vec![];
+        self.table_rows(&table.children, width, &mut rows, &mut ret);
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths = (0..columns)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .flat_map(|cell: &Cell| cell.lines.iter().map(|l| l.chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<usize>>();
+        let visible = widths.iter().filter(|w| **w > 0).count();
+        let grid = visible > 1
+            && rows
+                .iter()
+                .all(|row| row.iter().all(|cell| cell.lines.len() <= 1))
+            && widths.iter().sum::<usize>() + 2 * (visible - 1) <= width;
+        if !grid {
+            for cell in rows.into_iter().flatten() {
+                ret.extend(cell.lines);
+            }
+            return ret;
+        }
+        for (i, row) in rows.iter().enumerate() {
+            if row.iter().all(|cell| cell.lines.is_empty()) {
+                continue;
+            }
+            let mut line = String::new();
+            for (cell, width) in row
+                .iter()
+                .zip(widths.iter().copied())
+                .filter(|(_, w)| *w > 0)
+            {
+                if !line.is_empty() {
+                    line.push_str("  ");
+                }
+                let text = cell.lines.first().map(String::as_str).unwrap_or_default();
+                let _ = write!(line, "{:width$}", text, width = width);
+            }
+            ret.push(line.trim_end().to_string());
+            if i == 0 && rows.len() > 1 && row.iter().all(|cell| cell.header) {
+                ret.push(
+                    widths
+                        .iter()
+                        .filter(|w| **w > 0)
+                        .map(|w| "-".repeat(*w))
+                        .collect::<Vec<_>>()
+                        .join("  "),
+                );
+            }
+        }
+        ret
+    }
+
+    fn table_rows(
+        &mut self,
+        nodes: &[Node],
+        width: usize,
+        rows: &mut Vec<Vec<Cell>>,
+        caption: &mut Vec<String>,
+    ) {
+        for node in nodes {
+            let element = match node {
+                Node::Element(element) if !element.is_hidden() => element,
+                _ => continue,
+            };
+            match element.name.as_str() {
+                "tr" => {
+                    let row = element
+                        .children
+                        .iter()
+                        .filter_map(|node| match node {
+                            Node::Element(cell)
+                                if (cell.name == "td" || cell.name == "th")
+                                    && !cell.is_hidden() =>
+                            {
+                                Some(self.cell(cell, width))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    rows.push(row);
+                }
+                "td" | "th" => {
+                    let cell = self.cell(element, width);
+                    rows.push(vec![cell]);
+                }
+                "thead" | "tbody" | "tfoot" => {
+                    self.table_rows(&element.children, width, rows, caption)
+                }
+                "caption" => {
+                    let mut block = Block::new(width, false);
+                    self.walk_all(&element.children, &mut block);
+                    caption.extend(block.finish());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn cell(&mut self, cell: &Element, width: usize) -> Cell {
+        let mut block = Block::new(width, false);
+        self.walk_all(&cell.children, &mut block);
+        Cell {
+            lines: block.finish(),
+            header: cell.name == "th",
+        }
+    }
+}
+
+/// A rendered table cell.
+struct Cell {
+    lines: Vec<String>,
+    header: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_render_text() {
+        let html = r##"<!DOCTYPE html>
+<html><head><title>Newsletter</title><style>p { color: red; }</style></head>
+<body>
+<span style="display:none">Preheader text&zwnj;&nbsp;&zwnj;</span>
+<h1>Weekly   news</h1>
+<p>Hello <b>dear</b> reader,<br>this is <em>the</em> <a href="https://example.com/a">first link</a>.
+<p>Fish &amp; chips <code></code>cost &pound;5. See <a href="https://example.com/a">it</a>, <a href="mailto:me@example.com">me@example.com</a> and <a href="#top">top</a>.</p>
+<ul><li>One<li>Two<ol start="3"><li>Three</ol></ul>
+<blockquote>Quoted text</blockquote>
+<pre>
+  indented
+	tab</pre>
+<script>document.write("<p>no</p>")</script>
+</body></html>"##;
+        let rendered = render(html, 80);
+        assert_eq!(
+            rendered.text,
+            "Weekly news
+===========
+
+Hello *dear* reader,
+this is _the_ first link[1].
+
+Fish & chips cost £5. See it[1], me@example.com and top.
+
+* One
+* Two
+  3. Three
+
+> Quoted text
+
+  indented
+    tab
+
+Links:
+[1] https://example.com/a
+"
+        );
+        assert_eq!(rendered.links, vec!["https://example.com/a".to_string()]);
+    }
+
+    #[test]
+    fn test_html_render_wrap_and_tables() {
+        assert_eq!(
+            render("<p>one two three four five six</p>", 20).text,
+            "one two three four\nfive six\n"
+        );
+        assert_eq!(
+            render(
+                "<table><tr><th>Item<th>Price<tr><td>Tea<td>2<tr><td>Coffee<td>3</table>",
+                80
+            )
+            .text,
+            "Item    Price\n------  -----\nTea     2\nCoffee  3\n"
+        );
+        /* A layout table, with a cell of more than one line. */
+        assert_eq!(
+            render(
+                "<table><tr><td><p>Left</p><p>column</p></td><td>Right</td></tr></table>",
+                80
+            )
+            .text,
+            "Left\n\ncolumn\nRight\n"
+        );
+        assert_eq!(
+            render(
+                "<p><a href='https://example.com/x'><img src='x.png'></a></p>",
+                80
+            )
+            .text,
+            "[1]\n\nLinks:\n[1] https://example.com/x\n"
+        );
+        assert_eq!(render("a < b &gt; c <3", 80).text, "a < b > c <3\n");
+    }
+
+    #[test]
+    fn test_html_render_elements() {
+        assert_eq!(
+            render(
+                "<h3>Agenda</h3><dl><dt>Monday<dd>Planning</dl><hr><p>A <q>quote</q>, \
+                 <i></i><a href='javascript:void(0)'>no link</a> and <span hidden>no</span>\
+                 <tt>code</tt>.",
+                20
+            )
+            .text,
+            "### Agenda\n\nMonday\n    Planning\n--------------------\n\nA \"quote\", no link\nand `code`.\n"
+        );
+        assert_eq!(
+            render("<ul><li>a<ul><li>b<li>c</ul><li>d</ul>", 80).text,
+            "* a\n  * b\n  * c\n* d\n"
+        );
+    }
+
+    #[test]
+    fn test_html_render_raw_text() {
+        assert_eq!(
+            render(
+                "<STYLE>p { color: red; }</Style>shown<TITLE>Title</title>",
+                80
+            )
+            .text,
+            "shown\n"
+        );
+        assert_eq!(
+            render("<textarea><p>not markup</textarea >after", 80).text,
+            "after\n"
+        );
+        assert_eq!(
+            render("before<script>unterminated <b>", 80).text,
+            "before\n"
+        );
+        /* Each raw text element is scanned once. */
+        let html = "<style>x</style>text ".repeat(20_000);
+        assert_eq!(render(&html, 80).text.matches("text").count(), 20_000);
+    }
+
+    #[test]
+    fn test_html_render_deep_nesting() {
+        let depth = 100_000;
+        let html = format!(
+            "{}deep<script>hidden</script>{}after",
+            "<div><span>".repeat(depth),
+            "</span></div>".repeat(depth)
+        );
+        assert_eq!(render(&html, 80).text, "deep\nafter\n");
+        let nodes = parse(&html);
+        let mut depth = 0;
+        let mut level = &nodes;
+        while let Some(Node::Element(element)) = level.first() {
+            depth += 1;
+            level = &element.children;
+        }
+        assert_eq!(depth, MAX_DEPTH);
+    }
+}
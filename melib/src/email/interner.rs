@@ -0,0 +1,180 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Sharing of envelope fields that repeat across messages.
+ *
+ * Subjects, sender addresses and list ids repeat a lot in a mailbox: every
+ * reply of a thread has the same subject and mailing lists send everything
+ * from the same few addresses. [`Envelope`](super::Envelope) keeps them in
+ * reference counted buffers that are looked up here first, so that equal
+ * values share one allocation.
+ *
+ * Envelopes are parsed on several threads at once, so the values are spread
+ * over independently locked shards by their hash.
+ */
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use super::address::Address;
+
+/// Values are dropped from a shard once they are no longer used by any
+/// envelope, checked whenever the shard has grown by this much.
+const PURGE_INTERVAL: usize = 1024;
+/// Number of independently locked sets of an interner.
+const SHARDS: usize = 16;
+
+type Shard<T> = Mutex<Option<(HashSet<Arc<T>>, usize)>>;
+
+struct Interner<T: ?Sized> {
+    shards: [Shard<T>; SHARDS],
+}
+
+impl<T: ?Sized> Interner<T> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SHARD: Shard<T> = Mutex::new(None);
+
+    const fn new() -> Self {
+        Interner {
+            shards: [Self::EMPTY_SHARD; SHARDS],
+        }
+    }
+}
+
+impl<T: ?Sized + Hash + Eq> Interner<T>
+where
+    for<'a> Arc<T>: From<&'a T>,
+{
+    fn intern(&self, value: &T) -> Arc<T> {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let mut lck = self.shards[hasher.finish() as usize % SHARDS]
+            .lock()
+            .unwrap();
+        let (set, purge_at) = lck.get_or_insert_with(|| (HashSet::default(), PURGE_INTERVAL));
+        if let Some(ret) = set.get(value) {
+            return ret.clone();
+        }
+        if set.len() >= *purge_at {
+            set.retain(|v| Arc::strong_count(v) > 1);
+            *purge_at = set.len() + PURGE_INTERVAL;
+        }
+        let ret: Arc<T> = Arc::from(value);
+        set.insert(ret.clone());
+        ret
+    }
+}
+
+static STRINGS: Interner<str> = Interner::new();
+static BYTES: Interner<[u8]> = Interner::new();
+
+/// Returns a shared copy of `value`.
+pub fn intern_str(value: &str) -> Arc<str> {
+    STRINGS.intern(value)
+}
+
+/// Returns a shared copy of `value`.
+pub fn intern_bytes(value: &[u8]) -> Arc<[u8]> {
+    BYTES.intern(value)
+}
+
+/// Replaces the raw buffers of `addresses` with shared copies.
+pub fn intern_addresses(addresses: &mut [Address]) {
+    for address in addresses {
+        match address {
+            Address::Mailbox(m) => m.raw = intern_bytes(&m.raw),
+            Address::Group(g) => {
+                g.raw = intern_bytes(&g.raw);
+                intern_addresses(&mut g.mailbox_list);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::Envelope;
+
+    #[test]
+    fn test_envelope_interning() {
+        const SENDERS: usize = 10;
+        const SUBJECTS: usize = 50;
+        const MESSAGES: usize = 1000;
+
+        let envelopes = (0..MESSAGES)
+            .map(|i| {
+                let raw = format!(
+                    "From: Sender Number {sender} <sender{sender}@lists.example.com>\nTo: \
+                     list@lists.example.com\nSubject: Re: [list] discussion topic number \
+                     {subject}\nList-Id: Discussion list <list.lists.example.com>\nMessage-ID: \
+                     <{i}@example.com>\n\nhello\n",
+                    sender = i % SENDERS,
+                    subject = i % SUBJECTS,
+                    i = i
+                );
+                Envelope::from_bytes(raw.as_bytes(), None).unwrap()
+            })
+            .collect::<Vec<Envelope>>();
+
+        /* Bytes of the subject and sender buffers if every envelope owned its
+         * own copies, and the bytes actually allocated. */
+        let mut before = 0;
+        let mut after = 0;
+        let mut subjects: Vec<Arc<str>> = vec![];
+        let mut list_ids: Vec<Arc<str>> = vec![];
+        let mut senders: Vec<Arc<[u8]>> = vec![];
+        for e in &envelopes {
+            let subject = e.subject.clone().unwrap();
+            before += subject.len();
+            if !subjects.iter().any(|s| Arc::ptr_eq(s, &subject)) {
+                after += subject.len();
+                subjects.push(subject);
+            }
+            let list_id = e.list_id.clone().unwrap();
+            before += list_id.len();
+            if !list_ids.iter().any(|l| Arc::ptr_eq(l, &list_id)) {
+                after += list_id.len();
+                list_ids.push(list_id);
+            }
+            for address in e.from.iter().chain(e.to.iter()) {
+                let raw = match address {
+                    Address::Mailbox(m) => m.raw.clone(),
+                    Address::Group(g) => g.raw.clone(),
+                };
+                before += raw.len();
+                if !senders.iter().any(|s| Arc::ptr_eq(s, &raw)) {
+                    after += raw.len();
+                    senders.push(raw);
+                }
+            }
+        }
+        /* About 142KB without interning and 2KB with it. */
+        assert_eq!(subjects.len(), SUBJECTS);
+        assert_eq!(list_ids.len(), 1);
+        assert_eq!(senders.len(), SENDERS + 1);
+        assert!(before > 130_000, "{}", before);
+        assert!(after < 2_500, "{}", after);
+    }
+}
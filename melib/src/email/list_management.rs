@@ -25,6 +25,8 @@ use std::convert::From;
 use smallvec::SmallVec;
 
 use super::{parser, Envelope};
+#[cfg(feature = "http")]
+use crate::error::{Error, ErrorKind, Result};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ListAction<'a> {
@@ -80,13 +82,39 @@ pub struct ListActions<'a> {
     pub archive: Option<&'a str>,
     pub post: Option<SmallVec<[ListAction<'a>; 4]>>,
     pub unsubscribe: Option<SmallVec<[ListAction<'a>; 4]>>,
+    /// Whether the `List-Unsubscribe-Post` header offers one-click
+    /// unsubscription ([RFC8058](https://tools.ietf.org/html/rfc8058)) with
+    /// the https urls of `unsubscribe`.
+    pub one_click: bool,
+}
+
+/// A way of unsubscribing from a mailing list or newsletter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnsubscribeMethod {
+    /// `POST` `List-Unsubscribe=One-Click` to an https url.
+    OneClick(String),
+    /// Send an e-mail to a `mailto:` url.
+    Email(String),
+    /// Open a url in a web browser.
+    Url(String),
+}
+
+impl std::fmt::Display for UnsubscribeMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::OneClick(url) => write!(f, "one-click unsubscribe via {}", url),
+            Self::Email(mailto) => write!(
+                f,
+                "send e-mail to {}",
+                mailto.strip_prefix("mailto:").unwrap_or(mailto)
+            ),
+            Self::Url(url) => write!(f, "open {}", url),
+        }
+    }
 }
 
 pub fn list_id_header(envelope: &'_ Envelope) -> Option<&'_ str> {
-    envelope
-        .other_headers()
-        .get("List-ID")
-        .or_else(|| envelope.other_headers().get("List-Id"))
+    envelope.list_id()
 }
 
 pub fn list_id(header: Option<&'_ str>) -> Option<&'_ str> {
@@ -133,6 +161,12 @@ impl<'a> ListActions<'a> {
 
         if let Some(unsubscribe) = envelope.other_headers().get("List-Unsubscribe") {
             ret.unsubscribe = ListAction::parse_options_list(unsubscribe.as_bytes());
+            ret.one_click = envelope
+                .other_headers()
+                .get("List-Unsubscribe-Post")
+                .map_or(false, |v| {
+                    v.trim().eq_ignore_ascii_case("List-Unsubscribe=One-Click")
+                });
         }
 
         if ret.id.is_none()
@@ -145,4 +179,107 @@ impl<'a> ListActions<'a> {
             Some(ret)
         }
     }
+
+    /// Returns every way of unsubscribing offered by `List-Unsubscribe`,
+    /// one-click unsubscription first.
+    pub fn unsubscribe_methods(&self) -> Vec<UnsubscribeMethod> {
+        let mut ret = vec![];
+        let options = match self.unsubscribe {
+            Some(ref options) => options,
+            None => return ret,
+        };
+        for option in options.iter() {
+            match *option {
+                ListAction::Url(url) => {
+                    let url = String::from_utf8_lossy(url).into_owned();
+                    if self.one_click && url.starts_with("https://") {
+                        ret.insert(0, UnsubscribeMethod::OneClick(url.clone()));
+                    }
+                    ret.push(UnsubscribeMethod::Url(url));
+                }
+                ListAction::Email(mailto) => {
+                    ret.push(UnsubscribeMethod::Email(
+                        String::from_utf8_lossy(mailto).into_owned(),
+                    ));
+                }
+                ListAction::No => {}
+            }
+        }
+        ret
+    }
+}
+
+/// Unsubscribes with an RFC8058 one-click `POST` request to `url`.
+#[cfg(feature = "http")]
+pub async fn one_click_unsubscribe(url: String) -> Result<()> {
+    use isahc::{
+        config::{Configurable, RedirectPolicy},
+        HttpClient, Request,
+    };
+
+    let client = HttpClient::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        /* RFC8058: the POST must not be redirected. */
+        .redirect_policy(RedirectPolicy::None)
+        .build()?;
+    let req = Request::post(url.as_str())
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .map_err(|err| Error::new(err.to_string()))?;
+    let res = client.send_async(req).await?;
+    if !res.status().is_success() {
+        return Err(Error::new(format!("Could not unsubscribe via {}", url))
+            .set_details(res.status().to_string())
+            .set_kind(ErrorKind::Network(res.status().into())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_methods() {
+        let envelope = Envelope::from_bytes(
+            b"From: news@example.com\nList-Unsubscribe: <https://example.com/u/1>, \
+              <mailto:leave@example.com?subject=unsubscribe>\nList-Unsubscribe-Post: \
+              List-Unsubscribe=One-Click\nSubject: news\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        let actions = ListActions::detect(&envelope).unwrap();
+        assert!(actions.one_click);
+        assert_eq!(
+            actions.unsubscribe_methods(),
+            vec![
+                UnsubscribeMethod::OneClick("https://example.com/u/1".to_string()),
+                UnsubscribeMethod::Email(
+                    "mailto:leave@example.com?subject=unsubscribe".to_string()
+                ),
+                UnsubscribeMethod::Url("https://example.com/u/1".to_string()),
+            ]
+        );
+        assert_eq!(
+            actions.unsubscribe_methods()[1].to_string(),
+            "send e-mail to leave@example.com?subject=unsubscribe"
+        );
+
+        let envelope = Envelope::from_bytes(
+            b"From: news@example.com\nList-Unsubscribe: <http://example.com/u/1>\n\
+              List-Unsubscribe-Post: List-Unsubscribe=One-Click\nSubject: news\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            ListActions::detect(&envelope)
+                .unwrap()
+                .unsubscribe_methods(),
+            vec![UnsubscribeMethod::Url("http://example.com/u/1".to_string())]
+        );
+
+        let envelope =
+            Envelope::from_bytes(b"From: friend@example.com\nSubject: hi\n\nbody\n", None).unwrap();
+        assert!(ListActions::detect(&envelope).is_none());
+    }
 }
@@ -299,12 +299,14 @@ impl<'a, P: for<'r> FnMut(&'r u8) -> bool> BytesIterExt for std::slice::Split<'a
 }
 
 //fn parser(input: I) -> IResult<I, O, E>;
+/// Parses the headers of a message and returns them with its body. Only the
+/// header section is parsed; the body is returned as is.
 pub fn mail(input: &[u8]) -> Result<(Vec<(&[u8], &[u8])>, &[u8])> {
     let (rest, result) = alt((
         separated_pair(
             headers::headers,
             alt((tag(b"\n"), tag(b"\r\n"))),
-            take_while(|_| true),
+            nom::combinator::rest,
         ),
         pair(headers::headers, generic::eof),
     ))(input)
@@ -2026,7 +2028,125 @@ pub mod encodings {
             }
             Charset::ISO2022JP => Ok(ISO_2022_JP.decode(s, DecoderTrap::Strict)?),
             Charset::EUCJP => Ok(EUC_JP.decode(s, DecoderTrap::Strict)?),
+            Charset::ShiftJIS => Ok(WINDOWS_31J.decode(s, DecoderTrap::Strict)?),
+        }
+    }
+
+    /// Guess the charset of `s`, for text with a missing or wrong charset
+    /// declaration.
+    ///
+    /// Every candidate legacy encoding that decodes `s` without errors is
+    /// scored by how plausible the decoded text looks (e.g. lowercase
+    /// Cyrillic letters inside words, kana in Japanese text, ideographs in
+    /// double byte encodings) and the best one is returned. Returns `None` if
+    /// `s` is valid UTF-8 or no candidate produces plausible text.
+    pub fn detect_charset(s: &[u8]) -> Option<Charset> {
+        const CANDIDATES: &[Charset] = &[
+            Charset::ShiftJIS,
+            Charset::EUCJP,
+            Charset::GBK,
+            Charset::BIG5,
+            Charset::KOI8R,
+            Charset::Windows1251,
+            Charset::Windows1252,
+            Charset::Windows1250,
+            Charset::Windows1253,
+        ];
+        if std::str::from_utf8(s).is_ok() {
+            return None;
+        }
+        if s.windows(3).any(|w| w == b"\x1b$B" || w == b"\x1b$@")
+            && decode_charset(s, Charset::ISO2022JP).is_ok()
+        {
+            return Some(Charset::ISO2022JP);
+        }
+        let high_bytes = s.iter().filter(|b| !b.is_ascii()).count() as f64;
+        let mut best: Option<(Charset, f64)> = None;
+        for &charset in CANDIDATES {
+            let text = match decode_charset(s, charset) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            let mut score = charset_plausibility(&text) / high_bytes;
+            if matches!(charset, Charset::ShiftJIS | Charset::EUCJP)
+                && !text.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c))
+            {
+                /* Japanese text without any kana is unlikely. */
+                score /= 2.0;
+            }
+            if best.map(|(_, s)| score > s).unwrap_or(true) {
+                best = Some((charset, score));
+            }
         }
+        best.filter(|(_, score)| *score > 0.0).map(|(c, _)| c)
+    }
+
+    /// Guess the charset of `s` if it is declared as `charset` but decodes to
+    /// implausible text with it, e.g. KOI8-R text declared as ISO-8859-1.
+    ///
+    /// Valid UTF-8 is taken as UTF-8, and other text is checked as in
+    /// [`detect_charset`]. Returns `None` if the declared charset looks right
+    /// or `s` has too few non-ASCII bytes to tell.
+    pub fn detect_misdeclared_charset(s: &[u8], charset: Charset) -> Option<Charset> {
+        const MIN_NON_ASCII: usize = 8;
+        if matches!(
+            charset,
+            Charset::UTF8 | Charset::Ascii | Charset::UTF16 | Charset::ISO2022JP
+        ) || s.iter().filter(|b| !b.is_ascii()).count() < MIN_NON_ASCII
+        {
+            return None;
+        }
+        if std::str::from_utf8(s).is_ok() {
+            return Some(Charset::UTF8);
+        }
+        let text = decode_charset(s, charset).ok()?;
+        if charset_plausibility(&text) >= 0.0 {
+            return None;
+        }
+        detect_charset(s).filter(|c| *c != charset)
+    }
+
+    fn charset_plausibility(text: &str) -> f64 {
+        let is_cyrillic_lower = |c: char| ('\u{0430}'..='\u{044f}').contains(&c) || c == '\u{0451}';
+        let is_cyrillic_upper = |c: char| ('\u{0410}'..='\u{042f}').contains(&c) || c == '\u{0401}';
+        let is_greek = |c: char| ('\u{0386}'..='\u{03ce}').contains(&c);
+        let is_latin = |c: char| {
+            (('\u{00c0}'..='\u{017f}').contains(&c)) && c != '\u{00d7}' && c != '\u{00f7}'
+        };
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut score = 0.0;
+        for (i, &c) in chars.iter().enumerate() {
+            if c.is_ascii() {
+                continue;
+            }
+            let prev = if i > 0 { chars[i - 1] } else { ' ' };
+            let next = chars.get(i + 1).copied().unwrap_or(' ');
+            let ascii_neighbour = prev.is_ascii_alphabetic() || next.is_ascii_alphabetic();
+            score += match c {
+                '\u{fffd}' | '\u{80}'..='\u{9f}' => -5.0,
+                c if is_cyrillic_lower(c) && ascii_neighbour => -1.0,
+                c if is_cyrillic_lower(c) => 2.0,
+                c if is_cyrillic_upper(c) && is_cyrillic_lower(prev) => -2.0,
+                c if is_cyrillic_upper(c) && ascii_neighbour => -1.0,
+                c if is_cyrillic_upper(c) => 1.0,
+                c if is_greek(c) && ascii_neighbour => -1.0,
+                c if is_greek(c) => 2.0,
+                /* Accented letters are usually surrounded by ASCII letters, while runs of
+                 * them point to a misdetected non-Latin script. */
+                c if is_latin(c) && ascii_neighbour => 2.0,
+                c if is_latin(c) && !prev.is_alphabetic() && !next.is_alphabetic() => 1.0,
+                c if is_latin(c) => -1.0,
+                '\u{3040}'..='\u{30ff}' => 3.0,
+                '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff5e}' => 2.0,
+                '\u{4e00}'..='\u{9fff}' => 2.0,
+                '\u{ff61}'..='\u{ff9f}' => 0.0,
+                '\u{2010}'..='\u{2027}' | '\u{20ac}' | '\u{a0}' | '\u{ab}' | '\u{bb}' => 1.0,
+                /* Symbols such as `£` and `°`. */
+                '\u{a1}'..='\u{bf}' => 0.0,
+                _ => -1.0,
+            };
+        }
+        score
     }
 
     fn quoted_printable_soft_break(input: &[u8]) -> IResult<&[u8], u8> {
@@ -2274,7 +2394,7 @@ pub mod address {
                 Ok((
                     input.get(rest_start..).unwrap_or_default(),
                     Address::Mailbox(MailboxAddress {
-                        raw,
+                        raw: raw.into(),
                         display_name,
                         address_spec,
                     }),
@@ -3033,4 +3153,71 @@ border=3D=220=22>
         let c = msg_id(s).unwrap().1;
         assert_eq!(&msg_id_list(value).unwrap().1, &[a, c]);
     }
+
+    #[test]
+    fn test_detect_charset() {
+        use encoding::{all::*, EncoderTrap, Encoding};
+
+        use crate::email::attachment_types::Charset;
+
+        assert_eq!(detect_charset("Καλημέρα".as_bytes()), None);
+        let russian = "Привет! Это тестовое сообщение, отправленное без указания кодировки.";
+        for (enc, charset) in [
+            (KOI8_R as &dyn Encoding, Charset::KOI8R),
+            (WINDOWS_1251, Charset::Windows1251),
+        ] {
+            let bytes = enc.encode(russian, EncoderTrap::Strict).unwrap();
+            assert_eq!(detect_charset(&bytes), Some(charset));
+        }
+        let japanese = "こんにちは、世界。これは文字コードのテストです。";
+        for (enc, charset) in [
+            (WINDOWS_31J as &dyn Encoding, Charset::ShiftJIS),
+            (EUC_JP, Charset::EUCJP),
+        ] {
+            let bytes = enc.encode(japanese, EncoderTrap::Strict).unwrap();
+            assert_eq!(detect_charset(&bytes), Some(charset));
+        }
+        let bytes = GBK
+            .encode(
+                "你好，世界。这是一个字符集检测的测试。",
+                EncoderTrap::Strict,
+            )
+            .unwrap();
+        assert_eq!(detect_charset(&bytes), Some(Charset::GBK));
+        let bytes = WINDOWS_1252
+            .encode(
+                "Café crème, façade et déjà vu à l'hôtel.",
+                EncoderTrap::Strict,
+            )
+            .unwrap();
+        assert_eq!(detect_charset(&bytes), Some(Charset::Windows1252));
+
+        let mut raw = b"Content-Type: text/plain\nContent-Transfer-Encoding: 8bit\n\n".to_vec();
+        raw.extend(KOI8_R.encode(russian, EncoderTrap::Strict).unwrap());
+        let attachment = crate::email::AttachmentBuilder::new(&raw).build();
+        assert_eq!(attachment.text(), russian);
+
+        let koi8 = KOI8_R.encode(russian, EncoderTrap::Strict).unwrap();
+        assert_eq!(
+            detect_misdeclared_charset(&koi8, Charset::ISO8859_1),
+            Some(Charset::KOI8R)
+        );
+        assert_eq!(
+            detect_misdeclared_charset(russian.as_bytes(), Charset::ISO8859_1),
+            Some(Charset::UTF8)
+        );
+        for latin in [
+            "Café crème, façade et déjà vu à l'hôtel.",
+            "£5 each, £20 for five, 30° in the shade, © the authors, § 3.",
+        ] {
+            let bytes = ISO_8859_1.encode(latin, EncoderTrap::Strict).unwrap();
+            assert_eq!(detect_misdeclared_charset(&bytes, Charset::ISO8859_1), None);
+        }
+        let mut raw = b"Content-Type: text/plain; charset=iso-8859-1\nContent-Transfer-Encoding: \
+                        8bit\n\n"
+            .to_vec();
+        raw.extend(&koi8);
+        let attachment = crate::email::AttachmentBuilder::new(&raw).build();
+        assert_eq!(attachment.text(), russian);
+    }
 }
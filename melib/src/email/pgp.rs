@@ -19,15 +19,34 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-/*! Verification of OpenPGP signatures */
+/*! Verification of OpenPGP signatures and protected headers of encrypted
+ * messages */
 use crate::{
     email::{
         attachment_types::{ContentType, MultipartType},
         attachments::Attachment,
+        compose::mime::encode_header,
+        parser,
     },
     Error, Result,
 };
 
+/// Headers copied into the encrypted part of a message, following the
+/// [protected headers](https://datatracker.ietf.org/doc/draft-autocrypt-lamps-protected-headers/)
+/// draft ("memory hole").
+pub const PROTECTED_HEADERS: &[&str] = &[
+    "From",
+    "To",
+    "Cc",
+    "Reply-To",
+    "Mail-Followup-To",
+    "Subject",
+];
+
+/// Subject of the outer, unencrypted headers of a message whose real subject
+/// is protected.
+pub const OBSCURED_SUBJECT: &str = "...";
+
 /// Convert raw attachment to the form needed for signature verification ([rfc3156](https://tools.ietf.org/html/rfc3156))
 ///
 /// ## rfc3156
@@ -139,6 +158,90 @@ pub fn verify_signature(a: &Attachment) -> Result<(Vec<u8>, &Attachment)> {
     }
 }
 
+/// Returns the raw MIME entity of `a` with `headers` prepended to its content
+/// headers and the `protected-headers="v1"` Content-Type parameter, ready to
+/// be encrypted.
+pub fn with_protected_headers(mut a: Attachment, headers: &[(&str, String)]) -> Vec<u8> {
+    match a.content_type {
+        ContentType::Text {
+            ref mut parameters, ..
+        }
+        | ContentType::Multipart {
+            ref mut parameters, ..
+        }
+        | ContentType::OctetStream {
+            ref mut parameters, ..
+        } => {
+            parameters.push((b"protected-headers".to_vec(), b"v1".to_vec()));
+        }
+        _ => {}
+    }
+    let mut ret = String::new();
+    for (name, value) in headers {
+        ret.push_str(name);
+        ret.push_str(": ");
+        ret.push_str(&encode_header(value));
+        ret.push_str("\r\n");
+    }
+    ret.push_str(&a.into_raw());
+    ret.into_bytes()
+}
+
+/// Returns the protected headers of the decrypted MIME entity `raw`, or
+/// nothing if its Content-Type doesn't have the `protected-headers`
+/// parameter.
+pub fn protected_headers(raw: &[u8]) -> Vec<(String, String)> {
+    let headers = match parser::attachments::attachment(raw) {
+        Ok((_, (headers, _))) => headers,
+        Err(_) => return vec![],
+    };
+    let is_protected = headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case(b"Content-Type")
+            && parser::attachments::content_type(value)
+                .map(|(_, (_, _, params))| {
+                    params
+                        .iter()
+                        .any(|(n, _)| n.eq_ignore_ascii_case(b"protected-headers"))
+                })
+                .unwrap_or(false)
+    });
+    if !is_protected {
+        return vec![];
+    }
+    headers
+        .into_iter()
+        .filter(|(name, _)| {
+            !name.to_ascii_lowercase().starts_with(b"content-")
+                && !name.eq_ignore_ascii_case(b"MIME-Version")
+        })
+        .map(|(name, value)| {
+            let value = parser::encodings::phrase(value, false)
+                .map(|(_, v)| String::from_utf8_lossy(&v).into_owned())
+                .unwrap_or_else(|_| String::from_utf8_lossy(value).into_owned());
+            (String::from_utf8_lossy(name).into_owned(), value)
+        })
+        .collect()
+}
+
+/// Returns the byte range of the first ASCII armored OpenPGP message in
+/// `text`, the form of inline PGP encrypted bodies.
+pub fn inline_pgp_message(text: &[u8]) -> Option<std::ops::Range<usize>> {
+    use parser::BytesExt;
+
+    const BEGIN: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+    const END: &[u8] = b"-----END PGP MESSAGE-----";
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find(BEGIN) {
+        let start = offset + pos;
+        if start == 0 || text[start - 1] == b'\n' {
+            let end = start + text[start..].find(END)? + END.len();
+            return Some(start..end);
+        }
+        offset = start + BEGIN.len();
+    }
+    None
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DecryptionMetadata {
     pub recipients: Vec<Recipient>,
@@ -159,3 +262,51 @@ pub struct SignatureMetadata {
     pub file_name: Option<String>,
     pub is_mime: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::attachments::AttachmentBuilder;
+
+    #[test]
+    fn test_pgp_inline_message() {
+        let armored = "-----BEGIN PGP MESSAGE-----\n\nhQEMA3x1\n=abcd\n-----END PGP MESSAGE-----";
+        assert_eq!(
+            inline_pgp_message(armored.as_bytes()),
+            Some(0..armored.len())
+        );
+        let text = format!("Hi,\n\n{}\n\n-- \nsig\n", armored);
+        let range = inline_pgp_message(text.as_bytes()).unwrap();
+        assert_eq!(&text[range], armored);
+        /* Quoted or unterminated armor is not a message. */
+        assert_eq!(
+            inline_pgp_message(b"> -----BEGIN PGP MESSAGE-----\n> hQEMA3x1\n"),
+            None
+        );
+        assert_eq!(
+            inline_pgp_message(b"-----BEGIN PGP MESSAGE-----\nhQEMA3x1\n"),
+            None
+        );
+        assert_eq!(inline_pgp_message(b"plain text"), None);
+    }
+
+    #[test]
+    fn test_pgp_protected_headers() {
+        let body = AttachmentBuilder::new(b"Content-Type: text/plain\r\n\r\nHello\r\n").build();
+        let headers = vec![
+            ("From", "Alice <alice@example.com>".to_string()),
+            ("Subject", "Ünïcode plans".to_string()),
+        ];
+        let raw = with_protected_headers(body, &headers);
+        let parsed = AttachmentBuilder::new(&raw).build();
+        assert_eq!(parsed.body(), b"Hello\r\n");
+        assert_eq!(
+            protected_headers(&raw),
+            vec![
+                ("From".to_string(), "Alice <alice@example.com>".to_string()),
+                ("Subject".to_string(), "Ünïcode plans".to_string()),
+            ]
+        );
+        assert!(protected_headers(b"Subject: x\r\nContent-Type: text/plain\r\n\r\nHi").is_empty());
+    }
+}
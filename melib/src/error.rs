@@ -562,6 +562,16 @@ impl From<native_tls::Error> for Error {
     }
 }
 
+#[cfg(feature = "tls")]
+impl From<openssl::error::ErrorStack> for Error {
+    #[inline]
+    fn from(kind: openssl::error::ErrorStack) -> Error {
+        Error::new(kind.to_string())
+            .set_source(Some(Arc::new(kind)))
+            .set_kind(ErrorKind::Network(NetworkErrorKind::BadServerCertificate))
+    }
+}
+
 impl From<std::num::ParseIntError> for Error {
     #[inline]
     fn from(kind: std::num::ParseIntError) -> Error {
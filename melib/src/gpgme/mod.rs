@@ -42,7 +42,7 @@ use smol::Async;
 
 use crate::{
     email::{
-        pgp::{DecryptionMetadata, Recipient},
+        pgp::{DecryptionMetadata, Recipient, SignatureMetadata},
         Address,
     },
     error::{Error, ErrorKind, IntoError, Result, ResultIntoError},
@@ -465,7 +465,7 @@ impl Context {
         &mut self,
         mut signature: Data,
         mut text: Data,
-    ) -> Result<impl Future<Output = Result<()>> + Send> {
+    ) -> Result<impl Future<Output = Result<SignatureMetadata>> + Send> {
         unsafe {
             gpgme_error_try(
                 &self.inner.lib,
@@ -544,6 +544,8 @@ impl Context {
                 io_state_lck.receiver.clone()
             };
             let _ = rcv.recv().await;
+            let mut missing_key = None;
+            let mut signatures = vec![];
             {
                 let verify_result: gpgme_verify_result_t =
                     unsafe { call!(&ctx.lib, gpgme_op_verify_result)(ctx.inner.as_ptr()) };
@@ -553,6 +555,31 @@ impl Context {
                     )
                     .set_err_kind(ErrorKind::External));
                 }
+                let mut signature = unsafe { (*verify_result).signatures };
+                while !signature.is_null() {
+                    let sig = unsafe { &*signature };
+                    let fingerprint = if sig.fpr.is_null() {
+                        None
+                    } else {
+                        Some(
+                            unsafe { CStr::from_ptr(sig.fpr) }
+                                .to_string_lossy()
+                                .to_string(),
+                        )
+                    };
+                    if sig.status & GPG_ERR_CODE_MASK == GPG_ERR_NO_PUBKEY {
+                        if let Some(ref fingerprint) = fingerprint {
+                            missing_key = Some(MissingKey {
+                                fingerprint: fingerprint.clone(),
+                            });
+                        }
+                    }
+                    signatures.push(Recipient {
+                        keyid: fingerprint,
+                        status: gpgme_error_try(&ctx.lib, sig.status),
+                    });
+                    signature = sig.next;
+                }
                 unsafe { call!(&ctx.lib, gpgme_free)(verify_result as *mut ::libc::c_void) };
             }
             let io_state_lck = io_state.lock().unwrap();
@@ -562,7 +589,31 @@ impl Context {
                 .unwrap()
                 .take()
                 .unwrap_or_else(|| Err(Error::new("Unspecified libgpgme error")));
-            ret
+            ret?;
+            if let Some(missing_key) = missing_key {
+                return Err(
+                    Error::new(missing_key.to_string()).set_source(Some(Arc::new(missing_key)))
+                );
+            }
+            if signatures.is_empty() {
+                return Err(Error::new("No signatures found."));
+            }
+            if let Some(Recipient {
+                keyid,
+                status: Err(err),
+            }) = signatures.iter().find(|s| s.status.is_err())
+            {
+                return Err(Error::new(format!(
+                    "Bad signature by key {}: {}",
+                    keyid.as_deref().unwrap_or("(unknown)"),
+                    err
+                )));
+            }
+            Ok(SignatureMetadata {
+                signatures,
+                file_name: None,
+                is_mime: false,
+            })
         })
     }
 
@@ -969,13 +1020,43 @@ impl Context {
         &mut self,
         sign_keys: Option<Vec<Key>>,
         encrypt_keys: Vec<Key>,
-        mut plain: Data,
+        plain: Data,
     ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
         if encrypt_keys.is_empty() {
             return Err(
                 Error::new("gpgme: Call to encrypt() with zero keys.").set_kind(ErrorKind::Bug)
             );
         }
+        self.encrypt_with_flags(
+            sign_keys,
+            encrypt_keys,
+            gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_ENCRYPT_TO
+                | gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_COMPRESS,
+            plain,
+        )
+    }
+
+    /// Encrypts `plain` with a passphrase instead of public keys. The
+    /// passphrase is asked for by the gpg-agent's pinentry.
+    pub fn encrypt_symmetric(
+        &mut self,
+        plain: Data,
+    ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
+        self.encrypt_with_flags(
+            None,
+            vec![],
+            gpgme_encrypt_flags_t_GPGME_ENCRYPT_SYMMETRIC,
+            plain,
+        )
+    }
+
+    fn encrypt_with_flags(
+        &mut self,
+        sign_keys: Option<Vec<Key>>,
+        encrypt_keys: Vec<Key>,
+        flags: gpgme_encrypt_flags_t,
+        mut plain: Data,
+    ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
         unsafe {
             call!(&self.inner.lib, gpgme_signers_clear)(self.inner.inner.as_ptr());
         }
@@ -1004,6 +1085,12 @@ impl Context {
         let mut raw_keys: Vec<gpgme_key_t> = Vec::with_capacity(encrypt_keys.len() + 1);
         raw_keys.extend(encrypt_keys.iter().map(|k| k.inner.inner.as_ptr()));
         raw_keys.push(std::ptr::null_mut());
+        /* Symmetric encryption expects a NULL recipient list. */
+        let recipients = if encrypt_keys.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            raw_keys.as_mut_ptr()
+        };
         unsafe {
             gpgme_error_try(
                 &self.inner.lib,
@@ -1014,18 +1101,16 @@ impl Context {
                 if also_sign {
                     call!(&self.inner.lib, gpgme_op_encrypt_sign_start)(
                         self.inner.inner.as_ptr(),
-                        raw_keys.as_mut_ptr(),
-                        gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_ENCRYPT_TO
-                            | gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_COMPRESS,
+                        recipients,
+                        flags,
                         plain.inner.as_mut(),
                         cipher,
                     )
                 } else {
                     call!(&self.inner.lib, gpgme_op_encrypt_start)(
                         self.inner.inner.as_ptr(),
-                        raw_keys.as_mut_ptr(),
-                        gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_ENCRYPT_TO
-                            | gpgme_encrypt_flags_t_GPGME_ENCRYPT_NO_COMPRESS,
+                        recipients,
+                        flags,
                         plain.inner.as_mut(),
                         cipher,
                     )
@@ -1125,6 +1210,177 @@ impl Context {
             cipher.into_bytes()
         })
     }
+
+    /// Exports the public part of `keys`, armored if the `AsciiArmor` flag is
+    /// set.
+    pub fn export_keys(
+        &mut self,
+        keys: Vec<Key>,
+    ) -> Result<impl Future<Output = Result<Vec<u8>>> + Send> {
+        if keys.is_empty() {
+            return Err(
+                Error::new("gpgme: Call to export_keys() with zero keys.").set_kind(ErrorKind::Bug)
+            );
+        }
+        let mut keydata: gpgme_data_t = std::ptr::null_mut();
+        let mut raw_keys: Vec<gpgme_key_t> = Vec::with_capacity(keys.len() + 1);
+        raw_keys.extend(keys.iter().map(|k| k.inner.inner.as_ptr()));
+        raw_keys.push(std::ptr::null_mut());
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_data_new)(&mut keydata),
+            )?;
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_export_keys_start)(
+                    self.inner.inner.as_ptr(),
+                    raw_keys.as_mut_ptr(),
+                    GPGME_EXPORT_MODE_MINIMAL,
+                    keydata,
+                ),
+            )?;
+        }
+        let mut keydata = Data {
+            lib: self.inner.lib.clone(),
+            kind: DataKind::Memory,
+            inner: core::ptr::NonNull::new(keydata)
+                .ok_or_else(|| Error::new("internal libgpgme error").set_kind(ErrorKind::Bug))?,
+        };
+
+        let io_state = self.io_state.clone();
+        let io_state_lck = self.io_state.lock().unwrap();
+        let done = io_state_lck.done.clone();
+        let fut = io_state_lck
+            .ops
+            .values()
+            .map(|a| Async::new(a.clone()).unwrap())
+            .collect::<Vec<Async<GpgmeFd>>>();
+        drop(io_state_lck);
+        Ok(async move {
+            futures::future::join_all(fut.iter().map(|fut| {
+                let done = done.clone();
+                if fut.get_ref().write {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.write_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                } else {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.read_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                }
+            }))
+            .await;
+            let rcv = {
+                let io_state_lck = io_state.lock().unwrap();
+                io_state_lck.receiver.clone()
+            };
+            let _ = rcv.recv().await;
+            let io_state_lck = io_state.lock().unwrap();
+            io_state_lck
+                .done
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| Err(Error::new("Unspecified libgpgme error")))?;
+
+            /* Rewind cursor */
+            keydata
+                .seek(std::io::SeekFrom::Start(0))
+                .chain_err_summary(|| "libgpgme error: could not perform seek on key data")?;
+            keydata.into_bytes()
+        })
+    }
+}
+
+const GPG_ERR_CODE_MASK: gpgme_error_t = 65535;
+const GPG_ERR_NO_PUBKEY: gpgme_error_t = 9;
+
+/// A signature could not be verified because the public key of the signer is
+/// not in the keyring.
+#[derive(Debug, Clone)]
+pub struct MissingKey {
+    pub fingerprint: String,
+}
+
+impl std::fmt::Display for MissingKey {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "No public key for signature by {}", self.fingerprint)
+    }
+}
+
+impl std::error::Error for MissingKey {}
+
+/// How sure the keyring is that a user ID belongs to the owner of its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Validity {
+    Unknown,
+    Undefined,
+    Never,
+    Marginal,
+    Full,
+    Ultimate,
+}
+
+#[allow(non_upper_case_globals)]
+impl From<gpgme_validity_t> for Validity {
+    fn from(val: gpgme_validity_t) -> Self {
+        match val {
+            gpgme_validity_t_GPGME_VALIDITY_UNDEFINED => Validity::Undefined,
+            gpgme_validity_t_GPGME_VALIDITY_NEVER => Validity::Never,
+            gpgme_validity_t_GPGME_VALIDITY_MARGINAL => Validity::Marginal,
+            gpgme_validity_t_GPGME_VALIDITY_FULL => Validity::Full,
+            gpgme_validity_t_GPGME_VALIDITY_ULTIMATE => Validity::Ultimate,
+            _ => Validity::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for Validity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Validity::Unknown => write!(fmt, "unknown"),
+            Validity::Undefined => write!(fmt, "undefined"),
+            Validity::Never => write!(fmt, "never"),
+            Validity::Marginal => write!(fmt, "marginal"),
+            Validity::Full => write!(fmt, "full"),
+            Validity::Ultimate => write!(fmt, "ultimate"),
+        }
+    }
 }
 
 fn gpgme_error_try(lib: &libloading::Library, error_code: GpgmeError) -> Result<()> {
@@ -1301,6 +1557,25 @@ impl Key {
     pub fn fingerprint(&self) -> Cow<'_, str> {
         (unsafe { CStr::from_ptr((*(self.inner.inner.as_ptr())).fpr) }).to_string_lossy()
     }
+
+    /// Returns the validity of the user ID with address `email`, or
+    /// [`Validity::Unknown`] if the key has none.
+    pub fn uid_validity(&self, email: &str) -> Validity {
+        let mut uid = unsafe { (*(self.inner.inner.as_ptr())).uids };
+        while !uid.is_null() {
+            unsafe {
+                if !(*uid).email.is_null()
+                    && CStr::from_ptr((*uid).email)
+                        .to_string_lossy()
+                        .eq_ignore_ascii_case(email)
+                {
+                    return Validity::from((*uid).validity);
+                }
+                uid = (*uid).next;
+            }
+        }
+        Validity::Unknown
+    }
 }
 
 impl std::fmt::Debug for Key {
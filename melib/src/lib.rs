@@ -112,6 +112,7 @@ pub mod search;
 
 #[cfg(feature = "gpgme")]
 pub mod gpgme;
+pub mod smime;
 #[cfg(feature = "smtp")]
 pub mod smtp;
 #[cfg(feature = "sqlite3")]
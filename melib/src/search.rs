@@ -24,7 +24,7 @@ use std::{borrow::Cow, convert::TryFrom};
 pub use query_parser::query;
 use Query::*;
 
-use crate::{parsec::*, UnixTimestamp};
+use crate::{backends::TagHash, email::Flag, parsec::*, UnixTimestamp};
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
 pub enum Query {
@@ -47,6 +47,8 @@ pub enum Query {
     /* * * * */
     Flags(Vec<String>),
     HasAttachment,
+    /// Matches file names and extracted text of attachments.
+    Attachment(String),
     And(Box<Query>, Box<Query>),
     Or(Box<Query>, Box<Query>),
     Not(Box<Query>),
@@ -79,7 +81,9 @@ impl QueryTrait for crate::Envelope {
                     || self.is_match(&Cc(s.clone()))
                     || self.is_match(&Bcc(s.clone()))
             }
-            Flags(v) => v.iter().any(|s| self.flags() == s.as_str()),
+            Flags(v) => v
+                .iter()
+                .all(|s| FlagQuery::new(s).is_match(self.flags(), self.tags())),
             Subject(s) => self.other_headers()["Subject"].contains(s),
             HasAttachment => self.has_attachments(),
             And(q_a, q_b) => self.is_match(q_a) && self.is_match(q_b),
@@ -90,6 +94,139 @@ impl QueryTrait for crate::Envelope {
     }
 }
 
+/// A single term of a `flags:`/`tags:`/`is:` query atom, normalised so that
+/// every backend agrees on what e.g. `is:unread` or `flag:flagged` means.
+///
+/// Terms that name a known flag (or its negation) map to [`Flag`] values,
+/// anything else is a user tag/keyword.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlagQuery<'a> {
+    Set(Flag),
+    Unset(Flag),
+    Tag(&'a str),
+}
+
+impl<'a> FlagQuery<'a> {
+    pub fn new(term: &'a str) -> Self {
+        use FlagQuery::*;
+        match term.to_ascii_lowercase().as_str() {
+            "seen" | "read" => Set(Flag::SEEN),
+            "unseen" | "unread" | "new" | "recent" => Unset(Flag::SEEN),
+            "flagged" | "starred" => Set(Flag::FLAGGED),
+            "unflagged" | "unstarred" => Unset(Flag::FLAGGED),
+            "replied" | "answered" => Set(Flag::REPLIED),
+            "unreplied" | "unanswered" => Unset(Flag::REPLIED),
+            "draft" => Set(Flag::DRAFT),
+            "trashed" | "trash" | "deleted" | "junk" => Set(Flag::TRASHED),
+            "passed" | "forwarded" => Set(Flag::PASSED),
+            _ => Tag(term),
+        }
+    }
+
+    pub fn is_match(&self, flags: Flag, tags: &[TagHash]) -> bool {
+        match self {
+            FlagQuery::Set(f) => flags.contains(*f),
+            FlagQuery::Unset(f) => !flags.contains(*f),
+            FlagQuery::Tag(t) => tags.contains(&TagHash::from_bytes(t.as_bytes())),
+        }
+    }
+}
+
+/// Query atoms that not every query engine can evaluate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchAtom {
+    Text,
+    Flag,
+    Tag,
+    HasAttachment,
+    Attachment,
+}
+
+impl std::fmt::Display for SearchAtom {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SearchAtom::Text => write!(fmt, "body text"),
+            SearchAtom::Flag => write!(fmt, "flag:"),
+            SearchAtom::Tag => write!(fmt, "tag:"),
+            SearchAtom::HasAttachment => write!(fmt, "has:attachment"),
+            SearchAtom::Attachment => write!(fmt, "attachment:"),
+        }
+    }
+}
+
+/// Search capabilities of a query engine, used to decide whether a query can
+/// be sent to a backend as-is or has to be evaluated locally.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SearchCapabilities {
+    pub text: bool,
+    pub flags: bool,
+    pub tags: bool,
+    pub has_attachment: bool,
+    pub attachment_content: bool,
+}
+
+impl SearchCapabilities {
+    pub const ALL: Self = SearchCapabilities {
+        text: true,
+        flags: true,
+        tags: true,
+        has_attachment: true,
+        attachment_content: true,
+    };
+
+    /// What [`QueryTrait::is_match`] can evaluate from envelope data alone.
+    pub const LOCAL: Self = SearchCapabilities {
+        text: false,
+        flags: true,
+        tags: true,
+        has_attachment: true,
+        attachment_content: false,
+    };
+
+    pub fn supports(&self, atom: SearchAtom) -> bool {
+        match atom {
+            SearchAtom::Text => self.text,
+            SearchAtom::Flag => self.flags,
+            SearchAtom::Tag => self.tags,
+            SearchAtom::HasAttachment => self.has_attachment,
+            SearchAtom::Attachment => self.attachment_content,
+        }
+    }
+}
+
+impl Query {
+    /// Returns the first atom in this query that `caps` can't evaluate, if
+    /// any.
+    pub fn unsupported_atom(&self, caps: SearchCapabilities) -> Option<SearchAtom> {
+        let atom = match self {
+            Body(_) | AllText(_) => SearchAtom::Text,
+            Flags(v) => {
+                return v
+                    .iter()
+                    .map(|t| match FlagQuery::new(t) {
+                        FlagQuery::Tag(_) => SearchAtom::Tag,
+                        FlagQuery::Set(_) | FlagQuery::Unset(_) => SearchAtom::Flag,
+                    })
+                    .find(|atom| !caps.supports(*atom));
+            }
+            HasAttachment => SearchAtom::HasAttachment,
+            Attachment(_) => SearchAtom::Attachment,
+            And(q_a, q_b) | Or(q_a, q_b) => {
+                return q_a
+                    .unsupported_atom(caps)
+                    .or_else(|| q_b.unsupported_atom(caps));
+            }
+            Not(q) => return q.unsupported_atom(caps),
+            _ => return None,
+        };
+        if caps.supports(atom) {
+            None
+        } else {
+            Some(atom)
+        }
+    }
+}
+
 impl TryFrom<&str> for Query {
     type Error = crate::error::Error;
     fn try_from(t: &str) -> crate::error::Result<Query> {
@@ -111,6 +248,14 @@ pub mod query_parser {
         .map(Query::Subject)
     }
 
+    fn body<'a>() -> impl Parser<'a, Query> {
+        prefix(
+            whitespace_wrap(match_literal("body:")),
+            whitespace_wrap(literal()),
+        )
+        .map(Query::Body)
+    }
+
     fn from<'a>() -> impl Parser<'a, Query> {
         prefix(
             whitespace_wrap(match_literal("from:")),
@@ -143,6 +288,40 @@ pub mod query_parser {
         .map(Query::Bcc)
     }
 
+    fn attachment<'a>() -> impl Parser<'a, Query> {
+        prefix(
+            whitespace_wrap(match_literal("attachment:")),
+            whitespace_wrap(literal()),
+        )
+        .map(Query::Attachment)
+    }
+
+    fn date<'a>() -> impl Parser<'a, UnixTimestamp> {
+        map_res(
+            is_a(b"0123456789-"),
+            |s: &str| match crate::datetime::timestamp_from_string(s, "%Y-%m-%d") {
+                Ok(Some(t)) => Ok(t),
+                _ => Err(s),
+            },
+        )
+    }
+
+    fn before<'a>() -> impl Parser<'a, Query> {
+        prefix(
+            whitespace_wrap(match_literal_anycase("before:")),
+            whitespace_wrap(date()),
+        )
+        .map(Query::Before)
+    }
+
+    fn after<'a>() -> impl Parser<'a, Query> {
+        prefix(
+            whitespace_wrap(match_literal_anycase("after:")),
+            whitespace_wrap(date()),
+        )
+        .map(Query::After)
+    }
+
     fn or<'a>() -> impl Parser<'a, Query> {
         move |input| {
             whitespace_wrap(match_literal_anycase("or"))
@@ -197,8 +376,14 @@ pub mod query_parser {
         move |input| {
             whitespace_wrap(either(
                 either(
-                    match_literal_anycase("flags:"),
-                    match_literal_anycase("tags:"),
+                    either(
+                        match_literal_anycase("flags:"),
+                        match_literal_anycase("tags:"),
+                    ),
+                    either(
+                        match_literal_anycase("flag:"),
+                        match_literal_anycase("tag:"),
+                    ),
                 ),
                 match_literal_anycase("is:"),
             ))
@@ -250,8 +435,12 @@ pub mod query_parser {
                 .or_else(|_| cc().parse(input))
                 .or_else(|_| bcc().parse(input))
                 .or_else(|_| subject().parse(input))
+                .or_else(|_| body().parse(input))
                 .or_else(|_| flags().parse(input))
                 .or_else(|_| has_attachment().parse(input))
+                .or_else(|_| attachment().parse(input))
+                .or_else(|_| before().parse(input))
+                .or_else(|_| after().parse(input))
             {
                 Ok(q)
             } else if let Ok((rest, query_a)) = not().parse(input) {
@@ -313,6 +502,10 @@ pub mod query_parser {
             Ok(("", Subject("test".to_string()))),
             query().parse_complete("subject: test")
         );
+        assert_eq!(
+            Ok(("", Body("meeting notes".to_string()))),
+            query().parse_complete("body: \"meeting notes\"")
+        );
         assert_eq!(
             Ok((
                 "",
@@ -380,6 +573,68 @@ pub mod query_parser {
             Ok(("", Flags(vec!["f".to_string()]))),
             query().parse_complete("tags:f")
         );
+        assert_eq!(
+            Ok((
+                "",
+                And(
+                    Box::new(Attachment("report budget".to_string())),
+                    Box::new(HasAttachment)
+                )
+            )),
+            query().parse_complete("attachment:\"report budget\" and has:attachment")
+        );
+        assert_eq!(
+            query().parse_complete("flags:flagged"),
+            query().parse_complete("flag:flagged")
+        );
+        assert_eq!(
+            Ok(("", Flags(vec!["foo".to_string()]))),
+            query().parse_complete("tag:foo")
+        );
+        let (_, q) = query()
+            .parse_complete("after:2020-01-01 and before:2020-02-01")
+            .unwrap();
+        if let And(a, b) = q {
+            if let (After(a), Before(b)) = (*a, *b) {
+                assert_eq!(b - a, 31 * 24 * 60 * 60);
+            } else {
+                panic!("unexpected date atoms");
+            }
+        } else {
+            panic!("unexpected query");
+        }
+        assert!(query().parse_complete("before:yesterday").is_err());
+    }
+
+    #[test]
+    fn test_query_flag_terms() {
+        assert_eq!(FlagQuery::new("unread"), FlagQuery::Unset(Flag::SEEN));
+        assert_eq!(FlagQuery::new("Seen"), FlagQuery::Set(Flag::SEEN));
+        assert_eq!(FlagQuery::new("answered"), FlagQuery::Set(Flag::REPLIED));
+        assert_eq!(FlagQuery::new("foo"), FlagQuery::Tag("foo"));
+
+        let tags = [TagHash::from_bytes(b"foo")];
+        assert!(FlagQuery::new("unread").is_match(Flag::FLAGGED, &tags));
+        assert!(!FlagQuery::new("seen").is_match(Flag::FLAGGED, &tags));
+        assert!(FlagQuery::new("foo").is_match(Flag::empty(), &tags));
+        assert!(!FlagQuery::new("bar").is_match(Flag::empty(), &tags));
+
+        let (_, q) = query()
+            .parse_complete("tag:foo and has:attachment")
+            .unwrap();
+        assert_eq!(q.unsupported_atom(SearchCapabilities::ALL), None);
+        assert_eq!(q.unsupported_atom(SearchCapabilities::LOCAL), None);
+        assert_eq!(
+            q.unsupported_atom(SearchCapabilities {
+                tags: false,
+                ..SearchCapabilities::ALL
+            }),
+            Some(SearchAtom::Tag)
+        );
+        assert_eq!(
+            AllText("x".to_string()).unsupported_atom(SearchCapabilities::LOCAL),
+            Some(SearchAtom::Text)
+        );
     }
 }
 
@@ -0,0 +1,300 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! S/MIME (RFC 8551) signing and signature verification.
+ *
+ * The cryptographic work is done by the `openssl` command line tool, so
+ * signatures are plain DER encoded CMS structures that are attached as
+ * `application/pkcs7-signature` parts of `multipart/signed` messages.
+ */
+
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    email::{
+        attachment_types::{ContentType, MultipartType},
+        Attachment,
+    },
+    error::{Error, ErrorKind, Result, ResultIntoError},
+};
+
+/// The `openssl` binary used when none is configured.
+pub const DEFAULT_OPENSSL_BINARY: &str = "openssl";
+
+/// Parameters of the `multipart/signed` part of an S/MIME signed message.
+pub const MULTIPART_SIGNED_PARAMETERS: &[(&[u8], &[u8])] = &[
+    (b"protocol", b"\"application/pkcs7-signature\""),
+    (b"micalg", b"sha-256"),
+];
+
+/// Whether the signer's certificate could be traced back to a trusted
+/// certificate authority.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmimeTrust {
+    Trusted,
+    /// The signature is valid but the certificate isn't trusted, for the
+    /// given reason.
+    Untrusted(String),
+}
+
+/// The result of a valid S/MIME signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmimeSignature {
+    /// Common name of the signer's certificate subject.
+    pub signer: Option<String>,
+    pub trust: SmimeTrust,
+}
+
+impl std::fmt::Display for SmimeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Good signature by {}",
+            self.signer.as_deref().unwrap_or("unknown signer")
+        )?;
+        match self.trust {
+            SmimeTrust::Trusted => write!(f, ", trusted."),
+            SmimeTrust::Untrusted(ref reason) => write!(f, ", not trusted: {}.", reason),
+        }
+    }
+}
+
+/// Returns true if `a` is a `multipart/signed` part with an S/MIME signature.
+pub fn is_smime_signed(a: &Attachment) -> bool {
+    match a.content_type {
+        ContentType::Multipart {
+            kind: MultipartType::Signed,
+            ref parts,
+            ref parameters,
+            ..
+        } => {
+            parameters.iter().any(|(k, v)| {
+                k.eq_ignore_ascii_case(b"protocol")
+                    && (v.eq_ignore_ascii_case(b"application/pkcs7-signature")
+                        || v.eq_ignore_ascii_case(b"application/x-pkcs7-signature"))
+            }) || parts
+                .iter()
+                .any(|p| p.content_type == ContentType::CMSSignature)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the common name (`CN`) of `subject`, a distinguished name in the
+/// RFC 2253 format printed by `openssl x509 -subject -nameopt RFC2253`.
+pub fn common_name(subject: &str) -> Option<String> {
+    let subject = subject.trim();
+    let subject = subject.strip_prefix("subject=").unwrap_or(subject);
+    let mut rdn = String::new();
+    let mut chars = subject.chars();
+    loop {
+        let c = chars.next();
+        match c {
+            Some('\\') => {
+                if let Some(c) = chars.next() {
+                    rdn.push(c);
+                }
+            }
+            Some(',') | None => {
+                if let Some(cn) = rdn.trim().strip_prefix("CN=") {
+                    return Some(cn.to_string());
+                }
+                c?;
+                rdn.clear();
+            }
+            Some(c) => rdn.push(c),
+        }
+    }
+}
+
+/// Returns a DER encoded detached signature of `data`, made with the PEM
+/// encoded `certificate` and its `private_key`.
+pub fn sign(
+    openssl: Option<&str>,
+    certificate: &Path,
+    private_key: &Path,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    let mut child = Command::new(openssl.unwrap_or(DEFAULT_OPENSSL_BINARY))
+        .args(["cms", "-sign", "-binary", "-md", "sha256"])
+        .args(["-outform", "DER"])
+        .arg("-signer")
+        .arg(certificate)
+        .arg("-inkey")
+        .arg(private_key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .chain_err_summary(|| "Could not execute openssl")
+        .chain_err_kind(ErrorKind::External)?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(data)
+        .chain_err_summary(|| "Could not write message to openssl")?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::new("Could not create S/MIME signature.")
+            .set_details(String::from_utf8_lossy(&output.stderr).trim().to_string())
+            .set_kind(ErrorKind::External));
+    }
+    Ok(output.stdout)
+}
+
+/// Verifies the DER encoded detached `signature` of `data`. The signer's
+/// certificate is checked against the certificates of `ca_file`, or the
+/// default trust store of openssl if that is `None`.
+pub fn verify(
+    openssl: Option<&str>,
+    ca_file: Option<&Path>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<SmimeSignature> {
+    let openssl = openssl.unwrap_or(DEFAULT_OPENSSL_BINARY);
+    let dir = TempDir::new()?;
+    let data_path = dir.write("content", data)?;
+    let signature_path = dir.write("signature.p7s", signature)?;
+    let signer_path = dir.0.join("signer.pem");
+    let run = |check_chain: bool| -> Result<std::process::Output> {
+        let mut cmd = Command::new(openssl);
+        cmd.args(["cms", "-verify", "-binary", "-inform", "DER"])
+            .arg("-in")
+            .arg(&signature_path)
+            .arg("-content")
+            .arg(&data_path)
+            .arg("-signer")
+            .arg(&signer_path)
+            .args(["-out", "/dev/null"]);
+        if !check_chain {
+            cmd.arg("-noverify");
+        } else if let Some(ca_file) = ca_file {
+            cmd.arg("-CAfile").arg(ca_file);
+        }
+        cmd.stdin(Stdio::null())
+            .output()
+            .chain_err_summary(|| "Could not execute openssl")
+            .chain_err_kind(ErrorKind::External)
+    };
+
+    /* Check the signature itself first, so that a bad signature isn't
+     * reported as an untrusted one. */
+    let output = run(false)?;
+    if !output.status.success() {
+        return Err(Error::new("Bad S/MIME signature.")
+            .set_details(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    let output = run(true)?;
+    let trust = if output.status.success() {
+        SmimeTrust::Trusted
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        SmimeTrust::Untrusted(
+            stderr
+                .lines()
+                .find_map(|l| {
+                    l.split_once("Verify error:")
+                        .map(|(_, reason)| reason.trim().to_string())
+                })
+                .unwrap_or_else(|| "certificate verification failed".to_string()),
+        )
+    };
+    let subject = Command::new(openssl)
+        .args(["x509", "-noout", "-subject", "-nameopt", "RFC2253", "-in"])
+        .arg(&signer_path)
+        .stdin(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string());
+    Ok(SmimeSignature {
+        signer: subject.as_deref().and_then(common_name),
+        trust,
+    })
+}
+
+/// A private temporary directory that is removed when dropped. openssl
+/// can't read the signed content and the signature from the same stream.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Result<Self> {
+        let path =
+            std::env::temp_dir().join(format!("meli-smime-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+        Ok(TempDir(path))
+    }
+
+    fn write(&self, name: &str, bytes: &[u8]) -> Result<PathBuf> {
+        let path = self.0.join(name);
+        fs::write(&path, bytes)?;
+        Ok(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::attachments::AttachmentBuilder;
+
+    #[test]
+    fn test_smime_common_name() {
+        assert_eq!(
+            common_name("subject=CN=Jane Doe,O=Example Inc.,C=GR\n"),
+            Some("Jane Doe".to_string())
+        );
+        assert_eq!(
+            common_name("emailAddress=jane@example.com,CN=Doe\\, Jane,O=Example"),
+            Some("Doe, Jane".to_string())
+        );
+        assert_eq!(common_name("subject=O=Example,C=GR"), None);
+        assert_eq!(common_name(""), None);
+    }
+
+    #[test]
+    fn test_smime_is_signed() {
+        let raw = b"Content-Type: multipart/signed; protocol=\"application/pkcs7-signature\"; \
+                    micalg=sha-256; boundary=\"XYZ\"\n\n--XYZ\nContent-Type: text/plain\n\nhello\n\
+                    --XYZ\nContent-Type: application/pkcs7-signature; name=smime.p7s\n\
+                    Content-Transfer-Encoding: base64\n\nMIAGCSqGSIb3DQEHAqCAMIACAQEx\n--XYZ--\n";
+        let a = AttachmentBuilder::new(raw).build();
+        assert!(is_smime_signed(&a));
+        let raw = b"Content-Type: multipart/signed; protocol=\"application/pgp-signature\"; \
+                    micalg=pgp-sha512; boundary=\"XYZ\"\n\n--XYZ\nContent-Type: text/plain\n\n\
+                    hello\n--XYZ\nContent-Type: application/pgp-signature\n\nsig\n--XYZ--\n";
+        let a = AttachmentBuilder::new(raw).build();
+        assert!(!is_smime_signed(&a));
+    }
+}
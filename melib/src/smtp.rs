@@ -44,6 +44,9 @@
 //!         danger_accept_invalid_certs: false,
 //!     },
 //!     extensions: SmtpExtensionSupport::default(),
+//!     proxy: None,
+//!     use_srv: false,
+//!     tls: Default::default(),
 //!     auth: SmtpAuth::Auto {
 //!         username: "l15".into(),
 //!         password: Password::CommandEval(
@@ -72,15 +75,14 @@
 //! Ok(())
 //! ```
 
-use std::{borrow::Cow, convert::TryFrom, net::TcpStream, process::Command};
+use std::{borrow::Cow, convert::TryFrom, process::Command};
 
 use futures::io::{AsyncReadExt, AsyncWriteExt};
-use native_tls::TlsConnector;
 use smallvec::SmallVec;
 use smol::{unblock, Async as AsyncWrapper};
 
 use crate::{
-    connections::{lookup_ipv4, Connection},
+    connections::{connect_tcp, dns, proxy::Proxy, tls::TlsOptions, Connection},
     email::{parser::BytesExt, Address, Envelope},
     error::{Error, Result, ResultIntoError},
 };
@@ -178,6 +180,19 @@ pub struct SmtpServerConf {
     pub security: SmtpSecurity,
     #[serde(default)]
     pub extensions: SmtpExtensionSupport,
+    /// Connect through this proxy. Defaults to the `proxy` setting of the
+    /// account.
+    #[serde(default)]
+    pub proxy: Option<Proxy>,
+    /// Connect to the target of the `_submissions._tcp` or `_submission._tcp`
+    /// SRV record of `hostname`, if there is one.
+    #[serde(default)]
+    pub use_srv: bool,
+    /// Minimum TLS version, pinned certificates and keys and client
+    /// certificate, from the `tls_*` settings of the account. Certificate
+    /// validation is controlled by `security` instead.
+    #[serde(skip)]
+    pub tls: TlsOptions,
 }
 
 //example: "SIZE 52428800", "8BITMIME", "PIPELINING", "CHUNKING", "PRDR",
@@ -243,7 +258,21 @@ pub struct SmtpConnection {
 impl SmtpConnection {
     /// Performs connection and if configured: TLS negotiation and SMTP AUTH
     pub async fn new_connection(mut server_conf: SmtpServerConf) -> Result<Self> {
-        let path = &server_conf.hostname;
+        let endpoint = if server_conf.use_srv && server_conf.proxy.is_none() {
+            let services: &[&str] = match server_conf.security {
+                SmtpSecurity::Tls { .. } => &["_submissions._tcp"],
+                SmtpSecurity::StartTLS { .. } | SmtpSecurity::None => &["_submission._tcp"],
+                SmtpSecurity::Auto { .. } => &["_submissions._tcp", "_submission._tcp"],
+            };
+            dns::resolve_service(services, &server_conf.hostname, server_conf.port)
+        } else {
+            dns::ServiceEndpoint {
+                host: server_conf.hostname.clone(),
+                port: server_conf.port,
+                tls_domain: server_conf.hostname.clone(),
+            }
+        };
+        let path = &endpoint.host;
         let mut res = String::with_capacity(8 * 1024);
         let stream = match server_conf.security {
             SmtpSecurity::Auto {
@@ -255,16 +284,17 @@ impl SmtpConnection {
             | SmtpSecurity::StartTLS {
                 danger_accept_invalid_certs,
             } => {
-                let mut connector = TlsConnector::builder();
-                if danger_accept_invalid_certs {
-                    connector.danger_accept_invalid_certs(true);
-                }
-                let connector = connector.build()?;
+                let tls = TlsOptions {
+                    danger_accept_invalid_certs,
+                    ..server_conf.tls.clone()
+                };
+                let connector = tls.connector()?;
 
-                let addr = lookup_ipv4(path, server_conf.port)?;
-                let mut socket = AsyncWrapper::new(Connection::Tcp(TcpStream::connect_timeout(
-                    &addr,
-                    std::time::Duration::new(4, 0),
+                let mut socket = AsyncWrapper::new(Connection::Tcp(connect_tcp(
+                    path,
+                    endpoint.port,
+                    server_conf.proxy.as_ref(),
+                    Some(std::time::Duration::new(4, 0)),
                 )?))?;
                 let pre_ehlo_extensions_reply = read_lines(
                     &mut socket,
@@ -276,11 +306,11 @@ impl SmtpConnection {
                 drop(pre_ehlo_extensions_reply);
                 //debug!(pre_ehlo_extensions_reply);
                 if let SmtpSecurity::Auto { .. } = server_conf.security {
-                    if server_conf.port == 465 {
+                    if endpoint.port == 465 {
                         server_conf.security = SmtpSecurity::Tls {
                             danger_accept_invalid_certs,
                         };
-                    } else if server_conf.port == 587 {
+                    } else if endpoint.port == 587 {
                         server_conf.security = SmtpSecurity::StartTLS {
                             danger_accept_invalid_certs,
                         };
@@ -315,10 +345,11 @@ impl SmtpConnection {
 
                 let mut ret = {
                     let socket = socket.into_inner()?;
-                    let _path = path.clone();
+                    let _path = endpoint.tls_domain.clone();
 
                     socket.set_nonblocking(false)?;
                     let conn = unblock(move || connector.connect(&_path, socket)).await?;
+                    tls.verify_peer(&endpoint.tls_domain, &conn)?;
                     /*
                     if let Err(native_tls::HandshakeError::WouldBlock(midhandshake_stream)) =
                         conn_result
@@ -346,10 +377,11 @@ impl SmtpConnection {
                 ret
             }
             SmtpSecurity::None => {
-                let addr = lookup_ipv4(path, server_conf.port)?;
-                let mut ret = AsyncWrapper::new(Connection::Tcp(TcpStream::connect_timeout(
-                    &addr,
-                    std::time::Duration::new(4, 0),
+                let mut ret = AsyncWrapper::new(Connection::Tcp(connect_tcp(
+                    path,
+                    endpoint.port,
+                    server_conf.proxy.as_ref(),
+                    Some(std::time::Duration::new(4, 0)),
                 )?))?;
                 res.clear();
                 let reply = read_lines(
@@ -1230,6 +1262,9 @@ mod test {
             auth: SmtpAuth::None,
             security: SmtpSecurity::None,
             extensions: Default::default(),
+            proxy: None,
+            use_srv: false,
+            tls: Default::default(),
         }
     }
 
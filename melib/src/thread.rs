@@ -34,11 +34,15 @@
  */
 
 use crate::{
+    collection::{EnvelopeStore, Envelopes},
     datetime::UnixTimestamp,
     email::{address::StrBuild, parser::BytesExt, *},
+    error::Error,
 };
 
+mod heuristics;
 mod iterators;
+mod overlay;
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
@@ -51,15 +55,15 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+pub use heuristics::*;
 pub use iterators::*;
+pub use overlay::*;
 use smallvec::SmallVec;
 use uuid::Uuid;
 
 #[cfg(feature = "unicode_algorithms")]
 use crate::text_processing::grapheme_clusters::*;
 
-type Envelopes = Arc<RwLock<HashMap<EnvelopeHash, Envelope>>>;
-
 macro_rules! uuid_hash_type {
     ($n:ident) => {
         #[derive(PartialEq, Hash, Eq, Copy, Clone, Serialize, Deserialize, Default)]
@@ -654,6 +658,15 @@ impl ThreadNode {
     }
 }
 
+/// The threads changed by inserting a message with [`Threads::insert`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadUpdate {
+    /// The thread the message is in.
+    pub thread: ThreadHash,
+    /// Threads that the message joined to `thread`, and are no longer roots.
+    pub merged: SmallVec<[ThreadHash; 8]>,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Threads {
     pub thread_nodes: HashMap<ThreadNodeHash, ThreadNode>,
@@ -662,11 +675,15 @@ pub struct Threads {
     pub groups: HashMap<ThreadHash, ThreadGroup>,
 
     message_ids: HashMap<Vec<u8>, ThreadNodeHash>,
+    /// The `Message-ID` of each node in `message_ids`.
+    node_message_ids: HashMap<ThreadNodeHash, Vec<u8>>,
     pub message_ids_set: HashSet<Vec<u8>>,
     pub missing_message_ids: HashSet<Vec<u8>>,
     pub hash_set: HashSet<EnvelopeHash>,
     pub thread_to_envelope: HashMap<ThreadHash, Vec<EnvelopeHash>>,
     pub envelope_to_thread: HashMap<EnvelopeHash, ThreadHash>,
+    /// The node holding each message.
+    envelope_to_node: HashMap<EnvelopeHash, ThreadNodeHash>,
     sort: Arc<RwLock<(SortField, SortOrder)>>,
     subsort: Arc<RwLock<(SortField, SortOrder)>>,
 }
@@ -726,6 +743,8 @@ impl Threads {
         /* A hash table of Message IDs */
         let message_ids: HashMap<Vec<u8>, ThreadNodeHash> =
             HashMap::with_capacity_and_hasher(length, Default::default());
+        let node_message_ids: HashMap<ThreadNodeHash, Vec<u8>> =
+            HashMap::with_capacity_and_hasher(length, Default::default());
         /* A hash set of Message IDs we haven't encountered yet as an Envelope */
         let missing_message_ids: HashSet<Vec<u8>> =
             HashSet::with_capacity_and_hasher(length, Default::default());
@@ -738,15 +757,19 @@ impl Threads {
             HashMap::with_capacity_and_hasher(length, Default::default());
         let envelope_to_thread: HashMap<EnvelopeHash, ThreadHash> =
             HashMap::with_capacity_and_hasher(length, Default::default());
+        let envelope_to_node: HashMap<EnvelopeHash, ThreadNodeHash> =
+            HashMap::with_capacity_and_hasher(length, Default::default());
 
         Threads {
             thread_nodes,
             message_ids,
+            node_message_ids,
             message_ids_set,
             missing_message_ids,
             hash_set,
             thread_to_envelope,
             envelope_to_thread,
+            envelope_to_node,
             sort: Arc::new(RwLock::new((SortField::Date, SortOrder::Desc))),
             subsort: Arc::new(RwLock::new((SortField::Subject, SortOrder::Desc))),
 
@@ -784,16 +807,10 @@ impl Threads {
         /* must update:
          * - hash_set
          * - message fields in thread_nodes
+         * - envelope_to_node
          */
-        let thread_node_hash = if let Some((key, _)) = self
-            .thread_nodes
-            .iter()
-            .find(|(_, n)| n.message.map(|n| n == old_hash).unwrap_or(false))
-        {
-            *key
-        } else {
-            return Err(());
-        };
+        let thread_node_hash = self.envelope_to_node.remove(&old_hash).ok_or(())?;
+        self.envelope_to_node.insert(new_hash, thread_node_hash);
 
         self.thread_nodes
             .get_mut(&thread_node_hash)
@@ -830,16 +847,15 @@ impl Threads {
     pub fn remove(&mut self, envelope_hash: EnvelopeHash) {
         self.hash_set.remove(&envelope_hash);
 
-        let t_id: ThreadNodeHash = if let Some((pos, n)) = self
-            .thread_nodes
-            .iter_mut()
-            .find(|(_, n)| n.message.map(|n| n == envelope_hash).unwrap_or(false))
+        let t_id: ThreadNodeHash = if let Some(t_id) = self.envelope_to_node.remove(&envelope_hash)
         {
-            n.message = None;
-            *pos
+            t_id
         } else {
             return;
         };
+        if let Some(n) = self.thread_nodes.get_mut(&t_id) {
+            n.message = None;
+        }
 
         if self.thread_nodes[&t_id].parent.is_none() {
             let mut tree_index = self.tree_index.write().unwrap();
@@ -847,11 +863,21 @@ impl Threads {
                 tree_index.remove(i);
             }
         }
-        if let Some((message_id, _)) = self.message_ids.iter().find(|(_, h)| **h == t_id) {
+        if let Some(message_id) = self.node_message_ids.get(&t_id) {
             self.missing_message_ids.insert(message_id.to_vec());
         }
     }
 
+    /// Records that `message_id` belongs to node `id`.
+    fn insert_message_id(&mut self, message_id: Vec<u8>, id: ThreadNodeHash) {
+        if let Some(old_id) = self.message_ids.insert(message_id.clone(), id) {
+            if self.node_message_ids.get(&old_id) == Some(&message_id) {
+                self.node_message_ids.remove(&old_id);
+            }
+        }
+        self.node_message_ids.insert(id, message_id);
+    }
+
     pub fn amend(&mut self, envelopes: &Envelopes) {
         let envelopes_lck = envelopes.read().unwrap();
         let new_hash_set = HashSet::from_iter(envelopes_lck.keys().cloned());
@@ -915,8 +941,30 @@ impl Threads {
         }
     }
 
-    pub fn insert(&mut self, envelopes: &Envelopes, env_hash: EnvelopeHash) {
+    /// Inserts `env_hash` and returns the threads that changed, so that
+    /// views can update just these instead of every thread.
+    pub fn insert(&mut self, envelopes: &Envelopes, env_hash: EnvelopeHash) -> ThreadUpdate {
+        /* The message can only join threads that one of its ids is already in. */
+        let roots: SmallVec<[ThreadHash; 8]> = {
+            let envelopes_lck = envelopes.read().unwrap();
+            let envelope = &envelopes_lck[&env_hash];
+            std::iter::once(envelope.message_id())
+                .chain(envelope.in_reply_to())
+                .chain(envelope.references())
+                .filter_map(|id| self.message_ids.get(id.raw()))
+                .filter_map(|node| Some(self.find_group(self.thread_nodes.get(node)?.group)))
+                .collect()
+        };
         self.insert_internal(envelopes, env_hash, false);
+        let node = envelopes.read().unwrap()[&env_hash].thread();
+        let thread = self.find_group(self.thread_nodes[&node].group);
+        let mut merged = SmallVec::new();
+        for root in roots {
+            if root != thread && !merged.contains(&root) {
+                merged.push(root);
+            }
+        }
+        ThreadUpdate { thread, merged }
     }
 
     fn insert_internal(
@@ -981,7 +1029,10 @@ impl Threads {
             .unwrap_or_else(|| ThreadNodeHash::from(message_id));
         {
             let mut node = self.thread_nodes.entry(new_id).or_default();
-            node.message = Some(env_hash);
+            if let Some(old_hash) = node.message.replace(env_hash) {
+                self.envelope_to_node.remove(&old_hash);
+            }
+            self.envelope_to_node.insert(env_hash, new_id);
             if node.parent.is_none() {
                 node.parent = reply_to_id;
             }
@@ -1027,7 +1078,7 @@ impl Threads {
             };
         }
 
-        self.message_ids.insert(message_id.to_vec(), new_id);
+        self.insert_message_id(message_id.to_vec(), new_id);
         self.message_ids_set.insert(message_id.to_vec());
         self.missing_message_ids.remove(message_id);
         self.hash_set.insert(env_hash);
@@ -1060,7 +1111,7 @@ impl Threads {
                 }),
             );
             make!((reply_to_id) parent of (new_id), self);
-            self.message_ids.insert(r.to_vec(), reply_to_id);
+            self.insert_message_id(r.to_vec(), reply_to_id);
             self.message_ids_set.insert(r.to_vec());
             self.missing_message_ids.insert(r.to_vec());
         }
@@ -1104,7 +1155,7 @@ impl Threads {
                     );
                     make!((id) parent of (current_descendant_id), self);
                     self.missing_message_ids.insert(reference.raw().to_vec());
-                    self.message_ids.insert(reference.raw().to_vec(), id);
+                    self.insert_message_id(reference.raw().to_vec(), id);
                     self.message_ids_set.insert(reference.raw().to_vec());
                     current_descendant_id = id;
                 }
@@ -1139,6 +1190,139 @@ impl Threads {
         self.insert_internal(envelopes, env_hash, true)
     }
 
+    /// Returns the node holding message `env_hash`.
+    pub fn envelope_node(&self, env_hash: EnvelopeHash) -> Option<ThreadNodeHash> {
+        self.envelope_to_node.get(&env_hash).cloned()
+    }
+
+    /// Returns `id` and all its descendants.
+    pub fn subtree(&self, id: ThreadNodeHash) -> Vec<ThreadNodeHash> {
+        let mut ret = vec![];
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.thread_nodes.get(&id) {
+                stack.extend(node.children.iter().cloned());
+                ret.push(id);
+            }
+        }
+        ret
+    }
+
+    /// Moves node `id` along with its descendants under `new_parent`, or to a
+    /// thread of its own if `new_parent` is `None`.
+    pub fn reparent(
+        &mut self,
+        envelopes: &Envelopes,
+        id: ThreadNodeHash,
+        new_parent: Option<ThreadNodeHash>,
+    ) -> StdResult<(), Error> {
+        if !self.thread_nodes.contains_key(&id) {
+            return Err(Error::new("Message is not part of this mailbox's threads."));
+        }
+        let subtree = self.subtree(id);
+        let old_group = self.find_group(self.thread_nodes[&id].group);
+        let new_group = match new_parent {
+            Some(p) if !self.thread_nodes.contains_key(&p) => {
+                return Err(Error::new(
+                    "Target message is not part of this mailbox's threads.",
+                ));
+            }
+            Some(p) if subtree.contains(&p) => {
+                return Err(Error::new(
+                    "Cannot attach a message to itself or to one of its replies.",
+                ));
+            }
+            Some(p) if self.thread_nodes[&id].parent == Some(p) => return Ok(()),
+            Some(p) => self.find_group(self.thread_nodes[&p].group),
+            None if self.thread_nodes[&id].parent.is_none() => return Ok(()),
+            None => ThreadHash::new(),
+        };
+        let was_root = self.thread_ref(old_group).root == id;
+        remove_from_parent!(&mut self.thread_nodes, id);
+        if let Some(p) = new_parent {
+            self.thread_nodes
+                .entry(p)
+                .and_modify(|e| e.children.push(id));
+            self.thread_nodes
+                .entry(id)
+                .and_modify(|e| e.parent = Some(p));
+        } else {
+            self.groups.insert(
+                new_group,
+                ThreadGroup::Root(Thread {
+                    root: id,
+                    ..Thread::default()
+                }),
+            );
+        }
+        if old_group != new_group {
+            let mut moved = vec![];
+            for n in &subtree {
+                let node = self.thread_nodes.get_mut(n).unwrap();
+                node.group = new_group;
+                moved.extend(node.message);
+            }
+            if was_root {
+                /* The whole thread was moved. */
+                self.groups.insert(
+                    old_group,
+                    ThreadGroup::Node {
+                        parent: Arc::new(RwLock::new(new_group)),
+                    },
+                );
+            } else {
+                self.recount(envelopes, old_group);
+            }
+            if let Some(env_hashes) = self.thread_to_envelope.get_mut(&old_group) {
+                env_hashes.retain(|h| !moved.contains(h));
+            }
+            for &env_hash in &moved {
+                self.envelope_to_thread.insert(env_hash, new_group);
+            }
+            self.thread_to_envelope
+                .entry(new_group)
+                .or_default()
+                .extend(moved);
+        }
+        self.recount(envelopes, new_group);
+        if let Some(env_hash) = self.thread_nodes[&id].message {
+            self.thread_nodes
+                .entry(id)
+                .and_modify(|e| e.show_subject = true);
+            self.update_show_subject(id, env_hash, envelopes);
+        }
+        Ok(())
+    }
+
+    /// Recomputes the counters of thread `group` from its nodes.
+    fn recount(&mut self, envelopes: &Envelopes, group: ThreadHash) {
+        let envelopes_lck = envelopes.read().unwrap();
+        let (mut date, mut len, mut unseen, mut attachments) = (0, 0, 0, 0);
+        for id in self.subtree(self.thread_ref(group).root) {
+            let node = &self.thread_nodes[&id];
+            date = std::cmp::max(date, node.date);
+            if let Some(env_hash) = node.message {
+                len += 1;
+                if node.unseen {
+                    unseen += 1;
+                }
+                if envelopes_lck
+                    .get(&env_hash)
+                    .map(Envelope::has_attachments)
+                    .unwrap_or(false)
+                {
+                    attachments += 1;
+                }
+            }
+        }
+        drop(envelopes_lck);
+        let thread = self.thread_ref_mut(group);
+        thread.date = date;
+        thread.len = len;
+        thread.unseen = unseen;
+        thread.attachments = attachments;
+    }
+
     fn inner_subsort_by(&self, _subsort: (SortField, SortOrder), _envelopes: &Envelopes) {
         //FIXME: self\.thread_nodes needs interior mutability */
         /*
@@ -1212,20 +1396,54 @@ impl Threads {
         envelopes: &Envelopes,
     ) {
         let envelopes = envelopes.read().unwrap();
-        vec.sort_by(|a, b| match sort {
+        vec.sort_by(|a, b| self.group_cmp(*a, *b, sort, &**envelopes));
+    }
+
+    /// Returns where `thread` goes among `len` threads sorted with
+    /// [`Threads::group_inner_sort_by`], where `thread_at(i)` is the `i`th
+    /// of them. It goes after the threads that compare equal to it.
+    pub fn group_sorted_position(
+        &self,
+        thread: ThreadHash,
+        len: usize,
+        thread_at: impl Fn(usize) -> ThreadHash,
+        sort: (SortField, SortOrder),
+        envelopes: &Envelopes,
+    ) -> usize {
+        let envelopes = envelopes.read().unwrap();
+        let (mut low, mut high) = (0, len);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.group_cmp(thread_at(mid), thread, sort, &**envelopes) == Ordering::Greater {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        low
+    }
+
+    fn group_cmp(
+        &self,
+        a: ThreadHash,
+        b: ThreadHash,
+        sort: (SortField, SortOrder),
+        envelopes: &(dyn EnvelopeStore + 'static),
+    ) -> Ordering {
+        match sort {
             (SortField::Date, SortOrder::Desc) => {
-                let a = self.thread_ref(*a).date();
-                let b = self.thread_ref(*b).date();
+                let a = self.thread_ref(a).date();
+                let b = self.thread_ref(b).date();
                 b.cmp(&a)
             }
             (SortField::Date, SortOrder::Asc) => {
-                let a = self.thread_ref(*a).date();
-                let b = self.thread_ref(*b).date();
+                let a = self.thread_ref(a).date();
+                let b = self.thread_ref(b).date();
                 a.cmp(&b)
             }
             (SortField::Subject, SortOrder::Desc) => {
-                let a = &self.thread_nodes[&self.thread_ref(*a).root()].message();
-                let b = &self.thread_nodes[&self.thread_ref(*b).root()].message();
+                let a = &self.thread_nodes[&self.thread_ref(a).root()].message();
+                let b = &self.thread_nodes[&self.thread_ref(b).root()].message();
 
                 match (a, b) {
                     (Some(_), Some(_)) => {}
@@ -1253,8 +1471,8 @@ impl Threads {
                 }
             }
             (SortField::Subject, SortOrder::Asc) => {
-                let a = &self.thread_nodes[&self.thread_ref(*a).root()].message();
-                let b = &self.thread_nodes[&self.thread_ref(*b).root()].message();
+                let a = &self.thread_nodes[&self.thread_ref(a).root()].message();
+                let b = &self.thread_nodes[&self.thread_ref(b).root()].message();
 
                 match (a, b) {
                     (Some(_), Some(_)) => {}
@@ -1283,8 +1501,9 @@ impl Threads {
                     mb.subject().as_ref().cmp(&ma.subject())
                 }
             }
-        });
+        }
     }
+
     pub fn node_inner_sort_by(
         &self,
         vec: &mut [ThreadNodeHash],
@@ -1671,3 +1890,130 @@ fn save_graph(
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(envelopes: &Envelopes, threads: &mut Threads, headers: &str) -> ThreadUpdate {
+        let env = Envelope::from_bytes(format!("{}\nhello\n", headers).as_bytes(), None)
+            .expect("Could not parse mail");
+        let hash = env.hash();
+        envelopes.write().unwrap().insert(hash, env);
+        threads.insert(envelopes, hash)
+    }
+
+    #[test]
+    fn test_thread_insert_update() {
+        let envelopes: Envelopes = Default::default();
+        let mut threads = Threads::new(4);
+        let one = insert(
+            &envelopes,
+            &mut threads,
+            "Subject: One\nMessage-ID: <1@example.com>\nDate: Mon, 01 May 2023 10:00:00 +0000\n",
+        );
+        assert!(one.merged.is_empty());
+        /* A reply to a message that hasn't arrived yet starts a thread of its own. */
+        let three = insert(
+            &envelopes,
+            &mut threads,
+            "Subject: Re: Re: One\nMessage-ID: <3@example.com>\nIn-Reply-To: \
+             <2@example.com>\nDate: Mon, 01 May 2023 12:00:00 +0000\n",
+        );
+        assert_ne!(three.thread, one.thread);
+        assert_eq!(threads.roots().len(), 2);
+        /* The missing message joins both threads. */
+        let two = insert(
+            &envelopes,
+            &mut threads,
+            "Subject: Re: One\nMessage-ID: <2@example.com>\nIn-Reply-To: \
+             <1@example.com>\nDate: Mon, 01 May 2023 11:00:00 +0000\n",
+        );
+        assert_eq!(two.thread, one.thread);
+        assert_eq!(two.merged.as_slice(), &[three.thread]);
+        assert_eq!(threads.roots().as_slice(), &[one.thread]);
+        assert_eq!(threads.thread_ref(one.thread).len(), 3);
+    }
+
+    #[test]
+    fn test_thread_envelope_node() {
+        let envelopes: Envelopes = Default::default();
+        let mut threads = Threads::new(2);
+        let mut hashes = vec![];
+        for headers in [
+            "Subject: One\nMessage-ID: <1@example.com>\nDate: Mon, 01 May 2023 10:00:00 +0000\n",
+            "Subject: Re: One\nMessage-ID: <2@example.com>\nIn-Reply-To: \
+             <1@example.com>\nDate: Mon, 01 May 2023 11:00:00 +0000\n",
+        ] {
+            insert(&envelopes, &mut threads, headers);
+            hashes.push(
+                *envelopes
+                    .read()
+                    .unwrap()
+                    .keys()
+                    .find(|h| !hashes.contains(*h))
+                    .unwrap(),
+            );
+        }
+        for hash in &hashes {
+            let node = threads.envelope_node(*hash).unwrap();
+            assert_eq!(threads.thread_nodes[&node].message, Some(*hash));
+        }
+        let node = threads.envelope_node(hashes[1]).unwrap();
+
+        /* A message keeps its node when its hash changes. */
+        let mut env = envelopes.read().unwrap()[&hashes[1]].clone();
+        let new_hash = EnvelopeHash(hashes[1].0.wrapping_add(1));
+        env.set_hash(new_hash);
+        envelopes.write().unwrap().insert(new_hash, env);
+        threads
+            .update_envelope(&envelopes, hashes[1], new_hash)
+            .unwrap();
+        assert_eq!(threads.envelope_node(hashes[1]), None);
+        assert_eq!(threads.envelope_node(new_hash), Some(node));
+        assert_eq!(
+            threads.update_envelope(&envelopes, hashes[1], new_hash),
+            Err(())
+        );
+
+        threads.remove(new_hash);
+        assert_eq!(threads.envelope_node(new_hash), None);
+        assert_eq!(threads.thread_nodes[&node].message, None);
+        assert!(threads.envelope_node(hashes[0]).is_some());
+    }
+
+    #[test]
+    fn test_thread_group_sorted_position() {
+        let envelopes: Envelopes = Default::default();
+        let mut threads = Threads::new(3);
+        let mut sorted = vec![];
+        for headers in [
+            "Subject: B\nMessage-ID: <1@example.com>\nDate: Mon, 01 May 2023 10:00:00 +0000\n",
+            "Subject: C\nMessage-ID: <2@example.com>\nDate: Mon, 01 May 2023 12:00:00 +0000\n",
+        ] {
+            sorted.push(insert(&envelopes, &mut threads, headers).thread);
+        }
+        let new = insert(
+            &envelopes,
+            &mut threads,
+            "Subject: A\nMessage-ID: <3@example.com>\nDate: Mon, 01 May 2023 11:00:00 +0000\n",
+        )
+        .thread;
+        for (sort, expected) in [
+            ((SortField::Date, SortOrder::Desc), 1),
+            ((SortField::Date, SortOrder::Asc), 1),
+            ((SortField::Subject, SortOrder::Desc), 0),
+            ((SortField::Subject, SortOrder::Asc), 2),
+        ] {
+            threads.group_inner_sort_by(&mut sorted, sort, &envelopes);
+            let pos =
+                threads.group_sorted_position(new, sorted.len(), |i| sorted[i], sort, &envelopes);
+            assert_eq!(pos, expected, "{:?}", sort);
+            let mut all = sorted.clone();
+            all.insert(pos, new);
+            let mut resorted = all.clone();
+            threads.group_inner_sort_by(&mut resorted, sort, &envelopes);
+            assert_eq!(all, resorted, "{:?}", sort);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Heuristic threading of replies whose `In-Reply-To` and `References`
+//! headers were dropped by the sender's client.
+
+use super::*;
+
+/// Maximum time between an orphaned reply and the message that adopts it.
+pub const ADOPTION_WINDOW: UnixTimestamp = 14 * 24 * 60 * 60;
+
+fn normalize_subject(subject: &str) -> String {
+    let mut subject = subject;
+    subject.strip_prefixes().trim().to_lowercase()
+}
+
+fn contains_email(list: &[Address], address: &Address) -> bool {
+    let email = address.get_email();
+    list.iter()
+        .any(|a| a.get_email().eq_ignore_ascii_case(&email))
+}
+
+/// Returns whether `orphan` could be a reply to `candidate` judging by their
+/// participants: it was sent to the candidate's author, by one of the
+/// candidate's recipients or by its author, or to the same mailing list.
+fn related(orphan: &Envelope, candidate: &Envelope) -> bool {
+    let orphan_recipients = [orphan.to(), orphan.cc()].concat();
+    let candidate_participants = [candidate.from(), candidate.to(), candidate.cc()].concat();
+    if candidate
+        .from()
+        .iter()
+        .any(|a| contains_email(&orphan_recipients, a))
+        || orphan
+            .from()
+            .iter()
+            .any(|a| contains_email(&candidate_participants, a))
+    {
+        return true;
+    }
+    match (orphan.list_id(), candidate.list_id()) {
+        (Some(a), Some(b)) => a.trim() == b.trim(),
+        _ => false,
+    }
+}
+
+impl Threads {
+    /// Adopts orphaned replies into the thread they most likely belong to.
+    ///
+    /// An orphan is a thread root whose subject marks it as a reply (e.g.
+    /// `Re: ...`). It is attached to the latest earlier message with the same
+    /// subject that was sent at most [`ADOPTION_WINDOW`] before it and is
+    /// related to it by its participants. Returns the number of adopted
+    /// messages.
+    pub fn adopt_orphans(&mut self, envelopes: &Envelopes) -> usize {
+        let mut by_subject: HashMap<String, Vec<(UnixTimestamp, ThreadNodeHash)>> =
+            HashMap::default();
+        let mut orphans = vec![];
+        {
+            let envelopes_lck = envelopes.read().unwrap();
+            for (id, node) in self.thread_nodes.iter() {
+                let env = match node.message.and_then(|h| envelopes_lck.get(&h)) {
+                    Some(env) => env,
+                    None => continue,
+                };
+                let subject = env.subject();
+                let subject: &str = &subject;
+                let key = normalize_subject(subject);
+                if key.is_empty() {
+                    continue;
+                }
+                if node.parent.is_none() && subject.is_a_reply() {
+                    orphans.push((env.date(), *id, key.clone()));
+                }
+                by_subject.entry(key).or_default().push((env.date(), *id));
+            }
+        }
+        orphans.sort_by_key(|(date, _, _)| *date);
+
+        let mut adopted = 0;
+        for (date, id, key) in orphans {
+            let subtree = self.subtree(id);
+            let parent = {
+                let envelopes_lck = envelopes.read().unwrap();
+                let orphan = &envelopes_lck[&self.thread_nodes[&id].message.unwrap()];
+                by_subject[&key]
+                    .iter()
+                    .filter(|(d, c)| {
+                        *d <= date && date - *d <= ADOPTION_WINDOW && !subtree.contains(c)
+                    })
+                    .filter(|(_, c)| {
+                        self.thread_nodes[c]
+                            .message
+                            .and_then(|h| envelopes_lck.get(&h))
+                            .map(|candidate| related(orphan, candidate))
+                            .unwrap_or(false)
+                    })
+                    .max_by_key(|(d, _)| *d)
+                    .map(|(_, c)| *c)
+            };
+            if let Some(parent) = parent {
+                if self.reparent(envelopes, id, Some(parent)).is_ok() {
+                    adopted += 1;
+                }
+            }
+        }
+        adopted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(headers: &str) -> Envelope {
+        Envelope::from_bytes(format!("{}\nhello world.\n", headers).as_bytes(), None)
+            .expect("Could not parse mail")
+    }
+
+    #[test]
+    fn test_adopt_orphans() {
+        let envelopes: Envelopes = Default::default();
+        let mut threads = Threads::new(4);
+        for headers in [
+            "From: alice@example.com\nTo: bob@example.com\nSubject: Lunch\nMessage-ID: \
+             <1@example.com>\nDate: Mon, 01 May 2023 10:00:00 +0000\n",
+            /* A reply from a client that dropped In-Reply-To. */
+            "From: bob@example.com\nTo: alice@example.com\nSubject: Re: Lunch\nMessage-ID: \
+             <2@example.com>\nDate: Mon, 01 May 2023 11:00:00 +0000\n",
+            /* Same subject but unrelated participants. */
+            "From: carol@example.com\nTo: dave@example.com\nSubject: Re: Lunch\nMessage-ID: \
+             <3@example.com>\nDate: Mon, 01 May 2023 12:00:00 +0000\n",
+            /* Related, but sent long after. */
+            "From: alice@example.com\nTo: bob@example.com\nSubject: RE: lunch\nMessage-ID: \
+             <4@example.com>\nDate: Thu, 01 Jun 2023 10:00:00 +0000\n",
+        ] {
+            let env = envelope(headers);
+            let hash = env.hash();
+            envelopes.write().unwrap().insert(hash, env);
+            threads.insert(&envelopes, hash);
+        }
+        let node = |message_id: &str| {
+            let env_hash = envelopes
+                .read()
+                .unwrap()
+                .values()
+                .find(|e| e.message_id_display().contains(message_id))
+                .unwrap()
+                .hash();
+            threads.envelope_node(env_hash).unwrap()
+        };
+        let (first, reply, unrelated, late) = (node("<1@"), node("<2@"), node("<3@"), node("<4@"));
+        assert!(threads[&reply].parent().is_none());
+
+        assert_eq!(threads.adopt_orphans(&envelopes), 1);
+        assert_eq!(threads[&reply].parent(), Some(first));
+        assert!(threads[&unrelated].parent().is_none());
+        assert!(threads[&late].parent().is_none());
+        let group = threads.find_group(threads[&first].group);
+        assert_eq!(threads.thread_ref(group).len(), 2);
+        assert_eq!(threads.find_group(threads[&reply].group), group);
+        assert_eq!(threads.roots().len(), 3);
+        /* Adoption is idempotent. */
+        assert_eq!(threads.adopt_orphans(&envelopes), 0);
+
+        /* Moving the reply back to a thread of its own. */
+        threads.reparent(&envelopes, reply, None).unwrap();
+        assert!(threads[&reply].parent().is_none());
+        assert_eq!(threads.thread_ref(group).len(), 1);
+        assert_ne!(threads.find_group(threads[&reply].group), group);
+        assert_eq!(threads.roots().len(), 4);
+        assert!(threads.reparent(&envelopes, first, Some(first)).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * meli - melib library
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Manual threading corrections that are applied on top of the threads
+//! computed from message headers.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::*;
+
+/// Threading corrections made by the user, keyed by `Message-ID` so that they
+/// can be stored and applied again when the messages are reloaded.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ThreadOverlay {
+    /// Messages that were made replies of another message, mapped to that
+    /// message.
+    #[serde(default)]
+    pub joins: BTreeMap<String, String>,
+    /// Messages that were detached from their parent to a thread of their
+    /// own.
+    #[serde(default)]
+    pub splits: BTreeSet<String>,
+}
+
+impl ThreadOverlay {
+    /// Records that message `child` is a reply to message `parent`.
+    pub fn set_parent(&mut self, child: String, parent: String) {
+        self.splits.remove(&child);
+        self.joins.insert(child, parent);
+    }
+
+    /// Records that message `message_id` starts a thread of its own.
+    pub fn detach(&mut self, message_id: String) {
+        self.joins.remove(&message_id);
+        self.splits.insert(message_id);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joins.is_empty() && self.splits.is_empty()
+    }
+}
+
+impl Threads {
+    /// Returns the `Message-ID` of node `id`, which might not have a message.
+    pub fn node_message_id(&self, id: ThreadNodeHash) -> Option<String> {
+        self.node_message_ids
+            .get(&id)
+            .map(|message_id| String::from_utf8_lossy(message_id).to_string())
+    }
+
+    /// Returns the thread group of the message with `message_id`, if it is
+    /// known.
+    pub fn message_id_thread(&self, message_id: &str) -> Option<ThreadHash> {
+        let node = self.message_ids.get(message_id.as_bytes())?;
+        Some(self.find_group(self.thread_nodes.get(node)?.group))
+    }
+
+    /// Applies the corrections of `overlay` whose messages are known. Returns
+    /// `true` if any threads changed.
+    pub fn apply_overlay(&mut self, envelopes: &Envelopes, overlay: &ThreadOverlay) -> bool {
+        let mut changed = false;
+        for message_id in &overlay.splits {
+            if let Some(&id) = self.message_ids.get(message_id.as_bytes()) {
+                if self.thread_nodes[&id].parent.is_some()
+                    && self.reparent(envelopes, id, None).is_ok()
+                {
+                    changed = true;
+                }
+            }
+        }
+        for (child, parent) in &overlay.joins {
+            if let (Some(&id), Some(&parent)) = (
+                self.message_ids.get(child.as_bytes()),
+                self.message_ids.get(parent.as_bytes()),
+            ) {
+                if self.thread_nodes[&id].parent != Some(parent)
+                    && self.reparent(envelopes, id, Some(parent)).is_ok()
+                {
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_overlay() {
+        let envelopes: Envelopes = Default::default();
+        let mut threads = Threads::new(3);
+        for headers in [
+            "From: a@example.com\nSubject: One\nMessage-ID: <1@example.com>\nDate: Mon, 01 May \
+             2023 10:00:00 +0000\n",
+            "From: b@example.com\nSubject: Re: One\nMessage-ID: <2@example.com>\nIn-Reply-To: \
+             <1@example.com>\nDate: Mon, 01 May 2023 11:00:00 +0000\n",
+            "From: c@example.com\nSubject: Two\nMessage-ID: <3@example.com>\nDate: Mon, 01 May \
+             2023 12:00:00 +0000\n",
+        ] {
+            let env = Envelope::from_bytes(format!("{}\nhello\n", headers).as_bytes(), None)
+                .expect("Could not parse mail");
+            let hash = env.hash();
+            envelopes.write().unwrap().insert(hash, env);
+            threads.insert(&envelopes, hash);
+        }
+        let node = |message_id: &str| threads.message_ids[message_id.as_bytes()];
+        let (one, two, three) = (
+            node("1@example.com"),
+            node("2@example.com"),
+            node("3@example.com"),
+        );
+        assert_eq!(threads[&two].parent(), Some(one));
+        for (id, message_id) in [
+            (one, "1@example.com"),
+            (two, "2@example.com"),
+            (three, "3@example.com"),
+        ] {
+            assert_eq!(threads.node_message_id(id).as_deref(), Some(message_id));
+        }
+
+        let mut overlay = ThreadOverlay::default();
+        overlay.detach("2@example.com".to_string());
+        overlay.set_parent("3@example.com".to_string(), "1@example.com".to_string());
+        assert!(threads.apply_overlay(&envelopes, &overlay));
+        assert!(threads[&two].parent().is_none());
+        assert_eq!(threads[&three].parent(), Some(one));
+        assert_eq!(threads.roots().len(), 2);
+        assert!(!threads.apply_overlay(&envelopes, &overlay));
+
+        /* A later correction replaces an earlier one for the same message. */
+        overlay.set_parent("2@example.com".to_string(), "3@example.com".to_string());
+        assert!(overlay.splits.is_empty());
+        assert!(threads.apply_overlay(&envelopes, &overlay));
+        assert_eq!(threads[&two].parent(), Some(three));
+        assert_eq!(threads.roots().len(), 1);
+    }
+}
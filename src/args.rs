@@ -55,6 +55,32 @@ pub struct Opt {
     #[structopt(short, long, parse(from_os_str))]
     pub config: Option<PathBuf>,
 
+    /// open this account on startup
+    #[structopt(long, value_name = "NAME")]
+    pub account: Option<String>,
+
+    /// open the mailbox with this path on startup
+    #[structopt(long, value_name = "PATH")]
+    pub mailbox: Option<String>,
+
+    /// search the startup mailbox with this query
+    #[structopt(long, value_name = "QUERY")]
+    pub query: Option<String>,
+
+    /// record the session's input, terminal size and mailbox changes to
+    /// FILE, to reproduce a bug with `--replay`
+    #[structopt(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with = "replay"
+    )]
+    pub record: Option<PathBuf>,
+
+    /// replay a session recorded with `--record`
+    #[structopt(long, value_name = "FILE", parse(from_os_str))]
+    pub replay: Option<PathBuf>,
+
     #[structopt(subcommand)]
     pub subcommand: Option<SubCommand>,
 }
@@ -90,11 +116,64 @@ pub enum SubCommand {
     /// print compile time feature flags of this binary
     CompiledWith,
 
-    /// View mail from input file.
+    /// View mail from input files. Input can be e-mail or mbox files, or `-`
+    /// for standard input.
     View {
-        #[structopt(value_name = "INPUT", parse(from_os_str))]
+        #[structopt(value_name = "INPUT", parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// start meli with a new draft open in the composer.
+    Compose(ComposeOpt),
+
+    /// rebuild the sqlite3 search index of an account and exit.
+    Reindex {
+        /// name of the account to reindex
+        #[structopt(short, long, value_name = "NAME")]
+        account: String,
+    },
+
+    /// import messages from a message file, mbox file or maildir into a
+    /// mailbox and exit.
+    Import {
+        /// name of the account to import to
+        #[structopt(short, long, value_name = "NAME")]
+        account: String,
+        /// path of the mailbox to import to
+        #[structopt(long, value_name = "MAILBOX_PATH")]
+        mailbox: String,
+        #[structopt(value_name = "PATH", parse(from_os_str))]
         path: PathBuf,
     },
+
+    /// look up the IMAP and submission servers of an e-mail address or
+    /// domain from its DNS SRV records and print an account configuration.
+    Discover {
+        #[structopt(value_name = "ADDRESS")]
+        address: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ComposeOpt {
+    /// add recipient address, can be given multiple times
+    #[structopt(long, value_name = "ADDRESS")]
+    pub to: Vec<String>,
+    /// add carbon copy address, can be given multiple times
+    #[structopt(long, value_name = "ADDRESS")]
+    pub cc: Vec<String>,
+    /// add blind carbon copy address, can be given multiple times
+    #[structopt(long, value_name = "ADDRESS")]
+    pub bcc: Vec<String>,
+    /// set the subject of the draft
+    #[structopt(short, long, value_name = "SUBJECT")]
+    pub subject: Option<String>,
+    /// read the body of the draft from this file
+    #[structopt(long, value_name = "PATH", parse(from_os_str))]
+    pub body: Option<PathBuf>,
+    /// attach file, can be given multiple times
+    #[structopt(short, long, value_name = "PATH", parse(from_os_str))]
+    pub attach: Vec<PathBuf>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -28,7 +28,7 @@ use melib::{
         branch::alt,
         bytes::complete::{is_a, is_not, tag, take_until},
         character::complete::{digit1, not_line_ending},
-        combinator::{map, map_res},
+        combinator::{map, map_res, opt},
         error::Error as NomError,
         multi::separated_list1,
         sequence::{pair, preceded, separated_pair},
@@ -42,6 +42,9 @@ pub mod actions;
 use std::collections::HashSet;
 
 use actions::MailboxOperation;
+
+use crate::{conf::notifications::DndMode, power::PowerSavingMode};
+
 pub mod history;
 use std::str::FromStr;
 
@@ -115,6 +118,15 @@ use Token::*;
 use TokenAdicity::*;
 
 impl TokenStream {
+    /// The command's signature, e.g. `copyto|moveto [ACCOUNT] MAILBOX`.
+    pub fn signature(&self) -> String {
+        self.tokens
+            .iter()
+            .map(TokenAdicity::signature)
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
     fn matches<'s>(&self, s: &mut &'s str, sugg: &mut HashSet<String>) -> Vec<(&'s str, Token)> {
         let mut tokens = vec![];
         for t in self.tokens.iter() {
@@ -147,6 +159,7 @@ impl TokenStream {
                     | t @ Filepath
                     | t @ AccountName
                     | t @ MailboxPath
+                    | t @ TagName
                     | t @ QuotedStringValue
                     | t @ AlphanumericStringValue => {
                         let _t = t;
@@ -155,6 +168,10 @@ impl TokenStream {
                     }
                 }
                 tokens.push((*s, *t.inner()));
+                /* An optional token may be skipped, so the next one is expected as well. */
+                if matches!(t, ZeroOrOne(_) | ZeroOrMore(_)) {
+                    continue;
+                }
                 return tokens;
             }
             match t.inner() {
@@ -203,6 +220,7 @@ impl TokenStream {
                 | Filepath
                 | AccountName
                 | MailboxPath
+                | TagName
                 | QuotedStringValue
                 | AlphanumericStringValue => {
                     let mut ptr = 0;
@@ -237,6 +255,15 @@ impl TokenAdicity {
             OneOrMore(ref t) => t,
         }
     }
+
+    fn signature(&self) -> String {
+        match self {
+            ZeroOrOne(t) => format!("[{}]", t.signature()),
+            ZeroOrMore(t) => format!("[{}...]", t.signature()),
+            One(t) => t.signature(),
+            OneOrMore(t) => format!("{}...", t.signature()),
+        }
+    }
 }
 
 /// A token encountered in the UI's command execution bar
@@ -248,6 +275,7 @@ pub enum Token {
     Seq(&'static [TokenAdicity]),
     AccountName,
     MailboxPath,
+    TagName,
     QuotedStringValue,
     RestOfStringValue,
     AlphanumericStringValue,
@@ -256,6 +284,35 @@ pub enum Token {
     IndexValue,
 }
 
+impl Token {
+    /// How the token is shown in a command's signature, e.g. `MAILBOX`.
+    fn signature(&self) -> String {
+        match self {
+            Literal(lit) => lit.to_string(),
+            Filepath => "PATH".to_string(),
+            Alternatives(v) => v
+                .iter()
+                .map(TokenStream::signature)
+                .collect::<Vec<String>>()
+                .join("|"),
+            Seq(v) => v
+                .iter()
+                .map(TokenAdicity::signature)
+                .collect::<Vec<String>>()
+                .join(""),
+            AccountName => "ACCOUNT".to_string(),
+            MailboxPath => "MAILBOX".to_string(),
+            TagName => "TAG".to_string(),
+            QuotedStringValue => "\"STRING\"".to_string(),
+            RestOfStringValue => "TEXT...".to_string(),
+            AlphanumericStringValue => "VALUE".to_string(),
+            AttachmentIndexValue => "ATTACHMENT_INDEX".to_string(),
+            MailboxIndexValue => "MAILBOX_INDEX".to_string(),
+            IndexValue => "INDEX".to_string(),
+        }
+    }
+}
+
 fn eof(input: &[u8]) -> IResult<&[u8], ()> {
     if input.is_empty() {
         Ok((input, ()))
@@ -292,6 +349,85 @@ define_commands!([
                        }
                    )
                  },
+                 { tags: ["expunge"],
+                   desc: "expunge, permanently remove messages marked for deletion",
+                   tokens: &[One(Literal("expunge"))],
+                   parser: (
+                       fn expunge(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("expunge")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(Expunge)))
+                       }
+                   )
+                 },
+                 { tags: ["attach-to-thread"],
+                   desc: "attach selected messages to the thread under the cursor",
+                   tokens: &[One(Literal("attach-to-thread"))],
+                   parser: (
+                       fn attach_to_thread(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("attach-to-thread")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(AttachToThread)))
+                       }
+                   )
+                 },
+                 { tags: ["thread-join"],
+                   desc: "merge selected threads into the thread under the cursor",
+                   tokens: &[One(Literal("thread-join"))],
+                   parser: (
+                       fn thread_join(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("thread-join")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(ThreadJoin)))
+                       }
+                   )
+                 },
+                 { tags: ["thread-split"],
+                   desc: "detach the message under the cursor and its replies to a new thread",
+                   tokens: &[One(Literal("thread-split"))],
+                   parser: (
+                       fn thread_split(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("thread-split")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(ThreadSplit)))
+                       }
+                   )
+                 },
+                 { tags: ["pin"],
+                   desc: "pin selected threads to the top of the listing, or unpin them if already pinned",
+                   tokens: &[One(Literal("pin"))],
+                   parser: (
+                       fn pin(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("pin")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(TogglePin)))
+                       }
+                   )
+                 },
+                 { tags: ["snooze "],
+                   desc: "snooze YYYY-MM-DDTHH:MM, hide the selected threads from the listing until this local time",
+                   tokens: &[One(Literal("snooze")), One(QuotedStringValue)],
+                   parser: (
+                       fn snooze(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("snooze")(input.trim())?;
+                           let (input, _) = is_a(" ")(input)?;
+                           let (input, time) = map_res(quoted_argument, crate::components::mail::scheduled::parse_send_time)(input)?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(Snooze(time))))
+                       }
+                   )
+                 },
+                 { tags: ["unsnooze"],
+                   desc: "show the snoozed threads of the current mailbox in the listing again",
+                   tokens: &[One(Literal("unsnooze"))],
+                   parser: (
+                       fn unsnooze(input: &'_ [u8]) -> IResult<&'_ [u8], Action> {
+                           let (input, _) = tag("unsnooze")(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Listing(UnsnoozeAll)))
+                       }
+                   )
+                 },
                  { tags: ["copyto", "moveto"],
                    desc: "copy/move message",
                    tokens: &[One(Alternatives(&[to_stream!(One(Literal("copyto"))), to_stream!(One(Literal("moveto")))])), ZeroOrOne(AccountName), One(MailboxPath)],
@@ -335,7 +471,7 @@ define_commands!([
                    )
                  },
                 { tags: ["import "],
-                  desc: "import FILESYSTEM_PATH MAILBOX_PATH",
+                  desc: "import FILESYSTEM_PATH MAILBOX_PATH, import a message, mbox file or maildir",
                   tokens: &[One(Literal("import")), One(Filepath), One(MailboxPath)],
                   parser:(
                       fn import(input: &[u8]) -> IResult<&[u8], Action> {
@@ -463,13 +599,16 @@ define_commands!([
                       }
                   )
                 },
-                { tags: ["list-archive", "list-post", "list-unsubscribe", "list-"],
-                  desc: "list-[unsubscribe/post/archive]",
-                  tokens: &[One(Alternatives(&[to_stream!(One(Literal("list-archive"))), to_stream!(One(Literal("list-post"))), to_stream!(One(Literal("list-unsubscribe")))]))],
+                { tags: ["list-archive", "list-post", "list-unsubscribe", "list-unsubscribe-assistant", "list-"],
+                  desc: "list-[unsubscribe/unsubscribe-assistant/post/archive]",
+                  tokens: &[One(Alternatives(&[to_stream!(One(Literal("list-archive"))), to_stream!(One(Literal("list-post"))), to_stream!(One(Literal("list-unsubscribe"))), to_stream!(One(Literal("list-unsubscribe-assistant")))]))],
                   parser: (
                       fn mailinglist(input: &[u8]) -> IResult<&[u8], Action> {
                           let (input, ret) = alt((
                               map(tag("list-post"), |_| MailingListAction(ListPost))
+                              , map(tag("list-unsubscribe-assistant"), |_| MailingListAction(
+                                      UnsubscribeAssistant
+                              ))
                               , map(tag("list-unsubscribe"), |_| MailingListAction(
                                       ListUnsubscribe
                               ))
@@ -524,6 +663,18 @@ define_commands!([
                       }
                   )
                 },
+                { tags: ["compose-from-template"],
+                  desc: "compose-from-template [TEMPLATE]",
+                  tokens: &[One(Literal("compose-from-template")), ZeroOrOne(QuotedStringValue)],
+                  parser:(
+                      fn compose_from_template(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("compose-from-template")(input.trim())?;
+                          let (input, name) = opt(preceded(is_a(" "), quoted_argument))(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, Compose(ComposeFromTemplate(name.map(str::to_string)))))
+                      }
+                  )
+                },
                 /* Pipe pager contents to binary */
                 { tags: ["pipe "],
                   desc: "pipe EXECUTABLE ARGS",
@@ -621,6 +772,20 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["encrypt-attachment "],
+                  desc: "encrypt-attachment [symmetric] INDEX",
+                  tokens: &[One(Literal("encrypt-attachment")), ZeroOrOne(Literal("symmetric")), One(IndexValue)],
+                  parser:(
+                      fn encrypt_attachment(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("encrypt-attachment")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, symmetric) = opt(pair(tag("symmetric"), is_a(" ")))(input)?;
+                          let (input, idx) = map_res(quoted_argument, usize::from_str)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(EncryptAttachment(idx, symmetric.is_some()))))
+                      }
+                  )
+                },
                 { tags: ["save-draft"],
                   desc: "save draft",
                   tokens: &[One(Literal("save-draft"))],
@@ -632,6 +797,28 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["lint-draft"],
+                  desc: "check draft for MIME conformance problems",
+                  tokens: &[One(Literal("lint-draft"))],
+                  parser:(
+                      fn lint_draft(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("lint-draft")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(LintDraft)))
+                      }
+                  )
+                },
+                { tags: ["edit-headers"],
+                  desc: "edit all headers of this draft as text in $EDITOR, including headers not shown in the composer form",
+                  tokens: &[One(Literal("edit-headers"))],
+                  parser:(
+                      fn edit_headers(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("edit-headers")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(EditHeaders)))
+                      }
+                  )
+                },
                 { tags: ["toggle sign "],
                   desc: "switch between sign/unsign for this draft",
                   tokens: &[One(Literal("toggle")), One(Literal("sign"))],
@@ -658,6 +845,45 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["toggle smime-sign"],
+                  desc: "toggle S/MIME signing for this draft",
+                  tokens: &[One(Literal("toggle")), One(Literal("smime-sign"))],
+                  parser:(
+                      fn toggle_smime_sign(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("smime-sign")(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(ToggleSmimeSign)))
+                      }
+                  )
+                },
+                { tags: ["send-at "],
+                  desc: "send-at YYYY-MM-DDTHH:MM, send this draft later, at this local time",
+                  tokens: &[One(Literal("send-at")), One(QuotedStringValue)],
+                  parser:(
+                      fn send_at(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("send-at")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, time) = map_res(quoted_argument, crate::components::mail::scheduled::parse_send_time)(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(SendAt(time))))
+                      }
+                  )
+                },
+                { tags: ["toggle signature"],
+                  desc: "toggle the signature of this draft",
+                  tokens: &[One(Literal("toggle")), One(Literal("signature"))],
+                  parser:(
+                      fn toggle_signature(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input)?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, _) = tag("signature")(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Compose(ToggleSignature)))
+                      }
+                  )
+                },
                 { tags: ["create-mailbox "],
                   desc: "create-mailbox ACCOUNT MAILBOX_PATH",
                   tokens: &[One(Literal("create-mailbox")), One(AccountName), One(MailboxPath)],
@@ -748,6 +974,19 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["sync-contacts "],
+                  desc: "sync-contacts ACCOUNT, sync the account's address book with its CardDAV server",
+                  tokens: &[One(Literal("sync-contacts")), One(AccountName)],
+                  parser:(
+                      fn sync_contacts(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("sync-contacts")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), SyncContacts)))
+                      }
+                  )
+                },
                 { tags: ["open-in-tab"],
                   desc: "opens envelope view in new tab",
                   tokens: &[One(Literal("open-in-tab"))],
@@ -760,17 +999,23 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                   )
                 },
                 { tags: ["save-attachment "],
-                  desc: "save-attachment INDEX PATH",
-                  tokens: &[One(Literal("save-attachment")), One(AttachmentIndexValue), One(Filepath)],
+                  desc: "save-attachment INDEX PATH, or all attachments in directory PATH",
+                  tokens: &[One(Literal("save-attachment")), One(Alternatives(&[to_stream!(One(AttachmentIndexValue)), to_stream!(One(Literal("all")))])), One(Filepath)],
                   parser:(
                       fn save_attachment(input: &[u8]) -> IResult<&[u8], Action> {
                           let (input, _) = tag("save-attachment")(input.trim())?;
                           let (input, _) = is_a(" ")(input)?;
-                          let (input, idx) = map_res(quoted_argument, usize::from_str)(input)?;
+                          let (input, idx) = alt((
+                              map(tag("all"), |_| None),
+                              map(map_res(quoted_argument, usize::from_str), Some),
+                          ))(input)?;
                           let (input, _) = is_a(" ")(input)?;
                           let (input, path) = quoted_argument(input.trim())?;
                           let (input, _) = eof(input)?;
-                          Ok((input, View(SaveAttachment(idx, path.to_string()))))
+                          Ok((input, View(match idx {
+                              Some(idx) => SaveAttachment(idx, path.to_string()),
+                              None => SaveAllAttachments(path.to_string()),
+                          })))
                       }
                   )
                 },
@@ -787,6 +1032,48 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["set-charset "],
+                  desc: "set-charset CHARSET|auto",
+                  tokens: &[One(Literal("set-charset")), One(AlphanumericStringValue)],
+                  parser:(
+                      fn set_charset(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("set-charset")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, charset) = map_res(quoted_argument, |s: &str| -> Result<_, Error> {
+                              if s.eq_ignore_ascii_case("auto") {
+                                  Ok(None)
+                              } else {
+                                  melib::email::attachment_types::Charset::from_str(s).map(Some)
+                              }
+                          })(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(SetCharset(charset))))
+                      }
+                  )
+                },
+                { tags: ["fetch-key"],
+                  desc: "fetch-key",
+                  tokens: &[One(Literal("fetch-key"))],
+                  parser:(
+                      fn fetch_key(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("fetch-key")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(FetchKey)))
+                      }
+                  )
+                },
+                { tags: ["reply"],
+                  desc: "reply [--no-quote], reply to the viewed message, without quoting it with --no-quote",
+                  tokens: &[One(Literal("reply")), ZeroOrOne(Literal("--no-quote"))],
+                  parser:(
+                      fn reply(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("reply")(input.trim())?;
+                          let (input, no_quote) = opt(preceded(is_a(" "), tag("--no-quote")))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(Reply(no_quote.is_none()))))
+                      }
+                  )
+                },
                 { tags: ["add-addresses-to-contacts "],
                   desc: "add-addresses-to-contacts",
                   tokens: &[One(Literal("add-addresses-to-contacts"))],
@@ -798,9 +1085,21 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["address-history"],
+                  desc: "address-history [ADDRESS]",
+                  tokens: &[One(Literal("address-history")), ZeroOrOne(QuotedStringValue)],
+                  parser:(
+                      fn address_history(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("address-history")(input.trim())?;
+                          let (input, address) = opt(preceded(is_a(" "), quoted_argument))(input)?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, View(AddressHistory(address.map(str::to_string)))))
+                      }
+                  )
+                },
                 { tags: ["tag", "tag add", "tag remove"],
-                   desc: "tag [add/remove], edits message's tags.",
-                   tokens: &[One(Literal("tag")), One(Alternatives(&[to_stream!(One(Literal("add"))), to_stream!(One(Literal("remove")))]))],
+                   desc: "tag [add/remove], edits message's tags. Without a tag, choose them from a list.",
+                   tokens: &[One(Literal("tag")), One(Alternatives(&[to_stream!(One(Literal("add")), ZeroOrOne(TagName)), to_stream!(One(Literal("remove")), ZeroOrOne(TagName))]))],
                    parser: (
                        fn _tag<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
                            preceded(
@@ -817,6 +1116,14 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                                    let (input, tag) = quoted_argument(input.trim())?;
                                    let (input, _) = eof(input)?;
                                    Ok((input, Listing(Tag(Remove(tag.to_string())))))
+                               }, |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                   let (input, _) = tag("add")(input.trim())?;
+                                   let (input, _) = eof(input.trim())?;
+                                   Ok((input, Listing(Tag(AddInteractive))))
+                               }, |input: &'a [u8]| -> IResult<&'a [u8], Action> {
+                                   let (input, _) = tag("remove")(input.trim())?;
+                                   let (input, _) = eof(input.trim())?;
+                                   Ok((input, Listing(Tag(RemoveInteractive))))
                                }
                                ))
                            )(input.trim())
@@ -864,6 +1171,53 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["power-saving"],
+                  desc: "power-saving on|off|auto, pause or resume periodic refreshes and index rebuilds regardless of the power and network state, or decide automatically",
+                  tokens: &[One(Literal("power-saving")), One(Alternatives(&[to_stream!(One(Literal("on"))), to_stream!(One(Literal("off"))), to_stream!(One(Literal("auto")))]))],
+                  parser:(
+                      fn power_saving(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("power-saving")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, mode) = alt((
+                              map(tag("on"), |_| PowerSavingMode::On),
+                              map(tag("off"), |_| PowerSavingMode::Off),
+                              map(tag("auto"), |_| PowerSavingMode::Auto),
+                          ))(input.trim())?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, PowerSaving(mode)))
+                      }
+                  )
+                },
+                { tags: ["dnd"],
+                  desc: "dnd on|off|auto, suppress new mail notifications, show them, or suppress them according to the dnd_schedule notifications setting",
+                  tokens: &[One(Literal("dnd")), One(Alternatives(&[to_stream!(One(Literal("on"))), to_stream!(One(Literal("off"))), to_stream!(One(Literal("auto")))]))],
+                  parser:(
+                      fn dnd(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("dnd")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, mode) = alt((
+                              map(tag("on"), |_| DndMode::On),
+                              map(tag("off"), |_| DndMode::Off),
+                              map(tag("auto"), |_| DndMode::Auto),
+                          ))(input.trim())?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, Dnd(mode)))
+                      }
+                  )
+                },
+                { tags: ["workspace "],
+                  desc: "workspace NAME|all, show only the accounts of workspace NAME in the sidebar and in notifications, or show every account",
+                  tokens: &[One(Literal("workspace")), One(QuotedStringValue)],
+                  parser:(
+                      fn workspace(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("workspace")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, name) = quoted_argument(input.trim())?;
+                          let (input, _) = eof(input.trim())?;
+                          Ok((input, Workspace(Some(name.to_string()).filter(|n| n != "all"))))
+                      }
+                  )
+                },
                 { tags: ["manage-mailboxes"],
                   desc: "view and manage mailbox preferences",
                   tokens: &[One(Literal("manage-mailboxes"))],
@@ -875,6 +1229,51 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["statistics"],
+                  desc: "view mail activity statistics of the current account",
+                  tokens: &[One(Literal("statistics"))],
+                  parser:(
+                      fn statistics(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("statistics")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Tab(Statistics)))
+                      }
+                  )
+                },
+                { tags: ["duplicates"],
+                  desc: "duplicates [account], review and delete duplicate messages of the current mailbox or of the whole account",
+                  tokens: &[One(Literal("duplicates")), ZeroOrOne(Literal("account"))],
+                  parser:(
+                      fn duplicates(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("duplicates")(input.trim())?;
+                          let (input, account) = opt(preceded(is_a(" "), tag("account")))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Tab(Duplicates(account.is_some()))))
+                      }
+                  )
+                },
+                { tags: ["drafts"],
+                  desc: "resume, send or delete the drafts of the current account",
+                  tokens: &[One(Literal("drafts"))],
+                  parser:(
+                      fn drafts(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("drafts")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Tab(Drafts)))
+                      }
+                  )
+                },
+                { tags: ["scheduled"],
+                  desc: "edit, send or cancel the messages of the current account scheduled with send-at",
+                  tokens: &[One(Literal("scheduled"))],
+                  parser:(
+                      fn scheduled(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("scheduled")(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Tab(Scheduled)))
+                      }
+                  )
+                },
                 { tags: ["quit"],
                   desc: "quit meli",
                   tokens: &[One(Literal("quit"))],
@@ -941,6 +1340,13 @@ fn listing_action(input: &[u8]) -> IResult<&[u8], Action> {
         toggle,
         seen_flag,
         delete_message,
+        expunge,
+        attach_to_thread,
+        thread_join,
+        thread_split,
+        pin,
+        snooze,
+        unsnooze,
         copymove,
         import,
         search,
@@ -956,15 +1362,26 @@ fn compose_action(input: &[u8]) -> IResult<&[u8], Action> {
     alt((
         add_attachment,
         mailto,
+        compose_from_template,
         remove_attachment,
+        encrypt_attachment,
         toggle_sign,
         toggle_encrypt,
+        toggle_smime_sign,
+        toggle_signature,
+        send_at,
         save_draft,
+        lint_draft,
+        edit_headers,
     ))(input)
 }
 
+fn tab_action(input: &[u8]) -> IResult<&[u8], Action> {
+    alt((manage_mailboxes, statistics, duplicates, drafts, scheduled))(input)
+}
+
 fn account_action(input: &[u8]) -> IResult<&[u8], Action> {
-    alt((reindex, print_account_setting))(input)
+    alt((reindex, print_account_setting, sync_contacts))(input)
 }
 
 fn view(input: &[u8]) -> IResult<&[u8], Action> {
@@ -974,32 +1391,46 @@ fn view(input: &[u8]) -> IResult<&[u8], Action> {
         save_attachment,
         export_mail,
         add_addresses_to_contacts,
+        address_history,
+        set_charset,
+        fetch_key,
+        reply,
     ))(input)
 }
 
 pub fn parse_command(input: &[u8]) -> Result<Action, Error> {
+    /* nom's alt takes at most 21 parsers, so they are grouped. */
     alt((
-        goto,
-        listing_action,
-        sort,
-        subsort,
-        close,
-        mailinglist,
-        setenv,
-        printenv,
-        view,
-        compose_action,
-        create_mailbox,
-        sub_mailbox,
-        unsub_mailbox,
-        delete_mailbox,
-        rename_mailbox,
-        manage_mailboxes,
-        account_action,
-        print_setting,
-        toggle_mouse,
-        reload_config,
-        quit,
+        alt((
+            goto,
+            listing_action,
+            sort,
+            subsort,
+            close,
+            mailinglist,
+            setenv,
+            printenv,
+            view,
+            compose_action,
+        )),
+        alt((
+            create_mailbox,
+            sub_mailbox,
+            unsub_mailbox,
+            delete_mailbox,
+            rename_mailbox,
+        )),
+        alt((
+            tab_action,
+            account_action,
+            print_setting,
+            toggle_mouse,
+            power_saving,
+            dnd,
+            workspace,
+            reload_config,
+            quit,
+        )),
     ))(input)
     .map(|(_, v)| v)
     .map_err(|err| err.into())
@@ -1050,6 +1481,8 @@ fn test_parser() {
         &IntoIterator::into_iter([
             "toggle mouse".to_string(),
             "toggle sign".to_string(),
+            "toggle smime-sign".to_string(),
+            "toggle signature".to_string(),
             "toggle encrypt".to_string(),
             "toggle thread_snooze".to_string()
         ])
@@ -1057,6 +1490,194 @@ fn test_parser() {
     );
 }
 
+#[test]
+fn test_command_argument_completion() {
+    let values = CompletionValues {
+        accounts: vec![
+            (
+                "work".to_string(),
+                vec!["INBOX".to_string(), "INBOX/Sent Items".to_string()],
+            ),
+            (
+                "personal".to_string(),
+                vec!["INBOX".to_string(), "Archive".to_string()],
+            ),
+        ],
+        tags: vec!["important".to_string(), "todo".to_string()],
+    };
+    let complete = |input: &str| -> Vec<String> {
+        let mut ret = command_completion_suggestions(input, &values);
+        ret.sort();
+        ret
+    };
+    /* The account argument of moveto is optional: both accounts and mailboxes
+     * are suggested. */
+    assert_eq!(
+        complete("moveto "),
+        vec![
+            "moveto \"INBOX/Sent Items\"",
+            "moveto Archive",
+            "moveto INBOX",
+            "moveto personal",
+            "moveto work",
+        ]
+    );
+    assert_eq!(complete("moveto p"), vec!["moveto personal"]);
+    assert_eq!(
+        complete("moveto IN"),
+        vec!["moveto \"INBOX/Sent Items\"", "moveto INBOX"]
+    );
+    assert_eq!(
+        complete("moveto work \"INBOX/S"),
+        vec!["moveto work \"INBOX/Sent Items\""]
+    );
+    assert_eq!(
+        complete("moveto personal A"),
+        vec!["moveto personal Archive"]
+    );
+    assert_eq!(complete("reindex w"), vec!["reindex work"]);
+    assert_eq!(complete("tag add i"), vec!["tag add important"]);
+    assert_eq!(
+        complete("tag remove "),
+        vec!["tag remove important", "tag remove todo"]
+    );
+    assert_eq!(complete("tag "), vec!["tag add", "tag remove"]);
+}
+
+#[test]
+fn test_command_signatures() {
+    assert_eq!(
+        command_signatures("moveto"),
+        vec![(
+            "copyto|moveto [ACCOUNT] MAILBOX".to_string(),
+            "copy/move message"
+        )]
+    );
+    assert_eq!(
+        command_signatures("tag add"),
+        vec![(
+            "tag add [TAG]|remove [TAG]".to_string(),
+            "tag [add/remove], edits message's tags. Without a tag, choose them from a list."
+        )]
+    );
+    assert_eq!(
+        command_signatures("setenv"),
+        vec![("setenv VALUE=\"STRING\"...".to_string(), "setenv VAR=VALUE")]
+    );
+    assert!(command_signatures("mov").is_empty());
+    assert!(command_signatures("").is_empty());
+}
+
+#[test]
+fn test_save_attachment_command() {
+    assert!(matches!(
+        parse_command(b"save-attachment 2 /tmp"),
+        Ok(View(SaveAttachment(2, ref path))) if path == "/tmp"
+    ));
+    assert!(matches!(
+        parse_command(b"save-attachment all \"~/My Files\""),
+        Ok(View(SaveAllAttachments(ref path))) if path == "~/My Files"
+    ));
+    assert!(parse_command(b"save-attachment all").is_err());
+}
+
+#[test]
+fn test_tag_command() {
+    assert!(matches!(
+        parse_command(b"tag add \"to do\""),
+        Ok(Listing(Tag(Add(ref tag)))) if tag == "to do"
+    ));
+    assert!(matches!(
+        parse_command(b"tag remove todo"),
+        Ok(Listing(Tag(Remove(ref tag)))) if tag == "todo"
+    ));
+    assert!(matches!(
+        parse_command(b"tag add"),
+        Ok(Listing(Tag(AddInteractive)))
+    ));
+    assert!(matches!(
+        parse_command(b"tag remove "),
+        Ok(Listing(Tag(RemoveInteractive)))
+    ));
+    assert!(parse_command(b"tag").is_err());
+}
+
+#[test]
+fn test_shortcut_commands() {
+    use crate::conf::shortcuts::*;
+
+    let sections = [
+        (Shortcuts::GENERAL, GeneralShortcuts::default().key_values()),
+        (Shortcuts::LISTING, ListingShortcuts::default().key_values()),
+        (
+            Shortcuts::COMPOSING,
+            ComposingShortcuts::default().key_values(),
+        ),
+        (
+            Shortcuts::CONTACT_LIST,
+            ContactListShortcuts::default().key_values(),
+        ),
+        (
+            Shortcuts::ENVELOPE_VIEW,
+            EnvelopeViewShortcuts::default().key_values(),
+        ),
+        (
+            Shortcuts::THREAD_VIEW,
+            ThreadViewShortcuts::default().key_values(),
+        ),
+        (Shortcuts::PAGER, PagerShortcuts::default().key_values()),
+    ];
+    let mut count = 0;
+    for (section, shortcuts) in sections {
+        for key in shortcuts.keys() {
+            if let Some(command) = Shortcuts::command(section, key) {
+                count += 1;
+                let command = command
+                    .replace("QUERY", "subject:meli")
+                    .replace("MAILBOX", "INBOX")
+                    .replace("CHARSET", "utf-8")
+                    .replace("PATH", "/tmp");
+                assert!(
+                    parse_command(command.as_bytes()).is_ok(),
+                    "{}.{}: {:?} does not parse",
+                    section,
+                    key,
+                    command
+                );
+            }
+        }
+    }
+    assert_eq!(count, 12);
+    assert_eq!(
+        Shortcuts::command(Shortcuts::LISTING, "set_seen"),
+        Some("set seen")
+    );
+    assert_eq!(Shortcuts::command(Shortcuts::PAGER, "scroll_up"), None);
+}
+
+#[test]
+fn test_reply_command() {
+    assert!(matches!(parse_command(b"reply"), Ok(View(Reply(true)))));
+    assert!(matches!(
+        parse_command(b"reply --no-quote"),
+        Ok(View(Reply(false)))
+    ));
+    assert!(parse_command(b"reply --quote").is_err());
+}
+
+#[test]
+fn test_list_unsubscribe_command() {
+    assert!(matches!(
+        parse_command(b"list-unsubscribe"),
+        Ok(MailingListAction(ListUnsubscribe))
+    ));
+    assert!(matches!(
+        parse_command(b"list-unsubscribe-assistant"),
+        Ok(MailingListAction(UnsubscribeAssistant))
+    ));
+    assert!(parse_command(b"list-unsubscribe-all").is_err());
+}
+
 #[test]
 #[ignore]
 fn test_parser_interactive() {
@@ -1106,10 +1727,97 @@ fn test_parser_interactive() {
     println!("alright");
 }
 
+/// Values that command arguments are completed with.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionValues {
+    /// Account names, each with the paths of its mailboxes.
+    pub accounts: Vec<(String, Vec<String>)>,
+    /// Tag names.
+    pub tags: Vec<String>,
+}
+
+impl CompletionValues {
+    /// Values for an argument of kind `token`, given the tokens matched before
+    /// it.
+    fn candidates(&self, token: Token, previous: &[(&str, Token)]) -> Vec<String> {
+        match token {
+            AccountName => self.accounts.iter().map(|(a, _)| a.clone()).collect(),
+            MailboxPath => {
+                /* Complete with the mailboxes of the account argument, if there is one. */
+                if let Some((_, mailboxes)) = previous.iter().rev().find_map(|(s, t)| {
+                    if matches!(t, AccountName) {
+                        self.accounts.iter().find(|(a, _)| a == s.trim())
+                    } else {
+                        None
+                    }
+                }) {
+                    return mailboxes.clone();
+                }
+                let mut ret = self
+                    .accounts
+                    .iter()
+                    .flat_map(|(_, m)| m.iter().cloned())
+                    .collect::<Vec<String>>();
+                ret.sort();
+                ret.dedup();
+                ret
+            }
+            TagName => self.tags.clone(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Completions of the argument `input` ends with, given the tokens `m` of a
+/// command it matched.
+fn argument_suggestions(
+    input: &str,
+    m: &[(&str, Token)],
+    values: &CompletionValues,
+) -> Vec<String> {
+    /* The word being typed starts after the last whitespace, or at an open
+     * quote. */
+    let word_start = if input.matches('"').count() % 2 == 1 {
+        input.rfind('"').unwrap_or(0)
+    } else {
+        input.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0)
+    };
+    if word_start == 0 {
+        return vec![];
+    }
+    let word = &input[word_start..];
+    let needle = word.strip_prefix('"').unwrap_or(word);
+    /* Tokens still expected after the input, and the one the word was matched
+     * against, if any. */
+    let pending = m
+        .iter()
+        .rev()
+        .take_while(|(s, _)| s.trim().is_empty())
+        .count();
+    let mut kinds = vec![];
+    if !word.is_empty() && pending < m.len() {
+        kinds.push(m.len() - pending - 1);
+    }
+    kinds.extend(m.len() - pending..m.len());
+    kinds
+        .into_iter()
+        .flat_map(|i| values.candidates(m[i].1, &m[..i]))
+        .filter(|c| c.starts_with(needle) && c != needle)
+        .map(|c| {
+            if c.contains(char::is_whitespace) || c.contains('"') {
+                format!("{}\"{}\"", &input[..word_start], c)
+            } else {
+                format!("{}{}", &input[..word_start], c)
+            }
+        })
+        .collect()
+}
+
 /// Get command suggestions for input
-pub fn command_completion_suggestions(input: &str) -> Vec<String> {
+pub fn command_completion_suggestions(input: &str, values: &CompletionValues) -> Vec<String> {
     use crate::melib::ShellExpandTrait;
     let mut sugg = Default::default();
+    let mut ret = vec![];
     for (_tags, _desc, tokens) in COMMAND_COMPLETION.iter() {
         let _m = tokens.matches(&mut &(*input), &mut sugg);
         if _m.is_empty() {
@@ -1119,8 +1827,40 @@ pub fn command_completion_suggestions(input: &str) -> Vec<String> {
             let p = std::path::Path::new(s);
             sugg.extend(p.complete(true).into_iter());
         }
+        ret.extend(argument_suggestions(input, &_m, values));
     }
-    sugg.into_iter()
+    let mut ret = sugg
+        .into_iter()
         .map(|s| format!("{}{}", input, s.as_str()))
-        .collect::<Vec<String>>()
+        .chain(ret)
+        .collect::<Vec<String>>();
+    ret.sort();
+    ret.dedup();
+    ret
+}
+
+/// Signatures and descriptions of the commands whose name `input` starts
+/// with, to show as a hint while typing them.
+pub fn command_signatures(input: &str) -> Vec<(String, &'static str)> {
+    let mut ret: Vec<(String, &'static str)> = vec![];
+    for (_tags, desc, tokens) in COMMAND_COMPLETION.iter() {
+        let m = tokens.matches(&mut &(*input), &mut HashSet::default());
+        let lit = match m.first() {
+            Some((s, Literal(lit))) if s == lit => lit,
+            _ => continue,
+        };
+        /* The literal must be a whole word: `set` is not a prefix of `setenv`. */
+        if !input.trim_start()[lit.len()..]
+            .chars()
+            .next()
+            .map_or(true, char::is_whitespace)
+        {
+            continue;
+        }
+        let signature = tokens.signature();
+        if !ret.iter().any(|(s, _)| *s == signature) {
+            ret.push((signature, *desc));
+        }
+    }
+    ret
 }
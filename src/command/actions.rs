@@ -28,12 +28,16 @@ use std::path::PathBuf;
 pub use melib::thread::{SortField, SortOrder};
 use melib::uuid::Uuid;
 
-use crate::components::Component;
+use crate::{components::Component, conf::notifications::DndMode, power::PowerSavingMode};
 
 #[derive(Debug)]
 pub enum TagAction {
     Add(String),
     Remove(String),
+    /// Choose the tags to add from the tags of the account.
+    AddInteractive,
+    /// Choose the tags to remove from the tags of the messages.
+    RemoveInteractive,
 }
 
 #[derive(Debug)]
@@ -53,9 +57,18 @@ pub enum ListingAction {
     Import(PathBuf, MailboxPath),
     ExportMbox(Option<melib::backends::mbox::MboxFormat>, PathBuf),
     Delete,
+    Expunge,
     OpenInNewTab,
     Tag(TagAction),
+    /// Set or unset several flags and tags (as `Err(tag)`) at once.
+    SetFlags(Vec<(std::result::Result<melib::Flag, String>, bool)>),
     ToggleThreadSnooze,
+    AttachToThread,
+    ThreadJoin,
+    ThreadSplit,
+    TogglePin,
+    Snooze(melib::datetime::UnixTimestamp),
+    UnsnoozeAll,
 }
 
 #[derive(Debug)]
@@ -64,6 +77,13 @@ pub enum TabAction {
     Kill(Uuid),
     New(Option<Box<dyn Component>>),
     ManageMailboxes,
+    Statistics,
+    /// Review duplicate messages of the current mailbox, or of the whole
+    /// account if `true`.
+    Duplicates(bool),
+    Drafts,
+    /// List the messages of the current account scheduled with `send-at`.
+    Scheduled,
 }
 
 #[derive(Debug)]
@@ -71,6 +91,11 @@ pub enum MailingListAction {
     ListPost,
     ListArchive,
     ListUnsubscribe,
+    /// Choose among the unsubscribe methods of the selected messages.
+    UnsubscribeAssistant,
+    /// Unsubscribe from senders, as their lowercased address, with the
+    /// chosen methods.
+    Unsubscribe(Vec<(String, melib::email::list_management::UnsubscribeMethod)>),
 }
 
 #[derive(Debug)]
@@ -78,8 +103,21 @@ pub enum ViewAction {
     Pipe(String, Vec<String>),
     Filter(String),
     SaveAttachment(usize, String),
+    /// Save every attachment of the message in a directory.
+    SaveAllAttachments(String),
     ExportMail(String),
     AddAddressesToContacts,
+    /// Show all mail exchanged with an address, or with the sender of the
+    /// viewed message if `None`.
+    AddressHistory(Option<String>),
+    /// Force a charset for decoding the viewed message, or `None` for
+    /// automatic detection.
+    SetCharset(Option<melib::email::attachment_types::Charset>),
+    /// Retrieve missing public keys of signers and verify the signatures
+    /// again.
+    FetchKey,
+    /// Reply to the viewed message, quoting it if `true`.
+    Reply(bool),
 }
 
 #[derive(Debug)]
@@ -88,16 +126,32 @@ pub enum ComposeAction {
     AddAttachmentFilePicker(Option<String>),
     AddAttachmentPipe(String),
     RemoveAttachment(usize),
+    /// Encrypt the attachment at this index, with a passphrase if `true` or
+    /// else for the recipients.
+    EncryptAttachment(usize, bool),
     SaveDraft,
+    LintDraft,
+    EditHeaders,
     ToggleSign,
     ToggleEncrypt,
+    /// Toggle signing with the certificate of the `smime` settings.
+    ToggleSmimeSign,
+    /// Remove the signature of the `signature` setting from the draft, or
+    /// add it back.
+    ToggleSignature,
+    /// Send the draft later, at this time.
+    SendAt(melib::datetime::UnixTimestamp),
     Mailto(melib::Mailto),
+    /// Start a draft from the named file of the `templates_folder` setting,
+    /// or choose one if `None`.
+    ComposeFromTemplate(Option<String>),
 }
 
 #[derive(Debug)]
 pub enum AccountAction {
     ReIndex,
     PrintAccountSetting(String),
+    SyncContacts,
 }
 
 #[derive(Debug)]
@@ -107,6 +161,7 @@ pub enum MailboxOperation {
     Subscribe(MailboxPath),
     Unsubscribe(MailboxPath),
     Rename(MailboxPath, NewMailboxPath),
+    Expunge(MailboxPath),
     // Placeholder
     SetPermissions(MailboxPath),
 }
@@ -128,6 +183,10 @@ pub enum Action {
     PrintSetting(String),
     ReloadConfiguration,
     ToggleMouse,
+    PowerSaving(PowerSavingMode),
+    Dnd(DndMode),
+    /// Switch to the named workspace, or show all accounts if `None`.
+    Workspace(Option<String>),
     Quit,
 }
 
@@ -41,7 +41,7 @@ pub mod utilities;
 pub use self::utilities::*;
 
 pub mod contacts;
-pub use crate::contacts::*;
+pub use self::contacts::*;
 
 pub mod mailbox_management;
 pub use self::mailbox_management::*;
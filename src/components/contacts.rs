@@ -127,7 +127,16 @@ impl ContactManager {
             .push(("E-MAIL".into(), self.card.email().to_string()));
         self.form.push(("URL".into(), self.card.url().to_string()));
         self.form.push(("KEY".into(), self.card.key().to_string()));
+        for k in ["COLOR", "LABEL"] {
+            self.form.push((
+                k.into(),
+                self.card.extra_property(k).unwrap_or_default().to_string(),
+            ));
+        }
         for (k, v) in self.card.extra_properties() {
+            if k == "COLOR" || k == "LABEL" {
+                continue;
+            }
             self.form.push((k.to_string().into(), v.to_string()));
         }
     }
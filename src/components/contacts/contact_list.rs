@@ -203,7 +203,7 @@ impl ContactList {
         }
 
         if self.length == 0 {
-            let message = "Address book is empty.".to_string();
+            let message = tr!("Address book is empty.");
             self.data_columns.columns[0] =
                 CellBuffer::new_with_context(message.len(), self.length, None, context);
             write_string_to_grid(
@@ -693,6 +693,23 @@ impl Component for ContactList {
 
                     return true;
                 }
+                UIEvent::Input(ref key)
+                    if shortcut!(key == shortcuts[Shortcuts::CONTACT_LIST]["address_history"])
+                        && self.length > 0 =>
+                {
+                    let account = &context.accounts[self.account_pos];
+                    let account_hash = account.hash();
+                    let card = &account.address_book[&self.id_positions[self.cursor_pos]];
+                    let address = Address::new(
+                        Some(card.name().to_string()).filter(|n| !n.is_empty()),
+                        card.email().to_string(),
+                    );
+                    let history = AddressHistoryView::new(account_hash, address, context);
+                    context
+                        .replies
+                        .push_back(UIEvent::Action(Tab(New(Some(Box::new(history))))));
+                    return true;
+                }
                 UIEvent::Input(ref key)
                     if shortcut!(key == shortcuts[Shortcuts::CONTACT_LIST]["next_account"]) =>
                 {
@@ -38,6 +38,25 @@ pub use self::compose::*;
 
 #[cfg(feature = "gpgme")]
 pub mod pgp;
+pub mod smime;
 
 mod status;
 pub use self::status::*;
+
+mod address_history;
+pub use self::address_history::*;
+
+mod statistics;
+pub use self::statistics::*;
+
+mod duplicates;
+pub use self::duplicates::*;
+
+mod drafts;
+pub use self::drafts::*;
+
+pub mod scheduled;
+pub use self::scheduled::ScheduledView;
+
+mod message_list;
+pub use self::message_list::*;
@@ -0,0 +1,566 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A listing of all mail exchanged with an address across an account.
+
+use std::{cmp, collections::HashSet};
+
+use melib::{datetime::UnixTimestamp, email::Address, EnvelopeHash};
+
+use super::*;
+use crate::{jobs::JoinHandle, melib::text_processing::TextProcessing};
+
+const DATE_FMT: &str = "%Y-%m-%d %H:%M";
+
+#[derive(Debug)]
+struct HistoryEntry {
+    env_hash: EnvelopeHash,
+    mailbox_hash: MailboxHash,
+    timestamp: UnixTimestamp,
+    /// Whether the message was received, i.e. not sent from one of the user's
+    /// addresses.
+    incoming: bool,
+}
+
+impl HistoryEntry {
+    fn new(
+        env_hash: EnvelopeHash,
+        mailbox_hash: MailboxHash,
+        envelope: &Envelope,
+        own: &[Address],
+    ) -> Self {
+        HistoryEntry {
+            env_hash,
+            mailbox_hash,
+            timestamp: envelope.date(),
+            incoming: !envelope
+                .from()
+                .iter()
+                .any(|a| reply::is_own_address(own, a)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AddressHistoryView {
+    cursor_pos: usize,
+    new_cursor_pos: usize,
+    account_hash: AccountHash,
+    address: Address,
+    /// Messages from and to `address`, newest first.
+    entries: Vec<HistoryEntry>,
+    length: usize,
+    data_columns: DataColumns<4>,
+    handle: Option<JoinHandle<Result<Vec<EnvelopeHash>>>>,
+
+    initialized: bool,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+
+    dirty: bool,
+
+    movement: Option<PageMovement>,
+    id: ComponentId,
+}
+
+impl fmt::Display for AddressHistoryView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "history: {}", self.address.get_email())
+    }
+}
+
+impl AddressHistoryView {
+    pub fn new(account_hash: AccountHash, address: Address, context: &mut Context) -> Self {
+        let theme_default = crate::conf::value(context, "theme_default");
+        let mut data_columns = DataColumns::default();
+        data_columns.theme_config.set_single_theme(theme_default);
+        let mut ret = AddressHistoryView {
+            cursor_pos: 0,
+            new_cursor_pos: 0,
+            account_hash,
+            address,
+            entries: vec![],
+            length: 0,
+            data_columns,
+            handle: None,
+            theme_default,
+            highlight_theme: crate::conf::value(context, "highlight"),
+            initialized: false,
+            dirty: true,
+            movement: None,
+            id: ComponentId::new_v4(),
+        };
+        ret.search(context);
+        ret
+    }
+
+    /// Searches every loaded mailbox of the account for messages from, to or
+    /// cc'ed to the address.
+    fn search(&mut self, context: &mut Context) {
+        let account = &context.accounts[&self.account_hash];
+        let query = format!(
+            "from:\"{email}\" or to:\"{email}\" or cc:\"{email}\"",
+            email = self.address.get_email()
+        );
+        let mailbox_hashes: Vec<MailboxHash> = account
+            .collection
+            .mailboxes
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        let futures = match mailbox_hashes
+            .into_iter()
+            .map(|mailbox_hash| {
+                account.search(&query, (SortField::Date, SortOrder::Desc), mailbox_hash)
+            })
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(futures) => futures,
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some("Could not search address history".to_string()),
+                    err.to_string(),
+                    Some(crate::types::NotificationType::Error(err.kind)),
+                ));
+                return;
+            }
+        };
+        let job = async move {
+            let mut ret = vec![];
+            for fut in futures {
+                ret.extend(fut.await?);
+            }
+            Ok(ret)
+        };
+        let handle = account.job_executor.spawn_specialized(job);
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        self.handle = Some(handle);
+    }
+
+    fn set_results(&mut self, env_hashes: Vec<EnvelopeHash>, context: &Context) {
+        let account = &context.accounts[&self.account_hash];
+        let own = reply::own_addresses(&account.settings.account);
+        let envelopes = account.collection.envelopes.read().unwrap();
+        let mailboxes = account.collection.mailboxes.read().unwrap();
+        let mut seen = HashSet::new();
+        self.entries = env_hashes
+            .into_iter()
+            .filter(|env_hash| seen.insert(*env_hash))
+            .filter_map(|env_hash| {
+                let envelope = envelopes.get(&env_hash)?;
+                /* Searching a backend or cache might return messages of other
+                 * mailboxes, so look the mailbox up in the collection. */
+                let mailbox_hash = mailboxes
+                    .iter()
+                    .find(|(_, hashes)| hashes.contains(&env_hash))
+                    .map(|(mailbox_hash, _)| *mailbox_hash)?;
+                Some(HistoryEntry::new(env_hash, mailbox_hash, envelope, &own))
+            })
+            .collect();
+        self.entries.sort_by_key(|e| cmp::Reverse(e.timestamp));
+        self.cursor_pos = 0;
+        self.new_cursor_pos = 0;
+        self.initialized = false;
+        self.set_dirty(true);
+    }
+
+    /// Returns the header line of the view with the statistics of the
+    /// correspondence.
+    fn summary(&self) -> String {
+        if self.handle.is_some() {
+            return format!("Searching for mail exchanged with {}…", self.address);
+        }
+        let (first, last) = match (self.entries.last(), self.entries.first()) {
+            (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+            _ => return format!("No mail exchanged with {}.", self.address),
+        };
+        let received = self.entries.iter().filter(|e| e.incoming).count();
+        format!(
+            "{}: {} message{} ({} received, {} sent), first contact {}, last contact {}",
+            self.address,
+            self.entries.len(),
+            if self.entries.len() == 1 { "" } else { "s" },
+            received,
+            self.entries.len() - received,
+            melib::datetime::timestamp_to_string(first, Some(DATE_FMT), false),
+            melib::datetime::timestamp_to_string(last, Some(DATE_FMT), false),
+        )
+    }
+
+    fn initialize(&mut self, context: &mut Context) {
+        self.initialized = true;
+        self.length = self.entries.len();
+        self.set_dirty(true);
+        let account = &context.accounts[&self.account_hash];
+        let rows: Vec<[String; 4]> = self
+            .entries
+            .iter()
+            .map(|e| {
+                let envelope = account.collection.get_env(e.env_hash);
+                [
+                    melib::datetime::timestamp_to_string(e.timestamp, Some(DATE_FMT), false),
+                    if e.incoming { "received" } else { "sent" }.to_string(),
+                    account
+                        .mailbox_entries
+                        .get(&e.mailbox_hash)
+                        .map(|m| m.ref_mailbox.path().to_string())
+                        .unwrap_or_default(),
+                    envelope.subject().to_string(),
+                ]
+            })
+            .collect();
+
+        let mut min_width = ["date".len(), "received".len(), "mailbox".len(), 0];
+        for row in &rows {
+            for (w, s) in min_width.iter_mut().zip(row.iter()) {
+                *w = cmp::max(*w, s.grapheme_width());
+            }
+        }
+        for (i, w) in min_width.iter().enumerate() {
+            self.data_columns.columns[i] =
+                CellBuffer::new_with_context(*w, self.length, None, context);
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            for (i, s) in row.iter().enumerate() {
+                write_string_to_grid(
+                    s,
+                    &mut self.data_columns.columns[i],
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs,
+                    ((0, idx), (min_width[i], idx)),
+                    None,
+                );
+            }
+        }
+    }
+
+    fn draw_list(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        let (upper_left, bottom_right) = area;
+
+        if self.length == 0 {
+            clear_area(grid, area, self.theme_default);
+            context.dirty_areas.push_back(area);
+            return;
+        }
+        let rows = get_y(bottom_right) - get_y(upper_left) + 1;
+
+        if let Some(mvm) = self.movement.take() {
+            match mvm {
+                PageMovement::Up(amount) => {
+                    self.new_cursor_pos = self.new_cursor_pos.saturating_sub(amount);
+                }
+                PageMovement::PageUp(multiplier) => {
+                    self.new_cursor_pos = self.new_cursor_pos.saturating_sub(rows * multiplier);
+                }
+                PageMovement::Down(amount) => {
+                    if self.new_cursor_pos + amount < self.length {
+                        self.new_cursor_pos += amount;
+                    } else {
+                        self.new_cursor_pos = self.length - 1;
+                    }
+                }
+                PageMovement::PageDown(multiplier) => {
+                    #[allow(clippy::comparison_chain)]
+                    if self.new_cursor_pos + rows * multiplier < self.length {
+                        self.new_cursor_pos += rows * multiplier;
+                    } else if self.new_cursor_pos + rows * multiplier > self.length {
+                        self.new_cursor_pos = self.length - 1;
+                    } else {
+                        self.new_cursor_pos = (self.length / rows) * rows;
+                    }
+                }
+                PageMovement::Right(_) | PageMovement::Left(_) => {}
+                PageMovement::Home => {
+                    self.new_cursor_pos = 0;
+                }
+                PageMovement::End => {
+                    self.new_cursor_pos = self.length - 1;
+                }
+            }
+        }
+
+        let prev_page_no = (self.cursor_pos).wrapping_div(rows);
+        let page_no = (self.new_cursor_pos).wrapping_div(rows);
+
+        let top_idx = page_no * rows;
+
+        /* If cursor position has changed, remove the highlight from the previous
+         * position and apply it in the new one. */
+        if self.cursor_pos != self.new_cursor_pos && prev_page_no == page_no {
+            let old_cursor_pos = self.cursor_pos;
+            self.cursor_pos = self.new_cursor_pos;
+            for &(idx, highlight) in &[(old_cursor_pos, false), (self.new_cursor_pos, true)] {
+                if idx >= self.length {
+                    continue; //bounds check
+                }
+                let new_area = nth_row_area(area, idx % rows);
+                self.data_columns
+                    .draw(grid, idx, self.cursor_pos, grid.bounds_iter(new_area));
+                let row_attr = if highlight {
+                    self.highlight_theme
+                } else {
+                    self.theme_default
+                };
+                change_colors(grid, new_area, row_attr.fg, row_attr.bg);
+                context.dirty_areas.push_back(new_area);
+            }
+            return;
+        } else if self.cursor_pos != self.new_cursor_pos {
+            self.cursor_pos = self.new_cursor_pos;
+        }
+        if self.new_cursor_pos >= self.length {
+            self.new_cursor_pos = self.length - 1;
+            self.cursor_pos = self.new_cursor_pos;
+        }
+        /* Page_no has changed, so draw new page */
+        _ = self
+            .data_columns
+            .recalc_widths((width!(area), height!(area)), top_idx);
+        clear_area(grid, area, self.theme_default);
+        /* copy table columns */
+        self.data_columns
+            .draw(grid, top_idx, self.cursor_pos, grid.bounds_iter(area));
+
+        /* highlight cursor */
+        change_colors(
+            grid,
+            nth_row_area(area, self.cursor_pos % rows),
+            self.highlight_theme.fg,
+            self.highlight_theme.bg,
+        );
+
+        /* clear gap if available height is more than count of entries */
+        if top_idx + rows > self.length {
+            clear_area(
+                grid,
+                (
+                    pos_inc(upper_left, (0, self.length - top_idx)),
+                    bottom_right,
+                ),
+                self.theme_default,
+            );
+        }
+        context.dirty_areas.push_back(area);
+    }
+}
+
+impl Component for AddressHistoryView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.is_dirty() {
+            return;
+        }
+        if !self.initialized {
+            self.initialize(context);
+        }
+        let (upper_left, bottom_right) = area;
+        let header_area = (upper_left, set_y(bottom_right, get_y(upper_left) + 1));
+        clear_area(grid, header_area, self.theme_default);
+        write_string_to_grid(
+            &self.summary(),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (upper_left, set_y(bottom_right, get_y(upper_left))),
+            None,
+        );
+        context.dirty_areas.push_back(header_area);
+        if height!(area) > 2 {
+            self.draw_list(grid, (pos_inc(upper_left, (0, 2)), bottom_right), context);
+        }
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "highlight");
+                self.initialized = false;
+                self.set_dirty(true);
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .handle
+                    .as_ref()
+                    .map(|h| h.job_id == *job_id)
+                    .unwrap_or(false) =>
+            {
+                let mut handle = self.handle.take().unwrap();
+                match handle.chan.try_recv() {
+                    Err(_) => { /* Job was canceled */ }
+                    Ok(None) => { /* something happened, perhaps a worker thread panicked */ }
+                    Ok(Some(Ok(env_hashes))) => {
+                        self.set_results(env_hashes, context);
+                    }
+                    Ok(Some(Err(err))) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not search address history".to_string()),
+                            err.to_string(),
+                            Some(crate::types::NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_up"]) =>
+            {
+                self.movement = Some(PageMovement::Up(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_down"])
+                    && self.cursor_pos < self.length.saturating_sub(1) =>
+            {
+                self.movement = Some(PageMovement::Down(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["prev_page"]) =>
+            {
+                self.movement = Some(PageMovement::PageUp(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["next_page"]) =>
+            {
+                self.movement = Some(PageMovement::PageDown(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["home_page"]) =>
+            {
+                self.movement = Some(PageMovement::Home);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["end_page"]) =>
+            {
+                self.movement = Some(PageMovement::End);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["open_entry"])
+                    && self.cursor_pos < self.length =>
+            {
+                let entry = &self.entries[self.cursor_pos];
+                let view = MailView::new(
+                    (self.account_hash, entry.mailbox_hash, entry.env_hash),
+                    None,
+                    None,
+                    context,
+                );
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        if let Some(handle) = self.handle.take() {
+            handle.cancel();
+        }
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+
+        map.insert(
+            Shortcuts::GENERAL,
+            context.settings.shortcuts.general.key_values(),
+        );
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+
+    fn get_status(&self, _context: &Context) -> String {
+        format!("{} entries", self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    #[test]
+    fn test_address_history_entry_direction() {
+        let own = vec![Address::try_from("Me <me@example.com>").unwrap()];
+        let entry = |raw: &[u8]| {
+            HistoryEntry::new(
+                EnvelopeHash(1),
+                MailboxHash::default(),
+                &Envelope::from_bytes(raw, None).unwrap(),
+                &own,
+            )
+        };
+        assert!(entry(b"From: alice@example.org\nTo: me@example.com\n\nbody\n").incoming);
+        assert!(!entry(b"From: me@example.com\nTo: alice@example.org\n\nbody\n").incoming);
+        /* Subaddresses and other capitalizations are the user's too. */
+        assert!(!entry(b"From: Me+lists@Example.com\nTo: alice@example.org\n\nbody\n").incoming);
+        /* A message from the address to someone else, e.g. when the user was
+         * bcc'ed, was still received. */
+        assert!(entry(b"From: alice@example.org\nTo: bob@example.org\n\nbody\n").incoming);
+        /* Mail to oneself is sent. */
+        assert!(!entry(b"From: me@example.com\nTo: me@example.com\n\nbody\n").incoming);
+    }
+}
@@ -20,15 +20,16 @@
  */
 
 use std::{
+    collections::HashSet,
     convert::TryInto,
     future::Future,
     io::Write,
+    path::PathBuf,
     pin::Pin,
     process::{Command, Stdio},
     sync::{Arc, Mutex},
 };
 
-use indexmap::IndexSet;
 use melib::{
     email::attachment_types::{ContentType, MultipartType},
     list_management, Draft,
@@ -46,6 +47,25 @@ use edit_attachments::*;
 
 pub mod hooks;
 
+pub mod reply;
+
+pub mod auto_recipients;
+
+pub mod aliases;
+
+pub mod contact_picker;
+use contact_picker::ContactPicker;
+
+pub mod outgoing_policy;
+
+pub mod downscale;
+use downscale::DownscaleImage;
+
+pub mod templates;
+
+pub mod signature;
+pub use reply::ReplyKind;
+
 #[derive(Debug, PartialEq, Eq)]
 enum Cursor {
     Headers,
@@ -101,8 +121,29 @@ pub struct Composer {
 
     embed_area: Area,
     embed: Option<EmbedStatus>,
+    /// Whether the file being edited in `$EDITOR` only holds the headers of
+    /// the draft.
+    editing_headers: bool,
+    /// Recipients added by `auto_recipients` rules, as `header:email`, so
+    /// that they aren't added again after the user removes them.
+    auto_recipients_applied: HashSet<String>,
+    /// Headers shown in the form, from the `form_headers` setting.
+    form_headers: Vec<String>,
     #[cfg(feature = "gpgme")]
     gpg_state: gpg::GpgComposeState,
+    /// Whether to sign with the certificate of the `smime` settings.
+    smime_sign: ToggleFlag,
+    /// Whether the draft has the signature of the `signature` setting, which
+    /// is appended when the draft is first shown unless this is `False`.
+    signature: ToggleFlag,
+    /// The signature that was appended to the body.
+    signature_text: Option<String>,
+    /// Attachments being encrypted, by index.
+    #[cfg(feature = "gpgme")]
+    encrypt_attachment_jobs: Vec<(usize, JoinHandle<Result<AttachmentBuilder>>)>,
+    /// Key lookups of new recipients, with their addresses.
+    #[cfg(feature = "gpgme")]
+    recipient_keys_jobs: Vec<(Vec<String>, gpg::RecipientKeysJob)>,
     dirty: bool,
     has_changes: bool,
     initialized: bool,
@@ -118,9 +159,17 @@ enum ViewMode {
     },
     Edit,
     Embed,
+    PickContacts(ContactPicker),
+    /// Choosing files to attach.
+    BrowseAttachments(FileBrowser),
     SelectRecipients(UIDialog<Address>),
+    /// Selecting the encryption key of a recipient, or the signing key if
+    /// `None`.
     #[cfg(feature = "gpgme")]
-    SelectEncryptKey(bool, gpg::KeySelection),
+    SelectEncryptKey(Option<String>, gpg::KeySelection),
+    /// Selecting the recipient whose encryption key to change.
+    #[cfg(feature = "gpgme")]
+    SelectKeyRecipient(UIDialog<String>),
     Send(UIConfirmationDialog),
     WaitingForSendResult(UIDialog<char>, JoinHandle<Result<()>>),
 }
@@ -164,15 +213,26 @@ impl Composer {
                 hooks::PASTDATEWARN,
                 hooks::MISSINGATTACHMENTWARN,
                 hooks::EMPTYDRAFTWARN,
+                hooks::MIMELINTWARN,
             ],
             form: FormWidget::default(),
             mode: ViewMode::Edit,
             #[cfg(feature = "gpgme")]
             gpg_state: gpg::GpgComposeState::default(),
+            smime_sign: ToggleFlag::Unset,
+            signature: ToggleFlag::Unset,
+            signature_text: None,
+            #[cfg(feature = "gpgme")]
+            encrypt_attachment_jobs: vec![],
+            #[cfg(feature = "gpgme")]
+            recipient_keys_jobs: vec![],
             dirty: true,
             has_changes: false,
             embed_area: ((0, 0), (0, 0)),
             embed: None,
+            editing_headers: false,
+            auto_recipients_applied: HashSet::default(),
+            form_headers: crate::conf::composing::form_headers(),
             initialized: false,
             id: ComponentId::new_v4(),
         }
@@ -181,6 +241,7 @@ impl Composer {
     pub fn with_account(account_hash: AccountHash, context: &Context) -> Self {
         let mut ret = Composer {
             account_hash,
+            form_headers: account_settings!(context[account_hash].composing.form_headers).clone(),
             ..Composer::new(context)
         };
 
@@ -212,6 +273,12 @@ impl Composer {
                 format!("meli {}", option_env!("CARGO_PKG_VERSION").unwrap_or("0.0")),
             );
         }
+        if *account_settings!(context[account_hash].composing.insert_x_mailer) {
+            ret.draft.set_header(
+                "X-Mailer",
+                format!("meli {}", option_env!("CARGO_PKG_VERSION").unwrap_or("0.0")),
+            );
+        }
         if *account_settings!(context[account_hash].composing.format_flowed) {
             ret.pager
                 .set_reflow(melib::text_processing::Reflow::FormatFlowed);
@@ -244,14 +311,15 @@ impl Composer {
         ret.draft = Draft::edit(&envelope, bytes)?;
 
         ret.account_hash = account_hash;
+        ret.disable_signature();
         Ok(ret)
     }
 
     pub fn reply_to(
         coordinates @ (account_hash, _, _): (AccountHash, MailboxHash, EnvelopeHash),
-        reply_body: String,
+        reply_body: Option<String>,
         context: &mut Context,
-        reply_to_all: bool,
+        kind: ReplyKind,
     ) -> Self {
         let mut ret = Composer::with_account(account_hash, context);
         // Add user's custom hooks.
@@ -328,71 +396,24 @@ impl Composer {
             }
         }
 
-        // "Mail-Followup-To/(To+Cc+(Mail-Reply-To/Reply-To/From)) for follow-up,
-        // Mail-Reply-To/Reply-To/From for reply-to-author."
-        // source: https://cr.yp.to/proto/replyto.html
-        if reply_to_all {
-            let mut to = IndexSet::new();
-
-            if let Some(actions) = list_management::ListActions::detect(&envelope) {
-                if let Some(post) = actions.post {
-                    if let list_management::ListAction::Email(list_post_addr) = post[0] {
-                        if let Ok(list_address) =
-                            melib::email::parser::generic::mailto(list_post_addr)
-                                .map(|(_, m)| m.address)
-                        {
-                            to.insert(list_address);
-                        }
-                    }
+        let own = ret.own_addresses(context);
+        let recipients_err = match reply::reply_recipients(&envelope, kind, &own) {
+            Ok(recipients) => {
+                ret.draft
+                    .set_header("To", reply::addresses_to_string(&recipients.to));
+                if kind == ReplyKind::All {
+                    ret.draft
+                        .set_header("Cc", reply::addresses_to_string(&recipients.cc));
                 }
+                None
             }
-            if let Some(reply_to) = envelope
-                .other_headers()
-                .get("Mail-Followup-To")
-                .and_then(|v| v.try_into().ok())
-            {
-                to.insert(reply_to);
-            } else if let Some(reply_to) = envelope
-                .other_headers()
-                .get("Reply-To")
-                .and_then(|v| v.try_into().ok())
-            {
-                to.insert(reply_to);
-            } else {
-                to.extend(envelope.from().iter().cloned());
-            }
-            to.extend(envelope.to().iter().cloned());
-            if let Ok(ours) = TryInto::<Address>::try_into(
-                context.accounts[&coordinates.0]
-                    .settings
-                    .account()
-                    .make_display_name()
-                    .as_str(),
-            ) {
-                to.remove(&ours);
-            }
-            ret.draft.set_header("To", {
-                let mut ret: String =
-                    to.into_iter()
-                        .fold(String::new(), |mut s: String, n: Address| {
-                            s.push_str(&n.to_string());
-                            s.push_str(", ");
-                            s
-                        });
-                ret.pop();
-                ret.pop();
-                ret
-            });
-            ret.draft.set_header("Cc", envelope.field_cc_to_string());
-        } else if let Some(reply_to) = envelope.other_headers().get("Mail-Reply-To") {
-            ret.draft.set_header("To", reply_to.to_string());
-        } else if let Some(reply_to) = envelope.other_headers().get("Reply-To") {
-            ret.draft.set_header("To", reply_to.to_string());
-        } else {
-            ret.draft.set_header("To", envelope.field_from_to_string());
-        }
-        ret.draft.body = {
-            let mut ret = attribution_string(
+            Err(err) => {
+                ret.draft.set_header("To", envelope.field_from_to_string());
+                Some(err)
+            }
+        };
+        if let Some(reply_body) = reply_body {
+            let mut body = attribution_string(
                 account_settings!(
                     context[ret.account_hash]
                         .composing
@@ -408,13 +429,18 @@ impl Composer {
                         .attribution_use_posix_locale
                 ),
             );
-            for l in reply_body.lines() {
-                ret.push('>');
-                ret.push_str(l);
-                ret.push('\n');
-            }
-            ret
-        };
+            body.push_str(&quote(&reply_body));
+            ret.draft.body = body;
+        }
+
+        drop(envelope);
+        if let Some(err) = recipients_err {
+            context.replies.push_back(UIEvent::Notification(
+                Some(tr!("Could not select reply recipients")),
+                err.to_string(),
+                Some(NotificationType::Error(err.kind)),
+            ));
+        }
 
         ret.account_hash = coordinates.0;
         ret.reply_context = Some((coordinates.1, coordinates.2));
@@ -423,67 +449,370 @@ impl Composer {
 
     pub fn reply_to_select(
         coordinates @ (account_hash, _, _): (AccountHash, MailboxHash, EnvelopeHash),
-        reply_body: String,
+        reply_body: Option<String>,
         context: &mut Context,
     ) -> Self {
-        let mut ret = Composer::reply_to(coordinates, reply_body, context, false);
+        let mut ret = Composer::reply_to(coordinates, reply_body, context, ReplyKind::Author);
+        let own = ret.own_addresses(context);
         let account = &context.accounts[&account_hash];
         let parent_message = account.collection.get_env(coordinates.2);
-        /* If message is from a mailing list and we detect a List-Post header, ask
-         * user if they want to reply to the mailing list or the submitter of
-         * the message */
-        if let Some(actions) = list_management::ListActions::detect(&parent_message) {
-            if let Some(post) = actions.post {
-                if let list_management::ListAction::Email(list_post_addr) = post[0] {
-                    if let Ok(list_address) = melib::email::parser::generic::mailto(list_post_addr)
-                        .map(|(_, m)| m.address)
-                    {
-                        let list_address_string = list_address.to_string();
-                        ret.mode = ViewMode::SelectRecipients(UIDialog::new(
-                            "select recipients",
-                            vec![
-                                (
-                                    parent_message.from()[0].clone(),
-                                    parent_message.field_from_to_string(),
-                                ),
-                                (list_address, list_address_string),
-                            ],
-                            false,
-                            Some(Box::new(move |id: ComponentId, results: &[Address]| {
-                                Some(UIEvent::FinishedUIDialog(
-                                    id,
-                                    Box::new(
-                                        results
-                                            .iter()
-                                            .map(|a| a.to_string())
-                                            .collect::<Vec<String>>()
-                                            .join(", "),
-                                    ),
-                                ))
-                            })),
-                            context,
-                        ));
-                    }
-                }
-            }
+        /* If it is unclear whether the reply should go to the author or to the
+         * mailing list, e.g. because the list has set Reply-To to itself, ask
+         * the user */
+        if let Some(candidates) = reply::reply_recipients(&parent_message, ReplyKind::Author, &own)
+            .ok()
+            .and_then(|r| r.ambiguous)
+        {
+            ret.mode = ViewMode::SelectRecipients(UIDialog::new(
+                "select recipients",
+                candidates
+                    .into_iter()
+                    .map(|a| {
+                        let s = a.to_string();
+                        (a, s)
+                    })
+                    .collect(),
+                false,
+                Some(Box::new(move |id: ComponentId, results: &[Address]| {
+                    Some(UIEvent::FinishedUIDialog(
+                        id,
+                        Box::new(reply::addresses_to_string(results)),
+                    ))
+                })),
+                context,
+            ));
         }
         ret
     }
 
     pub fn reply_to_author(
-        coordinates: (AccountHash, MailboxHash, EnvelopeHash),
-        reply_body: String,
+        coordinates @ (account_hash, _, _): (AccountHash, MailboxHash, EnvelopeHash),
+        reply_body: Option<String>,
         context: &mut Context,
     ) -> Self {
-        Composer::reply_to(coordinates, reply_body, context, false)
+        let mut ret = Composer::reply_to(coordinates, reply_body, context, ReplyKind::Author);
+        let own = ret.own_addresses(context);
+        let account = &context.accounts[&account_hash];
+        let parent_message = account.collection.get_env(coordinates.2);
+        /* Skip a Reply-To that points to a mailing list */
+        if reply::reply_recipients(&parent_message, ReplyKind::Author, &own)
+            .map(|r| r.ambiguous.is_some())
+            .unwrap_or(false)
+        {
+            ret.draft
+                .set_header("To", parent_message.field_from_to_string());
+        }
+        drop(parent_message);
+        ret
     }
 
     pub fn reply_to_all(
         coordinates: (AccountHash, MailboxHash, EnvelopeHash),
-        reply_body: String,
+        reply_body: Option<String>,
+        context: &mut Context,
+    ) -> Self {
+        Composer::reply_to(coordinates, reply_body, context, ReplyKind::All)
+    }
+
+    pub fn reply_to_list(
+        coordinates: (AccountHash, MailboxHash, EnvelopeHash),
+        reply_body: Option<String>,
         context: &mut Context,
     ) -> Self {
-        Composer::reply_to(coordinates, reply_body, context, true)
+        Composer::reply_to(coordinates, reply_body, context, ReplyKind::List)
+    }
+
+    /// Keeps the draft's body as it is instead of appending the signature of
+    /// the `signature` setting, for drafts that are resumed or come from
+    /// templates.
+    pub fn disable_signature(&mut self) {
+        self.signature = ToggleFlag::False;
+    }
+
+    /// Appends the account's signature to the body.
+    fn add_signature(&mut self, context: &mut Context) {
+        let text = match self.signature_text.clone() {
+            Some(text) => Some(text),
+            None => match signature::account_signature(context, self.account_hash) {
+                Ok(text) => text,
+                Err(err) => {
+                    self.signature = ToggleFlag::False;
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(tr!("Could not add signature")),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    ));
+                    return;
+                }
+            },
+        };
+        let text = match text {
+            Some(text) if !text.is_empty() => text,
+            _ => {
+                if self.initialized {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                            "No signature is set for this account."
+                        ))));
+                }
+                self.signature = ToggleFlag::False;
+                return;
+            }
+        };
+        let body = signature::append_signature(self.draft.body(), &text);
+        self.draft.set_body(body);
+        self.pager.update_from_str(self.draft.body(), Some(77));
+        self.signature = ToggleFlag::from(true);
+        self.signature_text = Some(text);
+        if self.initialized {
+            self.has_changes = true;
+        }
+    }
+
+    /// Removes the signature appended by [`Self::add_signature`] from the
+    /// body.
+    fn remove_signature(&mut self, context: &mut Context) {
+        let body = self
+            .signature_text
+            .as_deref()
+            .and_then(|text| signature::remove_signature(self.draft.body(), text));
+        match body {
+            Some(body) => {
+                self.draft.set_body(body);
+                self.pager.update_from_str(self.draft.body(), Some(77));
+                self.signature = ToggleFlag::from(false);
+                self.has_changes = true;
+            }
+            None => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        "Could not find the signature in the draft, it has been edited."
+                            .to_string(),
+                    )));
+            }
+        }
+    }
+
+    /// Stores the draft to be sent at `send_at` by
+    /// [`scheduled::send_due`](crate::components::mail::scheduled::send_due)
+    /// and closes the composer.
+    fn schedule_send(&mut self, send_at: UnixTimestamp, context: &mut Context) {
+        let notify = |context: &mut Context, err: String| {
+            context.replies.push_back(UIEvent::Notification(
+                Some(tr!("Could not schedule draft")),
+                err,
+                Some(NotificationType::Error(melib::error::ErrorKind::None)),
+            ));
+        };
+        if send_at <= melib::datetime::now() {
+            notify(context, tr!("The send time has already passed."));
+            return;
+        }
+        #[cfg(feature = "gpgme")]
+        let is_pgp = self.gpg_state.sign_mail.is_true() || self.gpg_state.encrypt_mail.is_true();
+        #[cfg(not(feature = "gpgme"))]
+        let is_pgp = false;
+        if is_pgp || self.smime_sign.is_true() {
+            notify(
+                context,
+                "Scheduled messages are sent without signing or encryption. Disable them for \
+                 this draft first."
+                    .to_string(),
+            );
+            return;
+        }
+        self.update_draft();
+        self.expand_draft_aliases(context);
+        if *account_settings!(context[self.account_hash].composing.followup_to) {
+            self.set_followup_to(context);
+        }
+        if let Err(err) = self.check_outgoing_policies(context) {
+            notify(context, err);
+            return;
+        }
+        let date =
+            melib::datetime::timestamp_to_string(send_at, Some(melib::datetime::RFC822_DATE), true);
+        self.draft.set_header("Date", date);
+        let result = self.draft.clone().finalise().and_then(|message| {
+            context.accounts[&self.account_hash].schedule_message(message.as_bytes(), send_at)
+        });
+        match result {
+            Ok(_) => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                        "Message scheduled to be sent at {}. See the `scheduled` command.",
+                        melib::datetime::timestamp_to_string(
+                            send_at,
+                            Some("%Y-%m-%d %H:%M"),
+                            false
+                        )
+                    ))));
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(Kill(self.id))));
+            }
+            Err(err) => notify(context, err.to_string()),
+        }
+    }
+
+    /// The addresses of the user in this account, including extra
+    /// identities.
+    fn own_addresses(&self, context: &Context) -> Vec<Address> {
+        reply::own_addresses(&context.accounts[&self.account_hash].settings.account)
+    }
+
+    /// Sets `Mail-Followup-To` in a reply to a mailing list, so that
+    /// follow-ups are sent to the list and the other recipients but not to
+    /// us. See the `followup_to` setting.
+    fn set_followup_to(&mut self, context: &Context) {
+        if self.draft.headers().contains_key("Mail-Followup-To") {
+            return;
+        }
+        let env_hash = match self.reply_context {
+            Some((_, env_hash)) => env_hash,
+            None => return,
+        };
+        let account = &context.accounts[&self.account_hash];
+        if !account.collection.contains_key(&env_hash) {
+            return;
+        }
+        let list = match reply::list_post_address(&account.collection.get_env(env_hash)) {
+            Some(list) => list,
+            None => return,
+        };
+        let recipients: Vec<Address> = ["To", "Cc"]
+            .iter()
+            .filter_map(|h| self.draft.headers().get(h))
+            .filter_map(|v| melib::email::parser::address::rfc2822address_list(v.as_bytes()).ok())
+            .flat_map(|(_, list)| list.into_iter())
+            .collect();
+        if !reply::contains_address(&recipients, &list) {
+            return;
+        }
+        let own = self.own_addresses(context);
+        let followup: Vec<Address> = recipients
+            .into_iter()
+            .filter(|a| !reply::is_own_address(&own, a))
+            .collect();
+        self.draft
+            .set_header("Mail-Followup-To", reply::addresses_to_string(&followup));
+    }
+
+    /// Adds the Cc/Bcc recipients of the `auto_recipients` rules that match
+    /// the draft's `To` header. Returns whether the draft was changed.
+    fn apply_auto_recipients(&mut self, context: &mut Context) -> bool {
+        let additions = {
+            let headers = self.draft.headers();
+            auto_recipients::auto_recipients(
+                account_settings!(context[self.account_hash].composing.auto_recipients),
+                headers.get("To").unwrap_or_default(),
+                headers.get("Cc").unwrap_or_default(),
+                headers.get("Bcc").unwrap_or_default(),
+            )
+        };
+        let mut added = vec![];
+        for (header, address) in additions {
+            let key = format!("{}:{}", header, address.get_email().to_lowercase());
+            if !self.auto_recipients_applied.insert(key) {
+                continue;
+            }
+            let value = match self.draft.headers().get(header) {
+                Some(v) if !v.trim().is_empty() => format!("{}, {}", v, address),
+                _ => address.to_string(),
+            };
+            self.draft.set_header(header, value);
+            added.push(format!("{}: {}", header, address));
+        }
+        if added.is_empty() {
+            return false;
+        }
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                "Added recipients from auto_recipients rules: {}",
+                added.join(", ")
+            ))));
+        true
+    }
+
+    /// Applies the `outgoing_policies` rules that match the draft's
+    /// recipients. Returns warnings to show before sending, or why sending
+    /// is not allowed.
+    fn check_outgoing_policies(
+        &mut self,
+        context: &Context,
+    ) -> std::result::Result<Vec<String>, String> {
+        use crate::conf::composing::OutgoingPolicy;
+
+        let rules = account_settings!(context[self.account_hash].composing.outgoing_policies);
+        if rules.is_empty() {
+            return Ok(vec![]);
+        }
+        let parse = |header: &str| {
+            self.draft
+                .headers()
+                .get(header)
+                .and_then(|v| melib::email::parser::address::rfc2822address_list(v.as_bytes()).ok())
+                .map(|(_, list)| list.into_iter().collect::<Vec<Address>>())
+                .unwrap_or_default()
+        };
+        let from = parse("From")
+            .first()
+            .map(Address::get_email)
+            .unwrap_or_default();
+        let recipients = ["To", "Cc", "Bcc"]
+            .iter()
+            .flat_map(|h| parse(h))
+            .collect::<Vec<Address>>();
+        let policies = outgoing_policy::recipient_policies(rules, &from, &recipients);
+        let addresses = |policy: OutgoingPolicy| {
+            policies
+                .iter()
+                .filter(|(p, _)| *p == policy)
+                .map(|(_, a)| a.get_email())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let blocked = addresses(OutgoingPolicy::Block);
+        if !blocked.is_empty() {
+            return Err(tr!(
+                "Sending to {} is not allowed by the outgoing_policies setting.",
+                blocked
+            ));
+        }
+        let encrypted_only = addresses(OutgoingPolicy::RequireEncryption);
+        #[cfg(feature = "gpgme")]
+        let is_encrypted =
+            self.gpg_state.encrypt_mail.is_true() && !self.gpg_state.encrypt_keys.is_empty();
+        #[cfg(not(feature = "gpgme"))]
+        let is_encrypted = false;
+        if !encrypted_only.is_empty() && !is_encrypted {
+            return Err(tr!(
+                "Mail to {} must be encrypted: enable encryption and select a key first.",
+                encrypted_only
+            ));
+        }
+        let mut warnings = vec![];
+        let plain_text_only = addresses(OutgoingPolicy::PlainText);
+        if !plain_text_only.is_empty() {
+            let removed = outgoing_policy::strip_html_parts(&mut self.draft);
+            if removed > 0 {
+                warnings.push(format!(
+                    "Removed {} HTML part{} for {}.",
+                    removed,
+                    if removed == 1 { "" } else { "s" },
+                    plain_text_only
+                ));
+            }
+        }
+        let warn = addresses(OutgoingPolicy::Warn);
+        if !warn.is_empty() {
+            warnings.push(tr!("Sending to {}.", warn));
+        }
+        Ok(warnings)
     }
 
     pub fn forward(
@@ -540,14 +869,306 @@ To: {}
         self.update_form();
     }
 
+    /// Returns the draft for modification. Header changes made before the
+    /// composer is first drawn are picked up by its header form.
+    pub fn draft_mut(&mut self) -> &mut Draft {
+        &mut self.draft
+    }
+
+    /// Opens a [`FileBrowser`] in the current directory to choose files to
+    /// attach.
+    fn open_file_browser(&mut self, context: &Context) {
+        let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        self.update_draft();
+        self.mode =
+            ViewMode::BrowseAttachments(FileBrowser::new(tr!("Attach files"), dir, context));
+        self.set_dirty(true);
+    }
+
+    /// Adds `attachment` to the draft. Large images are then downscaled, or
+    /// the user is asked, according to the `downscale_images` setting.
+    fn add_attachment(&mut self, attachment: AttachmentBuilder, context: &mut Context) {
+        let threshold = *account_settings!(
+            context[self.account_hash]
+                .composing
+                .downscale_images_threshold
+        );
+        let large_image = downscale::needs_downscaling(&attachment, threshold);
+        self.draft.attachments_mut().push(attachment);
+        self.has_changes = true;
+        if !large_image {
+            return;
+        }
+        let idx = self.draft.attachments().len() - 1;
+        let downscale_images =
+            *account_settings!(context[self.account_hash].composing.downscale_images);
+        if downscale_images.is_true() {
+            self.downscale_attachment(idx, context);
+        } else if downscale_images.is_ask() {
+            let id = self.id;
+            let a = &self.draft.attachments()[idx];
+            let msg = format!(
+                "{} is a {} image, downscale it?",
+                a.filename().unwrap_or("attachment"),
+                melib::Bytes(a.raw().len())
+            );
+            context.replies.push_back(UIEvent::GlobalUIDialog(Box::new(
+                UIConfirmationDialog::new(
+                    &msg,
+                    vec![(true, "yes".to_string()), (false, "no".to_string())],
+                    true,
+                    Some(Box::new(move |_: ComponentId, result: bool| {
+                        Some(UIEvent::FinishedUIDialog(
+                            id,
+                            Box::new(result.then_some(DownscaleImage(idx))),
+                        ))
+                    })),
+                    context,
+                ),
+            )));
+        }
+    }
+
+    /// Downscales the image attachment at `idx` with the
+    /// `downscale_images_command` setting.
+    fn downscale_attachment(&mut self, idx: usize, context: &mut Context) {
+        let command = account_settings!(
+            context[self.account_hash]
+                .composing
+                .downscale_images_command
+        )
+        .clone();
+        let attachment = match self.draft.attachments_mut().get_mut(idx) {
+            Some(a) => a,
+            None => return,
+        };
+        let size = attachment.raw().len();
+        match downscale::downscale(attachment, &command) {
+            Ok(()) => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                        "Downscaled attachment from {} to {}.",
+                        melib::Bytes(size),
+                        melib::Bytes(attachment.raw().len())
+                    ))));
+                self.has_changes = true;
+                self.set_dirty(true);
+            }
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(tr!("Could not downscale image")),
+                    err.to_string(),
+                    Some(NotificationType::Error(melib::error::ErrorKind::External)),
+                ));
+            }
+        }
+    }
+
+    /// Encrypts the attachment at `idx` in the background, with a passphrase
+    /// if `symmetric` or else for the recipients and the sender of the draft.
+    #[cfg(feature = "gpgme")]
+    fn encrypt_attachment(&mut self, idx: usize, symmetric: bool, context: &mut Context) {
+        let attachment = match self.draft.attachments().get(idx) {
+            Some(a) => a.clone(),
+            None => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        "attachment with given index does not exist".to_string(),
+                    )));
+                return;
+            }
+        };
+        let recipients = if symmetric {
+            None
+        } else {
+            Some(
+                ["From", "To", "Cc", "Bcc"]
+                    .iter()
+                    .filter_map(|h| self.draft.headers().get(h))
+                    .filter_map(|v| {
+                        melib::email::parser::address::rfc2822address_list(v.as_bytes()).ok()
+                    })
+                    .flat_map(|(_, list)| list.into_iter().map(|a| a.get_email()))
+                    .collect::<Vec<String>>(),
+            )
+        };
+        let handle = context.job_executor.spawn_specialized(
+            crate::components::mail::pgp::encrypt_attachment(attachment, recipients),
+        );
+        self.encrypt_attachment_jobs.push((idx, handle));
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                "Encrypting attachment {}.",
+                idx
+            ))));
+    }
+
+    /// Updates the encryption keys of the recipients of the draft, looking up
+    /// the keys of new recipients in the background.
+    #[cfg(feature = "gpgme")]
+    fn lookup_recipient_keys(&mut self, context: &mut Context) {
+        let recipients = ["To", "Cc", "Bcc"]
+            .iter()
+            .filter_map(|h| self.form.values().get(*h))
+            .filter_map(|v| {
+                melib::email::parser::address::rfc2822address_list(v.as_str().as_bytes()).ok()
+            })
+            .flat_map(|(_, list)| list.into_iter().map(|a| a.get_email()))
+            .collect::<Vec<String>>();
+        let new = self.gpg_state.set_recipients(&recipients);
+        if new.is_empty() {
+            return;
+        }
+        let handle = context
+            .job_executor
+            .spawn_specialized(crate::components::mail::pgp::recipient_keys(new.clone()));
+        self.recipient_keys_jobs.push((new, handle));
+    }
+
+    /// Lets the user override the encryption key of a recipient, asking which
+    /// recipient first if there are several.
+    #[cfg(feature = "gpgme")]
+    fn select_recipient_key(&mut self, context: &mut Context) {
+        self.lookup_recipient_keys(context);
+        let recipients = self
+            .gpg_state
+            .recipient_keys
+            .iter()
+            .map(|(address, key)| {
+                (
+                    address.clone(),
+                    format!("{}: {}", address, key.describe(address)),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        match recipients.len() {
+            0 => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(tr!("Could not list keys.")),
+                    tr!("No valid recipient addresses in `To:`, `Cc:` or `Bcc:`"),
+                    Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                ));
+            }
+            1 => {
+                let address = recipients[0].0.clone();
+                self.select_encrypt_key(address, context);
+            }
+            _ => {
+                self.mode = ViewMode::SelectKeyRecipient(UIDialog::new(
+                    "select recipient",
+                    recipients,
+                    true,
+                    Some(Box::new(move |id: ComponentId, results: &[String]| {
+                        Some(UIEvent::FinishedUIDialog(
+                            id,
+                            Box::new(results.first().cloned()),
+                        ))
+                    })),
+                    context,
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "gpgme")]
+    fn select_encrypt_key(&mut self, address: String, context: &mut Context) {
+        match gpg::KeySelection::new(
+            false,
+            account_settings!(context[self.account_hash].pgp.allow_remote_lookup).is_true(),
+            address.clone(),
+            *account_settings!(context[self.account_hash].pgp.allow_remote_lookup),
+            context,
+        ) {
+            Ok(widget) => {
+                self.gpg_state.encrypt_mail = ToggleFlag::from(true);
+                self.mode = ViewMode::SelectEncryptKey(Some(address), widget);
+            }
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(tr!("Could not list keys.")),
+                    format!("libgpgme error: {}", &err),
+                    Some(NotificationType::Error(melib::error::ErrorKind::External)),
+                ));
+            }
+        }
+    }
+
+    /// Number of lines listing the encryption key of each recipient.
+    fn recipient_key_lines(&self) -> usize {
+        #[cfg(feature = "gpgme")]
+        if self.gpg_state.encrypt_mail.is_true() {
+            return self.gpg_state.recipient_keys.len();
+        }
+        0
+    }
+
     fn update_draft(&mut self) {
         let header_values = self.form.values_mut();
         let draft_header_map = self.draft.headers_mut();
         for (k, v) in draft_header_map.iter_mut() {
-            if let Some(vn) = header_values.get(k.as_str()) {
+            if let Some((_, vn)) = header_values
+                .iter()
+                .find(|(f, _)| f.eq_ignore_ascii_case(k.as_str()))
+            {
                 *v = vn.as_str().to_string();
             }
         }
+        /* Headers removed with `edit-headers` but filled in the form again */
+        for (k, v) in header_values.iter() {
+            if !v.as_str().is_empty() && !self.draft.headers().contains_key(k) {
+                self.draft.set_header(k, v.as_str().to_string());
+            }
+        }
+    }
+
+    /// Expands the alias at the end of the focused `To`, `Cc` or `Bcc` field.
+    /// Returns whether there was one.
+    fn expand_focused_alias(&mut self, context: &Context) -> bool {
+        let account_hash = self.account_hash;
+        let field = match self.form.focused_field() {
+            Some(f)
+                if ["To", "Cc", "Bcc"]
+                    .iter()
+                    .any(|h| h.eq_ignore_ascii_case(f)) =>
+            {
+                f.to_string()
+            }
+            _ => return false,
+        };
+        if let Some(Field::Text(ref mut text, _)) = self.form.values_mut().get_mut(field.as_str()) {
+            let expanded = aliases::expand_aliases(text.as_str(), true, |alias| {
+                aliases::resolve_alias(context, account_hash, alias)
+            });
+            if expanded != text.as_str() {
+                *text = UText::new(expanded);
+                self.has_changes = true;
+                self.set_dirty(true);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Expands the aliases in the `To`, `Cc` and `Bcc` headers of the draft.
+    fn expand_draft_aliases(&mut self, context: &Context) {
+        let account_hash = self.account_hash;
+        let mut changed = false;
+        for field in ["To", "Cc", "Bcc"] {
+            let value = self.draft.headers().get(field).unwrap_or_default();
+            let expanded = aliases::expand_aliases(value, false, |alias| {
+                aliases::resolve_alias(context, account_hash, alias)
+            });
+            if expanded != value {
+                self.draft.set_header(field, expanded);
+                changed = true;
+            }
+        }
+        if changed {
+            self.update_form();
+        }
     }
 
     fn update_form(&mut self) {
@@ -557,11 +1178,24 @@ To: {}
         self.form.set_cursor(old_cursor);
         let headers = self.draft.headers();
         let account_hash = self.account_hash;
-        for &k in &["Date", "From", "To", "Cc", "Bcc", "Subject"] {
-            if k == "To" || k == "Cc" || k == "Bcc" {
+        let mut fields = self.form_headers.clone();
+        /* Don't hide the main headers of a draft, e.g. the Cc of a reply. */
+        for k in ["From", "To", "Cc", "Bcc", "Subject"] {
+            if !headers.get(k).unwrap_or_default().is_empty()
+                && !fields.iter().any(|f| f.eq_ignore_ascii_case(k))
+            {
+                fields.push(k.to_string());
+            }
+        }
+        for k in fields {
+            if ["To", "Cc", "Bcc", "Reply-To"]
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(&k))
+            {
+                let value = headers.get(&k).unwrap_or_default().to_string();
                 self.form.push_cl((
                     k.into(),
-                    headers[k].to_string(),
+                    value,
                     Box::new(move |c, term| {
                         let book: &AddressBook = &c.accounts[&account_hash].address_book;
                         let results: Vec<String> = book.search(term);
@@ -571,10 +1205,11 @@ To: {}
                             .collect::<Vec<AutoCompleteEntry>>()
                     }),
                 ));
-            } else if k == "From" {
+            } else if k.eq_ignore_ascii_case("From") {
+                let value = headers.get(&k).unwrap_or_default().to_string();
                 self.form.push_cl((
                     k.into(),
-                    headers[k].to_string(),
+                    value,
                     Box::new(move |c, _term| {
                         c.accounts
                             .values()
@@ -605,13 +1240,15 @@ To: {}
                     }),
                 ));
             } else {
-                self.form.push((k.into(), headers[k].to_string()));
+                let value = headers.get(&k).unwrap_or_default().to_string();
+                self.form.push((k.into(), value));
             }
         }
     }
 
     fn draw_attachments(&self, grid: &mut CellBuffer, area: Area, context: &Context) {
         let attachments_no = self.draft.attachments().len();
+        let key_lines = self.recipient_key_lines();
         let theme_default = crate::conf::value(context, "theme_default");
         clear_area(grid, area, theme_default);
         #[cfg(feature = "gpgme")]
@@ -660,28 +1297,19 @@ To: {}
         }
         #[cfg(feature = "gpgme")]
         if self.gpg_state.encrypt_mail.is_true() {
-            let key_list = self
-                .gpg_state
-                .encrypt_keys
-                .iter()
-                .map(|k| k.fingerprint())
-                .collect::<Vec<_>>()
-                .join(", ");
-
+            let missing = self.gpg_state.missing_keys().len();
             write_string_to_grid(
-                &format!(
-                    "{}{}",
-                    if self.gpg_state.encrypt_keys.is_empty() {
-                        "☐ no keys to encrypt with!"
-                    } else {
-                        "☑ encrypt with "
-                    },
-                    if self.gpg_state.encrypt_keys.is_empty() {
-                        ""
-                    } else {
-                        key_list.as_str()
-                    }
-                ),
+                &if self.gpg_state.encrypt_keys.is_empty() {
+                    "☐ no keys to encrypt with!".to_string()
+                } else if missing > 0 {
+                    format!(
+                        "☑ encrypt with ({} recipient{} without a key!)",
+                        missing,
+                        if missing == 1 { "" } else { "s" }
+                    )
+                } else {
+                    "☑ encrypt with".to_string()
+                },
                 grid,
                 theme_default.fg,
                 if self.cursor == Cursor::Encrypt {
@@ -693,6 +1321,17 @@ To: {}
                 (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area)),
                 None,
             );
+            for (i, (address, key)) in self.gpg_state.recipient_keys.iter().enumerate() {
+                write_string_to_grid(
+                    &format!("  {}: {}", address, key.describe(address)),
+                    grid,
+                    theme_default.fg,
+                    theme_default.bg,
+                    theme_default.attrs,
+                    (pos_inc(upper_left!(area), (0, 3 + i)), bottom_right!(area)),
+                    None,
+                );
+            }
         } else {
             write_string_to_grid(
                 "☐ don't encrypt",
@@ -719,7 +1358,10 @@ To: {}
                     theme_default.bg
                 },
                 theme_default.attrs,
-                (pos_inc(upper_left!(area), (0, 3)), bottom_right!(area)),
+                (
+                    pos_inc(upper_left!(area), (0, 3 + key_lines)),
+                    bottom_right!(area),
+                ),
                 None,
             );
         } else {
@@ -733,43 +1375,149 @@ To: {}
                     theme_default.bg
                 },
                 theme_default.attrs,
-                (pos_inc(upper_left!(area), (0, 3)), bottom_right!(area)),
+                (
+                    pos_inc(upper_left!(area), (0, 3 + key_lines)),
+                    bottom_right!(area),
+                ),
                 None,
             );
             for (i, a) in self.draft.attachments().iter().enumerate() {
-                if let Some(name) = a.content_type().name() {
-                    write_string_to_grid(
-                        &format!(
-                            "[{}] \"{}\", {} {}",
-                            i,
-                            name,
-                            a.content_type(),
-                            melib::Bytes(a.raw.len())
-                        ),
-                        grid,
-                        theme_default.fg,
-                        theme_default.bg,
-                        theme_default.attrs,
-                        (pos_inc(upper_left!(area), (0, 4 + i)), bottom_right!(area)),
-                        None,
-                    );
-                } else {
-                    write_string_to_grid(
-                        &format!("[{}] {} {}", i, a.content_type(), melib::Bytes(a.raw.len())),
-                        grid,
-                        theme_default.fg,
-                        theme_default.bg,
-                        theme_default.attrs,
-                        (pos_inc(upper_left!(area), (0, 4 + i)), bottom_right!(area)),
-                        None,
-                    );
+                write_string_to_grid(
+                    &attachment_line(i, a),
+                    grid,
+                    theme_default.fg,
+                    theme_default.bg,
+                    theme_default.attrs,
+                    (
+                        pos_inc(upper_left!(area), (0, 4 + key_lines + i)),
+                        bottom_right!(area),
+                    ),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// Opens the draft in `$EDITOR`, or only its headers if `edit_headers` is
+    /// set. See [`Composer::update_from_file`].
+    fn open_editor(&mut self, edit_headers: bool, context: &mut Context) {
+        let editor = if let Some(editor_command) =
+            account_settings!(context[self.account_hash].composing.editor_command).as_ref()
+        {
+            editor_command.to_string()
+        } else {
+            match std::env::var("EDITOR") {
+                Err(err) => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(err.to_string()),
+                        "$EDITOR is not set. You can change an envvar's value with setenv or set \
+                         composing.editor_command setting in your configuration."
+                            .to_string(),
+                        Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                    ));
+                    return;
+                }
+                Ok(v) => v,
+            }
+        };
+        /* update Draft's headers based on form values */
+        self.update_draft();
+        self.draft.set_wrap_header_preamble(
+            account_settings!(context[self.account_hash].composing.wrap_header_preamble).clone(),
+        );
+
+        let text = if edit_headers {
+            self.draft.headers_to_edit_string()
+        } else {
+            self.draft.to_edit_string()
+        };
+        let f = create_temp_file(text.as_bytes(), None, None, true);
+        self.editing_headers = edit_headers;
+
+        if *account_settings!(context[self.account_hash].composing.embed) {
+            match crate::terminal::embed::create_pty(
+                width!(self.embed_area),
+                height!(self.embed_area),
+                [editor, f.path().display().to_string()].join(" "),
+            ) {
+                Ok(embed) => {
+                    self.embed = Some(EmbedStatus::Running(embed, f));
+                    self.set_dirty(true);
+                    context
+                        .replies
+                        .push_back(UIEvent::ChangeMode(UIMode::Embed));
+                    context.replies.push_back(UIEvent::Fork(ForkType::Embed(
+                        self.embed.as_ref().unwrap().lock().unwrap().child_pid,
+                    )));
+                    self.mode = ViewMode::Embed;
+                }
+                Err(err) => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(tr!("Failed to create pseudoterminal: {}", err)),
+                        err.to_string(),
+                        Some(NotificationType::Error(melib::error::ErrorKind::External)),
+                    ));
                 }
             }
+            return;
+        }
+        /* Kill input thread so that spawned command can be sole receiver of stdin */
+        {
+            context.input_kill();
+        }
+
+        let editor_command = format!("{} {}", editor, f.path().display());
+        log::debug!(
+            "Executing: sh -c \"{}\"",
+            editor_command.replace('"', "\\\"")
+        );
+        match Command::new("sh")
+            .args(["-c", &editor_command])
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .spawn()
+        {
+            Ok(mut child) => {
+                let _ = child.wait();
+            }
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(tr!("Failed to execute {}: {}", editor, err)),
+                    err.to_string(),
+                    Some(NotificationType::Error(melib::error::ErrorKind::External)),
+                ));
+                context.replies.push_back(UIEvent::Fork(ForkType::Finished));
+                context.restore_input();
+                return;
+            }
         }
+        context.replies.push_back(UIEvent::Fork(ForkType::Finished));
+        self.update_from_file(f, context);
+        self.initialized = false;
+        self.set_dirty(true);
     }
 
     fn update_from_file(&mut self, file: File, context: &mut Context) -> bool {
         let result = file.read_to_string();
+        if std::mem::replace(&mut self.editing_headers, false) {
+            return match self.draft.update_headers(result.as_str()) {
+                Ok(has_changes) => {
+                    self.has_changes |= has_changes;
+                    true
+                }
+                Err(err) => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(tr!("Could not update draft headers.")),
+                        format!(
+                            "{}\nThe headers of your draft have been left unchanged.",
+                            &err
+                        ),
+                        Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                    ));
+                    false
+                }
+            };
+        }
         match self.draft.update(result.as_str()) {
             Ok(has_changes) => {
                 self.has_changes = has_changes;
@@ -777,7 +1525,7 @@ To: {}
             }
             Err(err) => {
                 context.replies.push_back(UIEvent::Notification(
-                    Some("Could not parse draft headers correctly.".to_string()),
+                    Some(tr!("Could not parse draft headers correctly.")),
                     format!(
                         "{}\nThe invalid text has been set as the body of your draft",
                         &err
@@ -812,6 +1560,14 @@ impl Component for Composer {
                     context[self.account_hash].pgp.auto_sign
                 ));
             }
+            if self.smime_sign.is_unset() {
+                self.smime_sign = ToggleFlag::InternalVal(*account_settings!(
+                    context[self.account_hash].smime.auto_sign
+                ));
+            }
+            if self.signature.is_unset() {
+                self.add_signature(context);
+            }
             if !self.draft.headers().contains_key("From") || self.draft.headers()["From"].is_empty()
             {
                 self.draft.set_header(
@@ -823,9 +1579,14 @@ impl Component for Composer {
                 );
             }
             self.pager.update_from_str(self.draft.body(), Some(77));
+            self.apply_auto_recipients(context);
             self.update_form();
             self.initialized = true;
         }
+        #[cfg(feature = "gpgme")]
+        if self.gpg_state.encrypt_mail.is_true() {
+            self.lookup_recipient_keys(context);
+        }
         let header_height = self.form.len();
         let theme_default = crate::conf::value(context, "theme_default");
 
@@ -861,7 +1622,7 @@ impl Component for Composer {
         let attachment_area = (
             (
                 mid + 1,
-                get_y(bottom_right).saturating_sub(4 + attachments_no),
+                get_y(bottom_right).saturating_sub(4 + attachments_no + self.recipient_key_lines()),
             ),
             pos_dec(bottom_right, (mid, 0)),
         );
@@ -957,7 +1718,7 @@ impl Component for Composer {
                     let mut shortcuts: ShortcutMaps = Default::default();
                     shortcuts.insert(Shortcuts::COMPOSING, our_map);
                     let stopped_message: String =
-                        format!("Process with PID {} has stopped.", guard.child_pid);
+                        tr!("Process with PID {} has stopped.", guard.child_pid);
                     let stopped_message_2: String = format!(
                         "-press '{}' (edit_mail shortcut) to re-activate.",
                         shortcuts[Shortcuts::COMPOSING]["edit_mail"]
@@ -1087,9 +1848,19 @@ impl Component for Composer {
             }
             #[cfg(feature = "gpgme")]
             ViewMode::SelectEncryptKey(_, _) => {}
+            #[cfg(feature = "gpgme")]
+            ViewMode::SelectKeyRecipient(ref mut s) => {
+                s.draw(grid, area, context);
+            }
             ViewMode::SelectRecipients(ref mut s) => {
                 s.draw(grid, area, context);
             }
+            ViewMode::PickContacts(ref mut picker) => {
+                picker.draw(grid, area, context);
+            }
+            ViewMode::BrowseAttachments(ref mut browser) => {
+                browser.draw(grid, area, context);
+            }
             ViewMode::Discard(_, ref mut s) => {
                 /* Let user choose whether to quit with/without saving or cancel */
                 s.draw(grid, area, context);
@@ -1136,9 +1907,13 @@ impl Component for Composer {
             {
                 if let Some(true) = result.downcast_ref::<bool>() {
                     self.update_draft();
+                    if *account_settings!(context[self.account_hash].composing.followup_to) {
+                        self.set_followup_to(context);
+                    }
                     match send_draft_async(
                         #[cfg(feature = "gpgme")]
                         self.gpg_state.clone(),
+                        self.smime_sign.is_true(),
                         context,
                         self.account_hash,
                         self.draft.clone(),
@@ -1220,6 +1995,14 @@ impl Component for Composer {
                 self.set_dirty(true);
                 return true;
             }
+            #[cfg(feature = "gpgme")]
+            (ViewMode::SelectKeyRecipient(ref dialog), UIEvent::ComponentKill(ref id))
+                if *id == dialog.id() =>
+            {
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
             (ViewMode::Send(ref mut selector), _) => {
                 if selector.process_event(event, context) {
                     return true;
@@ -1241,6 +2024,69 @@ impl Component for Composer {
                     return true;
                 }
             }
+            (ViewMode::PickContacts(ref picker), UIEvent::FinishedUIDialog(id, ref result))
+                if picker.id() == *id =>
+            {
+                if let Some(picked) = result.downcast_ref::<Vec<Address>>() {
+                    let field = picker.field();
+                    let value = contact_picker::append_addresses(
+                        self.draft.headers().get(field).unwrap_or_default(),
+                        picked,
+                    );
+                    self.draft.set_header(field, value);
+                    self.update_form();
+                    self.has_changes = true;
+                }
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::PickContacts(ref picker), UIEvent::ComponentKill(ref id))
+                if picker.id() == *id =>
+            {
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::PickContacts(ref mut picker), _) => {
+                if picker.process_event(event, context) {
+                    return true;
+                }
+            }
+            (
+                ViewMode::BrowseAttachments(ref browser),
+                UIEvent::FinishedUIDialog(id, ref result),
+            ) if browser.id() == *id => {
+                if let Some(paths) = result.downcast_ref::<Vec<PathBuf>>() {
+                    for path in paths {
+                        match melib::email::compose::attachment_from_file(path) {
+                            Ok(attachment) => self.add_attachment(attachment, context),
+                            Err(err) => {
+                                context.replies.push_back(UIEvent::Notification(
+                                    Some("could not add attachment".to_string()),
+                                    err.to_string(),
+                                    Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                                ));
+                            }
+                        }
+                    }
+                }
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::BrowseAttachments(ref browser), UIEvent::ComponentKill(ref id))
+                if browser.id() == *id =>
+            {
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::BrowseAttachments(ref mut browser), _) => {
+                if browser.process_event(event, context) {
+                    return true;
+                }
+            }
             (ViewMode::Discard(u, ref selector), UIEvent::FinishedUIDialog(id, ref mut result))
                 if selector.id() == *id =>
             {
@@ -1336,51 +2182,189 @@ impl Component for Composer {
             }
             #[cfg(feature = "gpgme")]
             (
-                ViewMode::SelectEncryptKey(is_encrypt, ref mut selector),
+                ViewMode::SelectEncryptKey(ref recipient, ref mut selector),
                 UIEvent::FinishedUIDialog(id, result),
             ) if *id == selector.id() => {
                 debug!(&result);
                 if let Some(key) = result.downcast_mut::<Option<melib::gpgme::Key>>() {
                     debug!("got key {:?}", key);
                     if let Some(key) = key {
-                        if *is_encrypt {
-                            self.gpg_state.encrypt_keys.clear();
-                            self.gpg_state.encrypt_keys.push(key.clone());
+                        if let Some(address) = recipient {
+                            self.gpg_state.set_recipient_key(
+                                address,
+                                gpg::RecipientKey::Selected(key.clone()),
+                            );
                         } else {
                             self.gpg_state.sign_keys.clear();
                             self.gpg_state.sign_keys.push(key.clone());
                         }
                     }
                 }
-                self.mode = ViewMode::Edit;
+                self.mode = ViewMode::Edit;
+                self.set_dirty(true);
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            (ViewMode::SelectEncryptKey(_, ref mut selector), _) => {
+                if selector.process_event(event, context) {
+                    return true;
+                }
+            }
+            #[cfg(feature = "gpgme")]
+            (
+                ViewMode::SelectKeyRecipient(ref selector),
+                UIEvent::FinishedUIDialog(id, ref mut result),
+            ) if selector.id() == *id => {
+                self.mode = ViewMode::Edit;
+                if let Some(Some(address)) = result.downcast_mut::<Option<String>>() {
+                    let address = std::mem::take(address);
+                    self.select_encrypt_key(address, context);
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            (ViewMode::SelectKeyRecipient(ref mut selector), _) => {
+                if selector.process_event(event, context) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        if let UIEvent::InsertInput(Key::Char('\t')) = event {
+            if self.cursor == Cursor::Headers
+                && self.mode.is_edit()
+                && self.expand_focused_alias(context)
+            {
+                return true;
+            }
+        }
+        if self.cursor == Cursor::Headers
+            && self.mode.is_edit()
+            && self.form.process_event(event, context)
+        {
+            if let UIEvent::InsertInput(_) = event {
+                self.has_changes = true;
+            }
+            return true;
+        }
+
+        match *event {
+            UIEvent::FinishedUIDialog(id, ref result)
+                if id == self.id && result.downcast_ref::<Option<DownscaleImage>>().is_some() =>
+            {
+                if let Some(Some(DownscaleImage(idx))) =
+                    result.downcast_ref::<Option<DownscaleImage>>()
+                {
+                    self.downscale_attachment(*idx, context);
+                }
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .encrypt_attachment_jobs
+                    .iter()
+                    .any(|(_, h)| h.job_id == *job_id) =>
+            {
+                let pos = self
+                    .encrypt_attachment_jobs
+                    .iter()
+                    .position(|(_, h)| h.job_id == *job_id)
+                    .unwrap();
+                let (idx, mut handle) = self.encrypt_attachment_jobs.remove(pos);
+                match handle
+                    .chan
+                    .try_recv()
+                    .map_err(|_: futures::channel::oneshot::Canceled| {
+                        Error::new("Job was canceled")
+                    }) {
+                    Err(err) | Ok(Some(Err(err))) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not encrypt attachment")),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                    Ok(None) => {}
+                    Ok(Some(Ok(encrypted))) => {
+                        if let Some(a) = self.draft.attachments_mut().get_mut(idx) {
+                            *a = encrypted;
+                            self.has_changes = true;
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(tr!("Encrypted attachment {}.", idx)),
+                            ));
+                        }
+                    }
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .recipient_keys_jobs
+                    .iter()
+                    .any(|(_, h)| h.job_id == *job_id) =>
+            {
+                let pos = self
+                    .recipient_keys_jobs
+                    .iter()
+                    .position(|(_, h)| h.job_id == *job_id)
+                    .unwrap();
+                let (addresses, mut handle) = self.recipient_keys_jobs.remove(pos);
+                match handle
+                    .chan
+                    .try_recv()
+                    .map_err(|_: futures::channel::oneshot::Canceled| {
+                        Error::new("Job was canceled")
+                    }) {
+                    Err(err) | Ok(Some(Err(err))) => {
+                        for address in addresses {
+                            self.gpg_state
+                                .set_recipient_key(&address, gpg::RecipientKey::Missing);
+                        }
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not look up recipient keys")),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                    Ok(None) => {
+                        for address in addresses {
+                            self.gpg_state
+                                .set_recipient_key(&address, gpg::RecipientKey::Missing);
+                        }
+                    }
+                    Ok(Some(Ok(keys))) => {
+                        for (address, key) in keys {
+                            self.gpg_state.set_recipient_key(
+                                &address,
+                                key.map(gpg::RecipientKey::Found)
+                                    .unwrap_or(gpg::RecipientKey::Missing),
+                            );
+                        }
+                    }
+                }
                 self.set_dirty(true);
                 return true;
             }
-            #[cfg(feature = "gpgme")]
-            (ViewMode::SelectEncryptKey(_, ref mut selector), _) => {
-                if selector.process_event(event, context) {
-                    return true;
-                }
-            }
-            _ => {}
-        }
-        if self.cursor == Cursor::Headers
-            && self.mode.is_edit()
-            && self.form.process_event(event, context)
-        {
-            if let UIEvent::InsertInput(_) = event {
-                self.has_changes = true;
-            }
-            return true;
-        }
-
-        match *event {
             UIEvent::ConfigReload { old_settings: _ } => {
                 self.set_dirty(true);
             }
             UIEvent::Resize => {
                 self.set_dirty(true);
             }
+            UIEvent::ChangeMode(UIMode::Normal)
+                if self.cursor == Cursor::Headers && self.mode.is_edit() =>
+            {
+                /* A header field may have been edited. */
+                self.update_draft();
+                if self.apply_auto_recipients(context) {
+                    self.update_form();
+                    self.set_dirty(true);
+                }
+            }
             /*
             /* Switch e-mail From: field to the `left` configured account. */
             UIEvent::Input(Key::Left) if self.cursor == Cursor::From => {
@@ -1450,11 +2434,36 @@ impl Component for Composer {
                 };
                 self.dirty = true;
             }
+            UIEvent::Input(ref key)
+                if self.mode.is_edit()
+                    && self.cursor == Cursor::Headers
+                    && shortcut!(key == shortcuts[Shortcuts::COMPOSING]["pick_contacts"]) =>
+            {
+                /* Pick for the focused address field, or `To` otherwise. */
+                let field = match self.form.focused_field() {
+                    Some(f) if f.eq_ignore_ascii_case("Cc") => "Cc",
+                    Some(f) if f.eq_ignore_ascii_case("Bcc") => "Bcc",
+                    _ => "To",
+                };
+                self.update_draft();
+                self.mode =
+                    ViewMode::PickContacts(ContactPicker::new(field, self.account_hash, context));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if self.mode.is_edit()
+                    && shortcut!(key == shortcuts[Shortcuts::COMPOSING]["browse_attachments"]) =>
+            {
+                self.open_file_browser(context);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Shortcuts::COMPOSING]["send_mail"])
                     && self.mode.is_edit() =>
             {
                 self.update_draft();
+                self.expand_draft_aliases(context);
 
                 {
                     let Self {
@@ -1481,8 +2490,51 @@ impl Component for Composer {
                         ));
                     }
                 }
+                #[cfg(feature = "gpgme")]
+                if self.gpg_state.encrypt_mail.is_true() {
+                    self.lookup_recipient_keys(context);
+                    let missing = self.gpg_state.missing_keys();
+                    let pending = self.gpg_state.pending_keys();
+                    let err = if !missing.is_empty() {
+                        Some(format!(
+                            "No usable encryption key for {}. Select a key for them or disable \
+                             encryption.",
+                            missing.join(", ")
+                        ))
+                    } else if !pending.is_empty() {
+                        Some(tr!(
+                            "Still looking up the encryption keys of {}.",
+                            pending.join(", ")
+                        ))
+                    } else {
+                        None
+                    };
+                    if let Some(err) = err {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not send draft")),
+                            err,
+                            Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                        ));
+                        return true;
+                    }
+                }
+                let warnings = match self.check_outgoing_policies(context) {
+                    Ok(warnings) => warnings,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not send draft")),
+                            err,
+                            Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                        ));
+                        return true;
+                    }
+                };
                 self.mode = ViewMode::Send(UIConfirmationDialog::new(
-                    "send mail?",
+                    &if warnings.is_empty() {
+                        "send mail?".to_string()
+                    } else {
+                        format!("{} send mail?", warnings.join(" "))
+                    },
                     vec![(true, "yes".to_string()), (false, "no".to_string())],
                     /* only one choice */
                     true,
@@ -1517,10 +2569,7 @@ impl Component for Composer {
                                 if exit_code != 0 {
                                     context.replies.push_back(UIEvent::Notification(
                                         None,
-                                        format!(
-                                            "Subprocess has exited with exit code {}",
-                                            exit_code
-                                        ),
+                                        tr!("Subprocess has exited with exit code {}", exit_code),
                                         Some(NotificationType::Error(
                                             melib::error::ErrorKind::External,
                                         )),
@@ -1579,7 +2628,7 @@ impl Component for Composer {
                                 drop(embed_guard);
                                 context.replies.push_back(UIEvent::Notification(
                                     None,
-                                    format!("Subprocess was killed by {} signal", signal),
+                                    tr!("Subprocess was killed by {} signal", signal),
                                     Some(NotificationType::Error(
                                         melib::error::ErrorKind::External,
                                     )),
@@ -1593,8 +2642,8 @@ impl Component for Composer {
                             }
                             Err(err) => {
                                 context.replies.push_back(UIEvent::Notification(
-                                    Some("Embed editor crashed.".to_string()),
-                                    format!("Subprocess has exited with reason {}", &err),
+                                    Some(tr!("Embed editor crashed.")),
+                                    tr!("Subprocess has exited with reason {}", &err),
                                     Some(NotificationType::Error(
                                         melib::error::ErrorKind::External,
                                     )),
@@ -1640,11 +2689,11 @@ impl Component for Composer {
                 }) {
                     Ok(widget) => {
                         self.gpg_state.sign_mail = ToggleFlag::from(true);
-                        self.mode = ViewMode::SelectEncryptKey(false, widget);
+                        self.mode = ViewMode::SelectEncryptKey(None, widget);
                     }
                     Err(err) => {
                         context.replies.push_back(UIEvent::Notification(
-                            Some("Could not list keys.".to_string()),
+                            Some(tr!("Could not list keys.")),
                             format!("libgpgme error: {}", &err),
                             Some(NotificationType::Error(melib::error::ErrorKind::External)),
                         ));
@@ -1659,37 +2708,7 @@ impl Component for Composer {
                     && shortcut!(key == shortcuts[Shortcuts::COMPOSING]["edit_mail"]) =>
             {
                 #[cfg(feature = "gpgme")]
-                match melib::email::parser::address::rfc2822address_list(
-                    self.form.values()["To"].as_str().as_bytes(),
-                )
-                .map_err(|_err| -> Error { "No valid recipient addresses in `To:`".into() })
-                .and_then(|(_, list)| {
-                    list.get(0)
-                        .cloned()
-                        .ok_or_else(|| "No valid recipient addresses in `To:`".into())
-                })
-                .and_then(|addr| {
-                    gpg::KeySelection::new(
-                        false,
-                        account_settings!(context[self.account_hash].pgp.allow_remote_lookup)
-                            .is_true(),
-                        addr.get_email(),
-                        *account_settings!(context[self.account_hash].pgp.allow_remote_lookup),
-                        context,
-                    )
-                }) {
-                    Ok(widget) => {
-                        self.gpg_state.encrypt_mail = ToggleFlag::from(true);
-                        self.mode = ViewMode::SelectEncryptKey(true, widget);
-                    }
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::Notification(
-                            Some("Could not list keys.".to_string()),
-                            format!("libgpgme error: {}", &err),
-                            Some(NotificationType::Error(melib::error::ErrorKind::External)),
-                        ));
-                    }
-                }
+                self.select_recipient_key(context);
                 self.set_dirty(true);
                 return true;
             }
@@ -1738,7 +2757,7 @@ impl Component for Composer {
                 }
                 context.replies.push_back(UIEvent::Notification(
                     None,
-                    "Subprocess was killed by SIGTERM signal".to_string(),
+                    tr!("Subprocess was killed by SIGTERM signal"),
                     Some(NotificationType::Error(melib::error::ErrorKind::External)),
                 ));
                 self.initialized = false;
@@ -1753,118 +2772,7 @@ impl Component for Composer {
                 if self.mode.is_edit()
                     && shortcut!(key == shortcuts[Shortcuts::COMPOSING]["edit_mail"]) =>
             {
-                /* Edit draft in $EDITOR */
-                let editor = if let Some(editor_command) =
-                    account_settings!(context[self.account_hash].composing.editor_command).as_ref()
-                {
-                    editor_command.to_string()
-                } else {
-                    match std::env::var("EDITOR") {
-                        Err(err) => {
-                            context.replies.push_back(UIEvent::Notification(
-                                Some(err.to_string()),
-                                "$EDITOR is not set. You can change an envvar's value with setenv \
-                                 or set composing.editor_command setting in your configuration."
-                                    .to_string(),
-                                Some(NotificationType::Error(melib::error::ErrorKind::None)),
-                            ));
-                            return true;
-                        }
-                        Ok(v) => v,
-                    }
-                };
-                /* update Draft's headers based on form values */
-                self.update_draft();
-                self.draft.set_wrap_header_preamble(
-                    account_settings!(context[self.account_hash].composing.wrap_header_preamble)
-                        .clone(),
-                );
-
-                let f = create_temp_file(
-                    self.draft.to_edit_string().as_str().as_bytes(),
-                    None,
-                    None,
-                    true,
-                );
-
-                if *account_settings!(context[self.account_hash].composing.embed) {
-                    match crate::terminal::embed::create_pty(
-                        width!(self.embed_area),
-                        height!(self.embed_area),
-                        [editor, f.path().display().to_string()].join(" "),
-                    ) {
-                        Ok(embed) => {
-                            self.embed = Some(EmbedStatus::Running(embed, f));
-                            self.set_dirty(true);
-                            context
-                                .replies
-                                .push_back(UIEvent::ChangeMode(UIMode::Embed));
-                            context.replies.push_back(UIEvent::Fork(ForkType::Embed(
-                                self.embed.as_ref().unwrap().lock().unwrap().child_pid,
-                            )));
-                            self.mode = ViewMode::Embed;
-                        }
-                        Err(err) => {
-                            context.replies.push_back(UIEvent::Notification(
-                                Some(format!("Failed to create pseudoterminal: {}", err)),
-                                err.to_string(),
-                                Some(NotificationType::Error(melib::error::ErrorKind::External)),
-                            ));
-                        }
-                    }
-                    return true;
-                }
-                /* Kill input thread so that spawned command can be sole receiver of stdin */
-                {
-                    context.input_kill();
-                }
-
-                let editor_command = format!("{} {}", editor, f.path().display());
-                log::debug!(
-                    "Executing: sh -c \"{}\"",
-                    editor_command.replace('"', "\\\"")
-                );
-                match Command::new("sh")
-                    .args(["-c", &editor_command])
-                    .stdin(Stdio::inherit())
-                    .stdout(Stdio::inherit())
-                    .spawn()
-                {
-                    Ok(mut child) => {
-                        let _ = child.wait();
-                    }
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::Notification(
-                            Some(format!("Failed to execute {}: {}", editor, err)),
-                            err.to_string(),
-                            Some(NotificationType::Error(melib::error::ErrorKind::External)),
-                        ));
-                        context.replies.push_back(UIEvent::Fork(ForkType::Finished));
-                        context.restore_input();
-                        return true;
-                    }
-                }
-                context.replies.push_back(UIEvent::Fork(ForkType::Finished));
-                let result = f.read_to_string();
-                match self.draft.update(result.as_str()) {
-                    Ok(has_changes) => {
-                        self.has_changes = has_changes;
-                    }
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::Notification(
-                            Some("Could not parse draft headers correctly.".to_string()),
-                            format!(
-                                "{}\nThe invalid text has been set as the body of your draft",
-                                &err
-                            ),
-                            Some(NotificationType::Error(melib::error::ErrorKind::None)),
-                        ));
-                        self.draft.set_body(result);
-                        self.has_changes = true;
-                    }
-                }
-                self.initialized = false;
-                self.set_dirty(true);
+                self.open_editor(false, context);
                 return true;
             }
             UIEvent::Action(ref a) => match a {
@@ -1888,7 +2796,7 @@ impl Component for Composer {
                         Ok(stderr) => {
                             if !stderr.is_empty() {
                                 context.replies.push_back(UIEvent::StatusEvent(
-                                    StatusEvent::DisplayMessage(format!(
+                                    StatusEvent::DisplayMessage(tr!(
                                         "Command stderr output: `{}`.",
                                         String::from_utf8_lossy(&stderr)
                                     )),
@@ -1909,8 +2817,7 @@ impl Component for Composer {
                                         return true;
                                     }
                                 };
-                            self.draft.attachments_mut().push(attachment);
-                            self.has_changes = true;
+                            self.add_attachment(attachment, context);
                             self.set_dirty(true);
                             return true;
                         }
@@ -1937,8 +2844,7 @@ impl Component for Composer {
                             return true;
                         }
                     };
-                    self.draft.attachments_mut().push(attachment);
-                    self.has_changes = true;
+                    self.add_attachment(attachment, context);
                     self.set_dirty(true);
                     return true;
                 }
@@ -1950,11 +2856,8 @@ impl Component for Composer {
                     {
                         cmd.as_str()
                     } else {
-                        context.replies.push_back(UIEvent::Notification(
-                            None,
-                            "You haven't defined any command to launch.".into(),
-                            Some(NotificationType::Error(melib::error::ErrorKind::None)),
-                        ));
+                        /* Without a command, use the built-in file browser. */
+                        self.open_file_browser(context);
                         return true;
                     };
                     /* Kill input thread so that spawned command can be sole receiver of stdin */
@@ -1978,8 +2881,7 @@ impl Component for Composer {
                                     &String::from_utf8_lossy(path).as_ref(),
                                 ) {
                                     Ok(a) => {
-                                        self.draft.attachments_mut().push(a);
-                                        self.has_changes = true;
+                                        self.add_attachment(a, context);
                                     }
                                     Err(err) => {
                                         context.replies.push_back(UIEvent::Notification(
@@ -1999,7 +2901,7 @@ impl Component for Composer {
                         Err(err) => {
                             let command = command.to_string();
                             context.replies.push_back(UIEvent::Notification(
-                                Some(format!("Failed to execute {}: {}", command, err)),
+                                Some(tr!("Failed to execute {}: {}", command, err)),
                                 err.to_string(),
                                 Some(NotificationType::Error(melib::error::ErrorKind::External)),
                             ));
@@ -2040,6 +2942,54 @@ impl Component for Composer {
                     );
                     return true;
                 }
+                Action::Compose(ComposeAction::EditHeaders) => {
+                    if self.mode.is_edit() && self.embed.is_none() {
+                        self.open_editor(true, context);
+                    }
+                    return true;
+                }
+                Action::Compose(ComposeAction::LintDraft) => {
+                    self.update_draft();
+                    let messages = match self.draft.clone().finalise() {
+                        Ok(message) => melib::email::compose::lint::lint(message.as_bytes()),
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some("Could not finalise draft".into()),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                            return true;
+                        }
+                    };
+                    if messages.is_empty() {
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                                "lint-draft: no problems found".to_string(),
+                            )));
+                    } else {
+                        let is_error = messages.iter().any(|m| m.is_error());
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(format!("lint-draft: {} problem(s) found", messages.len())),
+                            messages
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<String>>()
+                                .join("\n"),
+                            Some(if is_error {
+                                NotificationType::Error(melib::error::ErrorKind::None)
+                            } else {
+                                NotificationType::Info
+                            }),
+                        ));
+                    }
+                    return true;
+                }
+                #[cfg(feature = "gpgme")]
+                Action::Compose(ComposeAction::EncryptAttachment(idx, symmetric)) => {
+                    self.encrypt_attachment(*idx, *symmetric, context);
+                    return true;
+                }
                 #[cfg(feature = "gpgme")]
                 Action::Compose(ComposeAction::ToggleSign) => {
                     let is_true = self.gpg_state.sign_mail.is_true();
@@ -2054,6 +3004,35 @@ impl Component for Composer {
                     self.set_dirty(true);
                     return true;
                 }
+                Action::Compose(ComposeAction::ToggleSmimeSign) => {
+                    let is_true = self.smime_sign.is_true();
+                    self.smime_sign = ToggleFlag::from(!is_true);
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            if is_true {
+                                "S/MIME signing disabled."
+                            } else {
+                                "S/MIME signing enabled."
+                            }
+                            .to_string(),
+                        )));
+                    self.set_dirty(true);
+                    return true;
+                }
+                Action::Compose(ComposeAction::SendAt(send_at)) => {
+                    self.schedule_send(*send_at, context);
+                    return true;
+                }
+                Action::Compose(ComposeAction::ToggleSignature) => {
+                    if self.signature.is_true() {
+                        self.remove_signature(context);
+                    } else {
+                        self.add_signature(context);
+                    }
+                    self.set_dirty(true);
+                    return true;
+                }
                 _ => {}
             },
             _ => {}
@@ -2072,10 +3051,20 @@ impl Component for Composer {
             ViewMode::SelectRecipients(ref widget) => {
                 widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
             }
+            ViewMode::PickContacts(ref widget) => {
+                widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
+            }
+            ViewMode::BrowseAttachments(ref widget) => {
+                widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
+            }
             #[cfg(feature = "gpgme")]
             ViewMode::SelectEncryptKey(_, ref widget) => {
                 widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
             }
+            #[cfg(feature = "gpgme")]
+            ViewMode::SelectKeyRecipient(ref widget) => {
+                widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
+            }
             ViewMode::Send(ref widget) => {
                 widget.is_dirty() || self.pager.is_dirty() || self.form.is_dirty()
             }
@@ -2241,7 +3230,7 @@ pub fn send_draft(
                     context.accounts[&account_hash].name(),
             );
             context.replies.push_back(UIEvent::Notification(
-                Some(format!(
+                Some(tr!(
                     "Could not sign draft in account `{}`.",
                     context.accounts[&account_hash].name()
                 )),
@@ -2306,7 +3295,7 @@ pub fn save_draft(
         Ok(mailbox_hash) => {
             context.replies.push_back(UIEvent::Notification(
                 Some("Message saved".into()),
-                format!(
+                tr!(
                     "Message saved in `{}`",
                     &context.accounts[&account_hash].mailbox_entries[&mailbox_hash].name
                 ),
@@ -2318,6 +3307,7 @@ pub fn save_draft(
 
 pub fn send_draft_async(
     #[cfg(feature = "gpgme")] gpg_state: gpg::GpgComposeState,
+    smime_sign: bool,
     context: &mut Context,
     account_hash: AccountHash,
     mut draft: Draft,
@@ -2339,11 +3329,54 @@ pub fn send_draft_async(
         >,
     > = vec![];
     #[cfg(feature = "gpgme")]
+    let public_key = if *account_settings!(context[account_hash].pgp.attach_public_key)
+        && !draft.attachments.iter().any(|a| {
+            a.content_type()
+                .to_string()
+                .eq_ignore_ascii_case("application/pgp-keys")
+        }) {
+        let pattern = gpg_state
+            .sign_keys
+            .first()
+            .map(|k| k.fingerprint().to_string())
+            .or_else(|| account_settings!(context[account_hash].pgp.sign_key).clone())
+            .or_else(|| {
+                draft
+                    .headers()
+                    .get("From")
+                    .and_then(|v| {
+                        melib::email::parser::address::rfc2822address_list(v.as_bytes()).ok()
+                    })
+                    .and_then(|(_, list)| list.first().map(Address::get_email))
+            });
+        pattern.map(crate::components::mail::pgp::public_key_attachment)
+    } else {
+        None
+    };
+    #[cfg(feature = "gpgme")]
     if gpg_state.sign_mail.is_true() && !gpg_state.encrypt_mail.is_true() {
         filters_stack.push(Box::new(crate::components::mail::pgp::sign_filter(
             gpg_state.sign_keys,
         )?));
     } else if gpg_state.encrypt_mail.is_true() {
+        /* Move the sensitive headers into the encrypted part and hide the
+         * subject from the outer headers. */
+        let protected_headers = if *account_settings!(context[account_hash].pgp.protected_headers) {
+            let headers = melib::pgp::PROTECTED_HEADERS
+                .iter()
+                .filter_map(|name| {
+                    draft
+                        .headers()
+                        .get(name)
+                        .filter(|v| !v.is_empty())
+                        .map(|v| (*name, v.to_string()))
+                })
+                .collect::<Vec<(&'static str, String)>>();
+            draft.set_header("Subject", melib::pgp::OBSCURED_SUBJECT.to_string());
+            headers
+        } else {
+            vec![]
+        };
         filters_stack.push(Box::new(crate::components::mail::pgp::encrypt_filter(
             if gpg_state.sign_mail.is_true() {
                 Some(gpg_state.sign_keys.clone())
@@ -2351,8 +3384,18 @@ pub fn send_draft_async(
                 None
             },
             gpg_state.encrypt_keys,
+            protected_headers,
         )?));
     }
+    let smime_sign_filter = if smime_sign {
+        Some(crate::components::mail::smime::sign_filter(
+            account_settings!(context[account_hash].smime.openssl_binary).clone(),
+            account_settings!(context[account_hash].smime.certificate).clone(),
+            account_settings!(context[account_hash].smime.private_key).clone(),
+        )?)
+    } else {
+        None
+    };
     let send_mail = account_settings!(context[account_hash].composing.send_mail).clone();
     let send_cb = context.accounts[&account_hash].send_async(send_mail);
     let mut content_type = ContentType::default();
@@ -2370,27 +3413,35 @@ pub fn send_draft_async(
         std::mem::take(&mut draft.body).into_bytes(),
     )
     .into();
-    if !draft.attachments.is_empty() {
-        let mut parts = std::mem::take(&mut draft.attachments);
-        parts.insert(0, body);
-        let boundary = ContentType::make_boundary(&parts);
-        body = Attachment::new(
-            ContentType::Multipart {
-                boundary: boundary.into_bytes(),
-                kind: MultipartType::Mixed,
-                parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
-                parameters: vec![],
-            },
-            Default::default(),
-            vec![],
-        )
-        .into();
-    }
     Ok(Box::pin(async move {
+        /* The key is attached before the message is signed or encrypted. */
+        #[cfg(feature = "gpgme")]
+        if let Some(public_key) = public_key {
+            draft.attachments.push(public_key.await?);
+        }
+        if !draft.attachments.is_empty() {
+            let mut parts = std::mem::take(&mut draft.attachments);
+            parts.insert(0, body);
+            let boundary = ContentType::make_boundary(&parts);
+            body = Attachment::new(
+                ContentType::Multipart {
+                    boundary: boundary.into_bytes(),
+                    kind: MultipartType::Mixed,
+                    parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
+                    parameters: vec![],
+                },
+                Default::default(),
+                vec![],
+            )
+            .into();
+        }
         #[cfg(feature = "gpgme")]
         for f in filters_stack {
             body = f(body).await?;
         }
+        if let Some(f) = smime_sign_filter {
+            body = f(body).await?;
+        }
 
         draft.attachments.insert(0, body);
         let message = Arc::new(draft.finalise()?);
@@ -2430,9 +3481,11 @@ pub fn send_draft_async(
 }
 
 /* Sender details
- * %+f — the sender's name and email address.
- * %+n — the sender's name (or email address, if no name is included).
- * %+a — the sender's email address.
+ * %+f or %{from} — the sender's name and email address.
+ * %+n or %{name} — the sender's name (or email address, if no name is
+ * included).
+ * %+a or %{email} — the sender's email address.
+ * %{date} — the date, as `%a, %0e %b %Y %H:%M`.
  */
 fn attribution_string(
     fmt: Option<&str>,
@@ -2440,7 +3493,12 @@ fn attribution_string(
     date: UnixTimestamp,
     posix: bool,
 ) -> String {
-    let fmt = fmt.unwrap_or("On %a, %0e %b %Y %H:%M, %+f wrote:%n");
+    let fmt = fmt
+        .unwrap_or("On %{date}, %{from} wrote:%n")
+        .replace("%{date}", "%a, %0e %b %Y %H:%M")
+        .replace("%{from}", "%+f")
+        .replace("%{name}", "%+n")
+        .replace("%{email}", "%+a");
     let fmt = fmt.replace(
         "%+f",
         &sender
@@ -2462,10 +3520,52 @@ fn attribution_string(
     melib::datetime::timestamp_to_string(date, Some(fmt.as_str()), posix)
 }
 
+/// Quotes `text` for a reply: lines get a `> ` prefix, or only `>` if they
+/// are empty or quoted already.
+fn quote(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len() + text.len() / 8);
+    for line in text.lines() {
+        ret.push('>');
+        if !line.is_empty() && !line.starts_with('>') {
+            ret.push(' ');
+        }
+        ret.push_str(line);
+        ret.push('\n');
+    }
+    ret
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compose_reply_quote() {
+        assert_eq!(
+            quote("hello\n\n> earlier\nworld"),
+            "> hello\n>\n>> earlier\n> world\n"
+        );
+        let sender = Address::new(Some("Alice".to_string()), "alice@example.com".to_string());
+        /* Noon, so that the date is the same in every timezone. */
+        let date = 12 * 60 * 60;
+        assert_eq!(
+            attribution_string(
+                Some("%{name} (%{email}) wrote in %Y:"),
+                Some(&sender),
+                date,
+                true
+            ),
+            "Alice (alice@example.com) wrote in 1970:"
+        );
+        let attribution = attribution_string(None, Some(&sender), date, true);
+        assert!(attribution.starts_with("On Thu, "), "{}", attribution);
+        assert!(
+            attribution.ends_with("<alice@example.com> wrote:\n"),
+            "{}",
+            attribution
+        );
+    }
+
     #[test]
     fn test_compose_reply_subject_prefix() {
         let raw_mail = r#"From: "some name" <some@example.com>
@@ -2490,9 +3590,9 @@ hello world.
             .insert(envelope, mailbox_hash);
         let composer = Composer::reply_to(
             (account_hash, mailbox_hash, envelope_hash),
-            String::new(),
+            Some(String::new()),
             &mut context,
-            false,
+            ReplyKind::Author,
         );
         assert_eq!(&composer.draft.headers()["Subject"], "RE: your e-mail");
         assert_eq!(
@@ -2516,9 +3616,9 @@ hello world.
             .insert(envelope, mailbox_hash);
         let composer = Composer::reply_to(
             (account_hash, mailbox_hash, envelope_hash),
-            String::new(),
+            Some(String::new()),
             &mut context,
-            false,
+            ReplyKind::Author,
         );
         assert_eq!(&composer.draft.headers()["Subject"], "Re: your e-mail");
         assert_eq!(
@@ -0,0 +1,119 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Expansion of address aliases in the `To`, `Cc` and `Bcc` fields.
+
+use super::*;
+
+/// Returns the expansion of `alias`: the `aliases` composing setting of the
+/// account, or else the address of the address book contact with that
+/// nickname.
+pub fn resolve_alias(context: &Context, account_hash: AccountHash, alias: &str) -> Option<String> {
+    if let Some((_, expansion)) = account_settings!(context[account_hash].composing.aliases)
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(alias))
+    {
+        return Some(expansion.clone());
+    }
+    context.accounts[&account_hash]
+        .address_book
+        .values()
+        .filter(|card| !card.email().is_empty())
+        .find(|card| {
+            card.title().eq_ignore_ascii_case(alias)
+                || card.additionalname().eq_ignore_ascii_case(alias)
+        })
+        .map(|card| {
+            Address::new(
+                Some(card.name().to_string()).filter(|n| !n.is_empty()),
+                card.email().to_string(),
+            )
+            .to_string()
+        })
+}
+
+/// Whether `entry` of an address list can be an alias: a single word that
+/// isn't an address.
+fn is_alias_candidate(entry: &str) -> bool {
+    !entry.is_empty()
+        && !entry
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '@' | '<' | '>' | '"' | ':' | ';'))
+}
+
+/// Returns `value`, a comma separated list of addresses, with the entries
+/// that are aliases replaced by their expansion. If `last_only` is true only
+/// the last entry is expanded.
+pub fn expand_aliases(
+    value: &str,
+    last_only: bool,
+    resolve: impl Fn(&str) -> Option<String>,
+) -> String {
+    let entries = value.split(',').collect::<Vec<&str>>();
+    let last = entries.len() - 1;
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let trimmed = entry.trim();
+            if (last_only && i != last) || !is_alias_candidate(trimmed) {
+                return entry.to_string();
+            }
+            match resolve(trimmed) {
+                Some(expansion) => entry.replacen(trimmed, &expansion, 1),
+                None => entry.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_aliases() {
+        let resolve = |alias: &str| match alias {
+            "boss" => Some("Jane Doe <jane@corp.example>".to_string()),
+            "team" => Some("a@corp.example, b@corp.example".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_aliases("", false, resolve), "");
+        assert_eq!(
+            expand_aliases("boss", false, resolve),
+            "Jane Doe <jane@corp.example>"
+        );
+        assert_eq!(
+            expand_aliases("x@example.com, boss,team, nobody", false, resolve),
+            "x@example.com, Jane Doe <jane@corp.example>,a@corp.example, b@corp.example, nobody"
+        );
+        assert_eq!(
+            expand_aliases("boss, team", true, resolve),
+            "boss, a@corp.example, b@corp.example"
+        );
+        /* Display names with commas are left alone. */
+        assert_eq!(
+            expand_aliases("\"boss, the\" <boss@example.com>", false, resolve),
+            "\"boss, the\" <boss@example.com>"
+        );
+    }
+}
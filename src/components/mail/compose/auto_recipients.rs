@@ -0,0 +1,120 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Cc/Bcc recipients added by the `auto_recipients` composing rules.
+
+use melib::text_processing::GlobMatch;
+
+use super::{reply::contains_address, *};
+use crate::conf::composing::AutoRecipientRule;
+
+pub fn parse_addresses(value: &str) -> Vec<Address> {
+    melib::email::parser::address::rfc2822address_list(value.as_bytes())
+        .map(|(_, list)| list.into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Returns the recipients that `rules` add to a draft with the given `To`,
+/// `Cc` and `Bcc` header values, as `("Cc" | "Bcc", address)` pairs.
+/// Addresses that already are recipients of the draft are skipped.
+pub fn auto_recipients(
+    rules: &[AutoRecipientRule],
+    to: &str,
+    cc: &str,
+    bcc: &str,
+) -> Vec<(&'static str, Address)> {
+    let to = parse_addresses(to);
+    let mut existing: Vec<Address> = to
+        .iter()
+        .cloned()
+        .chain(parse_addresses(cc))
+        .chain(parse_addresses(bcc))
+        .collect();
+    let mut ret = vec![];
+    for rule in rules {
+        let pattern = rule.to.to_lowercase();
+        if !to
+            .iter()
+            .any(|a| a.get_email().to_lowercase().matches_glob(&pattern))
+        {
+            continue;
+        }
+        for (header, values) in [("Cc", &rule.cc), ("Bcc", &rule.bcc)] {
+            for address in values.iter().flat_map(|v| parse_addresses(v)) {
+                if contains_address(&existing, &address) {
+                    continue;
+                }
+                existing.push(address.clone());
+                ret.push((header, address));
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_recipients() {
+        let rules = vec![
+            AutoRecipientRule {
+                to: "*@customer.example.com".to_string(),
+                cc: vec!["Team <team@example.com>".to_string()],
+                bcc: vec!["archive@example.com".to_string()],
+            },
+            AutoRecipientRule {
+                to: "boss@example.com".to_string(),
+                cc: vec!["team@example.com".to_string()],
+                bcc: vec![],
+            },
+        ];
+        let emails = |list: Vec<(&'static str, Address)>| {
+            list.into_iter()
+                .map(|(h, a)| format!("{}: {}", h, a.get_email()))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            emails(auto_recipients(&rules, "other@example.com", "", "")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            emails(auto_recipients(
+                &rules,
+                "Jane <Jane@Customer.example.com>, boss@example.com",
+                "",
+                ""
+            )),
+            vec!["Cc: team@example.com", "Bcc: archive@example.com"]
+        );
+        /* Existing recipients aren't added again. */
+        assert_eq!(
+            emails(auto_recipients(
+                &rules,
+                "jane@customer.example.com",
+                "team@example.com",
+                ""
+            )),
+            vec!["Bcc: archive@example.com"]
+        );
+    }
+}
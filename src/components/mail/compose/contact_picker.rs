@@ -0,0 +1,424 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Full-screen picker of recipients for the `To`, `Cc` and `Bcc` fields of
+//! the composer.
+
+use melib::addressbook::Card;
+
+use super::{auto_recipients::parse_addresses, reply::contains_address, *};
+
+/// How many distinct recipients of sent mail are offered after the address
+/// book contacts.
+const MAX_RECENT: usize = 100;
+
+#[derive(Debug)]
+struct PickerEntry {
+    address: Address,
+    display: String,
+    /// Whether the address comes from sent mail and not from the address
+    /// book.
+    recent: bool,
+    selected: bool,
+}
+
+/// Returns the addresses offered by the picker: the address book contacts
+/// sorted by name, followed by the `recent` recipients of sent mail that are
+/// neither in the address book nor one of `own`. The boolean is true for the
+/// latter.
+pub fn contact_entries<'a>(
+    cards: impl IntoIterator<Item = &'a Card>,
+    recent: impl IntoIterator<Item = Address>,
+    own: &[Address],
+) -> Vec<(Address, bool)> {
+    let mut contacts = cards
+        .into_iter()
+        .filter(|card| !card.email().trim().is_empty())
+        .map(|card| {
+            let name = card.name().trim();
+            Address::new(
+                if name.is_empty() {
+                    None
+                } else {
+                    Some(name.to_string())
+                },
+                card.email().trim().to_string(),
+            )
+        })
+        .collect::<Vec<Address>>();
+    contacts.sort_by_cached_key(|a| a.to_string().to_lowercase());
+    let mut seen = contacts
+        .iter()
+        .chain(own.iter())
+        .map(|a| a.get_email().to_lowercase())
+        .collect::<HashSet<String>>();
+    let mut ret = contacts
+        .into_iter()
+        .map(|a| (a, false))
+        .collect::<Vec<(Address, bool)>>();
+    let mut recent_count = 0;
+    for address in recent {
+        if recent_count == MAX_RECENT {
+            break;
+        }
+        let email = address.get_email().to_lowercase();
+        if email.is_empty() || !seen.insert(email) {
+            continue;
+        }
+        ret.push((address, true));
+        recent_count += 1;
+    }
+    ret
+}
+
+/// Appends `picked` to the header value `current`, skipping the addresses
+/// it already contains.
+pub fn append_addresses(current: &str, picked: &[Address]) -> String {
+    let mut existing = parse_addresses(current);
+    let mut ret = current.trim().trim_end_matches(',').trim_end().to_string();
+    for address in picked {
+        if contains_address(&existing, address) {
+            continue;
+        }
+        if !ret.is_empty() {
+            ret.push_str(", ");
+        }
+        ret.push_str(&address.to_string());
+        existing.push(address.clone());
+    }
+    ret
+}
+
+/// Overlay that lists the address book contacts and recent recipients of
+/// an account.
+///
+/// Typing filters the list with [`fuzzy_match`], Space toggles the entry
+/// under the cursor and Enter finishes with a `Vec<Address>` of the selected
+/// entries, or of the entry under the cursor if none is selected.
+#[derive(Debug)]
+pub struct ContactPicker {
+    field: &'static str,
+    entries: Vec<PickerEntry>,
+    filter: String,
+    results: Vec<usize>,
+    cursor: usize,
+    theme_default: ThemeAttribute,
+    highlighted_attrs: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for ContactPicker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.field)
+    }
+}
+
+impl ContactPicker {
+    pub fn new(field: &'static str, account_hash: AccountHash, context: &Context) -> Self {
+        let account = &context.accounts[&account_hash];
+        let mut recent = vec![];
+        if let Some(sent) = account.special_use_mailbox(SpecialUsageMailbox::Sent) {
+            let envelopes = account.collection.envelopes.read().unwrap();
+            let mailboxes = account.collection.mailboxes.read().unwrap();
+            if let Some(hashes) = mailboxes.get(&sent) {
+                let mut sent_envelopes = hashes
+                    .iter()
+                    .filter_map(|h| envelopes.get(h))
+                    .collect::<Vec<&Envelope>>();
+                sent_envelopes.sort_by_key(|e| std::cmp::Reverse(e.date()));
+                for envelope in sent_envelopes {
+                    recent.extend(envelope.to().iter().chain(envelope.cc().iter()).cloned());
+                }
+            }
+        }
+        let own = reply::own_addresses(&account.settings.account);
+        let entries = contact_entries(account.address_book.values(), recent, &own)
+            .into_iter()
+            .map(|(address, recent)| PickerEntry {
+                display: address.to_string(),
+                address,
+                recent,
+                selected: false,
+            })
+            .collect();
+        let mut ret = ContactPicker {
+            field,
+            entries,
+            filter: String::new(),
+            results: vec![],
+            cursor: 0,
+            theme_default: Default::default(),
+            highlighted_attrs: Default::default(),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.initialise(context);
+        ret.update_results();
+        ret
+    }
+
+    /// The header the picked addresses are added to.
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+
+    fn initialise(&mut self, context: &Context) {
+        self.theme_default = crate::conf::value(context, "theme_default");
+        self.highlighted_attrs = crate::conf::value(context, "widgets.options.highlighted");
+        if !context.settings.terminal.use_color() {
+            self.highlighted_attrs.attrs |= Attr::REVERSE;
+        }
+    }
+
+    fn update_results(&mut self) {
+        let mut results: Vec<(isize, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| fuzzy_match(&self.filter, &e.display).map(|score| (score, i)))
+            .collect();
+        /* Stable sort, so that ties keep the address book contacts first. */
+        results.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.results = results.into_iter().map(|(_, i)| i).collect();
+        self.cursor = 0;
+        self.dirty = true;
+    }
+}
+
+impl Component for ContactPicker {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        let inner_area = create_box(grid, area);
+        clear_area(grid, inner_area, self.theme_default);
+        let selected = self.entries.iter().filter(|e| e.selected).count();
+        write_string_to_grid(
+            &format!(
+                "Add to {}: {} selected (Space: select, Enter: add, Esc: cancel)",
+                self.field, selected
+            ),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        let upper_left = upper_left!(inner_area);
+        let bottom_right = bottom_right!(inner_area);
+        write_string_to_grid(
+            &format!("> {}", self.filter),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            (upper_left, set_y(bottom_right, get_y(upper_left))),
+            None,
+        );
+        let rows = height!(inner_area).saturating_sub(1);
+        if self.results.is_empty() {
+            write_string_to_grid(
+                "No matching contacts.",
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs | Attr::ITALICS,
+                (pos_inc(upper_left, (0, 1)), bottom_right),
+                None,
+            );
+        } else if rows > 0 {
+            let top = self.cursor - self.cursor % rows;
+            for (i, idx) in self.results.iter().skip(top).take(rows).enumerate() {
+                let entry = &self.entries[*idx];
+                let y = get_y(upper_left) + 1 + i;
+                let row_area = (set_y(upper_left, y), set_y(bottom_right, y));
+                let attrs = if top + i == self.cursor {
+                    self.highlighted_attrs
+                } else {
+                    self.theme_default
+                };
+                clear_area(grid, row_area, attrs);
+                let (x, _) = write_string_to_grid(
+                    &format!(
+                        "[{}] {}",
+                        if entry.selected { 'x' } else { ' ' },
+                        entry.display
+                    ),
+                    grid,
+                    attrs.fg,
+                    attrs.bg,
+                    attrs.attrs,
+                    row_area,
+                    None,
+                );
+                if entry.recent {
+                    write_string_to_grid(
+                        " (recent)",
+                        grid,
+                        attrs.fg,
+                        attrs.bg,
+                        attrs.attrs | Attr::ITALICS,
+                        ((x, y), bottom_right!(row_area)),
+                        None,
+                    );
+                }
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.initialise(context);
+                self.set_dirty(true);
+                false
+            }
+            UIEvent::Input(Key::Esc) => {
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                true
+            }
+            UIEvent::Input(Key::Char('\n')) => {
+                let mut picked = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.selected)
+                    .map(|e| e.address.clone())
+                    .collect::<Vec<Address>>();
+                if picked.is_empty() {
+                    picked.extend(
+                        self.results
+                            .get(self.cursor)
+                            .map(|i| self.entries[*i].address.clone()),
+                    );
+                }
+                if !picked.is_empty() {
+                    context
+                        .replies
+                        .push_back(UIEvent::FinishedUIDialog(self.id, Box::new(picked)));
+                }
+                true
+            }
+            UIEvent::Input(Key::Char(' ')) => {
+                if let Some(i) = self.results.get(self.cursor) {
+                    self.entries[*i].selected = !self.entries[*i].selected;
+                    if self.cursor + 1 < self.results.len() {
+                        self.cursor += 1;
+                    }
+                    self.set_dirty(true);
+                }
+                true
+            }
+            UIEvent::Input(Key::Up) | UIEvent::Input(Key::Ctrl('p')) => {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Down) | UIEvent::Input(Key::Ctrl('n')) => {
+                if self.cursor + 1 < self.results.len() {
+                    self.cursor += 1;
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Backspace) => {
+                self.filter.pop();
+                self.update_results();
+                true
+            }
+            UIEvent::Input(Key::Char(c)) => {
+                self.filter.push(*c);
+                self.update_results();
+                true
+            }
+            /* The picker is modal; swallow any other input. */
+            UIEvent::Input(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_picker_entries() {
+        let mut alice = Card::new();
+        alice
+            .set_name("Alice".to_string())
+            .set_email("alice@example.com".to_string());
+        let mut bob = Card::new();
+        bob.set_email("bob@example.com".to_string());
+        let nobody = Card::new();
+        let own = [Address::new(None, "me@example.com".to_string())];
+        let recent = vec![
+            Address::new(None, "Alice@Example.com".to_string()),
+            Address::new(None, "me@example.com".to_string()),
+            Address::new(Some("Carol".to_string()), "carol@example.com".to_string()),
+            Address::new(None, "carol@example.com".to_string()),
+        ];
+        let entries = contact_entries([&bob, &nobody, &alice], recent, &own)
+            .into_iter()
+            .map(|(a, recent)| (a.to_string(), recent))
+            .collect::<Vec<(String, bool)>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("Alice <alice@example.com>".to_string(), false),
+                ("bob@example.com".to_string(), false),
+                ("Carol <carol@example.com>".to_string(), true),
+            ]
+        );
+
+        let picked = [
+            Address::new(Some("Alice".to_string()), "alice@example.com".to_string()),
+            Address::new(None, "bob@example.com".to_string()),
+        ];
+        assert_eq!(
+            append_addresses("", &picked),
+            "Alice <alice@example.com>, bob@example.com"
+        );
+        assert_eq!(
+            append_addresses("BOB@example.com, ", &picked),
+            "BOB@example.com, Alice <alice@example.com>"
+        );
+    }
+}
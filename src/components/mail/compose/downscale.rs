@@ -0,0 +1,151 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Downscaling of large image attachments, see the `downscale_images`
+//! composing setting.
+
+use super::*;
+
+/// Result of the confirmation dialog for downscaling the attachment at this
+/// index.
+#[derive(Debug, Clone, Copy)]
+pub struct DownscaleImage(pub usize);
+
+/// Whether `attachment` is an image larger than `threshold` bytes.
+pub fn needs_downscaling(attachment: &AttachmentBuilder, threshold: usize) -> bool {
+    match attachment.content_type() {
+        ContentType::Other { ref tag, .. } => {
+            tag.len() > b"image/".len()
+                && tag[..b"image/".len()].eq_ignore_ascii_case(b"image/")
+                && attachment.raw().len() > threshold
+        }
+        _ => false,
+    }
+}
+
+/// Returns the MIME type of `image` if it's a JPEG or PNG.
+fn image_type(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else {
+        None
+    }
+}
+
+/// Replaces the extension of `filename` with the one of `mime_type`.
+fn filename_for(filename: &str, mime_type: &str) -> String {
+    let ext = match mime_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        _ => return filename.to_string(),
+    };
+    match filename.rsplit_once('.') {
+        Some((_, old)) if old.eq_ignore_ascii_case(ext) => filename.to_string(),
+        Some((_, old)) if ext == "jpg" && old.eq_ignore_ascii_case("jpeg") => filename.to_string(),
+        Some((stem, _)) if !stem.is_empty() => format!("{}.{}", stem, ext),
+        _ => format!("{}.{}", filename, ext),
+    }
+}
+
+/// Pipes the image in `attachment` through `command`, which reads it from
+/// its standard input and writes the downscaled image to its standard output.
+pub fn downscale(attachment: &mut AttachmentBuilder, command: &str) -> Result<()> {
+    log::debug!("Executing: sh -c \"{}\"", command.replace('"', "\\\""));
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = attachment.raw().to_vec();
+    /* Write from another thread, the command might not read all of its input
+     * before its output buffer fills up. */
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(Error::new(format!(
+            "`{}` exited with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    if output.stdout.is_empty() {
+        return Err(Error::new(format!("`{}` returned no output", command)));
+    }
+    if output.stdout.len() >= attachment.raw().len() {
+        return Err(Error::new(
+            "The downscaled image is not smaller than the original.",
+        ));
+    }
+    if let Some(mime_type) = image_type(&output.stdout) {
+        let filename = attachment.filename().map(|f| filename_for(f, mime_type));
+        attachment
+            .set_content_type(ContentType::Other {
+                tag: mime_type.as_bytes().to_vec(),
+                name: None,
+                parameters: vec![],
+            })
+            .set_filename(filename);
+    }
+    attachment.set_raw(output.stdout).set_body_to_raw();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downscale_images() {
+        let mut attachment = AttachmentBuilder::default();
+        attachment
+            .set_raw(vec![0xff, 0xd8, 0xff, 0xe0, 0, 0, 0, 0])
+            .set_body_to_raw()
+            .set_content_type(ContentType::Other {
+                tag: b"image/jpeg".to_vec(),
+                name: None,
+                parameters: vec![],
+            });
+        assert!(needs_downscaling(&attachment, 4));
+        assert!(!needs_downscaling(&attachment, 8));
+        attachment.set_content_type(ContentType::Other {
+            tag: b"application/pdf".to_vec(),
+            name: None,
+            parameters: vec![],
+        });
+        assert!(!needs_downscaling(&attachment, 4));
+
+        assert_eq!(image_type(&[0xff, 0xd8, 0xff, 0xdb]), Some("image/jpeg"));
+        assert_eq!(image_type(b"\x89PNG\r\n\x1a\n"), Some("image/png"));
+        assert_eq!(image_type(b"GIF89a"), None);
+
+        assert_eq!(filename_for("IMG_001.HEIC", "image/jpeg"), "IMG_001.jpg");
+        assert_eq!(filename_for("photo.jpeg", "image/jpeg"), "photo.jpeg");
+        assert_eq!(filename_for("photo.JPG", "image/jpeg"), "photo.JPG");
+        assert_eq!(filename_for("scan", "image/png"), "scan.png");
+        assert_eq!(filename_for(".hidden", "image/png"), ".hidden.png");
+    }
+}
@@ -19,8 +19,52 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use melib::email::attachment_types::{ContentDisposition, ContentDispositionKind};
+
 use super::*;
 
+/// Describes attachment `no` in a single line.
+pub fn attachment_line(no: usize, a: &AttachmentBuilder) -> String {
+    if let Some(name) = a.filename() {
+        format!(
+            "[{}] \"{}\", {}, {} {}",
+            no,
+            name,
+            a.content_type(),
+            a.content_disposition().kind,
+            melib::Bytes(a.raw.len())
+        )
+    } else {
+        format!(
+            "[{}] {}, {} {}",
+            no,
+            a.content_type(),
+            a.content_disposition().kind,
+            melib::Bytes(a.raw.len())
+        )
+    }
+}
+
+/// Parses a `type/subtype` MIME type. Multipart types are refused since
+/// their parts can't be edited here.
+pub fn parse_mime_type(s: &str) -> Result<Vec<u8>> {
+    let is_token = |t: &str| {
+        !t.is_empty()
+            && t.bytes()
+                .all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&b))
+    };
+    match s.trim().split_once('/') {
+        Some((t, _)) if t.eq_ignore_ascii_case("multipart") => Err(Error::new(
+            "Multipart MIME types can't be set on an attachment.",
+        )),
+        Some((t, st)) if is_token(t) && is_token(st) => Ok(s.trim().as_bytes().to_vec()),
+        _ => Err(Error::new(format!(
+            "Invalid MIME type `{}`, expected `type/subtype`.",
+            s.trim()
+        ))),
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 pub enum EditAttachmentCursor {
     AttachmentNo(usize),
@@ -67,16 +111,93 @@ impl EditAttachmentsRefMut<'_, '_> {
         if no >= self.draft.attachments().len() {
             return None;
         }
-        let filename = self.draft.attachments()[no].content_type().name();
-        let mime_type = self.draft.attachments()[no].content_type();
+        let attachment = &self.draft.attachments()[no];
         let mut ret = FormWidget::new(("Save".into(), FormButtonActions::Accept));
 
         ret.add_button(("Reset".into(), FormButtonActions::Reset));
         ret.add_button(("Cancel".into(), FormButtonActions::Cancel));
-        ret.push(("Filename".into(), filename.unwrap_or_default().to_string()));
-        ret.push(("Mime type".into(), mime_type.to_string()));
+        ret.push((
+            "Filename".into(),
+            attachment.filename().unwrap_or_default().to_string(),
+        ));
+        ret.push(("Mime type".into(), attachment.content_type().to_string()));
+        /* The first choice is the selected one. */
+        ret.push_choices((
+            "Disposition".into(),
+            if attachment.content_disposition().kind.is_inline() {
+                vec!["inline".into(), "attachment".into()]
+            } else {
+                vec!["attachment".into(), "inline".into()]
+            },
+        ));
         Some(Box::new(ret))
     }
+
+    /// Applies the values of the edit form to attachment `no`.
+    fn apply_edit(
+        &mut self,
+        no: usize,
+        filename: &str,
+        mime_type: &str,
+        disposition: &str,
+    ) -> Result<()> {
+        let tag = parse_mime_type(mime_type)?;
+        let attachment = self
+            .draft
+            .attachments_mut()
+            .get_mut(no)
+            .ok_or_else(|| Error::new(format!("Attachment {} not found.", no)))?;
+        if let ContentType::Multipart { .. } = attachment.content_type() {
+            return Err(Error::new("Multipart attachments can't be edited."));
+        }
+        /* Parts of edited drafts still carry their headers and transfer encoding;
+         * keep only the decoded content so that they are printed like
+         * attachments added from files. */
+        if attachment.body().len() != attachment.raw().len() {
+            let decoded = attachment.clone().build().decode(Default::default());
+            attachment
+                .set_raw(decoded)
+                .set_body_to_raw()
+                .set_content_transfer_encoding(ContentTransferEncoding::default());
+        }
+        let parameters = match attachment.content_type() {
+            ContentType::Other { parameters, .. } | ContentType::OctetStream { parameters, .. } => {
+                parameters.clone()
+            }
+            _ => vec![],
+        };
+        attachment.set_content_type(ContentType::Other {
+            name: None,
+            tag,
+            parameters,
+        });
+        attachment.set_content_disposition(ContentDisposition {
+            kind: if disposition == "inline" {
+                ContentDispositionKind::Inline
+            } else {
+                ContentDispositionKind::Attachment
+            },
+            ..attachment.content_disposition().clone()
+        });
+        attachment.set_filename(Some(filename.trim().to_string()).filter(|f| !f.is_empty()));
+        Ok(())
+    }
+
+    /// Moves the attachment under the cursor one position up or down.
+    fn move_attachment(&mut self, up: bool) {
+        if let EditAttachmentCursor::AttachmentNo(ref mut n) = self.inner.cursor {
+            let len = self.draft.attachments().len();
+            let other = if up {
+                n.checked_sub(1)
+            } else {
+                Some(*n + 1).filter(|m| *m < len)
+            };
+            if let Some(other) = other {
+                self.draft.attachments_mut().swap(*n, other);
+                *n = other;
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -133,33 +254,15 @@ impl Component for EditAttachmentsRefMut<'_, '_> {
                     } else {
                         theme_default.bg
                     };
-                    if let Some(name) = a.content_type().name() {
-                        write_string_to_grid(
-                            &format!(
-                                "[{}] \"{}\", {} {}",
-                                i,
-                                name,
-                                a.content_type(),
-                                melib::Bytes(a.raw.len())
-                            ),
-                            grid,
-                            theme_default.fg,
-                            bg,
-                            theme_default.attrs,
-                            (pos_inc(upper_left!(area), (0, 1 + i)), bottom_right!(area)),
-                            None,
-                        );
-                    } else {
-                        write_string_to_grid(
-                            &format!("[{}] {} {}", i, a.content_type(), melib::Bytes(a.raw.len())),
-                            grid,
-                            theme_default.fg,
-                            bg,
-                            theme_default.attrs,
-                            (pos_inc(upper_left!(area), (0, 1 + i)), bottom_right!(area)),
-                            None,
-                        );
-                    }
+                    write_string_to_grid(
+                        &attachment_line(i, a),
+                        grid,
+                        theme_default.fg,
+                        bg,
+                        theme_default.attrs,
+                        (pos_inc(upper_left!(area), (0, 1 + i)), bottom_right!(area)),
+                        None,
+                    );
                 }
             }
             self.inner.buttons.draw(
@@ -183,7 +286,33 @@ impl Component for EditAttachmentsRefMut<'_, '_> {
         {
             if inner.process_event(event, context) {
                 match inner.buttons_result() {
-                    Some(FormButtonActions::Accept) | Some(FormButtonActions::Cancel) => {
+                    Some(FormButtonActions::Accept) => {
+                        let no = *no;
+                        let field = |name: &str| {
+                            inner
+                                .values()
+                                .get(name)
+                                .map(|f| f.as_str().to_string())
+                                .unwrap_or_default()
+                        };
+                        let (filename, mime_type, disposition) =
+                            (field("Filename"), field("Mime type"), field("Disposition"));
+                        match self.apply_edit(no, &filename, &mime_type, &disposition) {
+                            Ok(()) => {
+                                self.inner.mode = EditAttachmentMode::Overview;
+                            }
+                            Err(err) => {
+                                context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(err.to_string()),
+                                ));
+                                if let Some(inner) = self.new_edit_widget(no) {
+                                    self.inner.mode = EditAttachmentMode::Edit { inner, no };
+                                }
+                            }
+                        }
+                        self.set_dirty(true);
+                    }
+                    Some(FormButtonActions::Cancel) => {
                         self.inner.mode = EditAttachmentMode::Overview;
                     }
                     Some(FormButtonActions::Reset) => {
@@ -197,7 +326,24 @@ impl Component for EditAttachmentsRefMut<'_, '_> {
                 return true;
             }
         } else {
+            let shortcuts = self.get_shortcuts(context);
             match event {
+                UIEvent::Input(ref key)
+                    if shortcut!(key == shortcuts[Shortcuts::COMPOSING]["move_attachment_up"]) =>
+                {
+                    self.move_attachment(true);
+                    self.set_dirty(true);
+                    return true;
+                }
+                UIEvent::Input(ref key)
+                    if shortcut!(
+                        key == shortcuts[Shortcuts::COMPOSING]["move_attachment_down"]
+                    ) =>
+                {
+                    self.move_attachment(false);
+                    self.set_dirty(true);
+                    return true;
+                }
                 UIEvent::Input(Key::Up) => {
                     self.set_dirty(true);
                     match self.inner.cursor {
@@ -293,8 +439,13 @@ impl Component for EditAttachmentsRefMut<'_, '_> {
 
     fn kill(&mut self, _uuid: Uuid, _context: &mut Context) {}
 
-    fn get_shortcuts(&self, _context: &Context) -> ShortcutMaps {
-        ShortcutMaps::default()
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+        map.insert(
+            Shortcuts::COMPOSING,
+            context.settings.shortcuts.composing.key_values(),
+        );
+        map
     }
 
     fn id(&self) -> ComponentId {
@@ -305,3 +456,24 @@ impl Component for EditAttachmentsRefMut<'_, '_> {
         self.inner.id = new_id;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_attachments_parse_mime_type() {
+        assert_eq!(
+            parse_mime_type(" application/pdf ").unwrap(),
+            b"application/pdf".to_vec()
+        );
+        assert_eq!(
+            parse_mime_type("image/svg+xml").unwrap(),
+            b"image/svg+xml".to_vec()
+        );
+        assert!(parse_mime_type("application").is_err());
+        assert!(parse_mime_type("text/").is_err());
+        assert!(parse_mime_type("text/plain; charset=utf-8").is_err());
+        assert!(parse_mime_type("multipart/mixed").is_err());
+    }
+}
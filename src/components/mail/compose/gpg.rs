@@ -19,6 +19,8 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use indexmap::IndexMap;
+
 use super::*;
 
 #[derive(Debug)]
@@ -263,6 +265,52 @@ impl Component for KeySelection {
     }
 }
 
+/// The key a recipient's copy of the mail is encrypted with.
+#[derive(Debug, Clone)]
+pub enum RecipientKey {
+    /// The recipient's keys are being looked up.
+    Pending,
+    /// No usable key was found for the recipient.
+    Missing,
+    /// The most valid usable key of the recipient.
+    Found(melib::gpgme::Key),
+    /// A key the user selected for the recipient.
+    Selected(melib::gpgme::Key),
+}
+
+impl RecipientKey {
+    pub fn key(&self) -> Option<&melib::gpgme::Key> {
+        match self {
+            RecipientKey::Found(ref key) | RecipientKey::Selected(ref key) => Some(key),
+            RecipientKey::Pending | RecipientKey::Missing => None,
+        }
+    }
+
+    /// Describes the key and its validity for `address`, e.g.
+    /// `0123456789ABCDEF (full)`.
+    pub fn describe(&self, address: &str) -> String {
+        let fingerprint = |key: &melib::gpgme::Key| {
+            let fpr = key.fingerprint();
+            fpr[fpr.len().saturating_sub(16)..].to_string()
+        };
+        match self {
+            RecipientKey::Pending => "looking up key".to_string(),
+            RecipientKey::Missing => "no usable key!".to_string(),
+            RecipientKey::Found(ref key) => {
+                format!("{} ({})", fingerprint(key), key.uid_validity(address))
+            }
+            RecipientKey::Selected(ref key) => format!(
+                "{} ({}, selected)",
+                fingerprint(key),
+                key.uid_validity(address)
+            ),
+        }
+    }
+}
+
+/// Job looking up the keys of the recipients.
+pub type RecipientKeysJob = JoinHandle<Result<Vec<(String, Option<melib::gpgme::Key>)>>>;
+
 #[derive(Debug, Clone)]
 pub struct GpgComposeState {
     pub sign_mail: ToggleFlag,
@@ -270,6 +318,8 @@ pub struct GpgComposeState {
     pub encrypt_keys: Vec<melib::gpgme::Key>,
     pub encrypt_for_self: bool,
     pub sign_keys: Vec<melib::gpgme::Key>,
+    /// Encryption key of each recipient of the draft.
+    pub recipient_keys: IndexMap<String, RecipientKey>,
 }
 
 impl Default for GpgComposeState {
@@ -280,6 +330,101 @@ impl Default for GpgComposeState {
             encrypt_keys: vec![],
             encrypt_for_self: true,
             sign_keys: vec![],
+            recipient_keys: IndexMap::default(),
+        }
+    }
+}
+
+impl GpgComposeState {
+    /// Updates the recipient list, keeping the keys of known recipients.
+    /// Returns the new recipients, whose keys must be looked up.
+    pub fn set_recipients(&mut self, addresses: &[String]) -> Vec<String> {
+        let mut new = vec![];
+        let mut recipient_keys = IndexMap::default();
+        for address in addresses {
+            if recipient_keys.contains_key(address) {
+                continue;
+            }
+            let key = match self.recipient_keys.remove(address) {
+                Some(key) => key,
+                None => {
+                    new.push(address.clone());
+                    RecipientKey::Pending
+                }
+            };
+            recipient_keys.insert(address.clone(), key);
         }
+        self.recipient_keys = recipient_keys;
+        self.update_encrypt_keys();
+        new
+    }
+
+    /// Sets the key of `address`, unless the user already selected one
+    /// while it was being looked up.
+    pub fn set_recipient_key(&mut self, address: &str, key: RecipientKey) {
+        match (self.recipient_keys.get_mut(address), key) {
+            (Some(RecipientKey::Selected(_)), RecipientKey::Found(_))
+            | (Some(RecipientKey::Selected(_)), RecipientKey::Missing)
+            | (None, _) => return,
+            (Some(entry), key) => *entry = key,
+        }
+        self.update_encrypt_keys();
+    }
+
+    /// Recipients without a usable key.
+    pub fn missing_keys(&self) -> Vec<&str> {
+        self.recipient_keys
+            .iter()
+            .filter(|(_, key)| matches!(key, RecipientKey::Missing))
+            .map(|(address, _)| address.as_str())
+            .collect()
+    }
+
+    /// Recipients whose keys are still being looked up.
+    pub fn pending_keys(&self) -> Vec<&str> {
+        self.recipient_keys
+            .iter()
+            .filter(|(_, key)| matches!(key, RecipientKey::Pending))
+            .map(|(address, _)| address.as_str())
+            .collect()
+    }
+
+    fn update_encrypt_keys(&mut self) {
+        self.encrypt_keys = self
+            .recipient_keys
+            .values()
+            .filter_map(RecipientKey::key)
+            .cloned()
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_gpg_recipient_keys() {
+        let mut state = GpgComposeState::default();
+        let addresses = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            state.set_recipients(&addresses(&[
+                "a@example.com",
+                "b@example.com",
+                "a@example.com"
+            ])),
+            addresses(&["a@example.com", "b@example.com"])
+        );
+        assert_eq!(state.pending_keys(), vec!["a@example.com", "b@example.com"]);
+        state.set_recipient_key("b@example.com", RecipientKey::Missing);
+        state.set_recipient_key("c@example.com", RecipientKey::Missing);
+        assert_eq!(state.missing_keys(), vec!["b@example.com"]);
+        assert_eq!(
+            state.set_recipients(&addresses(&["b@example.com", "c@example.com"])),
+            addresses(&["c@example.com"])
+        );
+        assert_eq!(state.missing_keys(), vec!["b@example.com"]);
+        assert_eq!(state.pending_keys(), vec!["c@example.com"]);
+        assert!(state.encrypt_keys.is_empty());
     }
 }
@@ -264,6 +264,31 @@ pub const EMPTYDRAFTWARN: Hook = Hook {
     hook_fn: HookFn::Ptr(empty_draft_warn),
 };
 
+fn mime_lint_warn(_ctx: &mut Context, draft: &mut Draft) -> Result<()> {
+    let message = draft.clone().finalise()?;
+    let errors = melib::email::compose::lint::lint(message.as_bytes())
+        .into_iter()
+        .filter(melib::email::compose::lint::LintMessage::is_error)
+        .map(|m| m.description)
+        .collect::<Vec<String>>();
+    if !errors.is_empty() {
+        return Err(format!(
+            "Draft violates MIME conformance rules:\n{}",
+            errors.join("\n")
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Warn if the finalised draft violates MIME conformance rules (see
+/// [`melib::email::compose::lint`]).
+pub const MIMELINTWARN: Hook = Hook {
+    name: Cow::Borrowed("mime-lint-warn"),
+    hook_fn: HookFn::Ptr(mime_lint_warn),
+};
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -430,4 +455,30 @@ mod tests {
         draft.set_header("Subject", "Ping".into());
         hook(&mut ctx, &mut draft).unwrap();
     }
+
+    #[test]
+    fn test_draft_hook_mimelintwarn() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut ctx = Context::new_mock(&tempdir);
+        let mut draft = Draft::default();
+        draft
+            .set_header("From", "user <user@example.com>".into())
+            .set_header("To", "other user <user@example.com>".into())
+            .set_header("Subject", "Καλημέρα".into())
+            .set_body("αδφαφσαφασ".to_string());
+
+        let mut hook = MIMELINTWARN;
+
+        println!("Check that a conforming draft produces no warning…");
+        hook(&mut ctx, &mut draft).unwrap();
+
+        println!("Check that an overlong line produces a warning…");
+        draft.set_body("a".repeat(1000));
+        let err_msg = hook(&mut ctx, &mut draft).unwrap_err().to_string();
+        assert!(
+            err_msg.starts_with("Draft violates MIME conformance rules:\nline "),
+            "MIMELINTWARN should complain about line length: {}",
+            err_msg
+        );
+    }
 }
@@ -0,0 +1,132 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Send-time policies keyed by recipient domain, see the
+//! `outgoing_policies` composing setting.
+
+use melib::text_processing::GlobMatch;
+
+use super::*;
+use crate::conf::composing::{OutgoingPolicy, OutgoingPolicyRule};
+
+/// Returns the policy that applies to each recipient in `recipients` when
+/// sending from the e-mail address `from`. Recipients without a matching rule,
+/// or whose rule is [`OutgoingPolicy::Allow`], are left out.
+pub fn recipient_policies(
+    rules: &[OutgoingPolicyRule],
+    from: &str,
+    recipients: &[Address],
+) -> Vec<(OutgoingPolicy, Address)> {
+    let from = from.to_lowercase();
+    let rules = rules
+        .iter()
+        .filter(|r| {
+            r.from
+                .as_ref()
+                .map(|f| from.matches_glob(&f.to_lowercase()))
+                .unwrap_or(true)
+        })
+        .collect::<Vec<&OutgoingPolicyRule>>();
+    let mut ret = vec![];
+    for address in recipients {
+        let domain = match address.get_fqdn() {
+            Some(d) => d.to_lowercase(),
+            None => continue,
+        };
+        if let Some(rule) = rules
+            .iter()
+            .find(|r| domain.matches_glob(&r.domain.to_lowercase()))
+        {
+            if rule.policy != OutgoingPolicy::Allow {
+                ret.push((rule.policy, address.clone()));
+            }
+        }
+    }
+    ret
+}
+
+/// Removes HTML parts from `draft`. Returns how many were removed.
+pub fn strip_html_parts(draft: &mut Draft) -> usize {
+    let before = draft.attachments().len();
+    draft.attachments_mut().retain(|a| {
+        !matches!(
+            a.content_type(),
+            ContentType::Text {
+                kind: melib::email::attachment_types::Text::Html,
+                ..
+            }
+        )
+    });
+    before - draft.attachments().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outgoing_policy_recipients() {
+        let rules = vec![
+            OutgoingPolicyRule {
+                domain: "work.example.com".to_string(),
+                from: Some("*@work.example.com".to_string()),
+                policy: OutgoingPolicy::Allow,
+            },
+            OutgoingPolicyRule {
+                domain: "*".to_string(),
+                from: Some("*@work.example.com".to_string()),
+                policy: OutgoingPolicy::Warn,
+            },
+            OutgoingPolicyRule {
+                domain: "*.gov.example".to_string(),
+                from: None,
+                policy: OutgoingPolicy::RequireEncryption,
+            },
+        ];
+        let recipients = melib::email::parser::address::rfc2822address_list(
+            b"a@work.example.com, b@Other.example, c@tax.gov.example",
+        )
+        .unwrap()
+        .1
+        .into_iter()
+        .collect::<Vec<Address>>();
+        let policies = |from: &str| {
+            recipient_policies(&rules, from, &recipients)
+                .into_iter()
+                .map(|(p, a)| (p, a.get_email()))
+                .collect::<Vec<(OutgoingPolicy, String)>>()
+        };
+        assert_eq!(
+            policies("me@work.example.com"),
+            vec![
+                (OutgoingPolicy::Warn, "b@Other.example".to_string()),
+                (OutgoingPolicy::Warn, "c@tax.gov.example".to_string()),
+            ]
+        );
+        assert_eq!(
+            policies("me@home.example"),
+            vec![(
+                OutgoingPolicy::RequireEncryption,
+                "c@tax.gov.example".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Selection of reply recipients.
+//!
+//! "Mail-Followup-To/(To+Cc+(Mail-Reply-To/Reply-To/From)) for follow-up,
+//! Mail-Reply-To/Reply-To/From for reply-to-author."
+//! source: <https://cr.yp.to/proto/replyto.html>
+
+use super::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplyKind {
+    /// Reply to the author: `Mail-Reply-To`, `Reply-To` or `From`.
+    Author,
+    /// Reply to everyone: `Mail-Followup-To`, or the author and all
+    /// recipients.
+    All,
+    /// Reply to the mailing list the message was posted to.
+    List,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReplyRecipients {
+    pub to: Vec<Address>,
+    pub cc: Vec<Address>,
+    /// Set when it is unclear who a reply to the author should go to, for
+    /// example when a mailing list has set `Reply-To` to itself. Holds the
+    /// candidates for the user to choose from.
+    pub ambiguous: Option<Vec<Address>>,
+}
+
+/// Returns the posting address of the mailing list `envelope` was sent to,
+/// from its `List-Post` header.
+pub fn list_post_address(envelope: &Envelope) -> Option<Address> {
+    let post = list_management::ListActions::detect(envelope)?.post?;
+    match post.first()? {
+        list_management::ListAction::Email(list_post_addr) => {
+            melib::email::parser::generic::mailto(list_post_addr)
+                .map(|(_, m)| m.address)
+                .ok()
+        }
+        _ => None,
+    }
+}
+
+/// Parses the address list in header `header` of `envelope`.
+pub fn header_addresses(envelope: &Envelope, header: &str) -> Vec<Address> {
+    envelope
+        .other_headers()
+        .get(header)
+        .and_then(|v| melib::email::parser::address::rfc2822address_list(v.as_bytes()).ok())
+        .map(|(_, list)| list.into_iter().collect())
+        .unwrap_or_default()
+}
+
+pub fn contains_address(list: &[Address], address: &Address) -> bool {
+    let email = address.get_email();
+    list.iter()
+        .any(|a| a.get_email().eq_ignore_ascii_case(&email))
+}
+
+/// Returns the user's addresses of an account: its main identity and its
+/// `extra_identities`.
+pub fn own_addresses(settings: &AccountSettings) -> Vec<Address> {
+    std::iter::once(settings.make_display_name())
+        .chain(settings.extra_identities.iter().cloned())
+        .filter_map(|a| TryInto::<Address>::try_into(a.as_str()).ok())
+        .collect()
+}
+
+/// Returns whether `address` is one of the user's addresses in `own`. A
+/// subaddress such as `user+lists@example.com` counts as its base address.
+pub fn is_own_address(own: &[Address], address: &Address) -> bool {
+    contains_address(own, address)
+        || address
+            .subaddress("+")
+            .map(|(base, _)| contains_address(own, &base))
+            .unwrap_or(false)
+}
+
+/// Appends the addresses of `iter` to `list`, skipping duplicates, addresses
+/// in `exclude` and the user's addresses in `own`. Groups are replaced by
+/// their members. When an address is repeated, the form with a display name
+/// is kept.
+fn extend_unique(
+    list: &mut Vec<Address>,
+    iter: impl IntoIterator<Item = Address>,
+    exclude: &[Address],
+    own: &[Address],
+) {
+    for address in iter {
+        if let Address::Group(group) = address {
+            extend_unique(list, group.mailbox_list, exclude, own);
+            continue;
+        }
+        let email = address.get_email();
+        if email.is_empty() || contains_address(exclude, &address) || is_own_address(own, &address)
+        {
+            continue;
+        }
+        if let Some(existing) = list
+            .iter_mut()
+            .find(|a| a.get_email().eq_ignore_ascii_case(&email))
+        {
+            if existing.get_display_name().is_none() && address.get_display_name().is_some() {
+                *existing = address;
+            }
+            continue;
+        }
+        list.push(address);
+    }
+}
+
+/// Selects the recipients of a reply of kind `kind` to `envelope`. `own` are
+/// the addresses of the user, which are left out of group replies.
+pub fn reply_recipients(
+    envelope: &Envelope,
+    kind: ReplyKind,
+    own: &[Address],
+) -> Result<ReplyRecipients> {
+    let mut ret = ReplyRecipients::default();
+    let list = list_post_address(envelope);
+    let mail_reply_to = header_addresses(envelope, "Mail-Reply-To");
+    let reply_to = header_addresses(envelope, "Reply-To");
+    let author = if !mail_reply_to.is_empty() {
+        mail_reply_to.clone()
+    } else if !reply_to.is_empty() {
+        reply_to.clone()
+    } else {
+        envelope.from().to_vec()
+    };
+    match kind {
+        ReplyKind::Author => {
+            extend_unique(&mut ret.to, author, &[], &[]);
+            /* A mailing list that munges Reply-To to point to itself hides
+             * whether the author wants private replies. */
+            if let Some(ref list) = list {
+                if mail_reply_to.is_empty()
+                    && contains_address(&reply_to, list)
+                    && !envelope
+                        .from()
+                        .iter()
+                        .all(|f| contains_address(&reply_to, f))
+                {
+                    let mut candidates = vec![];
+                    extend_unique(&mut candidates, envelope.from().iter().cloned(), &[], &[]);
+                    extend_unique(&mut candidates, reply_to, &[], &[]);
+                    ret.ambiguous = Some(candidates);
+                }
+            }
+        }
+        ReplyKind::All => {
+            let followup = header_addresses(envelope, "Mail-Followup-To");
+            if !followup.is_empty() {
+                extend_unique(&mut ret.to, followup, &[], own);
+            } else {
+                if let Some(list) = list {
+                    if !contains_address(envelope.to(), &list)
+                        && !contains_address(envelope.cc(), &list)
+                    {
+                        extend_unique(&mut ret.to, Some(list), &[], own);
+                    }
+                }
+                extend_unique(&mut ret.to, author.clone(), &[], own);
+                extend_unique(&mut ret.to, envelope.to().iter().cloned(), &[], own);
+                let to = ret.to.clone();
+                extend_unique(&mut ret.cc, envelope.cc().iter().cloned(), &to, own);
+            }
+            if ret.to.is_empty() {
+                /* Only we were addressed, e.g. when replying to our own message. */
+                extend_unique(&mut ret.to, author, &[], &[]);
+            }
+        }
+        ReplyKind::List => {
+            if let Some(list) = list {
+                ret.to.push(list);
+            } else {
+                let followup = header_addresses(envelope, "Mail-Followup-To");
+                if followup.is_empty() {
+                    return Err(Error::new(
+                        "Message was not sent to a mailing list: it has no List-Post or \
+                         Mail-Followup-To header.",
+                    ));
+                }
+                extend_unique(&mut ret.to, followup, &[], own);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Joins `addresses` into a header value.
+pub fn addresses_to_string(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(headers: &str) -> Envelope {
+        Envelope::from_bytes(
+            format!(
+                "{}Subject: test\nMessage-ID: <a@example.com>\n\nhello world.\n",
+                headers
+            )
+            .as_bytes(),
+            None,
+        )
+        .expect("Could not parse mail")
+    }
+
+    fn emails(list: &[Address]) -> Vec<String> {
+        list.iter().map(|a| a.get_email()).collect()
+    }
+
+    #[test]
+    fn test_reply_recipients() {
+        let own: Vec<Address> = vec!["me <me@example.com>".try_into().unwrap()];
+
+        let env = envelope(
+            "From: Author <author@example.com>\nTo: me@example.com, other@example.com\nCc: \
+             cc@example.com, me@example.com\n",
+        );
+        let r = reply_recipients(&env, ReplyKind::Author, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["author@example.com"]);
+        assert!(r.ambiguous.is_none());
+        let r = reply_recipients(&env, ReplyKind::All, &own).unwrap();
+        assert_eq!(
+            emails(&r.to),
+            vec!["author@example.com", "other@example.com"]
+        );
+        assert_eq!(emails(&r.cc), vec!["cc@example.com"]);
+        assert!(reply_recipients(&env, ReplyKind::List, &own).is_err());
+
+        /* Reply-To is honored, Mail-Followup-To overrides group replies. */
+        let env = envelope(
+            "From: author@example.com\nReply-To: private@example.com\nMail-Followup-To: \
+             list@example.com, me@example.com\nTo: list@example.com\n",
+        );
+        let r = reply_recipients(&env, ReplyKind::Author, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["private@example.com"]);
+        let r = reply_recipients(&env, ReplyKind::All, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["list@example.com"]);
+        assert!(r.cc.is_empty());
+        let r = reply_recipients(&env, ReplyKind::List, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["list@example.com"]);
+
+        /* A list munging Reply-To makes replies to the author ambiguous. */
+        let env = envelope(
+            "From: author@example.com\nReply-To: list@example.com\nTo: \
+             list@example.com\nList-Post: <mailto:list@example.com>\n",
+        );
+        let r = reply_recipients(&env, ReplyKind::Author, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["list@example.com"]);
+        assert_eq!(
+            emails(&r.ambiguous.unwrap()),
+            vec!["author@example.com", "list@example.com"]
+        );
+        let r = reply_recipients(&env, ReplyKind::List, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["list@example.com"]);
+
+        /* Replying to our own message goes to its recipients. */
+        let env = envelope("From: me@example.com\nTo: friend@example.com\n");
+        let r = reply_recipients(&env, ReplyKind::All, &own).unwrap();
+        assert_eq!(emails(&r.to), vec!["friend@example.com"]);
+
+        /* Duplicates are collapsed keeping the display name, our subaddresses
+         * are dropped and addresses already in To are left out of Cc. */
+        let env = envelope(
+            "From: author@example.com\nTo: Me <me+lists@example.com>, Other@example.com, Other \
+             <other@example.com>, undisclosed-recipients:;\nCc: AUTHOR@example.com, team: \
+             cc@example.com, other@example.com;\n",
+        );
+        let r = reply_recipients(&env, ReplyKind::All, &own).unwrap();
+        assert_eq!(
+            emails(&r.to),
+            vec!["author@example.com", "other@example.com"]
+        );
+        assert_eq!(r.to[1].get_display_name().as_deref(), Some("Other"));
+        assert_eq!(emails(&r.cc), vec!["cc@example.com"]);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Signatures of new drafts, from the `signature` composing setting.
+
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use super::*;
+use crate::conf::composing::SignatureSource;
+
+/// The line that separates the signature from the body (RFC 3676 section
+/// 4.3).
+pub const SIGNATURE_DELIMITER: &str = "-- \n";
+
+/// Returns the signature of `source`, without trailing whitespace.
+pub fn read_signature(source: &SignatureSource) -> Result<String> {
+    let signature = match source {
+        SignatureSource::Text(text) => text.clone(),
+        SignatureSource::File(path) => std::fs::read_to_string(Path::new(path).expand())
+            .chain_err_summary(|| format!("Could not read signature file {}", path))?,
+        SignatureSource::Command(command) => {
+            let output = Command::new("sh")
+                .args(["-c", command])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .chain_err_summary(|| {
+                    format!("Could not execute signature command `{}`", command)
+                })?;
+            if !output.status.success() {
+                return Err(Error::new(format!(
+                    "Signature command `{}` failed with {}",
+                    command, output.status
+                ))
+                .set_details(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+    };
+    Ok(signature.trim_end().to_string())
+}
+
+/// Returns the signature of the account's `signature` setting, falling back
+/// to the contents of `signature_file`.
+pub fn account_signature(context: &Context, account_hash: AccountHash) -> Result<Option<String>> {
+    if let Some(source) = account_settings!(context[account_hash].composing.signature) {
+        return read_signature(source).map(Some);
+    }
+    account_settings!(context[account_hash].composing.signature_file)
+        .as_ref()
+        .map(|path| read_signature(&SignatureSource::File(path.clone())))
+        .transpose()
+}
+
+fn signature_block(signature: &str) -> String {
+    format!("{}{}\n", SIGNATURE_DELIMITER, signature)
+}
+
+/// Returns `body` with `signature` appended after a blank line and the
+/// signature delimiter.
+pub fn append_signature(body: &str, signature: &str) -> String {
+    let mut ret = body.trim_end_matches('\n').to_string();
+    ret.push_str("\n\n");
+    ret.push_str(&signature_block(signature));
+    ret
+}
+
+/// Returns `body` without the last occurrence of `signature` and its
+/// delimiter, or `None` if it isn't there.
+pub fn remove_signature(body: &str, signature: &str) -> Option<String> {
+    let block = signature_block(signature);
+    let pos = body.rfind(&block)?;
+    let mut ret = body[..pos].trim_end_matches('\n').to_string();
+    if !ret.is_empty() {
+        ret.push('\n');
+    }
+    ret.push_str(&body[pos + block.len()..]);
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_signature_append_remove() {
+        let signature = "Jane Doe\nhttps://example.com";
+        let body = append_signature("", signature);
+        assert_eq!(body, "\n\n-- \nJane Doe\nhttps://example.com\n");
+        assert_eq!(remove_signature(&body, signature).unwrap(), "");
+
+        let body = append_signature("> quoted\n\n", signature);
+        assert_eq!(body, "> quoted\n\n-- \nJane Doe\nhttps://example.com\n");
+        assert_eq!(remove_signature(&body, signature).unwrap(), "> quoted\n");
+
+        let edited = body.replace("Jane Doe\n", "J. Doe\n");
+        assert_eq!(remove_signature(&edited, signature), None);
+    }
+
+    #[test]
+    fn test_compose_signature_sources() {
+        assert_eq!(
+            read_signature(&SignatureSource::Text("Jane\n\n".to_string())).unwrap(),
+            "Jane"
+        );
+        assert_eq!(
+            read_signature(&SignatureSource::Command("printf 'a\\nb\\n'".to_string())).unwrap(),
+            "a\nb"
+        );
+        assert!(read_signature(&SignatureSource::Command("exit 1".to_string())).is_err());
+        assert!(
+            read_signature(&SignatureSource::File("/nonexistent/signature".to_string())).is_err()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Drafts started from the files of the `templates_folder` composing
+//! setting.
+//!
+//! A template is a draft, headers and body, with `%{name}` placeholders:
+//! - `%{to}` — the sender of the highlighted message.
+//! - `%{to_name}` — the sender's name (or email address).
+//! - `%{subject}` — the subject of the highlighted message.
+//! - `%{date}` — the current date.
+//! - `%{from}` — the account's identity.
+//! - `%{account_name}` — the account's name.
+//! - `%{account_signature}` — the account's signature, from the `signature`
+//!   or `signature_file` setting.
+//!
+//! Unknown placeholders are left as they are.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::*;
+
+/// Returns the templates folder of the account.
+fn templates_folder(context: &Context, account_hash: AccountHash) -> Result<PathBuf> {
+    account_settings!(context[account_hash].composing.templates_folder)
+        .as_ref()
+        .map(|folder| Path::new(folder).expand())
+        .ok_or_else(|| {
+            Error::new(format!(
+                "No templates_folder is set for account {}.",
+                context.accounts[&account_hash].name()
+            ))
+            .set_kind(ErrorKind::Configuration)
+        })
+}
+
+/// Returns the names of the templates of the account, sorted.
+pub fn list_templates(context: &Context, account_hash: AccountHash) -> Result<Vec<String>> {
+    let folder = templates_folder(context, account_hash)?;
+    let mut ret = std::fs::read_dir(&folder)
+        .chain_err_summary(|| format!("Could not read templates folder {}", folder.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect::<Vec<String>>();
+    ret.sort();
+    Ok(ret)
+}
+
+/// Returns `template` with each `%{name}` placeholder replaced by
+/// `lookup(name)`, or left alone if that is `None`.
+pub fn expand_placeholders(template: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut ret = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("%{") {
+        ret.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after
+            .find('}')
+            .and_then(|end| Some((end, lookup(&after[..end])?)))
+        {
+            Some((end, value)) => {
+                ret.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                ret.push_str("%{");
+                rest = after;
+            }
+        }
+    }
+    ret.push_str(rest);
+    ret
+}
+
+/// Returns the value of placeholder `name` for a draft of account
+/// `account_hash` written in reply to `reply_to`.
+fn placeholder(
+    name: &str,
+    context: &Context,
+    account_hash: AccountHash,
+    reply_to: Option<EnvelopeHash>,
+) -> Option<String> {
+    let account = &context.accounts[&account_hash];
+    let envelope = reply_to.map(|env_hash| account.collection.get_env(env_hash));
+    Some(match name {
+        "to" => envelope
+            .map(|e| {
+                e.from()
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            })
+            .unwrap_or_default(),
+        "to_name" => envelope
+            .and_then(|e| {
+                e.from()
+                    .first()
+                    .map(|a| a.get_display_name().unwrap_or_else(|| a.get_email()))
+            })
+            .unwrap_or_default(),
+        "subject" => envelope
+            .map(|e| e.subject().to_string())
+            .unwrap_or_default(),
+        "date" => melib::datetime::timestamp_to_string(
+            melib::datetime::now(),
+            Some(melib::datetime::RFC822_DATE),
+            true,
+        ),
+        "from" => account.settings.account().make_display_name(),
+        "account_name" => account.name().to_string(),
+        "account_signature" => super::signature::account_signature(context, account_hash)
+            .unwrap_or_else(|err| {
+                log::warn!("Could not read account signature: {}", err);
+                None
+            })
+            .unwrap_or_default(),
+        _ => return None,
+    })
+}
+
+/// Returns a composer with the draft of template `name`, its placeholders
+/// filled in for a reply to `reply_to`.
+pub fn compose_from_template(
+    name: &str,
+    account_hash: AccountHash,
+    reply_to: Option<EnvelopeHash>,
+    context: &Context,
+) -> Result<Composer> {
+    if name.contains('/') {
+        return Err(Error::new(format!("Invalid template name {}.", name)));
+    }
+    let path = templates_folder(context, account_hash)?.join(name);
+    let template = std::fs::read_to_string(&path)
+        .chain_err_summary(|| format!("Could not read template {}", path.display()))?;
+    let template = expand_placeholders(&template, |p| {
+        placeholder(p, context, account_hash, reply_to)
+    });
+    let draft = Draft::from_str(&template)
+        .chain_err_summary(|| format!("Could not parse template {}", name))?;
+    let mut composer = Composer::with_account(account_hash, context);
+    for (header, value) in draft.headers().iter() {
+        if !value.is_empty() {
+            composer
+                .draft_mut()
+                .set_header(header.as_str(), value.clone());
+        }
+    }
+    composer.draft_mut().set_body(draft.body().to_string());
+    composer.disable_signature();
+    composer.update_form();
+    Ok(composer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let lookup = |name: &str| match name {
+            "to" => Some("Jane Doe <jane@example.com>".to_string()),
+            "account_signature" => Some("--\nSupport team".to_string()),
+            "subject" => Some(String::new()),
+            _ => None,
+        };
+        assert_eq!(expand_placeholders("", lookup), "");
+        assert_eq!(
+            expand_placeholders("no placeholders", lookup),
+            "no placeholders"
+        );
+        assert_eq!(
+            expand_placeholders(
+                "To: %{to}\nSubject: Re: %{subject}\n\nHello,\n\n%{account_signature}\n",
+                lookup
+            ),
+            "To: Jane Doe <jane@example.com>\nSubject: Re: \n\nHello,\n\n--\nSupport team\n"
+        );
+        /* Unknown and unterminated placeholders are left alone. */
+        assert_eq!(
+            expand_placeholders("%{unknown} %{to}%{to", lookup),
+            "%{unknown} Jane Doe <jane@example.com>%{to"
+        );
+        assert_eq!(expand_placeholders("100%{}%", lookup), "100%{}%");
+    }
+}
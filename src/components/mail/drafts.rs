@@ -0,0 +1,497 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Resume, send or delete the unfinished drafts of an account.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use melib::{backends::EnvelopeHashBatch, datetime::UnixTimestamp};
+
+use super::*;
+use crate::conf::accounts::JobRequest;
+
+const DATE_FMT: &str = "%Y-%m-%d %H:%M";
+
+/// Where a draft is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DraftSource {
+    /// A message in the drafts mailbox of the account.
+    Mailbox(EnvelopeHash, MailboxHash),
+    /// A draft that couldn't be saved in any mailbox and was stored in the
+    /// data directory of the account instead.
+    Local(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftEntry {
+    pub source: DraftSource,
+    pub date: UnixTimestamp,
+    pub to: String,
+    pub subject: String,
+}
+
+impl DraftEntry {
+    fn new(envelope: &Envelope, source: DraftSource) -> Self {
+        DraftEntry {
+            source,
+            date: envelope.date(),
+            to: envelope.field_to_to_string(),
+            subject: envelope.subject().to_string(),
+        }
+    }
+}
+
+/// Lists the drafts stored in `dir`. Files that can't be parsed are skipped.
+pub fn local_drafts(dir: &Path) -> Vec<DraftEntry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let bytes = fs::read(&path).ok()?;
+            let envelope = Envelope::from_bytes(&bytes, None).ok()?;
+            Some(DraftEntry::new(&envelope, DraftSource::Local(path)))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DraftAction {
+    Resume,
+    SendNow,
+}
+
+impl DraftAction {
+    fn failure_title(self) -> &'static str {
+        match self {
+            DraftAction::Resume => "Could not open draft",
+            DraftAction::SendNow => "Could not send draft",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DraftsView {
+    account_hash: AccountHash,
+    /// Newest drafts first.
+    entries: Vec<DraftEntry>,
+    message: String,
+    cursor: usize,
+    /// First visible row.
+    offset: usize,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for DraftsView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "drafts")
+    }
+}
+
+impl DraftsView {
+    pub fn new(account_hash: AccountHash, context: &Context) -> Self {
+        let mut ret = DraftsView {
+            account_hash,
+            entries: vec![],
+            message: String::new(),
+            cursor: 0,
+            offset: 0,
+            theme_default: crate::conf::value(context, "theme_default"),
+            highlight_theme: crate::conf::value(context, "highlight"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.refresh(context);
+        ret
+    }
+
+    fn drafts_mailbox(&self, context: &Context) -> Option<MailboxHash> {
+        context.accounts[&self.account_hash].special_use_mailbox(SpecialUsageMailbox::Drafts)
+    }
+
+    fn refresh(&mut self, context: &Context) {
+        let account = &context.accounts[&self.account_hash];
+        let mut entries = vec![];
+        if let Some(mailbox_hash) = self.drafts_mailbox(context) {
+            let envelopes = account.collection.envelopes.read().unwrap();
+            let mailboxes = account.collection.mailboxes.read().unwrap();
+            if let Some(env_hashes) = mailboxes.get(&mailbox_hash) {
+                entries.extend(
+                    env_hashes
+                        .iter()
+                        .filter_map(|h| envelopes.get(h))
+                        .map(|env| {
+                            DraftEntry::new(env, DraftSource::Mailbox(env.hash(), mailbox_hash))
+                        }),
+                );
+            }
+        }
+        match account.local_drafts_dir() {
+            Ok(dir) => entries.extend(local_drafts(&dir)),
+            Err(err) => log::error!("Could not open local drafts directory: {}.", err),
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.date));
+        self.entries = entries;
+        self.cursor = std::cmp::min(self.cursor, self.entries.len().saturating_sub(1));
+        self.message = if self.entries.is_empty() {
+            format!("No drafts in {}.", account.name())
+        } else {
+            format!(
+                "{} drafts in {}. Resume a draft with `open_entry`, send it with `send_mail` or \
+                 remove it with the `delete` command.",
+                self.entries.len(),
+                account.name()
+            )
+        };
+        self.set_dirty(true);
+    }
+
+    fn row_to_string(entry: &DraftEntry) -> String {
+        format!(
+            "{}  {}  {}  {}",
+            melib::datetime::timestamp_to_string(entry.date, Some(DATE_FMT), false),
+            match entry.source {
+                DraftSource::Mailbox(_, _) => "mailbox",
+                DraftSource::Local(_) => "local  ",
+            },
+            entry.to,
+            entry.subject
+        )
+    }
+
+    /// Fetches the bytes of the selected draft and performs `action` on them.
+    fn perform(&mut self, action: DraftAction, context: &mut Context) {
+        let entry = match self.entries.get(self.cursor) {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+        let account_hash = self.account_hash;
+        match entry.source {
+            DraftSource::Local(ref path) => {
+                if let Err(err) = fs::read(path)
+                    .map_err(Error::from)
+                    .and_then(|bytes| finish(action, account_hash, &entry.source, &bytes, context))
+                {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(action.failure_title().to_string()),
+                        err.to_string(),
+                        Some(NotificationType::Error(err.kind)),
+                    ));
+                }
+                self.refresh(context);
+            }
+            DraftSource::Mailbox(env_hash, _) => {
+                let (sender, mut receiver) = crate::jobs::oneshot::channel();
+                let operation = context.accounts[&account_hash].operation(env_hash);
+                let bytes_job = async move {
+                    let _ = sender.send(operation?.as_bytes()?.await);
+                    Ok(())
+                };
+                let account = &mut context.accounts[&account_hash];
+                let handle = if account.backend_capabilities.is_async {
+                    account.job_executor.spawn_specialized(bytes_job)
+                } else {
+                    account.job_executor.spawn_blocking(bytes_job)
+                };
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: "fetch draft".into(),
+                        handle,
+                        on_finish: Some(CallbackFn(Box::new(move |context: &mut Context| {
+                            if let Ok(Some(result)) = receiver.try_recv() {
+                                if let Err(err) = result.and_then(|bytes| {
+                                    finish(action, account_hash, &entry.source, &bytes, context)
+                                }) {
+                                    context.replies.push_back(UIEvent::Notification(
+                                        Some(action.failure_title().to_string()),
+                                        err.to_string(),
+                                        Some(NotificationType::Error(err.kind)),
+                                    ));
+                                }
+                            }
+                        }))),
+                        log_level: LogLevel::DEBUG,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Opens a draft in a new composer tab, or sends it and removes it.
+fn finish(
+    action: DraftAction,
+    account_hash: AccountHash,
+    source: &DraftSource,
+    bytes: &[u8],
+    context: &mut Context,
+) -> Result<()> {
+    match (action, source) {
+        (DraftAction::Resume, DraftSource::Mailbox(env_hash, _)) => {
+            let composer = Composer::edit(account_hash, *env_hash, bytes, context)?;
+            context
+                .replies
+                .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+        }
+        (DraftAction::Resume, DraftSource::Local(_)) => {
+            let envelope = Envelope::from_bytes(bytes, None)?;
+            let mut composer = Composer::with_account(account_hash, context);
+            composer.set_draft(Draft::edit(&envelope, bytes)?);
+            composer.disable_signature();
+            context
+                .replies
+                .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+        }
+        (DraftAction::SendNow, _) => {
+            let envelope = Envelope::from_bytes(bytes, None)?;
+            let draft = Draft::edit(&envelope, bytes)?;
+            super::compose::send_draft(
+                ToggleFlag::False,
+                context,
+                account_hash,
+                draft,
+                SpecialUsageMailbox::Sent,
+                Flag::SEEN,
+                true,
+            )?;
+            /* A copy of the sent message is saved in the Sent mailbox, so the draft
+             * is not needed anymore. */
+            delete_draft(account_hash, source, context)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete_draft(
+    account_hash: AccountHash,
+    source: &DraftSource,
+    context: &mut Context,
+) -> Result<()> {
+    match source {
+        DraftSource::Local(path) => {
+            fs::remove_file(path)?;
+        }
+        DraftSource::Mailbox(env_hash, mailbox_hash) => {
+            let env_hashes = EnvelopeHashBatch::from(*env_hash);
+            let account = &mut context.accounts[&account_hash];
+            let fut = account
+                .backend
+                .write()
+                .unwrap()
+                .delete_messages(env_hashes.clone(), *mailbox_hash)?;
+            let handle = account.job_executor.spawn_specialized(fut);
+            account.insert_job(
+                handle.job_id,
+                JobRequest::DeleteMessages { env_hashes, handle },
+            );
+        }
+    }
+    Ok(())
+}
+
+impl Component for DraftsView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        clear_area(grid, area, self.theme_default);
+        let (upper_left, bottom_right) = area;
+        write_string_to_grid(
+            &self.message,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            ((get_x(upper_left) + 1, get_y(upper_left)), bottom_right),
+            None,
+        );
+        let rows = height!(area).saturating_sub(2);
+        if rows > 0 {
+            if self.cursor < self.offset {
+                self.offset = self.cursor;
+            } else if self.cursor >= self.offset + rows {
+                self.offset = self.cursor + 1 - rows;
+            }
+            for (y, (idx, entry)) in (get_y(upper_left) + 2..=get_y(bottom_right))
+                .zip(self.entries.iter().enumerate().skip(self.offset))
+            {
+                let theme = if idx == self.cursor {
+                    self.highlight_theme
+                } else {
+                    self.theme_default
+                };
+                let row_area = ((get_x(upper_left), y), set_y(bottom_right, y));
+                clear_area(grid, row_area, theme);
+                write_string_to_grid(
+                    &Self::row_to_string(entry),
+                    grid,
+                    theme.fg,
+                    theme.bg,
+                    theme.attrs,
+                    ((get_x(upper_left) + 1, y), set_y(bottom_right, y)),
+                    None,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "highlight");
+                self.set_dirty(true);
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_up"]) =>
+            {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_down"]) =>
+            {
+                self.cursor = std::cmp::min(self.cursor + 1, self.entries.len().saturating_sub(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["open_entry"]) =>
+            {
+                self.perform(DraftAction::Resume, context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::COMPOSING]["send_mail"]) =>
+            {
+                self.perform(DraftAction::SendNow, context);
+                return true;
+            }
+            UIEvent::Action(Action::Listing(ListingAction::Delete)) => {
+                if let Some(entry) = self.entries.get(self.cursor) {
+                    if let Err(err) = delete_draft(self.account_hash, &entry.source, context) {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                    }
+                    self.refresh(context);
+                }
+                return true;
+            }
+            UIEvent::MailboxUpdate((account_hash, mailbox_hash))
+            | UIEvent::ThreadUpdate((account_hash, mailbox_hash), _)
+                if *account_hash == self.account_hash
+                    && Some(*mailbox_hash) == self.drafts_mailbox(context) =>
+            {
+                self.refresh(context);
+            }
+            UIEvent::EnvelopeRemove(ref env_hash, _)
+                if self
+                    .entries
+                    .iter()
+                    .any(|e| matches!(e.source, DraftSource::Mailbox(h, _) if h == *env_hash)) =>
+            {
+                self.refresh(context);
+            }
+            UIEvent::Resize => {
+                self.set_dirty(true);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+        map.insert(
+            Shortcuts::LISTING,
+            context.settings.shortcuts.listing.key_values(),
+        );
+        map.insert(
+            Shortcuts::COMPOSING,
+            context.settings.shortcuts.composing.key_values(),
+        );
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drafts_local() {
+        let dir = std::env::temp_dir().join(format!("meli-drafts-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.eml"),
+            "Date: Tue, 3 Jan 2023 10:00:00 +0000\r\nTo: a@example.com\r\nSubject: \
+             first\r\n\r\nbody\r\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.eml"), "").unwrap();
+        let entries = local_drafts(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].subject, "first");
+        assert_eq!(entries[0].to, "a@example.com");
+        assert_eq!(entries[0].source, DraftSource::Local(dir.join("a.eml")));
+    }
+}
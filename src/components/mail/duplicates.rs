@@ -0,0 +1,555 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Review and delete duplicate copies of messages in a mailbox or an account.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+use melib::{backends::EnvelopeHashBatch, datetime::UnixTimestamp, parser::BytesExt};
+
+use super::*;
+use crate::{conf::accounts::JobRequest, jobs::JoinHandle};
+
+const DATE_FMT: &str = "%Y-%m-%d %H:%M";
+
+/// A copy of a message that may have duplicates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEntry {
+    pub env_hash: EnvelopeHash,
+    pub mailbox_hash: MailboxHash,
+    pub message_id: Vec<u8>,
+    pub date: UnixTimestamp,
+    pub from: String,
+    pub subject: String,
+    pub mailbox: String,
+}
+
+/// Hashes the body of a message. Headers are skipped since copies of the
+/// same message often differ in `Received` and other transport headers.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let body = [&b"\r\n\r\n"[..], &b"\n\n"[..]]
+        .iter()
+        .filter_map(|sep| bytes.find(sep).map(|pos| pos + sep.len()))
+        .min()
+        .map(|pos| &bytes[pos..])
+        .unwrap_or(bytes);
+    let mut hasher = DefaultHasher::new();
+    body.trim().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Groups entries with the same Message-ID and content hash, keeping only
+/// groups with more than one copy. Copies are sorted by date, so the first
+/// one is the oldest, and groups are sorted by the date of their first copy.
+pub fn group_duplicates(entries: Vec<(DuplicateEntry, u64)>) -> Vec<Vec<DuplicateEntry>> {
+    let mut groups: HashMap<(Vec<u8>, u64), Vec<DuplicateEntry>> = HashMap::default();
+    for (entry, hash) in entries {
+        groups
+            .entry((entry.message_id.clone(), hash))
+            .or_default()
+            .push(entry);
+    }
+    let mut ret = groups
+        .into_values()
+        .filter(|g| g.len() > 1)
+        .map(|mut g| {
+            g.sort_by_key(|e| (e.date, e.env_hash.0));
+            g
+        })
+        .collect::<Vec<_>>();
+    ret.sort_by_key(|g| (g[0].date, g[0].env_hash.0));
+    ret
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Row {
+    Group(usize),
+    Entry(usize, usize),
+}
+
+#[derive(Debug)]
+pub struct DuplicatesView {
+    account_hash: AccountHash,
+    groups: Vec<Vec<DuplicateEntry>>,
+    rows: Vec<Row>,
+    /// Copies that will be deleted.
+    selection: HashSet<EnvelopeHash>,
+    handle: Option<JoinHandle<Result<Vec<Vec<DuplicateEntry>>>>>,
+    message: String,
+    cursor: usize,
+    /// First visible row.
+    offset: usize,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for DuplicatesView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "duplicates")
+    }
+}
+
+impl DuplicatesView {
+    pub fn new(
+        account_hash: AccountHash,
+        mailbox_hash: Option<MailboxHash>,
+        context: &mut Context,
+    ) -> Self {
+        let account = &context.accounts[&account_hash];
+        let mut entries: Vec<DuplicateEntry> = vec![];
+        {
+            let envelopes = account.collection.envelopes.read().unwrap();
+            let mailboxes = account.collection.mailboxes.read().unwrap();
+            for (mh, env_hashes) in mailboxes
+                .iter()
+                .filter(|(mh, _)| mailbox_hash.map(|m| m == **mh).unwrap_or(true))
+            {
+                let mailbox = account
+                    .mailbox_entries
+                    .get(mh)
+                    .map(|m| m.name().to_string())
+                    .unwrap_or_default();
+                for env in env_hashes.iter().filter_map(|h| envelopes.get(h)) {
+                    entries.push(DuplicateEntry {
+                        env_hash: env.hash(),
+                        mailbox_hash: *mh,
+                        message_id: env.message_id().raw().to_vec(),
+                        date: env.date(),
+                        from: env.field_from_to_string(),
+                        subject: env.subject().to_string(),
+                        mailbox: mailbox.clone(),
+                    });
+                }
+            }
+        }
+        /* Only copies sharing a Message-ID need their content compared. */
+        let candidates: Vec<DuplicateEntry> =
+            group_duplicates(entries.into_iter().map(|e| (e, 0)).collect())
+                .into_iter()
+                .flatten()
+                .collect();
+        let backend = account.backend.clone();
+        let fut = async move {
+            let mut hashed = Vec::with_capacity(candidates.len());
+            for entry in candidates {
+                let mut op = backend.read().unwrap().operation(entry.env_hash)?;
+                let bytes = op.as_bytes()?.await?;
+                hashed.push((entry, content_hash(&bytes)));
+            }
+            Ok(group_duplicates(hashed))
+        };
+        let handle = account.job_executor.spawn_blocking(fut);
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        DuplicatesView {
+            account_hash,
+            groups: vec![],
+            rows: vec![],
+            selection: HashSet::default(),
+            handle: Some(handle),
+            message: format!(
+                "Looking for duplicate messages in {}…",
+                match mailbox_hash
+                    .and_then(|h| context.accounts[&account_hash].mailbox_entries.get(&h))
+                {
+                    Some(entry) => format!(
+                        "{}/{}",
+                        context.accounts[&account_hash].name(),
+                        entry.name()
+                    ),
+                    None => context.accounts[&account_hash].name().to_string(),
+                }
+            ),
+            cursor: 0,
+            offset: 0,
+            theme_default: crate::conf::value(context, "theme_default"),
+            highlight_theme: crate::conf::value(context, "highlight"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    fn set_groups(&mut self, groups: Vec<Vec<DuplicateEntry>>) {
+        self.groups = groups;
+        self.rows.clear();
+        for (i, group) in self.groups.iter().enumerate() {
+            self.rows.push(Row::Group(i));
+            self.rows.extend((0..group.len()).map(|j| Row::Entry(i, j)));
+        }
+        let remaining: HashSet<EnvelopeHash> =
+            self.groups.iter().flatten().map(|e| e.env_hash).collect();
+        self.selection.retain(|h| remaining.contains(h));
+        self.cursor = std::cmp::min(self.cursor, self.rows.len().saturating_sub(1));
+        let copies = self.groups.iter().map(|g| g.len() - 1).sum::<usize>();
+        self.message = if self.groups.is_empty() {
+            "No duplicate messages found.".to_string()
+        } else {
+            format!(
+                "{} messages have {} duplicate copies. Select copies with the `select_entry` \
+                 shortcut and remove them with the `delete` command.",
+                self.groups.len(),
+                copies
+            )
+        };
+    }
+
+    fn row_to_string(&self, row: Row) -> String {
+        match row {
+            Row::Group(i) => format!(
+                "{} ({} copies)",
+                String::from_utf8_lossy(&self.groups[i][0].message_id),
+                self.groups[i].len()
+            ),
+            Row::Entry(i, j) => {
+                let e = &self.groups[i][j];
+                format!(
+                    "  [{}] {}  {}  {}  {}",
+                    if self.selection.contains(&e.env_hash) {
+                        "x"
+                    } else {
+                        " "
+                    },
+                    melib::datetime::timestamp_to_string(e.date, Some(DATE_FMT), false),
+                    e.mailbox,
+                    e.from,
+                    e.subject
+                )
+            }
+        }
+    }
+
+    fn delete_selection(&mut self, context: &mut Context) {
+        if self.selection.is_empty() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "No copies are selected.".to_string(),
+                )));
+            return;
+        }
+        if self
+            .groups
+            .iter()
+            .any(|g| g.iter().all(|e| self.selection.contains(&e.env_hash)))
+        {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "Refusing to delete every copy of a message; unselect one of them first."
+                        .to_string(),
+                )));
+            return;
+        }
+        let mut per_mailbox: HashMap<MailboxHash, Vec<EnvelopeHash>> = HashMap::default();
+        for e in self
+            .groups
+            .iter()
+            .flatten()
+            .filter(|e| self.selection.contains(&e.env_hash))
+        {
+            per_mailbox
+                .entry(e.mailbox_hash)
+                .or_default()
+                .push(e.env_hash);
+        }
+        let account = &mut context.accounts[&self.account_hash];
+        for (mailbox_hash, env_hashes) in per_mailbox {
+            let env_hashes = match EnvelopeHashBatch::try_from(env_hashes.as_slice()) {
+                Ok(batch) => batch,
+                Err(_) => continue,
+            };
+            let result = account
+                .backend
+                .write()
+                .unwrap()
+                .delete_messages(env_hashes.clone(), mailbox_hash);
+            match result {
+                Err(err) => {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            err.to_string(),
+                        )));
+                    return;
+                }
+                Ok(fut) => {
+                    let handle = account.job_executor.spawn_specialized(fut);
+                    account.insert_job(
+                        handle.job_id,
+                        JobRequest::DeleteMessages { env_hashes, handle },
+                    );
+                }
+            }
+        }
+        let selection = std::mem::take(&mut self.selection);
+        let groups = std::mem::take(&mut self.groups)
+            .into_iter()
+            .map(|g| {
+                g.into_iter()
+                    .filter(|e| !selection.contains(&e.env_hash))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|g| g.len() > 1)
+            .collect();
+        self.set_groups(groups);
+        self.set_dirty(true);
+    }
+}
+
+impl Component for DuplicatesView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        clear_area(grid, area, self.theme_default);
+        let (upper_left, bottom_right) = area;
+        write_string_to_grid(
+            &self.message,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            ((get_x(upper_left) + 1, get_y(upper_left)), bottom_right),
+            None,
+        );
+        let rows = height!(area).saturating_sub(2);
+        if rows > 0 {
+            if self.cursor < self.offset {
+                self.offset = self.cursor;
+            } else if self.cursor >= self.offset + rows {
+                self.offset = self.cursor + 1 - rows;
+            }
+            for (y, (idx, row)) in (get_y(upper_left) + 2..=get_y(bottom_right))
+                .zip(self.rows.iter().enumerate().skip(self.offset))
+            {
+                let theme = if idx == self.cursor {
+                    self.highlight_theme
+                } else {
+                    self.theme_default
+                };
+                let row_area = ((get_x(upper_left), y), set_y(bottom_right, y));
+                clear_area(grid, row_area, theme);
+                write_string_to_grid(
+                    &self.row_to_string(*row),
+                    grid,
+                    theme.fg,
+                    theme.bg,
+                    if matches!(row, Row::Group(_)) {
+                        theme.attrs | Attr::BOLD
+                    } else {
+                        theme.attrs
+                    },
+                    ((get_x(upper_left) + 1, y), set_y(bottom_right, y)),
+                    None,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "highlight");
+                self.set_dirty(true);
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .handle
+                    .as_ref()
+                    .map(|h| h.job_id == *job_id)
+                    .unwrap_or(false) =>
+            {
+                let mut handle = self.handle.take().unwrap();
+                match handle.chan.try_recv() {
+                    Ok(Some(Ok(groups))) => {
+                        /* Keep the oldest copy of each message by default. */
+                        self.selection = groups
+                            .iter()
+                            .flat_map(|g| g.iter().skip(1).map(|e| e.env_hash))
+                            .collect();
+                        self.set_groups(groups);
+                    }
+                    Ok(Some(Err(err))) => {
+                        self.message = format!("Could not look for duplicate messages: {}", err);
+                    }
+                    Err(_) | Ok(None) => {}
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_up"]) =>
+            {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_down"]) =>
+            {
+                self.cursor = std::cmp::min(self.cursor + 1, self.rows.len().saturating_sub(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["select_entry"]) =>
+            {
+                let env_hashes: Vec<EnvelopeHash> = match self.rows.get(self.cursor) {
+                    Some(Row::Entry(i, j)) => vec![self.groups[*i][*j].env_hash],
+                    Some(Row::Group(i)) => self.groups[*i].iter().map(|e| e.env_hash).collect(),
+                    None => return true,
+                };
+                let select = env_hashes.iter().any(|h| !self.selection.contains(h));
+                for h in env_hashes {
+                    if select {
+                        self.selection.insert(h);
+                    } else {
+                        self.selection.remove(&h);
+                    }
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Action(Action::Listing(ListingAction::Delete)) => {
+                self.delete_selection(context);
+                return true;
+            }
+            UIEvent::EnvelopeRemove(ref env_hash, _)
+                if self
+                    .groups
+                    .iter()
+                    .flatten()
+                    .any(|e| e.env_hash == *env_hash) =>
+            {
+                let groups = std::mem::take(&mut self.groups)
+                    .into_iter()
+                    .map(|g| {
+                        g.into_iter()
+                            .filter(|e| e.env_hash != *env_hash)
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|g| g.len() > 1)
+                    .collect();
+                self.set_groups(groups);
+                self.set_dirty(true);
+            }
+            UIEvent::Resize => {
+                self.set_dirty(true);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        if let Some(handle) = self.handle.take() {
+            handle.cancel();
+        }
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+        let config_map = context.settings.shortcuts.listing.key_values();
+        map.insert(Shortcuts::LISTING, config_map);
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: u64, message_id: &str, date: UnixTimestamp) -> DuplicateEntry {
+        DuplicateEntry {
+            env_hash: EnvelopeHash(hash),
+            mailbox_hash: MailboxHash::default(),
+            message_id: message_id.as_bytes().to_vec(),
+            date,
+            from: String::new(),
+            subject: String::new(),
+            mailbox: "INBOX".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_group() {
+        assert_eq!(
+            content_hash(b"Received: a\r\nSubject: x\r\n\r\nBody\r\n"),
+            content_hash(b"Received: b\nSubject: x\n\nBody\n")
+        );
+        assert_ne!(
+            content_hash(b"Subject: x\n\nBody\n"),
+            content_hash(b"Subject: x\n\nOther body\n")
+        );
+
+        let groups = group_duplicates(vec![
+            (entry(1, "<a@example.com>", 30), 7),
+            (entry(2, "<a@example.com>", 10), 7),
+            (entry(3, "<a@example.com>", 20), 8),
+            (entry(4, "<b@example.com>", 5), 1),
+            (entry(5, "<b@example.com>", 5), 1),
+            (entry(6, "<c@example.com>", 1), 1),
+        ]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups[0].iter().map(|e| e.env_hash.0).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_eq!(
+            groups[1].iter().map(|e| e.env_hash.0).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+    }
+}
@@ -20,16 +20,21 @@
  */
 
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     convert::TryFrom,
     ops::{Deref, DerefMut},
 };
 
+use indexmap::IndexMap;
 use melib::backends::EnvelopeHashBatch;
 use smallvec::SmallVec;
 
 use super::*;
-use crate::{conf::accounts::JobRequest, types::segment_tree::SegmentTree};
+use crate::{
+    conf::{accounts::JobRequest, ContactHighlight},
+    types::segment_tree::SegmentTree,
+    undo::{UndoEntry, UndoKind},
+};
 
 // TODO: emoji_text_presentation_selector should be printed along with the chars
 // before it but not as a separate Cell
@@ -206,6 +211,64 @@ impl<T> RowsState<T> {
         self.all_envelopes.remove(&old_hash);
         self.all_envelopes.insert(old_hash);
     }
+
+    /// Removes the row of `thread` and moves the rows after it up. Returns
+    /// the index the row had.
+    pub fn remove_thread(&mut self, thread: ThreadHash) -> Option<usize> {
+        let index = self.thread_order.remove(&thread)?;
+        self.all_threads.remove(&thread);
+        for env_hash in self.thread_to_env.remove(&thread).unwrap_or_default() {
+            self.selection.remove(&env_hash);
+            self.env_to_thread.remove(&env_hash);
+            self.env_order.remove(&env_hash);
+            self.all_envelopes.remove(&env_hash);
+        }
+        self.entries.remove(index);
+        for row in self
+            .thread_order
+            .values_mut()
+            .chain(self.env_order.values_mut())
+        {
+            if *row > index {
+                *row -= 1;
+            }
+        }
+        self.row_attr_cache.clear();
+        Some(index)
+    }
+
+    /// Inserts a row for `thread` at `index` and moves the rows after it
+    /// down.
+    pub fn insert_thread_at(
+        &mut self,
+        index: usize,
+        thread: ThreadHash,
+        metadata: T,
+        env_hashes: SmallVec<[EnvelopeHash; 8]>,
+        entry_strings: EntryStrings,
+    ) {
+        if index >= self.entries.len() {
+            self.insert_thread(thread, metadata, env_hashes, entry_strings);
+            return;
+        }
+        for row in self
+            .thread_order
+            .values_mut()
+            .chain(self.env_order.values_mut())
+        {
+            if *row >= index {
+                *row += 1;
+            }
+        }
+        self.insert_thread(thread, metadata, env_hashes, entry_strings);
+        let entry = self.entries.pop().unwrap();
+        self.entries.insert(index, entry);
+        self.thread_order.insert(thread, index);
+        for env_hash in &self.thread_to_env[&thread] {
+            self.env_order.insert(*env_hash, index);
+        }
+        self.row_attr_cache.clear();
+    }
 }
 
 mod conversations;
@@ -223,6 +286,18 @@ pub use self::plain::*;
 mod offline;
 pub use self::offline::*;
 
+mod mailbox_selector;
+pub use self::mailbox_selector::*;
+
+mod query_builder;
+pub use self::query_builder::*;
+
+mod flag_editor;
+pub use self::flag_editor::*;
+
+mod unsubscribe;
+pub use self::unsubscribe::*;
+
 #[derive(Debug, Copy, Clone)]
 pub enum Focus {
     None,
@@ -256,6 +331,7 @@ pub struct ColorCache {
     pub odd_highlighted: ThemeAttribute,
     pub odd_selected: ThemeAttribute,
     pub tag_default: ThemeAttribute,
+    pub pinned: ThemeAttribute,
 
     /* Conversations */
     pub subject: ThemeAttribute,
@@ -329,6 +405,7 @@ impl ColorCache {
                 ..Self::default()
             },
         };
+        ret.pinned = crate::conf::value(context, "mail.listing.pinned");
         if !context.settings.terminal.use_color() {
             ret.highlighted.attrs |= Attr::REVERSE;
             ret.tag_default.attrs |= Attr::REVERSE;
@@ -337,6 +414,150 @@ impl ColorCache {
         }
         ret
     }
+
+    /// Returns `row_attr` with the foreground and attributes of
+    /// `mail.listing.pinned` if `pinned` is true.
+    pub fn pinned_row_attr(&self, row_attr: ThemeAttribute, pinned: bool) -> ThemeAttribute {
+        if pinned {
+            ThemeAttribute {
+                fg: self.pinned.fg,
+                attrs: self.pinned.attrs,
+                ..row_attr
+            }
+        } else {
+            row_attr
+        }
+    }
+
+    /// Returns `row_attr` with the colors of the `age_highlight` rule that
+    /// applies to `envelope` in the mailbox at `coordinates`, if any, and the
+    /// color of its sender's contact highlight.
+    pub fn envelope_row_attr(
+        &self,
+        row_attr: ThemeAttribute,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+        envelope: &Envelope,
+    ) -> ThemeAttribute {
+        let rules = mailbox_settings!(context[coordinates.0][&coordinates.1].listing.age_highlight);
+        let row_attr = crate::conf::AgeHighlight::find(rules, envelope, melib::datetime::now())
+            .map_or(row_attr, |rule| rule.apply(row_attr));
+        match sender_highlight(context, coordinates.0, envelope).and_then(|h| h.color) {
+            Some(fg) => ThemeAttribute { fg, ..row_attr },
+            None => row_attr,
+        }
+    }
+}
+
+/// Returns the contact highlight of the first `From:` address of `envelope`.
+pub fn sender_highlight(
+    context: &Context,
+    account_hash: AccountHash,
+    envelope: &Envelope,
+) -> Option<ContactHighlight> {
+    envelope
+        .from()
+        .first()
+        .and_then(|address| context.contact_highlight(account_hash, address))
+}
+
+/// Returns `from` prefixed with the contact label of the sender of
+/// `envelope`, if any.
+pub fn labeled_from(
+    context: &Context,
+    account_hash: AccountHash,
+    envelope: &Envelope,
+    from: String,
+) -> FromString {
+    FromString(match sender_highlight(context, account_hash, envelope) {
+        Some(highlight) => highlight.labeled(from),
+        None => from,
+    })
+}
+
+/// Moves the threads of `pinned` in `roots` to the top, keeping the order of
+/// the rest.
+pub fn pinned_first(roots: &mut [ThreadHash], pinned: &HashSet<ThreadHash>) {
+    if !pinned.is_empty() {
+        roots.sort_by_key(|thread| !pinned.contains(thread));
+    }
+}
+
+/// Removes the threads of `snoozed` from `roots`.
+pub fn hide_snoozed(roots: &mut SmallVec<[ThreadHash; 1024]>, snoozed: &HashSet<ThreadHash>) {
+    if !snoozed.is_empty() {
+        roots.retain(|thread| !snoozed.contains(thread));
+    }
+}
+
+/// A row of a listing that stands for the newsletter threads of a period,
+/// see [`DigestSettings`](crate::conf::DigestSettings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// The key of the row, which is the same for the same period.
+    pub hash: ThreadHash,
+    /// The name of the period.
+    pub period: String,
+    /// The threads of the digest, in listing order.
+    pub threads: Vec<ThreadHash>,
+}
+
+/// Replaces the threads of `roots` that `period_of` puts in a period with one
+/// digest per period, at the position of its first thread. Returns the new
+/// roots, where the digests are the keys of the returned map. `mailbox_hash`
+/// makes the keys of digests of different mailboxes differ.
+pub fn group_digests(
+    roots: &[ThreadHash],
+    mailbox_hash: MailboxHash,
+    mut period_of: impl FnMut(ThreadHash) -> Option<String>,
+) -> (Vec<ThreadHash>, HashMap<ThreadHash, Digest>) {
+    let mut ret = Vec::with_capacity(roots.len());
+    let mut digests: HashMap<ThreadHash, Digest> = HashMap::default();
+    for &thread in roots {
+        let period = if let Some(period) = period_of(thread) {
+            period
+        } else {
+            ret.push(thread);
+            continue;
+        };
+        let hash = ThreadHash::from(format!("digest {} {}", mailbox_hash, period).as_bytes());
+        digests
+            .entry(hash)
+            .or_insert_with(|| {
+                ret.push(hash);
+                Digest {
+                    hash,
+                    period,
+                    threads: vec![],
+                }
+            })
+            .threads
+            .push(thread);
+    }
+    (ret, digests)
+}
+
+/// Returns the index of the entry drawn at `pos` by a paged list of `length`
+/// entries, each `entry_height` lines high, that was drawn in `area` with the
+/// cursor on entry `cursor`.
+pub fn entry_at_pos(
+    area: Area,
+    entry_height: usize,
+    cursor: usize,
+    length: usize,
+    pos: Pos,
+) -> Option<usize> {
+    let rows = height!(area) / entry_height;
+    if rows == 0 || !area_contains(area, pos) {
+        return None;
+    }
+    let row = (get_y(pos) - get_y(upper_left!(area))) / entry_height;
+    let idx = (cursor / rows) * rows + row;
+    if row < rows && idx < length {
+        Some(idx)
+    } else {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -417,10 +638,39 @@ pub trait MailListingTrait: ListingTrait {
         context: &mut Context,
         envs_to_set: SmallVec<[EnvelopeHash; 8]>,
         a: &ListingAction,
+    ) {
+        let (account_hash, mailbox_hash) = self.coordinates();
+        let account = &context.accounts[&account_hash];
+        if account.virtual_mailboxes.contains_key(&mailbox_hash) {
+            /* The backend only knows the mailboxes the envelopes of a virtual
+             * mailbox are stored in. */
+            let mut groups: IndexMap<MailboxHash, SmallVec<[EnvelopeHash; 8]>> =
+                IndexMap::default();
+            for env_hash in envs_to_set {
+                groups
+                    .entry(account.storage_mailbox(mailbox_hash, env_hash))
+                    .or_default()
+                    .push(env_hash);
+            }
+            for (storage_mailbox_hash, envs_to_set) in groups {
+                self.perform_action_in(context, storage_mailbox_hash, envs_to_set, a);
+            }
+            return;
+        }
+        self.perform_action_in(context, mailbox_hash, envs_to_set, a);
+    }
+
+    /// Performs `a` on envelopes `envs_to_set` of `mailbox_hash`.
+    fn perform_action_in(
+        &mut self,
+        context: &mut Context,
+        mailbox_hash: MailboxHash,
+        envs_to_set: SmallVec<[EnvelopeHash; 8]>,
+        a: &ListingAction,
     ) {
         let account_hash = self.coordinates().0;
+        let undo_grace_period = *account_settings!(context[account_hash].listing.undo_grace_period);
         let account = &mut context.accounts[&account_hash];
-        let mailbox_hash = self.coordinates().1;
         /*{
             let threads_lck = account.collection.get_threads(mailbox_hash);
             for thread_hash in thread_hashes {
@@ -436,6 +686,42 @@ pub trait MailListingTrait: ListingTrait {
         } else {
             return;
         };
+        let undo_kind = if undo_grace_period == 0 {
+            None
+        } else {
+            match a {
+                ListingAction::SetSeen => {
+                    UndoKind::set_flags(&account.collection, &env_hashes, &[(Ok(Flag::SEEN), true)])
+                }
+                ListingAction::SetUnseen => UndoKind::set_flags(
+                    &account.collection,
+                    &env_hashes,
+                    &[(Ok(Flag::SEEN), false)],
+                ),
+                ListingAction::Tag(Add(ref tag_str)) => UndoKind::set_flags(
+                    &account.collection,
+                    &env_hashes,
+                    &[(Err(tag_str.to_string()), true)],
+                ),
+                ListingAction::Tag(Remove(ref tag_str)) => UndoKind::set_flags(
+                    &account.collection,
+                    &env_hashes,
+                    &[(Err(tag_str.to_string()), false)],
+                ),
+                ListingAction::SetFlags(ref ops) => {
+                    UndoKind::set_flags(&account.collection, &env_hashes, ops)
+                }
+                ListingAction::MoveTo(ref mailbox_path) => account
+                    .mailbox_by_path(mailbox_path)
+                    .ok()
+                    .map(|destination| {
+                        UndoKind::move_to(&account.collection, &env_hashes, destination)
+                    }),
+                _ => None,
+            }
+        };
+        let count = envs_to_set.len();
+        let mut started = false;
         match a {
             ListingAction::SetSeen => {
                 let job = account.backend.write().unwrap().set_flags(
@@ -453,6 +739,7 @@ pub trait MailListingTrait: ListingTrait {
                         let handle = account.job_executor.spawn_specialized(fut);
                         account
                             .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                        started = true;
                     }
                 }
             }
@@ -472,6 +759,7 @@ pub trait MailListingTrait: ListingTrait {
                         let handle = account.job_executor.spawn_specialized(fut);
                         account
                             .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                        started = true;
                     }
                 }
             }
@@ -491,6 +779,7 @@ pub trait MailListingTrait: ListingTrait {
                         let handle = account.job_executor.spawn_specialized(fut);
                         account
                             .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                        started = true;
                     }
                 }
             }
@@ -510,6 +799,27 @@ pub trait MailListingTrait: ListingTrait {
                         let handle = account.job_executor.spawn_specialized(fut);
                         account
                             .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                        started = true;
+                    }
+                }
+            }
+            ListingAction::SetFlags(ref ops) => {
+                let job = account.backend.write().unwrap().set_flags(
+                    env_hashes.clone(),
+                    mailbox_hash,
+                    ops.iter().cloned().collect(),
+                );
+                match job {
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                    }
+                    Ok(fut) => {
+                        let handle = account.job_executor.spawn_specialized(fut);
+                        account
+                            .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                        started = true;
                     }
                 }
             }
@@ -519,6 +829,19 @@ pub trait MailListingTrait: ListingTrait {
                     .write()
                     .unwrap()
                     .delete_messages(env_hashes.clone(), mailbox_hash);
+                let job = if undo_grace_period == 0 {
+                    job
+                } else {
+                    job.and_then(|delete| {
+                        crate::undo::undoable_delete(
+                            account,
+                            &env_hashes,
+                            mailbox_hash,
+                            undo_grace_period,
+                            delete,
+                        )
+                    })
+                };
                 match job {
                     Err(err) => {
                         context.replies.push_back(UIEvent::StatusEvent(
@@ -564,13 +887,6 @@ pub trait MailListingTrait: ListingTrait {
                     }
                 }
             }
-            ListingAction::CopyToOtherAccount(ref _account_name, ref _mailbox_path) => {
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
-                        "Copying to another account is currently unimplemented".into(),
-                    )));
-            }
             ListingAction::MoveTo(ref mailbox_path) => {
                 match account
                     .mailbox_by_path(mailbox_path)
@@ -598,6 +914,7 @@ pub trait MailListingTrait: ListingTrait {
                                 log_level: LogLevel::INFO,
                             },
                         );
+                        started = true;
                     }
                 }
             }
@@ -677,18 +994,18 @@ pub trait MailListingTrait: ListingTrait {
                         on_finish: Some(CallbackFn(Box::new(move |context: &mut Context| {
                             context.replies.push_back(match receiver.try_recv() {
                                 Err(_) | Ok(None) => UIEvent::Notification(
-                                    Some("Could not export mbox".to_string()),
-                                    "Job was canceled.".to_string(),
+                                    Some(tr!("Could not export mbox")),
+                                    tr!("Job was canceled."),
                                     Some(NotificationType::Info),
                                 ),
                                 Ok(Some(Err(err))) => UIEvent::Notification(
-                                    Some("Could not export mbox".to_string()),
+                                    Some(tr!("Could not export mbox")),
                                     err.to_string(),
                                     Some(NotificationType::Error(err.kind)),
                                 ),
                                 Ok(Some(Ok(()))) => UIEvent::Notification(
-                                    Some("Succesfully exported mbox".to_string()),
-                                    format!("Wrote to file {}", path.display()),
+                                    Some(tr!("Succesfully exported mbox")),
+                                    tr!("Wrote to file {}", path.display()),
                                     Some(NotificationType::Info),
                                 ),
                             });
@@ -697,21 +1014,198 @@ pub trait MailListingTrait: ListingTrait {
                     },
                 );
             }
-            ListingAction::MoveToOtherAccount(ref _account_name, ref _mailbox_path) => {
-                context
-                    .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
-                        "Moving to another account is currently unimplemented".into(),
-                    )));
+            ListingAction::CopyToOtherAccount(ref account_name, ref mailbox_path)
+            | ListingAction::MoveToOtherAccount(ref account_name, ref mailbox_path) => {
+                use std::{future::Future, pin::Pin};
+
+                let move_ = matches!(a, ListingAction::MoveToOtherAccount(_, _));
+                let destination = context
+                    .accounts
+                    .values()
+                    .find(|acc| acc.name() == account_name)
+                    .ok_or_else(|| {
+                        Error::new(format!("Account with name `{}` not found.", account_name))
+                    })
+                    .and_then(|acc| {
+                        if acc.settings.account.read_only {
+                            return Err(Error::new(format!(
+                                "Account {} is read-only.",
+                                acc.name()
+                            )));
+                        }
+                        Ok((acc.backend.clone(), acc.mailbox_by_path(mailbox_path)?))
+                    });
+                let account = &mut context.accounts[&account_hash];
+                let (destination_backend, destination_mailbox_hash) = match destination {
+                    Ok(v) => v,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                        return;
+                    }
+                };
+                /* Fetch operations are created here, since they need the source account, and
+                 * awaited in order inside the job. */
+                let futures = match envs_to_set
+                    .iter()
+                    .map(|&env_hash| {
+                        let flags = account.collection.get_env(env_hash).flags();
+                        account
+                            .operation(env_hash)
+                            .and_then(|mut op| op.as_bytes())
+                            .map(|fut| (fut, flags))
+                    })
+                    .collect::<Result<Vec<_>>>()
+                {
+                    Ok(v) => v,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(err.to_string()),
+                        ));
+                        return;
+                    }
+                };
+                let verb = if move_ { "moving" } else { "copying" };
+                let total = futures.len();
+                let source_backend = account.backend.clone();
+                let sender = account.sender.clone();
+                let (result_sender, mut receiver) = crate::jobs::oneshot::channel();
+                let fut: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>> =
+                    Box::pin(async move {
+                        let cl = async move {
+                            for (i, (bytes_fut, flags)) in futures.into_iter().enumerate() {
+                                let bytes = bytes_fut.await?;
+                                let job = destination_backend.write().unwrap().save(
+                                    bytes,
+                                    destination_mailbox_hash,
+                                    Some(flags),
+                                )?;
+                                job.await?;
+                                let progress = format!("{} messages: {}/{}", verb, i + 1, total);
+                                let _ = sender.send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                                    StatusEvent::UpdateSubStatus(progress),
+                                )));
+                            }
+                            /* Only remove the source copies once every message has been
+                             * saved in the destination. */
+                            if move_ {
+                                let job = source_backend
+                                    .write()
+                                    .unwrap()
+                                    .delete_messages(env_hashes, mailbox_hash)?;
+                                job.await?;
+                            }
+                            Ok(())
+                        };
+                        let r: Result<()> = cl.await;
+                        let _ = result_sender.send(r);
+                        Ok(())
+                    });
+                let handle = account.job_executor.spawn_specialized(fut);
+                let destination = format!("{}/{}", account_name, mailbox_path);
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: format!("{} messages to {}", verb, destination).into(),
+                        handle,
+                        on_finish: Some(CallbackFn(Box::new(move |context: &mut Context| {
+                            context.replies.push_back(match receiver.try_recv() {
+                                Err(_) | Ok(None) => UIEvent::Notification(
+                                    Some(format!("Stopped {} messages", verb)),
+                                    tr!("Job was canceled."),
+                                    Some(NotificationType::Info),
+                                ),
+                                Ok(Some(Err(err))) => UIEvent::Notification(
+                                    Some(format!("Could not finish {} messages", verb)),
+                                    err.to_string(),
+                                    Some(NotificationType::Error(err.kind)),
+                                ),
+                                Ok(Some(Ok(()))) => UIEvent::Notification(
+                                    Some(format!("Finished {} messages", verb)),
+                                    format!("{} message(s) saved to {}", total, destination),
+                                    Some(NotificationType::Info),
+                                ),
+                            });
+                        }))),
+                        log_level: LogLevel::INFO,
+                    },
+                );
             }
             _ => unreachable!(),
         }
+        if let (true, Some(undo_kind)) = (started, undo_kind) {
+            let done = match a {
+                ListingAction::SetSeen => "marked as seen".to_string(),
+                ListingAction::SetUnseen => "marked as unseen".to_string(),
+                ListingAction::Tag(Add(ref tag_str)) => format!("tagged {}", tag_str),
+                ListingAction::Tag(Remove(ref tag_str)) => format!("untagged {}", tag_str),
+                ListingAction::SetFlags(ref ops) => format!(
+                    "changed ({})",
+                    ops.iter()
+                        .map(|(flag, value)| format!(
+                            "{}{}",
+                            if *value { "+" } else { "-" },
+                            match flag {
+                                Ok(flag) => flag_name(*flag).to_string(),
+                                Err(tag) => tag.to_string(),
+                            }
+                        ))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                ),
+                ListingAction::MoveTo(ref mailbox_path) => format!("moved to {}", mailbox_path),
+                _ => String::new(),
+            };
+            UndoEntry::new(
+                account_hash,
+                mailbox_hash,
+                format!(
+                    "{} message{} {}",
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    done
+                ),
+                undo_grace_period,
+                undo_kind,
+            )
+            .push(context);
+        }
         self.set_dirty(true);
     }
 
+    /// Marks the entry under the cursor as seen and moves the cursor to the
+    /// next entry.
+    fn mark_read_and_advance(&mut self, context: &mut Context) {
+        let items = self.get_cursor_items(context);
+        self.perform_action(context, items, &ListingAction::SetSeen);
+        self.set_movement(PageMovement::Down(1));
+    }
+
+    /// Returns what the `open_entry` shortcut does in this listing, which is
+    /// of index style `style`.
+    fn open_entry_action(&self, style: IndexStyle, context: &Context) -> OpenEntryAction {
+        let (account_hash, mailbox_hash) = self.coordinates();
+        mailbox_settings!(
+            context[account_hash][&mailbox_hash]
+                .listing
+                .open_entry_action
+        )
+        .get(style)
+    }
+
     fn row_updates(&mut self) -> &mut SmallVec<[EnvelopeHash; 8]>;
     fn selection(&mut self) -> &mut HashMap<EnvelopeHash, bool>;
     fn get_focused_items(&self, _context: &Context) -> SmallVec<[EnvelopeHash; 8]>;
+
+    /// Returns the entries under the cursor, ignoring any selection.
+    fn get_cursor_items(&mut self, context: &Context) -> SmallVec<[EnvelopeHash; 8]> {
+        let selection = std::mem::take(self.selection());
+        let ret = self.get_focused_items(context);
+        *self.selection() = selection;
+        ret
+    }
+
     fn redraw_threads_list(
         &mut self,
         context: &Context,
@@ -746,6 +1240,14 @@ pub trait ListingTrait: Component {
     fn set_modifier_active(&mut self, _new_val: bool);
     fn set_modifier_command(&mut self, _new_val: Option<Modifier>);
     fn modifier_command(&self) -> Option<Modifier>;
+    /// Returns the index of the entry drawn at screen position `pos`, if any.
+    fn entry_at(&self, _pos: Pos) -> Option<usize> {
+        None
+    }
+    /// Returns the index of the entry the cursor is on.
+    fn cursor_index(&self) -> usize {
+        0
+    }
     fn set_movement(&mut self, mvm: PageMovement);
     fn focus(&self) -> Focus;
     fn set_focus(&mut self, new_value: Focus, context: &mut Context);
@@ -837,6 +1339,21 @@ enum ShowMenuScrollbar {
     False,
 }
 
+/// Account, mailbox and search query to open on startup, as given on the
+/// command line.
+#[derive(Debug, Default, Clone)]
+pub struct StartupView {
+    pub account: Option<String>,
+    pub mailbox: Option<String>,
+    pub query: Option<String>,
+}
+
+impl StartupView {
+    pub fn is_empty(&self) -> bool {
+        self.account.is_none() && self.mailbox.is_none() && self.query.is_none()
+    }
+}
+
 #[derive(Debug)]
 pub struct Listing {
     component: ListingComponent,
@@ -846,7 +1363,14 @@ pub struct Listing {
     cursor_pos: (usize, MenuEntryCursor),
     menu_cursor_pos: (usize, MenuEntryCursor),
     menu_content: CellBuffer,
+    /// Where the menu was last drawn and the line of `menu_content` shown on
+    /// its first row, for mouse events.
+    menu_area: Option<(Area, usize)>,
+    /// The entry printed on each line of `menu_content`.
+    menu_lines: HashMap<usize, (usize, MenuEntryCursor)>,
     menu_scrollbar_show_timer: crate::jobs::Timer,
+    /// Redraws the countdowns to the next periodic refresh of the accounts.
+    sync_countdown_timer: crate::jobs::Timer,
     show_menu_scrollbar: ShowMenuScrollbar,
     startup_checks_rate: RateLimit,
     id: ComponentId,
@@ -862,6 +1386,15 @@ pub struct Listing {
     prev_ratio: usize,
     menu_width: WidgetWidth,
     focus: ListingFocus,
+    /// Recently used move/copy destinations of each account, most recent
+    /// first.
+    mailbox_target_history: HashMap<AccountHash, VecDeque<String>>,
+    /// Pending startup view, applied once the account's mailboxes are
+    /// available.
+    startup_view: Option<StartupView>,
+    /// The mailbox shown last, whose `on_leave_command` runs when the listing
+    /// moves to another mailbox.
+    entered_mailbox: Option<(AccountHash, MailboxHash)>,
 }
 
 impl fmt::Display for Listing {
@@ -884,6 +1417,8 @@ impl Component for Listing {
         if !is_valid_area!(area) {
             return;
         }
+        self.run_mailbox_hooks(context);
+        self.menu_area = None;
         let upper_left = upper_left!(area);
         let bottom_right = bottom_right!(area);
         let total_cols = get_x(bottom_right) - get_x(upper_left);
@@ -937,6 +1472,7 @@ impl Component for Listing {
             if let Some(s) = self.status.as_mut() {
                 s.draw(grid, area, context);
             } else {
+                let area = self.draw_load_older_row(grid, area, context);
                 self.component.draw(grid, area, context);
             }
         } else if right_component_width == 0 {
@@ -956,8 +1492,12 @@ impl Component for Listing {
             if let Some(s) = self.status.as_mut() {
                 s.draw(grid, (set_x(upper_left, mid + 1), bottom_right), context);
             } else {
-                self.component
-                    .draw(grid, (set_x(upper_left, mid + 1), bottom_right), context);
+                let area = self.draw_load_older_row(
+                    grid,
+                    (set_x(upper_left, mid + 1), bottom_right),
+                    context,
+                );
+                self.component.draw(grid, area, context);
             }
         }
         self.dirty = false;
@@ -974,6 +1514,23 @@ impl Component for Listing {
                 self.menu_content = CellBuffer::new_with_context(0, 0, None, context);
                 self.set_dirty(true);
             }
+            UIEvent::Action(Action::Workspace(_)) => {
+                if !context.is_account_visible(self.cursor_pos.0) {
+                    if let Some(account_pos) =
+                        (0..self.accounts.len()).find(|i| context.is_account_visible(*i))
+                    {
+                        self.cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
+                        self.menu_cursor_pos = self.cursor_pos;
+                        self.change_account(context);
+                    }
+                }
+                if !context.is_account_visible(self.menu_cursor_pos.0) {
+                    self.menu_cursor_pos = self.cursor_pos;
+                }
+                self.menu_content.empty();
+                self.set_dirty(true);
+                return false;
+            }
             UIEvent::Timer(n) if *n == self.menu_scrollbar_show_timer.id() => {
                 if self.show_menu_scrollbar == ShowMenuScrollbar::True {
                     self.show_menu_scrollbar = ShowMenuScrollbar::False;
@@ -982,11 +1539,37 @@ impl Component for Listing {
                 }
                 return true;
             }
+            UIEvent::Timer(n) if *n == self.sync_countdown_timer.id() => {
+                if self.is_menu_visible()
+                    && self
+                        .accounts
+                        .iter()
+                        .any(|entry| context.accounts[entry.index].next_refresh.is_some())
+                {
+                    self.dirty = true;
+                    self.menu_content.empty();
+                }
+                return true;
+            }
             UIEvent::StartupCheck(ref f)
                 if self.component.coordinates().1 == *f && !self.startup_checks_rate.tick() =>
             {
                 return false;
             }
+            UIEvent::FinishedUIDialog(_, ref mut results)
+                if results.downcast_ref::<Option<StartupView>>().is_some() =>
+            {
+                /* Sent by `QueryBuilder`. */
+                if let Some(view) = results
+                    .downcast_mut::<Option<StartupView>>()
+                    .and_then(Option::take)
+                {
+                    self.startup_view = Some(view);
+                    self.apply_startup_view(context);
+                    self.set_dirty(true);
+                }
+                return true;
+            }
             UIEvent::Timer(n) if *n == self.startup_checks_rate.id() => {
                 if self.startup_checks_rate.active {
                     self.startup_checks_rate.reset();
@@ -1004,18 +1587,6 @@ impl Component for Listing {
                 if self.cursor_pos.0 == account_index {
                     self.change_account(context);
                 } else {
-                    let previous_collapsed_mailboxes: BTreeSet<MailboxHash> = self.accounts
-                        [account_index]
-                        .entries
-                        .iter()
-                        .filter_map(|e| {
-                            if e.collapsed {
-                                Some(e.mailbox_hash)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<_>();
                     self.accounts[account_index].entries = context.accounts[&*account_hash]
                         .list_mailboxes()
                         .into_iter()
@@ -1030,11 +1601,8 @@ impl Component for Listing {
                             has_sibling: f.has_sibling,
                             mailbox_hash: f.hash,
                             visible: true,
-                            collapsed: if previous_collapsed_mailboxes.is_empty() {
-                                context.accounts[&*account_hash][&f.hash].conf.collapsed
-                            } else {
-                                previous_collapsed_mailboxes.contains(&f.hash)
-                            },
+                            collapsed: context.accounts[&*account_hash]
+                                .is_mailbox_collapsed(f.hash),
                         })
                         .collect::<_>();
                     self.menu_content.empty();
@@ -1045,6 +1613,9 @@ impl Component for Listing {
                             None => self.get_status(context),
                         })));
                 }
+                if self.startup_view.is_some() {
+                    self.apply_startup_view(context);
+                }
             }
             UIEvent::MailboxDelete((account_hash, mailbox_hash))
             | UIEvent::MailboxCreate((account_hash, mailbox_hash)) => {
@@ -1053,18 +1624,6 @@ impl Component for Listing {
                     .get_index_of(account_hash)
                     .expect("Invalid account_hash in UIEventMailbox{Delete,Create}");
                 self.menu_content.empty();
-                let previous_collapsed_mailboxes: BTreeSet<MailboxHash> = self.accounts
-                    [account_index]
-                    .entries
-                    .iter()
-                    .filter_map(|e| {
-                        if e.collapsed {
-                            Some(e.mailbox_hash)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<_>();
                 self.accounts[account_index].entries = context.accounts[&*account_hash]
                     .list_mailboxes()
                     .into_iter()
@@ -1079,7 +1638,7 @@ impl Component for Listing {
                         has_sibling: f.has_sibling,
                         mailbox_hash: f.hash,
                         visible: true,
-                        collapsed: previous_collapsed_mailboxes.contains(&f.hash),
+                        collapsed: context.accounts[&*account_hash].is_mailbox_collapsed(f.hash),
                     })
                     .collect::<_>();
                 let mut fallback = 0;
@@ -1110,28 +1669,123 @@ impl Component for Listing {
                 self.set_dirty(true);
                 return true;
             }
+            UIEvent::MailboxUpdate((account_hash, mailbox_hash))
+                if context.accounts.contains_key(&*account_hash)
+                    && context.accounts[&*account_hash]
+                        .mailbox_entries
+                        .contains_key(mailbox_hash) =>
+            {
+                Self::fix_threads(*account_hash, *mailbox_hash, context);
+            }
+            UIEvent::ThreadUpdate((account_hash, mailbox_hash), _)
+                if context.accounts.contains_key(&*account_hash)
+                    && context.accounts[&*account_hash]
+                        .mailbox_entries
+                        .contains_key(mailbox_hash) =>
+            {
+                let coordinates = (*account_hash, *mailbox_hash);
+                if Self::fix_threads(coordinates.0, coordinates.1, context) {
+                    /* Threads other than the updated ones changed too. */
+                    *event = UIEvent::MailboxUpdate(coordinates);
+                }
+            }
             UIEvent::ChangeMode(UIMode::Normal) => {
                 self.set_dirty(true);
             }
             UIEvent::Resize => {
                 self.set_dirty(true);
             }
-            UIEvent::Action(Action::ViewMailbox(ref idx)) => {
-                if let Some(MailboxMenuEntry { mailbox_hash, .. }) =
-                    self.accounts[self.cursor_pos.0].entries.get(*idx)
-                {
-                    let account_hash = self.accounts[self.cursor_pos.0].hash;
-                    self.cursor_pos.1 = MenuEntryCursor::Mailbox(*idx);
-                    self.status = None;
-                    self.component
-                        .process_event(&mut UIEvent::VisibilityChange(false), context);
-                    self.component
-                        .set_coordinates((account_hash, *mailbox_hash));
-                    self.menu_content.empty();
-                    self.set_dirty(true);
+            UIEvent::Mouse(ref mev)
+                if matches!(mev, MouseEvent::Press(MouseButton::Left, _, _))
+                    && !matches!(self.menu_width, WidgetWidth::Hold(_)) =>
+            {
+                let pos = mouse_pos(mev);
+                if let Some(entry) = self.menu_entry_at(pos) {
+                    /* Open the clicked mailbox or account status. */
+                    if self.focus == ListingFocus::Menu {
+                        self.focus = ListingFocus::Mailbox;
+                        self.ratio = self.prev_ratio;
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::ScrollUpdate(
+                                ScrollUpdate::End(self.id),
+                            )));
+                    }
+                    self.cursor_pos = entry;
+                    self.change_account(context);
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                            self.get_status(context),
+                        )));
+                    return true;
                 }
-                return true;
-            }
+                if let Some(idx) = self
+                    .component
+                    .entry_at(pos)
+                    .filter(|_| self.status.is_none() && !self.component.unfocused())
+                {
+                    if self.focus == ListingFocus::Menu {
+                        self.focus = ListingFocus::Mailbox;
+                        self.ratio = self.prev_ratio;
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::ScrollUpdate(
+                                ScrollUpdate::End(self.id),
+                            )));
+                        self.set_dirty(true);
+                    }
+                    let cursor = self.component.cursor_index();
+                    if idx == cursor {
+                        /* Clicking the entry under the cursor opens it. */
+                        return self.component.process_event(
+                            &mut UIEvent::Input(
+                                context.settings.shortcuts.listing.open_entry.clone(),
+                            ),
+                            context,
+                        );
+                    } else if idx < cursor {
+                        self.component.set_movement(PageMovement::Up(cursor - idx));
+                    } else {
+                        self.component
+                            .set_movement(PageMovement::Down(idx - cursor));
+                    }
+                    return true;
+                }
+            }
+            UIEvent::Mouse(ref mev)
+                if matches!(
+                    mev,
+                    MouseEvent::Press(MouseButton::WheelUp | MouseButton::WheelDown, _, _)
+                ) && self.status.is_none()
+                    && !self.component.unfocused()
+                    && self.component.entry_at(mouse_pos(mev)).is_some() =>
+            {
+                self.component.set_movement(
+                    if matches!(mev, MouseEvent::Press(MouseButton::WheelUp, _, _)) {
+                        PageMovement::Up(1)
+                    } else {
+                        PageMovement::Down(1)
+                    },
+                );
+                return true;
+            }
+            UIEvent::Action(Action::ViewMailbox(ref idx)) => {
+                if let Some(MailboxMenuEntry { mailbox_hash, .. }) =
+                    self.accounts[self.cursor_pos.0].entries.get(*idx)
+                {
+                    let account_hash = self.accounts[self.cursor_pos.0].hash;
+                    self.cursor_pos.1 = MenuEntryCursor::Mailbox(*idx);
+                    self.status = None;
+                    self.component
+                        .process_event(&mut UIEvent::VisibilityChange(false), context);
+                    self.component
+                        .set_coordinates((account_hash, *mailbox_hash));
+                    self.menu_content.empty();
+                    self.set_dirty(true);
+                }
+                return true;
+            }
             _ => {}
         }
 
@@ -1149,10 +1803,30 @@ impl Component for Listing {
             return true;
         }
 
+        if let UIEvent::Input(ref k) = *event {
+            /* Open the saved search bound to this key, if any. */
+            let entry_idx = context.accounts[self.cursor_pos.0]
+                .saved_search_by_key(k)
+                .and_then(|mailbox_hash| {
+                    self.accounts[self.cursor_pos.0]
+                        .entries
+                        .iter()
+                        .position(|e| e.mailbox_hash == mailbox_hash)
+                });
+            if let Some(idx) = entry_idx {
+                if self.cursor_pos.1 != MenuEntryCursor::Mailbox(idx) {
+                    self.cursor_pos.1 = MenuEntryCursor::Mailbox(idx);
+                    self.menu_cursor_pos = self.cursor_pos;
+                    self.change_account(context);
+                }
+                return true;
+            }
+        }
+
         let shortcuts = self.get_shortcuts(context);
         if self.focus == ListingFocus::Mailbox {
             match *event {
-                UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, _y)))
+                UIEvent::Mouse(MouseEvent::Press(MouseButton::Left, x, _y))
                     if self.is_menu_visible() =>
                 {
                     match self.menu_width {
@@ -1170,7 +1844,7 @@ impl Component for Listing {
                     self.set_dirty(true);
                     return true;
                 }
-                UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, _y))) if self.is_menu_visible() => {
+                UIEvent::Mouse(MouseEvent::Hold(x, _y)) if self.is_menu_visible() => {
                     match self.menu_width {
                         WidgetWidth::Hold(ref mut hx) => {
                             *hx = usize::from(x).saturating_sub(1);
@@ -1180,9 +1854,7 @@ impl Component for Listing {
                     self.set_dirty(true);
                     return true;
                 }
-                UIEvent::Input(Key::Mouse(MouseEvent::Release(x, _y)))
-                    if self.is_menu_visible() =>
-                {
+                UIEvent::Mouse(MouseEvent::Release(x, _y)) if self.is_menu_visible() => {
                     match self.menu_width {
                         WidgetWidth::Hold(_) => {
                             self.menu_width = WidgetWidth::Set(usize::from(x).saturating_sub(1));
@@ -1284,17 +1956,19 @@ impl Component for Listing {
                     };
                     match k {
                         k if shortcut!(k == shortcuts[Shortcuts::LISTING]["next_account"]) => {
-                            if self.cursor_pos.0 + amount < self.accounts.len() {
-                                self.cursor_pos =
-                                    (self.cursor_pos.0 + amount, MenuEntryCursor::Mailbox(0));
+                            if let Some(account_pos) =
+                                self.step_account(self.cursor_pos.0, amount, true, context)
+                            {
+                                self.cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
                             } else {
                                 return true;
                             }
                         }
                         k if shortcut!(k == shortcuts[Shortcuts::LISTING]["prev_account"]) => {
-                            if self.cursor_pos.0 >= amount {
-                                self.cursor_pos =
-                                    (self.cursor_pos.0 - amount, MenuEntryCursor::Mailbox(0));
+                            if let Some(account_pos) =
+                                self.step_account(self.cursor_pos.0, amount, false, context)
+                            {
+                                self.cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
                             } else {
                                 return true;
                             }
@@ -1351,26 +2025,187 @@ impl Component for Listing {
                             self.component.set_style(IndexStyle::Conversations);
                             return true;
                         }
-                        Action::Listing(ListingAction::Import(file_path, mailbox_path)) => {
-                            let account = &mut context.accounts[self.cursor_pos.0];
-                            if let Err(err) = account
-                                .mailbox_by_path(mailbox_path)
-                                .and_then(|mailbox_hash| {
-                                    Ok((
-                                        std::fs::read(file_path).chain_err_summary(|| {
-                                            format!("Could not read {}", file_path.display())
-                                        })?,
-                                        mailbox_hash,
-                                    ))
-                                })
-                                .and_then(|(bytes, mailbox_hash)| {
-                                    account.save(&bytes, mailbox_hash, None)
+                        Action::Listing(ListingAction::Expunge) => {
+                            use crate::actions::MailboxOperation;
+
+                            let (account_hash, mailbox_hash) = self.component.coordinates();
+                            let account = &context.accounts[&account_hash];
+                            let path = match account.mailbox_entries.get(&mailbox_hash) {
+                                Some(entry) => entry.ref_mailbox.path().to_string(),
+                                None => return true,
+                            };
+                            let count = account
+                                .collection
+                                .get_mailbox(mailbox_hash)
+                                .iter()
+                                .filter(|&&env_hash| {
+                                    account.collection.get_env(env_hash).flags().is_trashed()
                                 })
-                            {
+                                .count();
+                            if count == 0 {
                                 context.replies.push_back(UIEvent::StatusEvent(
-                                    StatusEvent::DisplayMessage(err.to_string()),
+                                    StatusEvent::DisplayMessage(tr!(
+                                        "No messages marked for deletion in {}.",
+                                        path
+                                    )),
                                 ));
+                                return true;
                             }
+                            let account_name = account.name().to_string();
+                            context.replies.push_back(UIEvent::GlobalUIDialog(Box::new(
+                                UIConfirmationDialog::new(
+                                    &format!(
+                                        "Permanently remove {} message{} marked for deletion from \
+                                         {}?",
+                                        count,
+                                        if count == 1 { "" } else { "s" },
+                                        path
+                                    ),
+                                    vec![(true, "yes".to_string()), (false, "no".to_string())],
+                                    true,
+                                    Some(Box::new(move |id: ComponentId, result: bool| {
+                                        Some(UIEvent::FinishedUIDialog(
+                                            id,
+                                            Box::new(if result {
+                                                Some(Action::Mailbox(
+                                                    account_name,
+                                                    MailboxOperation::Expunge(path),
+                                                ))
+                                            } else {
+                                                None
+                                            }),
+                                        ))
+                                    })),
+                                    context,
+                                ),
+                            )));
+                            return true;
+                        }
+                        Action::Listing(ListingAction::AttachToThread) => {
+                            self.attach_to_thread(false, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::ThreadJoin) => {
+                            self.attach_to_thread(true, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::ThreadSplit) => {
+                            self.split_thread(context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::TogglePin) => {
+                            self.toggle_pin(context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Snooze(until)) => {
+                            self.snooze(*until, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::UnsnoozeAll) => {
+                            let (account_hash, mailbox_hash) = self.component.coordinates();
+                            let message =
+                                match context.accounts[&account_hash].unsnooze_all(mailbox_hash) {
+                                    Ok(0) => tr!("No threads are snoozed in this mailbox."),
+                                    Ok(1) => tr!("Unsnoozed 1 thread."),
+                                    Ok(n) => tr!("Unsnoozed {} threads.", n),
+                                    Err(err) => err.to_string(),
+                                };
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(message),
+                            ));
+                            context
+                                .replies
+                                .push_back(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Import(file_path, mailbox_path)) => {
+                            use std::{future::Future, pin::Pin};
+
+                            let account = &mut context.accounts[self.cursor_pos.0];
+                            let (source, mailbox_hash) = match account
+                                .mailbox_by_path(mailbox_path)
+                                .and_then(|mailbox_hash| {
+                                    Ok((crate::import::ImportSource::open(file_path)?, mailbox_hash))
+                                }) {
+                                Ok(v) => v,
+                                Err(err) => {
+                                    context.replies.push_back(UIEvent::StatusEvent(
+                                        StatusEvent::DisplayMessage(err.to_string()),
+                                    ));
+                                    return true;
+                                }
+                            };
+                            let sender = account.sender.clone();
+                            let (result_sender, mut receiver) = crate::jobs::oneshot::channel();
+                            let job = crate::import::import_messages(
+                                source,
+                                account.backend.clone(),
+                                mailbox_hash,
+                                move |done, total| {
+                                    let _ = sender.send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                                        StatusEvent::UpdateSubStatus(format!(
+                                            "importing messages: {}/{}",
+                                            done, total
+                                        )),
+                                    )));
+                                },
+                            );
+                            let fut: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>> =
+                                Box::pin(async move {
+                                    let _ = result_sender.send(job.await);
+                                    Ok(())
+                                });
+                            let handle = account.job_executor.spawn_specialized(fut);
+                            let destination = format!("{}/{}", account.name(), mailbox_path);
+                            account.insert_job(
+                                handle.job_id,
+                                JobRequest::Generic {
+                                    name: format!("importing messages to {}", destination).into(),
+                                    handle,
+                                    on_finish: Some(CallbackFn(Box::new(
+                                        move |context: &mut Context| {
+                                            context.replies.push_back(match receiver.try_recv() {
+                                                Err(_) | Ok(None) => UIEvent::Notification(
+                                                    Some(tr!("Stopped importing messages")),
+                                                    tr!("Job was canceled."),
+                                                    Some(NotificationType::Info),
+                                                ),
+                                                Ok(Some(Err(err))) => UIEvent::Notification(
+                                                    Some(tr!("Could not import messages")),
+                                                    err.to_string(),
+                                                    Some(NotificationType::Error(err.kind)),
+                                                ),
+                                                Ok(Some(Ok(total))) => UIEvent::Notification(
+                                                    Some(tr!("Finished importing messages")),
+                                                    format!(
+                                                        "{} message(s) imported to {}",
+                                                        total, destination
+                                                    ),
+                                                    Some(NotificationType::Info),
+                                                ),
+                                            });
+                                        },
+                                    ))),
+                                    log_level: LogLevel::INFO,
+                                },
+                            );
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Tag(AddInteractive)) => {
+                            self.open_tag_selector(true, context);
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Tag(RemoveInteractive)) => {
+                            self.open_tag_selector(false, context);
+                            return true;
+                        }
+                        Action::MailingListAction(MailingListAction::UnsubscribeAssistant) => {
+                            self.open_unsubscribe_assistant(context);
+                            return true;
+                        }
+                        Action::MailingListAction(MailingListAction::Unsubscribe(choices)) => {
+                            let (account_hash, mailbox_hash) = self.component.coordinates();
+                            unsubscribe(account_hash, mailbox_hash, choices.clone(), context);
                             return true;
                         }
                         Action::Listing(a @ ListingAction::SetSeen)
@@ -1381,7 +2216,13 @@ impl Component for Listing {
                         | Action::Listing(a @ ListingAction::CopyToOtherAccount(_, _))
                         | Action::Listing(a @ ListingAction::MoveToOtherAccount(_, _))
                         | Action::Listing(a @ ListingAction::ExportMbox(_, _))
-                        | Action::Listing(a @ ListingAction::Tag(_)) => {
+                        | Action::Listing(a @ ListingAction::Tag(_))
+                        | Action::Listing(a @ ListingAction::SetFlags(_)) => {
+                            if let ListingAction::CopyTo(ref path)
+                            | ListingAction::MoveTo(ref path) = a
+                            {
+                                self.push_mailbox_target(path);
+                            }
                             let focused = self.component.get_focused_items(context);
                             self.component.perform_action(context, focused, a);
                             let mut row_updates: SmallVec<[EnvelopeHash; 8]> = SmallVec::new();
@@ -1514,6 +2355,47 @@ impl Component for Listing {
                             return true;
                         }
                     }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && (shortcut!(
+                                key == shortcuts[Shortcuts::LISTING]["move_to_mailbox"]
+                            ) || shortcut!(
+                                key == shortcuts[Shortcuts::LISTING]["copy_to_mailbox"]
+                            )) =>
+                    {
+                        let move_messages =
+                            shortcut!(key == shortcuts[Shortcuts::LISTING]["move_to_mailbox"]);
+                        self.open_mailbox_selector(move_messages, context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && shortcut!(key == shortcuts[Shortcuts::LISTING]["toggle_pin"]) =>
+                    {
+                        self.toggle_pin(context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && shortcut!(key == shortcuts[Shortcuts::LISTING]["undo"]) =>
+                    {
+                        crate::undo::undo(context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && shortcut!(key == shortcuts[Shortcuts::LISTING]["query_builder"]) =>
+                    {
+                        self.open_query_builder(context);
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if !self.component.unfocused()
+                            && shortcut!(key == shortcuts[Shortcuts::LISTING]["edit_flags"]) =>
+                    {
+                        self.open_flag_editor(context);
+                        return true;
+                    }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Shortcuts::LISTING]["refresh"]) =>
                     {
@@ -1522,7 +2404,7 @@ impl Component for Listing {
                             if let Some(&mailbox_hash) = account.mailboxes_order.get(idx) {
                                 if let Err(err) = account.refresh(mailbox_hash) {
                                     context.replies.push_back(UIEvent::Notification(
-                                        Some("Could not refresh.".to_string()),
+                                        Some(tr!("Could not refresh.")),
                                         err.to_string(),
                                         Some(NotificationType::Error(err.kind)),
                                     ));
@@ -1531,6 +2413,44 @@ impl Component for Listing {
                         }
                         return true;
                     }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Shortcuts::LISTING]["sync_now"]) =>
+                    {
+                        let account = &mut context.accounts[self.cursor_pos.0];
+                        let result = match self.cursor_pos.1 {
+                            MenuEntryCursor::Mailbox(idx) => {
+                                match account.mailboxes_order.get(idx) {
+                                    Some(&mailbox_hash) => account.refresh(mailbox_hash),
+                                    None => Ok(()),
+                                }
+                            }
+                            MenuEntryCursor::Status => account.refresh_all(),
+                        };
+                        if let Err(err) = result {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some(tr!("Could not refresh.")),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                        }
+                        return true;
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Shortcuts::LISTING]["load_older"]) =>
+                    {
+                        let (account_hash, mailbox_hash) = self.component.coordinates();
+                        if let Some(account) = context.accounts.get_mut(&account_hash) {
+                            if let Err(err) = account.fetch_older(mailbox_hash) {
+                                context.replies.push_back(UIEvent::Notification(
+                                    Some(tr!("Could not load older messages.")),
+                                    err.to_string(),
+                                    Some(NotificationType::Error(err.kind)),
+                                ));
+                            }
+                        }
+                        self.set_dirty(true);
+                        return true;
+                    }
                     UIEvent::Input(ref key)
                         if !self.component.unfocused()
                             && shortcut!(
@@ -1607,6 +2527,11 @@ impl Component for Listing {
                         .get_mut(target_mailbox_idx)
                     {
                         target.collapsed = !(target.collapsed);
+                        if let Err(err) = context.accounts[self.menu_cursor_pos.0]
+                            .set_mailbox_collapsed(target.mailbox_hash, target.collapsed)
+                        {
+                            log::warn!("Could not save collapsed mailboxes: {}", err);
+                        }
                         self.dirty = true;
                         self.menu_content.empty();
                         context
@@ -1666,8 +2591,11 @@ impl Component for Listing {
                                     ref mut account_cursor,
                                     ref mut entry_cursor @ MenuEntryCursor::Status,
                                 ) => {
-                                    if *account_cursor > 0 {
-                                        *account_cursor -= 1;
+                                    if let Some(prev) = (0..*account_cursor)
+                                        .rev()
+                                        .find(|i| context.is_account_visible(*i))
+                                    {
+                                        *account_cursor = prev;
                                         *entry_cursor = MenuEntryCursor::Mailbox(
                                             self.accounts[*account_cursor]
                                                 .entries
@@ -1699,6 +2627,10 @@ impl Component for Listing {
                             amount -= 1;
                         }
                     } else if shortcut!(k == shortcuts[Shortcuts::LISTING]["scroll_down"]) {
+                        let accounts_len = self.accounts.len();
+                        let next_visible = |account_pos: usize| {
+                            (account_pos + 1..accounts_len).find(|i| context.is_account_visible(*i))
+                        };
                         while amount > 0 {
                             match self.menu_cursor_pos {
                                 /* If current account has mailboxes, go to first mailbox */
@@ -1712,8 +2644,8 @@ impl Component for Listing {
                                 (
                                     ref mut account_cursor,
                                     ref mut entry_cursor @ MenuEntryCursor::Status,
-                                ) if *account_cursor + 1 < self.accounts.len() => {
-                                    *account_cursor += 1;
+                                ) if next_visible(*account_cursor).is_some() => {
+                                    *account_cursor = next_visible(*account_cursor).unwrap();
                                     *entry_cursor = MenuEntryCursor::Status;
                                 }
                                 /* If current account has no mailboxes and there is no next
@@ -1734,8 +2666,8 @@ impl Component for Listing {
                                         {
                                             break;
                                         }
-                                    } else if *account_cursor + 1 < self.accounts.len() {
-                                        *account_cursor += 1;
+                                    } else if let Some(next) = next_visible(*account_cursor) {
+                                        *account_cursor = next;
                                         self.menu_cursor_pos.1 = MenuEntryCursor::Status;
                                         break;
                                     } else {
@@ -1842,9 +2774,10 @@ impl Component for Listing {
                         k if shortcut!(k == shortcuts[Shortcuts::LISTING]["next_account"])
                             || shortcut!(k == shortcuts[Shortcuts::LISTING]["next_page"]) =>
                         {
-                            if self.menu_cursor_pos.0 + amount < self.accounts.len() {
-                                self.menu_cursor_pos =
-                                    (self.menu_cursor_pos.0 + amount, MenuEntryCursor::Mailbox(0));
+                            if let Some(account_pos) =
+                                self.step_account(self.menu_cursor_pos.0, amount, true, context)
+                            {
+                                self.menu_cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
                             } else {
                                 return true;
                             }
@@ -1852,9 +2785,10 @@ impl Component for Listing {
                         k if shortcut!(k == shortcuts[Shortcuts::LISTING]["prev_account"])
                             || shortcut!(k == shortcuts[Shortcuts::LISTING]["prev_page"]) =>
                         {
-                            if self.menu_cursor_pos.0 >= amount {
-                                self.menu_cursor_pos =
-                                    (self.menu_cursor_pos.0 - amount, MenuEntryCursor::Mailbox(0));
+                            if let Some(account_pos) =
+                                self.step_account(self.menu_cursor_pos.0, amount, false, context)
+                            {
+                                self.menu_cursor_pos = (account_pos, MenuEntryCursor::Mailbox(0));
                             } else {
                                 return true;
                             }
@@ -1890,6 +2824,46 @@ impl Component for Listing {
                     .push_back(UIEvent::Action(Tab(New(Some(Box::new(mgr))))));
                 return true;
             }
+            UIEvent::Action(Action::Tab(TabAction::Statistics)) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                let view = StatisticsView::new(account_hash, context);
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
+            UIEvent::Action(Action::Tab(TabAction::Duplicates(whole_account))) => {
+                let (account_hash, mailbox_hash) = self.component.coordinates();
+                let view = DuplicatesView::new(
+                    account_hash,
+                    if whole_account {
+                        None
+                    } else {
+                        Some(mailbox_hash)
+                    },
+                    context,
+                );
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
+            UIEvent::Action(Action::Tab(TabAction::Drafts)) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                let view = DraftsView::new(account_hash, context);
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
+            UIEvent::Action(Action::Tab(TabAction::Scheduled)) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                let view = ScheduledView::new(account_hash, context);
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
             UIEvent::Action(Action::Compose(ComposeAction::Mailto(ref mailto))) => {
                 let account_hash = context.accounts[self.cursor_pos.0].hash();
                 let mut composer = Composer::with_account(account_hash, context);
@@ -1899,8 +2873,73 @@ impl Component for Listing {
                     .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
                 return true;
             }
+            UIEvent::Action(Action::Compose(ComposeAction::ComposeFromTemplate(None))) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                match compose::templates::list_templates(context, account_hash) {
+                    Ok(names) if names.is_empty() => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(tr!("No templates found.")),
+                        ));
+                    }
+                    Ok(names) => {
+                        context.replies.push_back(UIEvent::GlobalUIDialog(Box::new(
+                            UIDialog::new(
+                                "select template",
+                                names.into_iter().map(|n| (n.clone(), n)).collect(),
+                                true,
+                                Some(Box::new(move |id: ComponentId, results: &[String]| {
+                                    Some(UIEvent::FinishedUIDialog(
+                                        id,
+                                        Box::new(results.first().map(|name| {
+                                            Action::Compose(ComposeAction::ComposeFromTemplate(
+                                                Some(name.clone()),
+                                            ))
+                                        })),
+                                    ))
+                                })),
+                                context,
+                            ),
+                        )));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not list templates")),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
+            UIEvent::Action(Action::Compose(ComposeAction::ComposeFromTemplate(Some(
+                ref name,
+            )))) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                let reply_to = self.component.get_focused_items(context).first().copied();
+                match compose::templates::compose_from_template(
+                    name,
+                    account_hash,
+                    reply_to,
+                    context,
+                ) {
+                    Ok(composer) => {
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(tr!("Could not open template")),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
             UIEvent::StartupCheck(_)
             | UIEvent::MailboxUpdate(_)
+            | UIEvent::ThreadUpdate(_, _)
             | UIEvent::EnvelopeUpdate(_)
             | UIEvent::EnvelopeRename(_, _)
             | UIEvent::EnvelopeRemove(_, _) => {
@@ -2010,7 +3049,7 @@ impl Component for Listing {
                     .count()
                     .ok()
                     .unwrap_or((0, 0));
-                format!(
+                tr!(
                     "Mailbox: {}, Messages: {}, New: {}{}",
                     account[&mailbox_hash].name(),
                     total,
@@ -2028,6 +3067,25 @@ impl Component for Listing {
 }
 
 impl Listing {
+    /// Adopts orphans if heuristic threading is enabled and reapplies the
+    /// user's threading corrections. Returns `true` if any threads changed.
+    fn fix_threads(
+        account_hash: AccountHash,
+        mailbox_hash: MailboxHash,
+        context: &Context,
+    ) -> bool {
+        let account = &context.accounts[&account_hash];
+        let adopted = *mailbox_settings!(
+            context[account_hash][&mailbox_hash]
+                .listing
+                .heuristic_threading
+        ) && account.collection.adopt_orphans(mailbox_hash);
+        account
+            .collection
+            .apply_overlay(mailbox_hash, &account.thread_overlay)
+            || adopted
+    }
+
     pub fn new(context: &mut Context) -> Self {
         let account_entries: Vec<AccountMenuEntry> = context
             .accounts
@@ -2044,7 +3102,7 @@ impl Listing {
                         has_sibling: f.has_sibling,
                         mailbox_hash: f.hash,
                         visible: true,
-                        collapsed: a[&f.hash].conf.collapsed,
+                        collapsed: a.is_mailbox_collapsed(f.hash),
                     })
                     .collect::<_>();
 
@@ -2068,10 +3126,16 @@ impl Listing {
             cursor_pos: (0, MenuEntryCursor::Mailbox(0)),
             menu_cursor_pos: (0, MenuEntryCursor::Mailbox(0)),
             menu_content: CellBuffer::new_with_context(0, 0, None, context),
+            menu_area: None,
+            menu_lines: HashMap::default(),
             menu_scrollbar_show_timer: context.job_executor.clone().create_timer(
                 std::time::Duration::from_secs(0),
                 std::time::Duration::from_millis(1200),
             ),
+            sync_countdown_timer: context.job_executor.clone().create_timer(
+                std::time::Duration::from_secs(1),
+                std::time::Duration::from_secs(1),
+            ),
             show_menu_scrollbar: ShowMenuScrollbar::Never,
             startup_checks_rate: RateLimit::new(2, 1000, context.job_executor.clone()),
             theme_default: conf::value(context, "theme_default"),
@@ -2086,19 +3150,538 @@ impl Listing {
             menu_width: WidgetWidth::Unset,
             focus: ListingFocus::Mailbox,
             cmd_buf: String::with_capacity(4),
+            mailbox_target_history: HashMap::default(),
+            entered_mailbox: None,
+            startup_view: None,
         };
         ret.change_account(context);
         ret
     }
 
+    /// Focuses the account and mailbox of `view` and applies its search query,
+    /// waiting for the account's mailboxes to be fetched if necessary.
+    pub fn set_startup_view(&mut self, view: StartupView, context: &mut Context) {
+        self.startup_view = Some(view);
+        self.apply_startup_view(context);
+    }
+
+    fn apply_startup_view(&mut self, context: &mut Context) {
+        let view = match self.startup_view.take() {
+            Some(view) => view,
+            None => return,
+        };
+        let account_idx = match view.account {
+            Some(ref name) => match context.accounts.values().position(|a| a.name == *name) {
+                Some(idx) => idx,
+                None => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(tr!("Could not open startup view")),
+                        tr!("Account `{}` not found.", name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                }
+            },
+            None => self.cursor_pos.0,
+        };
+        if self.cursor_pos.0 != account_idx {
+            self.cursor_pos = (account_idx, MenuEntryCursor::Mailbox(0));
+            self.change_account(context);
+        }
+        if view.mailbox.is_none() && view.query.is_none() {
+            return;
+        }
+        if context.accounts[account_idx].mailbox_entries.is_empty() {
+            /* Mailboxes have not been fetched yet, try again on the next account
+             * status change. */
+            self.startup_view = Some(view);
+            return;
+        }
+        if let Some(ref path) = view.mailbox {
+            let entry_idx = context.accounts[account_idx]
+                .mailbox_by_path(path)
+                .ok()
+                .and_then(|mailbox_hash| {
+                    self.accounts[account_idx]
+                        .entries
+                        .iter()
+                        .position(|e| e.mailbox_hash == mailbox_hash)
+                });
+            match entry_idx {
+                Some(idx) => {
+                    if self.cursor_pos.1 != MenuEntryCursor::Mailbox(idx) {
+                        self.cursor_pos.1 = MenuEntryCursor::Mailbox(idx);
+                        self.change_account(context);
+                    }
+                }
+                None => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(tr!("Could not open startup view")),
+                        tr!(
+                            "Subscribed mailbox `{}` not found in account `{}`.",
+                            path,
+                            context.accounts[account_idx].name
+                        ),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                }
+            }
+        }
+        if let Some(query) = view.query {
+            self.component.process_event(
+                &mut UIEvent::Action(Action::Listing(ListingAction::Search(query))),
+                context,
+            );
+        }
+    }
+
+    /// Attaches the selected messages to the thread under the cursor. With
+    /// `join`, whole threads are merged and the change is stored in the
+    /// account's thread overlay.
+    fn attach_to_thread(&mut self, join: bool, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let selection = self
+            .component
+            .selection()
+            .iter()
+            .filter(|(_, v)| **v)
+            .map(|(k, _)| *k)
+            .collect::<Vec<EnvelopeHash>>();
+        if selection.is_empty() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "Select the messages to attach, then move the cursor to the target thread."
+                        .to_string(),
+                )));
+            return;
+        }
+        let cursor_items = self.component.get_cursor_items(context);
+        let account = &mut context.accounts[&account_hash];
+        let target = cursor_items
+            .into_iter()
+            .filter(|h| !selection.contains(h) && account.collection.contains_key(h))
+            .min_by_key(|h| account.collection.get_env(*h).date());
+        let result = match target {
+            None => Err(Error::new(
+                "Move the cursor to the thread to attach the selected messages to.",
+            )),
+            Some(target) if join => {
+                let threads = account.collection.get_threads(mailbox_hash);
+                let root_message_id = |env_hash: EnvelopeHash| {
+                    let node = threads.envelope_node(env_hash)?;
+                    let root = threads
+                        .thread_ref(threads.find_group(threads[&node].group))
+                        .root();
+                    threads.node_message_id(root)
+                };
+                let joins = match root_message_id(target) {
+                    Some(parent) => selection
+                        .iter()
+                        .filter_map(|h| root_message_id(*h))
+                        .filter(|child| *child != parent)
+                        .map(|child| (child, parent.clone()))
+                        .collect::<Vec<(String, String)>>(),
+                    None => vec![],
+                };
+                drop(threads);
+                for (child, parent) in joins {
+                    account.thread_overlay.set_parent(child, parent);
+                }
+                account.save_thread_overlay()
+            }
+            Some(target) => account.collection.graft(mailbox_hash, &selection, target),
+        };
+        if let Err(err) = result {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    err.to_string(),
+                )));
+            return;
+        }
+        for v in self.component.selection().values_mut() {
+            *v = false;
+        }
+        self.component.row_updates().extend(selection);
+        if join {
+            for h in context.accounts[&account_hash].apply_thread_overlay() {
+                if h != mailbox_hash {
+                    context
+                        .replies
+                        .push_back(UIEvent::MailboxUpdate((account_hash, h)));
+                }
+            }
+        }
+        context
+            .replies
+            .push_back(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+    }
+
+    /// Detaches the message under the cursor and its replies to a thread of
+    /// their own, storing the change in the account's thread overlay.
+    fn split_thread(&mut self, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let cursor_items = self.component.get_cursor_items(context);
+        let account = &mut context.accounts[&account_hash];
+        let message_id = if let [env_hash] = cursor_items.as_slice() {
+            let threads = account.collection.get_threads(mailbox_hash);
+            match threads.envelope_node(*env_hash) {
+                Some(node) if threads[&node].has_parent() => threads
+                    .node_message_id(node)
+                    .ok_or_else(|| Error::new("Message has no Message-ID and cannot be split.")),
+                _ => Err(Error::new("Message already starts its own thread.")),
+            }
+        } else {
+            Err(Error::new(
+                "Move the cursor to a single message; switch to the plain or threaded listing to \
+                 pick one within a thread.",
+            ))
+        };
+        if let Err(err) = message_id.and_then(|message_id| {
+            account.thread_overlay.detach(message_id);
+            account.save_thread_overlay()
+        }) {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    err.to_string(),
+                )));
+            return;
+        }
+        for h in context.accounts[&account_hash].apply_thread_overlay() {
+            context
+                .replies
+                .push_back(UIEvent::MailboxUpdate((account_hash, h)));
+        }
+    }
+
+    /// Runs the `on_leave_command` of the previously shown mailbox and the
+    /// `on_enter_command` of the current one, if the listing has moved to
+    /// another mailbox.
+    fn run_mailbox_hooks(&mut self, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let current = context
+            .accounts
+            .get(&account_hash)
+            .filter(|account| account.mailbox_entries.contains_key(&mailbox_hash))
+            .map(|_| (account_hash, mailbox_hash));
+        if current == self.entered_mailbox {
+            return;
+        }
+        if let Some((account_hash, mailbox_hash)) =
+            std::mem::replace(&mut self.entered_mailbox, current)
+        {
+            Self::run_mailbox_hook(account_hash, mailbox_hash, false, context);
+        }
+        if let Some((account_hash, mailbox_hash)) = current {
+            Self::run_mailbox_hook(account_hash, mailbox_hash, true, context);
+        }
+    }
+
+    /// Runs the `on_enter_command` or `on_leave_command` of a mailbox as a
+    /// background job. The command's output is written to the log.
+    fn run_mailbox_hook(
+        account_hash: AccountHash,
+        mailbox_hash: MailboxHash,
+        enter: bool,
+        context: &mut Context,
+    ) {
+        use std::{future::Future, pin::Pin};
+
+        let account = match context.accounts.get_mut(&account_hash) {
+            Some(account) => account,
+            None => return,
+        };
+        let (command, path) = match account.mailbox_entries.get(&mailbox_hash) {
+            Some(entry) => (
+                if enter {
+                    entry.conf.on_enter_command.clone()
+                } else {
+                    entry.conf.on_leave_command.clone()
+                },
+                entry.ref_mailbox.path().to_string(),
+            ),
+            None => return,
+        };
+        let command = match command {
+            Some(command) => command,
+            None => return,
+        };
+        let hook = if enter {
+            "on_enter_command"
+        } else {
+            "on_leave_command"
+        };
+        let account_name = account.name().to_string();
+        let name = format!("{} of {}", hook, path);
+        let job_name = name.clone();
+        let fut: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>> =
+            Box::pin(async move {
+                let output = std::process::Command::new("sh")
+                    .args(["-c", &command])
+                    .env("MELI_ACCOUNT", &account_name)
+                    .env("MELI_MAILBOX", &path)
+                    .stdin(std::process::Stdio::null())
+                    .output()
+                    .chain_err_summary(|| format!("Could not execute `{}`", command))?;
+                for (stream, bytes) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+                    let text = String::from_utf8_lossy(bytes);
+                    if !text.trim().is_empty() {
+                        log::info!("{} `{}` {}:\n{}", name, command, stream, text.trim_end());
+                    }
+                }
+                if !output.status.success() {
+                    return Err(Error::new(format!(
+                        "`{}` exited with {}",
+                        command, output.status
+                    )));
+                }
+                Ok(())
+            });
+        let handle = account.job_executor.spawn_blocking(fut);
+        account.insert_job(
+            handle.job_id,
+            JobRequest::Generic {
+                name: job_name.into(),
+                handle,
+                on_finish: None,
+                log_level: LogLevel::TRACE,
+            },
+        );
+    }
+
+    /// Pins the selected threads, or the thread under the cursor, to the top
+    /// of the listing, or unpins them if they are all pinned already.
+    fn toggle_pin(&mut self, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let items = self.component.get_focused_items(context);
+        if items.is_empty() {
+            return;
+        }
+        let message =
+            match context.accounts[&account_hash].toggle_pinned(mailbox_hash, items.as_slice()) {
+                Ok(true) => tr!("Pinned to the top of the listing."),
+                Ok(false) => tr!("Unpinned."),
+                Err(err) => err.to_string(),
+            };
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+        for v in self.component.selection().values_mut() {
+            *v = false;
+        }
+        context
+            .replies
+            .push_back(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+    }
+
+    /// Hides the selected threads, or the thread under the cursor, from the
+    /// listing until `until`.
+    fn snooze(&mut self, until: melib::datetime::UnixTimestamp, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let items = self.component.get_focused_items(context);
+        if items.is_empty() {
+            return;
+        }
+        if until <= melib::datetime::now() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                    "Snooze time is in the past."
+                ))));
+            return;
+        }
+        let message =
+            match context.accounts[&account_hash].snooze(mailbox_hash, items.as_slice(), until) {
+                Ok(()) => tr!(
+                    "Snoozed until {}.",
+                    melib::datetime::timestamp_to_string(until, Some("%Y-%m-%d %H:%M"), false)
+                ),
+                Err(err) => err.to_string(),
+            };
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message)));
+        for v in self.component.selection().values_mut() {
+            *v = false;
+        }
+        context
+            .replies
+            .push_back(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+    }
+
+    /// Records `path` as the most recently used move/copy destination of the
+    /// current account.
+    fn push_mailbox_target(&mut self, path: &str) {
+        const MAX_HISTORY: usize = 10;
+        let history = self
+            .mailbox_target_history
+            .entry(self.component.coordinates().0)
+            .or_default();
+        history.retain(|p| p != path);
+        history.push_front(path.to_string());
+        history.truncate(MAX_HISTORY);
+    }
+
+    /// Opens a fuzzy-searchable selector of the current account's mailboxes
+    /// as destinations for moving or copying the focused entries.
+    fn open_mailbox_selector(&mut self, move_messages: bool, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let account = &context.accounts[&account_hash];
+        let paths = account
+            .mailboxes_order
+            .iter()
+            .filter(|h| **h != mailbox_hash)
+            .filter_map(|h| account.mailbox_entries.get(h))
+            .map(|entry| entry.ref_mailbox.path().to_string())
+            .collect::<Vec<String>>();
+        let mut entries = self
+            .mailbox_target_history
+            .get(&account_hash)
+            .map(|history| {
+                history
+                    .iter()
+                    .filter(|p| paths.contains(p))
+                    .cloned()
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        for p in paths {
+            if !entries.contains(&p) {
+                entries.push(p);
+            }
+        }
+        context
+            .replies
+            .push_back(UIEvent::GlobalUIDialog(Box::new(MailboxSelector::new(
+                entries,
+                move_messages,
+                context,
+            ))));
+    }
+
+    /// Opens an editor of the flags and tags of the focused entries.
+    fn open_flag_editor(&mut self, context: &mut Context) {
+        let account_hash = self.component.coordinates().0;
+        let env_hashes = self.component.get_focused_items(context);
+        if env_hashes.is_empty() {
+            return;
+        }
+        let editor = FlagEditor::new(&context.accounts[&account_hash], &env_hashes, context);
+        context
+            .replies
+            .push_back(UIEvent::GlobalUIDialog(Box::new(editor)));
+    }
+
+    /// Opens a dialog for choosing tags to add to or remove from the focused
+    /// entries.
+    fn open_tag_selector(&mut self, add: bool, context: &mut Context) {
+        let account_hash = self.component.coordinates().0;
+        let env_hashes = self.component.get_focused_items(context);
+        match tag_selector(&context.accounts[&account_hash], &env_hashes, add, context) {
+            Ok(dialog) => context
+                .replies
+                .push_back(UIEvent::GlobalUIDialog(Box::new(dialog))),
+            Err(msg) => context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(msg))),
+        }
+    }
+
+    fn open_unsubscribe_assistant(&mut self, context: &mut Context) {
+        let account_hash = self.component.coordinates().0;
+        let env_hashes = self.component.get_focused_items(context);
+        match unsubscribe_assistant(&context.accounts[&account_hash], &env_hashes, context) {
+            Ok(dialog) => context
+                .replies
+                .push_back(UIEvent::GlobalUIDialog(Box::new(dialog))),
+            Err(msg) => context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(msg))),
+        }
+    }
+
+    /// Opens a form for building a search query, with the current
+    /// account's mailboxes as search scopes.
+    fn open_query_builder(&mut self, context: &mut Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        let account = &context.accounts[&account_hash];
+        let mailboxes = std::iter::once(mailbox_hash)
+            .chain(
+                account
+                    .mailboxes_order
+                    .iter()
+                    .copied()
+                    .filter(|h| *h != mailbox_hash),
+            )
+            .filter_map(|h| account.mailbox_entries.get(&h))
+            .map(|entry| entry.ref_mailbox.path().to_string())
+            .collect::<Vec<String>>();
+        context
+            .replies
+            .push_back(UIEvent::GlobalUIDialog(Box::new(QueryBuilder::new(
+                mailboxes, context,
+            ))));
+    }
+
+    /// Draws a row on the last line of `area` that offers to load the older
+    /// messages the backend left out of the current mailbox, if there are
+    /// any, and returns the area left for the listing above it.
+    fn draw_load_older_row(
+        &self,
+        grid: &mut CellBuffer,
+        area: Area,
+        context: &mut Context,
+    ) -> Area {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        if height!(area) < 2 || !context.accounts.contains_key(&account_hash) {
+            return area;
+        }
+        let account = &context.accounts[&account_hash];
+        let older = account.older_envelopes(mailbox_hash);
+        if older == 0 {
+            return area;
+        }
+        let text = if account
+            .active_jobs
+            .values()
+            .any(|j| j.is_fetch(mailbox_hash))
+        {
+            tr!("Loading older messages, {} left...", older)
+        } else {
+            tr!(
+                "{} older messages not loaded, press {} to load more.",
+                older, context.settings.shortcuts.listing.load_older
+            )
+        };
+        let upper_left = upper_left!(area);
+        let bottom_right = bottom_right!(area);
+        let row_area = (set_y(upper_left, get_y(bottom_right)), bottom_right);
+        clear_area(grid, row_area, self.theme_default);
+        write_string_to_grid(
+            &text,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::ITALICS,
+            row_area,
+            None,
+        );
+        context.dirty_areas.push_back(row_area);
+        (upper_left, set_y(bottom_right, get_y(bottom_right) - 1))
+    }
+
     fn draw_menu(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         clear_area(grid, area, self.theme_default);
-        let total_height: usize = 3 * (self.accounts.len())
-            + self
-                .accounts
-                .iter()
-                .map(|entry| entry.entries.len() + 1)
-                .sum::<usize>();
+        let total_height: usize = self
+            .accounts
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| context.is_account_visible(*idx))
+            .map(|(_, entry)| entry.entries.len() + 4)
+            .sum::<usize>();
         let min_width: usize = 2 * width!(area);
         let (width, height) = self.menu_content.size();
         let cursor = match self.focus {
@@ -2109,10 +3692,14 @@ impl Listing {
             let _ = self.menu_content.resize(min_width * 2, total_height, None);
             let bottom_right = pos_dec(self.menu_content.size(), (1, 1));
             let mut y = 0;
+            self.menu_lines.clear();
             for a in 0..self.accounts.len() {
                 if y > get_y(bottom_right) {
                     break;
                 }
+                if !context.is_account_visible(a) {
+                    continue;
+                }
                 y += self.print_account(((0, y), bottom_right), a, context);
                 y += 3;
             }
@@ -2121,13 +3708,14 @@ impl Listing {
         let rows = height!(area);
         let (width, height) = self.menu_content.size();
         const SCROLLING_CONTEXT: usize = 3;
-        let y_offset = (cursor.0)
-            + self
-                .accounts
-                .iter()
-                .take(cursor.0)
-                .map(|entry| entry.entries.len() + 1)
-                .sum::<usize>()
+        let y_offset = self
+            .accounts
+            .iter()
+            .enumerate()
+            .take(cursor.0)
+            .filter(|(idx, _)| context.is_account_visible(*idx))
+            .map(|(_, entry)| entry.entries.len() + 2)
+            .sum::<usize>()
             + match cursor.1 {
                 MenuEntryCursor::Status => 0,
                 MenuEntryCursor::Mailbox(idx) => idx + 1,
@@ -2138,16 +3726,15 @@ impl Listing {
         } else {
             rows * y_offset.wrapping_div(rows).saturating_sub(1) + y_offset.wrapping_rem(rows)
         };
+        let first_line = std::cmp::min((height - 1).saturating_sub(rows), skip_offset);
+        self.menu_area = Some((area, first_line));
 
         copy_area(
             grid,
             &self.menu_content,
             area,
             (
-                (
-                    0,
-                    std::cmp::min((height - 1).saturating_sub(rows), skip_offset),
-                ),
+                (0, first_line),
                 (width - 1, std::cmp::min(skip_offset + rows, height - 1)),
             ),
         );
@@ -2207,6 +3794,8 @@ impl Listing {
             mailbox_idx: MailboxHash,
             count: Option<usize>,
             collapsed_count: Option<usize>,
+            /// The mailbox has newsletter digests, so its count is dimmed.
+            digest: bool,
         }
         // Each entry and its index in the account
         let mailboxes: HashMap<MailboxHash, Mailbox> = context.accounts[self.accounts[aidx].index]
@@ -2240,6 +3829,10 @@ impl Listing {
         ) in self.accounts[aidx].entries.iter().enumerate()
         {
             if mailboxes[&mailbox_hash].is_subscribed() {
+                let digest = context.accounts[self.accounts[aidx].index][&mailbox_hash]
+                    .conf
+                    .digest
+                    .is_some();
                 match context.accounts[self.accounts[aidx].index][&mailbox_hash].status {
                     crate::conf::accounts::MailboxStatus::Failed(_) => {
                         lines.push(Line {
@@ -2251,6 +3844,7 @@ impl Listing {
                             mailbox_idx: mailbox_hash,
                             count: None,
                             collapsed_count: None,
+                            digest,
                         });
                     }
                     _ => {
@@ -2263,6 +3857,7 @@ impl Listing {
                             mailbox_idx: mailbox_hash,
                             count: mailboxes[&mailbox_hash].count().ok().map(|(v, _)| v),
                             collapsed_count: None,
+                            digest,
                         });
                     }
                 }
@@ -2284,7 +3879,9 @@ impl Listing {
         };
 
         /* Print account name first */
-        write_string_to_grid(
+        self.menu_lines
+            .insert(get_y(upper_left), (aidx, MenuEntryCursor::Status));
+        let (x, _) = write_string_to_grid(
             &self.accounts[aidx].name,
             &mut self.menu_content,
             account_attrs.fg,
@@ -2294,6 +3891,27 @@ impl Listing {
             None,
         );
 
+        /* Then when it was last synced and when the next periodic refresh is due */
+        let sync_status = {
+            let account = &context.accounts[self.accounts[aidx].index];
+            sync_status(
+                account.last_synced,
+                account.next_refresh,
+                melib::datetime::now(),
+            )
+        };
+        if !sync_status.is_empty() {
+            write_string_to_grid(
+                &format!(" ({})", sync_status),
+                &mut self.menu_content,
+                crate::conf::value(context, "mail.sidebar_unread_count").fg,
+                account_attrs.bg,
+                account_attrs.attrs,
+                ((x, get_y(upper_left)), bottom_right),
+                None,
+            );
+        }
+
         if lines.is_empty() {
             write_string_to_grid(
                 "offline",
@@ -2335,6 +3953,8 @@ impl Listing {
                 }
             }
             self.accounts[aidx].entries[idx].visible = true;
+            self.menu_lines
+                .insert(y, (aidx, MenuEntryCursor::Mailbox(idx)));
             if l.collapsed {
                 skip = Some(l.depth);
                 // Calculate total unseen from hidden children mailboxes
@@ -2344,7 +3964,9 @@ impl Listing {
                     if lines[idx].depth <= l.depth {
                         break;
                     }
-                    counter += lines[idx].count.unwrap_or(0);
+                    if !lines[idx].digest {
+                        counter += lines[idx].count.unwrap_or(0);
+                    }
                     idx += 1;
                 }
                 l.collapsed_count = Some(counter);
@@ -2504,7 +4126,9 @@ impl Listing {
                 unread_count_att.fg,
                 unread_count_att.bg,
                 unread_count_att.attrs
-                    | if l.count.unwrap_or(0) > 0 {
+                    | if l.digest {
+                        Attr::DIM
+                    } else if l.count.unwrap_or(0) > 0 {
                         Attr::BOLD
                     } else {
                         Attr::DEFAULT
@@ -2534,19 +4158,29 @@ impl Listing {
         }
     }
 
+    /// Returns the position of the account `amount` accounts after (or before,
+    /// if `forward` is `false`) `account_pos`, skipping accounts outside the
+    /// active workspace.
+    fn step_account(
+        &self,
+        account_pos: usize,
+        amount: usize,
+        forward: bool,
+        context: &Context,
+    ) -> Option<usize> {
+        if amount == 0 {
+            return Some(account_pos);
+        }
+        let visible = (0..self.accounts.len()).filter(|i| context.is_account_visible(*i));
+        if forward {
+            visible.filter(|i| *i > account_pos).nth(amount - 1)
+        } else {
+            visible.filter(|i| *i < account_pos).rev().nth(amount - 1)
+        }
+    }
+
     fn change_account(&mut self, context: &mut Context) {
         let account_hash = context.accounts[self.cursor_pos.0].hash();
-        let previous_collapsed_mailboxes: BTreeSet<MailboxHash> = self.accounts[self.cursor_pos.0]
-            .entries
-            .iter()
-            .filter_map(|e| {
-                if e.collapsed {
-                    Some(e.mailbox_hash)
-                } else {
-                    None
-                }
-            })
-            .collect::<_>();
         self.accounts[self.cursor_pos.0].entries = context.accounts[self.cursor_pos.0]
             .list_mailboxes()
             .into_iter()
@@ -2561,11 +4195,7 @@ impl Listing {
                 has_sibling: f.has_sibling,
                 mailbox_hash: f.hash,
                 visible: true,
-                collapsed: if previous_collapsed_mailboxes.is_empty() {
-                    context.accounts[self.cursor_pos.0][&f.hash].conf.collapsed
-                } else {
-                    previous_collapsed_mailboxes.contains(&f.hash)
-                },
+                collapsed: context.accounts[self.cursor_pos.0].is_mailbox_collapsed(f.hash),
             })
             .collect::<_>();
         match self.cursor_pos.1 {
@@ -2622,7 +4252,174 @@ impl Listing {
             )));
     }
 
+    /// Returns the menu entry drawn at screen position `pos`, if any.
+    fn menu_entry_at(&self, pos: Pos) -> Option<(usize, MenuEntryCursor)> {
+        let (area, first_line) = self.menu_area?;
+        if !area_contains(area, pos) {
+            return None;
+        }
+        self.menu_lines
+            .get(&(first_line + get_y(pos) - get_y(upper_left!(area))))
+            .copied()
+    }
+
     fn is_menu_visible(&self) -> bool {
         !matches!(self.component.focus(), Focus::EntryFullscreen) && self.menu_visibility
     }
 }
+
+/// Returns when an account was last synced and how long until its next
+/// periodic refresh, e.g. `synced 10:42, next in 4m 05s`.
+fn sync_status(
+    last_synced: Option<melib::datetime::UnixTimestamp>,
+    next_refresh: Option<melib::datetime::UnixTimestamp>,
+    now: melib::datetime::UnixTimestamp,
+) -> String {
+    let mut parts = vec![];
+    if let Some(t) = last_synced {
+        parts.push(format!(
+            "synced {}",
+            melib::datetime::timestamp_to_string(t, Some("%H:%M"), false)
+        ));
+    }
+    if let Some(t) = next_refresh {
+        let secs = t.saturating_sub(now);
+        parts.push(if secs >= 3600 {
+            format!("next in {}h {:02}m", secs / 3600, (secs % 3600) / 60)
+        } else if secs >= 60 {
+            format!("next in {}m {:02}s", secs / 60, secs % 60)
+        } else {
+            format!("next in {}s", secs)
+        });
+    }
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listing_entry_at_pos() {
+        /* 10 rows, with the cursor on the second page */
+        let area = ((5, 2), (40, 11));
+        assert_eq!(entry_at_pos(area, 1, 13, 30, (5, 2)), Some(10));
+        assert_eq!(entry_at_pos(area, 1, 13, 30, (40, 11)), Some(19));
+        assert_eq!(entry_at_pos(area, 1, 13, 15, (6, 8)), None);
+        assert_eq!(entry_at_pos(area, 1, 13, 30, (4, 2)), None);
+        assert_eq!(entry_at_pos(area, 1, 13, 30, (5, 12)), None);
+        /* three lines per entry leave the last line unused */
+        assert_eq!(entry_at_pos(area, 3, 0, 30, (5, 7)), Some(1));
+        assert_eq!(entry_at_pos(area, 3, 4, 30, (5, 4)), Some(3));
+        assert_eq!(entry_at_pos(area, 3, 0, 30, (5, 11)), None);
+    }
+
+    #[test]
+    fn test_listing_hide_snoozed() {
+        let threads = (0..4).map(|_| ThreadHash::new()).collect::<Vec<_>>();
+        let mut roots = threads
+            .iter()
+            .cloned()
+            .collect::<SmallVec<[ThreadHash; 1024]>>();
+        hide_snoozed(&mut roots, &HashSet::default());
+        assert_eq!(roots.as_slice(), threads.as_slice());
+        let snoozed = IntoIterator::into_iter([threads[1], threads[3]]).collect();
+        hide_snoozed(&mut roots, &snoozed);
+        assert_eq!(roots.as_slice(), &[threads[0], threads[2]]);
+    }
+
+    #[test]
+    fn test_listing_group_digests() {
+        let threads = (0..5).map(|_| ThreadHash::new()).collect::<Vec<_>>();
+        let periods = [None, Some("week 1"), Some("week 2"), None, Some("week 1")];
+        let period_of = |thread: ThreadHash| {
+            let i = threads.iter().position(|t| *t == thread).unwrap();
+            periods[i].map(str::to_string)
+        };
+        let (roots, digests) = group_digests(&threads, MailboxHash(1), period_of);
+        assert_eq!(digests.len(), 2);
+        assert_eq!(roots.len(), 4);
+        assert_eq!(roots[0], threads[0]);
+        assert_eq!(roots[3], threads[3]);
+        assert_eq!(digests[&roots[1]].period, "week 1");
+        assert_eq!(digests[&roots[1]].threads, vec![threads[1], threads[4]]);
+        assert_eq!(digests[&roots[2]].threads, vec![threads[2]]);
+
+        /* The keys stay the same, but differ between mailboxes. */
+        let (same, _) = group_digests(&threads, MailboxHash(1), period_of);
+        assert_eq!(same, roots);
+        let (other, _) = group_digests(&threads, MailboxHash(2), period_of);
+        assert_ne!(other[1], roots[1]);
+        let (none, digests) = group_digests(&threads, MailboxHash(1), |_| None);
+        assert_eq!(none, threads);
+        assert!(digests.is_empty());
+    }
+
+    #[test]
+    fn test_listing_rows_state_insert_remove_at() {
+        fn strings(subject: &str) -> EntryStrings {
+            EntryStrings {
+                date: DateString(String::new()),
+                subject: SubjectString(subject.to_string()),
+                flag: FlagString(String::new()),
+                from: FromString(String::new()),
+                tags: TagString(String::new(), SmallVec::new()),
+            }
+        }
+        fn subjects(rows: &RowsState<ThreadHash>) -> Vec<&str> {
+            rows.entries
+                .iter()
+                .map(|(_, s)| s.subject.as_str())
+                .collect()
+        }
+        let threads = (0..3).map(|_| ThreadHash::new()).collect::<Vec<_>>();
+        let envelopes = (0..3u8)
+            .map(|i| EnvelopeHash::from_bytes(&[i]))
+            .collect::<Vec<_>>();
+        let mut rows = RowsState::default();
+        for (i, name) in ["a", "b", "c"].iter().enumerate() {
+            rows.insert_thread(
+                threads[i],
+                threads[i],
+                SmallVec::from_slice(&[envelopes[i]]),
+                strings(name),
+            );
+        }
+        rows.update_selection_with_env(envelopes[2], |e| *e = true);
+
+        assert_eq!(rows.remove_thread(threads[0]), Some(0));
+        assert_eq!(rows.remove_thread(threads[0]), None);
+        assert_eq!(subjects(&rows), vec!["b", "c"]);
+        assert_eq!(rows.thread_order[&threads[2]], 1);
+        assert_eq!(rows.env_order[&envelopes[2]], 1);
+        assert!(!rows.contains_env(envelopes[0]));
+
+        rows.insert_thread_at(
+            1,
+            threads[0],
+            threads[0],
+            SmallVec::from_slice(&[envelopes[0]]),
+            strings("a"),
+        );
+        assert_eq!(subjects(&rows), vec!["b", "a", "c"]);
+        for (i, row) in [(0, 1), (1, 0), (2, 2)] {
+            assert_eq!(rows.thread_order[&threads[i]], row);
+            assert_eq!(rows.env_order[&envelopes[i]], row);
+        }
+        assert!(rows.is_thread_selected(threads[2]));
+        assert!(!rows.is_thread_selected(threads[0]));
+    }
+
+    #[test]
+    fn test_listing_sync_status() {
+        assert_eq!(sync_status(None, None, 1_000), "");
+        assert_eq!(sync_status(None, Some(1_245), 1_000), "next in 4m 05s");
+        assert_eq!(sync_status(None, Some(8_260), 1_000), "next in 2h 01m");
+        assert_eq!(sync_status(None, Some(1_059), 1_000), "next in 59s");
+        /* A refresh that is due but hasn't fired yet. */
+        assert_eq!(sync_status(None, Some(990), 1_000), "next in 0s");
+        let synced = sync_status(Some(0), Some(1_030), 1_000);
+        assert!(synced.starts_with("synced "), "{}", synced);
+        assert!(synced.ends_with(", next in 30s"), "{}", synced);
+    }
+}
@@ -179,6 +179,12 @@ pub struct CompactListing {
     filter_term: String,
     filtered_selection: Vec<ThreadHash>,
     filtered_order: HashMap<ThreadHash, usize>,
+    /// Threads pinned to the top of the listing.
+    pinned: HashSet<ThreadHash>,
+    /// Newsletter digests of the mailbox, by the key of their row.
+    digests: HashMap<ThreadHash, Digest>,
+    /// Digests whose threads are listed below their row.
+    expanded_digests: HashSet<ThreadHash>,
     /// If we must redraw on next redraw event
     dirty: bool,
     force_draw: bool,
@@ -188,6 +194,8 @@ pub struct CompactListing {
     color_cache: ColorCache,
 
     movement: Option<PageMovement>,
+    /// Where the entries were last drawn, for mouse events.
+    list_area: Option<Area>,
     modifier_active: bool,
     modifier_command: Option<Modifier>,
     id: ComponentId,
@@ -277,25 +285,75 @@ impl MailListingTrait for CompactListing {
             }
         }
 
+        self.pinned = context.accounts[&self.cursor_pos.0].pinned_threads(self.cursor_pos.1);
+        let snoozed = context.accounts[&self.cursor_pos.0].snoozed_threads(self.cursor_pos.1);
         let threads = context.accounts[&self.cursor_pos.0]
             .collection
             .get_threads(self.cursor_pos.1);
         let mut roots = threads.roots();
+        hide_snoozed(&mut roots, &snoozed);
         threads.group_inner_sort_by(
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
         );
+        pinned_first(&mut roots, &self.pinned);
+        let (roots, digests) = match context.accounts[&self.cursor_pos.0][&self.cursor_pos.1]
+            .conf
+            .digest
+        {
+            Some(ref digest) => {
+                use melib::search::QueryTrait;
+                let filter = mailbox_settings!(
+                    context[self.cursor_pos.0][&self.cursor_pos.1]
+                        .listing
+                        .filter
+                )
+                .as_ref();
+                let collection = &context.accounts[&self.cursor_pos.0].collection;
+                let pinned = &self.pinned;
+                group_digests(&roots, self.cursor_pos.1, |thread| {
+                    if pinned.contains(&thread) {
+                        return None;
+                    }
+                    let env_hash = threads
+                        .thread_group_iter(thread)
+                        .find_map(|(_, h)| threads.thread_nodes()[&h].message())?;
+                    let envelope = collection.get_env(env_hash);
+                    if !digest.contains(&envelope)
+                        || filter.map_or(false, |query| !envelope.is_match(query))
+                    {
+                        return None;
+                    }
+                    Some(digest.period.name(threads.thread_ref(thread).date()))
+                })
+            }
+            None => (roots.into_vec(), HashMap::default()),
+        };
+        let mut items = Vec::with_capacity(roots.len());
+        for thread in roots {
+            items.push(thread);
+            if let Some(digest) = digests
+                .get(&thread)
+                .filter(|digest| self.expanded_digests.contains(&digest.hash))
+            {
+                items.extend(digest.threads.iter().copied());
+            }
+        }
+        self.digests = digests;
 
         self.redraw_threads_list(
             context,
-            Box::new(roots.into_iter()) as Box<dyn Iterator<Item = ThreadHash>>,
+            Box::new(items.into_iter()) as Box<dyn Iterator<Item = ThreadHash>>,
         );
 
         if !force && old_cursor_pos == self.new_cursor_pos {
             self.view.update(context);
         } else if self.unfocused() {
-            if let Some(thread) = self.get_thread_under_cursor(self.cursor_pos.2) {
+            if let Some(thread) = self
+                .get_thread_under_cursor(self.cursor_pos.2)
+                .filter(|thread| !self.digests.contains_key(thread))
+            {
                 self.view = Box::new(ThreadView::new(self.new_cursor_pos, thread, None, context));
             }
         }
@@ -316,13 +374,7 @@ impl MailListingTrait for CompactListing {
         }
         self.length = 0;
         let mut min_width = (0, 0, 0, 0);
-        #[allow(clippy::type_complexity)]
-        let mut row_widths: (
-            SmallVec<[u8; 1024]>,
-            SmallVec<[u8; 1024]>,
-            SmallVec<[u8; 1024]>,
-            SmallVec<[u8; 1024]>,
-        ) = (
+        let mut row_widths: RowWidths = (
             SmallVec::new(),
             SmallVec::new(),
             SmallVec::new(),
@@ -337,6 +389,22 @@ impl MailListingTrait for CompactListing {
         let mut from_address_set: std::collections::HashSet<Vec<u8>> =
             std::collections::HashSet::new();
         'items_for_loop: for thread in items {
+            if let Some(digest) = self.digests.get(&thread) {
+                let (root_env_hash, env_hashes, entry_strings) =
+                    if let Some(row) = self.make_digest_entry(digest, context, &threads) {
+                        row
+                    } else {
+                        continue 'items_for_loop;
+                    };
+                let row_attr =
+                    row_attr!(self.color_cache, self.length % 2 == 0, false, false, false);
+                self.rows.row_attr_cache.insert(self.length, row_attr);
+                push_row_widths(&mut row_widths, &mut min_width, self.length, &entry_strings);
+                self.rows
+                    .insert_thread(thread, (thread, root_env_hash), env_hashes, entry_strings);
+                self.length += 1;
+                continue 'items_for_loop;
+            }
             let thread_node = &threads.thread_nodes()[&threads.thread_ref(thread).root()];
             let root_env_hash = if let Some(h) = thread_node.message().or_else(|| {
                 if thread_node.children().is_empty() {
@@ -420,12 +488,21 @@ impl MailListingTrait for CompactListing {
                 }
             }
 
-            let row_attr = row_attr!(
-                self.color_cache,
-                self.length % 2 == 0,
-                threads.thread_ref(thread).unseen() > 0,
-                false,
-                false
+            let row_attr = self.color_cache.pinned_row_attr(
+                row_attr!(
+                    self.color_cache,
+                    self.length % 2 == 0,
+                    threads.thread_ref(thread).unseen() > 0,
+                    false,
+                    false
+                ),
+                self.pinned.contains(&thread),
+            );
+            let row_attr = self.color_cache.envelope_row_attr(
+                row_attr,
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &root_envelope,
             );
             self.rows.row_attr_cache.insert(self.length, row_attr);
 
@@ -439,45 +516,7 @@ impl MailListingTrait for CompactListing {
                 &tags,
                 thread,
             );
-            row_widths
-                .0
-                .push(digits_of_num!(self.length).try_into().unwrap_or(255));
-            /* date */
-            row_widths.1.push(
-                entry_strings
-                    .date
-                    .grapheme_width()
-                    .try_into()
-                    .unwrap_or(255),
-            );
-            /* from */
-            row_widths.2.push(
-                entry_strings
-                    .from
-                    .grapheme_width()
-                    .try_into()
-                    .unwrap_or(255),
-            );
-            /* subject */
-            row_widths.3.push(
-                (entry_strings.flag.grapheme_width()
-                    + 1
-                    + entry_strings.subject.grapheme_width()
-                    + 1
-                    + entry_strings.tags.grapheme_width())
-                .try_into()
-                .unwrap_or(255),
-            );
-            min_width.1 = cmp::max(min_width.1, entry_strings.date.grapheme_width()); /* date */
-            min_width.2 = cmp::max(min_width.2, entry_strings.from.grapheme_width()); /* from */
-            min_width.3 = cmp::max(
-                min_width.3,
-                entry_strings.flag.grapheme_width()
-                    + 1
-                    + entry_strings.subject.grapheme_width()
-                    + 1
-                    + entry_strings.tags.grapheme_width(),
-            ); /* subject */
+            push_row_widths(&mut row_widths, &mut min_width, self.length, &entry_strings);
             self.rows.insert_thread(
                 thread,
                 (thread, root_env_hash),
@@ -555,6 +594,62 @@ impl MailListingTrait for CompactListing {
     }
 }
 
+type RowWidths = (
+    SmallVec<[u8; 1024]>,
+    SmallVec<[u8; 1024]>,
+    SmallVec<[u8; 1024]>,
+    SmallVec<[u8; 1024]>,
+);
+
+/// Adds the widths of the columns of row `idx`, which shows `entry_strings`,
+/// to `row_widths` and `min_width`.
+fn push_row_widths(
+    row_widths: &mut RowWidths,
+    min_width: &mut (usize, usize, usize, usize),
+    idx: usize,
+    entry_strings: &EntryStrings,
+) {
+    row_widths
+        .0
+        .push(digits_of_num!(idx).try_into().unwrap_or(255));
+    /* date */
+    row_widths.1.push(
+        entry_strings
+            .date
+            .grapheme_width()
+            .try_into()
+            .unwrap_or(255),
+    );
+    /* from */
+    row_widths.2.push(
+        entry_strings
+            .from
+            .grapheme_width()
+            .try_into()
+            .unwrap_or(255),
+    );
+    /* subject */
+    row_widths.3.push(
+        (entry_strings.flag.grapheme_width()
+            + 1
+            + entry_strings.subject.grapheme_width()
+            + 1
+            + entry_strings.tags.grapheme_width())
+        .try_into()
+        .unwrap_or(255),
+    );
+    min_width.1 = cmp::max(min_width.1, entry_strings.date.grapheme_width()); /* date */
+    min_width.2 = cmp::max(min_width.2, entry_strings.from.grapheme_width()); /* from */
+    min_width.3 = cmp::max(
+        min_width.3,
+        entry_strings.flag.grapheme_width()
+            + 1
+            + entry_strings.subject.grapheme_width()
+            + 1
+            + entry_strings.tags.grapheme_width(),
+    ); /* subject */
+}
+
 impl ListingTrait for CompactListing {
     fn coordinates(&self) -> (AccountHash, MailboxHash) {
         (self.new_cursor_pos.0, self.new_cursor_pos.1)
@@ -579,15 +674,42 @@ impl ListingTrait for CompactListing {
 
         let account = &context.accounts[&self.cursor_pos.0];
         let threads = account.collection.get_threads(self.cursor_pos.1);
-        let thread = threads.thread_ref(thread_hash);
+        /* Digests are never drawn as unseen, to keep newsletters out of the way. */
+        let is_digest = self.digests.contains_key(&thread_hash);
+        let unseen = !is_digest && threads.thread_ref(thread_hash).unseen() > 0;
 
-        let row_attr = row_attr!(
-            self.color_cache,
-            idx % 2 == 0,
-            thread.unseen() > 0,
-            self.cursor_pos.2 == idx,
-            self.rows.is_thread_selected(thread_hash)
+        let row_attr = self.color_cache.pinned_row_attr(
+            row_attr!(
+                self.color_cache,
+                idx % 2 == 0,
+                unseen,
+                self.cursor_pos.2 == idx,
+                self.rows.is_thread_selected(thread_hash)
+            ),
+            self.pinned.contains(&thread_hash)
+                && self.cursor_pos.2 != idx
+                && !self.rows.is_thread_selected(thread_hash),
         );
+        let root_env_hash = self
+            .rows
+            .thread_to_env
+            .get(&thread_hash)
+            .and_then(|e| e.first().copied());
+        let row_attr = match root_env_hash {
+            Some(env_hash)
+                if self.cursor_pos.2 != idx
+                    && !is_digest
+                    && !self.rows.is_thread_selected(thread_hash) =>
+            {
+                self.color_cache.envelope_row_attr(
+                    row_attr,
+                    context,
+                    (self.cursor_pos.0, self.cursor_pos.1),
+                    &account.collection.get_env(env_hash),
+                )
+            }
+            _ => row_attr,
+        };
         let (upper_left, bottom_right) = area;
         let x = get_x(upper_left)
             + self.data_columns.widths[0]
@@ -648,6 +770,7 @@ impl ListingTrait for CompactListing {
         if rows == 0 {
             return;
         }
+        self.list_area = Some(area);
 
         if let Some(mvm) = self.movement.take() {
             match mvm {
@@ -787,7 +910,7 @@ impl ListingTrait for CompactListing {
             if self.filtered_order.contains_key(&thread) {
                 continue;
             }
-            if self.rows.all_threads.contains(&thread) {
+            if self.is_listed(thread) {
                 self.filtered_selection.push(thread);
                 self.filtered_order
                     .insert(thread, self.filtered_selection.len() - 1);
@@ -799,6 +922,7 @@ impl ListingTrait for CompactListing {
                 self.sort,
                 &context.accounts[&self.cursor_pos.0].collection.envelopes,
             );
+            pinned_first(&mut self.filtered_selection, &self.pinned);
             self.new_cursor_pos.2 =
                 std::cmp::min(self.filtered_selection.len() - 1, self.cursor_pos.2);
         } else {
@@ -827,6 +951,14 @@ impl ListingTrait for CompactListing {
         self.modifier_command
     }
 
+    fn entry_at(&self, pos: Pos) -> Option<usize> {
+        entry_at_pos(self.list_area?, 1, self.cursor_pos.2, self.length, pos)
+    }
+
+    fn cursor_index(&self) -> usize {
+        self.new_cursor_pos.2
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
@@ -882,6 +1014,9 @@ impl CompactListing {
             filter_term: String::new(),
             filtered_selection: Vec::new(),
             filtered_order: HashMap::default(),
+            pinned: HashSet::default(),
+            digests: HashMap::default(),
+            expanded_digests: HashSet::default(),
             focus: Focus::None,
             data_columns: DataColumns::default(),
             rows_drawn: SegmentTree::default(),
@@ -891,6 +1026,7 @@ impl CompactListing {
             view: Box::<ThreadView>::default(),
             color_cache: ColorCache::default(),
             movement: None,
+            list_area: None,
             modifier_active: false,
             modifier_command: None,
             id: ComponentId::new_v4(),
@@ -964,7 +1100,11 @@ impl CompactListing {
         };
         subject.truncate_at_boundary(150);
         EntryStrings {
-            date: DateString(ConversationsListing::format_date(context, thread.date())),
+            date: DateString(ConversationsListing::format_date(
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                thread.date(),
+            )),
             subject: if thread.len() > 1 {
                 SubjectString(format!("{} ({})", subject, thread.len()))
             } else {
@@ -1041,11 +1181,135 @@ impl CompactListing {
                     ""
                 },
             )),
-            from: FromString(address_list!((from) as comma_sep_list)),
+            from: super::labeled_from(
+                context,
+                self.cursor_pos.0,
+                root_envelope,
+                address_list!((from) as comma_sep_list),
+            ),
             tags: TagString(tags_string, colors),
         }
     }
 
+    /// Returns the first envelope, the envelopes and the strings of the row of
+    /// `digest`, or `None` if its threads have no envelopes.
+    fn make_digest_entry(
+        &self,
+        digest: &Digest,
+        context: &Context,
+        threads: &Threads,
+    ) -> Option<(EnvelopeHash, SmallVec<[EnvelopeHash; 8]>, EntryStrings)> {
+        let account = &context.accounts[&self.cursor_pos.0];
+        let mut root_env_hash = None;
+        let mut env_hashes = SmallVec::new();
+        let mut subjects = IndexSet::new();
+        let mut from_address_list = Vec::new();
+        let mut from_address_set: std::collections::HashSet<Vec<u8>> =
+            std::collections::HashSet::new();
+        let mut date = 0;
+        let mut unseen = 0;
+        for &thread in &digest.threads {
+            env_hashes.extend(
+                threads
+                    .thread_to_envelope
+                    .get(&thread)
+                    .into_iter()
+                    .flatten()
+                    .copied(),
+            );
+            date = cmp::max(date, threads.thread_ref(thread).date());
+            unseen += threads.thread_ref(thread).unseen();
+            let env_hash = match threads
+                .thread_group_iter(thread)
+                .find_map(|(_, h)| threads.thread_nodes()[&h].message())
+            {
+                Some(env_hash) => env_hash,
+                None => continue,
+            };
+            root_env_hash.get_or_insert(env_hash);
+            let envelope = account.collection.get_env(env_hash);
+            subjects.insert(envelope.subject().to_string());
+            for addr in envelope.from().iter() {
+                if from_address_set.contains(addr.address_spec_raw()) {
+                    continue;
+                }
+                from_address_set.insert(addr.address_spec_raw().to_vec());
+                from_address_list.push(addr.clone());
+            }
+        }
+        let root_env_hash = root_env_hash?;
+        let mut subject = format!(
+            "Digest {}: {}",
+            digest.period,
+            subjects.into_iter().collect::<Vec<String>>().join(", ")
+        );
+        subject.truncate_at_boundary(150);
+        let selected = self
+            .rows
+            .selection
+            .get(&root_env_hash)
+            .cloned()
+            .unwrap_or(false);
+        let entry_strings = EntryStrings {
+            date: DateString(ConversationsListing::format_date(
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                date,
+            )),
+            subject: SubjectString(format!("{} ({})", subject, digest.threads.len())),
+            flag: FlagString(format!(
+                "{selected}{unseen}{whitespace}",
+                selected = if selected {
+                    mailbox_settings!(
+                        context[self.cursor_pos.0][&self.cursor_pos.1]
+                            .listing
+                            .selected_flag
+                    )
+                    .as_ref()
+                    .map(|s| s.as_str())
+                    .unwrap_or(super::DEFAULT_SELECTED_FLAG)
+                } else {
+                    ""
+                },
+                unseen = if unseen > 0 {
+                    mailbox_settings!(
+                        context[self.cursor_pos.0][&self.cursor_pos.1]
+                            .listing
+                            .unseen_flag
+                    )
+                    .as_ref()
+                    .map(|s| s.as_str())
+                    .unwrap_or(super::DEFAULT_UNSEEN_FLAG)
+                } else {
+                    ""
+                },
+                whitespace = if selected || unseen > 0 { " " } else { "" },
+            )),
+            from: FromString(address_list!((&from_address_list) as comma_sep_list)),
+            tags: TagString(String::new(), SmallVec::new()),
+        };
+        Some((root_env_hash, env_hashes, entry_strings))
+    }
+
+    /// Lists the threads of the digest row `hash` below it, or stops listing
+    /// them.
+    fn toggle_digest(&mut self, hash: ThreadHash, context: &mut Context) {
+        if !self.expanded_digests.remove(&hash) {
+            self.expanded_digests.insert(hash);
+        }
+        self.refresh_mailbox(context, false);
+        self.set_dirty(true);
+    }
+
+    /// Whether `thread` is listed or is in a digest that is listed.
+    fn is_listed(&self, thread: ThreadHash) -> bool {
+        self.rows.all_threads.contains(&thread)
+            || self
+                .digests
+                .values()
+                .any(|digest| digest.threads.contains(&thread))
+    }
+
     fn get_thread_under_cursor(&self, cursor: usize) -> Option<ThreadHash> {
         if self.filter_term.is_empty() {
             self.rows
@@ -1071,65 +1335,97 @@ impl CompactListing {
         let envelope: EnvelopeRef = account.collection.get_env(env_hash);
         let thread_hash = self.rows.env_to_thread[&env_hash];
         let threads = account.collection.get_threads(self.cursor_pos.1);
-        let thread = threads.thread_ref(thread_hash);
+        let digest_entry = self
+            .digests
+            .get(&thread_hash)
+            .map(|digest| self.make_digest_entry(digest, context, &threads));
+        let unseen = digest_entry.is_none() && threads.thread_ref(thread_hash).unseen() > 0;
         let idx = self.rows.thread_order[&thread_hash];
-        let row_attr = row_attr!(
-            self.color_cache,
-            idx % 2 == 0,
-            thread.unseen() > 0,
-            false,
-            self.rows.is_thread_selected(thread_hash)
+        let row_attr = self.color_cache.pinned_row_attr(
+            row_attr!(
+                self.color_cache,
+                idx % 2 == 0,
+                unseen,
+                false,
+                self.rows.is_thread_selected(thread_hash)
+            ),
+            self.pinned.contains(&thread_hash) && !self.rows.is_thread_selected(thread_hash),
         );
+        let root_env_hash = self
+            .rows
+            .thread_to_env
+            .get(&thread_hash)
+            .and_then(|e| e.first().copied());
+        let row_attr = match root_env_hash {
+            Some(env_hash)
+                if digest_entry.is_none() && !self.rows.is_thread_selected(thread_hash) =>
+            {
+                self.color_cache.envelope_row_attr(
+                    row_attr,
+                    context,
+                    (self.cursor_pos.0, self.cursor_pos.1),
+                    &account.collection.get_env(env_hash),
+                )
+            }
+            _ => row_attr,
+        };
         self.rows.row_attr_cache.insert(idx, row_attr);
 
-        let mut other_subjects = IndexSet::new();
-        let mut tags = IndexSet::new();
-        let mut from_address_list = Vec::new();
-        let mut from_address_set: std::collections::HashSet<Vec<u8>> =
-            std::collections::HashSet::new();
-        for (envelope, show_subject) in threads
-            .thread_group_iter(thread_hash)
-            .filter_map(|(_, h)| {
-                threads.thread_nodes()[&h]
-                    .message()
-                    .map(|env_hash| (env_hash, threads.thread_nodes()[&h].show_subject()))
-            })
-            .map(|(env_hash, show_subject)| {
-                (
-                    context.accounts[&self.cursor_pos.0]
-                        .collection
-                        .get_env(env_hash),
-                    show_subject,
-                )
-            })
-        {
-            if show_subject {
-                other_subjects.insert(envelope.subject().to_string());
+        let strings = if let Some(digest_entry) = digest_entry {
+            match digest_entry {
+                Some((_, _, strings)) => strings,
+                None => return,
             }
-            if account.backend_capabilities.supports_tags {
-                for &t in envelope.tags().iter() {
-                    tags.insert(t);
+        } else {
+            let mut other_subjects = IndexSet::new();
+            let mut tags = IndexSet::new();
+            let mut from_address_list = Vec::new();
+            let mut from_address_set: std::collections::HashSet<Vec<u8>> =
+                std::collections::HashSet::new();
+            for (envelope, show_subject) in threads
+                .thread_group_iter(thread_hash)
+                .filter_map(|(_, h)| {
+                    threads.thread_nodes()[&h]
+                        .message()
+                        .map(|env_hash| (env_hash, threads.thread_nodes()[&h].show_subject()))
+                })
+                .map(|(env_hash, show_subject)| {
+                    (
+                        context.accounts[&self.cursor_pos.0]
+                            .collection
+                            .get_env(env_hash),
+                        show_subject,
+                    )
+                })
+            {
+                if show_subject {
+                    other_subjects.insert(envelope.subject().to_string());
                 }
-            }
-            for addr in envelope.from().iter() {
-                if from_address_set.contains(addr.address_spec_raw()) {
-                    continue;
+                if account.backend_capabilities.supports_tags {
+                    for &t in envelope.tags().iter() {
+                        tags.insert(t);
+                    }
+                }
+                for addr in envelope.from().iter() {
+                    if from_address_set.contains(addr.address_spec_raw()) {
+                        continue;
+                    }
+                    from_address_set.insert(addr.address_spec_raw().to_vec());
+                    from_address_list.push(addr.clone());
                 }
-                from_address_set.insert(addr.address_spec_raw().to_vec());
-                from_address_list.push(addr.clone());
             }
-        }
 
-        let strings = self.make_entry_string(
-            &envelope,
-            context,
-            &tags_lck,
-            &from_address_list,
-            &threads,
-            &other_subjects,
-            &tags,
-            thread_hash,
-        );
+            self.make_entry_string(
+                &envelope,
+                context,
+                &tags_lck,
+                &from_address_list,
+                &threads,
+                &other_subjects,
+                &tags,
+                thread_hash,
+            )
+        };
         drop(envelope);
         let columns = &mut self.data_columns.columns;
         let min_width = (
@@ -1192,7 +1488,10 @@ impl CompactListing {
             ((x, idx), (min_width.3, idx)),
             None,
         );
-        columns[3][(x, idx)].set_bg(row_attr.bg).set_ch(' ');
+        /* The subject may fill the column. */
+        if x < min_width.3 {
+            columns[3][(x, idx)].set_bg(row_attr.bg).set_ch(' ');
+        }
         let x = {
             let mut x = x + 1;
             for (t, &color) in strings.tags.split_whitespace().zip(strings.tags.1.iter()) {
@@ -1222,7 +1521,9 @@ impl CompactListing {
                     columns[3][c].set_keep_bg(true);
                 }
                 x = _x + 1;
-                columns[3][(x, idx)].set_bg(row_attr.bg).set_ch(' ');
+                if x < min_width.3 {
+                    columns[3][(x, idx)].set_bg(row_attr.bg).set_ch(' ');
+                }
             }
             x
         };
@@ -1411,7 +1712,7 @@ impl CompactListing {
                     }
                     let thread =
                         threads.find_group(threads.thread_nodes[&env_thread_node_hash].group);
-                    if self.rows.all_threads.contains(&thread) {
+                    if self.is_listed(thread) {
                         self.rows
                             .selection
                             .entry(env_hash)
@@ -1721,10 +2022,29 @@ impl Component for CompactListing {
                         && (shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"])
                             || shortcut!(k == shortcuts[Shortcuts::LISTING]["focus_right"])) =>
                 {
+                    let action = if shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"]) {
+                        self.open_entry_action(IndexStyle::Compact, context)
+                    } else {
+                        OpenEntryAction::Preview
+                    };
+                    if action == OpenEntryAction::MarkReadAndAdvance {
+                        self.mark_read_and_advance(context);
+                        return true;
+                    }
                     if let Some(thread) = self.get_thread_under_cursor(self.cursor_pos.2) {
+                        if self.digests.contains_key(&thread) {
+                            self.toggle_digest(thread, context);
+                            return true;
+                        }
                         self.view =
                             Box::new(ThreadView::new(self.cursor_pos, thread, None, context));
+                        if action == OpenEntryAction::Expand {
+                            self.view.show_thread_only();
+                        }
                         self.set_focus(Focus::Entry, context);
+                        if action == OpenEntryAction::Fullscreen {
+                            self.set_focus(Focus::EntryFullscreen, context);
+                        }
                     }
                     return true;
                 }
@@ -1827,6 +2147,7 @@ impl Component for CompactListing {
                 self.set_dirty(true);
             }
             UIEvent::MailboxUpdate((ref idxa, ref idxf))
+            | UIEvent::ThreadUpdate((ref idxa, ref idxf), _)
                 if (*idxa, *idxf) == (self.new_cursor_pos.0, self.cursor_pos.1) =>
             {
                 self.refresh_mailbox(context, false);
@@ -1861,7 +2182,7 @@ impl Component for CompactListing {
                 }
             }
             UIEvent::EnvelopeRemove(ref _env_hash, ref thread_hash) => {
-                if self.rows.thread_order.contains_key(thread_hash) {
+                if self.is_listed(*thread_hash) {
                     self.refresh_mailbox(context, false);
                     self.set_dirty(true);
                 }
@@ -1881,6 +2202,9 @@ impl Component for CompactListing {
                 drop(threads);
                 if self.rows.contains_thread(thread) {
                     self.rows.row_update_add_thread(thread);
+                } else if self.rows.contains_env(*env_hash) {
+                    /* The thread is in a digest. */
+                    self.rows.row_update_add_envelope(*env_hash);
                 }
 
                 self.set_dirty(true);
@@ -109,6 +109,8 @@ pub struct ConversationsListing {
     filter_term: String,
     filtered_selection: Vec<ThreadHash>,
     filtered_order: HashMap<ThreadHash, usize>,
+    /// Threads pinned to the top of the listing.
+    pinned: HashSet<ThreadHash>,
     /// If we must redraw on next redraw event
     dirty: bool,
     force_draw: bool,
@@ -118,6 +120,8 @@ pub struct ConversationsListing {
     color_cache: ColorCache,
 
     movement: Option<PageMovement>,
+    /// Where the entries were last drawn, for mouse events.
+    list_area: Option<Area>,
     modifier_active: bool,
     modifier_command: Option<Modifier>,
     id: ComponentId,
@@ -194,15 +198,19 @@ impl MailListingTrait for ConversationsListing {
             }
         }
 
+        self.pinned = context.accounts[&self.cursor_pos.0].pinned_threads(self.cursor_pos.1);
+        let snoozed = context.accounts[&self.cursor_pos.0].snoozed_threads(self.cursor_pos.1);
         let threads = context.accounts[&self.cursor_pos.0]
             .collection
             .get_threads(self.cursor_pos.1);
         let mut roots = threads.roots();
+        hide_snoozed(&mut roots, &snoozed);
         threads.group_inner_sort_by(
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
         );
+        pinned_first(&mut roots, &self.pinned);
 
         self.redraw_threads_list(
             context,
@@ -234,119 +242,12 @@ impl MailListingTrait for ConversationsListing {
         if self.error.is_err() {
             self.error = Ok(());
         }
-        let mut max_entry_columns = 0;
-
-        let mut other_subjects = IndexSet::new();
-        let mut tags = IndexSet::new();
-        let mut from_address_list = Vec::new();
-        let mut from_address_set: std::collections::HashSet<Vec<u8>> =
-            std::collections::HashSet::new();
-        'items_for_loop: for thread in items {
-            let thread_node = &threads.thread_nodes()[&threads.thread_ref(thread).root()];
-            let root_env_hash = if let Some(h) = thread_node.message().or_else(|| {
-                if thread_node.children().is_empty() {
-                    return None;
-                }
-                let mut iter_ptr = thread_node.children()[0];
-                while threads.thread_nodes()[&iter_ptr].message().is_none() {
-                    if threads.thread_nodes()[&iter_ptr].children().is_empty() {
-                        return None;
-                    }
-                    iter_ptr = threads.thread_nodes()[&iter_ptr].children()[0];
-                }
-                threads.thread_nodes()[&iter_ptr].message()
-            }) {
-                h
-            } else {
-                continue 'items_for_loop;
-            };
-            if !context.accounts[&self.cursor_pos.0].contains_key(root_env_hash) {
-                debug!("key = {}", root_env_hash);
-                debug!(
-                    "name = {} {}",
-                    account[&self.cursor_pos.1].name(),
-                    context.accounts[&self.cursor_pos.0].name()
-                );
-                debug!("{:#?}", context.accounts);
-
-                panic!();
-            }
-            let root_envelope: &EnvelopeRef = &context.accounts[&self.cursor_pos.0]
-                .collection
-                .get_env(root_env_hash);
-            use melib::search::QueryTrait;
-            if let Some(filter_query) = mailbox_settings!(
-                context[self.cursor_pos.0][&self.cursor_pos.1]
-                    .listing
-                    .filter
-            )
-            .as_ref()
-            {
-                if !root_envelope.is_match(filter_query) {
-                    continue;
-                }
-            }
-            other_subjects.clear();
-            tags.clear();
-            from_address_list.clear();
-            from_address_set.clear();
-            for (envelope, show_subject) in threads
-                .thread_group_iter(thread)
-                .filter_map(|(_, h)| {
-                    Some((
-                        threads.thread_nodes()[&h].message()?,
-                        threads.thread_nodes()[&h].show_subject(),
-                    ))
-                })
-                .map(|(env_hash, show_subject)| {
-                    (
-                        context.accounts[&self.cursor_pos.0]
-                            .collection
-                            .get_env(env_hash),
-                        show_subject,
-                    )
-                })
-            {
-                if show_subject {
-                    other_subjects.insert(envelope.subject().to_string());
-                }
-                if account.backend_capabilities.supports_tags {
-                    for &t in envelope.tags().iter() {
-                        tags.insert(t);
-                    }
-                }
-
-                for addr in envelope.from().iter() {
-                    if from_address_set.contains(addr.address_spec_raw()) {
-                        continue;
-                    }
-                    from_address_set.insert(addr.address_spec_raw().to_vec());
-                    from_address_list.push(addr.clone());
-                }
-            }
-
-            let strings = self.make_entry_string(
-                root_envelope,
-                context,
-                &tags_lck,
-                &from_address_list,
-                &threads,
-                &other_subjects,
-                &tags,
-                thread,
-            );
-            max_entry_columns = std::cmp::max(
-                max_entry_columns,
-                strings.flag.len()
-                    + 3
-                    + strings.subject.grapheme_width()
-                    + 1
-                    + strings.tags.grapheme_width(),
-            );
-            max_entry_columns = std::cmp::max(
-                max_entry_columns,
-                strings.date.len() + 1 + strings.from.grapheme_width(),
-            );
+        for thread in items {
+            let (root_env_hash, strings) =
+                match self.thread_entry(context, &threads, &tags_lck, thread) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
             self.rows.insert_thread(
                 thread,
                 (thread, root_env_hash),
@@ -416,6 +317,7 @@ impl ListingTrait for ConversationsListing {
         if rows == 0 {
             return;
         }
+        self.list_area = Some(area);
         if let Some(mvm) = self.movement.take() {
             match mvm {
                 PageMovement::Up(amount) => {
@@ -543,6 +445,7 @@ impl ListingTrait for ConversationsListing {
                 self.sort,
                 &context.accounts[&self.cursor_pos.0].collection.envelopes,
             );
+            pinned_first(&mut self.filtered_selection, &self.pinned);
             self.new_cursor_pos.2 = std::cmp::min(
                 self.filtered_selection.len().saturating_sub(1),
                 self.cursor_pos.2,
@@ -571,6 +474,14 @@ impl ListingTrait for ConversationsListing {
         self.modifier_command
     }
 
+    fn entry_at(&self, pos: Pos) -> Option<usize> {
+        entry_at_pos(self.list_area?, 3, self.cursor_pos.2, self.length, pos)
+    }
+
+    fn cursor_index(&self) -> usize {
+        self.new_cursor_pos.2
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
@@ -627,18 +538,215 @@ impl ConversationsListing {
             filter_term: String::new(),
             filtered_selection: Vec::new(),
             filtered_order: HashMap::default(),
+            pinned: HashSet::default(),
             dirty: true,
             force_draw: true,
             focus: Focus::None,
             view: ThreadView::default(),
             color_cache: ColorCache::default(),
             movement: None,
+            list_area: None,
             modifier_active: false,
             modifier_command: None,
             id: ComponentId::new_v4(),
         })
     }
 
+    /// Returns the root envelope and the row strings of `thread`, or `None`
+    /// if it has no messages or the mailbox's listing filter hides it.
+    fn thread_entry(
+        &self,
+        context: &Context,
+        threads: &Threads,
+        tags_lck: &BTreeMap<TagHash, String>,
+        thread: ThreadHash,
+    ) -> Option<(EnvelopeHash, EntryStrings)> {
+        let account = &context.accounts[&self.cursor_pos.0];
+        let thread_node = &threads.thread_nodes()[&threads.thread_ref(thread).root()];
+        let root_env_hash = thread_node.message().or_else(|| {
+            if thread_node.children().is_empty() {
+                return None;
+            }
+            let mut iter_ptr = thread_node.children()[0];
+            while threads.thread_nodes()[&iter_ptr].message().is_none() {
+                if threads.thread_nodes()[&iter_ptr].children().is_empty() {
+                    return None;
+                }
+                iter_ptr = threads.thread_nodes()[&iter_ptr].children()[0];
+            }
+            threads.thread_nodes()[&iter_ptr].message()
+        })?;
+        if !account.contains_key(root_env_hash) {
+            debug!("key = {}", root_env_hash);
+            debug!(
+                "name = {} {}",
+                account[&self.cursor_pos.1].name(),
+                account.name()
+            );
+            debug!("{:#?}", context.accounts);
+
+            panic!();
+        }
+        let root_envelope: &EnvelopeRef = &account.collection.get_env(root_env_hash);
+        use melib::search::QueryTrait;
+        if let Some(filter_query) = mailbox_settings!(
+            context[self.cursor_pos.0][&self.cursor_pos.1]
+                .listing
+                .filter
+        )
+        .as_ref()
+        {
+            if !root_envelope.is_match(filter_query) {
+                return None;
+            }
+        }
+        let mut other_subjects = IndexSet::new();
+        let mut tags = IndexSet::new();
+        let mut from_address_list = Vec::new();
+        let mut from_address_set: std::collections::HashSet<Vec<u8>> =
+            std::collections::HashSet::new();
+        for (envelope, show_subject) in threads
+            .thread_group_iter(thread)
+            .filter_map(|(_, h)| {
+                Some((
+                    threads.thread_nodes()[&h].message()?,
+                    threads.thread_nodes()[&h].show_subject(),
+                ))
+            })
+            .map(|(env_hash, show_subject)| (account.collection.get_env(env_hash), show_subject))
+        {
+            if show_subject {
+                other_subjects.insert(envelope.subject().to_string());
+            }
+            if account.backend_capabilities.supports_tags {
+                for &t in envelope.tags().iter() {
+                    tags.insert(t);
+                }
+            }
+
+            for addr in envelope.from().iter() {
+                if from_address_set.contains(addr.address_spec_raw()) {
+                    continue;
+                }
+                from_address_set.insert(addr.address_spec_raw().to_vec());
+                from_address_list.push(addr.clone());
+            }
+        }
+
+        let strings = self.make_entry_string(
+            root_envelope,
+            context,
+            tags_lck,
+            &from_address_list,
+            threads,
+            &other_subjects,
+            &tags,
+            thread,
+        );
+        Some((root_env_hash, strings))
+    }
+
+    /// Updates just the rows of the threads in `update` and returns `true`,
+    /// or returns `false` if the whole listing must be refreshed instead.
+    fn update_threads(&mut self, context: &Context, update: &ThreadUpdate) -> bool {
+        let account = &context.accounts[&self.cursor_pos.0];
+        if self.error.is_err()
+            || self.cursor_pos.0 != self.new_cursor_pos.0
+            || !self.filter_term.is_empty()
+            || mailbox_settings!(
+                context[self.cursor_pos.0][&self.cursor_pos.1]
+                    .listing
+                    .filter
+            )
+            .is_some()
+            || std::iter::once(&update.thread)
+                .chain(update.merged.iter())
+                .any(|t| self.pinned.contains(t))
+        {
+            return false;
+        }
+        let thread_under_cursor = self.get_thread_under_cursor(self.cursor_pos.2);
+        let mut selected: SmallVec<[EnvelopeHash; 8]> = SmallVec::new();
+        for thread in std::iter::once(update.thread).chain(update.merged.iter().cloned()) {
+            if let Some(env_hashes) = self.rows.thread_to_env.get(&thread) {
+                selected.extend(
+                    env_hashes
+                        .iter()
+                        .filter(|h| self.rows.selection.get(h).cloned().unwrap_or(false))
+                        .cloned(),
+                );
+            }
+            if self.rows.remove_thread(thread).is_some() {
+                self.length -= 1;
+            }
+        }
+        if !account
+            .snoozed_threads(self.cursor_pos.1)
+            .contains(&update.thread)
+        {
+            let threads = account.collection.get_threads(self.cursor_pos.1);
+            let tags_lck = account.collection.tag_index.read().unwrap();
+            if let Some((root_env_hash, strings)) =
+                self.thread_entry(context, &threads, &tags_lck, update.thread)
+            {
+                /* Pinned threads are at the top, and the rest are sorted after them. */
+                let pinned = self
+                    .rows
+                    .entries
+                    .iter()
+                    .take_while(|((t, _), _)| self.pinned.contains(t))
+                    .count();
+                let index = pinned
+                    + threads.group_sorted_position(
+                        update.thread,
+                        self.rows.len() - pinned,
+                        |i| self.rows.entries[pinned + i].0 .0,
+                        self.sort,
+                        &account.collection.envelopes,
+                    );
+                self.rows.insert_thread_at(
+                    index,
+                    update.thread,
+                    (update.thread, root_env_hash),
+                    threads
+                        .thread_to_envelope
+                        .get(&update.thread)
+                        .cloned()
+                        .unwrap_or_default()
+                        .into(),
+                    strings,
+                );
+                self.length += 1;
+            }
+        }
+        for env_hash in selected {
+            self.rows.update_selection_with_env(env_hash, |e| *e = true);
+        }
+        /* Keep the cursor on the thread it was on. */
+        let thread_under_cursor = thread_under_cursor.map(|t| {
+            if update.merged.contains(&t) {
+                update.thread
+            } else {
+                t
+            }
+        });
+        if let Some(row) = thread_under_cursor.and_then(|t| self.rows.thread_order.get(&t).copied())
+        {
+            self.cursor_pos.2 = row;
+            self.new_cursor_pos.2 = row;
+        } else {
+            self.cursor_pos.2 = self.cursor_pos.2.min(self.length.saturating_sub(1));
+            self.new_cursor_pos.2 = self.cursor_pos.2;
+        }
+        if self.length == 0 {
+            let message: String = account[&self.cursor_pos.1].status();
+            self.error = Err(message);
+        }
+        self.force_draw = true;
+        self.set_dirty(true);
+        true
+    }
+
     pub(super) fn make_entry_string(
         &self,
         root_envelope: &Envelope,
@@ -706,7 +814,11 @@ impl ConversationsListing {
         };
         subject.truncate_at_boundary(100);
         EntryStrings {
-            date: DateString(ConversationsListing::format_date(context, thread.date())),
+            date: DateString(ConversationsListing::format_date(
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                thread.date(),
+            )),
             subject: SubjectString(if thread.len() > 1 {
                 format!("{} ({})", subject, thread.len())
             } else {
@@ -717,12 +829,21 @@ impl ConversationsListing {
                 if thread.has_attachments() { "📎" } else { "" },
                 if thread.snoozed() { "💤" } else { "" }
             )),
-            from: FromString(address_list!((from) as comma_sep_list)),
+            from: super::labeled_from(
+                context,
+                self.cursor_pos.0,
+                root_envelope,
+                address_list!((from) as comma_sep_list),
+            ),
             tags: TagString(tags_string, colors),
         }
     }
 
-    pub(super) fn format_date(context: &Context, epoch: UnixTimestamp) -> String {
+    pub(super) fn format_date(
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+        epoch: UnixTimestamp,
+    ) -> String {
         let d = std::time::UNIX_EPOCH + std::time::Duration::from_secs(epoch);
         let now: std::time::Duration = std::time::SystemTime::now()
             .duration_since(d)
@@ -743,8 +864,9 @@ impl ConversationsListing {
                 n / (24 * 60 * 60),
                 if n / (24 * 60 * 60) == 1 { "" } else { "s" }
             ),
-            _ => melib::datetime::timestamp_to_string(
+            _ => mailbox_settings!(context[coordinates.0][&coordinates.1].listing.timezone).format(
                 epoch,
+                None,
                 context
                     .settings
                     .listing
@@ -861,13 +983,29 @@ impl ConversationsListing {
             for x in x..(x + 3) {
                 grid[set_x(upper_left, x)].set_bg(row_attr.bg);
             }
-            let subject_attr = row_attr!(
-                subject,
-                self.color_cache,
-                thread.unseen() > 0,
-                self.cursor_pos.2 == idx,
-                self.rows.is_thread_selected(*thread_hash)
+            let subject_attr = self.color_cache.pinned_row_attr(
+                row_attr!(
+                    subject,
+                    self.color_cache,
+                    thread.unseen() > 0,
+                    self.cursor_pos.2 == idx,
+                    self.rows.is_thread_selected(*thread_hash)
+                ),
+                self.pinned.contains(thread_hash)
+                    && self.cursor_pos.2 != idx
+                    && !self.rows.is_thread_selected(*thread_hash),
             );
+            let subject_attr =
+                if self.cursor_pos.2 != idx && !self.rows.is_thread_selected(*thread_hash) {
+                    self.color_cache.envelope_row_attr(
+                        subject_attr,
+                        context,
+                        (self.cursor_pos.0, self.cursor_pos.1),
+                        &account.collection.get_env(*root_env_hash),
+                    )
+                } else {
+                    subject_attr
+                };
             /* draw subject */
             let (mut x, _) = write_string_to_grid(
                 &strings.subject,
@@ -1268,9 +1406,24 @@ impl Component for ConversationsListing {
                         && (shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"])
                             || shortcut!(k == shortcuts[Shortcuts::LISTING]["focus_right"])) =>
                 {
+                    let action = if shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"]) {
+                        self.open_entry_action(IndexStyle::Conversations, context)
+                    } else {
+                        OpenEntryAction::Preview
+                    };
+                    if action == OpenEntryAction::MarkReadAndAdvance {
+                        self.mark_read_and_advance(context);
+                        return true;
+                    }
                     if let Some(thread) = self.get_thread_under_cursor(self.cursor_pos.2) {
                         self.view = ThreadView::new(self.cursor_pos, thread, None, context);
+                        if action == OpenEntryAction::Expand {
+                            self.view.show_thread_only();
+                        }
                         self.set_focus(Focus::Entry, context);
+                        if action == OpenEntryAction::Fullscreen {
+                            self.set_focus(Focus::EntryFullscreen, context);
+                        }
                     }
                     return true;
                 }
@@ -1438,6 +1591,16 @@ impl Component for ConversationsListing {
                 self.refresh_mailbox(context, true);
                 self.set_dirty(true);
             }
+            UIEvent::ThreadUpdate((ref idxa, ref idxf), ref update)
+                if (*idxa, *idxf) == (self.new_cursor_pos.0, self.cursor_pos.1) =>
+            {
+                if !self.update_threads(context, update) {
+                    self.refresh_mailbox(context, false);
+                } else if self.unfocused() {
+                    self.view.update(context);
+                }
+                self.set_dirty(true);
+            }
             UIEvent::MailboxUpdate((ref idxa, ref idxf))
                 if (*idxa, *idxf) == (self.new_cursor_pos.0, self.cursor_pos.1) =>
             {
@@ -0,0 +1,452 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use crate::conf::accounts::Account;
+
+/// The flags the editor shows, with their names.
+const FLAGS: &[(Flag, &str)] = &[
+    (Flag::SEEN, "seen"),
+    (Flag::FLAGGED, "flagged"),
+    (Flag::REPLIED, "replied"),
+    (Flag::PASSED, "passed"),
+    (Flag::DRAFT, "draft"),
+    (Flag::TRASHED, "trashed"),
+];
+
+/// Returns the name of a single `flag`.
+pub fn flag_name(flag: Flag) -> &'static str {
+    FLAGS
+        .iter()
+        .find(|(f, _)| *f == flag)
+        .map(|(_, name)| *name)
+        .unwrap_or_default()
+}
+
+/// Returns a dialog for choosing tags to add to `env_hashes`, out of the
+/// tags of the account, or to remove from them, out of their tags. The
+/// chosen tags are set with one [`ListingAction::SetFlags`].
+pub fn tag_selector(
+    account: &Account,
+    env_hashes: &[EnvelopeHash],
+    add: bool,
+    context: &Context,
+) -> std::result::Result<UIDialog<String>, String> {
+    if !account.backend_capabilities.supports_tags {
+        return Err(format!("Account {} does not support tags.", account.name()));
+    }
+    let collection = &account.collection;
+    let tag_index = collection.tag_index.read().unwrap();
+    let mut tags = tag_index
+        .iter()
+        .filter(|(tag_hash, _)| {
+            add || env_hashes.iter().any(|env_hash| {
+                collection.contains_key(env_hash)
+                    && collection.get_env(*env_hash).tags().contains(tag_hash)
+            })
+        })
+        .map(|(_, tag)| (tag.clone(), tag.clone()))
+        .collect::<Vec<(String, String)>>();
+    if tags.is_empty() {
+        return Err(if add {
+            "There are no tags to add; add a new one with `tag add TAG`.".to_string()
+        } else {
+            "The messages have no tags.".to_string()
+        });
+    }
+    tags.sort();
+    Ok(UIDialog::new(
+        if add { "add tags" } else { "remove tags" },
+        tags,
+        false,
+        Some(Box::new(move |id: ComponentId, results: &[String]| {
+            Some(UIEvent::FinishedUIDialog(
+                id,
+                Box::new(if results.is_empty() {
+                    None
+                } else {
+                    Some(Action::Listing(ListingAction::SetFlags(
+                        results.iter().map(|tag| (Err(tag.clone()), add)).collect(),
+                    )))
+                }),
+            ))
+        })),
+        context,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FlagEditorEntry {
+    flag: std::result::Result<Flag, String>,
+    /// How many of the edited messages have the flag or tag.
+    count: usize,
+    /// Whether to set or unset the flag or tag, or `None` to leave it as it
+    /// is.
+    change: Option<bool>,
+}
+
+impl FlagEditorEntry {
+    fn name(&self) -> &str {
+        match self.flag {
+            Ok(flag) => flag_name(flag),
+            Err(ref tag) => tag,
+        }
+    }
+
+    /// Cycles the change between leaving the entry as it is, setting and
+    /// unsetting it, skipping changes that would do nothing.
+    fn cycle(&mut self, total: usize) {
+        loop {
+            self.change = match self.change {
+                None => Some(true),
+                Some(true) => Some(false),
+                Some(false) => None,
+            };
+            match self.change {
+                Some(true) if self.count == total => continue,
+                Some(false) if self.count == 0 => continue,
+                _ => break,
+            }
+        }
+    }
+
+    /// The checkbox of the entry after the change: set on all, some or none
+    /// of the messages.
+    fn checkbox(&self, total: usize) -> &'static str {
+        match (self.change, self.count) {
+            (Some(true), _) => "[x]",
+            (Some(false), _) | (None, 0) => "[ ]",
+            (None, count) if count == total => "[x]",
+            (None, _) => "[-]",
+        }
+    }
+}
+
+/// Overlay that edits the flags and tags of several messages at once.
+///
+/// Each flag, and each tag of the account if its backend supports tags, is
+/// shown as set on all (`[x]`), some (`[-]`) or none (`[ ]`) of the
+/// messages. Space cycles the entry under the cursor between leaving it as
+/// it is, setting it and unsetting it, and typing the name of a tag adds it
+/// to the tags to set. Pressing Enter dispatches all changes as one
+/// [`ListingAction::SetFlags`].
+#[derive(Debug)]
+pub struct FlagEditor {
+    total: usize,
+    entries: Vec<FlagEditorEntry>,
+    supports_tags: bool,
+    new_tag: String,
+    cursor: usize,
+    theme_default: ThemeAttribute,
+    highlighted_attrs: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for FlagEditor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "edit flags")
+    }
+}
+
+impl FlagEditor {
+    pub fn new(account: &Account, env_hashes: &[EnvelopeHash], context: &Context) -> Self {
+        let collection = &account.collection;
+        let env_hashes = env_hashes
+            .iter()
+            .copied()
+            .filter(|env_hash| collection.contains_key(env_hash))
+            .collect::<Vec<EnvelopeHash>>();
+        let mut entries = FLAGS
+            .iter()
+            .map(|(flag, _)| FlagEditorEntry {
+                flag: Ok(*flag),
+                count: env_hashes
+                    .iter()
+                    .filter(|env_hash| collection.get_env(**env_hash).flags().contains(*flag))
+                    .count(),
+                change: None,
+            })
+            .collect::<Vec<FlagEditorEntry>>();
+        let supports_tags = account.backend_capabilities.supports_tags;
+        if supports_tags {
+            let mut tags = collection
+                .tag_index
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(tag_hash, tag)| FlagEditorEntry {
+                    flag: Err(tag.clone()),
+                    count: env_hashes
+                        .iter()
+                        .filter(|env_hash| collection.get_env(**env_hash).tags().contains(tag_hash))
+                        .count(),
+                    change: None,
+                })
+                .collect::<Vec<FlagEditorEntry>>();
+            /* Tags of the messages first. */
+            tags.sort_by(|a, b| (a.count == 0, a.name()).cmp(&(b.count == 0, b.name())));
+            entries.extend(tags);
+        }
+        let mut ret = FlagEditor {
+            total: env_hashes.len(),
+            entries,
+            supports_tags,
+            new_tag: String::new(),
+            cursor: 0,
+            theme_default: Default::default(),
+            highlighted_attrs: Default::default(),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.initialise(context);
+        ret
+    }
+
+    fn initialise(&mut self, context: &Context) {
+        self.theme_default = crate::conf::value(context, "theme_default");
+        self.highlighted_attrs = crate::conf::value(context, "widgets.options.highlighted");
+        if !context.settings.terminal.use_color() {
+            self.highlighted_attrs.attrs |= Attr::REVERSE;
+        }
+    }
+
+    /// Adds the typed tag to the tags to set.
+    fn add_new_tag(&mut self) {
+        let tag = std::mem::take(&mut self.new_tag);
+        let total = self.total;
+        match self
+            .entries
+            .iter()
+            .position(|e| e.flag.as_ref().err() == Some(&tag))
+        {
+            Some(idx) => {
+                if self.entries[idx].count < total {
+                    self.entries[idx].change = Some(true);
+                }
+                self.cursor = idx;
+            }
+            None => {
+                self.entries.push(FlagEditorEntry {
+                    flag: Err(tag),
+                    count: 0,
+                    change: Some(true),
+                });
+                self.cursor = self.entries.len() - 1;
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// The flags and tags to set or unset.
+    fn change_set(&self) -> Vec<(std::result::Result<Flag, String>, bool)> {
+        self.entries
+            .iter()
+            .filter_map(|e| e.change.map(|value| (e.flag.clone(), value)))
+            .collect()
+    }
+}
+
+impl Component for FlagEditor {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        let inner_area = create_box(grid, area);
+        clear_area(grid, inner_area, self.theme_default);
+        write_string_to_grid(
+            &format!(
+                "Edit flags of {} message{}",
+                self.total,
+                if self.total == 1 { "" } else { "s" }
+            ),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        let upper_left = upper_left!(inner_area);
+        let bottom_right = bottom_right!(inner_area);
+        let mut y = get_y(upper_left);
+        if self.supports_tags {
+            write_string_to_grid(
+                &format!("new tag> {}", self.new_tag),
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                (upper_left, set_y(bottom_right, y)),
+                None,
+            );
+            y += 1;
+        }
+        write_string_to_grid(
+            "Space: change, Enter: apply, Esc: cancel",
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::ITALICS,
+            (set_y(upper_left, get_y(bottom_right)), bottom_right),
+            None,
+        );
+        let rows = (get_y(bottom_right) + 1).saturating_sub(y + 1);
+        if rows == 0 {
+            context.dirty_areas.push_back(area);
+            self.dirty = false;
+            return;
+        }
+        let top = self.cursor - self.cursor % rows;
+        for (i, entry) in self.entries.iter().skip(top).take(rows).enumerate() {
+            let row_area = (set_y(upper_left, y + i), set_y(bottom_right, y + i));
+            let attrs = if top + i == self.cursor {
+                self.highlighted_attrs
+            } else {
+                self.theme_default
+            };
+            clear_area(grid, row_area, attrs);
+            let change = match entry.change {
+                Some(true) => "+",
+                Some(false) => "-",
+                None => " ",
+            };
+            let mut line = format!("{}{} {}", change, entry.checkbox(self.total), entry.name());
+            if entry.change.is_none() && entry.count > 0 && entry.count < self.total {
+                line.push_str(&format!(" ({} of {})", entry.count, self.total));
+            }
+            write_string_to_grid(&line, grid, attrs.fg, attrs.bg, attrs.attrs, row_area, None);
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.initialise(context);
+                self.set_dirty(true);
+                false
+            }
+            UIEvent::Input(Key::Esc) => {
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                true
+            }
+            UIEvent::Input(Key::Char('\n')) if !self.new_tag.is_empty() => {
+                self.add_new_tag();
+                true
+            }
+            UIEvent::Input(Key::Char('\n')) => {
+                let change_set = self.change_set();
+                if !change_set.is_empty() {
+                    context.replies.push_back(UIEvent::FinishedUIDialog(
+                        self.id,
+                        Box::new(Some(Action::Listing(ListingAction::SetFlags(change_set)))),
+                    ));
+                }
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                true
+            }
+            UIEvent::Input(Key::Up) | UIEvent::Input(Key::Ctrl('p')) => {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Down) | UIEvent::Input(Key::Ctrl('n')) => {
+                if self.cursor + 1 < self.entries.len() {
+                    self.cursor += 1;
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Char(' ')) => {
+                let total = self.total;
+                if let Some(entry) = self.entries.get_mut(self.cursor) {
+                    entry.cycle(total);
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Backspace) if self.supports_tags => {
+                self.new_tag.pop();
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Char(c)) if self.supports_tags && !c.is_whitespace() => {
+                self.new_tag.push(*c);
+                self.set_dirty(true);
+                true
+            }
+            /* The editor is modal; swallow any other input. */
+            UIEvent::Input(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_editor_entry_cycle() {
+        let mut entry = FlagEditorEntry {
+            flag: Ok(Flag::SEEN),
+            count: 1,
+            change: None,
+        };
+        assert_eq!(entry.checkbox(2), "[-]");
+        entry.cycle(2);
+        assert_eq!((entry.change, entry.checkbox(2)), (Some(true), "[x]"));
+        entry.cycle(2);
+        assert_eq!((entry.change, entry.checkbox(2)), (Some(false), "[ ]"));
+        entry.cycle(2);
+        assert_eq!((entry.change, entry.checkbox(2)), (None, "[-]"));
+
+        /* Setting a flag all messages have does nothing. */
+        entry.count = 2;
+        entry.cycle(2);
+        assert_eq!(entry.change, Some(false));
+        entry.cycle(2);
+        assert_eq!(entry.change, None);
+        entry.count = 0;
+        entry.cycle(2);
+        assert_eq!(entry.change, Some(true));
+        entry.cycle(2);
+        assert_eq!(entry.change, None);
+    }
+}
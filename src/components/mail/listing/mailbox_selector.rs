@@ -0,0 +1,294 @@
+/*
+ * meli
+ *
+ * Copyright 2017-2018 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+
+/// Scores `candidate` against `pattern` as a case-insensitive subsequence
+/// match.
+///
+/// Returns `None` if the characters of `pattern` do not all appear in
+/// `candidate` in order. Higher scores are better: consecutive matches and
+/// matches at the start of a path component are preferred.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<isize> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score: isize = 0;
+    let mut p = 0;
+    let mut last_match: Option<usize> = None;
+    for (i, c) in candidate.iter().enumerate() {
+        if p == pattern.len() {
+            break;
+        }
+        if *c != pattern[p] {
+            continue;
+        }
+        score += 1;
+        if i == 0 || matches!(candidate[i - 1], '/' | '.' | '-' | '_' | ' ') {
+            score += 3;
+        }
+        if i > 0 && last_match == Some(i - 1) {
+            score += 2;
+        }
+        last_match = Some(i);
+        p += 1;
+    }
+    if p < pattern.len() {
+        return None;
+    }
+    Some(score)
+}
+
+/// Overlay that lets the user pick a destination mailbox for the focused
+/// entries by typing a fuzzy filter.
+///
+/// Entries are shown in the order they were given (recently used targets
+/// first) until the user types something, after which they are sorted by
+/// [`fuzzy_match`] score. Pressing Enter dispatches
+/// [`ListingAction::MoveTo`] or [`ListingAction::CopyTo`] with the
+/// selected path.
+#[derive(Debug)]
+pub struct MailboxSelector {
+    move_messages: bool,
+    entries: Vec<String>,
+    filter: String,
+    results: Vec<usize>,
+    cursor: usize,
+    theme_default: ThemeAttribute,
+    highlighted_attrs: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for MailboxSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.title())
+    }
+}
+
+impl MailboxSelector {
+    pub fn new(entries: Vec<String>, move_messages: bool, context: &Context) -> Self {
+        let mut ret = MailboxSelector {
+            move_messages,
+            entries,
+            filter: String::new(),
+            results: vec![],
+            cursor: 0,
+            theme_default: Default::default(),
+            highlighted_attrs: Default::default(),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.initialise(context);
+        ret.update_results();
+        ret
+    }
+
+    fn initialise(&mut self, context: &Context) {
+        self.theme_default = crate::conf::value(context, "theme_default");
+        self.highlighted_attrs = crate::conf::value(context, "widgets.options.highlighted");
+        if !context.settings.terminal.use_color() {
+            self.highlighted_attrs.attrs |= Attr::REVERSE;
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        if self.move_messages {
+            "Move to mailbox"
+        } else {
+            "Copy to mailbox"
+        }
+    }
+
+    fn update_results(&mut self) {
+        let mut results: Vec<(isize, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| fuzzy_match(&self.filter, e).map(|score| (score, i)))
+            .collect();
+        /* Stable sort, so that ties keep the recently used entries first. */
+        results.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.results = results.into_iter().map(|(_, i)| i).collect();
+        self.cursor = 0;
+        self.dirty = true;
+    }
+}
+
+impl Component for MailboxSelector {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        let inner_area = create_box(grid, area);
+        clear_area(grid, inner_area, self.theme_default);
+        write_string_to_grid(
+            self.title(),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        let upper_left = upper_left!(inner_area);
+        let bottom_right = bottom_right!(inner_area);
+        write_string_to_grid(
+            &format!("> {}", self.filter),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            (upper_left, set_y(bottom_right, get_y(upper_left))),
+            None,
+        );
+        let rows = height!(inner_area).saturating_sub(1);
+        if self.results.is_empty() {
+            write_string_to_grid(
+                "No matching mailboxes.",
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs | Attr::ITALICS,
+                (pos_inc(upper_left, (0, 1)), bottom_right),
+                None,
+            );
+        } else if rows > 0 {
+            let top = self.cursor - self.cursor % rows;
+            for (i, idx) in self.results.iter().skip(top).take(rows).enumerate() {
+                let y = get_y(upper_left) + 1 + i;
+                let row_area = (set_y(upper_left, y), set_y(bottom_right, y));
+                let attrs = if top + i == self.cursor {
+                    self.highlighted_attrs
+                } else {
+                    self.theme_default
+                };
+                clear_area(grid, row_area, attrs);
+                write_string_to_grid(
+                    &self.entries[*idx],
+                    grid,
+                    attrs.fg,
+                    attrs.bg,
+                    attrs.attrs,
+                    row_area,
+                    None,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.initialise(context);
+                self.set_dirty(true);
+                false
+            }
+            UIEvent::Input(Key::Esc) => {
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                true
+            }
+            UIEvent::Input(Key::Char('\n')) => {
+                if let Some(path) = self
+                    .results
+                    .get(self.cursor)
+                    .map(|i| self.entries[*i].clone())
+                {
+                    let action = if self.move_messages {
+                        ListingAction::MoveTo(path)
+                    } else {
+                        ListingAction::CopyTo(path)
+                    };
+                    context.replies.push_back(UIEvent::FinishedUIDialog(
+                        self.id,
+                        Box::new(Some(Action::Listing(action))),
+                    ));
+                    context.replies.push_back(UIEvent::ComponentKill(self.id));
+                }
+                true
+            }
+            UIEvent::Input(Key::Up) | UIEvent::Input(Key::Ctrl('p')) => {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Down) | UIEvent::Input(Key::Ctrl('n')) => {
+                if self.cursor + 1 < self.results.len() {
+                    self.cursor += 1;
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Backspace) => {
+                self.filter.pop();
+                self.update_results();
+                true
+            }
+            UIEvent::Input(Key::Char(c)) => {
+                self.filter.push(*c);
+                self.update_results();
+                true
+            }
+            /* The selector is modal; swallow any other input. */
+            UIEvent::Input(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "INBOX"), Some(0));
+        assert_eq!(fuzzy_match("xyz", "INBOX"), None);
+        assert_eq!(fuzzy_match("obni", "INBOX"), None);
+        assert!(fuzzy_match("inb", "INBOX").is_some());
+        /* Matches at path component boundaries rank higher. */
+        assert!(
+            fuzzy_match("lr", "INBOX/Lists/rust").unwrap()
+                > fuzzy_match("lr", "INBOX/Archive/lore").unwrap()
+        );
+        /* Consecutive matches rank higher. */
+        assert!(
+            fuzzy_match("arch", "Archive").unwrap() > fuzzy_match("arch", "A/receipts/ch").unwrap()
+        );
+    }
+}
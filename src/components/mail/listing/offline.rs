@@ -173,6 +173,33 @@ impl Component for OfflineListing {
                     None,
                 );
             }
+            if err.kind.is_timeout() || err.kind.is_network() {
+                let highlight = conf::value(context, "highlight");
+                let (x, y) = write_string_to_grid(
+                    "[ Retry ]",
+                    grid,
+                    highlight.fg,
+                    highlight.bg,
+                    highlight.attrs,
+                    (
+                        pos_inc((0, 2 + self.messages.len()), upper_left!(area)),
+                        bottom_right!(area),
+                    ),
+                    None,
+                );
+                write_string_to_grid(
+                    &format!(
+                        " press {} to reconnect now",
+                        context.settings.shortcuts.listing.sync_now
+                    ),
+                    grid,
+                    text_unfocused.fg,
+                    text_unfocused.bg,
+                    text_unfocused.attrs,
+                    ((x, y), bottom_right!(area)),
+                    None,
+                );
+            }
         } else {
             let (_, mut y) = write_string_to_grid(
                 "loading...",
@@ -208,8 +235,17 @@ impl Component for OfflineListing {
         context.dirty_areas.push_back(area);
     }
 
-    fn process_event(&mut self, event: &mut UIEvent, _context: &mut Context) -> bool {
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
         match event {
+            UIEvent::Input(ref key)
+                if *key == context.settings.shortcuts.listing.sync_now
+                    && context.accounts[&self.cursor_pos.0].is_online.is_err() =>
+            {
+                self.messages.push("Retrying connection.".into());
+                context.accounts[&self.cursor_pos.0].retry_connection();
+                self.dirty = true;
+                return true;
+            }
             UIEvent::AccountStatusChange(account_hash, msg)
                 if *account_hash == self.cursor_pos.0 =>
             {
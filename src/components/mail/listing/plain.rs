@@ -144,6 +144,8 @@ pub struct PlainListing {
     view: MailView,
     color_cache: ColorCache,
     movement: Option<PageMovement>,
+    /// Where the entries were last drawn, for mouse events.
+    list_area: Option<Area>,
     modifier_active: bool,
     modifier_command: Option<Modifier>,
     id: ComponentId,
@@ -327,6 +329,16 @@ impl ListingTrait for PlainListing {
             self.cursor_pos.2 == idx,
             self.rows.selection[&i]
         );
+        let row_attr = if self.cursor_pos.2 != idx && !self.rows.selection[&i] {
+            self.color_cache.envelope_row_attr(
+                row_attr,
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
+            )
+        } else {
+            row_attr
+        };
 
         let (upper_left, bottom_right) = area;
         let x = get_x(upper_left)
@@ -381,6 +393,7 @@ impl ListingTrait for PlainListing {
         if rows == 0 {
             return;
         }
+        self.list_area = Some(area);
 
         if let Some(mvm) = self.movement.take() {
             match mvm {
@@ -558,6 +571,14 @@ impl ListingTrait for PlainListing {
         self.modifier_command
     }
 
+    fn entry_at(&self, pos: Pos) -> Option<usize> {
+        entry_at_pos(self.list_area?, 1, self.cursor_pos.2, self.length, pos)
+    }
+
+    fn cursor_index(&self) -> usize {
+        self.new_cursor_pos.2
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
@@ -624,6 +645,7 @@ impl PlainListing {
             view: MailView::default(),
             color_cache: ColorCache::default(),
             movement: None,
+            list_area: None,
             modifier_active: false,
             modifier_command: None,
             id: ComponentId::new_v4(),
@@ -671,7 +693,11 @@ impl PlainListing {
         let mut subject = e.subject().to_string();
         subject.truncate_at_boundary(150);
         EntryStrings {
-            date: DateString(PlainListing::format_date(e)),
+            date: DateString(PlainListing::format_date(
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                e,
+            )),
             subject: SubjectString(subject),
             flag: FlagString(format!(
                 "{selected}{unseen}{attachments}{whitespace}",
@@ -720,7 +746,12 @@ impl PlainListing {
                     ""
                 },
             )),
-            from: FromString(address_list!((e.from()) as comma_sep_list)),
+            from: super::labeled_from(
+                context,
+                self.cursor_pos.0,
+                e,
+                address_list!((e.from()) as comma_sep_list),
+            ),
             tags: TagString(tags, colors),
         }
     }
@@ -759,12 +790,17 @@ impl PlainListing {
                     continue;
                 }
             }
-            let row_attr = row_attr!(
-                self.color_cache,
-                self.length % 2 == 0,
-                !envelope.is_seen(),
-                false,
-                false
+            let row_attr = self.color_cache.envelope_row_attr(
+                row_attr!(
+                    self.color_cache,
+                    self.length % 2 == 0,
+                    !envelope.is_seen(),
+                    false,
+                    false
+                ),
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
             );
             self.rows.row_attr_cache.insert(self.length, row_attr);
 
@@ -953,7 +989,11 @@ impl PlainListing {
         }
     }
 
-    fn format_date(envelope: &Envelope) -> String {
+    fn format_date(
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+        envelope: &Envelope,
+    ) -> String {
         let d = std::time::UNIX_EPOCH + std::time::Duration::from_secs(envelope.date());
         let now: std::time::Duration = std::time::SystemTime::now()
             .duration_since(d)
@@ -964,7 +1004,12 @@ impl PlainListing {
             n if n < 4 * 24 * 60 * 60 => {
                 format!("{} days ago{}", n / (24 * 60 * 60), " ".repeat(9))
             }
-            _ => melib::datetime::timestamp_to_string(envelope.datetime(), None, false),
+            _ => mailbox_settings!(context[coordinates.0][&coordinates.1].listing.timezone).format(
+                envelope.datetime(),
+                melib::datetime::rfc822_utc_offset(envelope.date_as_str()),
+                None,
+                false,
+            ),
         }
     }
 
@@ -986,6 +1031,16 @@ impl PlainListing {
             false,
             self.rows.selection[&env_hash]
         );
+        let row_attr = if self.rows.selection[&env_hash] {
+            row_attr
+        } else {
+            self.color_cache.envelope_row_attr(
+                row_attr,
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
+            )
+        };
         self.rows.row_attr_cache.insert(idx, row_attr);
 
         let strings = self.make_entry_string(&envelope, context);
@@ -1316,6 +1371,16 @@ impl Component for PlainListing {
                         false,
                         self.rows.selection[&env_hash]
                     );
+                    let row_attr = if self.rows.selection[&env_hash] {
+                        row_attr
+                    } else {
+                        self.color_cache.envelope_row_attr(
+                            row_attr,
+                            context,
+                            (self.cursor_pos.0, self.cursor_pos.1),
+                            &envelope,
+                        )
+                    };
                     self.rows.row_attr_cache.insert(row, row_attr);
                     let page_no = (self.new_cursor_pos.2).wrapping_div(rows);
 
@@ -1379,7 +1444,19 @@ impl Component for PlainListing {
                         && (shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"])
                             || shortcut!(k == shortcuts[Shortcuts::LISTING]["focus_right"])) =>
                 {
+                    let action = if shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"]) {
+                        self.open_entry_action(IndexStyle::Plain, context)
+                    } else {
+                        OpenEntryAction::Preview
+                    };
+                    if action == OpenEntryAction::MarkReadAndAdvance {
+                        self.mark_read_and_advance(context);
+                        return true;
+                    }
                     self.set_focus(Focus::Entry, context);
+                    if action == OpenEntryAction::Fullscreen {
+                        self.set_focus(Focus::EntryFullscreen, context);
+                    }
                     return true;
                 }
                 UIEvent::Input(ref k)
@@ -1450,6 +1527,7 @@ impl Component for PlainListing {
                 self.set_dirty(true);
             }
             UIEvent::MailboxUpdate((ref idxa, ref idxf))
+            | UIEvent::ThreadUpdate((ref idxa, ref idxf), _)
                 if (*idxa, *idxf) == (self.new_cursor_pos.0, self.cursor_pos.1) =>
             {
                 self.refresh_mailbox(context, false);
@@ -0,0 +1,342 @@
+/*
+ * meli
+ *
+ * Copyright 2017-2018 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::convert::TryFrom;
+
+use melib::search::Query;
+
+use super::*;
+
+const FROM: &str = "From";
+const TO: &str = "To";
+const SUBJECT: &str = "Subject";
+const TEXT: &str = "Text";
+const AFTER: &str = "After (YYYY-MM-DD)";
+const BEFORE: &str = "Before (YYYY-MM-DD)";
+const FLAGS: &str = "Flags";
+const ATTACHMENT: &str = "Attachments";
+const MAILBOX: &str = "Mailbox";
+
+/// The fields of the [`QueryBuilder`] form.
+#[derive(Debug, Default, Clone)]
+pub struct QueryTerms {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub after: String,
+    pub before: String,
+    /// Comma or space separated flags and tags, e.g. `unread, flagged`.
+    pub flags: String,
+    pub has_attachment: Option<bool>,
+}
+
+impl QueryTerms {
+    /// Builds a query string in the search syntax described in `meli(1)`,
+    /// joining all non-empty fields with `and`.
+    pub fn to_query_string(&self) -> String {
+        fn literal(value: &str) -> String {
+            let value = value.trim();
+            if value.chars().all(char::is_alphanumeric) {
+                value.to_string()
+            } else {
+                /* Quoted strings can't contain quotes. */
+                format!("\"{}\"", value.replace('"', ""))
+            }
+        }
+
+        let mut atoms = vec![];
+        for (prefix, value) in [
+            ("from:", &self.from),
+            ("to:", &self.to),
+            ("subject:", &self.subject),
+            ("", &self.text),
+        ] {
+            if !value.trim().is_empty() {
+                atoms.push(format!("{}{}", prefix, literal(value)));
+            }
+        }
+        for (prefix, value) in [("after:", &self.after), ("before:", &self.before)] {
+            if !value.trim().is_empty() {
+                atoms.push(format!("{}{}", prefix, value.trim()));
+            }
+        }
+        let flags = self
+            .flags
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|f| !f.is_empty())
+            .map(literal)
+            .collect::<Vec<String>>();
+        if !flags.is_empty() {
+            atoms.push(format!("is:{}", flags.join(",")));
+        }
+        match self.has_attachment {
+            Some(true) => atoms.push("has:attachment".to_string()),
+            /* `not` applies to everything that follows it. */
+            Some(false) => atoms.push("(not has:attachment)".to_string()),
+            None => {}
+        }
+        atoms.join(" and ")
+    }
+}
+
+/// Overlay with a form for building a search query field by field, for
+/// users who don't know the query syntax.
+///
+/// The resulting query is shown live at the bottom of the overlay. Running
+/// it sends a [`StartupView`] with the query and the chosen mailbox back to
+/// the listing with [`UIEvent::FinishedUIDialog`].
+#[derive(Debug)]
+pub struct QueryBuilder {
+    form: FormWidget<FormButtonActions>,
+    /// Mailbox paths to choose the search scope from, the current mailbox
+    /// first.
+    mailboxes: Vec<String>,
+    theme_default: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for QueryBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "query builder")
+    }
+}
+
+impl QueryBuilder {
+    pub fn new(mailboxes: Vec<String>, context: &Context) -> Self {
+        let mut ret = QueryBuilder {
+            form: Self::new_form(&mailboxes),
+            mailboxes,
+            theme_default: crate::conf::value(context, "theme_default"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.form.set_dirty(true);
+        ret
+    }
+
+    fn new_form(mailboxes: &[String]) -> FormWidget<FormButtonActions> {
+        let mut form = FormWidget::new(("Search".into(), FormButtonActions::Accept));
+        form.add_button(("Reset".into(), FormButtonActions::Reset));
+        form.add_button(("Cancel".into(), FormButtonActions::Cancel));
+        for field in [FROM, TO, SUBJECT, TEXT, AFTER, BEFORE, FLAGS] {
+            form.push((field.into(), String::new()));
+        }
+        form.push_choices((
+            ATTACHMENT.into(),
+            vec!["any".into(), "yes".into(), "no".into()],
+        ));
+        form.push_choices((
+            MAILBOX.into(),
+            mailboxes.iter().map(|p| p.clone().into()).collect(),
+        ));
+        form
+    }
+
+    fn terms(&self) -> QueryTerms {
+        let values = self.form.values();
+        let value = |field: &str| {
+            values
+                .get(field)
+                .map(|v| v.as_str().to_string())
+                .unwrap_or_default()
+        };
+        QueryTerms {
+            from: value(FROM),
+            to: value(TO),
+            subject: value(SUBJECT),
+            text: value(TEXT),
+            after: value(AFTER),
+            before: value(BEFORE),
+            flags: value(FLAGS),
+            has_attachment: match values.get(ATTACHMENT).map(Field::cursor) {
+                Some(1) => Some(true),
+                Some(2) => Some(false),
+                _ => None,
+            },
+        }
+    }
+
+    /// Validates the query and sends it to the listing. Returns `false` if
+    /// the form has to be corrected first.
+    fn run(&mut self, context: &mut Context) -> bool {
+        let query = self.terms().to_query_string();
+        if query.is_empty() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "Fill in at least one field.".to_string(),
+                )));
+            return false;
+        }
+        if let Err(err) = Query::try_from(query.as_str()) {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    "Invalid query `{}`: {}",
+                    query, err
+                ))));
+            return false;
+        }
+        let mailbox = match self.form.values().get(MAILBOX).map(Field::cursor) {
+            Some(idx) if idx > 0 => self.mailboxes.get(idx).cloned(),
+            _ => None,
+        };
+        context.replies.push_back(UIEvent::FinishedUIDialog(
+            self.id,
+            Box::new(Some(StartupView {
+                account: None,
+                mailbox,
+                query: Some(query),
+            })),
+        ));
+        context.replies.push_back(UIEvent::ComponentKill(self.id));
+        true
+    }
+}
+
+impl Component for QueryBuilder {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        let inner_area = create_box(grid, area);
+        clear_area(grid, inner_area, self.theme_default);
+        write_string_to_grid(
+            "Build search query",
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        /* Show the query being built on the bottom border. */
+        let query = self.terms().to_query_string();
+        write_string_to_grid(
+            &format!(
+                " {} ",
+                if query.is_empty() {
+                    "(empty query)"
+                } else {
+                    query.as_str()
+                }
+            ),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            (
+                set_y(
+                    pos_inc(upper_left!(area), (2, 0)),
+                    get_y(bottom_right!(area)),
+                ),
+                bottom_right!(area),
+            ),
+            None,
+        );
+        self.form.set_dirty(true);
+        self.form.draw(grid, inner_area, context);
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+                return false;
+            }
+            UIEvent::Input(Key::Esc) => {
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                return true;
+            }
+            _ => {}
+        }
+        if self.form.process_event(event, context) {
+            match self.form.buttons_result() {
+                Some(FormButtonActions::Accept) => {
+                    if !self.run(context) {
+                        /* Keep the values so that the user can correct them. */
+                        let form =
+                            std::mem::replace(&mut self.form, Self::new_form(&self.mailboxes));
+                        if let Some(values) = form.collect() {
+                            *self.form.values_mut() = values;
+                        }
+                    }
+                }
+                Some(FormButtonActions::Reset) => {
+                    self.form = Self::new_form(&self.mailboxes);
+                }
+                Some(FormButtonActions::Cancel) => {
+                    context.replies.push_back(UIEvent::ComponentKill(self.id));
+                }
+                Some(FormButtonActions::Other(_)) | None => {}
+            }
+            self.set_dirty(true);
+            return true;
+        }
+        /* The overlay is modal; swallow any other input. */
+        matches!(event, UIEvent::Input(_))
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.form.is_dirty()
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+        self.form.set_dirty(value);
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryTerms;
+
+    #[test]
+    fn test_query_builder_terms() {
+        assert_eq!(QueryTerms::default().to_query_string(), "");
+        let terms = QueryTerms {
+            from: "alice".to_string(),
+            subject: "weekly report".to_string(),
+            after: "2023-01-01".to_string(),
+            flags: "unread, flagged".to_string(),
+            has_attachment: Some(false),
+            ..QueryTerms::default()
+        };
+        assert_eq!(
+            terms.to_query_string(),
+            "from:alice and subject:\"weekly report\" and after:2023-01-01 and is:unread,flagged \
+             and (not has:attachment)"
+        );
+    }
+}
@@ -121,6 +121,8 @@ pub struct ThreadListing {
 
     data_columns: DataColumns<5>,
     rows: RowsState<(ThreadHash, EnvelopeHash)>,
+    /// Threads pinned to the top of the listing.
+    pinned: HashSet<ThreadHash>,
     /// If we must redraw on next redraw event
     dirty: bool,
     force_draw: bool,
@@ -131,6 +133,8 @@ pub struct ThreadListing {
     modifier_active: bool,
     modifier_command: Option<Modifier>,
     movement: Option<PageMovement>,
+    /// Where the entries were last drawn, for mouse events.
+    list_area: Option<Area>,
     id: ComponentId,
 }
 
@@ -202,15 +206,19 @@ impl MailListingTrait for ThreadListing {
                 return;
             }
         }
+        self.pinned = context.accounts[&self.cursor_pos.0].pinned_threads(self.cursor_pos.1);
+        let snoozed = context.accounts[&self.cursor_pos.0].snoozed_threads(self.cursor_pos.1);
         let threads = context.accounts[&self.cursor_pos.0]
             .collection
             .get_threads(self.cursor_pos.1);
         let mut roots = threads.roots();
+        hide_snoozed(&mut roots, &snoozed);
         threads.group_inner_sort_by(
             &mut roots,
             self.sort,
             &context.accounts[&self.cursor_pos.0].collection.envelopes,
         );
+        pinned_first(&mut roots, &self.pinned);
 
         self.redraw_threads_list(
             context,
@@ -340,12 +348,21 @@ impl MailListingTrait for ThreadListing {
                     smallvec::smallvec![env_hash],
                     entry_strings,
                 );
-                let row_attr = row_attr!(
-                    self.color_cache,
-                    idx % 2 == 0,
-                    !envelope.is_seen(),
-                    false,
-                    false,
+                let row_attr = self.color_cache.pinned_row_attr(
+                    row_attr!(
+                        self.color_cache,
+                        idx % 2 == 0,
+                        !envelope.is_seen(),
+                        false,
+                        false,
+                    ),
+                    self.pinned.contains(&prev_group),
+                );
+                let row_attr = self.color_cache.envelope_row_attr(
+                    row_attr,
+                    context,
+                    (self.cursor_pos.0, self.cursor_pos.1),
+                    &envelope,
                 );
                 self.rows.row_attr_cache.insert(idx, row_attr);
                 idx += 1;
@@ -444,6 +461,7 @@ impl ListingTrait for ThreadListing {
         if rows == 0 {
             return;
         }
+        self.list_area = Some(area);
         if let Some(mvm) = self.movement.take() {
             match mvm {
                 PageMovement::Up(amount) => {
@@ -576,13 +594,28 @@ impl ListingTrait for ThreadListing {
             .collection
             .get_env(env_hash);
 
-        let row_attr = row_attr!(
-            self.color_cache,
-            idx % 2 == 0,
-            !envelope.is_seen(),
-            self.cursor_pos.2 == idx,
-            self.rows.selection[&env_hash],
+        let row_attr = self.color_cache.pinned_row_attr(
+            row_attr!(
+                self.color_cache,
+                idx % 2 == 0,
+                !envelope.is_seen(),
+                self.cursor_pos.2 == idx,
+                self.rows.selection[&env_hash],
+            ),
+            self.cursor_pos.2 != idx
+                && !self.rows.selection[&env_hash]
+                && self.is_pinned(context, env_hash),
         );
+        let row_attr = if self.cursor_pos.2 != idx && !self.rows.selection[&env_hash] {
+            self.color_cache.envelope_row_attr(
+                row_attr,
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
+            )
+        } else {
+            row_attr
+        };
         for row in grid.bounds_iter(area) {
             for c in row {
                 grid[c]
@@ -622,6 +655,14 @@ impl ListingTrait for ThreadListing {
         self.modifier_command
     }
 
+    fn entry_at(&self, pos: Pos) -> Option<usize> {
+        entry_at_pos(self.list_area?, 1, self.cursor_pos.2, self.length, pos)
+    }
+
+    fn cursor_index(&self) -> usize {
+        self.new_cursor_pos.2
+    }
+
     fn set_movement(&mut self, mvm: PageMovement) {
         self.movement = Some(mvm);
         self.set_dirty(true);
@@ -692,6 +733,8 @@ impl ThreadListing {
             view: None,
             initialised: false,
             movement: None,
+            list_area: None,
+            pinned: HashSet::default(),
             modifier_active: false,
             modifier_command: None,
             id: ComponentId::new_v4(),
@@ -699,6 +742,21 @@ impl ThreadListing {
         })
     }
 
+    /// Returns whether the thread of `env_hash` is pinned.
+    fn is_pinned(&self, context: &Context, env_hash: EnvelopeHash) -> bool {
+        if self.pinned.is_empty() {
+            return false;
+        }
+        let threads = context.accounts[&self.cursor_pos.0]
+            .collection
+            .get_threads(self.cursor_pos.1);
+        self.rows
+            .env_to_thread
+            .get(&env_hash)
+            .map(|thread| self.pinned.contains(&threads.find_group(*thread)))
+            .unwrap_or(false)
+    }
+
     fn highlight_line_self(&mut self, _idx: usize, _context: &Context) {
         /*
          * FIXME
@@ -813,10 +871,19 @@ impl ThreadListing {
         let mut subject = e.subject().to_string();
         subject.truncate_at_boundary(150);
         EntryStrings {
-            date: DateString(ConversationsListing::format_date(context, e.date())),
+            date: DateString(ConversationsListing::format_date(
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                e.date(),
+            )),
             subject: SubjectString(subject),
             flag: FlagString((if e.has_attachments() { "📎" } else { "" }).to_string()),
-            from: FromString(address_list!((e.from()) as comma_sep_list)),
+            from: super::labeled_from(
+                context,
+                self.cursor_pos.0,
+                e,
+                address_list!((e.from()) as comma_sep_list),
+            ),
             tags: TagString(tags, colors),
         }
     }
@@ -988,13 +1055,26 @@ impl ThreadListing {
         let envelope: EnvelopeRef = account.collection.get_env(env_hash);
         let thread_hash = self.rows.env_to_thread[&env_hash];
         let idx = self.rows.env_order[&env_hash];
-        let row_attr = row_attr!(
-            self.color_cache,
-            idx % 2 == 0,
-            !envelope.is_seen(),
-            false,
-            self.rows.selection[&env_hash]
+        let row_attr = self.color_cache.pinned_row_attr(
+            row_attr!(
+                self.color_cache,
+                idx % 2 == 0,
+                !envelope.is_seen(),
+                false,
+                self.rows.selection[&env_hash]
+            ),
+            !self.rows.selection[&env_hash] && self.is_pinned(context, env_hash),
         );
+        let row_attr = if self.rows.selection[&env_hash] {
+            row_attr
+        } else {
+            self.color_cache.envelope_row_attr(
+                row_attr,
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
+            )
+        };
         self.rows.row_attr_cache.insert(idx, row_attr);
 
         let mut strings = self.make_entry_string(&envelope, context);
@@ -1354,7 +1434,19 @@ impl Component for ThreadListing {
                     && (shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"])
                         || shortcut!(k == shortcuts[Shortcuts::LISTING]["focus_right"])) =>
             {
+                let action = if shortcut!(k == shortcuts[Shortcuts::LISTING]["open_entry"]) {
+                    self.open_entry_action(IndexStyle::Threaded, context)
+                } else {
+                    OpenEntryAction::Preview
+                };
+                if action == OpenEntryAction::MarkReadAndAdvance {
+                    self.mark_read_and_advance(context);
+                    return true;
+                }
                 self.set_focus(Focus::Entry, context);
+                if action == OpenEntryAction::Fullscreen {
+                    self.set_focus(Focus::EntryFullscreen, context);
+                }
                 return true;
             }
             UIEvent::Input(ref k)
@@ -1389,6 +1481,7 @@ impl Component for ThreadListing {
                 return true;
             }
             UIEvent::MailboxUpdate((ref idxa, ref idxf))
+            | UIEvent::ThreadUpdate((ref idxa, ref idxf), _)
                 if (*idxa, *idxf) == (self.new_cursor_pos.0, self.cursor_pos.1) =>
             {
                 self.refresh_mailbox(context, false);
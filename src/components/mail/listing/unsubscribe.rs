@@ -0,0 +1,301 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The unsubscribe assistant: lists the unsubscribe methods of the selected
+//! messages per sender, and unsubscribes with the chosen ones.
+
+use std::{
+    convert::TryFrom,
+    process::{Command, Stdio},
+};
+
+use melib::{
+    email::{
+        list_management::{ListActions, UnsubscribeMethod},
+        mailto::Mailto,
+    },
+    error::ResultIntoError,
+};
+
+use super::*;
+use crate::{components::mail::sender_warning, conf::accounts::Account};
+
+/// Returns the unsubscribe methods offered by `envelopes` per lowercased
+/// sender address, in the order the senders first appear.
+pub fn unsubscribe_methods<'a>(
+    envelopes: impl IntoIterator<Item = &'a Envelope>,
+) -> Vec<(String, Vec<UnsubscribeMethod>)> {
+    let mut ret: Vec<(String, Vec<UnsubscribeMethod>)> = vec![];
+    for envelope in envelopes {
+        let sender = match envelope.from().first() {
+            Some(from) => from.get_email().to_lowercase(),
+            None => continue,
+        };
+        let methods = ListActions::detect(envelope)
+            .map(|actions| actions.unsubscribe_methods())
+            .unwrap_or_default();
+        if methods.is_empty() {
+            continue;
+        }
+        let pos = match ret.iter().position(|(s, _)| *s == sender) {
+            Some(pos) => pos,
+            None => {
+                ret.push((sender, vec![]));
+                ret.len() - 1
+            }
+        };
+        for method in methods {
+            if !ret[pos].1.contains(&method) {
+                ret[pos].1.push(method);
+            }
+        }
+    }
+    ret
+}
+
+/// Returns a dialog for choosing among the unsubscribe methods of
+/// `env_hashes`, noting the senders the user already unsubscribed from and
+/// whether they keep mailing. The chosen methods are run with one
+/// [`MailingListAction::Unsubscribe`].
+pub fn unsubscribe_assistant(
+    account: &Account,
+    env_hashes: &[EnvelopeHash],
+    context: &Context,
+) -> std::result::Result<UIDialog<(String, UnsubscribeMethod)>, String> {
+    let envelopes = account.collection.envelopes.read().unwrap();
+    let selected = env_hashes
+        .iter()
+        .filter_map(|env_hash| envelopes.get(env_hash))
+        .collect::<Vec<&Envelope>>();
+    let still_mailing = selected
+        .iter()
+        .filter_map(|envelope| {
+            sender_warning::unsubscribed_warning(envelope, &account.unsubscribed_senders)
+        })
+        .filter_map(|warning| match warning {
+            sender_warning::SenderWarning::StillMailing(email, _) => Some(email),
+            _ => None,
+        })
+        .collect::<HashSet<String>>();
+    let mut entries = vec![];
+    for (sender, methods) in unsubscribe_methods(selected.iter().copied()) {
+        let status = match account.unsubscribed_senders.get(&sender) {
+            Some(since) => format!(
+                " (unsubscribed on {}{})",
+                melib::datetime::timestamp_to_string(*since, Some("%Y-%m-%d"), false),
+                if still_mailing.contains(&sender) {
+                    ", still mailing"
+                } else {
+                    ""
+                }
+            ),
+            None => String::new(),
+        };
+        #[cfg(not(feature = "http"))]
+        let methods = methods
+            .into_iter()
+            .filter(|method| !matches!(method, UnsubscribeMethod::OneClick(_)));
+        for method in methods {
+            let title = format!("{}{}: {}", sender, status, method);
+            entries.push(((sender.clone(), method), title));
+        }
+    }
+    if entries.is_empty() {
+        return Err("The selected messages offer no way to unsubscribe.".to_string());
+    }
+    Ok(UIDialog::new(
+        "unsubscribe",
+        entries,
+        false,
+        Some(Box::new(
+            move |id: ComponentId, results: &[(String, UnsubscribeMethod)]| {
+                Some(UIEvent::FinishedUIDialog(
+                    id,
+                    Box::new(if results.is_empty() {
+                        None
+                    } else {
+                        Some(Action::MailingListAction(MailingListAction::Unsubscribe(
+                            results.to_vec(),
+                        )))
+                    }),
+                ))
+            },
+        )),
+        context,
+    ))
+}
+
+/// Unsubscribes from each sender with its chosen method, and records the
+/// senders in [`Account::unsubscribed_senders`].
+pub fn unsubscribe(
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+    choices: Vec<(String, UnsubscribeMethod)>,
+    context: &mut Context,
+) {
+    for (sender, method) in choices {
+        let result = match method {
+            #[cfg(feature = "http")]
+            UnsubscribeMethod::OneClick(url) => {
+                one_click(account_hash, sender, url, context);
+                continue;
+            }
+            #[cfg(not(feature = "http"))]
+            UnsubscribeMethod::OneClick(_) => Err(Error::new(
+                "One-click unsubscribing needs meli to be built with the `http` feature.",
+            )),
+            UnsubscribeMethod::Email(mailto) => send_email(account_hash, &mailto, context)
+                .map(|()| format!("Sent unsubscribe e-mail for {}.", sender)),
+            UnsubscribeMethod::Url(url) => open_url(account_hash, mailbox_hash, &url, context)
+                .map(|()| format!("Opened the unsubscribe page of {}.", sender)),
+        };
+        finish(account_hash, &sender, result, context);
+    }
+}
+
+/// Records the unsubscription from `sender` and shows `result`'s message, or
+/// reports its error.
+fn finish(account_hash: AccountHash, sender: &str, result: Result<String>, context: &mut Context) {
+    match result.and_then(|message| {
+        context.accounts[&account_hash].mark_unsubscribed(sender)?;
+        Ok(message)
+    }) {
+        Ok(message) => context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(message))),
+        Err(err) => context.replies.push_back(UIEvent::Notification(
+            Some(format!("Could not unsubscribe from {}", sender)),
+            err.to_string(),
+            Some(NotificationType::Error(err.kind)),
+        )),
+    }
+}
+
+#[cfg(feature = "http")]
+fn one_click(account_hash: AccountHash, sender: String, url: String, context: &mut Context) {
+    use std::{future::Future, pin::Pin};
+
+    let account = &mut context.accounts[&account_hash];
+    let (result_sender, mut receiver) = crate::jobs::oneshot::channel();
+    let fut: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>> = Box::pin(async move {
+        let _ = result_sender.send(melib::email::list_management::one_click_unsubscribe(url).await);
+        Ok(())
+    });
+    let handle = account.job_executor.spawn_specialized(fut);
+    account.insert_job(
+        handle.job_id,
+        JobRequest::Generic {
+            name: format!("unsubscribing from {}", sender).into(),
+            handle,
+            on_finish: Some(CallbackFn(Box::new(move |context: &mut Context| {
+                if let Ok(Some(result)) = receiver.try_recv() {
+                    let result = result.map(|()| format!("Unsubscribed from {}.", sender));
+                    finish(account_hash, &sender, result, context);
+                }
+            }))),
+            log_level: LogLevel::TRACE,
+        },
+    );
+}
+
+fn send_email(account_hash: AccountHash, mailto: &str, context: &mut Context) -> Result<()> {
+    let mut draft: Draft = Mailto::try_from(mailto.as_bytes())
+        .map_err(|_| Error::new(format!("Could not parse unsubscribe address {}.", mailto)))?
+        .into();
+    draft.set_header(
+        "From",
+        context.accounts[&account_hash]
+            .settings
+            .account()
+            .make_display_name(),
+    );
+    crate::components::mail::compose::send_draft(
+        ToggleFlag::False,
+        context,
+        account_hash,
+        draft,
+        SpecialUsageMailbox::Sent,
+        Flag::SEEN,
+        true,
+    )?;
+    Ok(())
+}
+
+fn open_url(
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+    url: &str,
+    context: &mut Context,
+) -> Result<()> {
+    let url_launcher = mailbox_settings!(context[account_hash][&mailbox_hash].pager.url_launcher)
+        .as_deref()
+        .unwrap_or(
+            #[cfg(target_os = "macos")]
+            {
+                "open"
+            },
+            #[cfg(not(target_os = "macos"))]
+            {
+                "xdg-open"
+            },
+        );
+    let child = Command::new(url_launcher)
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .chain_err_summary(|| format!("Couldn't launch {:?}", url_launcher))?;
+    context.children.push(child);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_methods() {
+        let first = Envelope::from_bytes(
+            b"From: News <News@example.com>\nList-Unsubscribe: <mailto:leave@example.com>\n\
+              Subject: news\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        let second = Envelope::from_bytes(
+            b"From: news@example.com\nList-Unsubscribe: <https://example.com/u>, \
+              <mailto:leave@example.com>\nSubject: news\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        let personal =
+            Envelope::from_bytes(b"From: friend@example.org\nSubject: hi\n\nbody\n", None).unwrap();
+        assert_eq!(
+            unsubscribe_methods([&first, &personal, &second]),
+            vec![(
+                "news@example.com".to_string(),
+                vec![
+                    UnsubscribeMethod::Email("mailto:leave@example.com".to_string()),
+                    UnsubscribeMethod::Url("https://example.com/u".to_string()),
+                ]
+            )]
+        );
+    }
+}
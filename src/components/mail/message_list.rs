@@ -0,0 +1,422 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A listing of messages read from files, used by the `view` subcommand when
+//! it is given more than one message.
+
+use std::{
+    cmp,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use melib::{backends::mbox::MessageIterator, email::Mail, Flag};
+
+use super::*;
+use crate::melib::text_processing::TextProcessing;
+
+const DATE_FMT: &str = "%Y-%m-%d %H:%M";
+
+/// Parses `bytes` read from `source` into messages. Input that starts with a
+/// `From ` line is split as an mbox file, anything else is parsed as a single
+/// message.
+pub fn read_messages(source: &str, bytes: Vec<u8>) -> Result<Vec<(String, Mail)>> {
+    if !bytes.starts_with(b"From ") {
+        let mail = Mail::new(bytes, Some(Flag::SEEN))
+            .chain_err_summary(|| format!("Could not parse `{}`", source))?;
+        return Ok(vec![(source.to_string(), mail)]);
+    }
+    let index = Arc::new(Mutex::new(HashMap::default()));
+    let envelopes = MessageIterator {
+        index: index.clone(),
+        input: &bytes,
+        file_offset: 0,
+        offset: 0,
+        format: None,
+    }
+    .collect::<Result<Vec<Envelope>>>()
+    .chain_err_summary(|| format!("Could not parse mbox file `{}`", source))?;
+    let index = index.lock().unwrap();
+    let mut ret = Vec::with_capacity(envelopes.len());
+    for (i, mut envelope) in envelopes.into_iter().enumerate() {
+        let (offset, length) = index[&envelope.hash()];
+        envelope.set_flags(envelope.flags() | Flag::SEEN);
+        ret.push((
+            format!("{}:{}", source, i + 1),
+            Mail {
+                envelope,
+                bytes: bytes[offset..offset + length].to_vec(),
+            },
+        ));
+    }
+    Ok(ret)
+}
+
+#[derive(Debug)]
+pub struct MessageList {
+    cursor_pos: usize,
+    new_cursor_pos: usize,
+    /// Messages and the file they were read from.
+    entries: Vec<(String, Mail)>,
+    length: usize,
+    data_columns: DataColumns<4>,
+
+    initialized: bool,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+
+    dirty: bool,
+
+    movement: Option<PageMovement>,
+    id: ComponentId,
+}
+
+impl fmt::Display for MessageList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "messages")
+    }
+}
+
+impl MessageList {
+    pub fn new(entries: Vec<(String, Mail)>, context: &Context) -> Self {
+        let theme_default = crate::conf::value(context, "theme_default");
+        let mut data_columns = DataColumns::default();
+        data_columns.theme_config.set_single_theme(theme_default);
+        MessageList {
+            cursor_pos: 0,
+            new_cursor_pos: 0,
+            length: entries.len(),
+            entries,
+            data_columns,
+            theme_default,
+            highlight_theme: crate::conf::value(context, "highlight"),
+            initialized: false,
+            dirty: true,
+            movement: None,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    fn initialize(&mut self, context: &Context) {
+        self.initialized = true;
+        self.set_dirty(true);
+        let rows: Vec<[String; 4]> = self
+            .entries
+            .iter()
+            .map(|(source, mail)| {
+                [
+                    source.clone(),
+                    melib::datetime::timestamp_to_string(mail.date(), Some(DATE_FMT), false),
+                    mail.field_from_to_string(),
+                    mail.subject().to_string(),
+                ]
+            })
+            .collect();
+
+        let mut min_width = ["file".len(), "date".len(), "from".len(), 0];
+        for row in &rows {
+            for (w, s) in min_width.iter_mut().zip(row.iter()) {
+                *w = cmp::max(*w, s.grapheme_width());
+            }
+        }
+        for (i, w) in min_width.iter().enumerate() {
+            self.data_columns.columns[i] =
+                CellBuffer::new_with_context(*w, self.length, None, context);
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            for (i, s) in row.iter().enumerate() {
+                write_string_to_grid(
+                    s,
+                    &mut self.data_columns.columns[i],
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs,
+                    ((0, idx), (min_width[i], idx)),
+                    None,
+                );
+            }
+        }
+    }
+}
+
+impl Component for MessageList {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.is_dirty() {
+            return;
+        }
+        if !self.initialized {
+            self.initialize(context);
+        }
+        let (upper_left, bottom_right) = area;
+        if self.length == 0 {
+            clear_area(grid, area, self.theme_default);
+            context.dirty_areas.push_back(area);
+            self.dirty = false;
+            return;
+        }
+        let rows = get_y(bottom_right) - get_y(upper_left) + 1;
+
+        if let Some(mvm) = self.movement.take() {
+            match mvm {
+                PageMovement::Up(amount) => {
+                    self.new_cursor_pos = self.new_cursor_pos.saturating_sub(amount);
+                }
+                PageMovement::PageUp(multiplier) => {
+                    self.new_cursor_pos = self.new_cursor_pos.saturating_sub(rows * multiplier);
+                }
+                PageMovement::Down(amount) => {
+                    if self.new_cursor_pos + amount < self.length {
+                        self.new_cursor_pos += amount;
+                    } else {
+                        self.new_cursor_pos = self.length - 1;
+                    }
+                }
+                PageMovement::PageDown(multiplier) => {
+                    #[allow(clippy::comparison_chain)]
+                    if self.new_cursor_pos + rows * multiplier < self.length {
+                        self.new_cursor_pos += rows * multiplier;
+                    } else if self.new_cursor_pos + rows * multiplier > self.length {
+                        self.new_cursor_pos = self.length - 1;
+                    } else {
+                        self.new_cursor_pos = (self.length / rows) * rows;
+                    }
+                }
+                PageMovement::Right(_) | PageMovement::Left(_) => {}
+                PageMovement::Home => {
+                    self.new_cursor_pos = 0;
+                }
+                PageMovement::End => {
+                    self.new_cursor_pos = self.length - 1;
+                }
+            }
+        }
+
+        let prev_page_no = (self.cursor_pos).wrapping_div(rows);
+        let page_no = (self.new_cursor_pos).wrapping_div(rows);
+
+        let top_idx = page_no * rows;
+
+        /* If cursor position has changed, remove the highlight from the previous
+         * position and apply it in the new one. */
+        if self.cursor_pos != self.new_cursor_pos && prev_page_no == page_no {
+            let old_cursor_pos = self.cursor_pos;
+            self.cursor_pos = self.new_cursor_pos;
+            for &(idx, highlight) in &[(old_cursor_pos, false), (self.new_cursor_pos, true)] {
+                if idx >= self.length {
+                    continue; //bounds check
+                }
+                let new_area = nth_row_area(area, idx % rows);
+                self.data_columns
+                    .draw(grid, idx, self.cursor_pos, grid.bounds_iter(new_area));
+                let row_attr = if highlight {
+                    self.highlight_theme
+                } else {
+                    self.theme_default
+                };
+                change_colors(grid, new_area, row_attr.fg, row_attr.bg);
+                context.dirty_areas.push_back(new_area);
+            }
+            self.dirty = false;
+            return;
+        } else if self.cursor_pos != self.new_cursor_pos {
+            self.cursor_pos = self.new_cursor_pos;
+        }
+        /* Page_no has changed, so draw new page */
+        _ = self
+            .data_columns
+            .recalc_widths((width!(area), height!(area)), top_idx);
+        clear_area(grid, area, self.theme_default);
+        /* copy table columns */
+        self.data_columns
+            .draw(grid, top_idx, self.cursor_pos, grid.bounds_iter(area));
+
+        /* highlight cursor */
+        change_colors(
+            grid,
+            nth_row_area(area, self.cursor_pos % rows),
+            self.highlight_theme.fg,
+            self.highlight_theme.bg,
+        );
+
+        /* clear gap if available height is more than count of entries */
+        if top_idx + rows > self.length {
+            clear_area(
+                grid,
+                (
+                    pos_inc(upper_left, (0, self.length - top_idx)),
+                    bottom_right,
+                ),
+                self.theme_default,
+            );
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "highlight");
+                self.initialized = false;
+                self.set_dirty(true);
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_up"]) =>
+            {
+                self.movement = Some(PageMovement::Up(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_down"])
+                    && self.cursor_pos < self.length.saturating_sub(1) =>
+            {
+                self.movement = Some(PageMovement::Down(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["prev_page"]) =>
+            {
+                self.movement = Some(PageMovement::PageUp(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["next_page"]) =>
+            {
+                self.movement = Some(PageMovement::PageDown(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["home_page"]) =>
+            {
+                self.movement = Some(PageMovement::Home);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["end_page"]) =>
+            {
+                self.movement = Some(PageMovement::End);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["open_entry"])
+                    && self.cursor_pos < self.length =>
+            {
+                let view = EnvelopeView::new(
+                    self.entries[self.cursor_pos].1.clone(),
+                    None,
+                    None,
+                    AccountHash::default(),
+                );
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(view))))));
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+
+        map.insert(
+            Shortcuts::GENERAL,
+            context.settings.shortcuts.general.key_values(),
+        );
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+
+    fn get_status(&self, _context: &Context) -> String {
+        format!("{} messages", self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_list_read_messages() {
+        let mbox = b"From alice@example.com Mon Jan  2 10:00:00 2023\n\
+From: alice@example.com\n\
+To: bob@example.com\n\
+Subject: first\n\
+Message-ID: <1@example.com>\n\
+\n\
+Hello.\n\
+\n\
+From bob@example.com Mon Jan  2 11:00:00 2023\n\
+From: bob@example.com\n\
+To: alice@example.com\n\
+Subject: second\n\
+Message-ID: <2@example.com>\n\
+\n\
+Hi.\n"
+            .to_vec();
+        let messages = read_messages("test.mbox", mbox).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].0, "test.mbox:1");
+        assert_eq!(messages[0].1.subject(), "first");
+        assert_eq!(messages[1].1.subject(), "second");
+        assert!(messages[1].1.bytes().ends_with(b"Hi.\n"));
+
+        let eml = b"From: alice@example.com\nSubject: single\n\nBody\n".to_vec();
+        let messages = read_messages("test.eml", eml).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, "test.eml");
+        assert_eq!(messages[0].1.subject(), "single");
+    }
+}
@@ -34,25 +34,39 @@ use melib::{
     parser::BytesExt,
 };
 
+/// Decrypts `raw`, the encrypted part of a PGP/MIME message or an inline
+/// ASCII armored message.
 pub async fn decrypt(raw: Vec<u8>) -> Result<(melib_pgp::DecryptionMetadata, Vec<u8>)> {
-    Err("libgpgme functions are temporarily disabled due to an unsolved bug <https://git.meli.delivery/meli/meli/issues/176>.".into())
-    /*
     let mut ctx = Context::new()?;
     let cipher = ctx.new_data_mem(&raw)?;
     ctx.decrypt(cipher)?.await
-    */
 }
 
-pub async fn verify(a: Attachment) -> Result<()> {
-    Err("libgpgme functions are temporarily disabled due to an unsolved bug <https://git.meli.delivery/meli/meli/issues/176>.".into())
-    /*
+pub async fn verify(a: Attachment) -> Result<melib_pgp::SignatureMetadata> {
     let (data, sig) =
         melib_pgp::verify_signature(&a).chain_err_summary(|| "Could not verify signature.")?;
     let mut ctx = Context::new()?;
     let sig = ctx.new_data_mem(sig.body().trim())?;
     let data = ctx.new_data_mem(&data)?;
     ctx.verify(sig, data)?.await
-    */
+}
+
+/// Verifies the signature of `a` and lets gpg retrieve a missing public key of
+/// the signer with the `mechanisms` lookup methods, such as WKD and the
+/// keyservers configured for gpg, importing it to the keyring.
+pub async fn verify_fetching_key(
+    a: Attachment,
+    mechanisms: LocateKey,
+) -> Result<melib_pgp::SignatureMetadata> {
+    let (data, sig) =
+        melib_pgp::verify_signature(&a).chain_err_summary(|| "Could not verify signature.")?;
+    let mut ctx = Context::new()?;
+    ctx.set_flag(GpgmeFlag::OfflineMode, false)?;
+    ctx.set_flag(GpgmeFlag::AutoKeyRetrieve, true)?;
+    ctx.set_auto_key_locate(mechanisms)?;
+    let sig = ctx.new_data_mem(sig.body().trim())?;
+    let data = ctx.new_data_mem(&data)?;
+    ctx.verify(sig, data)?.await
 }
 
 pub fn sign_filter(
@@ -96,9 +110,12 @@ pub fn sign_filter(
     )
 }
 
+/// Encrypts the message body to `encrypt_keys`. If `protected_headers` isn't
+/// empty they are included in the encrypted part as protected headers.
 pub fn encrypt_filter(
     sign_keys: Option<Vec<Key>>,
     encrypt_keys: Vec<Key>,
+    protected_headers: Vec<(&'static str, String)>,
 ) -> Result<
     impl FnOnce(AttachmentBuilder) -> Pin<Box<dyn Future<Output = Result<AttachmentBuilder>> + Send>>
         + Send,
@@ -111,9 +128,11 @@ pub fn encrypt_filter(
                 let a: Attachment = a.into();
                 debug!("main attachment is {:?}", &a);
                 let mut ctx = Context::new()?;
-                let data = ctx.new_data_mem(
-                                a.into_raw().as_bytes()
-                )?;
+                let data = if protected_headers.is_empty() {
+                    ctx.new_data_mem(a.into_raw().as_bytes())?
+                } else {
+                    ctx.new_data_mem(&melib_pgp::with_protected_headers(a, &protected_headers))?
+                };
 
                 let sig_attachment = {
                     let mut a = Attachment::new(
@@ -145,3 +164,114 @@ pub fn encrypt_filter(
         },
     )
 }
+
+/// Looks up usable public keys matching `pattern` in the local keyring.
+async fn usable_keys(pattern: String) -> Result<Vec<Key>> {
+    let mut ctx = Context::new()?;
+    ctx.set_auto_key_locate(LocateKey::LOCAL)?;
+    Ok(ctx
+        .keylist(false, Some(pattern))?
+        .await?
+        .into_iter()
+        .filter(|k| !(k.revoked() || k.expired() || k.disabled() || k.invalid()))
+        .collect())
+}
+
+/// Looks up the most valid usable encryption key of each address in the
+/// local keyring. Keys the keyring never trusts for an address are skipped.
+pub async fn recipient_keys(addresses: Vec<String>) -> Result<Vec<(String, Option<Key>)>> {
+    let mut ret = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let key = usable_keys(address.clone())
+            .await?
+            .into_iter()
+            .filter(|k| k.can_encrypt() && k.uid_validity(&address) != Validity::Never)
+            .max_by_key(|k| k.uid_validity(&address));
+        ret.push((address, key));
+    }
+    Ok(ret)
+}
+
+/// Returns an `application/pgp-keys` attachment with the public key matching
+/// `pattern`, usually the sender's address or a key fingerprint.
+pub async fn public_key_attachment(pattern: String) -> Result<AttachmentBuilder> {
+    let key = match usable_keys(pattern.clone()).await?.into_iter().next() {
+        Some(key) => key,
+        None => {
+            return Err(Error::new(format!("No public key found for {}.", pattern)));
+        }
+    };
+    let fingerprint = key.fingerprint().to_string();
+    let mut ctx = Context::new()?;
+    let key_data = ctx.export_keys(vec![key])?.await?;
+    if key_data.is_empty() {
+        return Err(Error::new(format!(
+            "Could not export public key {}.",
+            fingerprint
+        )));
+    }
+    let mut a = AttachmentBuilder::default();
+    a.set_raw(key_data)
+        .set_body_to_raw()
+        .set_content_type(ContentType::Other {
+            tag: b"application/pgp-keys".to_vec(),
+            name: None,
+            parameters: vec![],
+        })
+        .set_content_disposition(ContentDisposition::from(
+            melib::email::attachment_types::ContentDispositionKind::Attachment,
+        ))
+        .set_filename(Some(format!(
+            "OpenPGP_0x{}.asc",
+            &fingerprint[fingerprint.len().saturating_sub(16)..]
+        )));
+    Ok(a)
+}
+
+/// Encrypts the contents of attachment `a` into an armored `.asc` file, either
+/// for the keys of `recipients` or, if `recipients` is `None`, with a
+/// passphrase.
+pub async fn encrypt_attachment(
+    a: AttachmentBuilder,
+    recipients: Option<Vec<String>>,
+) -> Result<AttachmentBuilder> {
+    let filename = a.filename().unwrap_or("attachment").to_string();
+    let contents = Attachment::from(a).decode(Default::default());
+    let mut ctx = Context::new()?;
+    let plain = ctx.new_data_mem(&contents)?;
+    let cipher = if let Some(recipients) = recipients {
+        let mut keys = vec![];
+        for r in recipients {
+            let found = usable_keys(r.clone())
+                .await?
+                .into_iter()
+                .filter(|k| k.can_encrypt())
+                .collect::<Vec<Key>>();
+            if found.is_empty() {
+                return Err(Error::new(format!(
+                    "No public key found for recipient {}.",
+                    r
+                )));
+            }
+            keys.extend(found);
+        }
+        if keys.is_empty() {
+            return Err(Error::new("Draft has no recipients to encrypt for."));
+        }
+        ctx.encrypt(None, keys, plain)?.await?
+    } else {
+        ctx.encrypt_symmetric(plain)?.await?
+    };
+    let mut a = AttachmentBuilder::default();
+    a.set_raw(cipher)
+        .set_body_to_raw()
+        .set_content_type(ContentType::OctetStream {
+            name: None,
+            parameters: vec![],
+        })
+        .set_content_disposition(ContentDisposition::from(
+            melib::email::attachment_types::ContentDispositionKind::Attachment,
+        ))
+        .set_filename(Some(format!("{}.asc", filename)));
+    Ok(a)
+}
@@ -0,0 +1,482 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Messages scheduled to be sent later with `send-at`.
+//!
+//! Scheduled messages are stored in the data directory of their account (See
+//! [`Account::scheduled_dir`]) so that they survive restarts, with their send
+//! time as the prefix of the file name. [`send_due`] is called periodically
+//! and sends the messages whose time has come.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use melib::datetime::UnixTimestamp;
+
+use super::*;
+
+const DATE_FMT: &str = "%Y-%m-%d %H:%M";
+
+/// Formats accepted by [`parse_send_time`], in local time.
+const SEND_TIME_FMTS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledEntry {
+    pub path: PathBuf,
+    pub send_at: UnixTimestamp,
+    pub to: String,
+    pub subject: String,
+}
+
+/// Returns the local time `s`, e.g. `2024-05-01T09:00`, as a timestamp.
+pub fn parse_send_time(s: &str) -> Result<UnixTimestamp> {
+    let s = s.trim();
+    for fmt in SEND_TIME_FMTS {
+        /* strptime(3) accepts input with leftover characters, so check that
+         * the whole string was used by formatting the result back. */
+        if let Ok(Some(timestamp)) = melib::datetime::timestamp_from_string(s, fmt) {
+            if melib::datetime::timestamp_to_string(timestamp, Some(fmt), false) == s {
+                return Ok(timestamp);
+            }
+        }
+    }
+    Err(Error::new(format!(
+        "Invalid time {}, expected YYYY-MM-DDTHH:MM.",
+        s
+    )))
+}
+
+/// Returns the send time of the scheduled message file `path`.
+pub fn send_time(path: &Path) -> Option<UnixTimestamp> {
+    path.file_stem()?
+        .to_str()?
+        .split('-')
+        .next()?
+        .parse::<UnixTimestamp>()
+        .ok()
+}
+
+/// Lists the messages scheduled in `dir`, earliest first. Files that can't be
+/// parsed are skipped.
+pub fn scheduled_messages(dir: &Path) -> Vec<ScheduledEntry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut ret = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let send_at = send_time(&path)?;
+            let bytes = fs::read(&path).ok()?;
+            let envelope = Envelope::from_bytes(&bytes, None).ok()?;
+            Some(ScheduledEntry {
+                send_at,
+                to: envelope.field_to_to_string(),
+                subject: envelope.subject().to_string(),
+                path,
+            })
+        })
+        .collect::<Vec<ScheduledEntry>>();
+    ret.sort_by_key(|e| e.send_at);
+    ret
+}
+
+/// Sends the scheduled message at `path` now and removes it.
+fn send_scheduled(account_hash: AccountHash, path: &Path, context: &mut Context) -> Result<()> {
+    let bytes = fs::read(path)?;
+    let envelope = Envelope::from_bytes(&bytes, None)?;
+    let mut draft = Draft::edit(&envelope, &bytes)?;
+    draft.set_header(
+        "Date",
+        melib::datetime::timestamp_to_string(
+            melib::datetime::now(),
+            Some(melib::datetime::RFC822_DATE),
+            true,
+        ),
+    );
+    super::compose::send_draft(
+        ToggleFlag::False,
+        context,
+        account_hash,
+        draft,
+        SpecialUsageMailbox::Sent,
+        Flag::SEEN,
+        true,
+    )?;
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Sends the scheduled messages of all accounts whose send time has passed,
+/// including those that were due while meli wasn't running. Messages that
+/// can't be sent are moved to the local drafts of their account.
+pub fn send_due(context: &mut Context) {
+    let now = melib::datetime::now();
+    let account_hashes = context.accounts.keys().cloned().collect::<Vec<_>>();
+    for account_hash in account_hashes {
+        let dir = match context.accounts[&account_hash].scheduled_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                log::error!("Could not open scheduled messages directory: {}.", err);
+                continue;
+            }
+        };
+        for entry in scheduled_messages(&dir)
+            .into_iter()
+            .take_while(|e| e.send_at <= now)
+        {
+            if let Err(err) = send_scheduled(account_hash, &entry.path, context) {
+                let account = &context.accounts[&account_hash];
+                let kept = fs::read(&entry.path)
+                    .map_err(Error::from)
+                    .and_then(|bytes| account.save_local_draft(&bytes))
+                    .and_then(|_| fs::remove_file(&entry.path).map_err(Error::from));
+                context.replies.push_back(UIEvent::Notification(
+                    Some(format!(
+                        "Could not send scheduled message {}",
+                        entry.subject
+                    )),
+                    if kept.is_ok() {
+                        format!("{}\nThe message was moved to the drafts.", err)
+                    } else {
+                        err.to_string()
+                    },
+                    Some(NotificationType::Error(err.kind)),
+                ));
+            } else {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Sent scheduled message {}.",
+                        entry.subject
+                    ))));
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ScheduledView {
+    account_hash: AccountHash,
+    entries: Vec<ScheduledEntry>,
+    message: String,
+    cursor: usize,
+    /// First visible row.
+    offset: usize,
+    theme_default: ThemeAttribute,
+    highlight_theme: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for ScheduledView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "scheduled")
+    }
+}
+
+impl ScheduledView {
+    pub fn new(account_hash: AccountHash, context: &Context) -> Self {
+        let mut ret = ScheduledView {
+            account_hash,
+            entries: vec![],
+            message: String::new(),
+            cursor: 0,
+            offset: 0,
+            theme_default: crate::conf::value(context, "theme_default"),
+            highlight_theme: crate::conf::value(context, "highlight"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.refresh(context);
+        ret
+    }
+
+    fn refresh(&mut self, context: &Context) {
+        let account = &context.accounts[&self.account_hash];
+        self.entries = match account.scheduled_dir() {
+            Ok(dir) => scheduled_messages(&dir),
+            Err(err) => {
+                log::error!("Could not open scheduled messages directory: {}.", err);
+                vec![]
+            }
+        };
+        self.cursor = std::cmp::min(self.cursor, self.entries.len().saturating_sub(1));
+        self.message = if self.entries.is_empty() {
+            format!("No scheduled messages in {}.", account.name())
+        } else {
+            format!(
+                "{} scheduled messages in {}. Edit a message with `open_entry`, send it now with \
+                 `send_mail` or cancel it with the `delete` command.",
+                self.entries.len(),
+                account.name()
+            )
+        };
+        self.set_dirty(true);
+    }
+
+    fn row_to_string(entry: &ScheduledEntry) -> String {
+        format!(
+            "{}  {}  {}",
+            melib::datetime::timestamp_to_string(entry.send_at, Some(DATE_FMT), false),
+            entry.to,
+            entry.subject
+        )
+    }
+
+    /// Opens the selected message in a new composer tab, taking it out of the
+    /// schedule.
+    fn edit(&mut self, context: &mut Context) -> Result<()> {
+        let entry = match self.entries.get(self.cursor) {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let bytes = fs::read(&entry.path)?;
+        let envelope = Envelope::from_bytes(&bytes, None)?;
+        let mut composer = Composer::with_account(self.account_hash, context);
+        composer.set_draft(Draft::edit(&envelope, &bytes)?);
+        composer.disable_signature();
+        fs::remove_file(&entry.path)?;
+        context
+            .replies
+            .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+        Ok(())
+    }
+
+    fn report(&self, result: Result<()>, title: &str, context: &mut Context) {
+        if let Err(err) = result {
+            context.replies.push_back(UIEvent::Notification(
+                Some(title.to_string()),
+                err.to_string(),
+                Some(NotificationType::Error(err.kind)),
+            ));
+        }
+    }
+}
+
+impl Component for ScheduledView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        clear_area(grid, area, self.theme_default);
+        let (upper_left, bottom_right) = area;
+        write_string_to_grid(
+            &self.message,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            ((get_x(upper_left) + 1, get_y(upper_left)), bottom_right),
+            None,
+        );
+        let rows = height!(area).saturating_sub(2);
+        if rows > 0 {
+            if self.cursor < self.offset {
+                self.offset = self.cursor;
+            } else if self.cursor >= self.offset + rows {
+                self.offset = self.cursor + 1 - rows;
+            }
+            for (y, (idx, entry)) in (get_y(upper_left) + 2..=get_y(bottom_right))
+                .zip(self.entries.iter().enumerate().skip(self.offset))
+            {
+                let theme = if idx == self.cursor {
+                    self.highlight_theme
+                } else {
+                    self.theme_default
+                };
+                let row_area = ((get_x(upper_left), y), set_y(bottom_right, y));
+                clear_area(grid, row_area, theme);
+                write_string_to_grid(
+                    &Self::row_to_string(entry),
+                    grid,
+                    theme.fg,
+                    theme.bg,
+                    theme.attrs,
+                    ((get_x(upper_left) + 1, y), set_y(bottom_right, y)),
+                    None,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.highlight_theme = crate::conf::value(context, "highlight");
+                self.set_dirty(true);
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_up"]) =>
+            {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["scroll_down"]) =>
+            {
+                self.cursor = std::cmp::min(self.cursor + 1, self.entries.len().saturating_sub(1));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::LISTING]["open_entry"]) =>
+            {
+                let result = self.edit(context);
+                self.report(result, "Could not open scheduled message", context);
+                self.refresh(context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::COMPOSING]["send_mail"]) =>
+            {
+                if let Some(entry) = self.entries.get(self.cursor) {
+                    let result = send_scheduled(self.account_hash, &entry.path.clone(), context);
+                    self.report(result, "Could not send scheduled message", context);
+                    self.refresh(context);
+                }
+                return true;
+            }
+            UIEvent::Action(Action::Listing(ListingAction::Delete)) => {
+                if let Some(entry) = self.entries.get(self.cursor) {
+                    let result = fs::remove_file(&entry.path).map_err(Error::from);
+                    self.report(result, "Could not cancel scheduled message", context);
+                    self.refresh(context);
+                }
+                return true;
+            }
+            UIEvent::VisibilityChange(true) => {
+                /* Messages may have been sent or scheduled meanwhile. */
+                self.refresh(context);
+            }
+            UIEvent::Resize => {
+                self.set_dirty(true);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+        map.insert(
+            Shortcuts::LISTING,
+            context.settings.shortcuts.listing.key_values(),
+        );
+        map.insert(
+            Shortcuts::COMPOSING,
+            context.settings.shortcuts.composing.key_values(),
+        );
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheduled_messages() {
+        let dir = std::env::temp_dir().join(format!("meli-scheduled-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let message = |subject: &str| {
+            format!(
+                "Date: Tue, 3 Jan 2023 10:00:00 +0000\r\nTo: a@example.com\r\nSubject: \
+                 {}\r\n\r\nbody\r\n",
+                subject
+            )
+        };
+        fs::write(
+            dir.join(format!("2000-{}.eml", Uuid::new_v4())),
+            message("later"),
+        )
+        .unwrap();
+        fs::write(
+            dir.join(format!("1000-{}.eml", Uuid::new_v4())),
+            message("first"),
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), message("unscheduled")).unwrap();
+        let entries = scheduled_messages(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].send_at, 1000);
+        assert_eq!(entries[0].subject, "first");
+        assert_eq!(entries[0].to, "a@example.com");
+        assert_eq!(entries[1].send_at, 2000);
+        assert_eq!(entries[1].subject, "later");
+    }
+
+    #[test]
+    fn test_scheduled_parse_send_time() {
+        let timestamp = parse_send_time("2024-05-01T09:00").unwrap();
+        assert_eq!(parse_send_time("2024-05-01 09:00").unwrap(), timestamp);
+        assert_eq!(
+            parse_send_time("2024-05-01T09:00:30").unwrap(),
+            timestamp + 30
+        );
+        assert_eq!(
+            melib::datetime::timestamp_to_string(timestamp, Some("%Y-%m-%d %H:%M"), false),
+            "2024-05-01 09:00"
+        );
+        assert!(parse_send_time("tomorrow").is_err());
+        assert!(parse_send_time("2024-05-01").is_err());
+        assert!(parse_send_time("2024-05-01T09:00 junk").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! S/MIME jobs of the composer and the mail view, see [`melib::smime`].
+
+use std::{future::Future, path::Path, pin::Pin};
+
+use melib::{
+    email::{
+        attachment_types::{ContentType, MultipartType},
+        pgp as melib_pgp, Attachment, AttachmentBuilder,
+    },
+    error::*,
+    smime::{self, SmimeSignature},
+    ShellExpandTrait,
+};
+
+type SignFuture = Pin<Box<dyn Future<Output = Result<AttachmentBuilder>> + Send>>;
+
+/// Verifies the S/MIME signature of the `multipart/signed` part `a`.
+pub async fn verify(
+    a: Attachment,
+    openssl_binary: Option<String>,
+    ca_file: Option<String>,
+) -> Result<SmimeSignature> {
+    let (data, sig) =
+        melib_pgp::verify_signature(&a).chain_err_summary(|| "Could not verify signature.")?;
+    let ca_file = ca_file.map(|f| Path::new(&f).expand());
+    smime::verify(
+        openssl_binary.as_deref(),
+        ca_file.as_deref(),
+        &data,
+        &sig.decode(Default::default()),
+    )
+}
+
+/// Signs the message body with `certificate` and its `private_key`,
+/// wrapping it in a `multipart/signed` part along with the signature.
+pub fn sign_filter(
+    openssl_binary: Option<String>,
+    certificate: Option<String>,
+    private_key: Option<String>,
+) -> Result<impl FnOnce(AttachmentBuilder) -> SignFuture + Send> {
+    let (certificate, private_key) = match (certificate, private_key) {
+        (Some(certificate), Some(private_key)) => (
+            Path::new(&certificate).expand(),
+            Path::new(&private_key).expand(),
+        ),
+        _ => {
+            return Err(Error::new(
+                "S/MIME signing requires the `certificate` and `private_key` settings of the \
+                 `smime` section.",
+            )
+            .set_kind(ErrorKind::Configuration))
+        }
+    };
+    Ok(move |a: AttachmentBuilder| -> SignFuture {
+        Box::pin(async move {
+            let a: Attachment = a.into();
+            let signature = smime::sign(
+                openssl_binary.as_deref(),
+                &certificate,
+                &private_key,
+                &melib_pgp::convert_attachment_to_rfc_spec(a.into_raw().as_bytes()),
+            )?;
+            let sig_attachment =
+                Attachment::new(ContentType::CMSSignature, Default::default(), signature);
+            let a: AttachmentBuilder = a.into();
+            let parts = vec![a, sig_attachment.into()];
+            let boundary = ContentType::make_boundary(&parts);
+            Ok(Attachment::new(
+                ContentType::Multipart {
+                    boundary: boundary.into_bytes(),
+                    kind: MultipartType::Signed,
+                    parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
+                    parameters: smime::MULTIPART_SIGNED_PARAMETERS
+                        .iter()
+                        .map(|(k, v)| (k.to_vec(), v.to_vec()))
+                        .collect(),
+                },
+                Default::default(),
+                vec![],
+            )
+            .into())
+        })
+    })
+}
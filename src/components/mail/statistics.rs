@@ -0,0 +1,460 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Mail activity statistics of an account.
+
+use std::collections::HashMap;
+
+use melib::{datetime::UnixTimestamp, email::Address};
+
+use super::*;
+use crate::jobs::JoinHandle;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// Heatmap cells, from no messages to the busiest hour.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+const TOP_ENTRIES: usize = 10;
+
+/// The envelope metadata statistics are computed from.
+#[derive(Debug, Clone)]
+pub struct MessageMeta {
+    pub date: UnixTimestamp,
+    pub from: Option<Address>,
+    /// Whether the message was sent from one of the user's addresses.
+    pub sent: bool,
+    pub list_id: Option<String>,
+    pub message_id: Vec<u8>,
+    pub in_reply_to: Option<Vec<u8>>,
+}
+
+impl MessageMeta {
+    fn new(envelope: &Envelope, own: &[Address]) -> Self {
+        let from = envelope.from().first().cloned();
+        MessageMeta {
+            date: envelope.date(),
+            sent: from
+                .as_ref()
+                .map(|a| reply::is_own_address(own, a))
+                .unwrap_or(false),
+            from,
+            list_id: envelope
+                .list_id()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty()),
+            message_id: envelope.message_id().raw().to_vec(),
+            in_reply_to: envelope.in_reply_to().map(|m| m.raw().to_vec()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccountStatistics {
+    pub received: usize,
+    pub sent: usize,
+    /// Received messages per day of the week (Monday first) and hour.
+    pub received_heatmap: [[usize; 24]; 7],
+    /// Sent messages per day of the week (Monday first) and hour.
+    pub sent_heatmap: [[usize; 24]; 7],
+    /// Senders of the most received messages.
+    pub top_senders: Vec<(String, usize)>,
+    /// Mailing lists with the most received messages.
+    pub top_lists: Vec<(String, usize)>,
+    /// Average time between receiving a message and sending a reply to it.
+    pub average_response_time: Option<UnixTimestamp>,
+    /// Number of replies `average_response_time` is computed from.
+    pub responses: usize,
+}
+
+impl AccountStatistics {
+    pub fn compute(messages: &[MessageMeta]) -> Self {
+        fn top(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+            let mut ret: Vec<(String, usize)> = counts.into_iter().collect();
+            ret.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            ret.truncate(TOP_ENTRIES);
+            ret
+        }
+
+        let mut ret = AccountStatistics::default();
+        let mut senders: HashMap<String, usize> = HashMap::default();
+        let mut lists: HashMap<String, usize> = HashMap::default();
+        let mut received_dates: HashMap<&[u8], UnixTimestamp> = HashMap::default();
+        for m in messages {
+            let (weekday, hour) = melib::datetime::timestamp_to_weekday_hour(m.date);
+            if m.sent {
+                ret.sent += 1;
+                ret.sent_heatmap[weekday][hour] += 1;
+                continue;
+            }
+            ret.received += 1;
+            ret.received_heatmap[weekday][hour] += 1;
+            if let Some(ref from) = m.from {
+                *senders.entry(from.get_email()).or_default() += 1;
+            }
+            if let Some(ref list_id) = m.list_id {
+                *lists.entry(list_id.clone()).or_default() += 1;
+            }
+            received_dates.insert(&m.message_id, m.date);
+        }
+
+        let mut total_response_time = 0;
+        for m in messages.iter().filter(|m| m.sent) {
+            if let Some(&date) = m
+                .in_reply_to
+                .as_ref()
+                .and_then(|id| received_dates.get(id.as_slice()))
+            {
+                if m.date >= date {
+                    total_response_time += m.date - date;
+                    ret.responses += 1;
+                }
+            }
+        }
+        if ret.responses > 0 {
+            ret.average_response_time = Some(total_response_time / ret.responses as u64);
+        }
+        ret.top_senders = top(senders);
+        ret.top_lists = top(lists);
+        ret
+    }
+
+    fn heatmap_lines(heatmap: &[[usize; 24]; 7]) -> Vec<String> {
+        let max = heatmap.iter().flatten().copied().max().unwrap_or(0);
+        let mut ret = Vec::with_capacity(9);
+        let mut header = String::from("     ");
+        for hour in (0..24).step_by(3) {
+            header.push_str(&format!("{:<6}", hour));
+        }
+        ret.push(header);
+        for (weekday, hours) in WEEKDAYS.iter().zip(heatmap.iter()) {
+            let mut line = format!("{} │", weekday);
+            for &count in hours {
+                let shade = if count == 0 {
+                    SHADES[0]
+                } else {
+                    /* Scale non-zero counts to the remaining shades. */
+                    SHADES[1 + (count * (SHADES.len() - 1) - 1) / max]
+                };
+                line.push(shade);
+                line.push(shade);
+            }
+            ret.push(line);
+        }
+        ret.push(format!(
+            "     {} none  {} to {} busiest hour ({} messages)",
+            SHADES[0],
+            SHADES[1],
+            SHADES[SHADES.len() - 1],
+            max
+        ));
+        ret
+    }
+
+    /// Renders the statistics as lines of text.
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut ret = vec![
+            format!("Received messages: {}", self.received),
+            format!("Sent messages: {}", self.sent),
+            match self.average_response_time {
+                Some(t) => format!(
+                    "Average response time: {} (from {} replies)",
+                    format_duration(t),
+                    self.responses
+                ),
+                None => "Average response time: -".to_string(),
+            },
+            String::new(),
+            "Received per day and hour:".to_string(),
+        ];
+        ret.extend(Self::heatmap_lines(&self.received_heatmap));
+        ret.push(String::new());
+        ret.push("Sent per day and hour:".to_string());
+        ret.extend(Self::heatmap_lines(&self.sent_heatmap));
+        for (title, list) in [
+            ("Top senders:", &self.top_senders),
+            ("Top mailing lists:", &self.top_lists),
+        ] {
+            ret.push(String::new());
+            ret.push(title.to_string());
+            if list.is_empty() {
+                ret.push("  -".to_string());
+            }
+            for (name, count) in list {
+                ret.push(format!("  {:>6}  {}", count, name));
+            }
+        }
+        ret
+    }
+}
+
+fn format_duration(seconds: UnixTimestamp) -> String {
+    let (days, hours, minutes) = (
+        seconds / 86400,
+        (seconds % 86400) / 3600,
+        (seconds % 3600) / 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[derive(Debug)]
+pub struct StatisticsView {
+    account_hash: AccountHash,
+    lines: Vec<String>,
+    handle: Option<JoinHandle<AccountStatistics>>,
+    /// First visible line.
+    offset: usize,
+    theme_default: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for StatisticsView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "statistics")
+    }
+}
+
+impl StatisticsView {
+    pub fn new(account_hash: AccountHash, context: &mut Context) -> Self {
+        let account = &context.accounts[&account_hash];
+        let own = reply::own_addresses(&account.settings.account);
+        let messages: Vec<MessageMeta> = account
+            .collection
+            .envelopes
+            .read()
+            .unwrap()
+            .values()
+            .map(|e| MessageMeta::new(e, &own))
+            .collect();
+        let handle = account
+            .job_executor
+            .spawn_specialized(async move { AccountStatistics::compute(&messages) });
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        StatisticsView {
+            account_hash,
+            lines: vec![format!(
+                "Computing statistics of {}…",
+                context.accounts[&account_hash].name()
+            )],
+            handle: Some(handle),
+            offset: 0,
+            theme_default: crate::conf::value(context, "theme_default"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+}
+
+impl Component for StatisticsView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.dirty {
+            return;
+        }
+        clear_area(grid, area, self.theme_default);
+        let (upper_left, bottom_right) = area;
+        self.offset = std::cmp::min(self.offset, self.lines.len().saturating_sub(height!(area)));
+        for (y, line) in
+            (get_y(upper_left)..=get_y(bottom_right)).zip(self.lines.iter().skip(self.offset))
+        {
+            write_string_to_grid(
+                line,
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                ((get_x(upper_left) + 1, y), set_y(bottom_right, y)),
+                None,
+            );
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let shortcuts = self.get_shortcuts(context);
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .handle
+                    .as_ref()
+                    .map(|h| h.job_id == *job_id)
+                    .unwrap_or(false) =>
+            {
+                let mut handle = self.handle.take().unwrap();
+                if let Ok(Some(statistics)) = handle.chan.try_recv() {
+                    let mut lines = vec![
+                        format!(
+                            "AccountStatistics of {}",
+                            context.accounts[&self.account_hash].name()
+                        ),
+                        String::new(),
+                    ];
+                    lines.extend(statistics.to_lines());
+                    self.lines = lines;
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_up"]) =>
+            {
+                self.offset = self.offset.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["scroll_down"]) =>
+            {
+                self.offset += 1;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::GENERAL]["home_page"]) =>
+            {
+                self.offset = 0;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Resize => {
+                self.set_dirty(true);
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        if let Some(handle) = self.handle.take() {
+            handle.cancel();
+        }
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+
+        map.insert(
+            Shortcuts::GENERAL,
+            context.settings.shortcuts.general.key_values(),
+        );
+
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn can_quit_cleanly(&mut self, _context: &Context) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    fn meta(
+        date: UnixTimestamp,
+        from: &str,
+        sent: bool,
+        id: &str,
+        reply_to: Option<&str>,
+    ) -> MessageMeta {
+        MessageMeta {
+            date,
+            from: Address::try_from(from).ok(),
+            sent,
+            list_id: None,
+            message_id: id.as_bytes().to_vec(),
+            in_reply_to: reply_to.map(|r| r.as_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_statistics_compute() {
+        let mut messages = vec![
+            meta(1_000, "alice@example.com", false, "1@a", None),
+            meta(4_600, "me@example.com", true, "2@a", Some("1@a")),
+            meta(10_000, "alice@example.com", false, "3@a", None),
+            meta(20_000, "bob@example.com", false, "4@a", None),
+            meta(30_800, "me@example.com", true, "5@a", Some("4@a")),
+            /* A reply to a message we don't have. */
+            meta(40_000, "me@example.com", true, "6@a", Some("unknown@a")),
+        ];
+        messages[3].list_id = Some("<list.example.com>".to_string());
+        let stats = AccountStatistics::compute(&messages);
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.sent, 3);
+        assert_eq!(
+            stats.received_heatmap.iter().flatten().sum::<usize>(),
+            stats.received
+        );
+        assert_eq!(
+            stats.sent_heatmap.iter().flatten().sum::<usize>(),
+            stats.sent
+        );
+        assert_eq!(
+            stats.top_senders,
+            vec![
+                ("alice@example.com".to_string(), 2),
+                ("bob@example.com".to_string(), 1)
+            ]
+        );
+        assert_eq!(stats.top_lists, vec![("<list.example.com>".to_string(), 1)]);
+        assert_eq!(stats.responses, 2);
+        assert_eq!(stats.average_response_time, Some((3_600 + 10_800) / 2));
+        assert_eq!(format_duration(7_200), "2h 0m");
+
+        let lines = AccountStatistics::heatmap_lines(&stats.received_heatmap);
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1..8]
+            .iter()
+            .all(|l| l.chars().count() == "Mon │".chars().count() + 48));
+    }
+}
@@ -199,6 +199,39 @@ impl Component for AccountStatus {
         );
         width = self.content.size().0;
         line += 1;
+        let (_x, _y) = write_string_to_grid(
+            "Background sync: ",
+            &mut self.content,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            Attr::BOLD,
+            ((1, line), (width - 1, line)),
+            None,
+        );
+        width = self.content.size().0;
+        write_string_to_grid(
+            &format!(
+                "{} (power: {}, power-saving {}; change with `power-saving on|off|auto`)",
+                match crate::power::pause_reason(
+                    context.power_saving,
+                    &context.power_state,
+                    &context.settings.power,
+                ) {
+                    Some(reason) => format!("paused, {}", reason),
+                    None => "running".to_string(),
+                },
+                context.power_state,
+                context.power_saving,
+            ),
+            &mut self.content,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            ((_x, _y), (width - 1, _y)),
+            None,
+        );
+        width = self.content.size().0;
+        line += 1;
 
         write_string_to_grid(
             "Special Mailboxes:",
@@ -243,8 +276,12 @@ impl Component for AccountStatus {
             width = self.content.size().0;
             let f: &Mailbox = &a[&mailbox_node.hash].ref_mailbox;
             if f.is_subscribed() {
+                let path = match f.fetch_strategy() {
+                    Some(strategy) => format!("{} ({})", f.path(), strategy),
+                    None => f.path().to_string(),
+                };
                 write_string_to_grid(
-                    f.path(),
+                    &path,
                     &mut self.content,
                     self.theme_default.fg,
                     self.theme_default.bg,
@@ -435,6 +472,7 @@ impl Component for AccountStatus {
                 return true;
             }
             UIEvent::MailboxUpdate(_)
+            | UIEvent::ThreadUpdate(_, _)
             | UIEvent::StatusEvent(StatusEvent::NewJob(_))
             | UIEvent::StatusEvent(StatusEvent::JobFinished(_))
             | UIEvent::StatusEvent(StatusEvent::JobCanceled(_)) => {
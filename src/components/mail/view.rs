@@ -33,8 +33,9 @@ use smallvec::SmallVec;
 
 use super::*;
 use crate::{
-    conf::accounts::JobRequest,
+    conf::{accounts::JobRequest, DateTimezone},
     jobs::{JobId, JoinHandle},
+    pager::MimeFilter,
 };
 
 mod html;
@@ -47,6 +48,9 @@ use linkify::LinkFinder;
 use xdg_utils::query_default_app;
 
 pub use self::envelope::*;
+mod source;
+pub use self::source::*;
+pub mod sender_warning;
 
 #[derive(Debug, Default)]
 enum ForceCharset {
@@ -123,7 +127,8 @@ pub enum AttachmentDisplay {
     SignedPending {
         inner: Box<Attachment>,
         display: Vec<AttachmentDisplay>,
-        handle: JoinHandle<Result<()>>,
+        /// Resolves to the description of the valid signature.
+        handle: JoinHandle<Result<String>>,
         job_id: JobId,
     },
     SignedFailed {
@@ -153,6 +158,9 @@ pub enum AttachmentDisplay {
         plaintext: Box<Attachment>,
         plaintext_display: Vec<AttachmentDisplay>,
         description: String,
+        /// Headers protected by the encryption, see
+        /// [`melib::pgp::protected_headers`].
+        protected_headers: Vec<(String, String)>,
     },
 }
 
@@ -167,6 +175,11 @@ pub struct MailView {
     initialised: bool,
     mode: ViewMode,
     expand_headers: bool,
+    /// The timezone Date: was switched to with `toggle_timezone`, if any.
+    timezone: Option<DateTimezone>,
+    /// Whether the raw source view folds long headers and shows binary
+    /// parts as hex dumps.
+    fold_source: bool,
     attachment_tree: String,
     attachment_paths: Vec<Vec<usize>>,
     headers_no: usize,
@@ -176,6 +189,9 @@ pub struct MailView {
     active_jobs: HashSet<JobId>,
     state: MailViewState,
     force_charset: ForceCharset,
+    /// Directory chooser opened by `save_attachments`, and the indices of
+    /// the attachments to save.
+    save_dir_chooser: Option<(Box<FileBrowser>, Vec<usize>)>,
 
     cmd_buf: String,
     id: ComponentId,
@@ -184,8 +200,11 @@ pub struct MailView {
 #[derive(Debug, Copy, Clone)]
 pub enum PendingReplyAction {
     Reply,
+    /// Reply without the attribution line and the quoted message.
+    ReplyWithoutQuote,
     ReplyToAuthor,
     ReplyToAll,
+    ReplyToList,
     ForwardAttachment,
     ForwardInline,
 }
@@ -313,6 +332,7 @@ impl Clone for MailView {
             state: MailViewState::default(),
             active_jobs: self.active_jobs.clone(),
             force_charset: ForceCharset::None,
+            save_dir_chooser: None,
             ..*self
         }
     }
@@ -325,6 +345,33 @@ impl fmt::Display for MailView {
 }
 
 impl MailView {
+    /// The timezone Date: is shown in according to the `timezone` and
+    /// `show_date_in_my_timezone` pager settings.
+    fn configured_timezone(&self, context: &Context) -> DateTimezone {
+        if let Some(timezone) = mailbox_settings!(
+            context[self.coordinates.0][&self.coordinates.1]
+                .pager
+                .timezone
+        ) {
+            *timezone
+        } else if mailbox_settings!(
+            context[self.coordinates.0][&self.coordinates.1]
+                .pager
+                .show_date_in_my_timezone
+        )
+        .is_true()
+        {
+            DateTimezone::Local
+        } else {
+            DateTimezone::Sender
+        }
+    }
+
+    fn timezone(&self, context: &Context) -> DateTimezone {
+        self.timezone
+            .unwrap_or_else(|| self.configured_timezone(context))
+    }
+
     pub fn new(
         coordinates: (AccountHash, MailboxHash, EnvelopeHash),
         pager: Option<Pager>,
@@ -339,6 +386,8 @@ impl MailView {
             initialised: false,
             mode: ViewMode::Normal,
             expand_headers: false,
+            timezone: None,
+            fold_source: false,
             attachment_tree: String::new(),
             attachment_paths: vec![],
 
@@ -350,6 +399,7 @@ impl MailView {
             active_jobs: Default::default(),
             state: MailViewState::default(),
             force_charset: ForceCharset::None,
+            save_dir_chooser: None,
             cmd_buf: String::with_capacity(4),
             id: ComponentId::new_v4(),
         };
@@ -402,16 +452,17 @@ impl MailView {
                     }
                     Err(err) => {
                         context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(format!("Could not get message: {}", err)),
+                            StatusEvent::DisplayMessage(tr!("Could not get message: {}", err)),
                         ));
                     }
                 }
             }
             let account = &mut context.accounts[&self.coordinates.0];
             if !account.collection.get_env(self.coordinates.2).is_seen() {
+                let mailbox_hash = account.storage_mailbox(self.coordinates.1, self.coordinates.2);
                 let job = account.backend.write().unwrap().set_flags(
                     self.coordinates.2.into(),
-                    self.coordinates.1,
+                    mailbox_hash,
                     smallvec::smallvec![(Ok(Flag::SEEN), true)],
                 );
                 match job {
@@ -427,7 +478,7 @@ impl MailView {
                     }
                     Err(e) => {
                         context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(format!(
+                            StatusEvent::DisplayMessage(tr!(
                                 "Could not set message as seen: {}",
                                 e
                             )),
@@ -463,7 +514,11 @@ impl MailView {
                 ..
             } => (
                 bytes,
-                self.attachment_displays_to_text(display, context, false),
+                if let PendingReplyAction::ReplyWithoutQuote = action {
+                    None
+                } else {
+                    Some(self.attachment_displays_to_text(display, context, false))
+                },
                 env,
             ),
             MailViewState::Error { .. } => {
@@ -471,11 +526,9 @@ impl MailView {
             }
         };
         let composer = match action {
-            PendingReplyAction::Reply => Box::new(Composer::reply_to_select(
-                self.coordinates,
-                reply_body,
-                context,
-            )),
+            PendingReplyAction::Reply | PendingReplyAction::ReplyWithoutQuote => Box::new(
+                Composer::reply_to_select(self.coordinates, reply_body, context),
+            ),
             PendingReplyAction::ReplyToAuthor => Box::new(Composer::reply_to_author(
                 self.coordinates,
                 reply_body,
@@ -486,6 +539,25 @@ impl MailView {
                 reply_body,
                 context,
             )),
+            PendingReplyAction::ReplyToList => {
+                if reply::list_post_address(env).is_none()
+                    && reply::header_addresses(env, "Mail-Followup-To").is_empty()
+                {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "No mailing list found: message has no List-Post or \
+                             Mail-Followup-To header."
+                                .to_string(),
+                        )));
+                    return;
+                }
+                Box::new(Composer::reply_to_list(
+                    self.coordinates,
+                    reply_body,
+                    context,
+                ))
+            }
             PendingReplyAction::ForwardAttachment => Box::new(Composer::forward(
                 self.coordinates,
                 bytes,
@@ -582,6 +654,17 @@ impl MailView {
                 } => {
                     if show_comments {
                         let _ = writeln!(acc, "Failed to verify signature: {}.\n", error);
+                        #[cfg(feature = "gpgme")]
+                        if error
+                            .source
+                            .as_ref()
+                            .map(|err| err.is::<melib::gpgme::MissingKey>())
+                            .unwrap_or(false)
+                        {
+                            acc.push_str(
+                                "Use the `fetch-key` command to look up the key of the signer.\n\n",
+                            );
+                        }
                     }
                     acc.push_str(&self.attachment_displays_to_text(
                         display,
@@ -617,6 +700,7 @@ impl MailView {
                     plaintext: _,
                     plaintext_display,
                     description,
+                    protected_headers,
                 } => {
                     if show_comments {
                         if description.is_empty() {
@@ -625,6 +709,13 @@ impl MailView {
                             acc.push_str(description);
                             acc.push_str("\n\n");
                         }
+                        if !protected_headers.is_empty() {
+                            acc.push_str("Protected headers:\n");
+                            for (name, value) in protected_headers {
+                                let _ = writeln!(acc, "{}: {}", name, value);
+                            }
+                            acc.push('\n');
+                        }
                     }
                     acc.push_str(&self.attachment_displays_to_text(
                         plaintext_display,
@@ -637,6 +728,106 @@ impl MailView {
         acc
     }
 
+    /// Returns the protected header `name` of the decrypted body, if any.
+    fn protected_header(&self, name: &str) -> Option<&str> {
+        fn find<'a>(displays: &'a [AttachmentDisplay], name: &str) -> Option<&'a str> {
+            use AttachmentDisplay::*;
+            displays.iter().find_map(|d| match d {
+                EncryptedSuccess {
+                    protected_headers, ..
+                } => protected_headers
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.as_str()),
+                Alternative { display, .. }
+                | SignedPending { display, .. }
+                | SignedFailed { display, .. }
+                | SignedUnverified { display, .. }
+                | SignedVerified { display, .. } => find(display, name),
+                _ => None,
+            })
+        }
+        if let MailViewState::Loaded { ref display, .. } = self.state {
+            find(display, name)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the results of signature verification and decryption of the
+    /// body, for the headers, whether any of them failed and whether the
+    /// signature is an S/MIME one.
+    fn pgp_status(&self) -> Option<(String, bool, bool)> {
+        fn rec(
+            displays: &[AttachmentDisplay],
+            acc: &mut Vec<String>,
+            failed: &mut bool,
+            smime: &mut bool,
+        ) {
+            use AttachmentDisplay::*;
+            for d in displays {
+                match d {
+                    Alternative { display, .. } => rec(display, acc, failed, smime),
+                    InlineText { .. } | InlineOther { .. } | Attachment { .. } => {}
+                    SignedPending { inner, display, .. } => {
+                        *smime |= melib::smime::is_smime_signed(inner);
+                        acc.push(tr!("Waiting for signature verification."));
+                        rec(display, acc, failed, smime);
+                    }
+                    SignedUnverified { inner, display } => {
+                        *smime |= melib::smime::is_smime_signed(inner);
+                        acc.push(tr!("Unverified signature."));
+                        rec(display, acc, failed, smime);
+                    }
+                    SignedFailed {
+                        inner,
+                        display,
+                        error,
+                    } => {
+                        *smime |= melib::smime::is_smime_signed(inner);
+                        *failed = true;
+                        acc.push(tr!("Failed to verify signature: {}.", error));
+                        rec(display, acc, failed, smime);
+                    }
+                    SignedVerified {
+                        inner,
+                        display,
+                        description,
+                    } => {
+                        *smime |= melib::smime::is_smime_signed(inner);
+                        acc.push(description.clone());
+                        rec(display, acc, failed, smime);
+                    }
+                    EncryptedPending { .. } => {
+                        acc.push(tr!("Waiting for decryption result."));
+                    }
+                    EncryptedFailed { error, .. } => {
+                        *failed = true;
+                        acc.push(tr!("Decryption failed: {}.", error));
+                    }
+                    EncryptedSuccess {
+                        plaintext_display,
+                        description,
+                        ..
+                    } => {
+                        acc.push(description.clone());
+                        rec(plaintext_display, acc, failed, smime);
+                    }
+                }
+            }
+        }
+        if let MailViewState::Loaded { ref display, .. } = self.state {
+            let mut acc = vec![];
+            let mut failed = false;
+            let mut smime = false;
+            rec(display, &mut acc, &mut failed, &mut smime);
+            if !acc.is_empty() {
+                return Some((acc.join(" "), failed, smime));
+            }
+        }
+        None
+    }
+
     fn attachment_displays_to_tree(
         &self,
         displays: &[AttachmentDisplay],
@@ -697,6 +888,7 @@ impl MailView {
                     plaintext: inner,
                     plaintext_display: display,
                     description: _,
+                    protected_headers: _,
                 } => (inner, display.as_slice()),
             };
             s.extend(format!("\n[{}]", idx).chars());
@@ -786,63 +978,46 @@ impl MailView {
                     inner: Box::new(a.clone()),
                 });
             } else if a.content_type().is_text_html() {
-                let bytes = a.decode(force_charset.into());
-                let filter_invocation =
+                let mut bytes = a.decode(force_charset.into());
+                let report = sanitize_html(
+                    &mut bytes,
+                    *mailbox_settings!(context[coordinates.0][&coordinates.1].pager.sanitize_html),
+                );
+                let filter = MimeFilter::for_html(
                     mailbox_settings!(context[coordinates.0][&coordinates.1].pager.html_filter)
-                        .as_ref()
-                        .map(|s| s.as_str())
-                        .unwrap_or("w3m -I utf-8 -T text/html");
-                let command_obj = Command::new("sh")
-                    .args(["-c", filter_invocation])
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn();
-                match command_obj {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::Notification(
-                            Some(format!(
-                                "Failed to start html filter process: {}",
-                                filter_invocation,
-                            )),
-                            err.to_string(),
-                            Some(NotificationType::Error(melib::ErrorKind::External)),
-                        ));
-                        let comment = Some(format!(
-                            "Failed to start html filter process: `{}`. Press `v` to open in web \
-                             browser. \n\n",
-                            filter_invocation
-                        ));
-                        let text = String::from_utf8_lossy(&bytes).to_string();
-                        acc.push(AttachmentDisplay::InlineText {
-                            inner: Box::new(a.clone()),
-                            comment,
-                            text,
-                        });
-                    }
-                    Ok(mut html_filter) => {
-                        html_filter
-                            .stdin
-                            .as_mut()
-                            .unwrap()
-                            .write_all(&bytes)
-                            .expect("Failed to write to stdin");
-                        let comment = Some(format!(
-                            "Text piped through `{}`. Press `v` to open in web browser. \n\n",
-                            filter_invocation
-                        ));
-                        let text = String::from_utf8_lossy(
-                            &html_filter.wait_with_output().unwrap().stdout,
-                        )
-                        .to_string();
-                        acc.push(AttachmentDisplay::InlineText {
+                        .as_deref(),
+                    mailbox_settings!(context[coordinates.0][&coordinates.1].pager.filters),
+                );
+                let width =
+                    *mailbox_settings!(context[coordinates.0][&coordinates.1].pager.minimum_width);
+                let (mut comment, text) = html_to_text(&bytes, filter, width, context);
+                if let Some(report) = report {
+                    let _ = write!(comment, "{}\n\n", report);
+                }
+                acc.push(AttachmentDisplay::InlineText {
+                    inner: Box::new(a.clone()),
+                    comment: Some(comment),
+                    text,
+                });
+            } else if a.is_text() {
+                let bytes = a.decode(force_charset.into());
+                #[cfg(feature = "gpgme")]
+                if let Some(range) = melib::pgp::inline_pgp_message(&bytes) {
+                    if *mailbox_settings!(context[coordinates.0][&coordinates.1].pgp.auto_decrypt) {
+                        let decrypt_fut =
+                            crate::components::mail::pgp::decrypt(bytes[range].to_vec());
+                        let handle = context.job_executor.spawn_specialized(decrypt_fut);
+                        active_jobs.insert(handle.job_id);
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+                        acc.push(AttachmentDisplay::EncryptedPending {
                             inner: Box::new(a.clone()),
-                            comment,
-                            text,
+                            handle,
                         });
+                        return;
                     }
                 }
-            } else if a.is_text() {
-                let bytes = a.decode(force_charset.into());
                 acc.push(AttachmentDisplay::InlineText {
                     inner: Box::new(a.clone()),
                     comment: None,
@@ -900,6 +1075,55 @@ impl MailView {
                             display,
                         });
                     }
+                    MultipartType::Signed if melib::smime::is_smime_signed(a) => {
+                        let mut display = vec![];
+                        rec(
+                            &parts[0],
+                            context,
+                            coordinates,
+                            &mut display,
+                            active_jobs,
+                            force_charset,
+                        );
+                        if *mailbox_settings!(
+                            context[coordinates.0][&coordinates.1]
+                                .smime
+                                .auto_verify_signatures
+                        ) {
+                            let verify_fut = crate::components::mail::smime::verify(
+                                a.clone(),
+                                mailbox_settings!(
+                                    context[coordinates.0][&coordinates.1].smime.openssl_binary
+                                )
+                                .clone(),
+                                mailbox_settings!(
+                                    context[coordinates.0][&coordinates.1].smime.ca_file
+                                )
+                                .clone(),
+                            );
+                            /* openssl is run as a child process. */
+                            let handle = context.job_executor.spawn_blocking(async move {
+                                verify_fut.await.map(|signature| signature.to_string())
+                            });
+                            active_jobs.insert(handle.job_id);
+                            context
+                                .replies
+                                .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(
+                                    handle.job_id,
+                                )));
+                            acc.push(AttachmentDisplay::SignedPending {
+                                inner: Box::new(a.clone()),
+                                job_id: handle.job_id,
+                                display,
+                                handle,
+                            });
+                        } else {
+                            acc.push(AttachmentDisplay::SignedUnverified {
+                                inner: Box::new(a.clone()),
+                                display,
+                            });
+                        }
+                    }
                     MultipartType::Signed => {
                         #[cfg(not(feature = "gpgme"))]
                         {
@@ -927,7 +1151,14 @@ impl MailView {
                                     .auto_verify_signatures
                             ) {
                                 let verify_fut = crate::components::mail::pgp::verify(a.clone());
-                                let handle = context.job_executor.spawn_specialized(verify_fut);
+                                let handle = context.job_executor.spawn_specialized(async move {
+                                    verify_fut.await.map(|metadata| {
+                                        tr!(
+                                            "Good signature by key {}.",
+                                            key_ids(&metadata.signatures)
+                                        )
+                                    })
+                                });
                                 active_jobs.insert(handle.job_id);
                                 context.replies.push_back(UIEvent::StatusEvent(
                                     StatusEvent::NewJob(handle.job_id),
@@ -1097,6 +1328,7 @@ impl MailView {
                     plaintext: inner,
                     plaintext_display: _,
                     description: _,
+                    protected_headers: _,
                 } => inner,
             };
             fn find_attachment<'a>(
@@ -1132,13 +1364,182 @@ impl MailView {
         }
         context
             .replies
-            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
                 "Attachment `{}` not found.",
                 lidx
             ))));
         None
     }
 
+    /// Indices of the message's attachments: parts that aren't multipart
+    /// containers and are either marked as attachments or have a filename.
+    fn attachment_indices(&self, context: &mut Context) -> Vec<usize> {
+        (0..self.attachment_paths.len())
+            .filter(|&a_i| {
+                self.open_attachment(a_i, context)
+                    .map(|a| {
+                        !matches!(a.content_type, ContentType::Multipart { .. })
+                            && (a.content_disposition.kind.is_attachment()
+                                || a.filename().is_some())
+                    })
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Saves the attachments with indices `attachments` in directory `dir`,
+    /// with sanitized filenames and without overwriting existing files, and
+    /// reports the outcome in the status bar.
+    fn save_attachments(
+        &mut self,
+        attachments: &[usize],
+        dir: &std::path::Path,
+        context: &mut Context,
+    ) {
+        if !dir.is_dir() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    "{} is not a directory.",
+                    dir.display()
+                ))));
+            return;
+        }
+        let mut saved = 0;
+        let mut errors = vec![];
+        for &a_i in attachments {
+            let (filename, bytes) = match self.open_attachment(a_i, context) {
+                Some(u) => (
+                    u.filename()
+                        .unwrap_or_else(|| format!("attachment-{}", a_i)),
+                    u.decode(Default::default()),
+                ),
+                None => continue,
+            };
+            let path = unique_path(dir, &sanitize_filename(&filename));
+            match save_attachment(&path, &bytes) {
+                Ok(()) => saved += 1,
+                Err(err) => {
+                    log::error!("Failed to create file at {}: {err}", path.display());
+                    errors.push(format!("{}: {}", path.display(), err));
+                }
+            }
+        }
+        let mut msg = format!(
+            "Saved {} of {} attachment{} in {}.",
+            saved,
+            attachments.len(),
+            if attachments.len() == 1 { "" } else { "s" },
+            dir.display()
+        );
+        if !errors.is_empty() {
+            msg.push_str(" Failed: ");
+            msg.push_str(&errors.join(", "));
+        }
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(msg)));
+    }
+
+    /// Opens a dialog for choosing attachments to save, which is followed by
+    /// a directory chooser.
+    fn open_save_attachments_dialog(&mut self, context: &mut Context) {
+        let attachments = self.attachment_indices(context);
+        if attachments.is_empty() {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                    "This message has no attachments."
+                ))));
+            return;
+        }
+        let mut entries = vec![(None, "all attachments".to_string())];
+        for a_i in attachments {
+            if let Some(u) = self.open_attachment(a_i, context) {
+                entries.push((
+                    Some(a_i),
+                    format!(
+                        "[{}] {} ({})",
+                        a_i,
+                        u.filename().unwrap_or_else(|| "untitled".to_string()),
+                        u.mime_type()
+                    ),
+                ));
+            }
+        }
+        let id = self.id;
+        context
+            .replies
+            .push_back(UIEvent::GlobalUIDialog(Box::new(UIDialog::new(
+                "save attachments",
+                entries,
+                false,
+                Some(Box::new(
+                    move |_: ComponentId, results: &[Option<usize>]| {
+                        if results.is_empty() {
+                            None
+                        } else {
+                            Some(UIEvent::FinishedUIDialog(id, Box::new(results.to_vec())))
+                        }
+                    },
+                )),
+                context,
+            ))));
+    }
+
+    /// Opens a dialog for choosing tags to add to or remove from the
+    /// message.
+    fn open_tag_selector(&mut self, add: bool, context: &mut Context) {
+        match tag_selector(
+            &context.accounts[&self.coordinates.0],
+            &[self.coordinates.2],
+            add,
+            context,
+        ) {
+            Ok(dialog) => context
+                .replies
+                .push_back(UIEvent::GlobalUIDialog(Box::new(dialog))),
+            Err(msg) => context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(msg))),
+        }
+    }
+
+    /// Sets or unsets flags and tags of the message.
+    fn set_flags(
+        &mut self,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+        context: &mut Context,
+    ) {
+        let account = &mut context.accounts[&self.coordinates.0];
+        let mailbox_hash = account.storage_mailbox(self.coordinates.1, self.coordinates.2);
+        let job = account.backend.write().unwrap().set_flags(
+            self.coordinates.2.into(),
+            mailbox_hash,
+            flags,
+        );
+        match job {
+            Ok(fut) => {
+                let handle = account.job_executor.spawn_specialized(fut);
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::SetFlags {
+                        env_hashes: self.coordinates.2.into(),
+                        handle,
+                    },
+                );
+            }
+            Err(err) => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                        "Could not change the message's flags: {}",
+                        err
+                    ))));
+            }
+        }
+    }
+
     fn start_contact_selector(&mut self, context: &mut Context) {
         let account = &context.accounts[&self.coordinates.0];
         if !account.contains_key(self.coordinates.2) {
@@ -1215,7 +1616,10 @@ impl Component for MailView {
                 let (_, mut y) = upper_left;
                 macro_rules! print_header {
                     ($(($header:literal, $string:expr)),*$(,)?) => {
-                        $({
+                        $(print_header!(@ headers, $header, $string);)+
+                    };
+                    (@ $attr:expr, $header:literal, $string:expr) => {
+                        {
                             if sticky || skip_header_ctr == 0 {
                                 if y <= get_y(bottom_right) {
                                     let (_x, _y) = write_string_to_grid(
@@ -1237,9 +1641,9 @@ impl Component for MailView {
                                     let (_x, _y) = write_string_to_grid(
                                         &$string,
                                         grid,
-                                        headers.fg,
-                                        headers.bg,
-                                        headers.attrs,
+                                        $attr.fg,
+                                        $attr.bg,
+                                        $attr.attrs,
                                         ((_x + 1, _y), bottom_right),
                                         Some(get_x(upper_left)),
                                     );
@@ -1257,67 +1661,85 @@ impl Component for MailView {
                                 skip_header_ctr -= 1;
                             }
                             self.headers_no += 1;
-                        })+
+                        }
                     };
                 }
-                let find_offset = |s: &str| -> (bool, (i64, i64)) {
-                    let mut diff = (true, (0, 0));
-                    if let Some(pos) = s.as_bytes().iter().position(|b| *b == b'+' || *b == b'-') {
-                        let offset = &s[pos..];
-                        diff.0 = offset.starts_with('+');
-                        if let (Ok(hr_offset), Ok(min_offset)) =
-                            (offset[1..3].parse::<i64>(), offset[3..5].parse::<i64>())
-                        {
-                            diff.1 .0 = hr_offset;
-                            diff.1 .1 = min_offset;
+                let orig_date = envelope.date_as_str();
+                let date_str: std::borrow::Cow<str> = match self.timezone(context) {
+                    DateTimezone::Sender => orig_date.into(),
+                    timezone => {
+                        let orig_offset = melib::datetime::rfc822_utc_offset(orig_date);
+                        let date = timezone.format(
+                            envelope.timestamp,
+                            orig_offset,
+                            Some(melib::datetime::RFC822_DATE),
+                            false,
+                        );
+                        match orig_offset {
+                            Some(offset)
+                                if melib::datetime::rfc822_utc_offset(&date) != Some(offset) =>
+                            {
+                                format!(
+                                    "{} [actual timezone: {}]",
+                                    date,
+                                    melib::datetime::utc_offset_to_string(offset)
+                                )
+                                .into()
+                            }
+                            Some(_) => orig_date.into(),
+                            None => date.into(),
                         }
                     }
-                    diff
                 };
-                let orig_date = envelope.date_as_str();
-                let date_str: std::borrow::Cow<str> = if mailbox_settings!(
-                    context[self.coordinates.0][&self.coordinates.1]
-                        .pager
-                        .show_date_in_my_timezone
-                )
-                .is_true()
-                {
-                    let local_date = melib::datetime::timestamp_to_string(
-                        envelope.timestamp,
-                        Some(melib::datetime::RFC822_DATE),
-                        false,
-                    );
-                    let orig_offset = find_offset(orig_date);
-                    let local_offset = find_offset(&local_date);
-                    if orig_offset == local_offset {
-                        orig_date.into()
-                    } else {
-                        format!(
-                            "{} [actual timezone: {}{:02}{:02}]",
-                            local_date,
-                            if orig_offset.0 { '+' } else { '-' },
-                            orig_offset.1 .0,
-                            orig_offset.1 .1
-                        )
-                        .into()
-                    }
-                } else {
-                    orig_date.into()
+                let from_highlight = envelope
+                    .from()
+                    .first()
+                    .and_then(|address| context.contact_highlight(self.coordinates.0, address));
+                let from_attr = match from_highlight.as_ref().and_then(|h| h.color) {
+                    Some(fg) => ThemeAttribute { fg, ..headers },
+                    None => headers,
                 };
-                print_header!(
-                    ("Date:", date_str),
-                    ("From:", envelope.field_from_to_string()),
-                    ("To:", envelope.field_to_to_string()),
-                );
+                let from_str = match from_highlight {
+                    Some(ref highlight) => highlight.labeled(envelope.field_from_to_string()),
+                    None => envelope.field_from_to_string(),
+                };
+                print_header!(("Date:", date_str));
+                print_header!(@ from_attr, "From:", from_str);
+                print_header!(("To:", envelope.field_to_to_string()));
                 if envelope.other_headers().contains_key("Cc")
                     && !envelope.other_headers()["Cc"].is_empty()
                 {
                     print_header!(("Cc:", envelope.field_cc_to_string()));
                 }
+                /* Show the protected subject of decrypted messages instead of
+                 * the outer one, which is usually a placeholder. */
+                let subject = match self.protected_header("Subject") {
+                    Some(subject) => format!(
+                        "{} [protected, outer subject: {}]",
+                        subject,
+                        envelope.subject()
+                    ),
+                    None => envelope.subject().to_string(),
+                };
                 print_header!(
-                    ("Subject:", envelope.subject()),
+                    ("Subject:", subject),
                     ("Message-ID:", format!("<{}>", envelope.message_id_raw()))
                 );
+                if let Some((status, failed, smime)) = self.pgp_status() {
+                    let status_attr = crate::conf::value(
+                        context,
+                        if failed {
+                            "mail.view.pgp_failed"
+                        } else {
+                            "mail.view.pgp_status"
+                        },
+                    );
+                    if smime {
+                        print_header!(@ status_attr, "S/MIME:", status);
+                    } else {
+                        print_header!(@ status_attr, "OpenPGP:", status);
+                    }
+                }
                 if self.expand_headers {
                     if let Some(val) = envelope.in_reply_to_display() {
                         print_header!(
@@ -1339,6 +1761,7 @@ impl Component for MailView {
                     ref archive,
                     ref post,
                     ref unsubscribe,
+                    ..
                 }) = list_management::ListActions::detect(&envelope)
                 {
                     let mut x = get_x(upper_left);
@@ -1449,6 +1872,55 @@ impl Component for MailView {
                         y += 1;
                     }
                 }
+                let warn_unknown = *mailbox_settings!(
+                    context[self.coordinates.0][&self.coordinates.1]
+                        .pager
+                        .warn_unknown_senders
+                );
+                let warn_domains = *mailbox_settings!(
+                    context[self.coordinates.0][&self.coordinates.1]
+                        .pager
+                        .warn_sender_domain_mismatch
+                );
+                let mut warnings = if warn_unknown || warn_domains {
+                    sender_warning::sender_warnings(
+                        &envelope,
+                        Some(&account.known_senders).filter(|_| warn_unknown),
+                        &reply::own_addresses(&account.settings.account),
+                        warn_domains,
+                    )
+                } else {
+                    vec![]
+                };
+                warnings.extend(sender_warning::unsubscribed_warning(
+                    &envelope,
+                    &account.unsubscribed_senders,
+                ));
+                let banner = crate::conf::value(context, "mail.view.sender_warning");
+                for warning in warnings {
+                    if sticky || skip_header_ctr == 0 {
+                        if y <= get_y(bottom_right) {
+                            clear_area(
+                                grid,
+                                (set_y(upper_left, y), set_y(bottom_right, y)),
+                                banner,
+                            );
+                            let (_, _y) = write_string_to_grid(
+                                &tr!("Warning: {}", warning),
+                                grid,
+                                banner.fg,
+                                banner.bg,
+                                banner.attrs,
+                                (set_y(upper_left, y), bottom_right),
+                                None,
+                            );
+                            y = _y + 1;
+                        }
+                    } else {
+                        skip_header_ctr -= 1;
+                    }
+                    self.headers_no += 1;
+                }
 
                 self.force_draw_headers = false;
                 clear_area(
@@ -1503,7 +1975,7 @@ impl Component for MailView {
                     .dirty_areas
                     .push_back((set_y(upper_left, y), bottom_right));
                 context.replies.push_back(UIEvent::Notification(
-                    Some("Failed to open e-mail".to_string()),
+                    Some(tr!("Failed to open e-mail")),
                     err.to_string(),
                     Some(NotificationType::Error(err.kind)),
                 ));
@@ -1524,7 +1996,7 @@ impl Component for MailView {
             self.initialised = true;
             match self.mode {
                 ViewMode::Attachment(aidx) => {
-                    let mut text = "Viewing attachment. Press `r` to return \n".to_string();
+                    let mut text = tr!("Viewing attachment. Press `r` to return \n");
                     if let Some(attachment) = self.open_attachment(aidx, context) {
                         if attachment.is_html() {
                             let mut subview = Box::new(HtmlView::new(attachment, context));
@@ -1532,7 +2004,34 @@ impl Component for MailView {
                             self.subview = Some(subview);
                             self.mode = ViewMode::Subview;
                         } else {
-                            text.push_str(&attachment.text());
+                            let mime_type = attachment.mime_type();
+                            match MimeFilter::find(
+                                mailbox_settings!(
+                                    context[self.coordinates.0][&self.coordinates.1]
+                                        .pager
+                                        .filters
+                                ),
+                                &mime_type,
+                            )
+                            .filter(|f| f.copiousoutput)
+                            .map(|f| {
+                                crate::mailcap::filter_output(
+                                    f,
+                                    &mime_type,
+                                    &attachment.decode(Default::default()),
+                                )
+                            }) {
+                                Some(Ok(output)) => {
+                                    text.push_str(&String::from_utf8_lossy(&output));
+                                }
+                                Some(Err(err)) => {
+                                    let _ = writeln!(text, "{}\n", err);
+                                    text.push_str(&attachment.text());
+                                }
+                                None => {
+                                    text.push_str(&attachment.text());
+                                }
+                            }
                             let colors = crate::conf::value(context, "mail.view.body");
                             self.pager =
                                 Pager::from_string(text, Some(context), Some(0), None, colors);
@@ -1599,9 +2098,12 @@ impl Component for MailView {
                 }
                 ViewMode::Subview | ViewMode::ContactSelector(_) => {}
                 ViewMode::Source(source) => {
+                    let mut boundaries = None;
                     let text = {
                         if source == Source::Raw {
-                            String::from_utf8_lossy(bytes).into_owned()
+                            let (text, b) = format_source(bytes, self.fold_source);
+                            boundaries = Some(b);
+                            text
                         } else {
                             /* Decode each header value */
                             let mut ret = melib::email::parser::headers::headers(bytes)
@@ -1639,6 +2141,9 @@ impl Component for MailView {
                     };
                     let colors = crate::conf::value(context, "mail.view.body");
                     self.pager = Pager::from_string(text, Some(context), None, None, colors);
+                    if let Some(boundaries) = boundaries {
+                        self.pager.set_source_highlight(boundaries);
+                    }
                     if let Some(ref filter) = mailbox_settings!(
                         context[self.coordinates.0][&self.coordinates.1]
                             .pager
@@ -1736,6 +2241,10 @@ impl Component for MailView {
             s.draw(grid, area, context);
         }
 
+        if let Some((ref mut chooser, _)) = self.save_dir_chooser {
+            chooser.draw(grid, area, context);
+        }
+
         if let ForceCharset::Dialog(ref mut s) = self.force_charset {
             s.draw(grid, area, context);
         }
@@ -1748,6 +2257,30 @@ impl Component for MailView {
             return false;
         }
 
+        if let Some((ref mut chooser, _)) = self.save_dir_chooser {
+            match *event {
+                UIEvent::FinishedUIDialog(id, ref result) if id == chooser.id() => {
+                    let dir = result.downcast_ref::<std::path::PathBuf>().cloned();
+                    if let (Some(dir), Some((_, attachments))) = (dir, self.save_dir_chooser.take())
+                    {
+                        self.save_attachments(&attachments, &dir, context);
+                    }
+                    self.set_dirty(true);
+                    return true;
+                }
+                UIEvent::ComponentKill(id) if id == chooser.id() => {
+                    self.save_dir_chooser = None;
+                    self.set_dirty(true);
+                    return true;
+                }
+                _ => {
+                    if chooser.process_event(event, context) {
+                        return true;
+                    }
+                }
+            }
+        }
+
         match (&mut self.force_charset, &event) {
             (ForceCharset::Dialog(selector), UIEvent::FinishedUIDialog(id, results))
                 if *id == selector.id() =>
@@ -1892,7 +2425,7 @@ impl Component for MailView {
                                                   * perhaps a worker thread
                                                   * panicked */
                                             }
-                                            Ok(Some(Ok(()))) => {
+                                            Ok(Some(Ok(description))) => {
                                                 *d = AttachmentDisplay::SignedVerified {
                                                     inner: std::mem::replace(
                                                         inner,
@@ -1901,7 +2434,7 @@ impl Component for MailView {
                                                         ),
                                                     ),
                                                     display: std::mem::take(display),
-                                                    description: String::new(),
+                                                    description,
                                                 };
                                             }
                                             Ok(Some(Err(error))) => {
@@ -1930,10 +2463,27 @@ impl Component for MailView {
                                                   * panicked */
                                             }
                                             Ok(Some(Ok((metadata, decrypted_bytes)))) => {
-                                                let plaintext = Box::new(
-                                                    AttachmentBuilder::new(&decrypted_bytes)
-                                                        .build(),
-                                                );
+                                                /* Inline PGP messages are in text parts. */
+                                                let is_inline = inner.is_text();
+                                                let (plaintext, protected_headers) = if is_inline {
+                                                    (
+                                                        inline_pgp_plaintext(
+                                                            inner,
+                                                            decrypted_bytes,
+                                                            (&self.force_charset).into(),
+                                                        ),
+                                                        vec![],
+                                                    )
+                                                } else {
+                                                    (
+                                                        AttachmentBuilder::new(&decrypted_bytes)
+                                                            .build(),
+                                                        melib::pgp::protected_headers(
+                                                            &decrypted_bytes,
+                                                        ),
+                                                    )
+                                                };
+                                                let plaintext = Box::new(plaintext);
                                                 let plaintext_display = Self::attachment_to(
                                                     &plaintext,
                                                     context,
@@ -1950,7 +2500,11 @@ impl Component for MailView {
                                                     ),
                                                     plaintext,
                                                     plaintext_display,
-                                                    description: format!("{:?}", metadata),
+                                                    description: tr!(
+                                                        "Decrypted message encrypted to key {}.",
+                                                        key_ids(&metadata.recipients)
+                                                    ),
+                                                    protected_headers,
                                                 };
                                             }
                                             Ok(Some(Err(error))) => {
@@ -2027,6 +2581,12 @@ impl Component for MailView {
                 self.perform_action(PendingReplyAction::ReplyToAuthor, context);
                 return true;
             }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::ENVELOPE_VIEW]["reply_to_list"]) =>
+            {
+                self.perform_action(PendingReplyAction::ReplyToList, context);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Shortcuts::ENVELOPE_VIEW]["forward"]) =>
             {
@@ -2071,9 +2631,36 @@ impl Component for MailView {
             UIEvent::FinishedUIDialog(id, ref result) if id == self.id() => {
                 if let Some(result) = result.downcast_ref::<PendingReplyAction>() {
                     self.perform_action(*result, context);
+                } else if let Some(result) = result.downcast_ref::<Vec<Option<usize>>>() {
+                    /* Attachments chosen in the `save_attachments` dialog, `None` being all of
+                     * them. */
+                    let attachments = if result.contains(&None) {
+                        self.attachment_indices(context)
+                    } else {
+                        result.iter().flatten().copied().collect()
+                    };
+                    let dir = std::env::current_dir().unwrap_or_else(|_| "/".into());
+                    self.save_dir_chooser = Some((
+                        Box::new(FileBrowser::choose_dir(
+                            "save attachments in".to_string(),
+                            dir,
+                            context,
+                        )),
+                        attachments,
+                    ));
+                    self.set_dirty(true);
                 }
                 return true;
             }
+            UIEvent::Input(ref key)
+                if self.mode == ViewMode::Normal
+                    && shortcut!(
+                        key == shortcuts[Shortcuts::ENVELOPE_VIEW]["save_attachments"]
+                    ) =>
+            {
+                self.open_save_attachments_dialog(context);
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Shortcuts::ENVELOPE_VIEW]["edit"]) =>
             {
@@ -2118,7 +2705,7 @@ impl Component for MailView {
                                             ))));
                                         }
                                         Err(err) => {
-                                            let err_string = format!(
+                                            let err_string = tr!(
                                                 "Failed to open envelope {}: {}",
                                                 context.accounts[&account_hash]
                                                     .collection
@@ -2132,7 +2719,7 @@ impl Component for MailView {
                                             );
                                             log::error!("{err_string}");
                                             context.replies.push_back(UIEvent::Notification(
-                                                Some("Failed to open e-mail".to_string()),
+                                                Some(tr!("Failed to open e-mail")),
                                                 err_string,
                                                 Some(NotificationType::Error(err.kind)),
                                             ));
@@ -2146,10 +2733,61 @@ impl Component for MailView {
                 );
                 return true;
             }
+            UIEvent::Action(Listing(ListingAction::Tag(TagAction::AddInteractive))) => {
+                self.open_tag_selector(true, context);
+                return true;
+            }
+            UIEvent::Action(Listing(ListingAction::Tag(TagAction::RemoveInteractive))) => {
+                self.open_tag_selector(false, context);
+                return true;
+            }
+            UIEvent::Action(Listing(ListingAction::Tag(TagAction::Add(ref tag)))) => {
+                self.set_flags(smallvec::smallvec![(Err(tag.to_string()), true)], context);
+                return true;
+            }
+            UIEvent::Action(Listing(ListingAction::Tag(TagAction::Remove(ref tag)))) => {
+                self.set_flags(smallvec::smallvec![(Err(tag.to_string()), false)], context);
+                return true;
+            }
+            UIEvent::Action(Listing(ListingAction::SetFlags(ref ops))) => {
+                let ops = ops.iter().cloned().collect();
+                self.set_flags(ops, context);
+                return true;
+            }
             UIEvent::Action(View(ViewAction::AddAddressesToContacts)) => {
                 self.start_contact_selector(context);
                 return true;
             }
+            UIEvent::Action(View(ViewAction::AddressHistory(ref address))) => {
+                let address = match address {
+                    Some(address) => match Address::try_from(address.as_str()) {
+                        Ok(address) => Some(address),
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(tr!(
+                                    "Could not parse address `{}`: {}",
+                                    address,
+                                    err
+                                )),
+                            ));
+                            return true;
+                        }
+                    },
+                    None => context.accounts[&self.coordinates.0]
+                        .collection
+                        .get_env(self.coordinates.2)
+                        .from()
+                        .first()
+                        .cloned(),
+                };
+                if let Some(address) = address {
+                    let history = AddressHistoryView::new(self.coordinates.0, address, context);
+                    context
+                        .replies
+                        .push_back(UIEvent::Action(Tab(New(Some(Box::new(history))))));
+                }
+                return true;
+            }
             UIEvent::Input(ref key)
                 if !self.mode.is_contact_selector()
                     && shortcut!(
@@ -2262,6 +2900,34 @@ impl Component for MailView {
                     MailViewState::Error { .. } | MailViewState::LoadingBody { .. } => {}
                     MailViewState::Loaded { .. } => {
                         if let Some(attachment) = self.open_attachment(lidx, context) {
+                            let mime_type = attachment.mime_type();
+                            if let Some(filter) = MimeFilter::find(
+                                mailbox_settings!(
+                                    context[self.coordinates.0][&self.coordinates.1]
+                                        .pager
+                                        .filters
+                                ),
+                                &mime_type,
+                            )
+                            .cloned()
+                            {
+                                if filter.copiousoutput {
+                                    self.mode = ViewMode::Attachment(lidx);
+                                    self.initialised = false;
+                                    self.dirty = true;
+                                } else if let Err(err) = crate::mailcap::launch_viewer(
+                                    &filter,
+                                    &mime_type,
+                                    &attachment.decode(Default::default()),
+                                    attachment.filename().as_deref(),
+                                    context,
+                                ) {
+                                    context.replies.push_back(UIEvent::StatusEvent(
+                                        StatusEvent::DisplayMessage(err.to_string()),
+                                    ));
+                                }
+                                return true;
+                            }
                             match attachment.content_type() {
                                 ContentType::MessageRfc822 => {
                                     match Mail::new(attachment.body().to_vec(), Some(Flag::SEEN)) {
@@ -2325,9 +2991,10 @@ impl Component for MailView {
                                             }
                                             Err(err) => {
                                                 context.replies.push_back(UIEvent::StatusEvent(
-                                                    StatusEvent::DisplayMessage(format!(
+                                                    StatusEvent::DisplayMessage(tr!(
                                                         "Failed to start `{}`: {}",
-                                                        &exec_cmd, err
+                                                        &exec_cmd,
+                                                        err
                                                     )),
                                                 ));
                                             }
@@ -2383,6 +3050,35 @@ impl Component for MailView {
                 self.set_dirty(true);
                 return true;
             }
+            UIEvent::Input(ref key)
+                if (self.mode == ViewMode::Normal || self.mode == ViewMode::Url)
+                    && shortcut!(key == shortcuts[Shortcuts::ENVELOPE_VIEW]["toggle_timezone"]) =>
+            {
+                let timezone = self
+                    .timezone(context)
+                    .next(self.configured_timezone(context));
+                self.timezone = Some(timezone);
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Showing dates in {} time.",
+                        timezone
+                    ))));
+                self.force_draw_headers = true;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if self.mode == ViewMode::Source(Source::Raw)
+                    && shortcut!(
+                        key == shortcuts[Shortcuts::ENVELOPE_VIEW]["toggle_expand_headers"]
+                    ) =>
+            {
+                self.fold_source = !self.fold_source;
+                self.set_dirty(true);
+                self.initialised = false;
+                return true;
+            }
             UIEvent::Input(ref key)
                 if !self.cmd_buf.is_empty()
                     && self.mode == ViewMode::Url
@@ -2414,10 +3110,7 @@ impl Component for MailView {
                                 l
                             } else {
                                 context.replies.push_back(UIEvent::StatusEvent(
-                                    StatusEvent::DisplayMessage(format!(
-                                        "Link `{}` not found.",
-                                        lidx
-                                    )),
+                                    StatusEvent::DisplayMessage(tr!("Link `{}` not found.", lidx)),
                                 ));
                                 return true;
                             }
@@ -2477,6 +3170,84 @@ impl Component for MailView {
             UIEvent::EnvelopeRename(old_hash, new_hash) if self.coordinates.2 == old_hash => {
                 self.coordinates.2 = new_hash;
             }
+            UIEvent::Action(View(ViewAction::SetCharset(charset))) => {
+                self.force_charset = match charset {
+                    Some(charset) => ForceCharset::Forced(charset),
+                    None => ForceCharset::None,
+                };
+                MailViewState::redecode(self, context);
+                self.initialised = false;
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::Reply(quote))) => {
+                self.perform_action(
+                    if quote {
+                        PendingReplyAction::Reply
+                    } else {
+                        PendingReplyAction::ReplyWithoutQuote
+                    },
+                    context,
+                );
+                return true;
+            }
+            #[cfg(feature = "gpgme")]
+            UIEvent::Action(View(ViewAction::FetchKey)) => {
+                let mechanisms = *mailbox_settings!(
+                    context[self.coordinates.0][&self.coordinates.1]
+                        .pgp
+                        .remote_lookup_mechanisms
+                );
+                let mut fetching = false;
+                if let MailViewState::Loaded {
+                    ref mut display, ..
+                } = self.state
+                {
+                    for d in display.iter_mut() {
+                        if let AttachmentDisplay::SignedFailed { inner, display, .. } = d {
+                            if melib::smime::is_smime_signed(inner) {
+                                continue;
+                            }
+                            let verify_fut = crate::components::mail::pgp::verify_fetching_key(
+                                (**inner).clone(),
+                                mechanisms,
+                            );
+                            let handle = context.job_executor.spawn_specialized(async move {
+                                verify_fut.await.map(|metadata| {
+                                    tr!("Good signature by key {}.", key_ids(&metadata.signatures))
+                                })
+                            });
+                            self.active_jobs.insert(handle.job_id);
+                            context
+                                .replies
+                                .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(
+                                    handle.job_id,
+                                )));
+                            *d = AttachmentDisplay::SignedPending {
+                                inner: std::mem::replace(
+                                    inner,
+                                    Box::new(AttachmentBuilder::new(&[]).build()),
+                                ),
+                                display: std::mem::take(display),
+                                job_id: handle.job_id,
+                                handle,
+                            };
+                            fetching = true;
+                        }
+                    }
+                }
+                if fetching {
+                    self.initialised = false;
+                    self.set_dirty(true);
+                } else {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            "fetch-key: no unverified signatures in this message".to_string(),
+                        )));
+                }
+                return true;
+            }
             UIEvent::Action(View(ViewAction::ExportMail(ref path))) => {
                 // Save entire message as eml
                 let account = &context.accounts[&self.coordinates.0];
@@ -2489,7 +3260,7 @@ impl Component for MailView {
                     bytes
                 } else if let MailViewState::Error { ref err } = self.state {
                     context.replies.push_back(UIEvent::Notification(
-                        Some("Failed to open e-mail".to_string()),
+                        Some(tr!("Failed to open e-mail")),
                         err.to_string(),
                         Some(NotificationType::Error(err.kind)),
                     ));
@@ -2509,7 +3280,7 @@ impl Component for MailView {
                 match save_attachment(&path, bytes) {
                     Err(err) => {
                         context.replies.push_back(UIEvent::Notification(
-                            Some(format!("Failed to create file at {}", path.display())),
+                            Some(tr!("Failed to create file at {}", path.display())),
                             err.to_string(),
                             Some(NotificationType::Error(melib::ErrorKind::External)),
                         ));
@@ -2519,7 +3290,7 @@ impl Component for MailView {
                     Ok(()) => {
                         context.replies.push_back(UIEvent::Notification(
                             None,
-                            format!("Saved at {}", &path.display()),
+                            tr!("Saved at {}", &path.display()),
                             Some(NotificationType::Info),
                         ));
                     }
@@ -2527,6 +3298,22 @@ impl Component for MailView {
 
                 return true;
             }
+            UIEvent::Action(View(ViewAction::SaveAllAttachments(ref path))) => {
+                if !matches!(self.state, MailViewState::Loaded { .. }) {
+                    return true;
+                }
+                let attachments = self.attachment_indices(context);
+                if attachments.is_empty() {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
+                            "This message has no attachments."
+                        ))));
+                } else {
+                    self.save_attachments(&attachments, std::path::Path::new(path), context);
+                }
+                return true;
+            }
             UIEvent::Action(View(ViewAction::SaveAttachment(a_i, ref path))) => {
                 {
                     let account = &context.accounts[&self.coordinates.0];
@@ -2540,7 +3327,7 @@ impl Component for MailView {
                     bytes
                 } else if let MailViewState::Error { ref err } = self.state {
                     context.replies.push_back(UIEvent::Notification(
-                        Some("Failed to open e-mail".to_string()),
+                        Some(tr!("Failed to open e-mail")),
                         err.to_string(),
                         Some(NotificationType::Error(err.kind)),
                     ));
@@ -2555,17 +3342,17 @@ impl Component for MailView {
 
                 if let Some(u) = self.open_attachment(a_i, context) {
                     if path.is_dir() {
-                        if let Some(filename) = u.filename() {
-                            path.push(filename);
+                        let filename = if let Some(filename) = u.filename() {
+                            sanitize_filename(&filename)
                         } else {
-                            let u = melib::uuid::Uuid::new_v4();
-                            path.push(u.as_hyphenated().to_string());
-                        }
+                            melib::uuid::Uuid::new_v4().as_hyphenated().to_string()
+                        };
+                        path = unique_path(&path, &filename);
                     }
                     match save_attachment(&path, &u.decode(Default::default())) {
                         Err(err) => {
                             context.replies.push_back(UIEvent::Notification(
-                                Some(format!("Failed to create file at {}", path.display())),
+                                Some(tr!("Failed to create file at {}", path.display())),
                                 err.to_string(),
                                 Some(NotificationType::Error(melib::ErrorKind::External)),
                             ));
@@ -2574,7 +3361,7 @@ impl Component for MailView {
                         Ok(()) => {
                             context.replies.push_back(UIEvent::Notification(
                                 None,
-                                format!("Saved at {}", path.display()),
+                                tr!("Saved at {}", path.display()),
                                 Some(NotificationType::Info),
                             ));
                         }
@@ -2589,7 +3376,7 @@ impl Component for MailView {
                     match save_attachment(&path, bytes) {
                         Err(err) => {
                             context.replies.push_back(UIEvent::Notification(
-                                Some(format!("Failed to create file at {}", path.display())),
+                                Some(tr!("Failed to create file at {}", path.display())),
                                 err.to_string(),
                                 Some(NotificationType::Error(melib::ErrorKind::External)),
                             ));
@@ -2599,7 +3386,7 @@ impl Component for MailView {
                         Ok(()) => {
                             context.replies.push_back(UIEvent::Notification(
                                 None,
-                                format!("Saved at {}", &path.display()),
+                                tr!("Saved at {}", &path.display()),
                                 Some(NotificationType::Info),
                             ));
                         }
@@ -2609,14 +3396,19 @@ impl Component for MailView {
                 } else {
                     context
                         .replies
-                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
                             "Attachment `{}` not found.",
                             a_i
                         ))));
                 }
                 return true;
             }
-            UIEvent::Action(MailingListAction(ref e)) => {
+            UIEvent::Action(MailingListAction(ref e))
+                if !matches!(
+                    e,
+                    MailingListAction::UnsubscribeAssistant | MailingListAction::Unsubscribe(_)
+                ) =>
+            {
                 let account = &context.accounts[&self.coordinates.0];
                 if !account.contains_key(self.coordinates.2) {
                     /* The envelope has been renamed or removed, so wait for the appropriate
@@ -2683,7 +3475,7 @@ impl Component for MailView {
                                                 true,
                                             ) {
                                                 context.replies.push_back(UIEvent::StatusEvent(
-                                                    StatusEvent::DisplayMessage(format!(
+                                                    StatusEvent::DisplayMessage(tr!(
                                                         "Couldn't send unsubscribe e-mail: {}",
                                                         err
                                                     )),
@@ -2812,6 +3604,7 @@ impl Component for MailView {
                     (Some(Charset::BIG5), Charset::BIG5.to_string()),
                     (Some(Charset::ISO2022JP), Charset::ISO2022JP.to_string()),
                     (Some(Charset::EUCJP), Charset::EUCJP.to_string()),
+                    (Some(Charset::ShiftJIS), Charset::ShiftJIS.to_string()),
                     (Some(Charset::KOI8R), Charset::KOI8R.to_string()),
                     (Some(Charset::KOI8U), Charset::KOI8U.to_string()),
                 ];
@@ -2841,10 +3634,14 @@ impl Component for MailView {
             || self.subview.as_ref().map(|p| p.is_dirty()).unwrap_or(false)
             || matches!(self.force_charset, ForceCharset::Dialog(ref s) if s.is_dirty())
             || matches!(self.mode, ViewMode::ContactSelector(ref s) if s.is_dirty())
+            || matches!(self.save_dir_chooser, Some((ref c, _)) if c.is_dirty())
     }
 
     fn set_dirty(&mut self, value: bool) {
         self.dirty = value;
+        if let Some((ref mut chooser, _)) = self.save_dir_chooser {
+            chooser.set_dirty(value);
+        }
         match self.mode {
             ViewMode::Normal | ViewMode::Url | ViewMode::Source(_) | ViewMode::Attachment(_) => {
                 self.pager.set_dirty(value);
@@ -2907,6 +3704,69 @@ impl Component for MailView {
     }
 }
 
+/// Renders the html in `bytes` as text with `filter`, or with the built-in
+/// renderer without one, and returns the comment to show above the text and
+/// the text. If `filter` fails to run, the html is returned as it is.
+fn html_to_text(
+    bytes: &[u8],
+    filter: Option<MimeFilter>,
+    width: usize,
+    context: &mut Context,
+) -> (String, String) {
+    let filter = match filter {
+        Some(filter) => filter,
+        None => {
+            let rendered = melib::email::html::render(&String::from_utf8_lossy(bytes), width);
+            return (
+                tr!("Html rendered as text. Press `v` to open in web browser. \n\n"),
+                rendered.text,
+            );
+        }
+    };
+    match crate::mailcap::filter_output(&filter, "text/html", bytes) {
+        Err(err) => {
+            context.replies.push_back(UIEvent::Notification(
+                Some(tr!(
+                    "Failed to start html filter process: {}",
+                    filter.command,
+                )),
+                err.to_string(),
+                Some(NotificationType::Error(melib::ErrorKind::External)),
+            ));
+            (
+                format!(
+                    "Failed to start html filter process: `{}`. Press `v` to open in web browser. \
+                     \n\n",
+                    filter.command
+                ),
+                String::from_utf8_lossy(bytes).to_string(),
+            )
+        }
+        Ok(output) => (
+            tr!(
+                "Text piped through `{}`. Press `v` to open in web browser. \n\n",
+                filter.command
+            ),
+            String::from_utf8_lossy(&output).to_string(),
+        ),
+    }
+}
+
+/// Removes tracking pixels and click-tracking links from the html in `bytes`
+/// if `enabled`, and returns the report of what was removed, if anything.
+fn sanitize_html(bytes: &mut Vec<u8>, enabled: bool) -> Option<String> {
+    if !enabled {
+        return None;
+    }
+    let (html, report) = melib::email::html::sanitize(&String::from_utf8_lossy(bytes));
+    *bytes = html.into_bytes();
+    if report.is_empty() {
+        None
+    } else {
+        Some(report.to_string())
+    }
+}
+
 fn save_attachment(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
     let mut f = std::fs::File::create(path)?;
     let mut permissions = f.metadata()?.permissions();
@@ -2917,6 +3777,63 @@ fn save_attachment(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
     Ok(())
 }
 
+/// Makes attachment filename `name` safe to save a file as: only its last
+/// path component is kept, control characters are replaced and leading dots
+/// are removed, so that it can't refer to another directory or be hidden.
+fn sanitize_filename(name: &str) -> String {
+    let name = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_control() { '_' } else { c })
+        .collect::<String>();
+    let name = name.trim().trim_start_matches('.').trim_start();
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Returns `dir/name`, or if a file with that name exists, the first of
+/// `dir/name-1`, `dir/name-2`... that doesn't, with the number inserted
+/// before the extension.
+fn unique_path(dir: &std::path::Path, name: &str) -> std::path::PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, extension) = match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{}-{}{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[test]
+fn test_save_attachment_filenames() {
+    assert_eq!(sanitize_filename("report.pdf"), "report.pdf");
+    assert_eq!(sanitize_filename("../../.bashrc"), "bashrc");
+    assert_eq!(sanitize_filename("C:\\Users\\me\\cv.doc"), "cv.doc");
+    assert_eq!(sanitize_filename("tab\tname\n.txt"), "tab_name_.txt");
+    assert_eq!(sanitize_filename(" .. "), "attachment");
+    assert_eq!(sanitize_filename("dir/"), "attachment");
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let dir = tmp_dir.path();
+    assert_eq!(unique_path(dir, "a.tar.gz"), dir.join("a.tar.gz"));
+    std::fs::write(dir.join("a.tar.gz"), "").unwrap();
+    assert_eq!(unique_path(dir, "a.tar.gz"), dir.join("a.tar-1.gz"));
+    std::fs::write(dir.join("a.tar-1.gz"), "").unwrap();
+    assert_eq!(unique_path(dir, "a.tar.gz"), dir.join("a.tar-2.gz"));
+    std::fs::write(dir.join("README"), "").unwrap();
+    assert_eq!(unique_path(dir, "README"), dir.join("README-1"));
+}
+
 fn desktop_exec_to_command(command: &str, path: String, is_url: bool) -> String {
     /* Purge unused field codes */
     let command = command
@@ -2945,6 +3862,35 @@ fn desktop_exec_to_command(command: &str, path: String, is_url: bool) -> String
     }
 }
 
+/// Returns the key ids of `recipients`, the signers or recipients of a
+/// message.
+fn key_ids(recipients: &[melib::pgp::Recipient]) -> String {
+    let ids = recipients
+        .iter()
+        .filter_map(|r| r.keyid.as_deref())
+        .collect::<Vec<&str>>();
+    if ids.is_empty() {
+        "(unknown)".to_string()
+    } else {
+        ids.join(", ")
+    }
+}
+
+/// Returns text part `inner` of an inline PGP message with its armored
+/// message replaced by the `decrypted` plaintext.
+fn inline_pgp_plaintext(
+    inner: &Attachment,
+    decrypted: Vec<u8>,
+    force_charset: Option<Charset>,
+) -> Attachment {
+    let text = inner.decode(force_charset.into());
+    let text = match melib::pgp::inline_pgp_message(&text) {
+        Some(range) => [&text[..range.start], &decrypted, &text[range.end..]].concat(),
+        None => decrypted,
+    };
+    Attachment::new(ContentType::default(), Default::default(), text)
+}
+
 #[test]
 fn test_desktop_exec() {
     assert_eq!(
@@ -89,41 +89,18 @@ impl EnvelopeView {
         let body_text = String::from_utf8_lossy(&body.decode_rec(DecodeOptions {
             filter: Some(Box::new(|a: &Attachment, v: &mut Vec<u8>| {
                 if a.content_type().is_text_html() {
-                    let settings = &context.settings;
-                    if let Some(filter_invocation) = settings.pager.html_filter.as_ref() {
-                        let command_obj = Command::new("sh")
-                            .args(["-c", filter_invocation])
-                            .stdin(Stdio::piped())
-                            .stdout(Stdio::piped())
-                            .spawn();
-                        match command_obj {
-                            Err(err) => {
-                                context.replies.push_back(UIEvent::Notification(
-                                    Some(format!(
-                                        "Failed to start html filter process: {}",
-                                        filter_invocation,
-                                    )),
-                                    err.to_string(),
-                                    Some(NotificationType::Error(melib::ErrorKind::External)),
-                                ));
-                            }
-                            Ok(mut html_filter) => {
-                                html_filter
-                                    .stdin
-                                    .as_mut()
-                                    .unwrap()
-                                    .write_all(v)
-                                    .expect("Failed to write to stdin");
-                                *v = format!(
-                                    "Text piped through `{}`. Press `v` to open in web browser. \
-                                     \n\n",
-                                    filter_invocation
-                                )
-                                .into_bytes();
-                                v.extend(html_filter.wait_with_output().unwrap().stdout);
-                            }
-                        }
+                    let report = super::sanitize_html(v, context.settings.pager.sanitize_html);
+                    let filter = MimeFilter::for_html(
+                        context.settings.pager.html_filter.as_deref(),
+                        &context.settings.pager.filters,
+                    );
+                    let width = context.settings.pager.minimum_width;
+                    let (comment, text) = super::html_to_text(v, filter, width, context);
+                    *v = comment.into_bytes();
+                    if let Some(report) = report {
+                        v.extend(format!("{}\n\n", report).into_bytes());
                     }
+                    v.extend(text.into_bytes());
                 }
             })),
             force_charset: if let ForceCharset::Forced(val) = self.force_charset {
@@ -177,8 +154,25 @@ impl EnvelopeView {
             }
             ViewMode::Attachment(aidx) => {
                 let attachments = body.attachments();
+                let attachment = &attachments[aidx];
                 let mut ret = "Viewing attachment. Press `r` to return \n".to_string();
-                ret.push_str(&attachments[aidx].text());
+                let mime_type = attachment.mime_type();
+                match MimeFilter::find(&context.settings.pager.filters, &mime_type)
+                    .filter(|f| f.copiousoutput)
+                    .map(|f| {
+                        crate::mailcap::filter_output(
+                            f,
+                            &mime_type,
+                            &attachment.decode(Default::default()),
+                        )
+                    }) {
+                    Some(Ok(output)) => ret.push_str(&String::from_utf8_lossy(&output)),
+                    Some(Err(err)) => {
+                        let _ = writeln!(ret, "{}\n", err);
+                        ret.push_str(&attachment.text());
+                    }
+                    None => ret.push_str(&attachment.text()),
+                }
                 ret
             }
         }
@@ -407,6 +401,26 @@ impl Component for EnvelopeView {
                     .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
 
                 if let Some(u) = self.mail.body().attachments().get(lidx) {
+                    let mime_type = u.mime_type();
+                    if let Some(filter) =
+                        MimeFilter::find(&context.settings.pager.filters, &mime_type).cloned()
+                    {
+                        if filter.copiousoutput {
+                            self.mode = ViewMode::Attachment(lidx);
+                            self.dirty = true;
+                        } else if let Err(err) = crate::mailcap::launch_viewer(
+                            &filter,
+                            &mime_type,
+                            &u.decode(Default::default()),
+                            u.filename().as_deref(),
+                            context,
+                        ) {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(err.to_string()),
+                            ));
+                        }
+                        return true;
+                    }
                     match u.content_type() {
                         ContentType::MessageRfc822 => {
                             self.mode = ViewMode::Subview;
@@ -594,6 +608,7 @@ impl Component for EnvelopeView {
                     (Some(Charset::BIG5), Charset::BIG5.to_string()),
                     (Some(Charset::ISO2022JP), Charset::ISO2022JP.to_string()),
                     (Some(Charset::EUCJP), Charset::EUCJP.to_string()),
+                    (Some(Charset::ShiftJIS), Charset::ShiftJIS.to_string()),
                     (Some(Charset::KOI8R), Charset::KOI8R.to_string()),
                     (Some(Charset::KOI8U), Charset::KOI8U.to_string()),
                 ];
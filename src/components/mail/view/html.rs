@@ -19,10 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::{
-    io::Write,
-    process::{Command, Stdio},
-};
+use std::process::{Command, Stdio};
 
 use super::*;
 
@@ -37,72 +34,19 @@ pub struct HtmlView {
 impl HtmlView {
     pub fn new(body: &Attachment, context: &mut Context) -> Self {
         let id = ComponentId::new_v4();
-        let bytes: Vec<u8> = body.decode_rec(Default::default());
+        let mut bytes: Vec<u8> = body.decode_rec(Default::default());
+        let report = super::sanitize_html(&mut bytes, context.settings.pager.sanitize_html);
 
-        let settings = &context.settings;
-        let mut display_text = if let Some(filter_invocation) = settings.pager.html_filter.as_ref()
-        {
-            let command_obj = Command::new("sh")
-                .args(["-c", filter_invocation])
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn();
-            match command_obj {
-                Err(err) => {
-                    context.replies.push_back(UIEvent::Notification(
-                        Some(format!(
-                            "Failed to start html filter process: {}",
-                            filter_invocation,
-                        )),
-                        err.to_string(),
-                        Some(NotificationType::Error(melib::ErrorKind::External)),
-                    ));
-                    String::from_utf8_lossy(&bytes).to_string()
-                }
-                Ok(mut html_filter) => {
-                    html_filter
-                        .stdin
-                        .as_mut()
-                        .unwrap()
-                        .write_all(&bytes)
-                        .expect("Failed to write to html filter stdin");
-                    let mut display_text = format!(
-                        "Text piped through `{}`. Press `v` to open in web browser. \n\n",
-                        filter_invocation
-                    );
-                    display_text.push_str(&String::from_utf8_lossy(
-                        &html_filter.wait_with_output().unwrap().stdout,
-                    ));
-                    display_text
-                }
-            }
-        } else if let Ok(mut html_filter) = Command::new("w3m")
-            .args(["-I", "utf-8", "-T", "text/html"])
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-        {
-            html_filter
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(&bytes)
-                .expect("Failed to write to html filter stdin");
-            let mut display_text =
-                String::from("Text piped through `w3m`. Press `v` to open in web browser. \n\n");
-            display_text.push_str(&String::from_utf8_lossy(
-                &html_filter.wait_with_output().unwrap().stdout,
-            ));
-
-            display_text
-        } else {
-            context.replies.push_back(UIEvent::Notification(
-                Some("Failed to find any application to use as html filter".to_string()),
-                String::new(),
-                Some(NotificationType::Error(melib::error::ErrorKind::None)),
-            ));
-            String::from_utf8_lossy(&bytes).to_string()
-        };
+        let filter = MimeFilter::for_html(
+            context.settings.pager.html_filter.as_deref(),
+            &context.settings.pager.filters,
+        );
+        let width = context.settings.pager.minimum_width;
+        let (mut display_text, text) = super::html_to_text(&bytes, filter, width, context);
+        if let Some(report) = report {
+            let _ = write!(display_text, "{}\n\n", report);
+        }
+        display_text.push_str(&text);
         if body.count_attachments() > 1 {
             display_text =
                 body.attachments()
@@ -144,14 +88,34 @@ impl Component for HtmlView {
         }
 
         if let UIEvent::Input(Key::Char('v')) = event {
-            let command = if let Some(coordinates) = self.coordinates {
-                mailbox_settings!(context[coordinates.0][&coordinates.1].pager.html_open)
-                    .as_ref()
-                    .map(|s| s.to_string())
-                    .or_else(|| query_default_app("text/html").ok())
+            let (html_open, filters) = if let Some(coordinates) = self.coordinates {
+                (
+                    mailbox_settings!(context[coordinates.0][&coordinates.1].pager.html_open),
+                    mailbox_settings!(context[coordinates.0][&coordinates.1].pager.filters),
+                )
             } else {
-                query_default_app("text/html").ok()
+                (
+                    &context.settings.pager.html_open,
+                    &context.settings.pager.filters,
+                )
             };
+            let html_open = html_open.clone();
+            if let Some(viewer) = MimeFilter::find(filters, "text/html")
+                .filter(|f| html_open.is_none() && !f.copiousoutput)
+                .cloned()
+            {
+                if let Err(err) =
+                    crate::mailcap::launch_viewer(&viewer, "text/html", &self.bytes, None, context)
+                {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            err.to_string(),
+                        )));
+                }
+                return true;
+            }
+            let command = html_open.or_else(|| query_default_app("text/html").ok());
             if let Some(command) = command {
                 let p = create_temp_file(&self.bytes, None, None, true);
                 let exec_cmd =
@@ -0,0 +1,271 @@
+/*
+ * meli
+ *
+ * Copyright 2017-2018 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Warnings about the sender of a message, shown as a banner above the
+//! headers in [`MailView`](super::MailView).
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use melib::{datetime::UnixTimestamp, Address, Envelope};
+
+use crate::components::mail::reply;
+
+/// A reason to distrust the sender of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderWarning {
+    /// The user has never sent mail to the sender.
+    FirstTime(String),
+    /// The domain of `From` differs from the domain of `header`.
+    DomainMismatch {
+        header: &'static str,
+        from: String,
+        other: String,
+    },
+    /// The user unsubscribed from the sender at this time, but the sender
+    /// keeps mailing.
+    StillMailing(String, UnixTimestamp),
+}
+
+impl fmt::Display for SenderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FirstTime(email) => {
+                write!(f, "You have not corresponded with {} before.", email)
+            }
+            Self::DomainMismatch {
+                header,
+                from,
+                other,
+            } => write!(
+                f,
+                "From: domain {} differs from {}: domain {}.",
+                from, header, other
+            ),
+            Self::StillMailing(email, since) => write!(
+                f,
+                "You unsubscribed from {} on {}, but they keep mailing you.",
+                email,
+                melib::datetime::timestamp_to_string(*since, Some("%Y-%m-%d"), false)
+            ),
+        }
+    }
+}
+
+/// Returns whether two domains belong together, i.e. they are equal or one is
+/// a subdomain of the other (`example.com` and `mail.example.com`).
+pub fn domains_match(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+    let (a, b) = (a.trim_end_matches('.'), b.trim_end_matches('.'));
+    fn is_subdomain(sub: &str, parent: &str) -> bool {
+        sub.len() > parent.len()
+            && sub.ends_with(parent)
+            && sub.as_bytes()[sub.len() - parent.len() - 1] == b'.'
+    }
+    a == b || is_subdomain(a, b) || is_subdomain(b, a)
+}
+
+/// Returns the domain of the address in a `Return-Path` header value such as
+/// `<bounces@example.com>`.
+fn return_path_domain(value: &str) -> Option<String> {
+    let value = value.trim().trim_start_matches('<').trim_end_matches('>');
+    let (_, domain) = value.rsplit_once('@')?;
+    Some(domain.trim().to_string()).filter(|d| !d.is_empty())
+}
+
+/// Collects the addresses the user has sent mail to from `envelopes` into
+/// `known`. A message counts as sent if its `From` is one of the user's
+/// addresses in `own`.
+pub fn learn_known_senders<'a>(
+    known: &mut HashSet<String>,
+    own: &[Address],
+    envelopes: impl IntoIterator<Item = &'a Envelope>,
+) {
+    for envelope in envelopes {
+        if !envelope
+            .from()
+            .iter()
+            .any(|a| reply::is_own_address(own, a))
+        {
+            continue;
+        }
+        for address in envelope
+            .to()
+            .iter()
+            .chain(envelope.cc().iter())
+            .chain(envelope.bcc().iter())
+        {
+            let email = address.get_email();
+            if !email.is_empty() {
+                known.insert(email.to_lowercase());
+            }
+        }
+    }
+}
+
+/// Returns the warnings about the sender of `envelope`. `known` is the set
+/// of lowercased addresses the user has sent mail to; the user's own
+/// addresses in `own` are always trusted.
+pub fn sender_warnings(
+    envelope: &Envelope,
+    known: Option<&HashSet<String>>,
+    own: &[Address],
+    check_domains: bool,
+) -> Vec<SenderWarning> {
+    let mut ret = vec![];
+    let from = match envelope.from().first() {
+        Some(from) if !reply::is_own_address(own, from) => from,
+        _ => return ret,
+    };
+    if let Some(known) = known {
+        let email = from.get_email();
+        if !email.is_empty() && !known.contains(&email.to_lowercase()) {
+            ret.push(SenderWarning::FirstTime(email));
+        }
+    }
+    if check_domains {
+        let from_domain = match from.get_fqdn() {
+            Some(d) => d,
+            None => return ret,
+        };
+        let reply_to = reply::header_addresses(envelope, "Reply-To")
+            .iter()
+            .filter_map(Address::get_fqdn)
+            .find(|d| !domains_match(&from_domain, d));
+        let return_path = envelope
+            .other_headers()
+            .get("Return-Path")
+            .and_then(return_path_domain)
+            .filter(|d| !domains_match(&from_domain, d));
+        for (header, other) in [("Reply-To", reply_to), ("Return-Path", return_path)] {
+            if let Some(other) = other {
+                ret.push(SenderWarning::DomainMismatch {
+                    header,
+                    from: from_domain.clone(),
+                    other,
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// How long after unsubscribing mail from the sender is still expected, while
+/// the unsubscription is processed. [RFC8058](https://tools.ietf.org/html/rfc8058)
+/// gives senders two days.
+pub const UNSUBSCRIBE_GRACE_PERIOD: UnixTimestamp = 2 * 24 * 60 * 60;
+
+/// Returns a warning if `envelope` was sent more than
+/// [`UNSUBSCRIBE_GRACE_PERIOD`] after the user unsubscribed from its sender.
+/// `unsubscribed` maps lowercased addresses to the time of unsubscribing.
+pub fn unsubscribed_warning(
+    envelope: &Envelope,
+    unsubscribed: &BTreeMap<String, UnixTimestamp>,
+) -> Option<SenderWarning> {
+    let email = envelope.from().first()?.get_email().to_lowercase();
+    let since = *unsubscribed.get(&email)?;
+    if envelope.timestamp > since + UNSUBSCRIBE_GRACE_PERIOD {
+        Some(SenderWarning::StillMailing(email, since))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sender_warnings() {
+        assert!(domains_match("example.com", "EXAMPLE.com"));
+        assert!(domains_match("mail.example.com", "example.com"));
+        assert!(!domains_match("badexample.com", "example.com"));
+
+        let own = vec![Address::new(None, "me@example.com".to_string())];
+        let mut known = HashSet::new();
+        let sent = Envelope::from_bytes(
+            b"From: me@example.com\nTo: Alice <Alice@example.org>\nCc: bob@example.net\nSubject: \
+              hi\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        learn_known_senders(&mut known, &own, std::iter::once(&sent));
+        assert!(known.contains("alice@example.org"));
+        assert!(known.contains("bob@example.net"));
+
+        let envelope = Envelope::from_bytes(
+            b"From: alice@example.org\nTo: me@example.com\nReply-To: alice@lists.example.org\n\
+              Subject: hi\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        assert!(sender_warnings(&envelope, Some(&known), &own, true).is_empty());
+
+        let envelope = Envelope::from_bytes(
+            b"Return-Path: <bounce@phish.example>\nFrom: ceo@example.org\nTo: \
+              me@example.com\nReply-To: ceo@phish.example\nSubject: urgent\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            sender_warnings(&envelope, Some(&known), &own, true),
+            vec![
+                SenderWarning::FirstTime("ceo@example.org".to_string()),
+                SenderWarning::DomainMismatch {
+                    header: "Reply-To",
+                    from: "example.org".to_string(),
+                    other: "phish.example".to_string(),
+                },
+                SenderWarning::DomainMismatch {
+                    header: "Return-Path",
+                    from: "example.org".to_string(),
+                    other: "phish.example".to_string(),
+                },
+            ]
+        );
+        assert!(sender_warnings(&envelope, None, &own, false).is_empty());
+    }
+
+    #[test]
+    fn test_unsubscribed_warning() {
+        let envelope = Envelope::from_bytes(
+            b"From: News <News@example.com>\nDate: Thu, 15 Oct 2026 10:00:00 +0000\nSubject: \
+              deals\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        let mut unsubscribed = BTreeMap::new();
+        assert_eq!(unsubscribed_warning(&envelope, &unsubscribed), None);
+        unsubscribed.insert("news@example.com".to_string(), envelope.timestamp - 3600);
+        assert_eq!(unsubscribed_warning(&envelope, &unsubscribed), None);
+        let since = envelope.timestamp - 3 * 24 * 60 * 60;
+        unsubscribed.insert("news@example.com".to_string(), since);
+        assert_eq!(
+            unsubscribed_warning(&envelope, &unsubscribed),
+            Some(SenderWarning::StillMailing(
+                "news@example.com".to_string(),
+                since
+            ))
+        );
+    }
+}
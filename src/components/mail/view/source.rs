@@ -0,0 +1,441 @@
+/*
+ * meli
+ *
+ * Copyright 2017-2018 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Formatting and highlighting of raw e-mail source.
+
+use super::*;
+
+/// Prefix of lines inserted by the source view that are not part of the
+/// message itself, such as hex dumps of binary parts.
+pub const SOURCE_ANNOTATION_PREFIX: &str = "┆ ";
+
+/// Header values longer than this many characters are shortened when
+/// folding.
+const FOLD_WIDTH: usize = 120;
+
+/// Only this many bytes of each binary part are shown as a hex dump.
+const HEXDUMP_LIMIT: usize = 4096;
+
+/// Formats raw message `bytes` for the source view.
+///
+/// Returns the text to display and the MIME boundaries found in the
+/// message, to be passed to [`SourceHighlighter::new`]. If `fold` is
+/// `false` the text is the message as is. Otherwise multi-line headers are
+/// unfolded into a single line and shortened if they are too long, and the
+/// base64 content of binary parts is replaced with a hex dump of its
+/// decoded bytes.
+pub fn format_source(bytes: &[u8], fold: bool) -> (String, Vec<String>) {
+    let text = String::from_utf8_lossy(bytes);
+    let mut ret = String::with_capacity(if fold { text.len() } else { 0 });
+    let mut boundaries: Vec<String> = vec![];
+    let mut in_headers = true;
+    /* Lines of the header being read. */
+    let mut header: Vec<&str> = vec![];
+    /* Raw lines of the current part, kept while its body is binary. */
+    let mut part: Vec<&str> = vec![];
+    let mut content_type = String::new();
+    let mut transfer_encoding = String::new();
+    let mut binary = false;
+
+    let flush_header = |header: &mut Vec<&str>,
+                        ret: &mut String,
+                        boundaries: &mut Vec<String>,
+                        content_type: &mut String,
+                        transfer_encoding: &mut String| {
+        if header.is_empty() {
+            return;
+        }
+        let unfolded = header
+            .iter()
+            .map(|l| l.trim())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if let Some((name, value)) = unfolded.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-type") {
+                *content_type = value.trim().to_ascii_lowercase();
+                if let Some(b) = find_boundary(value) {
+                    boundaries.push(b);
+                }
+            } else if name.eq_ignore_ascii_case("content-transfer-encoding") {
+                *transfer_encoding = value.trim().to_ascii_lowercase();
+            }
+        }
+        if fold {
+            if header.len() == 1 && header[0].chars().count() <= FOLD_WIDTH {
+                ret.push_str(header[0]);
+            } else {
+                let mut unfolded = unfolded.as_str();
+                if unfolded.chars().count() > FOLD_WIDTH {
+                    unfolded = &unfolded[..unfolded
+                        .char_indices()
+                        .nth(FOLD_WIDTH)
+                        .map(|(i, _)| i)
+                        .unwrap_or(unfolded.len())];
+                    ret.push_str(unfolded);
+                    ret.push('…');
+                } else {
+                    ret.push_str(unfolded);
+                }
+            }
+            ret.push('\n');
+        }
+        header.clear();
+    };
+    let flush_part = |part: &mut Vec<&str>, ret: &mut String| {
+        if part.is_empty() {
+            return;
+        }
+        let raw = part.join("\n");
+        let decoded = AttachmentBuilder::new(raw.as_bytes())
+            .build()
+            .decode(Default::default());
+        ret.push_str(&hexdump(&decoded));
+        part.clear();
+    };
+
+    for line in text.lines() {
+        if in_headers {
+            if fold {
+                part.push(line);
+            }
+            if line.starts_with([' ', '\t']) && !header.is_empty() {
+                header.push(line);
+                continue;
+            }
+            flush_header(
+                &mut header,
+                &mut ret,
+                &mut boundaries,
+                &mut content_type,
+                &mut transfer_encoding,
+            );
+            if line.is_empty() {
+                in_headers = false;
+                binary = transfer_encoding == "base64"
+                    && !content_type.is_empty()
+                    && !["text/", "multipart/", "message/"]
+                        .iter()
+                        .any(|p| content_type.starts_with(p));
+                if !binary {
+                    part.clear();
+                }
+                if fold {
+                    ret.push('\n');
+                }
+                continue;
+            }
+            header.push(line);
+            continue;
+        }
+        if let Some(delimiter) = line.strip_prefix("--") {
+            let delimiter = delimiter.trim_end();
+            let closing = boundaries.iter().any(|b| delimiter == format!("{}--", b));
+            if closing || boundaries.iter().any(|b| delimiter == b) {
+                if fold && binary {
+                    flush_part(&mut part, &mut ret);
+                }
+                binary = false;
+                part.clear();
+                if !closing {
+                    in_headers = true;
+                    content_type.clear();
+                    transfer_encoding.clear();
+                }
+                if fold {
+                    ret.push_str(line);
+                    ret.push('\n');
+                }
+                continue;
+            }
+        }
+        if binary {
+            if fold {
+                part.push(line);
+            }
+        } else if fold {
+            ret.push_str(line);
+            ret.push('\n');
+        }
+    }
+    if fold {
+        flush_header(
+            &mut header,
+            &mut ret,
+            &mut boundaries,
+            &mut content_type,
+            &mut transfer_encoding,
+        );
+        if binary {
+            flush_part(&mut part, &mut ret);
+        }
+        (ret, boundaries)
+    } else {
+        (text.into_owned(), boundaries)
+    }
+}
+
+/// Extracts the value of the `boundary` parameter from a `Content-Type`
+/// header value.
+fn find_boundary(value: &str) -> Option<String> {
+    let idx = value.to_ascii_lowercase().find("boundary=")?;
+    let value = &value[idx + "boundary=".len()..];
+    let ret = if let Some(value) = value.strip_prefix('"') {
+        value.split('"').next()?
+    } else {
+        value
+            .split(|c: char| c == ';' || c.is_whitespace())
+            .next()?
+    };
+    if ret.is_empty() {
+        None
+    } else {
+        Some(ret.to_string())
+    }
+}
+
+/// Formats `bytes` as a hex dump, prefixing each line with
+/// [`SOURCE_ANNOTATION_PREFIX`].
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut ret = String::new();
+    ret.push_str(SOURCE_ANNOTATION_PREFIX);
+    if bytes.len() > HEXDUMP_LIMIT {
+        ret.push_str(&format!(
+            "binary part, {} bytes, showing the first {}:\n",
+            bytes.len(),
+            HEXDUMP_LIMIT
+        ));
+    } else {
+        ret.push_str(&format!("binary part, {} bytes:\n", bytes.len()));
+    }
+    for (i, chunk) in bytes.chunks(16).take(HEXDUMP_LIMIT / 16).enumerate() {
+        ret.push_str(SOURCE_ANNOTATION_PREFIX);
+        ret.push_str(&format!("{:08x} ", i * 16));
+        for j in 0..16 {
+            if j % 8 == 0 {
+                ret.push(' ');
+            }
+            if let Some(b) = chunk.get(j) {
+                ret.push_str(&format!("{:02x} ", b));
+            } else {
+                ret.push_str("   ");
+            }
+        }
+        ret.push_str(" |");
+        ret.extend(chunk.iter().map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        }));
+        ret.push_str("|\n");
+    }
+    ret
+}
+
+/// Kind of a displayed line of e-mail source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SourceLine {
+    /// Start of a header; the value is the length in bytes of the header
+    /// name including the colon.
+    HeaderName(usize),
+    /// Header value continuation.
+    HeaderValue,
+    /// MIME boundary delimiter.
+    Boundary,
+    /// Message or part body.
+    Body,
+    /// Line inserted by the source view, see [`SOURCE_ANNOTATION_PREFIX`].
+    Annotation,
+}
+
+/// Classifies the lines of e-mail source, in order, so that the pager can
+/// highlight them.
+///
+/// Lines wrapped by the pager (starting with `⤷`) have the same kind as the
+/// line they continue.
+#[derive(Debug, Clone)]
+pub struct SourceHighlighter {
+    boundaries: Vec<String>,
+    in_headers: bool,
+    prev: SourceLine,
+}
+
+impl SourceHighlighter {
+    pub fn new(boundaries: Vec<String>) -> Self {
+        SourceHighlighter {
+            boundaries,
+            in_headers: true,
+            prev: SourceLine::Body,
+        }
+    }
+
+    /// Restarts classification from the first line.
+    pub fn reset(&mut self) {
+        self.in_headers = true;
+        self.prev = SourceLine::Body;
+    }
+
+    pub fn classify(&mut self, line: &str) -> SourceLine {
+        let ret = self.classify_inner(line);
+        self.prev = ret;
+        ret
+    }
+
+    fn classify_inner(&mut self, line: &str) -> SourceLine {
+        if line.starts_with('⤷') {
+            return match self.prev {
+                SourceLine::HeaderName(_) => SourceLine::HeaderValue,
+                other => other,
+            };
+        }
+        if line.starts_with(SOURCE_ANNOTATION_PREFIX) {
+            return SourceLine::Annotation;
+        }
+        let line = line.trim_end_matches('\r');
+        if self.in_headers {
+            if line.is_empty() {
+                self.in_headers = false;
+                return SourceLine::Body;
+            }
+            if line.starts_with([' ', '\t']) {
+                return SourceLine::HeaderValue;
+            }
+            return match line.find(':') {
+                Some(idx) if idx > 0 && line.as_bytes()[..idx].iter().all(u8::is_ascii_graphic) => {
+                    SourceLine::HeaderName(idx + 1)
+                }
+                _ => SourceLine::HeaderValue,
+            };
+        }
+        if let Some(delimiter) = line.strip_prefix("--") {
+            let delimiter = delimiter.trim_end();
+            if self.boundaries.iter().any(|b| delimiter == b) {
+                self.in_headers = true;
+                return SourceLine::Boundary;
+            }
+            if self
+                .boundaries
+                .iter()
+                .any(|b| delimiter == format!("{}--", b))
+            {
+                return SourceLine::Boundary;
+            }
+        }
+        SourceLine::Body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "From: a@example.com\r
+Content-Type: multipart/mixed;\r
+ boundary=\"XYZ\"\r
+\r
+preamble\r
+--XYZ\r
+Content-Type: text/plain\r
+\r
+hello\r
+--XYZ\r
+Content-Type: application/octet-stream\r
+Content-Transfer-Encoding: base64\r
+\r
+AAEC\r
+--XYZ--\r
+";
+
+    #[test]
+    fn test_format_source() {
+        let (text, boundaries) = format_source(MESSAGE.as_bytes(), false);
+        assert_eq!(text, MESSAGE);
+        assert_eq!(boundaries, vec!["XYZ".to_string()]);
+
+        let (text, boundaries) = format_source(MESSAGE.as_bytes(), true);
+        assert_eq!(boundaries, vec!["XYZ".to_string()]);
+        assert_eq!(
+            text.lines().collect::<Vec<&str>>(),
+            vec![
+                "From: a@example.com",
+                "Content-Type: multipart/mixed; boundary=\"XYZ\"",
+                "",
+                "preamble",
+                "--XYZ",
+                "Content-Type: text/plain",
+                "",
+                "hello",
+                "--XYZ",
+                "Content-Type: application/octet-stream",
+                "Content-Transfer-Encoding: base64",
+                "",
+                "┆ binary part, 3 bytes:",
+                "┆ 00000000  00 01 02                                          |...|",
+                "--XYZ--",
+            ]
+        );
+
+        let long = format!("Subject: {}\n\nbody\n", "a".repeat(200));
+        let (text, _) = format_source(long.as_bytes(), true);
+        let subject = text.lines().next().unwrap();
+        assert!(subject.ends_with('…'));
+        assert_eq!(subject.chars().count(), FOLD_WIDTH + 1);
+    }
+
+    #[test]
+    fn test_source_highlighter() {
+        let (text, boundaries) = format_source(MESSAGE.as_bytes(), true);
+        let mut highlighter = SourceHighlighter::new(boundaries);
+        let kinds = text
+            .lines()
+            .map(|l| highlighter.classify(l))
+            .collect::<Vec<SourceLine>>();
+        assert_eq!(
+            kinds,
+            vec![
+                SourceLine::HeaderName(5),
+                SourceLine::HeaderName(13),
+                SourceLine::Body,
+                SourceLine::Body,
+                SourceLine::Boundary,
+                SourceLine::HeaderName(13),
+                SourceLine::Body,
+                SourceLine::Body,
+                SourceLine::Boundary,
+                SourceLine::HeaderName(13),
+                SourceLine::HeaderName(26),
+                SourceLine::Body,
+                SourceLine::Annotation,
+                SourceLine::Annotation,
+                SourceLine::Boundary,
+            ]
+        );
+        /* Lines wrapped by the pager continue the previous line. */
+        highlighter.reset();
+        assert_eq!(
+            highlighter.classify("Subject: a"),
+            SourceLine::HeaderName(8)
+        );
+        assert_eq!(highlighter.classify("⤷wrapped"), SourceLine::HeaderValue);
+    }
+}
@@ -19,7 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::cmp;
+use std::{cmp, collections::HashSet};
 
 use super::*;
 use crate::components::PageMovement;
@@ -37,6 +37,25 @@ struct ThreadEntry {
     timestamp: UnixTimestamp,
 }
 
+/// A search across the headers and bodies of all entries of a thread.
+#[derive(Debug)]
+struct ThreadSearch {
+    pattern: String,
+    /// Entries whose headers or body contain `pattern`.
+    matches: HashSet<EnvelopeHash>,
+    handle: Option<JoinHandle<Result<Vec<EnvelopeHash>>>>,
+}
+
+impl Clone for ThreadSearch {
+    fn clone(&self) -> Self {
+        ThreadSearch {
+            pattern: self.pattern.clone(),
+            matches: self.matches.clone(),
+            handle: None,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ThreadView {
     new_cursor_pos: usize,
@@ -53,10 +72,13 @@ pub struct ThreadView {
     visible_entries: Vec<Vec<usize>>,
     indentation_colors: [ThemeAttribute; 6],
     use_color: bool,
+    search: Option<ThreadSearch>,
 
     movement: Option<PageMovement>,
     dirty: bool,
     content: CellBuffer,
+    /// Where the thread entries were last drawn, for mouse events.
+    list_area: Option<Area>,
     id: ComponentId,
 }
 
@@ -101,6 +123,15 @@ impl ThreadView {
         view
     }
 
+    /// Shows only the entries of the thread, without the message view, unless
+    /// the thread has a single message.
+    pub fn show_thread_only(&mut self) {
+        if self.entries.len() > 1 {
+            self.show_mailview = false;
+            self.set_dirty(true);
+        }
+    }
+
     pub fn update(&mut self, context: &Context) {
         if self.entries.is_empty() {
             return;
@@ -268,6 +299,13 @@ impl ThreadView {
         }
         let theme_default = crate::conf::value(context, "theme_default");
         let highlight_theme = crate::conf::value(context, "highlight");
+        let search_theme = crate::conf::value(context, "pager.highlight_search");
+        let is_match = |e: &ThreadEntry| {
+            self.search
+                .as_ref()
+                .map(|s| s.matches.contains(&e.msg_hash))
+                .unwrap_or(false)
+        };
         let mut content = CellBuffer::new_with_context(width, height, None, context);
         if self.reversed {
             for (y, e) in self.entries.iter().rev().enumerate() {
@@ -315,6 +353,13 @@ impl ThreadView {
                     let area = ((index, 2 * y), (width - 2, 2 * y));
                     change_colors(&mut content, area, highlight_theme.fg, theme_default.bg);
                 }
+                if is_match(e) {
+                    let area = (
+                        (e.index.0 * 4 + 1, 2 * y),
+                        (e.index.0 * 4 + e.heading.grapheme_width(), 2 * y),
+                    );
+                    change_colors(&mut content, area, search_theme.fg, search_theme.bg);
+                }
                 set_and_join_box(&mut content, (e.index.0 * 4, 2 * y), BoxBoundary::Vertical);
                 set_and_join_box(
                     &mut content,
@@ -387,6 +432,13 @@ impl ThreadView {
                     let area = ((index, 2 * y), (width - 2, 2 * y));
                     change_colors(&mut content, area, highlight_theme.fg, theme_default.bg);
                 }
+                if is_match(e) {
+                    let area = (
+                        (e.index.0 * 4 + 1, 2 * y),
+                        (e.index.0 * 4 + e.heading.grapheme_width(), 2 * y),
+                    );
+                    change_colors(&mut content, area, search_theme.fg, search_theme.bg);
+                }
                 set_and_join_box(&mut content, (e.index.0 * 4, 2 * y), BoxBoundary::Vertical);
                 set_and_join_box(
                     &mut content,
@@ -452,6 +504,7 @@ impl ThreadView {
             context.dirty_areas.push_back(area);
             return;
         }
+        self.list_area = Some(area);
         if let Some(mvm) = self.movement.take() {
             match mvm {
                 PageMovement::Up(amount) => {
@@ -947,6 +1000,149 @@ impl ThreadView {
         let visibles: Vec<&usize> = self.visible_entries.iter().flat_map(|v| v.iter()).collect();
         *visibles[self.new_cursor_pos]
     }
+
+    /// Returns the cursor position of the entry drawn at screen position
+    /// `pos`, if any.
+    fn entry_at(&self, pos: Pos) -> Option<usize> {
+        let area = self.list_area?;
+        if !area_contains(area, pos) {
+            return None;
+        }
+        let rows = (get_y(bottom_right!(area)) - get_y(upper_left!(area))).wrapping_div(2);
+        /* Every entry takes up two lines. */
+        let row = (get_y(pos) - get_y(upper_left!(area))) / 2;
+        if row >= rows {
+            return None;
+        }
+        let idx = (self.cursor_pos / rows) * rows + row;
+        if idx < self.visible_entries.iter().flat_map(|v| v.iter()).count() {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Searches the headers and bodies of all entries for `pattern`, ignoring
+    /// case. The matches are highlighted when the search job finishes.
+    fn search(&mut self, pattern: &str, context: &mut Context) {
+        if let Some(handle) = self.search.take().and_then(|s| s.handle) {
+            handle.cancel();
+        }
+        let account = &mut context.accounts[&self.coordinates.0];
+        let futures = match self
+            .entries
+            .iter()
+            .map(|e| {
+                account
+                    .operation(e.msg_hash)
+                    .and_then(|mut op| op.as_bytes())
+                    .map(|fut| (e.msg_hash, fut))
+            })
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(futures) => futures,
+            Err(err) => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                        "Could not search thread: {}",
+                        err
+                    ))));
+                return;
+            }
+        };
+        let needle = pattern.to_lowercase();
+        let job = async move {
+            let mut ret = vec![];
+            for (env_hash, fut) in futures {
+                let mail = Mail::new(fut.await?, None)?;
+                if mail
+                    .other_headers()
+                    .values()
+                    .any(|v| v.to_lowercase().contains(&needle))
+                    || mail.body().text().to_lowercase().contains(&needle)
+                {
+                    ret.push(env_hash);
+                }
+            }
+            Ok(ret)
+        };
+        let handle = context.job_executor.spawn_specialized(job);
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        self.search = Some(ThreadSearch {
+            pattern: pattern.to_string(),
+            matches: HashSet::default(),
+            handle: Some(handle),
+        });
+    }
+
+    /// Moves the cursor to the next (or previous) visible entry that matches
+    /// the current search, wrapping around, and opens it.
+    fn go_to_match(&mut self, forward: bool) -> bool {
+        let search = match self.search {
+            Some(ref s) if !s.matches.is_empty() => s,
+            _ => return false,
+        };
+        let positions: Vec<usize> = self
+            .visible_entries
+            .iter()
+            .flat_map(|v| v.iter())
+            .enumerate()
+            .filter(|(_, idx)| search.matches.contains(&self.entries[**idx].msg_hash))
+            .map(|(pos, _)| pos)
+            .collect();
+        let pos = if forward {
+            positions
+                .iter()
+                .find(|p| **p > self.new_cursor_pos)
+                .or_else(|| positions.first())
+        } else {
+            positions
+                .iter()
+                .rev()
+                .find(|p| **p < self.new_cursor_pos)
+                .or_else(|| positions.last())
+        };
+        match pos {
+            Some(&pos) => {
+                self.new_cursor_pos = pos;
+                self.new_expanded_pos = self.current_pos();
+                self.show_mailview = true;
+                self.set_dirty(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redraws the entries, e.g. after the search matches have changed,
+    /// keeping collapsed subtrees and the opened entry as they were.
+    fn refresh_entries(&mut self, context: &Context) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let pending_expand = self.new_expanded_pos != self.expanded_pos;
+        let expanded_hash = self.entries[self.new_expanded_pos].index.1;
+        let hidden: HashSet<EnvelopeHash> = self
+            .entries
+            .iter()
+            .filter(|e| e.hidden)
+            .map(|e| e.msg_hash)
+            .collect();
+        self.initiate(Some(expanded_hash), context);
+        if !pending_expand {
+            self.expanded_pos = self.new_expanded_pos;
+        }
+        if !hidden.is_empty() {
+            for e in self.entries.iter_mut() {
+                e.hidden = hidden.contains(&e.msg_hash);
+            }
+            self.recalc_visible_entries();
+        }
+        self.dirty = true;
+    }
 }
 
 impl fmt::Display for ThreadView {
@@ -996,6 +1192,16 @@ impl Component for ThreadView {
             return true;
         }
 
+        if let UIEvent::Action(Listing(Search(ref pattern))) = event {
+            /* Search the whole thread if its entries are visible, otherwise let
+             * the mail view search the opened entry. */
+            if self.show_thread && self.entries.len() > 1 {
+                let pattern = pattern.to_string();
+                self.search(&pattern, context);
+                return true;
+            }
+        }
+
         if self.show_mailview && self.mailview.process_event(event, context) {
             return true;
         }
@@ -1021,6 +1227,43 @@ impl Component for ThreadView {
                 }
                 return true;
             }
+            UIEvent::Mouse(ref mev)
+                if self
+                    .list_area
+                    .map(|area| area_contains(area, mouse_pos(mev)))
+                    .unwrap_or(false) =>
+            {
+                match *mev {
+                    MouseEvent::Press(MouseButton::WheelUp, _, _) if self.cursor_pos > 0 => {
+                        self.new_cursor_pos = self.new_cursor_pos.saturating_sub(1);
+                        self.dirty = true;
+                    }
+                    MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+                        let height = self.visible_entries.iter().flat_map(|v| v.iter()).count();
+                        if height > 0 && self.new_cursor_pos + 1 < height {
+                            self.new_cursor_pos += 1;
+                            self.dirty = true;
+                        }
+                    }
+                    MouseEvent::Press(MouseButton::Left, _, _) => {
+                        match self.entry_at(mouse_pos(mev)) {
+                            /* Clicking the entry under the cursor opens it. */
+                            Some(idx) if idx == self.cursor_pos && self.entries.len() > 1 => {
+                                self.new_expanded_pos = self.current_pos();
+                                self.show_mailview = true;
+                                self.set_dirty(true);
+                            }
+                            Some(idx) => {
+                                self.new_cursor_pos = idx;
+                                self.dirty = true;
+                            }
+                            None => {}
+                        }
+                    }
+                    _ => {}
+                }
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Shortcuts::THREAD_VIEW]["prev_page"]) =>
             {
@@ -1061,6 +1304,7 @@ impl Component for ThreadView {
                 if shortcut!(key == shortcuts[Shortcuts::THREAD_VIEW]["toggle_threadview"]) =>
             {
                 self.show_thread = !self.show_thread;
+                self.list_area = None;
                 self.set_dirty(true);
                 return true;
             }
@@ -1105,6 +1349,86 @@ impl Component for ThreadView {
                 self.dirty = true;
                 return true;
             }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Shortcuts::THREAD_VIEW]["search"]) =>
+            {
+                context
+                    .replies
+                    .push_back(UIEvent::CmdInput(Key::Paste("search ".to_string())));
+                context
+                    .replies
+                    .push_back(UIEvent::ChangeMode(UIMode::Command));
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if self.search.is_some()
+                    && shortcut!(
+                        key == shortcuts[Shortcuts::THREAD_VIEW]["next_search_result"]
+                    ) =>
+            {
+                self.go_to_match(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if self.search.is_some()
+                    && shortcut!(
+                        key == shortcuts[Shortcuts::THREAD_VIEW]["previous_search_result"]
+                    ) =>
+            {
+                self.go_to_match(false);
+                return true;
+            }
+            UIEvent::Input(Key::Esc) if self.search.is_some() => {
+                if let Some(handle) = self.search.take().and_then(|s| s.handle) {
+                    handle.cancel();
+                }
+                self.refresh_entries(context);
+                return true;
+            }
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self
+                    .search
+                    .as_ref()
+                    .and_then(|s| s.handle.as_ref())
+                    .map(|h| h.job_id == *job_id)
+                    .unwrap_or(false) =>
+            {
+                let search = self.search.as_mut().unwrap();
+                let result = search.handle.take().unwrap().chan.try_recv();
+                match result {
+                    Err(_) => { /* Job was canceled */ }
+                    Ok(None) => { /* something happened, perhaps a worker thread panicked */ }
+                    Ok(Some(Ok(matches))) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(if matches.is_empty() {
+                                format!("No entries match `{}`.", search.pattern)
+                            } else {
+                                format!(
+                                    "{} of {} entries match `{}`.",
+                                    matches.len(),
+                                    self.entries.len(),
+                                    search.pattern
+                                )
+                            }),
+                        ));
+                        search.matches = matches.into_iter().collect();
+                        self.refresh_entries(context);
+                        let current = self.entries[self.current_pos()].msg_hash;
+                        if !self.search.as_ref().unwrap().matches.contains(&current) {
+                            self.go_to_match(true);
+                        }
+                    }
+                    Ok(Some(Err(err))) => {
+                        context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "Could not search thread: {}",
+                                err
+                            )),
+                        ));
+                    }
+                }
+                return true;
+            }
             UIEvent::Resize => {
                 self.set_dirty(true);
             }
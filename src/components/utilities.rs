@@ -37,6 +37,9 @@ pub use self::layouts::*;
 mod dialogs;
 pub use self::dialogs::*;
 
+mod file_browser;
+pub use self::file_browser::*;
+
 mod tables;
 use std::collections::HashSet;
 
@@ -63,6 +66,7 @@ pub struct StatusBar {
     display_buffer: String,
     mode: UIMode,
     mouse: bool,
+    dnd: bool,
     height: usize,
     dirty: bool,
     id: ComponentId,
@@ -108,6 +112,7 @@ impl StatusBar {
             dirty: true,
             mode: UIMode::Normal,
             mouse: context.settings.terminal.use_mouse.is_true(),
+            dnd: false,
             height: 1,
             id: ComponentId::new_v4(),
             auto_complete: AutoComplete::new(Vec::new()),
@@ -206,7 +211,7 @@ impl StatusBar {
 
     fn update_status(&mut self, context: &Context) {
         self.status = format!(
-            "{} {}| {}{}{}",
+            "{} {}{}| {}{}{}",
             self.mode,
             if self.mouse {
                 context
@@ -218,6 +223,7 @@ impl StatusBar {
             } else {
                 ""
             },
+            if self.dnd { "DND " } else { "" },
             &self.status_message,
             if !self.substatus_message.is_empty() {
                 " | "
@@ -251,8 +257,70 @@ impl StatusBar {
             crate::conf::value(context, "status.command_bar").fg,
             crate::conf::value(context, "status.command_bar").bg,
         );
+        /* Show the signature of the command being typed at the right end of the
+         * bar. */
+        let hint = crate::command::command_signatures(self.ex_buffer.as_str())
+            .into_iter()
+            .map(|(signature, _)| signature)
+            .collect::<Vec<String>>()
+            .join("  ");
+        let hint_width = hint.grapheme_width();
+        let buffer_width = self.ex_buffer.as_str().grapheme_width();
+        if !hint.is_empty() && buffer_width + hint_width + 2 < width!(area) {
+            let hint_attribute = crate::conf::value(context, "status.history.hints");
+            write_string_to_grid(
+                &hint,
+                grid,
+                hint_attribute.fg,
+                hint_attribute.bg,
+                hint_attribute.attrs,
+                (
+                    (get_x(bottom_right!(area)) + 1 - hint_width, y),
+                    bottom_right!(area),
+                ),
+                None,
+            );
+        }
         context.dirty_areas.push_back(area);
     }
+
+    /// Account names, mailbox paths and tags that command arguments are
+    /// completed with.
+    fn completion_values(context: &Context) -> crate::command::CompletionValues {
+        let mut tags = context
+            .accounts
+            .values()
+            .flat_map(|account| {
+                account
+                    .collection
+                    .tag_index
+                    .read()
+                    .unwrap()
+                    .values()
+                    .cloned()
+                    .collect::<Vec<String>>()
+            })
+            .collect::<Vec<String>>();
+        tags.sort();
+        tags.dedup();
+        crate::command::CompletionValues {
+            accounts: context
+                .accounts
+                .values()
+                .map(|account| {
+                    (
+                        account.name().to_string(),
+                        account
+                            .mailbox_entries
+                            .values()
+                            .map(|entry| entry.ref_mailbox.path().to_string())
+                            .collect::<Vec<String>>(),
+                    )
+                })
+                .collect(),
+            tags,
+        }
+    }
 }
 
 impl Component for StatusBar {
@@ -319,8 +387,10 @@ impl Component for StatusBar {
                         }
                     })
                     .collect();
-                let command_completion_suggestions =
-                    crate::command::command_completion_suggestions(self.ex_buffer.as_str());
+                let command_completion_suggestions = crate::command::command_completion_suggestions(
+                    self.ex_buffer.as_str(),
+                    &Self::completion_values(context),
+                );
 
                 suggestions.extend(command_completion_suggestions.iter().filter_map(|e| {
                     if !unique_suggestions.contains(e.as_str()) {
@@ -330,6 +400,22 @@ impl Component for StatusBar {
                         None
                     }
                 }));
+                /* user-defined commands */
+                suggestions.extend(
+                    context
+                        .settings
+                        .commands
+                        .aliases
+                        .iter()
+                        .filter(|(name, _)| {
+                            name.starts_with(self.ex_buffer.as_str())
+                                && name.as_str() != self.ex_buffer.as_str()
+                        })
+                        .map(|(name, template)| AutoCompleteEntry {
+                            entry: name.clone(),
+                            description: template.clone(),
+                        }),
+                );
                 /*
                 suggestions.extend(crate::command::COMMAND_COMPLETION.iter().filter_map(|e| {
                     if e.0.starts_with(self.ex_buffer.as_str()) {
@@ -561,7 +647,12 @@ impl Component for StatusBar {
                                 .replies
                                 .push_back(UIEvent::Command(self.ex_buffer.as_str().to_string()));
                         }
-                        if parse_command(self.ex_buffer.as_str().as_bytes()).is_ok()
+                        if (parse_command(self.ex_buffer.as_str().as_bytes()).is_ok()
+                            || context
+                                .settings
+                                .commands
+                                .expand(self.ex_buffer.as_str())
+                                .is_some())
                             && self.cmd_history.last().map(String::as_str)
                                 != Some(self.ex_buffer.as_str())
                         {
@@ -734,6 +825,11 @@ impl Component for StatusBar {
                 self.update_status(context);
                 self.dirty = true;
             }
+            UIEvent::StatusEvent(StatusEvent::SetDnd(val)) if self.dnd != *val => {
+                self.dnd = *val;
+                self.update_status(context);
+                self.dirty = true;
+            }
             UIEvent::StatusEvent(StatusEvent::JobCanceled(ref job_id))
             | UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id)) => {
                 self.done_jobs.insert(*job_id);
@@ -802,17 +898,66 @@ impl Component for StatusBar {
     }
 }
 
+/// A shortcut shown in the help overlay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HelpEntry {
+    key: String,
+    name: &'static str,
+    description: String,
+    command: Option<&'static str>,
+}
+
+/// Returns the shortcuts of `maps` grouped by section, leaving out those
+/// whose key, name, description or command don't contain `filter`, and the
+/// sections left empty.
+fn help_entries(maps: &ShortcutMaps, filter: &str) -> Vec<(&'static str, Vec<HelpEntry>)> {
+    let filter = filter.to_lowercase();
+    maps.iter()
+        .map(|(section, shortcuts)| {
+            let entries = shortcuts
+                .iter()
+                .map(|(name, key)| HelpEntry {
+                    key: key.to_string(),
+                    name,
+                    description: Shortcuts::description(section, name)
+                        .map(|d| crate::i18n::tr(d).into_owned())
+                        .unwrap_or_default(),
+                    command: Shortcuts::command(section, name),
+                })
+                .filter(|entry| {
+                    filter.is_empty()
+                        || entry.key.to_lowercase().contains(&filter)
+                        || entry.name.contains(&filter)
+                        || entry.description.to_lowercase().contains(&filter)
+                        || entry
+                            .command
+                            .map(|c| c.to_lowercase().contains(&filter))
+                            .unwrap_or(false)
+                })
+                .collect::<Vec<HelpEntry>>();
+            (*section, entries)
+        })
+        .filter(|(_, entries)| !entries.is_empty())
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct Tabbed {
     pinned: usize,
     children: Vec<Box<dyn Component>>,
     cursor_pos: usize,
+    /// Where the title of each tab was last drawn, for mouse events.
+    tab_areas: Vec<Area>,
 
     show_shortcuts: bool,
     help_screen_cursor: (usize, usize),
     help_content: CellBuffer,
     help_curr_views: ShortcutMaps,
     help_search: Option<SearchPattern>,
+    /// Text that shortcuts must contain to be shown in the help overlay.
+    help_filter: Option<String>,
+    /// Whether key presses edit `help_filter`.
+    help_filter_focused: bool,
     theme_default: ThemeAttribute,
 
     dirty: bool,
@@ -830,10 +975,13 @@ impl Tabbed {
             help_content: CellBuffer::default(),
             help_screen_cursor: (0, 0),
             help_search: None,
+            help_filter: None,
+            help_filter_focused: false,
             theme_default: crate::conf::value(context, "theme_default"),
             pinned,
             children,
             cursor_pos: 0,
+            tab_areas: vec![],
             show_shortcuts: false,
             dirty: true,
             id: ComponentId::new_v4(),
@@ -874,6 +1022,7 @@ impl Tabbed {
                 (set_x(upper_left, x), bottom_right!(area)),
                 None,
             );
+            self.tab_areas.push(((x, y), (x_, y)));
             x = x_ + 1;
             if idx == self.pinned.saturating_sub(1) {
                 x += 2;
@@ -919,6 +1068,24 @@ impl Tabbed {
     pub fn add_component(&mut self, new: Box<dyn Component>) {
         self.children.push(new);
     }
+
+    /// Switches to the tab at position `no`.
+    fn focus_tab(&mut self, no: usize, context: &mut Context) {
+        if no < self.children.len() && self.cursor_pos != no {
+            self.children[self.cursor_pos]
+                .process_event(&mut UIEvent::VisibilityChange(false), context);
+            self.cursor_pos = no;
+            let mut children_maps = self.children[self.cursor_pos].get_shortcuts(context);
+            children_maps.extend(self.get_shortcuts(context));
+            self.help_curr_views = children_maps;
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                    self.children[self.cursor_pos].get_status(context),
+                )));
+            self.set_dirty(true);
+        }
+    }
 }
 
 impl fmt::Display for Tabbed {
@@ -951,6 +1118,7 @@ impl Component for Tabbed {
         /* children should be drawn after the shortcuts/help panel lest they
          * overwrite the panel on the grid. the drawing order is determined
          * by the dirty_areas queue which is LIFO */
+        self.tab_areas.clear();
         if self.children.len() > 1 {
             self.draw_tabs(
                 grid,
@@ -991,7 +1159,7 @@ impl Component for Tabbed {
                 clear_area(grid, dialog_area, self.theme_default);
                 let inner_area = create_box(grid, dialog_area);
                 let (x, y) = write_string_to_grid(
-                    "shortcuts",
+                    &tr!("shortcuts"),
                     grid,
                     self.theme_default.fg,
                     self.theme_default.bg,
@@ -1003,7 +1171,7 @@ impl Component for Tabbed {
                     None,
                 );
                 write_string_to_grid(
-                    "Press ? to close",
+                    &tr!("Press ? to close"),
                     grid,
                     self.theme_default.fg,
                     self.theme_default.bg,
@@ -1077,39 +1245,42 @@ impl Component for Tabbed {
                 self.dirty = false;
                 return;
             }
+            let sections = help_entries(&children_maps, self.help_filter.as_deref().unwrap_or(""));
+            let filter_line = match self.help_filter {
+                Some(ref filter) if self.help_filter_focused => tr!("filter: {}▁", filter),
+                Some(ref filter) => tr!("filter: {} (Esc to clear)", filter),
+                None => tr!("press / to filter, use COMMAND \"search\" to highlight"),
+            };
             let mut max_length = 6;
             let mut max_width =
-                "Press ? to close, use COMMAND \"search\" to find shortcuts".len() + 3;
-
-            let mut max_first_column_width = 3;
-
-            for (desc, shortcuts) in children_maps.iter() {
-                max_length += shortcuts.len() + 3;
-                max_width = std::cmp::max(
-                    max_width,
-                    std::cmp::max(
-                        desc.len(),
-                        shortcuts
-                            .values()
-                            .map(|v| v.to_string().len() + 5)
-                            .max()
-                            .unwrap_or(0),
-                    ),
-                );
-                max_first_column_width = std::cmp::max(
-                    max_first_column_width,
-                    shortcuts
-                        .values()
-                        .map(|v| v.to_string().len() + 5)
-                        .max()
-                        .unwrap_or(0),
-                );
+                tr!("Press ? to close").grapheme_width() + filter_line.grapheme_width() + 5;
+            let mut key_column_width = 3;
+            let mut name_column_width = 0;
+            for (_, entries) in sections.iter() {
+                max_length += entries.len() + 3;
+                for entry in entries {
+                    key_column_width = std::cmp::max(key_column_width, entry.key.len() + 5);
+                    name_column_width = std::cmp::max(name_column_width, entry.name.len());
+                }
+            }
+            for (section, entries) in sections.iter() {
+                max_width = std::cmp::max(max_width, crate::i18n::tr(section).grapheme_width() + 4);
+                for entry in entries {
+                    max_width = std::cmp::max(
+                        max_width,
+                        key_column_width
+                            + name_column_width
+                            + entry.description.grapheme_width()
+                            + entry.command.map(|c| c.len() + 3).unwrap_or(0)
+                            + 8,
+                    );
+                }
             }
             self.help_content =
                 CellBuffer::new_with_context(max_width, max_length + 2, None, context);
             self.help_content.set_growable(true);
             write_string_to_grid(
-                "use COMMAND \"search\" to find shortcuts",
+                &filter_line,
                 &mut self.help_content,
                 self.theme_default.fg,
                 self.theme_default.bg,
@@ -1118,24 +1289,31 @@ impl Component for Tabbed {
                 None,
             );
             let mut idx = 2;
-            for (desc, shortcuts) in children_maps.iter() {
+            if sections.is_empty() {
+                write_string_to_grid(
+                    &tr!("No shortcuts match."),
+                    &mut self.help_content,
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs | Attr::ITALICS,
+                    ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
+                    None,
+                );
+            }
+            for (section, entries) in sections.iter() {
                 write_string_to_grid(
-                    desc,
+                    &crate::i18n::tr(section),
                     &mut self.help_content,
                     self.theme_default.fg,
                     self.theme_default.bg,
-                    self.theme_default.attrs,
+                    self.theme_default.attrs | Attr::UNDERLINE,
                     ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
                     None,
                 );
                 idx += 2;
-                for (k, v) in shortcuts {
+                for entry in entries {
                     let (x, y) = write_string_to_grid(
-                        &format!(
-                            "{: >width$}",
-                            format!("{}", v),
-                            width = max_first_column_width
-                        ),
+                        &format!("{: >width$}", entry.key, width = key_column_width),
                         &mut self.help_content,
                         self.theme_default.fg,
                         self.theme_default.bg,
@@ -1143,8 +1321,17 @@ impl Component for Tabbed {
                         ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
                         None,
                     );
-                    write_string_to_grid(
-                        k,
+                    let (x, y) = write_string_to_grid(
+                        &format!("{: <width$}", entry.name, width = name_column_width),
+                        &mut self.help_content,
+                        self.theme_default.fg,
+                        self.theme_default.bg,
+                        self.theme_default.attrs,
+                        ((x + 2, y), (max_width.saturating_sub(2), max_length - 1)),
+                        None,
+                    );
+                    let (x, y) = write_string_to_grid(
+                        &entry.description,
                         &mut self.help_content,
                         self.theme_default.fg,
                         self.theme_default.bg,
@@ -1152,6 +1339,17 @@ impl Component for Tabbed {
                         ((x + 2, y), (max_width.saturating_sub(2), max_length - 1)),
                         None,
                     );
+                    if let Some(command) = entry.command {
+                        write_string_to_grid(
+                            &format!(":{}", command),
+                            &mut self.help_content,
+                            self.theme_default.fg,
+                            self.theme_default.bg,
+                            self.theme_default.attrs | Attr::ITALICS,
+                            ((x + 2, y), (max_width.saturating_sub(2), max_length - 1)),
+                            None,
+                        );
+                    }
                     idx += 1;
                 }
                 idx += 1;
@@ -1170,7 +1368,7 @@ impl Component for Tabbed {
             clear_area(grid, dialog_area, self.theme_default);
             let inner_area = create_box(grid, dialog_area);
             let (x, y) = write_string_to_grid(
-                "shortcuts",
+                &tr!("shortcuts"),
                 grid,
                 self.theme_default.fg,
                 self.theme_default.bg,
@@ -1182,7 +1380,7 @@ impl Component for Tabbed {
                 None,
             );
             write_string_to_grid(
-                "Press ? to close",
+                &tr!("Press ? to close"),
                 grid,
                 self.theme_default.fg,
                 self.theme_default.bg,
@@ -1339,21 +1537,55 @@ impl Component for Tabbed {
                 self.theme_default = crate::conf::value(context, "theme_default");
                 self.set_dirty(true);
             }
+            UIEvent::Input(ref key) if self.show_shortcuts && self.help_filter_focused => {
+                match key {
+                    Key::Char('\n') => {
+                        self.help_filter_focused = false;
+                    }
+                    Key::Esc => {
+                        self.help_filter = None;
+                        self.help_filter_focused = false;
+                    }
+                    Key::Backspace => {
+                        if let Some(ref mut filter) = self.help_filter {
+                            filter.pop();
+                        }
+                    }
+                    Key::Char(c) => {
+                        if let Some(ref mut filter) = self.help_filter {
+                            filter.push(*c);
+                        }
+                    }
+                    Key::Paste(text) => {
+                        if let Some(ref mut filter) = self.help_filter {
+                            filter.push_str(text);
+                        }
+                    }
+                    _ => return true,
+                }
+                self.help_screen_cursor = (0, 0);
+                self.dirty = true;
+                return true;
+            }
             UIEvent::Input(Key::Alt(no)) if *no >= '1' && *no <= '9' => {
                 let no = *no as usize - '1' as usize;
-                if no < self.children.len() && self.cursor_pos != no % self.children.len() {
-                    self.children[self.cursor_pos]
-                        .process_event(&mut UIEvent::VisibilityChange(false), context);
-                    self.cursor_pos = no % self.children.len();
-                    let mut children_maps = self.children[self.cursor_pos].get_shortcuts(context);
-                    children_maps.extend(self.get_shortcuts(context));
-                    self.help_curr_views = children_maps;
-                    context
-                        .replies
-                        .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
-                            self.children[self.cursor_pos].get_status(context),
-                        )));
-                    self.set_dirty(true);
+                self.focus_tab(no, context);
+                return true;
+            }
+            UIEvent::Mouse(ref mev)
+                if matches!(mev, MouseEvent::Press(MouseButton::Left, _, _))
+                    && self
+                        .tab_areas
+                        .iter()
+                        .any(|area| area_contains(*area, mouse_pos(mev))) =>
+            {
+                let pos = mouse_pos(mev);
+                if let Some(no) = self
+                    .tab_areas
+                    .iter()
+                    .position(|area| area_contains(*area, pos))
+                {
+                    self.focus_tab(no, context);
                 }
                 return true;
             }
@@ -1378,6 +1610,8 @@ impl Component for Tabbed {
                 if shortcut!(key == shortcuts[Shortcuts::GENERAL]["toggle_help"]) =>
             {
                 if self.show_shortcuts {
+                    self.help_filter = None;
+                    self.help_filter_focused = false;
                     /* children below the shortcut overlay must be redrawn */
                     self.set_dirty(true);
                     context
@@ -1475,6 +1709,12 @@ impl Component for Tabbed {
                 self.dirty = true;
                 return true;
             }
+            UIEvent::Input(Key::Esc) if self.show_shortcuts && self.help_filter.is_some() => {
+                self.help_filter = None;
+                self.help_screen_cursor = (0, 0);
+                self.dirty = true;
+                return true;
+            }
             UIEvent::Resize => {
                 self.dirty = true;
             }
@@ -1482,12 +1722,9 @@ impl Component for Tabbed {
                 if self.show_shortcuts
                     && shortcut!(key == shortcuts[Shortcuts::LISTING]["search"]) =>
             {
-                context
-                    .replies
-                    .push_back(UIEvent::CmdInput(Key::Paste("search ".to_string())));
-                context
-                    .replies
-                    .push_back(UIEvent::ChangeMode(UIMode::Command));
+                self.help_filter.get_or_insert_with(String::new);
+                self.help_filter_focused = true;
+                self.dirty = true;
                 return true;
             }
             UIEvent::Input(ref key) if self.show_shortcuts => {
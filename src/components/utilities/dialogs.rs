@@ -872,7 +872,7 @@ impl<T: PartialEq + Debug + Clone + Sync + Send, F: 'static + Sync + Send> Selec
 
     fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         let shortcuts = context.settings.shortcuts.general.key_values();
-        let navigate_help_string = format!(
+        let navigate_help_string = tr!(
             "Navigate options with {} to go down, {} to go up, select with {}",
             shortcuts["scroll_down"],
             shortcuts["scroll_up"],
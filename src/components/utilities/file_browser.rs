@@ -0,0 +1,464 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Full-screen browser of the file system to choose files or a directory
+//! with.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileBrowserEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Returns the entries of directory `dir`, directories first and each group
+/// sorted by name. Entries whose name starts with a dot are left out unless
+/// `show_hidden` is set.
+pub fn list_dir(dir: &Path, show_hidden: bool) -> io::Result<Vec<FileBrowserEntry>> {
+    let mut ret = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !show_hidden && name.starts_with('.') {
+            continue;
+        }
+        /* Follow symbolic links, so that links to directories can be
+         * entered. */
+        ret.push(FileBrowserEntry {
+            is_dir: entry.path().is_dir(),
+            name,
+        });
+    }
+    ret.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(ret)
+}
+
+/// Overlay that lists the files of a directory.
+///
+/// Enter opens the directory under the cursor, Backspace goes to the parent
+/// directory, `.` shows or hides hidden files and Space selects the file
+/// under the cursor. Files can be selected in several directories. Enter on
+/// a file finishes with a `Vec<PathBuf>` of the selected files, or of the file
+/// under the cursor if none is selected.
+///
+/// A browser made with [`FileBrowser::choose_dir`] lists only directories,
+/// and Enter on the `./` entry at the top finishes with the `PathBuf` of the
+/// directory shown.
+#[derive(Debug)]
+pub struct FileBrowser {
+    title: String,
+    dir: PathBuf,
+    choose_dir: bool,
+    entries: Vec<FileBrowserEntry>,
+    /// Why the entries of `dir` could not be listed.
+    error: Option<String>,
+    show_hidden: bool,
+    selected: Vec<PathBuf>,
+    cursor: usize,
+    theme_default: ThemeAttribute,
+    highlighted_attrs: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for FileBrowser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.title)
+    }
+}
+
+impl FileBrowser {
+    pub fn new(title: String, dir: PathBuf, context: &Context) -> Self {
+        let mut ret = FileBrowser {
+            title,
+            dir,
+            choose_dir: false,
+            entries: vec![],
+            error: None,
+            show_hidden: false,
+            selected: vec![],
+            cursor: 0,
+            theme_default: Default::default(),
+            highlighted_attrs: Default::default(),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.initialise(context);
+        ret.read_dir();
+        ret
+    }
+
+    /// Makes a browser for choosing a directory instead of files.
+    pub fn choose_dir(title: String, dir: PathBuf, context: &Context) -> Self {
+        let mut ret = Self::new(title, dir, context);
+        ret.choose_dir = true;
+        ret.read_dir();
+        ret
+    }
+
+    /// The directory whose files are listed.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The chosen files, in the order they were selected.
+    pub fn selected(&self) -> &[PathBuf] {
+        &self.selected
+    }
+
+    fn initialise(&mut self, context: &Context) {
+        self.theme_default = crate::conf::value(context, "theme_default");
+        self.highlighted_attrs = crate::conf::value(context, "widgets.options.highlighted");
+        if !context.settings.terminal.use_color() {
+            self.highlighted_attrs.attrs |= Attr::REVERSE;
+        }
+    }
+
+    fn read_dir(&mut self) {
+        match list_dir(&self.dir, self.show_hidden) {
+            Ok(mut entries) if self.choose_dir => {
+                entries.retain(|e| e.is_dir);
+                entries.insert(
+                    0,
+                    FileBrowserEntry {
+                        name: ".".to_string(),
+                        is_dir: true,
+                    },
+                );
+                self.entries = entries;
+                self.error = None;
+            }
+            Ok(entries) => {
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(err) => {
+                self.entries.clear();
+                self.error = Some(err.to_string());
+            }
+        }
+        self.cursor = 0;
+        self.dirty = true;
+    }
+
+    /// Lists directory `dir` instead.
+    pub fn change_dir(&mut self, dir: PathBuf) {
+        self.dir = dir;
+        self.read_dir();
+    }
+
+    /// Goes to the parent directory, with the cursor on the directory left.
+    pub fn parent_dir(&mut self) {
+        let left = match (self.dir.parent(), self.dir.file_name()) {
+            (Some(parent), Some(name)) => {
+                let name = name.to_string_lossy().to_string();
+                self.dir = parent.to_path_buf();
+                name
+            }
+            _ => return,
+        };
+        self.read_dir();
+        if let Some(pos) = self.entries.iter().position(|e| e.is_dir && e.name == left) {
+            self.cursor = pos;
+        }
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        let current = self.entries.get(self.cursor).map(|e| e.name.clone());
+        self.show_hidden = !self.show_hidden;
+        self.read_dir();
+        if let Some(pos) = current.and_then(|name| self.entries.iter().position(|e| e.name == name))
+        {
+            self.cursor = pos;
+        }
+    }
+
+    /// Selects the file under the cursor, or unselects it if it's selected.
+    pub fn toggle_selected(&mut self) {
+        let path = match self.entries.get(self.cursor) {
+            Some(entry) if !entry.is_dir => self.dir.join(&entry.name),
+            _ => return,
+        };
+        if let Some(pos) = self.selected.iter().position(|p| *p == path) {
+            self.selected.remove(pos);
+        } else {
+            self.selected.push(path);
+        }
+        self.dirty = true;
+    }
+}
+
+impl Component for FileBrowser {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        let inner_area = create_box(grid, area);
+        clear_area(grid, inner_area, self.theme_default);
+        write_string_to_grid(
+            &if self.choose_dir {
+                format!(
+                    "{} (Enter: open, Enter on ./: choose, Backspace: up, .: hidden \
+                     directories, Esc: cancel)",
+                    self.title
+                )
+            } else {
+                format!(
+                    "{}: {} selected (Space: select, Enter: open, Backspace: up, .: hidden \
+                     files, Esc: cancel)",
+                    self.title,
+                    self.selected.len()
+                )
+            },
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        let upper_left = upper_left!(inner_area);
+        let bottom_right = bottom_right!(inner_area);
+        write_string_to_grid(
+            &self.dir.display().to_string(),
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::UNDERLINE,
+            (upper_left, set_y(bottom_right, get_y(upper_left))),
+            None,
+        );
+        let rows = height!(inner_area).saturating_sub(1);
+        if let Some(ref err) = self.error {
+            write_string_to_grid(
+                err,
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs | Attr::ITALICS,
+                (pos_inc(upper_left, (0, 1)), bottom_right),
+                None,
+            );
+        } else if self.entries.is_empty() {
+            write_string_to_grid(
+                "Empty directory.",
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs | Attr::ITALICS,
+                (pos_inc(upper_left, (0, 1)), bottom_right),
+                None,
+            );
+        } else if rows > 0 {
+            let top = self.cursor - self.cursor % rows;
+            for (i, entry) in self.entries.iter().skip(top).take(rows).enumerate() {
+                let y = get_y(upper_left) + 1 + i;
+                let row_area = (set_y(upper_left, y), set_y(bottom_right, y));
+                let attrs = if top + i == self.cursor {
+                    self.highlighted_attrs
+                } else {
+                    self.theme_default
+                };
+                clear_area(grid, row_area, attrs);
+                let line = if entry.is_dir {
+                    format!("    {}/", entry.name)
+                } else {
+                    format!(
+                        "[{}] {}",
+                        if self.selected.contains(&self.dir.join(&entry.name)) {
+                            'x'
+                        } else {
+                            ' '
+                        },
+                        entry.name
+                    )
+                };
+                write_string_to_grid(
+                    &line,
+                    grid,
+                    attrs.fg,
+                    attrs.bg,
+                    if entry.is_dir {
+                        attrs.attrs | Attr::BOLD
+                    } else {
+                        attrs.attrs
+                    },
+                    row_area,
+                    None,
+                );
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match event {
+            UIEvent::ConfigReload { old_settings: _ } => {
+                self.initialise(context);
+                self.set_dirty(true);
+                false
+            }
+            UIEvent::Input(Key::Esc) => {
+                context.replies.push_back(UIEvent::ComponentKill(self.id));
+                true
+            }
+            UIEvent::Input(Key::Char('\n')) | UIEvent::Input(Key::Right) => {
+                match self.entries.get(self.cursor) {
+                    Some(entry)
+                        if self.choose_dir
+                            && entry.name == "."
+                            && matches!(event, UIEvent::Input(Key::Char('\n'))) =>
+                    {
+                        context.replies.push_back(UIEvent::FinishedUIDialog(
+                            self.id,
+                            Box::new(self.dir.clone()),
+                        ));
+                    }
+                    Some(entry) if entry.is_dir && entry.name != "." => {
+                        let dir = self.dir.join(&entry.name);
+                        self.change_dir(dir);
+                    }
+                    Some(entry) if matches!(event, UIEvent::Input(Key::Char('\n'))) => {
+                        let mut chosen = self.selected.clone();
+                        if chosen.is_empty() {
+                            chosen.push(self.dir.join(&entry.name));
+                        }
+                        context
+                            .replies
+                            .push_back(UIEvent::FinishedUIDialog(self.id, Box::new(chosen)));
+                    }
+                    _ => {}
+                }
+                true
+            }
+            UIEvent::Input(Key::Backspace) | UIEvent::Input(Key::Left) => {
+                self.parent_dir();
+                true
+            }
+            UIEvent::Input(Key::Char('.')) => {
+                self.toggle_hidden();
+                true
+            }
+            UIEvent::Input(Key::Char(' ')) => {
+                self.toggle_selected();
+                if self.cursor + 1 < self.entries.len() {
+                    self.cursor += 1;
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Up)
+            | UIEvent::Input(Key::Ctrl('p'))
+            | UIEvent::Input(Key::Char('k')) => {
+                self.cursor = self.cursor.saturating_sub(1);
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Down)
+            | UIEvent::Input(Key::Ctrl('n'))
+            | UIEvent::Input(Key::Char('j')) => {
+                if self.cursor + 1 < self.entries.len() {
+                    self.cursor += 1;
+                }
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::Home) => {
+                self.cursor = 0;
+                self.set_dirty(true);
+                true
+            }
+            UIEvent::Input(Key::End) => {
+                self.cursor = self.entries.len().saturating_sub(1);
+                self.set_dirty(true);
+                true
+            }
+            /* The browser is modal; swallow any other input. */
+            UIEvent::Input(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_browser_list_dir() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir_all(root.join("b-dir")).unwrap();
+        fs::create_dir_all(root.join(".hidden-dir")).unwrap();
+        for name in &["c.txt", "a.pdf", ".hidden"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let names = |entries: Vec<FileBrowserEntry>| {
+            entries
+                .into_iter()
+                .map(|e| (e.name, e.is_dir))
+                .collect::<Vec<(String, bool)>>()
+        };
+
+        assert_eq!(
+            names(list_dir(root, false).unwrap()),
+            vec![
+                ("b-dir".to_string(), true),
+                ("a.pdf".to_string(), false),
+                ("c.txt".to_string(), false),
+            ]
+        );
+        assert_eq!(
+            names(list_dir(root, true).unwrap()),
+            vec![
+                (".hidden-dir".to_string(), true),
+                ("b-dir".to_string(), true),
+                (".hidden".to_string(), false),
+                ("a.pdf".to_string(), false),
+                ("c.txt".to_string(), false),
+            ]
+        );
+        list_dir(&root.join("a.pdf"), false).unwrap_err();
+    }
+}
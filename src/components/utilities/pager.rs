@@ -44,7 +44,12 @@ pub struct Pager {
     filtered_content: Option<(String, Result<CellBuffer>)>,
     text_lines: Vec<String>,
     line_breaker: LineBreakText,
+    /// Highlights the text as e-mail source, with the kind of each line in
+    /// `text_lines`.
+    source_highlight: Option<(SourceHighlighter, Vec<SourceLine>)>,
     movement: Option<PageMovement>,
+    /// Where the pager was last drawn, to tell which mouse events are ours.
+    drawn_area: Option<Area>,
     id: ComponentId,
 }
 
@@ -56,6 +61,8 @@ impl fmt::Display for Pager {
 
 impl Pager {
     const PAGES_AHEAD_TO_RENDER_NO: usize = 16;
+    /// Lines scrolled for each step of the mouse wheel.
+    const MOUSE_WHEEL_LINES: usize = 3;
 
     pub fn new(context: &Context) -> Self {
         let mut ret = Pager {
@@ -86,6 +93,21 @@ impl Pager {
         self
     }
 
+    /// Highlight the text as e-mail source with the given MIME boundaries.
+    /// See [`format_source`].
+    pub fn set_source_highlight(&mut self, boundaries: Vec<String>) -> &mut Self {
+        self.source_highlight = Some((SourceHighlighter::new(boundaries), vec![]));
+        self
+    }
+
+    fn clear_lines(&mut self) {
+        self.text_lines.clear();
+        if let Some((ref mut highlighter, ref mut kinds)) = self.source_highlight {
+            highlighter.reset();
+            kinds.clear();
+        }
+    }
+
     pub fn set_initialised(&mut self, new_val: bool) -> &mut Self {
         self.initialised = new_val;
         self
@@ -103,7 +125,7 @@ impl Pager {
         }
 
         self.text = text.to_string();
-        self.text_lines.clear();
+        self.clear_lines();
         self.line_breaker = LineBreakText::new(self.text.clone(), self.reflow, width);
         self.height = 0;
         self.width = 0;
@@ -230,7 +252,7 @@ impl Pager {
                 );
 
                 self.line_breaker = line_breaker;
-                self.text_lines.clear();
+                self.clear_lines();
             };
             self.height = self.text_lines.len();
             self.width = width;
@@ -284,6 +306,13 @@ impl Pager {
                 .extend(self.line_breaker.by_ref().take(new_lines_no));
         };
         let new_lines_no = self.text_lines.len() - old_lines_no;
+        if let Some((ref mut highlighter, ref mut kinds)) = self.source_highlight {
+            kinds.extend(
+                self.text_lines[old_lines_no..]
+                    .iter()
+                    .map(|l| highlighter.classify(l)),
+            );
+        }
         if let Some(ref mut search) = self.search {
             use melib::text_processing::search::KMP;
             for (y, l) in self.text_lines.iter().enumerate().skip(old_lines_no) {
@@ -339,22 +368,59 @@ impl Pager {
             }
         }
 
+        let source_colors = self.source_highlight.as_ref().map(|_| {
+            (
+                crate::conf::value(context, "mail.view.source.header_name"),
+                crate::conf::value(context, "mail.view.source.header_value"),
+                crate::conf::value(context, "mail.view.source.boundary"),
+                crate::conf::value(context, "mail.view.source.annotation"),
+            )
+        });
         let (mut upper_left, bottom_right) = area;
-        for l in self
+        for (i, l) in self
             .text_lines
             .iter()
+            .enumerate()
             .skip(self.cursor.1)
             .take(height!(area))
         {
+            let kind = self
+                .source_highlight
+                .as_ref()
+                .and_then(|(_, kinds)| kinds.get(i));
+            let colors = match (kind, source_colors) {
+                (
+                    Some(SourceLine::HeaderName(_)) | Some(SourceLine::HeaderValue),
+                    Some((_, header_value, _, _)),
+                ) => header_value,
+                (Some(SourceLine::Boundary), Some((_, _, boundary, _))) => boundary,
+                (Some(SourceLine::Annotation), Some((_, _, _, annotation))) => annotation,
+                _ => ThemeAttribute {
+                    attrs: Attr::DEFAULT,
+                    ..self.colors
+                },
+            };
             write_string_to_grid(
                 l,
                 grid,
-                self.colors.fg,
-                self.colors.bg,
-                Attr::DEFAULT,
+                colors.fg,
+                colors.bg,
+                colors.attrs,
                 (upper_left, bottom_right),
                 None,
             );
+            if let (Some(SourceLine::HeaderName(name_len)), Some((header_name, _, _, _))) =
+                (kind, source_colors)
+            {
+                let x = get_x(upper_left);
+                let end = std::cmp::min(x + *name_len, get_x(bottom_right) + 1);
+                for c in grid.row_iter(x..end, get_y(upper_left)) {
+                    grid[c]
+                        .set_fg(header_name.fg)
+                        .set_bg(header_name.bg)
+                        .set_attrs(header_name.attrs);
+                }
+            }
             if l.starts_with('⤷') {
                 grid[upper_left]
                     .set_fg(crate::conf::value(context, "highlight").fg)
@@ -442,6 +508,7 @@ impl Component for Pager {
         }
 
         self.dirty = false;
+        self.drawn_area = Some(area);
 
         let height = height!(area);
         if let Some(mvm) = self.movement.take() {
@@ -668,6 +735,25 @@ impl Component for Pager {
                 self.dirty = true;
                 return true;
             }
+            UIEvent::Mouse(ref mev)
+                if matches!(
+                    mev,
+                    MouseEvent::Press(MouseButton::WheelUp | MouseButton::WheelDown, _, _)
+                ) && self
+                    .drawn_area
+                    .map(|area| area_contains(area, mouse_pos(mev)))
+                    .unwrap_or(false) =>
+            {
+                self.movement = Some(
+                    if matches!(mev, MouseEvent::Press(MouseButton::WheelUp, _, _)) {
+                        PageMovement::Up(Self::MOUSE_WHEEL_LINES)
+                    } else {
+                        PageMovement::Down(Self::MOUSE_WHEEL_LINES)
+                    },
+                );
+                self.dirty = true;
+                return true;
+            }
             UIEvent::Input(ref key)
                 if shortcut!(key == shortcuts[Shortcuts::GENERAL]["home_page"]) =>
             {
@@ -725,10 +811,7 @@ impl Component for Pager {
                     Ok(o) => o,
                     Err(e) => {
                         context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(format!(
-                                "Could not pipe to {}: {}",
-                                bin, e
-                            )),
+                            StatusEvent::DisplayMessage(tr!("Could not pipe to {}: {}", bin, e)),
                         ));
                         return true;
                     }
@@ -740,7 +823,7 @@ impl Component for Pager {
 
                 context
                     .replies
-                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(tr!(
                         "Pager text piped to '{}{}{}'",
                         &bin,
                         if args.is_empty() { "" } else { " " },
@@ -436,6 +436,11 @@ impl<T: 'static + std::fmt::Debug + Copy + Default + Send + Sync> FormWidget<T>
         self.cursor = new_cursor;
     }
 
+    /// Returns the name of the field under the cursor.
+    pub fn focused_field(&self) -> Option<&str> {
+        self.layout.get(self.cursor).map(|f| f.as_ref())
+    }
+
     pub fn hide_buttons(&mut self) {
         self.hide_buttons = true;
         self.buttons.set_dirty(false);
@@ -39,10 +39,13 @@ use crate::{conf::deserializers::non_empty_opt_string, terminal::Color};
 #[rustfmt::skip]
 mod overrides;
 pub use overrides::*;
+pub mod commands;
 pub mod composing;
+mod contacts;
 pub mod notifications;
 pub mod pager;
 pub mod pgp;
+mod smime;
 pub mod tags;
 #[macro_use]
 pub mod shortcuts;
@@ -66,12 +69,28 @@ pub use melib::thread::{SortField, SortOrder};
 use melib::{
     conf::{AccountSettings, MailboxConf, ToggleFlag},
     error::*,
+    log,
+    text_processing::GlobMatch,
+    StoreKind,
 };
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-pub use self::{accounts::Account, composing::*, pgp::*, shortcuts::*, tags::*};
+pub use self::{
+    accounts::Account,
+    composing::*,
+    contacts::{AccountContactsSettings, ContactHighlight, ContactsSettings},
+    listing::{AgeHighlight, DigestPeriod, DigestSettings, OpenEntryAction, OpenEntrySetting},
+    pager::MimeFilter,
+    pgp::*,
+    shortcuts::*,
+    smime::*,
+    tags::*,
+};
 use self::{
-    default_vals::*, listing::ListingSettings, notifications::NotificationsSettings,
+    commands::CommandsSettings,
+    default_vals::*,
+    listing::ListingSettings,
+    notifications::{DndPeriod, NotificationsSettings},
     terminal::TerminalSettings,
 };
 use crate::pager::PagerSettings;
@@ -135,6 +154,8 @@ pub struct MailUIConf {
     pub themes: Option<Themes>,
     #[serde(default)]
     pub pgp: PGPSettingsOverride,
+    #[serde(default)]
+    pub smime: SmimeSettingsOverride,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -144,6 +165,17 @@ pub struct FileMailboxConf {
     pub conf_override: MailUIConf,
     #[serde(default = "false_val")]
     pub collapsed: bool,
+    /// A shell command to run in the background when the mailbox is opened
+    /// in the listing.
+    #[serde(default = "none", deserialize_with = "non_empty_opt_string")]
+    pub on_enter_command: Option<String>,
+    /// A shell command to run in the background when the listing moves from
+    /// the mailbox to another one.
+    #[serde(default = "none", deserialize_with = "non_empty_opt_string")]
+    pub on_leave_command: Option<String>,
+    /// Group the newsletters of the mailbox into digests.
+    #[serde(default = "none")]
+    pub digest: Option<DigestSettings>,
     #[serde(flatten)]
     pub mailbox_conf: MailboxConf,
 }
@@ -158,6 +190,35 @@ impl FileMailboxConf {
     }
 }
 
+/// A named search query of an account, shown in the sidebar below its
+/// mailboxes: either the query alone or a table with a `query` and a
+/// `shortcut` that opens it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SavedSearch {
+    Query(String),
+    WithShortcut {
+        query: String,
+        #[serde(default = "none")]
+        shortcut: Option<crate::terminal::Key>,
+    },
+}
+
+impl SavedSearch {
+    pub fn query(&self) -> &str {
+        match self {
+            Self::Query(query) | Self::WithShortcut { query, .. } => query,
+        }
+    }
+
+    pub fn shortcut(&self) -> Option<&crate::terminal::Key> {
+        match self {
+            Self::Query(_) => None,
+            Self::WithShortcut { shortcut, .. } => shortcut.as_ref(),
+        }
+    }
+}
+
 use crate::conf::deserializers::extra_settings;
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileAccount {
@@ -175,14 +236,44 @@ pub struct FileAccount {
     pub subscribed_mailboxes: Vec<String>,
     #[serde(default)]
     pub mailboxes: IndexMap<String, FileMailboxConf>,
+    /// Paths of mailboxes to show first in the sidebar, in this order, before
+    /// their siblings.
+    #[serde(default)]
+    pub mailbox_order: Vec<String>,
+    /// Named search queries shown in the sidebar below the mailboxes, whose
+    /// results are kept up to date like those of virtual mailboxes.
+    #[serde(default)]
+    pub searches: IndexMap<String, SavedSearch>,
     #[serde(default)]
     pub search_backend: SearchBackend,
+    /// Where to keep the envelopes of the account, `memory` or `btree`.
+    #[serde(default)]
+    pub envelope_store: StoreKind,
     #[serde(default)]
     pub order: (SortField, SortOrder),
     #[serde(default = "false_val")]
     pub manual_refresh: bool,
     #[serde(default = "none")]
     pub refresh_command: Option<String>,
+    /// Refresh all subscribed mailboxes of the account every this many
+    /// seconds.
+    #[serde(default = "none")]
+    pub refresh_interval: Option<u64>,
+    /// Index the text of attachments in the sqlite3 search index, for
+    /// `attachment:` queries.
+    #[serde(default = "false_val")]
+    pub index_attachments: bool,
+    /// Commands that print the text of an attachment given on their standard
+    /// input, keyed by MIME type glob.
+    #[serde(default = "attachment_text_commands")]
+    pub attachment_text_commands: IndexMap<String, String>,
+    /// Names of the workspaces the account belongs to. Accounts without any
+    /// workspace are shown in every workspace.
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    /// Address book settings, e.g. syncing with a CardDAV server.
+    #[serde(default)]
+    pub contacts: AccountContactsSettings,
     #[serde(flatten)]
     pub conf_override: MailUIConf,
     #[serde(flatten)]
@@ -223,9 +314,17 @@ pub struct FileSettings {
     #[serde(default)]
     pub pgp: PGPSettings,
     #[serde(default)]
+    pub smime: SmimeSettings,
+    #[serde(default)]
     pub terminal: TerminalSettings,
     #[serde(default)]
     pub log: LogSettings,
+    #[serde(default)]
+    pub power: PowerSettings,
+    #[serde(default)]
+    pub contacts: ContactsSettings,
+    #[serde(default)]
+    pub commands: CommandsSettings,
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -249,6 +348,51 @@ impl AccountConf {
     pub fn conf_mut(&mut self) -> &mut FileAccount {
         &mut self.conf
     }
+
+    /// Returns the key of the `mailboxes` configuration of mailbox `path`.
+    ///
+    /// IMAP mailbox paths are decoded from modified UTF-7, but keys that use
+    /// the raw path of the server are still accepted, with a warning.
+    pub fn mailbox_conf_key(&self, path: &str) -> Option<String> {
+        if self.mailbox_confs.contains_key(path) {
+            return Some(path.to_string());
+        }
+        let raw_path = self.imap_raw_path(path)?;
+        if !self.mailbox_confs.contains_key(&raw_path) {
+            return None;
+        }
+        log::warn!(
+            "Account `{}`: mailbox configuration `{}` uses the modified UTF-7 path of mailbox `{}`; \
+             rename it to `{}`.",
+            self.account.name,
+            raw_path,
+            path,
+            path
+        );
+        Some(raw_path)
+    }
+
+    /// Returns whether mailbox `path` is in `subscribed_mailboxes`, either
+    /// by its path or, for IMAP, by its raw modified UTF-7 path.
+    pub fn is_subscribed_mailbox(&self, path: &str) -> bool {
+        let raw_path = self.imap_raw_path(path);
+        self.account.subscribed_mailboxes.iter().any(|m| {
+            path.matches_glob(m)
+                || raw_path
+                    .as_ref()
+                    .map(|raw_path| raw_path.matches_glob(m))
+                    .unwrap_or(false)
+        })
+    }
+
+    /// The modified UTF-7 encoding of an IMAP mailbox `path`, if it differs
+    /// from `path`.
+    fn imap_raw_path(&self, path: &str) -> Option<String> {
+        if !self.account.format.eq_ignore_ascii_case("imap") {
+            return None;
+        }
+        Some(melib::backends::utf7::encode_utf7_imap(path)).filter(|raw_path| raw_path != path)
+    }
 }
 
 impl From<FileAccount> for AccountConf {
@@ -556,6 +700,7 @@ This is required so that you don't accidentally start meli and find out later th
         }
 
         s.terminal.themes.validate()?;
+        s.commands.validate()?;
         for (name, acc) in s.accounts.iter_mut() {
             let FileAccount {
                 root_mailbox,
@@ -567,10 +712,18 @@ This is required so that you don't accidentally start meli and find out later th
                 order,
                 subscribed_mailboxes,
                 mailboxes,
+                mailbox_order: _,
+                searches: _,
                 extra,
                 manual_refresh,
                 refresh_command: _,
+                refresh_interval: _,
+                index_attachments: _,
+                attachment_text_commands: _,
+                workspaces: _,
+                contacts: _,
                 search_backend: _,
+                envelope_store: _,
                 conf_override: _,
             } = acc.clone();
 
@@ -618,8 +771,12 @@ pub struct Settings {
     pub tags: TagsSettings,
     pub composing: ComposingSettings,
     pub pgp: PGPSettings,
+    pub smime: SmimeSettings,
     pub terminal: TerminalSettings,
     pub log: LogSettings,
+    pub power: PowerSettings,
+    pub contacts: ContactsSettings,
+    pub commands: CommandsSettings,
     #[serde(skip)]
     _logger: StderrLogger,
 }
@@ -651,8 +808,12 @@ impl Settings {
             tags: fs.tags,
             composing: fs.composing,
             pgp: fs.pgp,
+            smime: fs.smime,
             terminal: fs.terminal,
             log: fs.log,
+            power: fs.power,
+            contacts: fs.contacts,
+            commands: fs.commands,
             _logger,
         })
     }
@@ -674,8 +835,12 @@ impl Settings {
             tags: fs.tags,
             composing: fs.composing,
             pgp: fs.pgp,
+            smime: fs.smime,
             terminal: fs.terminal,
             log: fs.log,
+            power: fs.power,
+            contacts: fs.contacts,
+            commands: fs.commands,
             _logger,
         })
     }
@@ -711,10 +876,22 @@ mod default_vals {
         80.into()
     }
 
+    pub(in crate::conf) fn low_battery_threshold() -> usize {
+        20
+    }
+
+    pub(in crate::conf) fn power_check_interval() -> u64 {
+        60
+    }
+
     pub(in crate::conf) fn none<T>() -> Option<T> {
         None
     }
 
+    pub(in crate::conf) fn attachment_text_commands() -> indexmap::IndexMap<String, String> {
+        std::iter::once(("application/pdf".to_string(), "pdftotext - -".to_string())).collect()
+    }
+
     pub(in crate::conf) fn internal_value_false<T: std::convert::From<super::ToggleFlag>>() -> T {
         super::ToggleFlag::InternalVal(false).into()
     }
@@ -870,6 +1047,100 @@ impl Serialize for SearchBackend {
     }
 }
 
+/// The timezone to display message dates in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DateTimezone {
+    /// The timezone of the message's `Date:` header.
+    Sender,
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset in seconds east of UTC.
+    Offset(i64),
+}
+
+impl DateTimezone {
+    /// Formats `timestamp` in this timezone. `sender_offset` is the UTC offset
+    /// of the message's `Date:` header, if known; without it `Sender` falls
+    /// back to the local timezone.
+    pub fn format(
+        self,
+        timestamp: melib::UnixTimestamp,
+        sender_offset: Option<i64>,
+        fmt: Option<&str>,
+        posix: bool,
+    ) -> String {
+        use melib::datetime::{timestamp_to_string, timestamp_to_string_with_offset};
+        match (self, sender_offset) {
+            (DateTimezone::Local, _) | (DateTimezone::Sender, None) => {
+                timestamp_to_string(timestamp, fmt, posix)
+            }
+            (DateTimezone::Utc, _) => timestamp_to_string_with_offset(timestamp, 0, fmt, posix),
+            (DateTimezone::Sender, Some(offset)) | (DateTimezone::Offset(offset), _) => {
+                timestamp_to_string_with_offset(timestamp, offset, fmt, posix)
+            }
+        }
+    }
+
+    /// The timezone after this one when cycling through sender, local, UTC
+    /// and `configured`, if it is a fixed offset.
+    pub fn next(self, configured: DateTimezone) -> Self {
+        match self {
+            DateTimezone::Sender => DateTimezone::Local,
+            DateTimezone::Local => DateTimezone::Utc,
+            DateTimezone::Utc if matches!(configured, DateTimezone::Offset(_)) => configured,
+            DateTimezone::Utc | DateTimezone::Offset(_) => DateTimezone::Sender,
+        }
+    }
+}
+
+impl std::fmt::Display for DateTimezone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DateTimezone::Sender => write!(f, "sender"),
+            DateTimezone::Local => write!(f, "local"),
+            DateTimezone::Utc => write!(f, "UTC"),
+            DateTimezone::Offset(offset) => {
+                write!(f, "{}", melib::datetime::utc_offset_to_string(*offset))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DateTimezone {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String>::deserialize(deserializer)?;
+        match s.as_str() {
+            sender if sender.eq_ignore_ascii_case("sender") => Ok(DateTimezone::Sender),
+            local if local.eq_ignore_ascii_case("local") => Ok(DateTimezone::Local),
+            utc if utc.eq_ignore_ascii_case("utc") => Ok(DateTimezone::Utc),
+            other => melib::datetime::parse_utc_offset(other)
+                .map(DateTimezone::Offset)
+                .ok_or_else(|| {
+                    de::Error::custom(
+                        "invalid `timezone` value, expected \"sender\", \"local\", \"utc\" \
+                         or an offset such as \"+0200\"",
+                    )
+                }),
+        }
+    }
+}
+
+impl Serialize for DateTimezone {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            DateTimezone::Utc => serializer.serialize_str("utc"),
+            other => serializer.serialize_str(&other.to_string()),
+        }
+    }
+}
+
 pub fn create_config_file(p: &Path) -> Result<()> {
     let mut file = OpenOptions::new()
         .write(true)
@@ -1045,6 +1316,43 @@ pub struct LogSettings {
     pub maximum_level: melib::LogLevel,
 }
 
+/// When to pause periodic refreshes and index rebuilds to save power or
+/// bandwidth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PowerSettings {
+    /// Pause background work on metered connections, as reported by
+    /// NetworkManager.
+    /// Default: true
+    #[serde(default = "true_val")]
+    pub pause_on_metered: bool,
+    /// Pause background work whenever running on battery.
+    /// Default: false
+    #[serde(default = "false_val")]
+    pub pause_on_battery: bool,
+    /// Pause background work when running on battery with a charge at or
+    /// below this percentage.
+    /// Default: 20
+    #[serde(default = "low_battery_threshold")]
+    pub low_battery_threshold: usize,
+    /// Seconds between checks of the power and network state. `0` disables
+    /// the checks.
+    /// Default: 60
+    #[serde(default = "power_check_interval")]
+    pub check_interval: u64,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_metered: true,
+            pause_on_battery: false,
+            low_battery_threshold: low_battery_threshold(),
+            check_interval: power_check_interval(),
+        }
+    }
+}
+
 pub use dotaddressable::*;
 mod dotaddressable {
     use super::*;
@@ -1078,7 +1386,13 @@ mod dotaddressable {
     impl DotAddressable for PathBuf {}
     impl DotAddressable for ToggleFlag {}
     impl DotAddressable for SearchBackend {}
+    impl DotAddressable for melib::StoreKind {}
+    impl DotAddressable for SavedSearch {}
+    impl DotAddressable for DateTimezone {}
     impl DotAddressable for melib::SpecialUsageMailbox {}
+    impl DotAddressable for super::notifications::DndPeriod {}
+    impl DotAddressable for AgeHighlight {}
+    impl DotAddressable for crate::pager::MimeFilter {}
     impl<T: DotAddressable> DotAddressable for Option<T> {}
     impl<T: DotAddressable> DotAddressable for Vec<T> {}
     impl<K: DotAddressable + std::cmp::Eq + std::hash::Hash, V: DotAddressable> DotAddressable
@@ -1112,6 +1426,28 @@ mod dotaddressable {
         }
     }
 
+    impl DotAddressable for PowerSettings {
+        fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
+            match path.first() {
+                Some(field) => {
+                    let tail = &path[1..];
+                    match *field {
+                        "pause_on_metered" => self.pause_on_metered.lookup(field, tail),
+                        "pause_on_battery" => self.pause_on_battery.lookup(field, tail),
+                        "low_battery_threshold" => self.low_battery_threshold.lookup(field, tail),
+                        "check_interval" => self.check_interval.lookup(field, tail),
+
+                        other => Err(Error::new(format!(
+                            "{} has no field named {}",
+                            parent_field, other
+                        ))),
+                    }
+                }
+                None => Ok(toml::to_string(self).map_err(|err| err.to_string())?),
+            }
+        }
+    }
+
     impl DotAddressable for Settings {
         fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
             match path.first() {
@@ -1126,8 +1462,12 @@ mod dotaddressable {
                         "tags" => Err(Error::new("unimplemented")),
                         "composing" => Err(Error::new("unimplemented")),
                         "pgp" => Err(Error::new("unimplemented")),
+                        "smime" => Err(Error::new("unimplemented")),
                         "terminal" => self.terminal.lookup(field, tail),
                         "log" => self.log.lookup(field, tail),
+                        "power" => self.power.lookup(field, tail),
+                        "contacts" => self.contacts.lookup(field, tail),
+                        "commands" => self.commands.lookup(field, tail),
 
                         other => Err(Error::new(format!(
                             "{} has no field named {}",
@@ -1184,6 +1524,7 @@ mod dotaddressable {
                         // lookup(field,
                         // tail)<Themes>,
                         "pgp" => Err(Error::new("unimplemented")), //self.pgp.lookup(field, tail),
+                        "smime" => Err(Error::new("unimplemented")),
 
                         other => Err(Error::new(format!(
                             "{} has no field named {}",
@@ -1203,6 +1544,9 @@ mod dotaddressable {
                     let tail = &path[1..];
                     match *field {
                         "conf_override" => self.conf_override.lookup(field, tail),
+                        "on_enter_command" => self.on_enter_command.lookup(field, tail),
+                        "on_leave_command" => self.on_leave_command.lookup(field, tail),
+                        "digest" => self.digest.lookup(field, tail),
                         "mailbox_conf" => self.mailbox_conf.lookup(field, tail),
                         other => Err(Error::new(format!(
                             "{} has no field named {}",
@@ -1228,9 +1572,19 @@ mod dotaddressable {
                         "read_only" => self.read_only.lookup(field, tail),
                         "subscribed_mailboxes" => self.subscribed_mailboxes.lookup(field, tail),
                         "mailboxes" => self.mailboxes.lookup(field, tail),
+                        "mailbox_order" => self.mailbox_order.lookup(field, tail),
+                        "searches" => self.searches.lookup(field, tail),
                         "search_backend" => self.search_backend.lookup(field, tail),
+                        "envelope_store" => self.envelope_store.lookup(field, tail),
                         "manual_refresh" => self.manual_refresh.lookup(field, tail),
                         "refresh_command" => self.refresh_command.lookup(field, tail),
+                        "refresh_interval" => self.refresh_interval.lookup(field, tail),
+                        "index_attachments" => self.index_attachments.lookup(field, tail),
+                        "attachment_text_commands" => {
+                            self.attachment_text_commands.lookup(field, tail)
+                        }
+                        "workspaces" => self.workspaces.lookup(field, tail),
+                        "contacts" => self.contacts.lookup(field, tail),
                         "conf_override" => self.conf_override.lookup(field, tail),
                         "extra" => self.extra.lookup(field, tail),
                         "order" => self.order.lookup(field, tail),
@@ -1435,3 +1789,30 @@ send_mail = 'false'
         }
     }
 }
+
+#[test]
+fn test_config_imap_raw_mailbox_path() {
+    let mut conf = AccountConf::default();
+    conf.account.format = "imap".to_string();
+    conf.account.subscribed_mailboxes = vec!["INBOX.Entw&APw-rfe".to_string()];
+    conf.mailbox_confs
+        .insert("INBOX.Entw&APw-rfe".to_string(), FileMailboxConf::default());
+    conf.mailbox_confs
+        .insert("INBOX.Sent".to_string(), FileMailboxConf::default());
+    assert_eq!(
+        conf.mailbox_conf_key("INBOX.Entwürfe").as_deref(),
+        Some("INBOX.Entw&APw-rfe")
+    );
+    assert_eq!(
+        conf.mailbox_conf_key("INBOX.Sent").as_deref(),
+        Some("INBOX.Sent")
+    );
+    assert!(conf.mailbox_conf_key("INBOX.Trash").is_none());
+    assert!(conf.is_subscribed_mailbox("INBOX.Entwürfe"));
+    assert!(!conf.is_subscribed_mailbox("INBOX.Sent"));
+
+    /* Raw paths are only accepted for IMAP accounts. */
+    conf.account.format = "maildir".to_string();
+    assert!(conf.mailbox_conf_key("INBOX.Entwürfe").is_none());
+    assert!(!conf.is_subscribed_mailbox("INBOX.Entwürfe"));
+}
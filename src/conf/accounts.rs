@@ -25,13 +25,14 @@
 
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     convert::TryFrom,
     fs,
     future::Future,
     io,
     ops::{Index, IndexMut},
     os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
     pin::Pin,
     result,
     sync::{Arc, RwLock},
@@ -46,17 +47,19 @@ use indexmap::IndexMap;
 use melib::{
     backends::*,
     email::*,
-    error::{Error, ErrorKind, Result},
+    error::{Error, ErrorKind, NetworkErrorKind, Result, ResultIntoError},
     log,
-    text_processing::GlobMatch,
-    thread::{SortField, SortOrder, Threads},
-    AddressBook, Collection, LogLevel,
+    search::SearchCapabilities,
+    thread::{SortField, SortOrder, ThreadHash, ThreadOverlay, Threads},
+    uuid::Uuid,
+    AddressBook, Collection, LogLevel, StoreKind, UnixTimestamp,
 };
+use serde::Serialize;
 use smallvec::SmallVec;
 
-use super::{AccountConf, FileMailboxConf};
+use super::{AccountConf, FileAccount, FileMailboxConf};
 use crate::{
-    jobs::{JobExecutor, JobId, JoinHandle},
+    jobs::{JobExecutor, JobId, JoinHandle, Timer},
     types::UIEvent::{self, EnvelopeRemove, EnvelopeRename, EnvelopeUpdate, Notification},
     StatusEvent, ThreadEvent,
 };
@@ -146,9 +149,9 @@ impl MailboxEntry {
                 self.ref_mailbox.count().ok().unwrap_or((0, 0)).1
             ),
             MailboxStatus::Failed(ref e) => e.to_string(),
-            MailboxStatus::None => "Retrieving mailbox.".to_string(),
+            MailboxStatus::None => tr!("Retrieving mailbox."),
             MailboxStatus::Parsing(done, total) => {
-                format!("Parsing messages. [{}/{}]", done, total)
+                tr!("Parsing messages. [{}/{}]", done, total)
             }
         }
     }
@@ -173,8 +176,31 @@ pub struct Account {
     pub sent_mailbox: Option<MailboxHash>,
     pub collection: Collection,
     pub address_book: AddressBook,
+    /// Lowercased addresses the user has sent mail to, collected from the
+    /// envelopes sent from one of the user's addresses.
+    pub known_senders: HashSet<String>,
+    /// Threading corrections made with the `thread-join` and `thread-split`
+    /// commands.
+    pub thread_overlay: ThreadOverlay,
+    /// Threads pinned to the top of the listing with the `pin` command, as
+    /// the `Message-ID` of their root message, per mailbox path.
+    pub pinned_threads: BTreeMap<String, BTreeSet<String>>,
+    /// Threads hidden from the listing with the `snooze` command, as the
+    /// `Message-ID` of their root message and the time they resurface, per
+    /// mailbox path.
+    pub snoozed_threads: BTreeMap<String, BTreeMap<String, UnixTimestamp>>,
+    /// Whether the subtree of a mailbox is collapsed in the sidebar, per
+    /// mailbox path, as last toggled by the user. It overrides the mailbox's
+    /// `collapsed` setting.
+    pub collapsed_mailboxes: BTreeMap<String, bool>,
+    /// Senders unsubscribed from with the unsubscribe assistant, as their
+    /// lowercased address, and when.
+    pub unsubscribed_senders: BTreeMap<String, UnixTimestamp>,
     pub settings: AccountConf,
     pub backend: Arc<RwLock<Box<dyn MailBackend>>>,
+    /// Mailboxes defined by a `query` in their configuration instead of
+    /// existing in the backend.
+    pub virtual_mailboxes: HashMap<MailboxHash, VirtualMailbox>,
 
     pub job_executor: Arc<JobExecutor>,
     pub active_jobs: HashMap<JobId, JobRequest>,
@@ -182,6 +208,24 @@ pub struct Account {
     pub sender: Sender<ThreadEvent>,
     pub event_queue: VecDeque<(MailboxHash, RefreshEvent)>,
     pub backend_capabilities: MailBackendCapabilities,
+    /// When a refresh of any mailbox last finished successfully.
+    pub last_synced: Option<UnixTimestamp>,
+    /// When the next periodic refresh is due, if `refresh_interval` is set.
+    pub next_refresh: Option<UnixTimestamp>,
+    /// Whether periodic refreshes and index rebuilds are paused to save power
+    /// or bandwidth, see `crate::power`.
+    pub background_paused: bool,
+    /// Refresh all mailboxes once the connection is back, after a resume
+    /// from suspend.
+    resync_on_online: bool,
+    refresh_timer: Option<Timer>,
+    /// Checks every minute for snoozed threads that are due.
+    snooze_timer: Timer,
+    /// Cards synced with the CardDAV server of the `contacts` settings.
+    #[cfg(feature = "carddav")]
+    carddav_state: melib::addressbook::carddav::SyncState,
+    #[cfg(feature = "carddav")]
+    contacts_sync_timer: Option<Timer>,
 }
 
 pub enum JobRequest {
@@ -234,7 +278,11 @@ pub enum JobRequest {
         mailbox_hash: MailboxHash,
         handle: JoinHandle<Result<HashMap<MailboxHash, Mailbox>>>,
     },
-    //RenameMailbox,
+    RenameMailbox {
+        mailbox_hash: MailboxHash,
+        new_path: String,
+        handle: JoinHandle<Result<Mailbox>>,
+    },
     SetMailboxPermissions {
         mailbox_hash: MailboxHash,
         handle: JoinHandle<Result<()>>,
@@ -247,6 +295,10 @@ pub enum JobRequest {
     Watch {
         handle: JoinHandle<Result<()>>,
     },
+    #[cfg(feature = "carddav")]
+    SyncContacts {
+        handle: JoinHandle<Result<melib::addressbook::carddav::SyncResult>>,
+    },
 }
 
 impl Drop for JobRequest {
@@ -257,7 +309,6 @@ impl Drop for JobRequest {
             JobRequest::Refresh { handle, .. } |
             JobRequest::SetFlags { handle, .. } |
             JobRequest::SaveMessage { handle, .. } |
-            //JobRequest::RenameMailbox,
             JobRequest::SetMailboxPermissions { handle, .. } |
             JobRequest::SetMailboxSubscription { handle, .. } |
             JobRequest::Watch { handle, .. } |
@@ -273,12 +324,19 @@ impl Drop for JobRequest {
             JobRequest::DeleteMailbox { handle, .. } => {
                 handle.cancel();
             }
+            JobRequest::RenameMailbox { handle, .. } => {
+                handle.cancel();
+            }
             JobRequest::Fetch { handle, .. } => {
                 handle.cancel();
             }
             JobRequest::Mailboxes { handle, .. } => {
                 handle.cancel();
             }
+            #[cfg(feature = "carddav")]
+            JobRequest::SyncContacts { handle, .. } => {
+                handle.cancel();
+            }
             JobRequest::SendMessage => {}
         }
     }
@@ -301,7 +359,9 @@ impl core::fmt::Debug for JobRequest {
             JobRequest::DeleteMailbox { mailbox_hash, .. } => {
                 write!(f, "JobRequest::DeleteMailbox({})", mailbox_hash)
             }
-            //JobRequest::RenameMailbox,
+            JobRequest::RenameMailbox { mailbox_hash, .. } => {
+                write!(f, "JobRequest::RenameMailbox({})", mailbox_hash)
+            }
             JobRequest::SetMailboxPermissions { .. } => {
                 write!(f, "JobRequest::SetMailboxPermissions")
             }
@@ -313,6 +373,8 @@ impl core::fmt::Debug for JobRequest {
             JobRequest::SendMessageBackground { .. } => {
                 write!(f, "JobRequest::SendMessageBackground")
             }
+            #[cfg(feature = "carddav")]
+            JobRequest::SyncContacts { .. } => write!(f, "JobRequest::SyncContacts"),
         }
     }
 }
@@ -340,13 +402,17 @@ impl core::fmt::Display for JobRequest {
             ),
             JobRequest::CreateMailbox { path, .. } => write!(f, "Create mailbox {}", path),
             JobRequest::DeleteMailbox { .. } => write!(f, "Delete mailbox"),
-            //JobRequest::RenameMailbox,
+            JobRequest::RenameMailbox { new_path, .. } => {
+                write!(f, "Rename mailbox to {}", new_path)
+            }
             JobRequest::SetMailboxPermissions { .. } => write!(f, "Set mailbox permissions"),
             JobRequest::SetMailboxSubscription { .. } => write!(f, "Set mailbox subscription"),
             JobRequest::Watch { .. } => write!(f, "Background watch"),
             JobRequest::SendMessageBackground { .. } | JobRequest::SendMessage => {
                 write!(f, "Sending message")
             }
+            #[cfg(feature = "carddav")]
+            JobRequest::SyncContacts { .. } => write!(f, "Contacts sync"),
         }
     }
 }
@@ -365,6 +431,18 @@ impl JobRequest {
     pub fn is_online(&self) -> bool {
         matches!(self, JobRequest::IsOnline { .. })
     }
+
+    pub fn is_send_message(&self) -> bool {
+        matches!(
+            self,
+            JobRequest::SendMessage | JobRequest::SendMessageBackground { .. }
+        )
+    }
+
+    #[cfg(feature = "carddav")]
+    pub fn is_sync_contacts(&self) -> bool {
+        matches!(self, JobRequest::SyncContacts { .. })
+    }
 }
 
 impl Drop for Account {
@@ -442,12 +520,10 @@ impl Account {
             settings.account(),
             Box::new(move |path: &str| {
                 s.account.subscribed_mailboxes.is_empty()
-                    || (s.mailbox_confs.contains_key(path)
-                        && s.mailbox_confs[path].mailbox_conf().subscribe.is_true())
-                    || s.account
-                        .subscribed_mailboxes
-                        .iter()
-                        .any(|m| path.matches_glob(m))
+                    || s.mailbox_conf_key(path).map_or(false, |key| {
+                        s.mailbox_confs[&key].mailbox_conf().subscribe.is_true()
+                    })
+                    || s.is_subscribed_mailbox(path)
             }),
             event_consumer,
         )?;
@@ -469,6 +545,32 @@ impl Account {
             }
         };
 
+        let thread_overlay = data_dir
+            .find_data_file("thread_overlay")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        let pinned_threads = data_dir
+            .find_data_file("pinned_threads")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        let snoozed_threads = data_dir
+            .find_data_file("snoozed_threads")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        let collapsed_mailboxes = data_dir
+            .find_data_file("collapsed_mailboxes")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        let unsubscribed_senders = data_dir
+            .find_data_file("unsubscribed_senders")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+
         if settings.conf.search_backend == crate::conf::SearchBackend::Auto {
             if backend.capabilities().supports_search {
                 settings.conf.search_backend = crate::conf::SearchBackend::None;
@@ -536,7 +638,47 @@ impl Account {
             }
         }
 
-        Ok(Account {
+        let collection = backend.collection();
+        if settings.conf.envelope_store != StoreKind::default() {
+            collection.set_store(settings.conf.envelope_store.new_store());
+        }
+
+        let refresh_interval = settings.conf.refresh_interval.filter(|secs| *secs > 0);
+        let refresh_timer = refresh_interval.map(|secs| {
+            job_executor.clone().create_timer(
+                std::time::Duration::from_secs(secs),
+                std::time::Duration::from_secs(secs),
+            )
+        });
+        let snooze_timer = job_executor.clone().create_timer(
+            std::time::Duration::from_secs(60),
+            std::time::Duration::from_secs(60),
+        );
+        #[cfg(feature = "carddav")]
+        let carddav_state = data_dir
+            .find_data_file("carddav_state")
+            .and_then(|path| fs::File::open(path).ok())
+            .and_then(|f| serde_json::from_reader(io::BufReader::new(f)).ok())
+            .unwrap_or_default();
+        #[cfg(feature = "carddav")]
+        let contacts_sync_timer = Some(settings.conf.contacts.sync_interval)
+            .filter(|secs| *secs > 0 && settings.conf.contacts.carddav_url.is_some())
+            .map(|secs| {
+                job_executor.clone().create_timer(
+                    std::time::Duration::from_secs(secs),
+                    std::time::Duration::from_secs(secs),
+                )
+            });
+        #[cfg(not(feature = "carddav"))]
+        if settings.conf.contacts.carddav_url.is_some() {
+            log::warn!(
+                "{}: carddav_url is set but meli is not built with CardDAV support.",
+                name
+            );
+        }
+
+        #[allow(unused_mut)]
+        let mut ret = Account {
             hash,
             name,
             is_online: if !backend.capabilities().is_remote {
@@ -548,8 +690,14 @@ impl Account {
             mailboxes_order: Default::default(),
             tree: Default::default(),
             address_book,
+            known_senders: Default::default(),
+            thread_overlay,
+            pinned_threads,
+            snoozed_threads,
+            collapsed_mailboxes,
+            unsubscribed_senders,
             sent_mailbox: Default::default(),
-            collection: backend.collection(),
+            collection,
             settings,
             sender,
             job_executor,
@@ -558,7 +706,366 @@ impl Account {
             event_queue: VecDeque::with_capacity(8),
             backend_capabilities: backend.capabilities(),
             backend: Arc::new(RwLock::new(backend)),
-        })
+            virtual_mailboxes: Default::default(),
+            last_synced: None,
+            next_refresh: refresh_interval.map(|secs| melib::datetime::now() + secs),
+            background_paused: false,
+            resync_on_online: false,
+            refresh_timer,
+            snooze_timer,
+            #[cfg(feature = "carddav")]
+            carddav_state,
+            #[cfg(feature = "carddav")]
+            contacts_sync_timer,
+        };
+        #[cfg(feature = "carddav")]
+        if ret.settings.conf.contacts.carddav_url.is_some() {
+            if let Err(err) = ret.sync_contacts() {
+                log::error!("{}: could not sync contacts: {}", ret.name, err);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Stores `value` as JSON in file `name` of the account's data directory.
+    fn save_data_file<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| Error::new(err.to_string()))?;
+        let path = data_dir
+            .place_data_file(name)
+            .chain_err_summary(|| format!("Could not create {} file", name))?;
+        let f = fs::File::create(&path)
+            .chain_err_summary(|| format!("Could not create {}", path.display()))?;
+        let mut permissions = f.metadata()?.permissions();
+        permissions.set_mode(0o600); // Read/write for owner only.
+        f.set_permissions(permissions)?;
+        serde_json::to_writer(io::BufWriter::new(f), value)
+            .map_err(|err| Error::new(err.to_string()))?;
+        Ok(())
+    }
+
+    /// Returns the directory where drafts that couldn't be saved in any mailbox
+    /// are kept, creating it if needed.
+    pub fn local_drafts_dir(&self) -> Result<PathBuf> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| Error::new(err.to_string()))?;
+        Ok(data_dir.create_data_directory("drafts")?)
+    }
+
+    /// Stores `bytes` as a new file in [`Account::local_drafts_dir`] and
+    /// returns its path.
+    pub fn save_local_draft(&self, bytes: &[u8]) -> Result<PathBuf> {
+        let path = self
+            .local_drafts_dir()?
+            .join(format!("{}.eml", Uuid::new_v4()));
+        write_private_file(&path, bytes)?;
+        Ok(path)
+    }
+
+    /// Returns the directory where messages scheduled with `send-at` are kept
+    /// until they are sent, creating it if needed.
+    pub fn scheduled_dir(&self) -> Result<PathBuf> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| Error::new(err.to_string()))?;
+        Ok(data_dir.create_data_directory("scheduled")?)
+    }
+
+    /// Stores the message `bytes` in [`Account::scheduled_dir`] to be sent at
+    /// `send_at` and returns its path. The send time is the prefix of the
+    /// file name.
+    pub fn schedule_message(&self, bytes: &[u8], send_at: UnixTimestamp) -> Result<PathBuf> {
+        let path = self
+            .scheduled_dir()?
+            .join(format!("{}-{}.eml", send_at, Uuid::new_v4()));
+        write_private_file(&path, bytes)?;
+        Ok(path)
+    }
+
+    /// Stores `thread_overlay` in the account's data directory.
+    pub fn save_thread_overlay(&self) -> Result<()> {
+        self.save_data_file("thread_overlay", &self.thread_overlay)
+    }
+
+    /// Returns the pinned threads of `mailbox_hash`.
+    pub fn pinned_threads(&self, mailbox_hash: MailboxHash) -> HashSet<ThreadHash> {
+        let pinned = match self
+            .mailbox_entries
+            .get(&mailbox_hash)
+            .and_then(|entry| self.pinned_threads.get(entry.ref_mailbox.path()))
+        {
+            Some(pinned) => pinned,
+            None => return HashSet::default(),
+        };
+        let threads = self.collection.get_threads(mailbox_hash);
+        pinned
+            .iter()
+            .filter_map(|message_id| threads.message_id_thread(message_id))
+            .collect()
+    }
+
+    /// Pins the threads of `env_hashes` in `mailbox_hash`, or unpins them if
+    /// they are all pinned already, and stores the change in the account's
+    /// data directory. Returns whether the threads were pinned.
+    pub fn toggle_pinned(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &[EnvelopeHash],
+    ) -> Result<bool> {
+        let path = self.mailbox_entries[&mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        let message_ids = {
+            let threads = self.collection.get_threads(mailbox_hash);
+            env_hashes
+                .iter()
+                .filter_map(|env_hash| {
+                    let node = threads.envelope_node(*env_hash)?;
+                    let root = threads
+                        .thread_ref(threads.find_group(threads[&node].group))
+                        .root();
+                    threads.node_message_id(root)
+                })
+                .collect::<BTreeSet<String>>()
+        };
+        if message_ids.is_empty() {
+            return Err(Error::new("Thread has no Message-ID and cannot be pinned."));
+        }
+        let pinned = self.pinned_threads.entry(path.clone()).or_default();
+        let pin = !message_ids.is_subset(pinned);
+        if pin {
+            pinned.extend(message_ids);
+        } else {
+            pinned.retain(|message_id| !message_ids.contains(message_id));
+            if pinned.is_empty() {
+                self.pinned_threads.remove(&path);
+            }
+        }
+        self.save_data_file("pinned_threads", &self.pinned_threads)?;
+        Ok(pin)
+    }
+
+    /// Returns the threads of `mailbox_hash` that are snoozed.
+    pub fn snoozed_threads(&self, mailbox_hash: MailboxHash) -> HashSet<ThreadHash> {
+        let snoozed = match self
+            .mailbox_entries
+            .get(&mailbox_hash)
+            .and_then(|entry| self.snoozed_threads.get(entry.ref_mailbox.path()))
+        {
+            Some(snoozed) => snoozed,
+            None => return HashSet::default(),
+        };
+        let now = melib::datetime::now();
+        let threads = self.collection.get_threads(mailbox_hash);
+        snoozed
+            .iter()
+            .filter(|(_, until)| **until > now)
+            .filter_map(|(message_id, _)| threads.message_id_thread(message_id))
+            .collect()
+    }
+
+    /// Hides the threads of `env_hashes` in `mailbox_hash` from the listing
+    /// until `until`, and stores them in the account's data directory.
+    pub fn snooze(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &[EnvelopeHash],
+        until: UnixTimestamp,
+    ) -> Result<()> {
+        let path = self.mailbox_entries[&mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        let message_ids = {
+            let threads = self.collection.get_threads(mailbox_hash);
+            env_hashes
+                .iter()
+                .filter_map(|env_hash| {
+                    let node = threads.envelope_node(*env_hash)?;
+                    let root = threads
+                        .thread_ref(threads.find_group(threads[&node].group))
+                        .root();
+                    threads.node_message_id(root)
+                })
+                .collect::<BTreeSet<String>>()
+        };
+        if message_ids.is_empty() {
+            return Err(Error::new(
+                "Thread has no Message-ID and cannot be snoozed.",
+            ));
+        }
+        self.snoozed_threads.entry(path).or_default().extend(
+            message_ids
+                .into_iter()
+                .map(|message_id| (message_id, until)),
+        );
+        self.save_data_file("snoozed_threads", &self.snoozed_threads)
+    }
+
+    /// Shows the snoozed threads of `mailbox_hash` in the listing again.
+    /// Returns how many there were.
+    pub fn unsnooze_all(&mut self, mailbox_hash: MailboxHash) -> Result<usize> {
+        let path = self.mailbox_entries[&mailbox_hash].ref_mailbox.path();
+        let count = match self.snoozed_threads.remove(path) {
+            Some(snoozed) => snoozed.len(),
+            None => return Ok(0),
+        };
+        self.save_data_file("snoozed_threads", &self.snoozed_threads)?;
+        Ok(count)
+    }
+
+    /// Returns whether the subtree of `mailbox_hash` is collapsed in the
+    /// sidebar.
+    pub fn is_mailbox_collapsed(&self, mailbox_hash: MailboxHash) -> bool {
+        self.mailbox_entries
+            .get(&mailbox_hash)
+            .map(|entry| {
+                self.collapsed_mailboxes
+                    .get(entry.ref_mailbox.path())
+                    .copied()
+                    .unwrap_or(entry.conf.collapsed)
+            })
+            .unwrap_or(false)
+    }
+
+    /// Collapses or expands the subtree of `mailbox_hash` in the sidebar, and
+    /// stores the state in the account's data directory.
+    pub fn set_mailbox_collapsed(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        collapsed: bool,
+    ) -> Result<()> {
+        let path = match self.mailbox_entries.get(&mailbox_hash) {
+            Some(entry) => entry.ref_mailbox.path().to_string(),
+            None => return Ok(()),
+        };
+        self.collapsed_mailboxes.insert(path, collapsed);
+        self.save_data_file("collapsed_mailboxes", &self.collapsed_mailboxes)
+    }
+
+    /// Records that the user unsubscribed from `sender` now, and stores it in
+    /// the account's data directory.
+    pub fn mark_unsubscribed(&mut self, sender: &str) -> Result<()> {
+        self.unsubscribed_senders
+            .insert(sender.to_lowercase(), melib::datetime::now());
+        self.save_data_file("unsubscribed_senders", &self.unsubscribed_senders)
+    }
+
+    /// Returns the id of the timer that resurfaces snoozed threads.
+    pub fn snooze_timer_id(&self) -> Uuid {
+        self.snooze_timer.id()
+    }
+
+    /// Called when the snooze timer fires. Shows the threads whose snooze
+    /// time has passed in the listing again, marked as unread, and notifies
+    /// the user. Threads of mailboxes that aren't loaded yet wait for a
+    /// later tick.
+    pub fn snooze_timer_fired(&mut self) -> Result<()> {
+        let now = melib::datetime::now();
+        let mut updated = vec![];
+        let mut resurfaced = 0;
+        for (mailbox_hash, entry) in self.mailbox_entries.iter() {
+            if !entry.status.is_available() {
+                continue;
+            }
+            let snoozed = match self.snoozed_threads.get_mut(entry.ref_mailbox.path()) {
+                Some(snoozed) => snoozed,
+                None => continue,
+            };
+            let due = snoozed
+                .iter()
+                .filter(|(_, until)| **until <= now)
+                .map(|(message_id, _)| message_id.clone())
+                .collect::<Vec<String>>();
+            if due.is_empty() {
+                continue;
+            }
+            let threads = self.collection.get_threads(*mailbox_hash);
+            let mut env_hashes = vec![];
+            for message_id in &due {
+                snoozed.remove(message_id);
+                if let Some(thread) = threads.message_id_thread(message_id) {
+                    resurfaced += 1;
+                    env_hashes.extend(
+                        threads
+                            .thread_group_iter(thread)
+                            .filter_map(|(_, h)| threads.thread_nodes()[&h].message()),
+                    );
+                }
+            }
+            updated.push((*mailbox_hash, env_hashes));
+        }
+        if updated.is_empty() {
+            return Ok(());
+        }
+        self.snoozed_threads
+            .retain(|_, snoozed| !snoozed.is_empty());
+        self.save_data_file("snoozed_threads", &self.snoozed_threads)?;
+        for (mailbox_hash, env_hashes) in updated {
+            if let Ok(env_hashes) = EnvelopeHashBatch::try_from(env_hashes.as_slice()) {
+                let job = self.backend.write().unwrap().set_flags(
+                    env_hashes.clone(),
+                    mailbox_hash,
+                    smallvec::smallvec![(Ok(Flag::SEEN), false)],
+                );
+                match job {
+                    Ok(job) => {
+                        let handle = self.job_executor.spawn_specialized(job);
+                        self.insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "{}: could not mark snoozed thread unread: {}",
+                            self.name,
+                            err
+                        );
+                    }
+                }
+            }
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
+                    self.hash,
+                    mailbox_hash,
+                ))))
+                .unwrap();
+        }
+        if resurfaced > 0 {
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                    Some(format!("{}: snoozed", self.name)),
+                    if resurfaced == 1 {
+                        tr!("A snoozed thread is back in the listing.")
+                    } else {
+                        format!("{} snoozed threads are back in the listing.", resurfaced)
+                    },
+                    Some(crate::types::NotificationType::NewMail),
+                )))
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    /// Applies `thread_overlay` to every loaded mailbox. Returns the
+    /// mailboxes whose threads changed.
+    pub fn apply_thread_overlay(&self) -> Vec<MailboxHash> {
+        if self.thread_overlay.is_empty() {
+            return vec![];
+        }
+        let mailboxes = self
+            .collection
+            .threads
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<MailboxHash>>();
+        mailboxes
+            .into_iter()
+            .filter(|mailbox_hash| {
+                self.collection
+                    .apply_overlay(*mailbox_hash, &self.thread_overlay)
+            })
+            .collect()
     }
 
     fn init(&mut self, mut ref_mailboxes: HashMap<MailboxHash, Mailbox>) -> Result<()> {
@@ -581,8 +1088,12 @@ impl Account {
             .cloned()
             .collect::<HashSet<String>>();
         for f in ref_mailboxes.values_mut() {
-            if let Some(conf) = self.settings.mailbox_confs.get_mut(f.path()) {
-                mailbox_conf_hash_set.remove(f.path());
+            let conf_key = self.settings.mailbox_conf_key(f.path());
+            if let Some(conf) = conf_key
+                .as_ref()
+                .and_then(|key| self.settings.mailbox_confs.get_mut(key))
+            {
+                mailbox_conf_hash_set.remove(conf_key.as_ref().unwrap());
                 conf.mailbox_conf.usage = if f.special_usage() != SpecialUsageMailbox::Normal {
                     Some(f.special_usage())
                 } else {
@@ -634,6 +1145,85 @@ impl Account {
             }
         }
 
+        /* Configured mailboxes that the backend doesn't have but that have a
+         * `query` are virtual mailboxes. */
+        self.virtual_mailboxes.clear();
+        for path in mailbox_conf_hash_set.clone() {
+            let conf = &self.settings.mailbox_confs[&path];
+            match VirtualMailbox::from_conf(&path, &conf.mailbox_conf) {
+                None => continue,
+                Some(Ok(mailbox)) => {
+                    mailbox_entries.insert(
+                        mailbox.hash(),
+                        MailboxEntry::new(
+                            MailboxStatus::None,
+                            path.clone(),
+                            BackendMailbox::clone(&mailbox),
+                            conf.clone(),
+                        ),
+                    );
+                    ref_mailboxes.insert(mailbox.hash(), BackendMailbox::clone(&mailbox));
+                    self.virtual_mailboxes.insert(mailbox.hash(), mailbox);
+                }
+                Some(Err(err)) => {
+                    log::warn!(
+                        "Account `{}` mailbox `{}` has an invalid query: {}",
+                        &self.name,
+                        path,
+                        err
+                    );
+                    self.sender
+                        .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(tr!(
+                                "Account `{}` mailbox `{}` has an invalid query: {}",
+                                &self.name,
+                                path,
+                                err
+                            )),
+                        )))
+                        .unwrap();
+                }
+            }
+            mailbox_conf_hash_set.remove(&path);
+        }
+
+        /* Saved searches are virtual mailboxes too. */
+        for (name, search) in self.settings.conf.searches.iter() {
+            match VirtualMailbox::from_saved_search(name, search.query()) {
+                Ok(mailbox) => {
+                    mailbox_entries.insert(
+                        mailbox.hash(),
+                        MailboxEntry::new(
+                            MailboxStatus::None,
+                            name.clone(),
+                            BackendMailbox::clone(&mailbox),
+                            FileMailboxConf::default(),
+                        ),
+                    );
+                    ref_mailboxes.insert(mailbox.hash(), BackendMailbox::clone(&mailbox));
+                    self.virtual_mailboxes.insert(mailbox.hash(), mailbox);
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Account `{}` saved search `{}` has an invalid query: {}",
+                        &self.name,
+                        name,
+                        err
+                    );
+                    self.sender
+                        .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(tr!(
+                                "Account `{}` saved search `{}` has an invalid query: {}",
+                                &self.name,
+                                name,
+                                err
+                            )),
+                        )))
+                        .unwrap();
+                }
+            }
+        }
+
         for missing_mailbox in &mailbox_conf_hash_set {
             log::warn!(
                 "Account `{}` mailbox `{}` configured but not present in account's mailboxes. Is \
@@ -671,9 +1261,10 @@ impl Account {
             );
             self.sender
                 .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
-                    StatusEvent::DisplayMessage(format!(
+                    StatusEvent::DisplayMessage(tr!(
                         "Account `{}` has the following mailboxes: [{}]",
-                        &self.name, mailbox_comma_sep_list_string,
+                        &self.name,
+                        mailbox_comma_sep_list_string,
                     )),
                 )))
                 .unwrap();
@@ -685,10 +1276,12 @@ impl Account {
                 /* Skip unsubscribed mailbox */
                 continue;
             }
+            let is_virtual = self.virtual_mailboxes.contains_key(h);
             mailbox_entries.entry(*h).and_modify(|entry| {
-                if entry.conf.mailbox_conf.autoload
-                    || (entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Inbox
-                        || entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Sent)
+                if !is_virtual
+                    && (entry.conf.mailbox_conf.autoload
+                        || (entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Inbox
+                            || entry.ref_mailbox.special_usage() == SpecialUsageMailbox::Sent))
                 {
                     let total = entry.ref_mailbox.count().ok().unwrap_or((0, 0)).1;
                     entry.status = MailboxStatus::Parsing(0, total);
@@ -720,7 +1313,12 @@ impl Account {
             self.collection.new_mailbox(*h);
         }
 
-        build_mailboxes_order(&mut tree, &mailbox_entries, &mut mailboxes_order);
+        build_mailboxes_order(
+            &mut tree,
+            &mailbox_entries,
+            &self.settings.conf,
+            &mut mailboxes_order,
+        );
         self.mailboxes_order = mailboxes_order;
         self.mailbox_entries = mailbox_entries;
         self.tree = tree;
@@ -729,6 +1327,35 @@ impl Account {
     }
 
     pub fn reload(&mut self, event: RefreshEvent, mailbox_hash: MailboxHash) -> Option<UIEvent> {
+        if self.virtual_mailboxes.is_empty() {
+            return self.reload_mailbox(event, mailbox_hash);
+        }
+        let touched = match event.kind {
+            RefreshEventKind::Update(old_hash, ref envelope) => {
+                Some((Some(old_hash), envelope.hash()))
+            }
+            RefreshEventKind::Rename(old_hash, new_hash) => Some((Some(old_hash), new_hash)),
+            RefreshEventKind::NewFlags(env_hash, _) | RefreshEventKind::Remove(env_hash) => {
+                Some((None, env_hash))
+            }
+            RefreshEventKind::Create(ref envelope) => Some((None, envelope.hash())),
+            _ => None,
+        };
+        let rescan = matches!(event.kind, RefreshEventKind::Rescan);
+        let ret = self.reload_mailbox(event, mailbox_hash);
+        if let Some((old_hash, env_hash)) = touched {
+            self.update_virtual_mailboxes(old_hash, env_hash);
+        } else if rescan {
+            self.evaluate_virtual_mailboxes();
+        }
+        ret
+    }
+
+    fn reload_mailbox(
+        &mut self,
+        event: RefreshEvent,
+        mailbox_hash: MailboxHash,
+    ) -> Option<UIEvent> {
         if !self.mailbox_entries[&mailbox_hash].status.is_available()
             && !self.mailbox_entries[&mailbox_hash].status.is_parsing()
         {
@@ -758,6 +1385,7 @@ impl Account {
                                 (*envelope).clone(),
                                 self.backend.clone(),
                                 self.name.clone(),
+                                crate::sqlite3::attachment_text_commands(&self.settings.conf),
                             )
                         }) {
                             Err(err) => {
@@ -792,18 +1420,14 @@ impl Account {
                     if !self.collection.contains_key(&env_hash) {
                         return None;
                     }
-                    self.collection
-                        .envelopes
-                        .write()
-                        .unwrap()
-                        .entry(env_hash)
-                        .and_modify(|entry| {
-                            entry.tags_mut().clear();
-                            entry.tags_mut().extend(
-                                tags.into_iter().map(|h| TagHash::from_bytes(h.as_bytes())),
-                            );
-                            entry.set_flags(flags);
-                        });
+                    {
+                        let mut entry = self.collection.get_env_mut(env_hash);
+                        entry.tags_mut().clear();
+                        entry
+                            .tags_mut()
+                            .extend(tags.into_iter().map(|h| TagHash::from_bytes(h.as_bytes())));
+                        entry.set_flags(flags);
+                    }
                     #[cfg(feature = "sqlite3")]
                     if self.settings.conf.search_backend == crate::conf::SearchBackend::Sqlite3 {
                         match crate::sqlite3::remove(env_hash).map(|_| {
@@ -811,6 +1435,7 @@ impl Account {
                                 self.collection.envelopes.read().unwrap()[&env_hash].clone(),
                                 self.backend.clone(),
                                 self.name.clone(),
+                                crate::sqlite3::attachment_text_commands(&self.settings.conf),
                             )
                         }) {
                             Ok(job) => {
@@ -855,6 +1480,7 @@ impl Account {
                                 self.collection.envelopes.read().unwrap()[&new_hash].clone(),
                                 self.backend.clone(),
                                 self.name.clone(),
+                                crate::sqlite3::attachment_text_commands(&self.settings.conf),
                             )
                         }) {
                             Err(err) => {
@@ -898,6 +1524,16 @@ impl Account {
                     }
                     let (is_seen, is_draft) =
                         { (envelope.is_seen(), envelope.flags().contains(Flag::DRAFT)) };
+                    let in_digest = self.mailbox_entries[&mailbox_hash]
+                        .conf
+                        .digest
+                        .as_ref()
+                        .map_or(false, |digest| digest.contains(&envelope));
+                    let still_mailing =
+                        crate::components::mail::sender_warning::unsubscribed_warning(
+                            &envelope,
+                            &self.unsubscribed_senders,
+                        );
                     let (subject, from) = {
                         (
                             envelope.subject().into_owned(),
@@ -910,6 +1546,7 @@ impl Account {
                             (*envelope).clone(),
                             self.backend.clone(),
                             self.name.clone(),
+                            crate::sqlite3::attachment_text_commands(&self.settings.conf),
                         ));
                         self.insert_job(
                             handle.job_id,
@@ -926,10 +1563,10 @@ impl Account {
                         );
                     }
 
-                    if self.collection.insert(*envelope, mailbox_hash) {
-                        /* is a duplicate */
-                        return None;
-                    }
+                    self.learn_known_senders(std::iter::once(envelope.as_ref()));
+                    let update = self.collection.insert(*envelope, mailbox_hash);
+                    let thread = update.thread;
+                    let update = UIEvent::ThreadUpdate((self.hash, mailbox_hash), update);
 
                     if self.mailbox_entries[&mailbox_hash]
                         .conf
@@ -937,39 +1574,33 @@ impl Account {
                         .ignore
                         .is_true()
                     {
-                        return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
+                        return Some(update);
                     }
 
-                    let thread_hash = self.collection.get_env(env_hash).thread();
                     if self
                         .collection
                         .get_threads(mailbox_hash)
-                        .thread_nodes()
-                        .contains_key(&thread_hash)
+                        .thread_ref(thread)
+                        .snoozed()
                     {
-                        let thread = self.collection.get_threads(mailbox_hash).find_group(
-                            self.collection.get_threads(mailbox_hash)[&thread_hash].group,
-                        );
-                        if self
-                            .collection
-                            .get_threads(mailbox_hash)
-                            .thread_ref(thread)
-                            .snoozed()
-                        {
-                            return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
-                        }
+                        return Some(update);
                     }
-                    if is_seen || is_draft {
-                        return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
+                    if is_seen || is_draft || in_digest {
+                        return Some(update);
                     }
 
+                    /* The notification is returned instead, so queue the update. */
+                    self.sender.send(ThreadEvent::UIEvent(update)).unwrap();
                     return Some(Notification(
                         Some(format!("new e-mail from: {}", from)),
                         format!(
-                            "{}\n{} {}",
+                            "{}\n{} {}{}",
                             subject,
                             self.name,
-                            self.mailbox_entries[&mailbox_hash].name()
+                            self.mailbox_entries[&mailbox_hash].name(),
+                            still_mailing
+                                .map(|warning| format!("\n{}", warning))
+                                .unwrap_or_default()
                         ),
                         Some(crate::types::NotificationType::NewMail),
                     ));
@@ -1007,6 +1638,43 @@ impl Account {
                     return Some(EnvelopeRemove(env_hash, thread_hash));
                 }
                 RefreshEventKind::Rescan => {
+                    #[cfg(feature = "sqlite3")]
+                    if self.settings.conf.search_backend == crate::conf::SearchBackend::Sqlite3
+                        && !self.background_paused
+                        && self
+                            .mailbox_entries
+                            .values()
+                            .all(|entry| entry.status.is_available())
+                    {
+                        /* Only reindex when every mailbox is loaded, otherwise messages of
+                         * the remaining mailboxes would be dropped from the index. */
+                        match crate::sqlite3::index_envelopes(
+                            self.name.clone(),
+                            self.collection.envelopes.clone(),
+                            self.backend.clone(),
+                            crate::sqlite3::attachment_text_commands(&self.settings.conf),
+                        ) {
+                            Ok(job) => {
+                                let handle = self.job_executor.spawn_blocking(job);
+                                self.insert_job(
+                                    handle.job_id,
+                                    JobRequest::Generic {
+                                        name: "Message index rebuild".into(),
+                                        handle,
+                                        log_level: LogLevel::TRACE,
+                                        on_finish: None,
+                                    },
+                                );
+                            }
+                            Err(err) => {
+                                log::error!(
+                                    "Failed to rebuild sqlite3 index of account {}: {}",
+                                    self.name,
+                                    err
+                                );
+                            }
+                        }
+                    }
                     self.watch();
                 }
                 RefreshEventKind::Failure(err) => {
@@ -1057,7 +1725,7 @@ impl Account {
                 .spawn()?;
             self.sender
                 .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
-                    StatusEvent::DisplayMessage(format!("Running command {}", refresh_command)),
+                    StatusEvent::DisplayMessage(tr!("Running command {}", refresh_command)),
                 )))
                 .unwrap();
             self.sender
@@ -1067,6 +1735,10 @@ impl Account {
                 .unwrap();
             return Ok(());
         }
+        if self.virtual_mailboxes.contains_key(&mailbox_hash) {
+            self.evaluate_virtual_mailbox(mailbox_hash);
+            return Ok(());
+        }
         let refresh_job = self.backend.write().unwrap().refresh(mailbox_hash);
         if let Ok(refresh_job) = refresh_job {
             let handle = if self.backend_capabilities.is_async {
@@ -1085,6 +1757,126 @@ impl Account {
         Ok(())
     }
 
+    /// Refreshes every subscribed mailbox of the account.
+    pub fn refresh_all(&mut self) -> Result<()> {
+        let mut mailboxes = self
+            .mailboxes_order
+            .iter()
+            .filter(|h| self.mailbox_entries[*h].ref_mailbox.is_subscribed())
+            .cloned()
+            .collect::<Vec<MailboxHash>>();
+        if self.settings.conf().refresh_command.is_some() {
+            /* The command refreshes the whole account, run it only once. */
+            mailboxes.truncate(1);
+        }
+        for mailbox_hash in mailboxes {
+            self.refresh(mailbox_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the id of the periodic refresh timer, if `refresh_interval`
+    /// is set.
+    pub fn refresh_timer_id(&self) -> Option<Uuid> {
+        self.refresh_timer.as_ref().map(Timer::id)
+    }
+
+    /// Called when the periodic refresh timer fires.
+    pub fn refresh_timer_fired(&mut self) -> Result<()> {
+        if let Some(secs) = self.settings.conf().refresh_interval {
+            self.next_refresh = Some(melib::datetime::now() + secs);
+        }
+        if self.is_online.is_err() || self.background_paused {
+            return Ok(());
+        }
+        self.refresh_all()
+    }
+
+    /// Starts syncing the address book with the CardDAV server of the
+    /// `contacts` settings in the background.
+    #[cfg(feature = "carddav")]
+    pub fn sync_contacts(&mut self) -> Result<()> {
+        use melib::addressbook::carddav::{CardDavClient, CardDavConf};
+
+        if self.active_jobs.values().any(JobRequest::is_sync_contacts) {
+            return Ok(());
+        }
+        let settings = &self.settings.conf.contacts;
+        let url = settings.carddav_url.clone().ok_or_else(|| {
+            Error::new(format!("Account {} has no carddav_url setting.", self.name))
+                .set_kind(ErrorKind::Configuration)
+        })?;
+        let conf = CardDavConf {
+            url,
+            username: settings
+                .carddav_username
+                .clone()
+                .unwrap_or_else(|| self.settings.account().identity.clone()),
+            password: settings.carddav_password()?,
+        };
+        let client = CardDavClient::new(&conf)?;
+        let cards = self.address_book.cards.clone();
+        let state = self.carddav_state.clone();
+        let handle = self
+            .job_executor
+            .spawn_specialized(async move { client.sync(cards, state).await });
+        self.insert_job(handle.job_id, JobRequest::SyncContacts { handle });
+        Ok(())
+    }
+
+    /// Returns the id of the periodic contacts sync timer, if `carddav_url`
+    /// and `sync_interval` are set.
+    #[cfg(feature = "carddav")]
+    pub fn contacts_sync_timer_id(&self) -> Option<Uuid> {
+        self.contacts_sync_timer.as_ref().map(Timer::id)
+    }
+
+    /// Called when the periodic contacts sync timer fires.
+    #[cfg(feature = "carddav")]
+    pub fn contacts_sync_timer_fired(&mut self) -> Result<()> {
+        if self.background_paused {
+            return Ok(());
+        }
+        self.sync_contacts()
+    }
+
+    #[cfg(feature = "carddav")]
+    fn finish_contacts_sync(&mut self, result: melib::addressbook::carddav::SyncResult) {
+        let removed = result.removed.len();
+        let updated = result.updated.len();
+        for card_id in result.removed {
+            self.address_book.remove_card(card_id);
+        }
+        for card in result.updated {
+            self.address_book.add_card(card);
+        }
+        self.carddav_state = result.state;
+        if let Err(err) = self.save_data_file("carddav_state", &self.carddav_state) {
+            log::error!("{}: could not save contacts sync state: {}", self.name, err);
+        }
+        if !result.conflicts.is_empty() {
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                    Some(format!("{}: contacts changed on both sides", &self.name)),
+                    tr!(
+                        "Kept the server's version of: {}",
+                        result.conflicts.join(", ")
+                    ),
+                    Some(crate::types::NotificationType::Info),
+                )))
+                .expect("Could not send event on main channel");
+        } else if removed + updated + result.pushed > 0 {
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                    StatusEvent::DisplayMessage(format!(
+                        "{}: contacts synced, {} updated, {} removed, {} sent to the server",
+                        &self.name, updated, removed, result.pushed
+                    )),
+                )))
+                .expect("Could not send event on main channel");
+        }
+    }
+
     pub fn watch(&mut self) {
         if self.settings.account().manual_refresh {
             return;
@@ -1107,9 +1899,10 @@ impl Account {
                 Err(e) => {
                     self.sender
                         .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(format!(
+                            StatusEvent::DisplayMessage(tr!(
                                 "Account `{}` watch action returned error: {}",
-                                &self.name, e
+                                &self.name,
+                                e
                             )),
                         )))
                         .unwrap();
@@ -1154,6 +1947,10 @@ impl Account {
         if mailbox_hash.is_null() {
             return Err(0);
         }
+        if self.virtual_mailboxes.contains_key(&mailbox_hash) {
+            self.load_virtual_mailbox(mailbox_hash);
+            return Ok(());
+        }
         match self.mailbox_entries[&mailbox_hash].status {
             MailboxStatus::Available | MailboxStatus::Parsing(_, _)
                 if self
@@ -1203,6 +2000,58 @@ impl Account {
         }
     }
 
+    /// How many of the messages of `mailbox_hash` the backend left out when
+    /// fetching it, because the mailbox is large. They can be loaded with
+    /// [`Account::fetch_older`].
+    pub fn older_envelopes(&self, mailbox_hash: MailboxHash) -> usize {
+        self.mailbox_entries
+            .get(&mailbox_hash)
+            .map(|entry| entry.ref_mailbox.older_envelopes())
+            .unwrap_or(0)
+    }
+
+    /// Fetches the next page of the messages of `mailbox_hash` that the
+    /// backend left out, unless the mailbox is being fetched already.
+    pub fn fetch_older(&mut self, mailbox_hash: MailboxHash) -> Result<()> {
+        if self.older_envelopes(mailbox_hash) == 0
+            || self.active_jobs.values().any(|j| j.is_fetch(mailbox_hash))
+        {
+            return Ok(());
+        }
+        let mailbox_job = self
+            .backend
+            .write()
+            .unwrap()
+            .fetch_older(mailbox_hash)?
+            .into_future();
+        let handle = if self.backend_capabilities.is_async {
+            self.job_executor.spawn_specialized(mailbox_job)
+        } else {
+            self.job_executor.spawn_blocking(mailbox_job)
+        };
+        self.insert_job(
+            handle.job_id,
+            JobRequest::Fetch {
+                mailbox_hash,
+                handle,
+            },
+        );
+        let loaded = self
+            .collection
+            .mailboxes
+            .read()
+            .unwrap()
+            .get(&mailbox_hash)
+            .map_or(0, |envelopes| envelopes.len());
+        self.mailbox_entries
+            .entry(mailbox_hash)
+            .and_modify(|entry| {
+                let total = entry.ref_mailbox.count().ok().unwrap_or((0, 0)).1;
+                entry.status = MailboxStatus::Parsing(loaded, std::cmp::max(loaded, total));
+            });
+        Ok(())
+    }
+
     pub fn save_special(
         &mut self,
         bytes: &[u8],
@@ -1231,6 +2080,21 @@ impl Account {
         if let Some(mailbox_hash) = saved_at {
             Ok(mailbox_hash)
         } else {
+            if mailbox_type == SpecialUsageMailbox::Drafts {
+                match self.save_local_draft(bytes) {
+                    Ok(path) => {
+                        log::info!("Draft was stored in {}.", path.display());
+                        return Err(Error::new(format!(
+                            "Draft was stored in {}; you can resume it from the `drafts` tab.",
+                            path.display()
+                        ))
+                        .set_summary("Could not save in any mailbox"));
+                    }
+                    Err(err) => {
+                        log::error!("Could not save draft in the data directory: {}.", err);
+                    }
+                }
+            }
             let file = crate::types::create_temp_file(bytes, None, None, false);
             debug!("message saved in {}", file.path.display());
             log::info!(
@@ -1316,12 +2180,13 @@ impl Account {
                     log::trace!("Message sent.");
                 } else {
                     let error_message = if let Some(exit_code) = output.code() {
-                        format!(
+                        tr!(
                             "Could not send e-mail using `{}`: Process exited with {}",
-                            command, exit_code
+                            command,
+                            exit_code
                         )
                     } else {
-                        format!(
+                        tr!(
                             "Could not send e-mail using `{}`: Process was killed by signal",
                             command
                         )
@@ -1332,7 +2197,11 @@ impl Account {
                 Ok(None)
             }
             #[cfg(feature = "smtp")]
-            SendMail::Smtp(conf) => {
+            SendMail::Smtp(mut conf) => {
+                if conf.proxy.is_none() {
+                    conf.proxy = melib::connections::proxy::Proxy::new(self.settings.account())?;
+                }
+                conf.tls = melib::connections::tls::TlsOptions::new(self.settings.account())?;
                 let handle = self.job_executor.spawn_specialized(async move {
                     let mut smtp_connection =
                         melib::smtp::SmtpConnection::new_connection(conf).await?;
@@ -1374,6 +2243,10 @@ impl Account {
     ) -> impl FnOnce(Arc<String>) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send {
         let capabilities = self.backend_capabilities.clone();
         let backend = self.backend.clone();
+        #[cfg(feature = "smtp")]
+        let account_proxy = melib::connections::proxy::Proxy::new(self.settings.account());
+        #[cfg(feature = "smtp")]
+        let account_tls = melib::connections::tls::TlsOptions::new(self.settings.account());
         |message: Arc<String>| -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
             Box::pin(async move {
                 use std::{
@@ -1406,9 +2279,10 @@ impl Account {
                             log::trace!("Message sent.");
                         } else {
                             let error_message = if let Some(exit_code) = output.code() {
-                                format!(
+                                tr!(
                                     "Could not send e-mail using `{}`: Process exited with {}",
-                                    command, exit_code
+                                    command,
+                                    exit_code
                                 )
                             } else {
                                 format!(
@@ -1423,7 +2297,11 @@ impl Account {
                         Ok(())
                     }
                     #[cfg(feature = "smtp")]
-                    SendMail::Smtp(conf) => {
+                    SendMail::Smtp(mut conf) => {
+                        if conf.proxy.is_none() {
+                            conf.proxy = account_proxy?;
+                        }
+                        conf.tls = account_tls?;
                         let mut smtp_connection =
                             melib::smtp::SmtpConnection::new_connection(conf).await?;
                         smtp_connection
@@ -1448,6 +2326,17 @@ impl Account {
         }
     }
 
+    /// Adds the recipients of any of `envelopes` sent by the user to
+    /// `known_senders`.
+    fn learn_known_senders<'a>(&mut self, envelopes: impl IntoIterator<Item = &'a Envelope>) {
+        let own = crate::components::mail::reply::own_addresses(&self.settings.account);
+        crate::components::mail::sender_warning::learn_known_senders(
+            &mut self.known_senders,
+            &own,
+            envelopes,
+        );
+    }
+
     pub fn contains_key(&self, h: EnvelopeHash) -> bool {
         self.collection.contains_key(&h)
     }
@@ -1548,7 +2437,47 @@ impl Account {
                 );
                 Ok(())
             }
-            MailboxOperation::Rename(_, _) => Err(Error::new("Not implemented.")),
+            MailboxOperation::Expunge(path) => {
+                let mailbox_hash = self.mailbox_by_path(&path)?;
+                let job = self.backend.write().unwrap().expunge(mailbox_hash)?;
+                let handle = if self.backend_capabilities.is_async {
+                    self.job_executor.spawn_specialized(job)
+                } else {
+                    self.job_executor.spawn_blocking(job)
+                };
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: format!("expunging {}", path).into(),
+                        handle,
+                        on_finish: None,
+                        log_level: LogLevel::INFO,
+                    },
+                );
+                Ok(())
+            }
+            MailboxOperation::Rename(path, new_path) => {
+                let mailbox_hash = self.mailbox_by_path(&path)?;
+                let job = self
+                    .backend
+                    .write()
+                    .unwrap()
+                    .rename_mailbox(mailbox_hash, new_path.clone())?;
+                let handle = if self.backend_capabilities.is_async {
+                    self.job_executor.spawn_specialized(job)
+                } else {
+                    self.job_executor.spawn_blocking(job)
+                };
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::RenameMailbox {
+                        mailbox_hash,
+                        new_path,
+                        handle,
+                    },
+                );
+                Ok(())
+            }
             MailboxOperation::SetPermissions(_) => Err(Error::new("Not implemented.")),
         }
     }
@@ -1595,6 +2524,108 @@ impl Account {
         self.is_online.clone()
     }
 
+    /// Cancels any pending online status check and starts a new one, when the
+    /// user asks to retry a connection that failed.
+    pub fn retry_connection(&mut self) {
+        let pending = self
+            .active_jobs
+            .iter()
+            .filter(|(_, job)| job.is_online())
+            .map(|(job_id, _)| *job_id)
+            .collect::<SmallVec<[JobId; 2]>>();
+        for job_id in pending {
+            self.cancel_job(job_id);
+        }
+        if let Err(err) = self.is_online.as_ref() {
+            if err.kind.is_authentication() {
+                /* is_online() does not retry authentication failures by itself. */
+                self.is_online = Err(Error::new("Retrying connection."));
+            }
+        }
+        let _ = self.is_online();
+    }
+
+    /// Adds the messages of `mailbox_hash` that aren't in the sqlite3 search
+    /// index yet in a background job.
+    #[cfg(feature = "sqlite3")]
+    fn index_new_envelopes(&mut self, mailbox_hash: MailboxHash) {
+        let env_hashes = self
+            .collection
+            .get_mailbox(mailbox_hash)
+            .iter()
+            .copied()
+            .collect::<Vec<EnvelopeHash>>();
+        match crate::sqlite3::index_new_envelopes(
+            self.name.clone(),
+            env_hashes,
+            self.collection.envelopes.clone(),
+            self.backend.clone(),
+        ) {
+            Ok(job) => {
+                let handle = self.job_executor.spawn_blocking(job);
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: format!(
+                            "Index new messages of {}",
+                            self.mailbox_entries[&mailbox_hash].name()
+                        )
+                        .into(),
+                        handle,
+                        log_level: LogLevel::TRACE,
+                        on_finish: None,
+                    },
+                );
+            }
+            Err(err) => {
+                log::error!("Failed to open the sqlite3 search index: {}", err);
+            }
+        }
+    }
+
+    /// Reconnects in the background after the machine resumed from suspend,
+    /// since the connections of remote backends are most likely dead, and
+    /// refreshes all mailboxes once the connection is back.
+    pub fn resumed(&mut self) {
+        if !self.backend_capabilities.is_remote {
+            return;
+        }
+        if let Err(err) = self.is_online.as_ref() {
+            if err.kind.is_authentication() {
+                return;
+            }
+        }
+        let stale = self
+            .active_jobs
+            .iter()
+            .filter(|(_, job)| job.is_online() || job.is_watch())
+            .map(|(job_id, _)| *job_id)
+            .collect::<SmallVec<[JobId; 4]>>();
+        for job_id in stale {
+            self.cancel_job(job_id);
+        }
+        /* Not an authentication error, so that the watch job is restarted once
+         * the reconnection succeeds. */
+        self.is_online = Err(Error::new("Reconnecting after resume.")
+            .set_kind(ErrorKind::Network(NetworkErrorKind::None)));
+        self.resync_on_online = true;
+        let reconnect_job = self.backend.read().unwrap().reconnect();
+        if let Ok(reconnect_job) = reconnect_job {
+            let handle = if self.backend_capabilities.is_async {
+                self.job_executor.spawn_specialized(reconnect_job)
+            } else {
+                self.job_executor.spawn_blocking(reconnect_job)
+            };
+            self.insert_job(handle.job_id, JobRequest::IsOnline { handle });
+        }
+        self.sender
+            .send(ThreadEvent::UIEvent(UIEvent::AccountStatusChange(
+                self.hash,
+                Some("Reconnecting after resume.".into()),
+            )))
+            .unwrap();
+    }
+
     pub fn search(
         &self,
         search_term: &str,
@@ -1602,32 +2633,291 @@ impl Account {
         mailbox_hash: MailboxHash,
     ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
         let query = melib::search::Query::try_from(search_term)?;
+        /* The backend doesn't know virtual mailboxes, search their envelopes
+         * locally. */
+        let backend_search = self.backend_capabilities.supports_search
+            && !self.virtual_mailboxes.contains_key(&mailbox_hash);
+        let engine = match self.settings.conf.search_backend {
+            #[cfg(feature = "sqlite3")]
+            crate::conf::SearchBackend::Sqlite3 => "sqlite3",
+            crate::conf::SearchBackend::Auto | crate::conf::SearchBackend::None
+                if backend_search =>
+            {
+                self.settings.account().format.as_str()
+            }
+            crate::conf::SearchBackend::Auto | crate::conf::SearchBackend::None => "local",
+        };
+        let unsupported = query.unsupported_atom(search_capabilities(engine));
+        if let Some(atom) = unsupported {
+            if query.unsupported_atom(SearchCapabilities::LOCAL).is_some() {
+                let supported_by = SEARCH_CAPABILITIES
+                    .iter()
+                    .filter(|(_, caps)| caps.supports(atom))
+                    .map(|(e, _)| *e)
+                    .collect::<Vec<&str>>();
+                return Err(Error::new(format!(
+                    "{} search does not support {} queries.",
+                    engine, atom
+                ))
+                .set_details(tr!("Supported by: {}", supported_by.join(", "))));
+            }
+        }
         match self.settings.conf.search_backend {
             #[cfg(feature = "sqlite3")]
-            crate::conf::SearchBackend::Sqlite3 => crate::sqlite3::search(&query, _sort),
-            crate::conf::SearchBackend::Auto | crate::conf::SearchBackend::None => {
-                if self.backend_capabilities.supports_search {
-                    self.backend
-                        .read()
-                        .unwrap()
-                        .search(query, Some(mailbox_hash))
-                } else {
-                    use melib::search::QueryTrait;
-                    let mut ret = SmallVec::new();
-                    let envelopes = self.collection.envelopes.read().unwrap();
-                    for &env_hash in self.collection.get_mailbox(mailbox_hash).iter() {
-                        if let Some(envelope) = envelopes.get(&env_hash) {
-                            if envelope.is_match(&query) {
-                                ret.push(env_hash);
-                            }
+            crate::conf::SearchBackend::Sqlite3 if unsupported.is_none() => {
+                crate::sqlite3::search(&query, _sort)
+            }
+            crate::conf::SearchBackend::Auto | crate::conf::SearchBackend::None
+                if unsupported.is_none() && backend_search =>
+            {
+                self.backend
+                    .read()
+                    .unwrap()
+                    .search(query, Some(mailbox_hash))
+            }
+            _ => {
+                /* Either there's no search backend, or it can't evaluate some atom of the
+                 * query but envelope data alone can. */
+                use melib::search::QueryTrait;
+                let mut ret = SmallVec::new();
+                let envelopes = self.collection.envelopes.read().unwrap();
+                for &env_hash in self.collection.get_mailbox(mailbox_hash).iter() {
+                    if let Some(envelope) = envelopes.get(&env_hash) {
+                        if envelope.is_match(&query) {
+                            ret.push(env_hash);
                         }
                     }
-                    Ok(Box::pin(async { Ok(ret) }))
                 }
+                Ok(Box::pin(async { Ok(ret) }))
+            }
+        }
+    }
+
+    /// Returns the mailbox that envelope `env_hash` of `mailbox_hash` is
+    /// stored in: `mailbox_hash` itself, unless it's a virtual mailbox.
+    pub fn storage_mailbox(
+        &self,
+        mailbox_hash: MailboxHash,
+        env_hash: EnvelopeHash,
+    ) -> MailboxHash {
+        if !self.virtual_mailboxes.contains_key(&mailbox_hash) {
+            return mailbox_hash;
+        }
+        self.collection
+            .mailboxes
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(h, env_hashes)| {
+                !self.virtual_mailboxes.contains_key(*h) && env_hashes.contains(&env_hash)
+            })
+            .map(|(h, _)| *h)
+            .unwrap_or(mailbox_hash)
+    }
+
+    /// Loads the mailboxes whose envelopes virtual mailbox `mailbox_hash` is
+    /// made of, which are all the subscribed ones, and evaluates its query.
+    fn load_virtual_mailbox(&mut self, mailbox_hash: MailboxHash) {
+        if self.mailbox_entries[&mailbox_hash].status.is_available() {
+            return;
+        }
+        self.collection.new_mailbox(mailbox_hash);
+        self.mailbox_entries
+            .entry(mailbox_hash)
+            .and_modify(|entry| entry.status = MailboxStatus::Available);
+        let mailboxes = self
+            .mailbox_entries
+            .iter()
+            .filter(|(h, entry)| {
+                !self.virtual_mailboxes.contains_key(*h) && entry.ref_mailbox.is_subscribed()
+            })
+            .map(|(h, _)| *h)
+            .collect::<Vec<MailboxHash>>();
+        for h in mailboxes {
+            let _ = self.load(h);
+        }
+        self.evaluate_virtual_mailbox(mailbox_hash);
+    }
+
+    /// Evaluates the queries of the loaded virtual mailboxes again.
+    fn evaluate_virtual_mailboxes(&mut self) {
+        let loaded = self
+            .virtual_mailboxes
+            .keys()
+            .filter(|h| self.mailbox_entries[*h].status.is_available())
+            .cloned()
+            .collect::<Vec<MailboxHash>>();
+        for mailbox_hash in loaded {
+            self.evaluate_virtual_mailbox(mailbox_hash);
+        }
+    }
+
+    /// Evaluates the query of virtual mailbox `mailbox_hash` with the
+    /// backend's search if it can, or against the loaded envelopes otherwise,
+    /// and sets the mailbox's envelopes to the results.
+    fn evaluate_virtual_mailbox(&mut self, mailbox_hash: MailboxHash) {
+        use melib::search::QueryTrait;
+
+        let query = self.virtual_mailboxes[&mailbox_hash].query().clone();
+        if self.backend_capabilities.supports_search
+            && query
+                .unsupported_atom(search_capabilities(&self.settings.account().format))
+                .is_none()
+        {
+            let search = self.backend.read().unwrap().search(query.clone(), None);
+            match search {
+                Ok(search) => {
+                    let account_hash = self.hash;
+                    let (sender, mut receiver) = crate::jobs::oneshot::channel();
+                    let fut: Pin<Box<dyn Future<Output = Result<()>> + Send + 'static>> =
+                        Box::pin(async move {
+                            let _ = sender.send(search.await);
+                            Ok(())
+                        });
+                    let handle = if self.backend_capabilities.is_async {
+                        self.job_executor.spawn_specialized(fut)
+                    } else {
+                        self.job_executor.spawn_blocking(fut)
+                    };
+                    self.insert_job(
+                        handle.job_id,
+                        JobRequest::Generic {
+                            name: "virtual mailbox search".into(),
+                            handle,
+                            on_finish: Some(crate::types::CallbackFn(Box::new(
+                                move |context: &mut crate::Context| match receiver.try_recv() {
+                                    Ok(Some(Ok(env_hashes))) => {
+                                        if let Some(account) =
+                                            context.accounts.get_mut(&account_hash)
+                                        {
+                                            account.set_virtual_mailbox_envelopes(
+                                                mailbox_hash,
+                                                &env_hashes,
+                                            );
+                                        }
+                                    }
+                                    Ok(Some(Err(err))) => {
+                                        context.replies.push_back(UIEvent::StatusEvent(
+                                            StatusEvent::DisplayMessage(tr!(
+                                                "Could not evaluate mailbox query: {}",
+                                                err
+                                            )),
+                                        ));
+                                    }
+                                    Err(_) | Ok(None) => {}
+                                },
+                            ))),
+                            log_level: LogLevel::TRACE,
+                        },
+                    );
+                    return;
+                }
+                Err(err) => {
+                    log::warn!(
+                        "{}: could not search with the backend, evaluating mailbox query \
+                         locally: {}",
+                        &self.name,
+                        err
+                    );
+                }
+            }
+        }
+        let env_hashes = {
+            let mailboxes = self.collection.mailboxes.read().unwrap();
+            let envelopes = self.collection.envelopes.read().unwrap();
+            mailboxes
+                .iter()
+                .filter(|(h, _)| !self.virtual_mailboxes.contains_key(*h))
+                .flat_map(|(_, env_hashes)| env_hashes.iter())
+                .filter(|h| {
+                    envelopes
+                        .get(h)
+                        .map(|e| e.is_match(&query))
+                        .unwrap_or(false)
+                })
+                .cloned()
+                .collect::<HashSet<EnvelopeHash>>()
+                .into_iter()
+                .collect::<Vec<EnvelopeHash>>()
+        };
+        self.set_virtual_mailbox_envelopes(mailbox_hash, &env_hashes);
+    }
+
+    /// Sets the envelopes of virtual mailbox `mailbox_hash` to those of
+    /// `env_hashes` that are loaded.
+    pub fn set_virtual_mailbox_envelopes(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        env_hashes: &[EnvelopeHash],
+    ) {
+        self.collection
+            .set_mailbox_envelopes(mailbox_hash, env_hashes);
+        self.update_virtual_mailbox_count(mailbox_hash);
+        self.sender
+            .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
+                self.hash,
+                mailbox_hash,
+            ))))
+            .unwrap();
+    }
+
+    /// Adds envelope `env_hash`, which used to be `old_hash`, to the loaded
+    /// virtual mailboxes whose query it matches and removes it from the rest.
+    fn update_virtual_mailboxes(&mut self, old_hash: Option<EnvelopeHash>, env_hash: EnvelopeHash) {
+        use melib::search::QueryTrait;
+
+        let loaded = self
+            .virtual_mailboxes
+            .keys()
+            .filter(|h| self.mailbox_entries[*h].status.is_available())
+            .cloned()
+            .collect::<Vec<MailboxHash>>();
+        for mailbox_hash in loaded {
+            let query = self.virtual_mailboxes[&mailbox_hash].query().clone();
+            if query.unsupported_atom(SearchCapabilities::LOCAL).is_some() {
+                /* Only the backend can tell. */
+                self.evaluate_virtual_mailbox(mailbox_hash);
+                continue;
+            }
+            let was_member = {
+                let env_hashes = self.collection.get_mailbox(mailbox_hash);
+                env_hashes.contains(&env_hash)
+                    || old_hash.map(|h| env_hashes.contains(&h)).unwrap_or(false)
+            };
+            if let Some(old_hash) = old_hash.filter(|h| *h != env_hash) {
+                self.collection.remove_from_mailbox(old_hash, mailbox_hash);
+            }
+            let is_member = self.collection.contains_key(&env_hash)
+                && self.collection.get_env(env_hash).is_match(&query);
+            if is_member {
+                self.collection.add_to_mailbox(env_hash, mailbox_hash);
+            } else if was_member {
+                self.collection.remove_from_mailbox(env_hash, mailbox_hash);
+            }
+            if is_member || was_member {
+                self.update_virtual_mailbox_count(mailbox_hash);
+                self.sender
+                    .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
+                        self.hash,
+                        mailbox_hash,
+                    ))))
+                    .unwrap();
             }
         }
     }
 
+    fn update_virtual_mailbox_count(&self, mailbox_hash: MailboxHash) {
+        if let Some(mailbox) = self.virtual_mailboxes.get(&mailbox_hash) {
+            let envelopes = self.collection.envelopes.read().unwrap();
+            let env_hashes = self.collection.get_mailbox(mailbox_hash);
+            let unseen = env_hashes
+                .iter()
+                .filter(|h| envelopes.get(h).map(|e| !e.is_seen()).unwrap_or(false))
+                .count();
+            mailbox.set_count(unseen, env_hashes.len());
+        }
+    }
+
     pub fn mailbox_by_path(&self, path: &str) -> Result<MailboxHash> {
         if let Some((mailbox_hash, _)) = self
             .mailbox_entries
@@ -1640,6 +2930,18 @@ impl Account {
         }
     }
 
+    /// Returns the mailbox of the saved search whose shortcut is `key`, if
+    /// any.
+    pub fn saved_search_by_key(&self, key: &crate::terminal::Key) -> Option<MailboxHash> {
+        self.settings
+            .conf
+            .searches
+            .iter()
+            .find(|(_, search)| search.shortcut() == Some(key))
+            .map(|(name, _)| VirtualMailbox::saved_search_hash(name))
+            .filter(|mailbox_hash| self.mailbox_entries.contains_key(mailbox_hash))
+    }
+
     pub fn process_event(&mut self, job_id: &JobId) -> bool {
         self.sender
             .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
@@ -1703,12 +3005,20 @@ impl Account {
                                 .and_modify(|entry| {
                                     entry.status = MailboxStatus::Available;
                                 });
+                            #[cfg(feature = "sqlite3")]
+                            if self.settings.conf.search_backend
+                                == crate::conf::SearchBackend::Sqlite3
+                                && !self.background_paused
+                            {
+                                self.index_new_envelopes(mailbox_hash);
+                            }
                             self.sender
                                 .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
                                     self.hash,
                                     mailbox_hash,
                                 ))))
                                 .unwrap();
+                            self.evaluate_virtual_mailboxes();
                             return true;
                         }
                         Ok(Some((Some(Err(err)), _))) => {
@@ -1749,6 +3059,20 @@ impl Account {
                                 .into_iter()
                                 .map(|e| (e.hash(), e))
                                 .collect::<HashMap<EnvelopeHash, Envelope>>();
+                            self.mailbox_entries
+                                .entry(mailbox_hash)
+                                .and_modify(|entry| {
+                                    if let MailboxStatus::Parsing(done, _) = entry.status {
+                                        let done = done + envelopes.len();
+                                        let total =
+                                            entry.ref_mailbox.count().ok().unwrap_or((0, 0)).1;
+                                        entry.status = MailboxStatus::Parsing(
+                                            done,
+                                            std::cmp::max(done, total),
+                                        );
+                                    }
+                                });
+                            self.learn_known_senders(envelopes.values());
                             if let Some(updated_mailboxes) =
                                 self.collection
                                     .merge(envelopes, mailbox_hash, self.sent_mailbox)
@@ -1789,6 +3113,9 @@ impl Account {
                                 self.watch();
                             }
                             self.is_online = Ok(());
+                            if std::mem::take(&mut self.resync_on_online) {
+                                let _ = self.refresh_all();
+                            }
                             return true;
                         }
                         self.is_online = is_online;
@@ -1808,6 +3135,7 @@ impl Account {
                         Err(_) => { /* canceled */ }
                         Ok(None) => {}
                         Ok(Some(Ok(()))) => {
+                            self.last_synced = Some(melib::datetime::now());
                             if self.is_online.is_err()
                                 && !self
                                     .is_online
@@ -1882,7 +3210,7 @@ impl Account {
                         self.sender
                             .send(ThreadEvent::UIEvent(UIEvent::Notification(
                                 Some(format!("{}: could not save message", &self.name)),
-                                format!(
+                                tr!(
                                     "Message was stored in {} so that you can restore it manually.",
                                     file.path.display()
                                 ),
@@ -1896,7 +3224,7 @@ impl Account {
                     if let Ok(Some(Err(err))) = handle.chan.try_recv() {
                         self.sender
                             .send(ThreadEvent::UIEvent(UIEvent::Notification(
-                                Some("Could not send message".to_string()),
+                                Some(tr!("Could not send message")),
                                 err.to_string(),
                                 Some(crate::types::NotificationType::Error(err.kind)),
                             )))
@@ -1993,6 +3321,7 @@ impl Account {
                                 build_mailboxes_order(
                                     &mut self.tree,
                                     &self.mailbox_entries,
+                                    &self.settings.conf,
                                     &mut self.mailboxes_order,
                                 );
                                 //Ok(format!("`{}` successfully created.",
@@ -2062,6 +3391,7 @@ impl Account {
                             build_mailboxes_order(
                                 &mut self.tree,
                                 &self.mailbox_entries,
+                                &self.settings.conf,
                                 &mut self.mailboxes_order,
                             );
                             // FIXME remove from settings as well
@@ -2076,7 +3406,82 @@ impl Account {
                         }
                     }
                 }
-                //JobRequest::RenameMailbox,
+                JobRequest::RenameMailbox {
+                    mailbox_hash,
+                    ref new_path,
+                    ref mut handle,
+                } => {
+                    match handle.chan.try_recv() {
+                        Err(_) => { /* canceled */ }
+                        Ok(None) => {}
+                        Ok(Some(Err(err))) => {
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!(
+                                        "{}: could not rename mailbox to {}",
+                                        &self.name, new_path
+                                    )),
+                                    err.to_string(),
+                                    Some(crate::types::NotificationType::Error(err.kind)),
+                                )))
+                                .expect("Could not send event on main channel");
+                        }
+                        Ok(Some(Ok(new_mailbox))) => {
+                            /* Renaming changes the hashes of the mailbox and of its
+                             * descendants, so the mailboxes are listed again. */
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::MailboxDelete((
+                                    self.hash,
+                                    mailbox_hash,
+                                ))))
+                                .unwrap();
+                            if let Some(old_path) = self
+                                .mailbox_entries
+                                .get(&mailbox_hash)
+                                .map(|entry| entry.ref_mailbox.path().to_string())
+                            {
+                                if let Some(conf) = self.settings.mailbox_confs.remove(&old_path) {
+                                    self.settings
+                                        .mailbox_confs
+                                        .insert(new_mailbox.path().to_string(), conf);
+                                }
+                            }
+                            if self.sent_mailbox == Some(mailbox_hash) {
+                                self.sent_mailbox = None;
+                            }
+                            self.collection
+                                .threads
+                                .write()
+                                .unwrap()
+                                .remove(&mailbox_hash);
+                            self.collection
+                                .mailboxes
+                                .write()
+                                .unwrap()
+                                .remove(&mailbox_hash);
+                            let mailboxes_job = self.backend.read().unwrap().mailboxes();
+                            if let Ok(mailboxes_job) = mailboxes_job {
+                                let handle = if self.backend_capabilities.is_async {
+                                    self.job_executor.spawn_specialized(mailboxes_job)
+                                } else {
+                                    self.job_executor.spawn_blocking(mailboxes_job)
+                                };
+                                self.insert_job(handle.job_id, JobRequest::Mailboxes { handle });
+                            }
+                            self.sender
+                                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                    Some(format!(
+                                        "{}: mailbox renamed to {}",
+                                        &self.name,
+                                        new_mailbox.path()
+                                    )),
+                                    String::new(),
+                                    Some(crate::types::NotificationType::Info),
+                                )))
+                                .expect("Could not send event on main channel");
+                        }
+                    }
+                }
                 JobRequest::SetMailboxPermissions { ref mut handle, .. } => {
                     match handle.chan.try_recv() {
                         Err(_) => { /* canceled */ }
@@ -2169,6 +3574,20 @@ impl Account {
                         }
                     }
                 }
+                #[cfg(feature = "carddav")]
+                JobRequest::SyncContacts { ref mut handle } => match handle.chan.try_recv() {
+                    Ok(Some(Ok(result))) => self.finish_contacts_sync(result),
+                    Ok(Some(Err(err))) => {
+                        self.sender
+                            .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                                Some(format!("{}: could not sync contacts", &self.name)),
+                                err.to_string(),
+                                Some(crate::types::NotificationType::Error(err.kind)),
+                            )))
+                            .expect("Could not send event on main channel");
+                    }
+                    Err(_) | Ok(None) => {}
+                },
                 JobRequest::Generic {
                     ref name,
                     ref mut handle,
@@ -2252,9 +3671,54 @@ impl IndexMut<&MailboxHash> for Account {
     }
 }
 
+/// Which query atoms each search engine can evaluate natively. Queries an
+/// engine can't handle are evaluated locally when possible.
+const SEARCH_CAPABILITIES: &[(&str, SearchCapabilities)] = &[
+    (
+        "sqlite3",
+        SearchCapabilities {
+            tags: false,
+            ..SearchCapabilities::ALL
+        },
+    ),
+    (
+        "imap",
+        SearchCapabilities {
+            has_attachment: false,
+            attachment_content: false,
+            ..SearchCapabilities::ALL
+        },
+    ),
+    ("notmuch", SearchCapabilities::ALL),
+    ("jmap", SearchCapabilities::ALL),
+    ("local", SearchCapabilities::LOCAL),
+];
+
+fn search_capabilities(engine: &str) -> SearchCapabilities {
+    SEARCH_CAPABILITIES
+        .iter()
+        .find(|(e, _)| *e == engine)
+        .map(|(_, caps)| *caps)
+        .unwrap_or(SearchCapabilities::ALL)
+}
+
+/// Creates the file `path` with `bytes`, readable only by the owner.
+fn write_private_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    use std::io::Write;
+
+    let mut f = fs::File::create(path)
+        .chain_err_summary(|| format!("Could not create {}", path.display()))?;
+    let mut permissions = f.metadata()?.permissions();
+    permissions.set_mode(0o600); // Read/write for owner only.
+    f.set_permissions(permissions)?;
+    f.write_all(bytes)?;
+    Ok(())
+}
+
 fn build_mailboxes_order(
     tree: &mut Vec<MailboxNode>,
     mailbox_entries: &IndexMap<MailboxHash, MailboxEntry>,
+    conf: &FileAccount,
     mailboxes_order: &mut Vec<MailboxHash>,
 ) {
     tree.clear();
@@ -2285,75 +3749,42 @@ fn build_mailboxes_order(
         }
     }
 
-    macro_rules! mailbox_eq_key {
-        ($mailbox:expr) => {{
-            if let Some(sort_order) = $mailbox.conf.mailbox_conf.sort_order {
-                (0, sort_order, $mailbox.ref_mailbox.path())
-            } else {
-                (1, 0, $mailbox.ref_mailbox.path())
-            }
-        }};
-    }
-    tree.sort_unstable_by(|a, b| {
-        if mailbox_entries[&b.hash]
-            .conf
-            .mailbox_conf
-            .sort_order
-            .is_none()
-            && mailbox_entries[&b.hash]
-                .ref_mailbox
-                .path()
-                .eq_ignore_ascii_case("INBOX")
-        {
-            std::cmp::Ordering::Greater
-        } else if mailbox_entries[&a.hash]
-            .conf
-            .mailbox_conf
-            .sort_order
-            .is_none()
-            && mailbox_entries[&a.hash]
-                .ref_mailbox
-                .path()
-                .eq_ignore_ascii_case("INBOX")
+    /* Mailboxes of the `mailbox_order` setting come first among their siblings,
+     * in its order, then INBOX, then mailboxes with a `sort_order`, then the
+     * rest by path, and saved searches last, in their order. */
+    let sort_key = |hash: MailboxHash| {
+        let mailbox = &mailbox_entries[&hash];
+        let path = mailbox.ref_mailbox.path();
+        if let Some(i) = conf
+            .searches
+            .keys()
+            .position(|name| VirtualMailbox::saved_search_hash(name) == hash)
         {
-            std::cmp::Ordering::Less
+            (4, i, path)
+        } else if let Some(i) = conf.mailbox_order.iter().position(|p| p == path) {
+            (0, i, path)
+        } else if let Some(sort_order) = mailbox.conf.mailbox_conf.sort_order {
+            (2, sort_order, path)
+        } else if path.eq_ignore_ascii_case("INBOX") {
+            (1, 0, path)
         } else {
-            mailbox_eq_key!(mailbox_entries[&a.hash])
-                .cmp(&mailbox_eq_key!(mailbox_entries[&b.hash]))
+            (3, 0, path)
+        }
+    };
+    fn sort_rec<'a>(
+        nodes: &mut [MailboxNode],
+        sort_key: &impl Fn(MailboxHash) -> (u8, usize, &'a str),
+    ) {
+        nodes.sort_unstable_by(|a, b| sort_key(a.hash).cmp(&sort_key(b.hash)));
+        for n in nodes.iter_mut() {
+            sort_rec(&mut n.children, sort_key);
         }
-    });
+    }
+    sort_rec(tree, &sort_key);
 
     let mut stack: SmallVec<[Option<&MailboxNode>; 16]> = SmallVec::new();
-    for n in tree.iter_mut() {
+    for n in tree.iter() {
         mailboxes_order.push(n.hash);
-        n.children.sort_unstable_by(|a, b| {
-            if mailbox_entries[&b.hash]
-                .conf
-                .mailbox_conf
-                .sort_order
-                .is_none()
-                && mailbox_entries[&b.hash]
-                    .ref_mailbox
-                    .path()
-                    .eq_ignore_ascii_case("INBOX")
-            {
-                std::cmp::Ordering::Greater
-            } else if mailbox_entries[&a.hash]
-                .conf
-                .mailbox_conf
-                .sort_order
-                .is_none()
-                && mailbox_entries[&a.hash]
-                    .ref_mailbox
-                    .path()
-                    .eq_ignore_ascii_case("INBOX")
-            {
-                std::cmp::Ordering::Less
-            } else {
-                mailbox_eq_key!(mailbox_entries[&a.hash])
-                    .cmp(&mailbox_eq_key!(mailbox_entries[&b.hash]))
-            }
-        });
         stack.extend(n.children.iter().rev().map(Some));
         while let Some(Some(next)) = stack.pop() {
             mailboxes_order.push(next.hash);
@@ -0,0 +1,234 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! User-defined commands, made of sequences of built-in commands.
+
+use indexmap::IndexMap;
+use melib::{
+    datetime::{self, UnixTimestamp},
+    error::{Error, Result},
+};
+
+use super::DotAddressable;
+use crate::terminal::Key;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandsSettings {
+    /// New command names, each expanding to a `;` separated sequence of
+    /// commands. `$1` to `$9` are replaced with the alias's arguments, `$@`
+    /// with all of them and `%` sequences with the current date, as in
+    /// strftime(3).
+    /// Default: empty
+    #[serde(default)]
+    pub aliases: IndexMap<String, String>,
+    /// Keys that run aliases, keyed by alias name.
+    /// Default: empty
+    #[serde(default)]
+    pub shortcuts: IndexMap<String, Key>,
+}
+
+impl CommandsSettings {
+    pub fn validate(&self) -> Result<()> {
+        for name in self.aliases.keys() {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ';') {
+                return Err(Error::new(format!(
+                    "commands.aliases: `{}` is not a valid command name, it must be a single word.",
+                    name
+                )));
+            }
+        }
+        for name in self.shortcuts.keys() {
+            if !self.aliases.contains_key(name) {
+                return Err(Error::new(format!(
+                    "commands.shortcuts: there is no alias named `{}`.",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the alias that `key` runs, if any.
+    pub fn alias_for_key(&self, key: &Key) -> Option<&str> {
+        self.shortcuts
+            .iter()
+            .find(|(_, k)| *k == key)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// If `input` is an alias invocation, returns the commands it expands to.
+    pub fn expand(&self, input: &str) -> Option<Vec<String>> {
+        let input = input.trim();
+        let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        let template = self.aliases.get(name)?;
+        Some(expand_template(
+            template,
+            &split_arguments(args),
+            datetime::now(),
+        ))
+    }
+}
+
+/// Splits `input` on whitespace, except inside double quotes. Quotes are
+/// kept, so that an argument can be passed on as a quoted command argument.
+fn split_arguments(input: &str) -> Vec<&str> {
+    let mut ret = vec![];
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, c) in input.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        if c.is_whitespace() && !in_quotes {
+            if let Some(s) = start.take() {
+                ret.push(&input[s..i]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        ret.push(&input[s..]);
+    }
+    ret
+}
+
+/// Expands alias `template` into its commands, substituting `args` and the
+/// date of `timestamp`.
+fn expand_template(template: &str, args: &[&str], timestamp: UnixTimestamp) -> Vec<String> {
+    template
+        .split(';')
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(|command| {
+            let mut ret = String::with_capacity(command.len());
+            let mut chars = command.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (c, chars.peek().copied()) {
+                    ('$', Some('$')) | ('%', Some('%')) => {
+                        ret.push(c);
+                        chars.next();
+                    }
+                    ('$', Some('@')) => {
+                        ret.push_str(&args.join(" "));
+                        chars.next();
+                    }
+                    ('$', Some(n @ '1'..='9')) => {
+                        if let Some(arg) = args.get(n as usize - '1' as usize) {
+                            ret.push_str(arg);
+                        }
+                        chars.next();
+                    }
+                    ('%', Some(spec)) => {
+                        ret.push_str(&datetime::timestamp_to_string(
+                            timestamp,
+                            Some(&format!("%{}", spec)),
+                            false,
+                        ));
+                        chars.next();
+                    }
+                    _ => ret.push(c),
+                }
+            }
+            ret
+        })
+        .collect()
+}
+
+impl DotAddressable for CommandsSettings {
+    fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
+        match path.first() {
+            Some(field) => {
+                let tail = &path[1..];
+                match *field {
+                    "aliases" => self.aliases.lookup(field, tail),
+                    "shortcuts" => self.shortcuts.lookup(field, tail),
+                    other => Err(Error::new(format!(
+                        "{} has no field named {}",
+                        parent_field, other
+                    ))),
+                }
+            }
+            None => Ok(toml::to_string(self).map_err(|err| err.to_string())?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_alias_expand() {
+        let mut settings = CommandsSettings::default();
+        settings.aliases.insert(
+            "triage".to_string(),
+            "tag add followup; moveto Archive/%Y".to_string(),
+        );
+        settings
+            .aliases
+            .insert("file".to_string(), "copyto $1 $2;tag add $@".to_string());
+        settings.validate().unwrap();
+
+        assert_eq!(settings.expand("set seen"), None);
+        assert_eq!(
+            settings.expand("triage"),
+            Some(vec![
+                "tag add followup".to_string(),
+                format!(
+                    "moveto Archive/{}",
+                    datetime::timestamp_to_string(datetime::now(), Some("%Y"), false)
+                ),
+            ])
+        );
+        assert_eq!(
+            expand_template("file $1 $2; tag add $@", &split_arguments(""), 0),
+            vec!["file  ".to_string(), "tag add ".to_string()]
+        );
+        assert_eq!(
+            expand_template(
+                "copyto $1 $2; tag add $@ $$1 100%%",
+                &split_arguments("work  \"Sent Items\""),
+                0
+            ),
+            vec![
+                "copyto work \"Sent Items\"".to_string(),
+                "tag add work \"Sent Items\" $1 100%".to_string()
+            ]
+        );
+
+        settings
+            .shortcuts
+            .insert("file".to_string(), Key::Char('F'));
+        assert_eq!(settings.alias_for_key(&Key::Char('F')), Some("file"));
+        assert_eq!(settings.alias_for_key(&Key::Char('G')), None);
+        settings
+            .shortcuts
+            .insert("unknown".to_string(), Key::Char('U'));
+        settings.validate().unwrap_err();
+        settings.shortcuts.clear();
+        settings
+            .aliases
+            .insert("two words".to_string(), "quit".to_string());
+        settings.validate().unwrap_err();
+    }
+}
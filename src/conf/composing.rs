@@ -75,11 +75,13 @@ pub struct ComposingSettings {
     pub store_sent_mail: bool,
     /// The attribution line appears above the quoted reply text.
     /// The format specifiers for the replied address are:
-    /// - `%+f` — the sender's name and email address.
-    /// - `%+n` — the sender's name (or email address, if no name is included).
-    /// - `%+a` — the sender's email address.
+    /// - `%{from}` or `%+f` — the sender's name and email address.
+    /// - `%{name}` or `%+n` — the sender's name (or email address, if no name
+    ///   is included).
+    /// - `%{email}` or `%+a` — the sender's email address.
+    /// - `%{date}` — the date, as `%a, %0e %b %Y %H:%M`.
     /// The format string is passed to strftime(3) with the replied envelope's
-    /// date. Default: "On %a, %0e %b %Y %H:%M, %+f wrote:%n"
+    /// date. Default: "On %{date}, %{from} wrote:%n"
     #[serde(default = "none")]
     pub attribution_format_string: Option<String>,
     /// Whether the strftime call for the attribution string uses the POSIX
@@ -109,6 +111,77 @@ pub struct ComposingSettings {
     /// Disabled `compose-hooks`.
     #[serde(default, alias = "disabled-compose-hooks")]
     pub disabled_compose_hooks: Vec<String>,
+    /// Add a `Mail-Followup-To` header when sending a reply to a mailing
+    /// list, so that follow-ups go to the list and the other recipients
+    /// instead of you.
+    /// Default: false
+    #[serde(default = "false_val", alias = "followup-to")]
+    pub followup_to: bool,
+    /// Rules that add Cc or Bcc recipients to a draft when one of its `To`
+    /// addresses matches a pattern. The additions show up in the composer's
+    /// header fields and can be removed from each draft.
+    /// Default: empty
+    #[serde(default, alias = "auto-recipients")]
+    pub auto_recipients: Vec<AutoRecipientRule>,
+    /// Send-time policies keyed by recipient domain. For each recipient the
+    /// first matching rule applies.
+    /// Default: empty
+    #[serde(default, alias = "outgoing-policies")]
+    pub outgoing_policies: Vec<OutgoingPolicyRule>,
+    /// Short aliases of addresses, e.g. `boss = "Jane Doe <jane@corp>"`,
+    /// expanded in the `To`, `Cc` and `Bcc` fields on Tab and when sending.
+    /// The nicknames of address book contacts are expanded too.
+    /// Default: empty
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Headers shown in the composer form, in this order. The `From`, `To`,
+    /// `Cc`, `Bcc` and `Subject` headers of a draft are also shown whenever
+    /// they have a value.
+    /// Default: ["Date", "From", "To", "Cc", "Bcc", "Subject"]
+    #[serde(default = "form_headers", alias = "form-headers")]
+    pub form_headers: Vec<String>,
+    /// Add an `X-Mailer` header with the meli version in new drafts.
+    /// Default: false
+    #[serde(default = "false_val", alias = "insert-x-mailer")]
+    pub insert_x_mailer: bool,
+    /// Downscale image attachments larger than `downscale_images_threshold`
+    /// when they are added to a draft.
+    /// Default: ask
+    #[serde(default = "ask", alias = "downscale-images")]
+    pub downscale_images: ToggleFlag,
+    /// Size in bytes above which image attachments are downscaled.
+    /// Default: 1048576
+    #[serde(
+        default = "downscale_images_threshold",
+        alias = "downscale-images-threshold"
+    )]
+    pub downscale_images_threshold: usize,
+    /// Command that reads an image from its standard input and writes the
+    /// downscaled image to its standard output.
+    /// Default: "convert - -resize '1600x1600>' -quality 85 jpeg:-"
+    #[serde(
+        default = "downscale_images_command",
+        alias = "downscale-images-command"
+    )]
+    pub downscale_images_command: String,
+    /// Folder with the message templates that `compose-from-template`
+    /// offers. Each file is a draft, headers and body, whose `%{...}`
+    /// placeholders are filled in when it is used.
+    /// Default: None
+    #[serde(default = "none", alias = "templates-folder")]
+    pub templates_folder: Option<String>,
+    /// File whose contents are substituted for the `%{account_signature}`
+    /// placeholder of templates.
+    /// Default: None
+    #[serde(default = "none", alias = "signature-file")]
+    pub signature_file: Option<String>,
+    /// Signature appended to new drafts and replies after a `-- ` line: a
+    /// literal string, a file or the output of a shell command, e.g.
+    /// `signature = { file = "~/.signature" }`. It can be removed from a
+    /// draft with `toggle signature`.
+    /// Default: None
+    #[serde(default = "none")]
+    pub signature: Option<SignatureSource>,
 }
 
 impl Default for ComposingSettings {
@@ -129,6 +202,18 @@ impl Default for ComposingSettings {
             reply_prefix: res(),
             custom_compose_hooks: vec![],
             disabled_compose_hooks: vec![],
+            followup_to: false,
+            auto_recipients: vec![],
+            outgoing_policies: vec![],
+            aliases: HashMap::default(),
+            form_headers: form_headers(),
+            insert_x_mailer: false,
+            downscale_images: ToggleFlag::Ask,
+            downscale_images_threshold: downscale_images_threshold(),
+            downscale_images_command: downscale_images_command(),
+            templates_folder: None,
+            signature_file: None,
+            signature: None,
         }
     }
 }
@@ -137,6 +222,21 @@ fn res() -> String {
     "Re:".to_string()
 }
 
+pub fn form_headers() -> Vec<String> {
+    ["Date", "From", "To", "Cc", "Bcc", "Subject"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect()
+}
+
+fn downscale_images_threshold() -> usize {
+    1024 * 1024
+}
+
+fn downscale_images_command() -> String {
+    "convert - -resize '1600x1600>' -quality 85 jpeg:-".to_string()
+}
+
 macro_rules! named_unit_variant {
     ($variant:ident) => {
         pub mod $variant {
@@ -185,6 +285,67 @@ pub enum SendMail {
     ShellCommand(String),
 }
 
+/// Cc and Bcc recipients to add when a `To` address matches `to` (See
+/// [`ComposingSettings::auto_recipients`])
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AutoRecipientRule {
+    /// Glob pattern matched case-insensitively against the e-mail address of
+    /// each `To` recipient, e.g. `*@customer.example.com`.
+    #[serde(deserialize_with = "non_empty_string")]
+    pub to: String,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+}
+
+/// What to do when sending to a recipient matched by an
+/// [`OutgoingPolicyRule`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutgoingPolicy {
+    /// Send normally; use it to exempt domains from later rules.
+    Allow,
+    /// Ask for confirmation with a warning.
+    Warn,
+    /// Refuse to send.
+    Block,
+    /// Refuse to send unless the draft is encrypted with at least one key.
+    RequireEncryption,
+    /// Remove HTML parts from the draft before sending.
+    PlainText,
+}
+
+/// A send-time policy for recipients whose domain matches `domain` (See
+/// [`ComposingSettings::outgoing_policies`])
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OutgoingPolicyRule {
+    /// Glob pattern matched case-insensitively against the domain of each
+    /// recipient, e.g. `*.example.com`.
+    #[serde(deserialize_with = "non_empty_string")]
+    pub domain: String,
+    /// Only apply the rule when the `From` address matches this glob
+    /// pattern, e.g. `*@work.example.com`.
+    #[serde(default = "none")]
+    pub from: Option<String>,
+    pub policy: OutgoingPolicy,
+}
+
+/// Where the signature of new drafts comes from (See
+/// [`ComposingSettings::signature`])
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SignatureSource {
+    /// The signature itself.
+    Text(String),
+    /// Path of a file with the signature.
+    File(String),
+    /// Shell command whose standard output is the signature.
+    Command(String),
+}
+
 /// Shell command compose hooks (See [`Hook`])
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
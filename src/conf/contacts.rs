@@ -0,0 +1,235 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Per-contact display settings and the address book settings of accounts.
+
+use indexmap::IndexMap;
+use melib::{
+    addressbook::Card,
+    error::{Error, Result},
+    Address,
+};
+
+use super::DotAddressable;
+use crate::terminal::Color;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContactsSettings {
+    /// Colors and labels of known senders, keyed by e-mail address or by
+    /// domain. A domain also matches its subdomains.
+    /// Default: empty
+    #[serde(default)]
+    pub highlights: IndexMap<String, ContactHighlight>,
+}
+
+impl ContactsSettings {
+    /// Returns the highlight of `address`. An exact address match takes
+    /// precedence over a domain match.
+    pub fn find(&self, address: &Address) -> Option<&ContactHighlight> {
+        let email = address.get_email().to_lowercase();
+        let (_, domain) = email.rsplit_once('@')?;
+        if let Some(highlight) = self
+            .highlights
+            .iter()
+            .find(|(key, _)| key.to_lowercase() == email)
+            .map(|(_, highlight)| highlight)
+        {
+            return Some(highlight);
+        }
+        self.highlights
+            .iter()
+            .find(|(key, _)| {
+                let key = key.trim_start_matches('@').to_lowercase();
+                !key.contains('@')
+                    && domain
+                        .strip_suffix(key.as_str())
+                        .map_or(false, |rest| rest.is_empty() || rest.ends_with('.'))
+            })
+            .map(|(_, highlight)| highlight)
+    }
+}
+
+/// How messages of a contact are shown in listings and in the mail view.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContactHighlight {
+    /// Foreground color of the contact's rows and `From:` header.
+    #[serde(default)]
+    pub color: Option<Color>,
+    /// Short label shown before the contact's address.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl ContactHighlight {
+    /// Reads the highlight from the `COLOR` and `LABEL` properties of an
+    /// address book card.
+    pub fn from_card(card: &Card) -> Option<Self> {
+        let color = card
+            .extra_property("COLOR")
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .and_then(|c| toml::Value::String(c.to_string()).try_into::<Color>().ok());
+        let label = card
+            .extra_property("LABEL")
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string);
+        if color.is_none() && label.is_none() {
+            return None;
+        }
+        Some(Self { color, label })
+    }
+
+    /// Returns `from` prefixed with the label, if any.
+    pub fn labeled(&self, from: String) -> String {
+        match self.label {
+            Some(ref label) => format!("[{}] {}", label, from),
+            None => from,
+        }
+    }
+}
+
+/// Address book settings of an account, in its `contacts` section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountContactsSettings {
+    /// URL of a CardDAV address book collection to sync the account's address
+    /// book with.
+    /// Default: None
+    #[serde(default, alias = "carddav-url")]
+    pub carddav_url: Option<String>,
+    /// User name for the CardDAV server.
+    /// Default: the account's identity
+    #[serde(default, alias = "carddav-username")]
+    pub carddav_username: Option<String>,
+    /// Password for the CardDAV server.
+    /// Default: None
+    #[serde(default, alias = "carddav-password")]
+    pub carddav_password: Option<String>,
+    /// Command that prints the password for the CardDAV server.
+    /// Default: None
+    #[serde(default, alias = "carddav-password-command")]
+    pub carddav_password_command: Option<String>,
+    /// Sync the address book every this many seconds. With 0 it is only
+    /// synced on startup and with the `sync-contacts` command.
+    /// Default: 900
+    #[serde(default = "sync_interval_val", alias = "sync-interval")]
+    pub sync_interval: u64,
+}
+
+fn sync_interval_val() -> u64 {
+    900
+}
+
+impl Default for AccountContactsSettings {
+    fn default() -> Self {
+        AccountContactsSettings {
+            carddav_url: None,
+            carddav_username: None,
+            carddav_password: None,
+            carddav_password_command: None,
+            sync_interval: sync_interval_val(),
+        }
+    }
+}
+
+impl AccountContactsSettings {
+    /// Returns the CardDAV password, running `carddav_password_command` if
+    /// it's set.
+    pub fn carddav_password(&self) -> Result<String> {
+        if let Some(ref cmd) = self.carddav_password_command {
+            let output = std::process::Command::new("sh")
+                .args(["-c", cmd])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .output()?;
+            if output.status.success() {
+                Ok(String::from_utf8_lossy(&output.stdout)
+                    .trim_end()
+                    .to_string())
+            } else {
+                Err(Error::new(format!(
+                    "carddav_password_command `{}` returned {}: {}",
+                    cmd,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr)
+                )))
+            }
+        } else if let Some(ref password) = self.carddav_password {
+            Ok(password.clone())
+        } else {
+            Err(Error::new(
+                "Neither carddav_password nor carddav_password_command is set.",
+            ))
+        }
+    }
+}
+
+impl DotAddressable for ContactsSettings {}
+impl DotAddressable for ContactHighlight {}
+impl DotAddressable for AccountContactsSettings {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_highlight() {
+        let settings: ContactsSettings = toml::from_str(
+            r#"
+[highlights]
+"boss@corp.example" = { color = "Red", label = "BOSS" }
+"@corp.example" = { color = "Blue" }
+"#,
+        )
+        .unwrap();
+        let boss = Address::new(None, "Boss@Corp.Example".to_string());
+        let colleague = Address::new(None, "jane@corp.example".to_string());
+        let sub = Address::new(None, "ci@build.corp.example".to_string());
+        let other = Address::new(None, "jane@notcorp.example".to_string());
+        assert_eq!(settings.find(&boss).unwrap().label.as_deref(), Some("BOSS"));
+        assert_eq!(
+            settings.find(&colleague).unwrap().color,
+            Some(Color::Byte(12))
+        );
+        assert!(settings.find(&sub).is_some());
+        assert!(settings.find(&other).is_none());
+        assert_eq!(
+            settings.find(&boss).unwrap().labeled("boss".to_string()),
+            "[BOSS] boss"
+        );
+
+        let mut card = Card::new();
+        assert_eq!(ContactHighlight::from_card(&card), None);
+        card.set_extra_property("COLOR", "Red".to_string());
+        card.set_extra_property("LABEL", " ".to_string());
+        assert_eq!(
+            ContactHighlight::from_card(&card),
+            Some(ContactHighlight {
+                color: Some(Color::Byte(9)),
+                label: None,
+            })
+        );
+    }
+}
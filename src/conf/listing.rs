@@ -19,9 +19,15 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use melib::{search::Query, Error, Result};
+use std::collections::HashMap;
 
-use super::{default_vals::*, DotAddressable, IndexStyle};
+use melib::{
+    search::{Query, QueryTrait},
+    Envelope, Error, Result, UnixTimestamp,
+};
+
+use super::{default_vals::*, DateTimezone, DotAddressable, IndexStyle, ThemeAttribute};
+use crate::terminal::{Attr, Color};
 
 /// Settings for mail listings
 ///
@@ -73,6 +79,12 @@ pub struct ListingSettings {
     #[serde(default = "none", alias = "datetime-fmt")]
     pub datetime_fmt: Option<String>,
 
+    /// Timezone to show dates in: "sender", "local", "utc" or a fixed offset
+    /// such as "+0200". Listings of threads show "sender" dates as "local".
+    /// Default: "local"
+    #[serde(default)]
+    pub timezone: DateTimezone,
+
     /// Show recent dates as `X {minutes,hours,days} ago`, up to 7 days.
     /// Default: true
     #[serde(default = "true_val", alias = "recent-dates")]
@@ -135,8 +147,170 @@ pub struct ListingSettings {
     /// Default: "true"
     #[serde(default = "true_val")]
     pub thread_subject_pack: bool,
+
+    /// Attach replies whose sender's client dropped their threading headers
+    /// to the thread they most likely belong to, judging by subject,
+    /// participants and date.
+    /// Default: "false"
+    #[serde(default = "false_val", alias = "heuristic-threading")]
+    pub heuristic_threading: bool,
+
+    /// Colors for rows of messages older than a number of days, e.g. to make
+    /// unanswered mail stand out. If several rules apply, the one with the
+    /// most days wins.
+    /// Default: []
+    #[serde(default, alias = "age-highlight")]
+    pub age_highlight: Vec<AgeHighlight>,
+
+    /// Seconds during which deleting, moving or changing the flags of
+    /// messages can be undone with the `undo` shortcut. `0` disables undo.
+    /// Default: 30
+    #[serde(default = "default_undo_grace_period", alias = "undo-grace-period")]
+    pub undo_grace_period: u64,
+
+    /// What the `open_entry` shortcut does: "preview", "fullscreen",
+    /// "expand" or "mark_read_and_advance", or a table of these per index
+    /// style.
+    /// Default: "preview"
+    #[serde(default, alias = "open-entry-action")]
+    pub open_entry_action: OpenEntrySetting,
+}
+
+/// What the `open_entry` shortcut does in a mail listing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenEntryAction {
+    /// Open the entry in the listing's view pane.
+    #[default]
+    Preview,
+    /// Open the entry on the whole listing area.
+    Fullscreen,
+    /// Show the messages of the entry's thread without opening any of them.
+    /// Listings of single messages open the entry as with `Preview`.
+    Expand,
+    /// Mark the entry as seen and move the cursor to the next one.
+    MarkReadAndAdvance,
+}
+
+/// An [`OpenEntryAction`] for all index styles, or one per index style.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum OpenEntrySetting {
+    All(OpenEntryAction),
+    PerIndexStyle(HashMap<IndexStyle, OpenEntryAction>),
+}
+
+impl Default for OpenEntrySetting {
+    fn default() -> Self {
+        Self::All(OpenEntryAction::default())
+    }
+}
+
+impl OpenEntrySetting {
+    /// Returns the action of listings of `style`.
+    pub fn get(&self, style: IndexStyle) -> OpenEntryAction {
+        match self {
+            Self::All(action) => *action,
+            Self::PerIndexStyle(actions) => actions.get(&style).copied().unwrap_or_default(),
+        }
+    }
+}
+
+impl DotAddressable for OpenEntrySetting {}
+
+/// A row highlight for messages that are at least `days` days old and match
+/// `query`.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AgeHighlight {
+    pub days: u64,
+    /// Only highlight messages that match this query, e.g.
+    /// `flags:unanswered`. Default: all messages
+    #[serde(default = "none")]
+    pub query: Option<Query>,
+    #[serde(default = "none")]
+    pub fg: Option<Color>,
+    #[serde(default = "none")]
+    pub bg: Option<Color>,
+    /// Added to the row's attributes.
+    #[serde(default = "none")]
+    pub attrs: Option<Attr>,
+}
+
+impl AgeHighlight {
+    /// Returns the rule of `rules` that applies to `envelope` at `now`, if
+    /// any.
+    pub fn find<'r>(
+        rules: &'r [Self],
+        envelope: &Envelope,
+        now: UnixTimestamp,
+    ) -> Option<&'r Self> {
+        let age = now.saturating_sub(envelope.date());
+        rules
+            .iter()
+            .filter(|rule| age >= rule.days.saturating_mul(24 * 60 * 60))
+            .filter(|rule| rule.query.as_ref().map_or(true, |q| envelope.is_match(q)))
+            .max_by_key(|rule| rule.days)
+    }
+
+    /// Returns `row_attr` with this rule's colors and attributes.
+    pub fn apply(&self, row_attr: ThemeAttribute) -> ThemeAttribute {
+        ThemeAttribute {
+            fg: self.fg.unwrap_or(row_attr.fg),
+            bg: self.bg.unwrap_or(row_attr.bg),
+            attrs: self
+                .attrs
+                .map_or(row_attr.attrs, |attrs| row_attr.attrs | attrs),
+        }
+    }
+}
+
+/// Newsletter digest of a mailbox: its threads that match `query` are shown
+/// as one row of the listing per `period`, which expands to the threads. Their
+/// new mail raises no notifications and the mailbox's unread count is dimmed.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DigestSettings {
+    /// Only threads whose first message matches this query, e.g.
+    /// `from:news@example.com or from:letters@example.org`.
+    /// Default: all threads of the mailbox
+    #[serde(default = "none")]
+    pub query: Option<Query>,
+    /// Default: "week"
+    #[serde(default)]
+    pub period: DigestPeriod,
+}
+
+impl DigestSettings {
+    /// Whether `envelope` belongs in a digest.
+    pub fn contains(&self, envelope: &Envelope) -> bool {
+        self.query.as_ref().map_or(true, |q| envelope.is_match(q))
+    }
+}
+
+/// How much time a digest covers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestPeriod {
+    Day,
+    #[default]
+    Week,
+}
+
+impl DigestPeriod {
+    /// Returns the name of the period `timestamp` falls in, in the local
+    /// timezone, which is the same for all timestamps of the period.
+    pub fn name(self, timestamp: UnixTimestamp) -> String {
+        let fmt = match self {
+            Self::Day => "%Y-%m-%d",
+            Self::Week => "week %V of %G",
+        };
+        melib::datetime::timestamp_to_string(timestamp, Some(fmt), false)
+    }
 }
 
+impl DotAddressable for DigestSettings {}
+
 const fn default_divider() -> char {
     ' '
 }
@@ -145,12 +319,17 @@ const fn default_ratio() -> usize {
     90
 }
 
+const fn default_undo_grace_period() -> u64 {
+    30
+}
+
 impl Default for ListingSettings {
     fn default() -> Self {
         Self {
             context_lines: 0,
             show_menu_scrollbar: true,
             datetime_fmt: None,
+            timezone: DateTimezone::default(),
             recent_dates: true,
             filter: None,
             index_style: IndexStyle::default(),
@@ -165,6 +344,10 @@ impl Default for ListingSettings {
             selected_flag: None,
             attachment_flag: None,
             thread_subject_pack: true,
+            heuristic_threading: false,
+            age_highlight: vec![],
+            undo_grace_period: default_undo_grace_period(),
+            open_entry_action: OpenEntrySetting::default(),
         }
     }
 }
@@ -178,6 +361,7 @@ impl DotAddressable for ListingSettings {
                     "context_lines" => self.context_lines.lookup(field, tail),
                     "show_menu_scrollbar" => self.show_menu_scrollbar.lookup(field, tail),
                     "datetime_fmt" => self.datetime_fmt.lookup(field, tail),
+                    "timezone" => self.timezone.lookup(field, tail),
                     "recent_dates" => self.recent_dates.lookup(field, tail),
                     "filter" => self.filter.lookup(field, tail),
                     "index_style" => self.index_style.lookup(field, tail),
@@ -200,6 +384,10 @@ impl DotAddressable for ListingSettings {
                     "selected_flag" => self.selected_flag.lookup(field, tail),
                     "attachment_flag" => self.attachment_flag.lookup(field, tail),
                     "thread_subject_pack" => self.thread_subject_pack.lookup(field, tail),
+                    "heuristic_threading" => self.heuristic_threading.lookup(field, tail),
+                    "age_highlight" => self.age_highlight.lookup(field, tail),
+                    "undo_grace_period" => self.undo_grace_period.lookup(field, tail),
+                    "open_entry_action" => self.open_entry_action.lookup(field, tail),
                     other => Err(Error::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -210,3 +398,119 @@ impl DotAddressable for ListingSettings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use melib::Flag;
+
+    use super::*;
+
+    #[test]
+    fn test_age_highlight() {
+        const DAY: UnixTimestamp = 24 * 60 * 60;
+        let rule = |days, query: Option<&str>, fg| AgeHighlight {
+            days,
+            query: query.map(|q| Query::try_from(q).unwrap()),
+            fg: Some(fg),
+            bg: None,
+            attrs: Some(Attr::BOLD),
+        };
+        let rules = [
+            rule(3, Some("flags:unanswered"), Color::Byte(3)),
+            rule(7, Some("flags:unanswered"), Color::Byte(1)),
+        ];
+        let now = 100 * DAY;
+        let mut envelope = Envelope::default();
+        envelope.set_datetime(now - 2 * DAY);
+        assert_eq!(AgeHighlight::find(&rules, &envelope, now), None);
+        envelope.set_datetime(now - 3 * DAY);
+        assert_eq!(AgeHighlight::find(&rules, &envelope, now), Some(&rules[0]));
+        envelope.set_datetime(now - 30 * DAY);
+        assert_eq!(AgeHighlight::find(&rules, &envelope, now), Some(&rules[1]));
+        envelope.set_flag(Flag::REPLIED, true);
+        assert_eq!(AgeHighlight::find(&rules, &envelope, now), None);
+
+        let row_attr = ThemeAttribute {
+            fg: Color::Default,
+            bg: Color::Byte(8),
+            attrs: Attr::UNDERLINE,
+        };
+        assert_eq!(
+            rules[1].apply(row_attr),
+            ThemeAttribute {
+                fg: Color::Byte(1),
+                bg: Color::Byte(8),
+                attrs: Attr::UNDERLINE | Attr::BOLD,
+            }
+        );
+    }
+
+    #[test]
+    fn test_open_entry_setting() {
+        #[derive(Deserialize)]
+        struct Settings {
+            open_entry_action: OpenEntrySetting,
+        }
+        let parse = |s: &str| toml::from_str::<Settings>(s).unwrap().open_entry_action;
+
+        assert_eq!(
+            OpenEntrySetting::default().get(IndexStyle::Plain),
+            OpenEntryAction::Preview
+        );
+        let setting = parse(r#"open_entry_action = "mark_read_and_advance""#);
+        assert_eq!(
+            setting.get(IndexStyle::Conversations),
+            OpenEntryAction::MarkReadAndAdvance
+        );
+        let setting = parse(
+            r#"[open_entry_action]
+compact = "expand"
+Plain = "fullscreen""#,
+        );
+        assert_eq!(setting.get(IndexStyle::Compact), OpenEntryAction::Expand);
+        assert_eq!(setting.get(IndexStyle::Plain), OpenEntryAction::Fullscreen);
+        assert_eq!(setting.get(IndexStyle::Threaded), OpenEntryAction::Preview);
+        assert!(toml::from_str::<Settings>(r#"open_entry_action = "open""#).is_err());
+    }
+
+    #[test]
+    fn test_digest_settings() {
+        const DAY: UnixTimestamp = 24 * 60 * 60;
+        let digest: DigestSettings = toml::from_str(r#"query = "from:news@example.com""#).unwrap();
+        assert_eq!(digest.period, DigestPeriod::Week);
+        let envelope = |from: &str| {
+            Envelope::from_bytes(
+                format!("From: {}\r\nSubject: Hi\r\n\r\nHello.\r\n", from).as_bytes(),
+                None,
+            )
+            .unwrap()
+        };
+        assert!(digest.contains(&envelope("News <news@example.com>")));
+        let envelope = envelope("friend@example.com");
+        assert!(!digest.contains(&envelope));
+        let digest: DigestSettings = toml::from_str(r#"period = "day""#).unwrap();
+        assert!(digest.contains(&envelope));
+        toml::from_str::<DigestSettings>(r#"period = "year""#).unwrap_err();
+
+        /* 2023-01-03 was a Tuesday. */
+        let tuesday = 1_672_747_200;
+        assert_eq!(
+            DigestPeriod::Week.name(tuesday),
+            DigestPeriod::Week.name(tuesday + 2 * DAY)
+        );
+        assert_ne!(
+            DigestPeriod::Week.name(tuesday),
+            DigestPeriod::Week.name(tuesday + 7 * DAY)
+        );
+        assert_eq!(
+            DigestPeriod::Day.name(tuesday),
+            DigestPeriod::Day.name(tuesday + 60)
+        );
+        assert_ne!(
+            DigestPeriod::Day.name(tuesday),
+            DigestPeriod::Day.name(tuesday + DAY)
+        );
+    }
+}
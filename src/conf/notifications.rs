@@ -19,7 +19,10 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::{fmt, str::FromStr};
+
 use melib::{Error, Result, ToggleFlag};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use super::{
     default_vals::{internal_value_false, none, true_val},
@@ -56,6 +59,12 @@ pub struct NotificationsSettings {
 
     #[serde(default = "none", alias = "sound-file")]
     pub sound_file: Option<String>,
+
+    /// Periods during which new mail notifications are suppressed, e.g.
+    /// `["Mon-Fri 19:00-08:00", "Sat-Sun"]`.
+    /// Default: []
+    #[serde(default, alias = "dnd-schedule")]
+    pub dnd_schedule: Vec<DndPeriod>,
 }
 
 impl Default for NotificationsSettings {
@@ -67,6 +76,7 @@ impl Default for NotificationsSettings {
             xbiff_file_path: None,
             play_sound: ToggleFlag::InternalVal(false),
             sound_file: None,
+            dnd_schedule: vec![],
         }
     }
 }
@@ -82,6 +92,7 @@ impl DotAddressable for NotificationsSettings {
                     "xbiff_file_path" => self.xbiff_file_path.lookup(field, tail),
                     "play_sound" => self.play_sound.lookup(field, tail),
                     "sound_file" => self.sound_file.lookup(field, tail),
+                    "dnd_schedule" => self.dnd_schedule.lookup(field, tail),
                     other => Err(Error::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -92,3 +103,218 @@ impl DotAddressable for NotificationsSettings {
         }
     }
 }
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// A recurring do not disturb period: a set of weekdays and an optional time
+/// range, written as e.g. `Mon-Fri 19:00-08:00`, `Sat,Sun` or `22:00-07:00`.
+///
+/// A time range that ends before it starts continues into the next day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DndPeriod {
+    /// Bit `i` is set if the period starts on weekday `i`, where `0` is
+    /// Monday.
+    days: u8,
+    /// Start and end, in minutes since midnight.
+    start: u16,
+    end: u16,
+}
+
+impl DndPeriod {
+    /// Returns whether `minute` (of the day) of `weekday` (`0` is Monday)
+    /// falls within this period.
+    pub fn contains(&self, weekday: usize, minute: usize) -> bool {
+        let starts_on = |day: usize| self.days & (1 << (day % 7)) != 0;
+        let (start, end) = (usize::from(self.start), usize::from(self.end));
+        if start < end {
+            starts_on(weekday) && (start..end).contains(&minute)
+        } else {
+            (starts_on(weekday) && minute >= start) || (starts_on(weekday + 6) && minute < end)
+        }
+    }
+
+    fn parse_days(s: &str) -> Option<u8> {
+        let day = |name: &str| {
+            WEEKDAYS
+                .iter()
+                .position(|d| name.to_ascii_lowercase().starts_with(d))
+        };
+        let mut days = 0;
+        for part in s.split(',') {
+            match part.trim().split_once('-') {
+                Some((from, to)) => {
+                    let (from, to) = (day(from)?, day(to)?);
+                    let mut i = from;
+                    loop {
+                        days |= 1 << i;
+                        if i == to {
+                            break;
+                        }
+                        i = (i + 1) % 7;
+                    }
+                }
+                None => days |= 1 << day(part.trim())?,
+            }
+        }
+        Some(days)
+    }
+
+    fn parse_time(s: &str) -> Option<u16> {
+        let (hours, minutes) = s.split_once(':')?;
+        let (hours, minutes) = (hours.parse::<u16>().ok()?, minutes.parse::<u16>().ok()?);
+        if hours > 24 || minutes > 59 || (hours == 24 && minutes > 0) {
+            return None;
+        }
+        Some(hours * 60 + minutes)
+    }
+}
+
+impl FromStr for DndPeriod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || {
+            Error::new(format!(
+                "Invalid do not disturb period `{}`, expected e.g. `Mon-Fri 19:00-08:00`, \
+                 `Sat,Sun` or `22:00-07:00`.",
+                s
+            ))
+        };
+        let mut ret = Self {
+            days: 0b111_1111,
+            start: 0,
+            end: MINUTES_PER_DAY,
+        };
+        let mut parts = s.split_whitespace().peekable();
+        if parts.peek().is_none() {
+            return Err(err());
+        }
+        if let Some(days) = parts.peek().filter(|p| !p.contains(':')) {
+            ret.days = Self::parse_days(days).ok_or_else(err)?;
+            parts.next();
+        }
+        if let Some(range) = parts.next() {
+            let (start, end) = range.split_once('-').ok_or_else(err)?;
+            ret.start = Self::parse_time(start).ok_or_else(err)?;
+            ret.end = Self::parse_time(end).ok_or_else(err)?;
+        }
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(ret)
+    }
+}
+
+impl fmt::Display for DndPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = (0..7)
+            .filter(|i| self.days & (1 << i) != 0)
+            .map(|i| WEEKDAYS[i])
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "{}", days)?;
+        if (self.start, self.end) != (0, MINUTES_PER_DAY) {
+            write!(
+                f,
+                " {:02}:{:02}-{:02}:{:02}",
+                self.start / 60,
+                self.start % 60,
+                self.end / 60,
+                self.end % 60
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for DndPeriod {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String>::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for DndPeriod {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Manual override of the do not disturb schedule, set with the `dnd` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DndMode {
+    /// Suppress new mail notifications according to `dnd_schedule`.
+    #[default]
+    Auto,
+    /// Always suppress new mail notifications.
+    On,
+    /// Never suppress new mail notifications.
+    Off,
+}
+
+impl fmt::Display for DndMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DndMode::Auto => write!(f, "auto"),
+            DndMode::On => write!(f, "on"),
+            DndMode::Off => write!(f, "off"),
+        }
+    }
+}
+
+/// Returns whether `timestamp` falls within any period of `schedule`.
+pub fn in_dnd_schedule(schedule: &[DndPeriod], timestamp: melib::UnixTimestamp) -> bool {
+    if schedule.is_empty() {
+        return false;
+    }
+    let (weekday, minute) = melib::datetime::timestamp_to_weekday_minute(timestamp);
+    schedule.iter().any(|p| p.contains(weekday, minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dnd_period() {
+        let nights: DndPeriod = "Mon-Fri 19:00-08:00".parse().unwrap();
+        assert_eq!(nights.to_string(), "mon,tue,wed,thu,fri 19:00-08:00");
+        assert!(nights.contains(0, 19 * 60));
+        assert!(!nights.contains(0, 18 * 60 + 59));
+        /* Friday night continues into Saturday morning. */
+        assert!(nights.contains(5, 7 * 60 + 59));
+        assert!(!nights.contains(5, 8 * 60));
+        /* Sunday night is not part of the period, nor is Monday morning. */
+        assert!(!nights.contains(6, 20 * 60));
+        assert!(!nights.contains(0, 7 * 60));
+
+        let weekends: DndPeriod = "sat,sun".parse().unwrap();
+        assert_eq!(weekends.to_string(), "sat,sun");
+        assert!(weekends.contains(5, 0) && weekends.contains(6, 24 * 60 - 1));
+        assert!(!weekends.contains(0, 0));
+
+        let wrapping: DndPeriod = "Sat-Mon".parse().unwrap();
+        assert_eq!(wrapping.to_string(), "mon,sat,sun");
+
+        let every_night: DndPeriod = "22:00-07:00".parse().unwrap();
+        assert!((0..7).all(|d| every_night.contains(d, 23 * 60) && every_night.contains(d, 0)));
+        assert!(!every_night.contains(3, 12 * 60));
+
+        for invalid in [
+            "",
+            "Someday",
+            "Mon 25:00-08:00",
+            "Mon 19:00",
+            "Mon 19:00-08:00 x",
+        ] {
+            assert!(invalid.parse::<DndPeriod>().is_err(), "{}", invalid);
+        }
+    }
+}
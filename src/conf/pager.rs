@@ -21,9 +21,10 @@
 
 //! Settings for the pager function.
 
-use melib::{Error, Result, ToggleFlag};
+use indexmap::IndexMap;
+use melib::{text_processing::GlobMatch, Error, Result, ToggleFlag};
 
-use super::{default_vals::*, deserializers::*, DotAddressable};
+use super::{default_vals::*, deserializers::*, DateTimezone, DotAddressable};
 
 /// Settings for the pager function.
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -92,6 +93,12 @@ pub struct PagerSettings {
     #[serde(default = "internal_value_true", alias = "show-date-in-my-timezone")]
     pub show_date_in_my_timezone: ToggleFlag,
 
+    /// Timezone to show Date: in: "sender", "local", "utc" or a fixed
+    /// offset such as "+0200". Overrides `show_date_in_my_timezone`.
+    /// Default: None
+    #[serde(default = "none")]
+    pub timezone: Option<DateTimezone>,
+
     /// A command to launch URLs with. The URL will be given as the first
     /// argument of the command. Default: None
     #[serde(default = "none", deserialize_with = "non_empty_opt_string")]
@@ -105,6 +112,106 @@ pub struct PagerSettings {
         alias = "html-open"
     )]
     pub html_open: Option<String>,
+
+    /// Show a warning banner above the headers if the user has never sent
+    /// mail to the sender.
+    /// Default: false
+    #[serde(default = "false_val", alias = "warn-unknown-senders")]
+    pub warn_unknown_senders: bool,
+
+    /// Show a warning banner above the headers if the domain of `From`
+    /// differs from the domain of `Reply-To` or `Return-Path`.
+    /// Default: false
+    #[serde(default = "false_val", alias = "warn-sender-domain-mismatch")]
+    pub warn_sender_domain_mismatch: bool,
+
+    /// Remove tracking pixels from html parts and rewrite click-tracking links
+    /// to their destination before displaying or opening them.
+    /// Default: true
+    #[serde(default = "true_val", alias = "sanitize-html")]
+    pub sanitize_html: bool,
+
+    /// Commands that display parts of a given MIME type, keyed by MIME type
+    /// glob, in the manner of mailcap(5). The first matching entry is used.
+    /// Default: empty
+    #[serde(default)]
+    pub filters: IndexMap<String, MimeFilter>,
+}
+
+/// An entry of `pager.filters`.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct MimeFilter {
+    /// Shell command to run. `%f` is replaced with the path of a temporary
+    /// file holding the decoded part, `%t` with its MIME type and `%%` with
+    /// `%`. Without `%f` the part is written to the command's standard input.
+    pub command: String,
+
+    /// The command prints text that is shown in the pager, instead of being
+    /// launched as an external viewer.
+    /// Default: false
+    #[serde(default = "false_val")]
+    pub copiousoutput: bool,
+}
+
+impl MimeFilter {
+    /// Returns the first entry of `filters` whose MIME type glob matches
+    /// `mime_type`.
+    pub fn find<'a>(filters: &'a IndexMap<String, Self>, mime_type: &str) -> Option<&'a Self> {
+        let mime_type = mime_type.to_ascii_lowercase();
+        filters
+            .iter()
+            .find(|(glob, _)| mime_type.as_str().matches_glob(glob))
+            .map(|(_, filter)| filter)
+    }
+
+    /// Returns the filter that renders `text/html` parts as text: `html_filter`
+    /// if set, or a `copiousoutput` entry of `filters`. Without one, html is
+    /// rendered with the built-in renderer.
+    pub fn for_html(html_filter: Option<&str>, filters: &IndexMap<String, Self>) -> Option<Self> {
+        if let Some(command) = html_filter {
+            return Some(Self {
+                command: command.to_string(),
+                copiousoutput: true,
+            });
+        }
+        Self::find(filters, "text/html")
+            .filter(|filter| filter.copiousoutput)
+            .cloned()
+    }
+
+    /// Whether the command reads the part from a file instead of its standard
+    /// input.
+    pub fn needs_file(&self) -> bool {
+        self.command.replace("%%", "").contains("%f")
+    }
+
+    /// Returns the shell command line, with `%f` replaced by `path` quoted
+    /// for the shell and `%t` by `mime_type`.
+    pub fn command_line(&self, path: Option<&str>, mime_type: &str) -> String {
+        let mut ret = String::with_capacity(self.command.len());
+        let mut chars = self.command.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek().copied()) {
+                ('%', Some('%')) => {
+                    ret.push('%');
+                    chars.next();
+                }
+                ('%', Some('f')) => {
+                    ret.push('\'');
+                    ret.push_str(&path.unwrap_or_default().replace('\'', "'\\''"));
+                    ret.push('\'');
+                    chars.next();
+                }
+                ('%', Some('t')) => {
+                    ret.push_str(mime_type);
+                    chars.next();
+                }
+                _ => ret.push(c),
+            }
+        }
+        ret
+    }
 }
 
 impl Default for PagerSettings {
@@ -122,7 +229,12 @@ impl Default for PagerSettings {
             minimum_width: 80,
             auto_choose_multipart_alternative: ToggleFlag::InternalVal(true),
             show_date_in_my_timezone: ToggleFlag::InternalVal(true),
+            timezone: None,
             url_launcher: None,
+            warn_unknown_senders: false,
+            warn_sender_domain_mismatch: false,
+            sanitize_html: true,
+            filters: IndexMap::default(),
         }
     }
 }
@@ -147,7 +259,14 @@ impl DotAddressable for PagerSettings {
                         self.auto_choose_multipart_alternative.lookup(field, tail)
                     }
                     "show_date_in_my_timezone" => self.show_date_in_my_timezone.lookup(field, tail),
-                    "url_launcher" => self.html_filter.lookup(field, tail),
+                    "timezone" => self.timezone.lookup(field, tail),
+                    "url_launcher" => self.url_launcher.lookup(field, tail),
+                    "warn_unknown_senders" => self.warn_unknown_senders.lookup(field, tail),
+                    "warn_sender_domain_mismatch" => {
+                        self.warn_sender_domain_mismatch.lookup(field, tail)
+                    }
+                    "sanitize_html" => self.sanitize_html.lookup(field, tail),
+                    "filters" => self.filters.lookup(field, tail),
                     other => Err(Error::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -158,3 +277,67 @@ impl DotAddressable for PagerSettings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_mime_filter() {
+        let filters: IndexMap<String, MimeFilter> = toml::from_str(
+            r#"
+"image/*" = { command = "feh %f" }
+"application/pdf" = { command = "pdftotext %f -", copiousoutput = true }
+"text/html" = { command = "lynx -dump -stdin", copiousoutput = true }
+"*" = { command = "file --mime-type=%t -" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            MimeFilter::find(&filters, "application/pdf")
+                .unwrap()
+                .command,
+            "pdftotext %f -"
+        );
+        assert_eq!(
+            MimeFilter::find(&filters, "IMAGE/png").unwrap().command,
+            "feh %f"
+        );
+        assert_eq!(
+            MimeFilter::find(&filters, "audio/ogg").unwrap().command,
+            "file --mime-type=%t -"
+        );
+        assert!(MimeFilter::find(&IndexMap::default(), "image/png").is_none());
+
+        let pdf = &filters["application/pdf"];
+        assert!(pdf.needs_file());
+        assert_eq!(
+            pdf.command_line(Some("/tmp/it's.pdf"), "application/pdf"),
+            "pdftotext '/tmp/it'\\''s.pdf' -"
+        );
+        let other = &filters["*"];
+        assert!(!other.needs_file());
+        assert_eq!(
+            other.command_line(None, "audio/ogg"),
+            "file --mime-type=audio/ogg -"
+        );
+        let escaped = MimeFilter {
+            command: "printf 100%%f".to_string(),
+            copiousoutput: false,
+        };
+        assert!(!escaped.needs_file());
+        assert_eq!(escaped.command_line(None, "text/plain"), "printf 100%f");
+
+        assert_eq!(
+            MimeFilter::for_html(None, &filters).unwrap().command,
+            "lynx -dump -stdin"
+        );
+        assert_eq!(
+            MimeFilter::for_html(Some("html2text"), &filters)
+                .unwrap()
+                .command,
+            "html2text"
+        );
+        assert_eq!(MimeFilter::for_html(None, &IndexMap::default()), None);
+    }
+}
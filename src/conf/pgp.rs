@@ -75,6 +75,17 @@ pub struct PGPSettings {
         alias = "remote-lookup-mechanisms"
     )]
     pub remote_lookup_mechanisms: melib::gpgme::LocateKey,
+
+    /// Attach the public key of the sender to sent e-mail.
+    /// Default: false
+    #[serde(default = "false_val", alias = "attach-public-key")]
+    pub attach_public_key: bool,
+
+    /// Include the subject and other sensitive headers in the encrypted part
+    /// of encrypted e-mail, and replace the subject of the outer headers.
+    /// Default: true
+    #[serde(default = "true_val", alias = "protected-headers")]
+    pub protected_headers: bool,
 }
 
 #[cfg(feature = "gpgme")]
@@ -95,6 +106,8 @@ impl Default for PGPSettings {
             encrypt_key: None,
             allow_remote_lookup: internal_value_false::<ToggleFlag>(),
             remote_lookup_mechanisms: default_lookup_mechanism(),
+            attach_public_key: false,
+            protected_headers: true,
         }
     }
 }
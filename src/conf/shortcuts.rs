@@ -62,6 +62,35 @@ impl Shortcuts {
     pub const ENVELOPE_VIEW: &'static str = "envelope_view";
     pub const THREAD_VIEW: &'static str = "thread_view";
     pub const PAGER: &'static str = "pager";
+
+    /// Returns the description of shortcut `key` of `section`.
+    pub fn description(section: &str, key: &str) -> Option<&'static str> {
+        match section {
+            Self::GENERAL => GeneralShortcuts::description(key),
+            Self::LISTING => ListingShortcuts::description(key),
+            Self::COMPOSING => ComposingShortcuts::description(key),
+            Self::CONTACT_LIST => ContactListShortcuts::description(key),
+            Self::ENVELOPE_VIEW => EnvelopeViewShortcuts::description(key),
+            Self::THREAD_VIEW => ThreadViewShortcuts::description(key),
+            Self::PAGER => PagerShortcuts::description(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the command that does what shortcut `key` of `section` does,
+    /// if there is one.
+    pub fn command(section: &str, key: &str) -> Option<&'static str> {
+        match section {
+            Self::GENERAL => GeneralShortcuts::command(key),
+            Self::LISTING => ListingShortcuts::command(key),
+            Self::COMPOSING => ComposingShortcuts::command(key),
+            Self::CONTACT_LIST => ContactListShortcuts::command(key),
+            Self::ENVELOPE_VIEW => EnvelopeViewShortcuts::command(key),
+            Self::THREAD_VIEW => ThreadViewShortcuts::command(key),
+            Self::PAGER => PagerShortcuts::command(key),
+            _ => None,
+        }
+    }
 }
 
 impl DotAddressable for Shortcuts {
@@ -89,12 +118,15 @@ impl DotAddressable for Shortcuts {
 }
 
 /// Create a struct holding all of a Component's shortcuts.
+///
+/// Each shortcut is declared as `name |> description |> default key`,
+/// optionally followed by `=> "command"` if a command does the same thing.
 #[macro_export]
 macro_rules! shortcut_key_values {
     (
         $cname:expr,
         $(#[$outer:meta])*
-        pub struct $name:ident { $($fname:ident |> $fdesc:literal |> $default:expr),* }) => {
+        pub struct $name:ident { $($fname:ident |> $fdesc:literal |> $default:expr $(=> $fcmd:literal)?),* }) => {
         $(#[$outer])*
         #[derive(Debug, Clone, Serialize, Deserialize)]
         #[serde(default)]
@@ -111,6 +143,20 @@ macro_rules! shortcut_key_values {
                         _ => unreachable!()
                 }
             }
+            /// Returns the description of shortcut `key`, if it exists.
+            pub fn description(key: &str) -> Option<&'static str> {
+                match key {
+                    $(stringify!($fname) => Some($fdesc),)*
+                    _ => None,
+                }
+            }
+            /// Returns the command equivalent of shortcut `key`, if it has one.
+            pub fn command(key: &str) -> Option<&'static str> {
+                match key {
+                    $($(stringify!($fname) => Some($fcmd),)?)*
+                    _ => None,
+                }
+            }
             /// Returns a hashmap of all shortcuts and their values
             pub fn key_values(&self) -> IndexMap<&'static str, Key> {
                 [
@@ -161,13 +207,21 @@ shortcut_key_values! { "listing",
         open_mailbox |> "Open selected mailbox" |> Key::Char('\n'),
         toggle_mailbox_collapse |> "Toggle mailbox collapse in menu." |> Key::Char(' '),
         prev_page |> "Go to previous page." |> Key::PageUp,
-        search |> "Search within list of e-mails." |> Key::Char('/'),
+        search |> "Search within list of e-mails." |> Key::Char('/') => "search QUERY",
+        query_builder |> "Build a search query with a form." |> Key::Alt('/'),
         refresh |> "Manually request a mailbox refresh." |> Key::F(5),
-        set_seen |> "Set thread as seen." |> Key::Char('n'),
+        sync_now |> "Refresh the selected mailbox, or every mailbox of the selected account." |> Key::Char('S'),
+        load_older |> "Load the next page of older messages of a large mailbox." |> Key::Char('O'),
+        set_seen |> "Set thread as seen." |> Key::Char('n') => "set seen",
+        move_to_mailbox |> "Move entries to a mailbox picked from a searchable list." |> Key::Char('M') => "moveto MAILBOX",
+        copy_to_mailbox |> "Copy entries to a mailbox picked from a searchable list." |> Key::Char('C') => "copyto MAILBOX",
+        edit_flags |> "Edit the flags and tags of the selected entries." |> Key::Char('E'),
         union_modifier |> "Union modifier." |> Key::Ctrl('u'),
         diff_modifier |> "Difference modifier." |> Key::Ctrl('d'),
         intersection_modifier |> "Intersection modifier." |> Key::Ctrl('i'),
         select_entry |> "Select thread entry." |> Key::Char('v'),
+        toggle_pin |> "Pin selected threads to the top of the listing, or unpin them." |> Key::Char('P') => "pin",
+        undo |> "Undo the last delete, move or flag change." |> Key::Char('u'),
         increase_sidebar |> "Increase sidebar width." |> Key::Ctrl('p'),
         decrease_sidebar |> "Decrease sidebar width." |> Key::Ctrl('o'),
         toggle_menu_visibility |> "Toggle visibility of side menu in mail list." |> Key::Char('`'),
@@ -186,6 +240,7 @@ shortcut_key_values! { "contact-list",
         create_contact |> "Create new contact." |> Key::Char('c'),
         edit_contact |> "Edit contact under cursor." |> Key::Char('e'),
         mail_contact |> "Mail contact under cursor." |> Key::Char('m'),
+        address_history |> "Show all mail exchanged with contact under cursor." |> Key::Char('H') => "address-history",
         next_account |> "Go to next account." |> Key::Char('h'),
         prev_account |> "Go to previous account." |> Key::Char('l'),
         toggle_menu_visibility |> "Toggle visibility of side menu in mail list." |> Key::Char('`')
@@ -206,7 +261,7 @@ shortcut_key_values! { "general",
     pub struct GeneralShortcuts {
         toggle_help |> "Toggle help and shortcuts view." |> Key::Char('?'),
         enter_command_mode |> "Enter COMMAND mode." |> Key::Char(':'),
-        quit |> "Quit meli." |> Key::Char('q'),
+        quit |> "Quit meli." |> Key::Char('q') => "quit",
         go_to_tab |> "Go to the nth tab" |> Key::Alt('n'),
         next_tab |> "Next tab." |> Key::Char('T'),
         scroll_right |> "Generic scroll right (catch-all setting)" |> Key::Right,
@@ -229,26 +284,33 @@ shortcut_key_values! { "composing",
         edit_mail |> "Edit mail." |> Key::Char('e'),
         send_mail |> "Deliver draft to mailer" |> Key::Char('s'),
         scroll_up |> "Change field focus." |> Key::Char('k'),
-        scroll_down |> "Change field focus." |> Key::Char('j')
+        scroll_down |> "Change field focus." |> Key::Char('j'),
+        pick_contacts |> "Pick recipients of the focused To, Cc or Bcc field from the address book and recent recipients." |> Key::Char('c'),
+        browse_attachments |> "Choose files to attach in a file browser." |> Key::Char('A') => "add-attachment-file-picker",
+        move_attachment_up |> "Move the selected attachment up in the attachment editor." |> Key::Char('K'),
+        move_attachment_down |> "Move the selected attachment down in the attachment editor." |> Key::Char('J')
     }
 }
 
 shortcut_key_values! { "envelope-view",
     pub struct EnvelopeViewShortcuts {
-        add_addresses_to_contacts |> "Select addresses from envelope to add to contacts." |> Key::Char('c'),
+        add_addresses_to_contacts |> "Select addresses from envelope to add to contacts." |> Key::Char('c') => "add-addresses-to-contacts",
         edit |> "Open envelope in composer." |> Key::Char('e'),
         go_to_url |> "Go to url of given index" |> Key::Char('g'),
         open_attachment |> "Opens selected attachment with xdg-open." |> Key::Char('a'),
         open_mailcap |> "Opens selected attachment according to its mailcap entry." |> Key::Char('m'),
-        reply |> "Reply to envelope." |> Key::Char('R'),
+        save_attachments |> "Choose attachments to save and the directory to save them in." |> Key::Char('s') => "save-attachment all PATH",
+        reply |> "Reply to envelope." |> Key::Char('R') => "reply",
         reply_to_author |> "Reply to author." |> Key::Ctrl('r'),
-        reply_to_all |> "Reply to all/Reply to list/Follow up." |> Key::Ctrl('g'),
+        reply_to_all |> "Reply to all/Follow up." |> Key::Ctrl('g'),
+        reply_to_list |> "Reply to mailing list." |> Key::Char('L'),
         forward |> "Forward email." |> Key::Ctrl('f'),
         return_to_normal_view |> "Return to envelope if viewing raw source or attachment." |> Key::Char('r'),
-        toggle_expand_headers |> "Expand extra headers (References and others)." |> Key::Char('h'),
+        toggle_expand_headers |> "Expand extra headers (References and others). In raw source view, toggle header folding and hex dumps of binary parts." |> Key::Char('h'),
         toggle_url_mode |> "Toggles url open mode." |> Key::Char('u'),
+        toggle_timezone |> "Cycle the timezone of the Date: header between sender, local, UTC and the configured timezone." |> Key::Char('z'),
         view_raw_source |> "View envelope source in a pager. (toggles between raw and decoded source)" |> Key::Alt('r'),
-        change_charset |> "Force attachment charset for decoding." |> Key::Char('d')
+        change_charset |> "Force attachment charset for decoding." |> Key::Char('d') => "set-charset CHARSET"
     }
 }
 
@@ -261,6 +323,9 @@ shortcut_key_values! { "thread-view",
         prev_page |> "Go to previous page." |> Key::PageUp,
         reverse_thread_order |> "reverse thread order" |> Key::Ctrl('r'),
         toggle_mailview |> "toggle mail view visibility" |> Key::Char('p'),
-        toggle_threadview |> "toggle thread view visibility" |> Key::Char('t')
+        toggle_threadview |> "toggle thread view visibility" |> Key::Char('t'),
+        search |> "Search within all entries of the thread." |> Key::Char('/'),
+        next_search_result |> "Go to next entry matching the search." |> Key::Char(']'),
+        previous_search_result |> "Go to previous entry matching the search." |> Key::Char('[')
     }
 }
@@ -0,0 +1,70 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::default_vals::*;
+
+/// Settings for S/MIME signing and signature verification
+#[derive(Debug, Deserialize, Clone, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmimeSettings {
+    /// auto verify S/MIME signed e-mail
+    /// Default: true
+    #[serde(default = "true_val", alias = "auto-verify-signatures")]
+    pub auto_verify_signatures: bool,
+
+    /// always sign sent e-mail with the S/MIME certificate
+    /// Default: false
+    #[serde(default = "false_val", alias = "auto-sign")]
+    pub auto_sign: bool,
+
+    /// PEM file of the certificate to sign with
+    /// Default: None
+    #[serde(default = "none")]
+    pub certificate: Option<String>,
+
+    /// PEM file of the private key of `certificate`
+    /// Default: None
+    #[serde(default = "none", alias = "private-key")]
+    pub private_key: Option<String>,
+
+    /// PEM file of the certificate authorities trusted for signers; the
+    /// default trust store of openssl is used if unset
+    /// Default: None
+    #[serde(default = "none", alias = "ca-file")]
+    pub ca_file: Option<String>,
+
+    /// Default: None
+    #[serde(default = "none", alias = "openssl-binary")]
+    pub openssl_binary: Option<String>,
+}
+
+impl Default for SmimeSettings {
+    fn default() -> Self {
+        SmimeSettings {
+            auto_verify_signatures: true,
+            auto_sign: false,
+            certificate: None,
+            private_key: None,
+            ca_file: None,
+            openssl_binary: None,
+        }
+    }
+}
@@ -35,7 +35,8 @@ pub struct TerminalSettings {
     pub ascii_drawing: bool,
     pub use_color: ToggleFlag,
     /// Use mouse events. This will disable text selection, but you will be able
-    /// to resize some widgets.
+    /// to click entries, mailboxes and tabs, scroll with the mouse wheel and
+    /// resize some widgets.
     /// Default: False
     pub use_mouse: ToggleFlag,
     /// String to show in status bar if mouse is active.
@@ -51,6 +52,11 @@ pub struct TerminalSettings {
     /// animation. Default: 0
     #[serde(default)]
     pub progress_spinner_sequence: Option<ProgressSpinnerSequence>,
+    /// Locale of the user interface strings and dates, such as `de_DE.UTF-8`.
+    /// Default: the `LC_ALL`, `LC_MESSAGES`/`LC_TIME` or `LANG` environment
+    /// variables.
+    #[serde(deserialize_with = "non_empty_opt_string")]
+    pub locale: Option<String>,
 }
 
 impl Default for TerminalSettings {
@@ -65,6 +71,7 @@ impl Default for TerminalSettings {
             window_title: Some("meli".to_string()),
             file_picker_command: None,
             progress_spinner_sequence: None,
+            locale: None,
         }
     }
 }
@@ -98,6 +105,7 @@ impl DotAddressable for TerminalSettings {
                     "progress_spinner_sequence" => {
                         self.progress_spinner_sequence.lookup(field, tail)
                     }
+                    "locale" => self.locale.lookup(field, tail),
                     other => Err(Error::new(format!(
                         "{} has no field named {}",
                         parent_field, other
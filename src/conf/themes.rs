@@ -305,6 +305,13 @@ const DEFAULT_KEYS: &[&str] = &[
     "mail.view.headers_names",
     "mail.view.headers_area",
     "mail.view.body",
+    "mail.view.sender_warning",
+    "mail.view.pgp_status",
+    "mail.view.pgp_failed",
+    "mail.view.source.header_name",
+    "mail.view.source.header_value",
+    "mail.view.source.boundary",
+    "mail.view.source.annotation",
     "mail.view.thread.indentation.a",
     "mail.view.thread.indentation.b",
     "mail.view.thread.indentation.c",
@@ -314,6 +321,7 @@ const DEFAULT_KEYS: &[&str] = &[
     "mail.listing.attachment_flag",
     "mail.listing.thread_snooze_flag",
     "mail.listing.tag_default",
+    "mail.listing.pinned",
     "pager.highlight_search",
     "pager.highlight_search_current",
 ];
@@ -1650,6 +1658,13 @@ impl Default for Themes {
         );
         add!("mail.view.headers_area");
         add!("mail.view.body");
+        add!("mail.view.sender_warning", dark = { fg: Color::Black, bg: Color::Byte(214), attrs: Attr::BOLD }, light = { fg: Color::Black, bg: Color::Byte(214), attrs: Attr::BOLD }); // Orange1
+        add!("mail.view.pgp_status", dark = { fg: Color::Byte(34), bg: "mail.view.headers" }, light = { fg: Color::Byte(28), bg: "mail.view.headers" }); // Green3, Green4
+        add!("mail.view.pgp_failed", dark = { fg: Color::White, bg: Color::Byte(160), attrs: Attr::BOLD }, light = { fg: Color::White, bg: Color::Byte(160), attrs: Attr::BOLD }); // Red3
+        add!("mail.view.source.header_name", dark = { fg: "mail.view.headers_names", bg: "mail.view.body", attrs: Attr::BOLD }, light = { fg: "mail.view.headers_names", bg: "mail.view.body", attrs: Attr::BOLD });
+        add!("mail.view.source.header_value", dark = { fg: "mail.view.headers", bg: "mail.view.body" }, light = { fg: "mail.view.headers", bg: "mail.view.body" });
+        add!("mail.view.source.boundary", dark = { fg: Color::Byte(172), bg: "mail.view.body", attrs: Attr::BOLD }, light = { fg: Color::Byte(172), bg: "mail.view.body", attrs: Attr::BOLD }); // Orange3
+        add!("mail.view.source.annotation", dark = { fg: Color::Byte(243), bg: "mail.view.body" }, light = { fg: Color::Byte(243), bg: "mail.view.body" }); // Grey46
         add!("mail.view.thread.indentation.a", light = { bg: Color::Byte(69) }, dark = { bg: Color::Byte(69) }); // CornflowerBlue
         add!("mail.view.thread.indentation.b", light = { bg: Color::Byte(196) }, dark = { bg: Color::Byte(196) }); // Red1
         add!("mail.view.thread.indentation.c", light = { bg: Color::Byte(175) }, dark = { bg: Color::Byte(175) }); // Pink3
@@ -1677,6 +1692,18 @@ impl Default for Themes {
             }
         );
 
+        add!(
+            "mail.listing.pinned",
+            light = {
+                fg: Color::Byte(166),
+                attrs: Attr::BOLD
+            },
+            dark = {
+                fg: Color::Byte(214),
+                attrs: Attr::BOLD
+            }
+        );
+
         add!(
             "mail.listing.tag_default",
             light = {
@@ -0,0 +1,380 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Localization of user interface strings.
+ *
+ * Translations are gettext `.po` catalogs named after the language, e.g.
+ * `de.po` or `pt_BR.po`, looked up in the `locale` directory of meli's XDG
+ * data directories (`$XDG_DATA_HOME/meli/locale/`, then
+ * `$XDG_DATA_DIRS/meli/locale/`). The language is the `terminal.locale`
+ * setting, or else taken from `LC_ALL`, `LC_MESSAGES` and `LANG` in that
+ * order.
+ *
+ * The translations that come with meli are in the `po` directory of the
+ * source tree and are installed with `make install-locale`; `make update-pot`
+ * extracts the template `po/meli.pot` for new ones.
+ *
+ * Strings are translated with the [`tr!`](crate::tr) macro, whose message ids
+ * are the English strings. Arguments replace `{}` placeholders in order, or
+ * `{0}`, `{1}`, … by position so that translations can reorder them.
+ */
+
+use std::{borrow::Cow, collections::HashMap, fmt, fs, path::Path, sync::RwLock};
+
+use melib::{log, Error, ErrorKind, Result};
+
+static CATALOG: RwLock<Option<Catalog>> = RwLock::new(None);
+
+/// Translates a string literal with the loaded catalog, formatting the rest
+/// of the arguments into its placeholders.
+#[macro_export]
+macro_rules! tr {
+    ($msgid:literal) => {
+        $crate::i18n::tr($msgid).into_owned()
+    };
+    ($msgid:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            &$crate::i18n::tr($msgid),
+            &[$(&$arg as &dyn std::fmt::Display),+],
+        )
+    };
+}
+
+/// The translations of a `.po` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parses the contents of a gettext `.po` file. Fuzzy and untranslated
+    /// entries are skipped; only the singular form of plural entries is
+    /// used.
+    pub fn parse_po(input: &str) -> Result<Self> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Field {
+            None,
+            Context,
+            Id,
+            IdPlural,
+            Str,
+            StrPlural,
+        }
+
+        let mut messages = HashMap::default();
+        let mut field = Field::None;
+        let (mut msgid, mut msgstr) = (String::new(), String::new());
+        let mut fuzzy = false;
+        let mut finish = |msgid: &mut String, msgstr: &mut String, fuzzy: &mut bool| {
+            if !msgid.is_empty() && !msgstr.is_empty() && !*fuzzy {
+                messages.insert(std::mem::take(msgid), std::mem::take(msgstr));
+            }
+            msgid.clear();
+            msgstr.clear();
+            *fuzzy = false;
+        };
+        for (lineno, line) in input.lines().enumerate() {
+            let line = line.trim();
+            let error = |msg: &str| {
+                Error::new(format!("line {}: {}", lineno + 1, msg))
+                    .set_kind(ErrorKind::Configuration)
+            };
+            if line.is_empty() {
+                continue;
+            } else if let Some(flags) = line.strip_prefix("#,") {
+                if field == Field::Str || field == Field::StrPlural {
+                    finish(&mut msgid, &mut msgstr, &mut fuzzy);
+                    field = Field::None;
+                }
+                fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+                continue;
+            } else if line.starts_with('#') {
+                continue;
+            }
+            let (keyword, rest) = match line.find(|c: char| c.is_whitespace() || c == '"') {
+                Some(pos) => (&line[..pos], line[pos..].trim_start()),
+                None => return Err(error("expected a quoted string")),
+            };
+            let next = match keyword {
+                "" => field,
+                "msgctxt" => Field::Context,
+                "msgid" => Field::Id,
+                "msgid_plural" => Field::IdPlural,
+                "msgstr" | "msgstr[0]" => Field::Str,
+                k if k.starts_with("msgstr[") => Field::StrPlural,
+                other => return Err(error(&format!("unknown keyword {}", other))),
+            };
+            if keyword.is_empty() && field == Field::None {
+                return Err(error("string without a keyword"));
+            }
+            if (next == Field::Context || next == Field::Id)
+                && (field == Field::Str || field == Field::StrPlural)
+            {
+                finish(&mut msgid, &mut msgstr, &mut fuzzy);
+            }
+            field = next;
+            let value = unquote(rest).ok_or_else(|| error("invalid quoted string"))?;
+            match field {
+                Field::Id => msgid.push_str(&value),
+                Field::Str => msgstr.push_str(&value),
+                Field::None | Field::Context | Field::IdPlural | Field::StrPlural => {}
+            }
+        }
+        finish(&mut msgid, &mut msgstr, &mut fuzzy);
+        Ok(Catalog { messages })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| Error::new(format!("Could not read {}: {}", path.display(), err)))?;
+        Self::parse_po(&contents)
+            .map_err(|err| Error::new(format!("Could not parse {}: {}", path.display(), err)))
+    }
+
+    pub fn get(&self, msgid: &str) -> Option<&str> {
+        self.messages.get(msgid).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Parses a C-style quoted string with the escapes gettext uses.
+fn unquote(s: &str) -> Option<String> {
+    let s = s.strip_prefix('"')?.strip_suffix('"')?;
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        ret.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            c @ ('"' | '\\') => c,
+            _ => return None,
+        });
+    }
+    Some(ret)
+}
+
+/// Returns the language of the user interface: `setting` if set, or else
+/// the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is set, without the
+/// encoding and modifier. The `C` and `POSIX` locales mean no translation.
+pub fn language(setting: Option<&str>) -> Option<String> {
+    let locale = match setting {
+        Some(locale) => locale.to_string(),
+        None => ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|val| !val.is_empty())?,
+    };
+    let lang = locale.split(['.', '@']).next().unwrap_or_default();
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return None;
+    }
+    Some(lang.to_string())
+}
+
+/// Returns the path of `file` in the `locale` directory for `lang` or, if
+/// there is none, for its language without the territory (`pt` for
+/// `pt_BR`).
+pub fn find_locale_file(lang: &str, file: impl Fn(&str) -> String) -> Option<std::path::PathBuf> {
+    let data_dirs = xdg::BaseDirectories::with_prefix("meli").ok()?;
+    let mut candidates = vec![lang];
+    if let Some((language, _territory)) = lang.split_once('_') {
+        candidates.push(language);
+    }
+    candidates
+        .into_iter()
+        .find_map(|lang| data_dirs.find_data_file(Path::new("locale").join(file(lang))))
+}
+
+/// Makes dates follow `LC_TIME`, or `setting` if it is set.
+///
+/// # Safety
+///
+/// `setlocale(3)` isn't thread-safe: this must be called before any other
+/// thread is spawned.
+pub unsafe fn set_time_locale(setting: Option<&str>) {
+    let time_locale = std::ffi::CString::new(setting.unwrap_or_default()).unwrap_or_default();
+    if libc::setlocale(libc::LC_TIME, time_locale.as_ptr()).is_null() {
+        log::warn!(
+            "Could not set LC_TIME to {:?}, dates use the default format.",
+            setting.unwrap_or_default()
+        );
+    }
+}
+
+/// Loads the catalog of the user interface language, see [`language`].
+pub fn init(setting: Option<&str>) -> Result<()> {
+    let catalog = match language(setting)
+        .and_then(|lang| find_locale_file(&lang, |lang| format!("{}.po", lang)))
+    {
+        Some(path) => Some(Catalog::load(&path)?),
+        None => None,
+    };
+    set_catalog(catalog);
+    Ok(())
+}
+
+pub fn set_catalog(catalog: Option<Catalog>) {
+    *CATALOG.write().unwrap() = catalog;
+}
+
+/// Returns the translation of `msgid`, or `msgid` if it has none.
+pub fn tr(msgid: &'static str) -> Cow<'static, str> {
+    match CATALOG.read().unwrap().as_ref().and_then(|c| c.get(msgid)) {
+        Some(msgstr) => Cow::Owned(msgstr.to_string()),
+        None => Cow::Borrowed(msgid),
+    }
+}
+
+/// Replaces the `{}` placeholders of `template` with `args` in order, and
+/// `{N}` with the `N`th argument. `{{` and `}}` are literal braces.
+pub fn format(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut ret = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        ret.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            ret.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') => end,
+            _ => {
+                ret.push_str(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let index = match &rest[1..end] {
+            "" => {
+                next += 1;
+                Some(next - 1)
+            }
+            n => n.parse::<usize>().ok(),
+        };
+        match index.and_then(|i| args.get(i)) {
+            Some(arg) => ret.push_str(&arg.to_string()),
+            None => ret.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    ret.push_str(rest);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i18n_parse_po() {
+        let catalog = Catalog::parse_po(
+            r#"# German translation of meli.
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: src/types.rs
+msgid "NORMAL"
+msgstr "NORMAL"
+
+msgid "Press ? to close"
+msgstr "Mit ? "
+"schließen"
+
+#, fuzzy
+msgid "shortcuts"
+msgstr "Tastenkürzel"
+
+msgid "Untranslated"
+msgstr ""
+
+msgctxt "mode"
+msgid "Snoozed until {}."
+msgstr "Zurückgestellt bis \"{}\"."
+
+msgid "{} thread"
+msgid_plural "{} threads"
+msgstr[0] "{} Thread"
+msgstr[1] "{} Threads"
+"#,
+        )
+        .unwrap();
+        assert_eq!(catalog.len(), 4);
+        assert_eq!(catalog.get("Press ? to close"), Some("Mit ? schließen"));
+        assert_eq!(catalog.get("shortcuts"), None);
+        assert_eq!(catalog.get("Untranslated"), None);
+        assert_eq!(
+            catalog.get("Snoozed until {}."),
+            Some("Zurückgestellt bis \"{}\".")
+        );
+        assert_eq!(catalog.get("{} thread"), Some("{} Thread"));
+        assert!(Catalog::parse_po("msgid \"unterminated\nmsgstr \"\"").is_err());
+        assert!(Catalog::parse_po("msgid unquoted").is_err());
+    }
+
+    #[test]
+    fn test_i18n_shipped_catalogs() {
+        let catalog = Catalog::parse_po(include_str!("../po/de.po")).unwrap();
+        assert_eq!(catalog.get("Press ? to close"), Some("Mit ? schließen"));
+        assert_eq!(
+            format(
+                catalog.get("Sending to {}.").unwrap(),
+                &[&"user@example.com"]
+            ),
+            "Sende an user@example.com."
+        );
+        assert!(Catalog::parse_po(include_str!("../po/meli.pot"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_i18n_format() {
+        assert_eq!(format("{} of {}", &[&1, &"two"]), "1 of two");
+        assert_eq!(format("{1} von {0}", &[&1, &"two"]), "two von 1");
+        assert_eq!(format("{{}} {}", &[&3]), "{} 3");
+        assert_eq!(format("{} {} {5}", &[&3]), "3 {} {5}");
+        assert_eq!(format("a } b {", &[]), "a } b {");
+    }
+
+    #[test]
+    fn test_i18n_language() {
+        assert_eq!(language(Some("de_DE.UTF-8")).as_deref(), Some("de_DE"));
+        assert_eq!(language(Some("sr_RS@latin")).as_deref(), Some("sr_RS"));
+        assert_eq!(language(Some("C.UTF-8")), None);
+        assert_eq!(language(Some("POSIX")), None);
+    }
+}
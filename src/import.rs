@@ -0,0 +1,270 @@
+/*
+ * meli - import.rs
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Import messages from mbox files, maildirs and message files into a
+ * mailbox of any backend.
+ */
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use melib::{
+    backends::{
+        maildir::MaildirPathTrait,
+        mbox::{Length, MessageIterator, Offset},
+        MailBackend, MailboxHash,
+    },
+    email::Flag,
+    error::{Error, Result, ResultIntoError},
+};
+
+/// How many messages are saved concurrently before progress is reported.
+pub const BATCH_SIZE: usize = 32;
+
+/// Messages to import, read from a path.
+#[derive(Debug)]
+pub enum ImportSource {
+    /// An mbox file, such as a Google Takeout export. Flags are read from the
+    /// `Status` and `X-Status` headers.
+    Mbox {
+        bytes: Vec<u8>,
+        messages: Vec<(Offset, Length, Flag)>,
+    },
+    /// A maildir, whose flags are read from the file names.
+    Maildir(Vec<PathBuf>),
+    /// A single message file.
+    File(PathBuf),
+}
+
+impl ImportSource {
+    /// Opens `path` as a maildir if it is a directory, as an mbox file if it
+    /// starts with a `From ` line, and as a single message otherwise.
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            let mut files = vec![];
+            for subdir in ["cur", "new"] {
+                let subdir = path.join(subdir);
+                if !subdir.is_dir() {
+                    continue;
+                }
+                for entry in std::fs::read_dir(&subdir)
+                    .chain_err_summary(|| format!("Could not read {}", subdir.display()))?
+                {
+                    let entry_path = entry?.path();
+                    if entry_path.is_file() {
+                        files.push(entry_path);
+                    }
+                }
+            }
+            if files.is_empty() && !path.join("cur").is_dir() {
+                return Err(Error::new(format!(
+                    "{} is not a maildir: it has no cur or new subdirectories.",
+                    path.display()
+                )));
+            }
+            files.sort();
+            return Ok(ImportSource::Maildir(files));
+        }
+        let bytes =
+            std::fs::read(path).chain_err_summary(|| format!("Could not read {}", path.display()))?;
+        if !bytes.starts_with(b"From ") {
+            return Ok(ImportSource::File(path.to_path_buf()));
+        }
+        let index = Arc::new(Mutex::new(HashMap::default()));
+        let envelopes = MessageIterator {
+            index: index.clone(),
+            input: &bytes,
+            file_offset: 0,
+            offset: 0,
+            format: None,
+        }
+        .collect::<Result<Vec<_>>>()
+        .chain_err_summary(|| format!("Could not parse mbox file {}", path.display()))?;
+        let index = index.lock().unwrap();
+        let mut messages = envelopes
+            .iter()
+            .map(|env| {
+                let (offset, length) = index[&env.hash()];
+                (offset, length, env.flags())
+            })
+            .collect::<Vec<_>>();
+        drop(index);
+        messages.sort_by_key(|(offset, _, _)| *offset);
+        Ok(ImportSource::Mbox { bytes, messages })
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ImportSource::Mbox { messages, .. } => messages.len(),
+            ImportSource::Maildir(files) => files.len(),
+            ImportSource::File(_) => 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the bytes and flags of the `i`th message.
+    pub fn message(&self, i: usize) -> Result<(Vec<u8>, Option<Flag>)> {
+        match self {
+            ImportSource::Mbox { bytes, messages } => {
+                let (offset, length, flags) = messages[i];
+                Ok((bytes[offset..offset + length].to_vec(), Some(flags)))
+            }
+            ImportSource::Maildir(files) => Ok((
+                std::fs::read(&files[i])
+                    .chain_err_summary(|| format!("Could not read {}", files[i].display()))?,
+                Some(files[i].as_path().flags()),
+            )),
+            ImportSource::File(path) => Ok((
+                std::fs::read(path)
+                    .chain_err_summary(|| format!("Could not read {}", path.display()))?,
+                None,
+            )),
+        }
+    }
+}
+
+/// Saves every message of `source` in `mailbox_hash`, `BATCH_SIZE` messages
+/// at a time. `progress` is called with the number of saved messages and the
+/// total after each batch. Resolves to the number of imported messages.
+pub async fn import_messages(
+    source: ImportSource,
+    backend: Arc<RwLock<Box<dyn MailBackend>>>,
+    mailbox_hash: MailboxHash,
+    progress: impl Fn(usize, usize) + Send + 'static,
+) -> Result<usize> {
+    let total = source.len();
+    let mut done = 0;
+    while done < total {
+        let batch_end = std::cmp::min(done + BATCH_SIZE, total);
+        let mut jobs = Vec::with_capacity(batch_end - done);
+        for i in done..batch_end {
+            let (bytes, flags) = source.message(i)?;
+            jobs.push(backend.write().unwrap().save(bytes, mailbox_hash, flags)?);
+        }
+        futures::future::try_join_all(jobs)
+            .await
+            .chain_err_summary(|| {
+                format!("Could not import messages {}-{}", done + 1, batch_end)
+            })?;
+        done = batch_end;
+        progress(done, total);
+    }
+    Ok(total)
+}
+
+/// Imports `path` into the mailbox with path `mailbox_path` of the account
+/// `conf` without starting the user interface, printing progress to stdout.
+pub fn import_to_account(
+    conf: &crate::conf::AccountConf,
+    mailbox_path: &str,
+    path: &Path,
+) -> Result<usize> {
+    use futures::executor::block_on;
+
+    let source = ImportSource::open(path)?;
+    let backends = melib::backends::Backends::new();
+    let backend = backends.get(&conf.account().format)(
+        conf.account(),
+        Box::new(|_: &str| true),
+        melib::backends::BackendEventConsumer::new(Arc::new(|_, _| {})),
+    )?;
+    let mailboxes = block_on(backend.mailboxes()?)?;
+    let mailbox_hash = mailboxes
+        .values()
+        .find(|m| m.path() == mailbox_path)
+        .map(|m| m.hash())
+        .ok_or_else(|| {
+            Error::new(format!(
+                "Mailbox `{}` not found in account `{}`.",
+                mailbox_path,
+                conf.account().name
+            ))
+        })?;
+    block_on(import_messages(
+        source,
+        Arc::new(RwLock::new(backend)),
+        mailbox_hash,
+        |done, total| println!("Imported {}/{} messages.", done, total),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_source_open() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mbox_path = dir.path().join("takeout.mbox");
+        std::fs::write(
+            &mbox_path,
+            b"From alice@example.com Mon Jan  2 10:00:00 2023\n\
+From: alice@example.com\n\
+Subject: first\n\
+Status: RO\n\
+\n\
+Hello.\n\
+\n\
+From bob@example.com Mon Jan  2 11:00:00 2023\n\
+From: bob@example.com\n\
+Subject: second\n\
+\n\
+Hi.\n",
+        )
+        .unwrap();
+        let source = ImportSource::open(&mbox_path).unwrap();
+        assert_eq!(source.len(), 2);
+        let (bytes, flags) = source.message(0).unwrap();
+        assert!(bytes.starts_with(b"From: alice@example.com"));
+        assert!(flags.unwrap().contains(Flag::SEEN));
+        let (bytes, flags) = source.message(1).unwrap();
+        assert!(bytes.ends_with(b"Hi.\n"));
+        assert!(!flags.unwrap().contains(Flag::SEEN));
+
+        let maildir = dir.path().join("maildir");
+        for subdir in ["cur", "new", "tmp"] {
+            std::fs::create_dir_all(maildir.join(subdir)).unwrap();
+        }
+        std::fs::write(maildir.join("cur").join("1:2,FS"), b"Subject: a\n\nA\n").unwrap();
+        std::fs::write(maildir.join("new").join("2"), b"Subject: b\n\nB\n").unwrap();
+        let source = ImportSource::open(&maildir).unwrap();
+        assert_eq!(source.len(), 2);
+        assert_eq!(
+            source.message(0).unwrap().1,
+            Some(Flag::FLAGGED | Flag::SEEN)
+        );
+        assert_eq!(source.message(1).unwrap().1, Some(Flag::default()));
+
+        let eml_path = dir.path().join("message.eml");
+        std::fs::write(&eml_path, b"Subject: single\n\nBody\n").unwrap();
+        let source = ImportSource::open(&eml_path).unwrap();
+        assert_eq!(source.len(), 1);
+        assert_eq!(source.message(0).unwrap().1, None);
+
+        assert!(ImportSource::open(dir.path()).is_err());
+    }
+}
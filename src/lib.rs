@@ -51,6 +51,9 @@ static GLOBAL: System = System;
 extern crate melib;
 pub use melib::*;
 
+#[macro_use]
+pub mod i18n;
+
 #[macro_use]
 pub mod types;
 pub use crate::types::*;
@@ -76,5 +79,14 @@ pub use crate::conf::*;
 #[cfg(feature = "sqlite3")]
 pub mod sqlite3;
 
+pub mod import;
 pub mod jobs;
 pub mod mailcap;
+pub mod power;
+pub mod recording;
+pub mod remote;
+pub mod sd_notify;
+pub mod undo;
+
+#[cfg(test)]
+pub mod testing;
@@ -33,6 +33,7 @@ use std::{
 use melib::{email::Attachment, log, text_processing::GlobMatch, Error, Result};
 
 use crate::{
+    pager::MimeFilter,
     state::Context,
     types::{create_temp_file, ForkType, UIEvent},
 };
@@ -251,3 +252,76 @@ impl MailcapEntry {
         }
     }
 }
+
+/// Runs the command of `filter` on `bytes`, a part of type `mime_type`, and
+/// returns its standard output.
+pub fn filter_output(filter: &MimeFilter, mime_type: &str, bytes: &[u8]) -> Result<Vec<u8>> {
+    let file = if filter.needs_file() {
+        Some(create_temp_file(bytes, None, None, true))
+    } else {
+        None
+    };
+    let command = filter.command_line(file.as_ref().and_then(|f| f.path().to_str()), mime_type);
+    log::debug!("Executing: sh -c \"{}\"", command.replace('"', "\\\""));
+    let mut child = Command::new("sh")
+        .args(["-c", &command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = if file.is_none() {
+        let input = bytes.to_vec();
+        /* Write from another thread, the command might not read all of its input
+         * before its output buffer fills up. */
+        Some(std::thread::spawn(move || stdin.write_all(&input)))
+    } else {
+        drop(stdin);
+        None
+    };
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    if !output.status.success() && output.stdout.is_empty() {
+        return Err(
+            Error::new(format!("`{}` exited with {}", command, output.status))
+                .set_details(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Launches the command of `filter` as an external viewer of `bytes`, a part
+/// of type `mime_type`. The temporary file and the child process are kept in
+/// `context` until meli exits.
+pub fn launch_viewer(
+    filter: &MimeFilter,
+    mime_type: &str,
+    bytes: &[u8],
+    filename: Option<&str>,
+    context: &mut Context,
+) -> Result<()> {
+    let file = if filter.needs_file() {
+        Some(create_temp_file(bytes, filename, None, true))
+    } else {
+        None
+    };
+    let command = filter.command_line(file.as_ref().and_then(|f| f.path().to_str()), mime_type);
+    log::debug!("Executing: sh -c \"{}\"", command.replace('"', "\\\""));
+    let mut child = Command::new("sh")
+        .args(["-c", &command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| Error::new(format!("Failed to start `{}`: {}", command, err)))?;
+    if file.is_none() {
+        let mut stdin = child.stdin.take().unwrap();
+        let input = bytes.to_vec();
+        std::thread::spawn(move || stdin.write_all(&input));
+    }
+    context.temp_files.extend(file);
+    context.children.push(child);
+    Ok(())
+}
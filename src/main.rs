@@ -87,6 +87,128 @@ fn main() {
     });
 }
 
+/// Reads all of standard input. If it was not a terminal, the terminal is
+/// then opened as standard input so that the UI can read key presses.
+fn read_stdin() -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut bytes = vec![];
+    std::io::stdin()
+        .read_to_end(&mut bytes)
+        .chain_err_summary(|| "Could not read from standard input")?;
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+        let tty = std::fs::File::open("/dev/tty")
+            .chain_err_summary(|| "Could not open terminal for input")?;
+        nix::unistd::dup2(
+            std::os::unix::io::AsRawFd::as_raw_fd(&tty),
+            libc::STDIN_FILENO,
+        )
+        .map_err(|err| Error::new(err.to_string()))
+        .chain_err_summary(|| "Could not open terminal for input")?;
+    }
+    Ok(bytes)
+}
+
+/// Collects the headers, body and attachments given to the `compose`
+/// subcommand for composing in `account`.
+fn compose_draft(opt: &ComposeOpt, account: Option<String>) -> Result<remote::ComposeRequest> {
+    let mut headers = vec![];
+    for (header, addresses) in [("To", &opt.to), ("Cc", &opt.cc), ("Bcc", &opt.bcc)] {
+        if !addresses.is_empty() {
+            headers.push((header.to_string(), addresses.join(", ")));
+        }
+    }
+    if let Some(ref subject) = opt.subject {
+        headers.push(("Subject".to_string(), subject.clone()));
+    }
+    let body = opt
+        .body
+        .as_ref()
+        .map(|path| {
+            std::fs::read_to_string(path)
+                .chain_err_summary(|| format!("Could not read from `{}`", path.display()))
+        })
+        .transpose()?;
+    /* The attachments are read by the instance that opens the draft, which
+     * may run in another directory. */
+    let attachments = opt
+        .attach
+        .iter()
+        .map(|path| {
+            let absolute = path
+                .canonicalize()
+                .chain_err_summary(|| format!("Could not attach `{}`", path.display()))?;
+            if !absolute.is_file() {
+                return Err(Error::new(format!(
+                    "Could not attach `{}`: not a file",
+                    path.display()
+                )));
+            }
+            Ok(absolute)
+        })
+        .collect::<Result<Vec<PathBuf>>>()?;
+    Ok(remote::ComposeRequest {
+        account,
+        headers,
+        body,
+        attachments,
+    })
+}
+
+/// Builds an IMAP account configuration for `address` from the SRV records
+/// of its domain ([RFC 6186](https://www.rfc-editor.org/rfc/rfc6186)).
+fn discover_account(address: &str) -> Result<String> {
+    use melib::connections::dns::srv_target;
+
+    let (username, domain) = match address.rsplit_once('@') {
+        Some((_, domain)) => (address, domain),
+        None => ("", address),
+    };
+    let (imap, use_starttls) = if let Some(target) = srv_target("_imaps._tcp", domain)? {
+        (target, false)
+    } else if let Some(target) = srv_target("_imap._tcp", domain)? {
+        (target, true)
+    } else {
+        return Err(Error::new(format!(
+            "{} has no _imaps._tcp or _imap._tcp SRV records.",
+            domain
+        )));
+    };
+    let submission = if let Some((hostname, port)) = srv_target("_submissions._tcp", domain)? {
+        Some((hostname, port, "TLS"))
+    } else {
+        srv_target("_submission._tcp", domain)?.map(|(hostname, port)| (hostname, port, "STARTTLS"))
+    };
+    let mut ret = format!(
+        "[accounts.\"{domain}\"]\n\
+         root_mailbox = \"INBOX\"\n\
+         format = \"imap\"\n\
+         identity = \"{username}\"\n\
+         server_hostname = \"{hostname}\"\n\
+         server_port = {port}\n\
+         use_starttls = {use_starttls}\n\
+         server_username = \"{username}\"\n\
+         server_password_command = \"\"\n",
+        domain = domain,
+        username = username,
+        hostname = imap.0,
+        port = imap.1,
+        use_starttls = use_starttls
+    );
+    if let Some((hostname, port, security)) = submission {
+        ret.push_str(&format!(
+            "composing.send_mail = {{ hostname = \"{hostname}\", port = {port}, auth = {{ type = \
+             \"auto\", username = \"{username}\", password = {{ type = \"command_eval\", value = \
+             \"\" }} }}, security = {{ type = \"{security}\" }} }}\n",
+            hostname = hostname,
+            port = port,
+            username = username,
+            security = security
+        ));
+    }
+    Ok(ret)
+}
+
 fn run_app(opt: Opt) -> Result<()> {
     if let Some(config_location) = opt.config.as_ref() {
         std::env::set_var("MELI_CONFIG", config_location);
@@ -165,6 +287,16 @@ fn run_app(opt: Opt) -> Result<()> {
                 }),
             );
             gz.read_to_string(&mut v)?;
+            /* Prefer a translated page installed in the locale data directory. */
+            const PAGE_NAMES: [&str; 4] = ["meli.1", "meli.conf.5", "meli-themes.5", "meli.7"];
+            if let Some(path) = i18n::language(None).and_then(|lang| {
+                i18n::find_locale_file(&lang, |lang| {
+                    format!("{}/{}.txt", lang, PAGE_NAMES[page as usize])
+                })
+            }) {
+                v = std::fs::read_to_string(&path)
+                    .chain_err_summary(|| format!("Could not read `{}`", path.display()))?;
+            }
 
             if let Some(no_raw) = no_raw {
                 match no_raw {
@@ -225,19 +357,105 @@ fn run_app(opt: Opt) -> Result<()> {
             print!("{}", conf::Themes::default().key_to_string("dark", false));
             return Ok(());
         }
-        Some(SubCommand::View { ref path }) => {
-            if !path.exists() {
-                return Err(Error::new(format!(
-                    "`{}` is not a valid path",
-                    path.display()
-                )));
-            } else if !path.is_file() {
-                return Err(Error::new(format!("`{}` is a directory", path.display())));
+        Some(SubCommand::View { ref paths }) => {
+            for path in paths.iter().filter(|p| p.as_os_str() != "-") {
+                if !path.exists() {
+                    return Err(Error::new(format!(
+                        "`{}` is not a valid path",
+                        path.display()
+                    )));
+                } else if !path.is_file() {
+                    return Err(Error::new(format!("`{}` is a directory", path.display())));
+                }
             }
         }
-        None => {}
+        #[cfg(feature = "sqlite3")]
+        Some(SubCommand::Reindex { ref account }) => {
+            let settings = Settings::new()?;
+            let conf = settings.accounts.get(account).ok_or_else(|| {
+                Error::new(format!("Account `{}` is not defined.", account))
+                    .set_kind(ErrorKind::Configuration)
+            })?;
+            println!("Indexing account `{}`…", account);
+            let total = sqlite3::reindex_account(conf)?;
+            println!("Indexed {} messages.", total);
+            return Ok(());
+        }
+        #[cfg(not(feature = "sqlite3"))]
+        Some(SubCommand::Reindex { .. }) => {
+            return Err(Error::new(
+                "error: this version of meli was not built with the sqlite3 search backend (cargo \
+                 feature `sqlite3`).",
+            ));
+        }
+        Some(SubCommand::Import {
+            ref account,
+            ref mailbox,
+            ref path,
+        }) => {
+            let settings = Settings::new()?;
+            let conf = settings.accounts.get(account).ok_or_else(|| {
+                Error::new(format!("Account `{}` is not defined.", account))
+                    .set_kind(ErrorKind::Configuration)
+            })?;
+            let total = import::import_to_account(conf, mailbox, path)?;
+            println!("Imported {} messages to {}/{}.", total, account, mailbox);
+            return Ok(());
+        }
+        Some(SubCommand::Discover { ref address }) => {
+            print!("{}", discover_account(address)?);
+            return Ok(());
+        }
+        Some(SubCommand::Compose(_)) | None => {}
     }
 
+    /* Read the body and check the attachments of a `compose` draft before
+     * starting the UI, so that errors are printed on the terminal. A running
+     * instance opens the draft instead, if there is one. */
+    let compose_draft = if let Some(SubCommand::Compose(ref compose_opt)) = opt.subcommand {
+        let request = compose_draft(compose_opt, opt.account.clone())?;
+        if let Ok(path) = remote::socket_path() {
+            if remote::forward(&path, &request)? {
+                println!("Opened the draft in the running meli instance.");
+                return Ok(());
+            }
+        }
+        Some(request)
+    } else {
+        None
+    };
+
+    /* Open the recording files before starting the UI, so that errors are
+     * printed on the terminal. */
+    let mut recorder = opt
+        .record
+        .as_deref()
+        .map(recording::Recorder::new)
+        .transpose()?;
+    let replay = opt
+        .replay
+        .as_deref()
+        .map(recording::read_recording)
+        .transpose()?;
+
+    /* Load the settings before any thread is spawned, since applying
+     * `terminal.locale` to dates with setlocale(3) isn't thread-safe. */
+    let settings = if let Some(SubCommand::View { .. }) = opt.subcommand {
+        Settings::without_accounts().unwrap_or_default()
+    } else {
+        let mut settings = Settings::new()?;
+        if opt.replay.is_some() {
+            for account in settings.accounts.values_mut() {
+                /* Mailboxes change only as recorded during a replay. */
+                account.account.manual_refresh = true;
+                account.conf.refresh_interval = None;
+            }
+        }
+        settings
+    };
+    /* SAFETY: no other thread has been spawned yet. */
+    unsafe { i18n::set_time_locale(settings.terminal.locale.as_deref()) };
+
     /* Create a channel to communicate with other threads. The main process is
      * the sole receiver.
      */
@@ -248,36 +466,72 @@ fn run_app(opt: Opt) -> Result<()> {
         signal_hook::consts::SIGWINCH,
         /* Catch SIGCHLD to handle embed applications status change */
         signal_hook::consts::SIGCHLD,
+        /* Catch SIGTERM to shut down cleanly when stopped by a service manager */
+        signal_hook::consts::SIGTERM,
     ];
 
     let signal_recvr = notify(signals, sender.clone())?;
 
     /* Create the application State. */
     let mut state;
+    /* Open the drafts of `meli compose` run from other terminals. */
+    let _compose_listener;
 
-    if let Some(SubCommand::View { path }) = opt.subcommand {
-        let bytes = std::fs::read(&path)
-            .chain_err_summary(|| format!("Could not read from `{}`", path.display()))?;
-        let wrapper = Mail::new(bytes, Some(Flag::SEEN))
-            .chain_err_summary(|| format!("Could not parse `{}`", path.display()))?;
-        state = State::new(
-            Some(Settings::without_accounts().unwrap_or_default()),
-            sender,
-            receiver.clone(),
-        )?;
-        state.register_component(Box::new(EnvelopeView::new(
-            wrapper,
-            None,
-            None,
-            AccountHash::default(),
-        )));
+    if let Some(SubCommand::View { paths }) = opt.subcommand {
+        let mut messages = vec![];
+        for path in paths {
+            messages.extend(if path.as_os_str() == "-" {
+                read_messages("stdin", read_stdin()?)?
+            } else {
+                let bytes = std::fs::read(&path)
+                    .chain_err_summary(|| format!("Could not read from `{}`", path.display()))?;
+                read_messages(&path.display().to_string(), bytes)?
+            });
+        }
+        state = State::new(Some(settings), sender, receiver.clone())?;
+        if messages.len() == 1 {
+            let (_, wrapper) = messages.remove(0);
+            state.register_component(Box::new(EnvelopeView::new(
+                wrapper,
+                None,
+                None,
+                AccountHash::default(),
+            )));
+        } else {
+            let window = Box::new(Tabbed::new(
+                vec![Box::new(MessageList::new(messages, &state.context))],
+                &state.context,
+            ));
+            state.register_component(window);
+        }
     } else {
-        state = State::new(None, sender, receiver.clone())?;
+        _compose_listener = remote::ComposeListener::new(sender.clone()).unwrap_or_else(|err| {
+            melib::log::warn!("Could not listen for forwarded drafts: {}", err);
+            None
+        });
+        state = State::new(Some(settings), sender, receiver.clone())?;
         #[cfg(feature = "svgscreenshot")]
         state.register_component(Box::new(components::svg::SVGScreenshotFilter::new()));
+        let startup_view = listing::StartupView {
+            account: opt.account,
+            mailbox: opt.mailbox,
+            query: opt.query,
+        };
+        if let Some(ref name) = startup_view.account {
+            if !state.context.accounts.values().any(|a| a.name == *name) {
+                return Err(Error::new(format!(
+                    "Account `{}` not found in configuration.",
+                    name
+                )));
+            }
+        }
+        let mut listing = listing::Listing::new(&mut state.context);
+        if !startup_view.is_empty() {
+            listing.set_startup_view(startup_view, &mut state.context);
+        }
         let window = Box::new(Tabbed::new(
             vec![
-                Box::new(listing::Listing::new(&mut state.context)),
+                Box::new(listing),
                 Box::new(ContactList::new(&state.context)),
             ],
             &state.context,
@@ -286,6 +540,14 @@ fn run_app(opt: Opt) -> Result<()> {
         let status_bar = Box::new(StatusBar::new(&state.context, window));
         state.register_component(status_bar);
 
+        if let Some(request) = compose_draft {
+            let composer = request.composer(&state.context)?;
+            state
+                .context
+                .replies
+                .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+        }
+
         #[cfg(all(target_os = "linux", feature = "dbus-notifications"))]
         {
             let dbus_notifications = Box::new(components::notifications::DbusNotifications::new(
@@ -306,6 +568,30 @@ fn run_app(opt: Opt) -> Result<()> {
         .clone();
     let quit_key: Key = state.context.settings.shortcuts.general.quit.clone();
 
+    /* Recorded sizes, envelopes and mailbox changes arrive here during a
+     * replay. The sender is kept so that the channel stays open after the
+     * replay ends. */
+    let (replay_sender, replay_recvr) = crossbeam::channel::unbounded();
+    let replaying = replay.is_some();
+    let mut replay_state = recording::Replay::default();
+    if let Some(events) = replay {
+        recording::spawn_replay(
+            events,
+            state.sender(),
+            replay_sender.clone(),
+            state.context.ignore_input_flag(),
+        )?;
+    }
+    if let Some(ref mut recorder) = recorder {
+        let (cols, rows) = state.size();
+        recorder.record(recording::RecordedEvent::Resize { cols, rows });
+    }
+
+    /* Tell the service manager, if any, that startup has finished. */
+    let mut sd_notify = sd_notify::SdNotify::from_env();
+    sd_notify.ready();
+    sd_notify.status(&state.status_summary());
+
     /* Keep track of the input mode. See UIMode for details */
     'main: loop {
         state.render();
@@ -326,6 +612,18 @@ fn run_app(opt: Opt) -> Result<()> {
                          Ok(ThreadEvent::Pulse) | Ok(ThreadEvent::UIEvent(UIEvent::Timer(_))) => {},
                         _ => {debug!(&r);}
                     }
+                    if let Some(ref mut recorder) = recorder {
+                        match r {
+                            Ok(ThreadEvent::Input((ref k, ref bytes))) => recorder.record_input(k, bytes),
+                            Ok(ThreadEvent::RefreshMailbox(ref event)) => recorder.record_refresh(&state.context, event),
+                            Ok(ThreadEvent::UIEvent(UIEvent::BackendEvent(_, BackendEvent::Refresh(ref event)))) => recorder.record_refresh(&state.context, event),
+                            _ => {}
+                        }
+                    }
+                    if replaying && matches!(r, Ok(ThreadEvent::RefreshMailbox(_)) | Ok(ThreadEvent::UIEvent(UIEvent::BackendEvent(_, BackendEvent::Refresh(_))))) {
+                        /* Only the recorded mailbox changes are applied. */
+                        continue 'inner;
+                    }
                     match r.unwrap() {
                         ThreadEvent::Input((Key::Ctrl('z'), _)) if state.mode != UIMode::Embed => {
                             state.switch_to_main_screen();
@@ -348,6 +646,11 @@ fn run_app(opt: Opt) -> Result<()> {
                                 state.redraw();
                             }
                         },
+                        ThreadEvent::Input((Key::Mouse(mev), _))
+                            if matches!(state.mode, UIMode::Normal | UIMode::Insert | UIMode::Command) => {
+                            state.rcv_event(UIEvent::Mouse(mev));
+                            state.redraw();
+                        },
                         ThreadEvent::Input((k, r)) => {
                             match state.mode {
                                 UIMode::Normal => {
@@ -422,6 +725,8 @@ fn run_app(opt: Opt) -> Result<()> {
                         ThreadEvent::Pulse => {
                             state.check_accounts();
                             state.redraw();
+                            sd_notify.watchdog();
+                            sd_notify.status(&state.status_summary());
                         },
                         ThreadEvent::JobFinished(id) => {
                             debug!("Job finished {}", id);
@@ -430,15 +735,31 @@ fn run_app(opt: Opt) -> Result<()> {
                                     break;
                                 }
                             }
+                            if let Some(ref mut recorder) = recorder {
+                                recorder.record_snapshots(&state.context);
+                            }
+                            if replaying {
+                                replay_state.apply_pending(&mut state);
+                            }
                             //state.new_thread(id, name);
                         },
                     }
                 },
+                recv(replay_recvr) -> event => {
+                    if let Ok(event) = event {
+                        replay_state.apply(&mut state, event);
+                        state.redraw();
+                    }
+                },
                 recv(signal_recvr) -> sig => {
                     match sig.unwrap() {
                         signal_hook::consts::SIGWINCH => {
                             if state.mode != UIMode::Fork  {
                                 state.update_size();
+                                if let Some(ref mut recorder) = recorder {
+                                    let (cols, rows) = state.size();
+                                    recorder.record(recording::RecordedEvent::Resize { cols, rows });
+                                }
                                 state.render();
                                 state.redraw();
                             }
@@ -448,6 +769,13 @@ fn run_app(opt: Opt) -> Result<()> {
                             state.redraw();
 
                         }
+                        signal_hook::consts::SIGTERM => {
+                            sd_notify.stopping();
+                            sd_notify.status("Shutting down");
+                            state.shutdown(std::time::Duration::from_secs(10));
+                            drop(state);
+                            break 'main;
+                        }
                         other => {
                             debug!("got other signal: {:?}", other);
                         }
@@ -34,6 +34,9 @@ extern crate termion;
 
 use melib::{backends::imap::managesieve::ManageSieveConnection, Result};
 
+#[macro_use]
+pub mod i18n;
+
 #[macro_use]
 pub mod types;
 use crate::types::*;
@@ -59,8 +62,14 @@ use crate::conf::*;
 #[cfg(feature = "sqlite3")]
 pub mod sqlite3;
 
+pub mod import;
 pub mod jobs;
 pub mod mailcap;
+pub mod power;
+pub mod recording;
+pub mod remote;
+pub mod sd_notify;
+pub mod undo;
 //pub mod plugins;
 
 use futures::executor::block_on;
@@ -0,0 +1,261 @@
+/*
+ * meli - power.rs
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Detect metered connections and battery power so that background work such
+ * as periodic refreshes and index rebuilds can be paused.
+ *
+ * `UPower` and `NetworkManager` are queried over D-Bus with `busctl` when
+ * available, and the battery state falls back to `/sys/class/power_supply`.
+ */
+use std::{
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::conf::PowerSettings;
+
+/// The power source and network state of the machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerState {
+    pub on_battery: bool,
+    /// Battery charge percentage, if a battery was found.
+    pub battery_level: Option<u8>,
+    pub metered: bool,
+}
+
+/// Manual override of the power saving decision, set with the `power-saving`
+/// command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PowerSavingMode {
+    /// Pause background work according to the `power` settings.
+    #[default]
+    Auto,
+    /// Always pause background work.
+    On,
+    /// Never pause background work.
+    Off,
+}
+
+impl fmt::Display for PowerSavingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerSavingMode::Auto => write!(f, "auto"),
+            PowerSavingMode::On => write!(f, "on"),
+            PowerSavingMode::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", if self.on_battery { "battery" } else { "AC" })?;
+        if let Some(level) = self.battery_level {
+            write!(f, " {}%", level)?;
+        }
+        if self.metered {
+            write!(f, ", metered connection")?;
+        }
+        Ok(())
+    }
+}
+
+impl PowerState {
+    /// Queries the current state. This runs external commands and should not
+    /// be called from the UI thread.
+    pub fn detect() -> Self {
+        let mut ret = Self::default();
+        if let Some(on_battery) = busctl_property(
+            "org.freedesktop.UPower",
+            "/org/freedesktop/UPower",
+            "org.freedesktop.UPower",
+            "OnBattery",
+        ) {
+            ret.on_battery = on_battery == "true";
+            ret.battery_level = busctl_property(
+                "org.freedesktop.UPower",
+                "/org/freedesktop/UPower/devices/DisplayDevice",
+                "org.freedesktop.UPower.Device",
+                "Percentage",
+            )
+            .and_then(|p| p.parse::<f64>().ok())
+            .filter(|p| *p > 0.0)
+            .map(|p| p.round().min(100.0) as u8);
+        } else {
+            ret.read_sysfs(Path::new("/sys/class/power_supply"));
+        }
+        ret.metered = busctl_property(
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        )
+        .map(|m| is_metered(&m))
+        .unwrap_or(false);
+        ret
+    }
+
+    fn read_sysfs(&mut self, root: &Path) {
+        let entries = match std::fs::read_dir(root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let read = |path: &Path, attr: &str| -> Option<String> {
+            std::fs::read_to_string(path.join(attr))
+                .ok()
+                .map(|s| s.trim().to_string())
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if read(&path, "type").as_deref() != Some("Battery") {
+                continue;
+            }
+            if read(&path, "status").as_deref() == Some("Discharging") {
+                self.on_battery = true;
+            }
+            if let Some(level) = read(&path, "capacity").and_then(|c| c.parse::<u8>().ok()) {
+                self.battery_level = Some(
+                    self.battery_level
+                        .map_or(level, |prev| std::cmp::min(prev, level)),
+                );
+            }
+        }
+    }
+
+    /// Returns why background work should be paused according to `settings`,
+    /// or `None` if it should run.
+    pub fn pause_reason(&self, settings: &PowerSettings) -> Option<&'static str> {
+        if settings.pause_on_metered && self.metered {
+            return Some("metered connection");
+        }
+        if self.on_battery {
+            if settings.pause_on_battery {
+                return Some("on battery");
+            }
+            if self.battery_level.map_or(false, |level| {
+                usize::from(level) <= settings.low_battery_threshold
+            }) {
+                return Some("low battery");
+            }
+        }
+        None
+    }
+}
+
+/// Returns the reason background work is paused, if it is, taking the manual
+/// override `mode` into account.
+pub fn pause_reason(
+    mode: PowerSavingMode,
+    state: &PowerState,
+    settings: &PowerSettings,
+) -> Option<&'static str> {
+    match mode {
+        PowerSavingMode::On => Some("power saving enabled manually"),
+        PowerSavingMode::Off => None,
+        PowerSavingMode::Auto => state.pause_reason(settings),
+    }
+}
+
+fn busctl_property(service: &str, object: &str, interface: &str, name: &str) -> Option<String> {
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "--timeout=1",
+            "get-property",
+            service,
+            object,
+            interface,
+            name,
+        ])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_busctl_value(&String::from_utf8_lossy(&output.stdout)).map(str::to_string)
+}
+
+/// `busctl get-property` prints the D-Bus signature followed by the value,
+/// e.g. `b true` or `d 87.000000`.
+fn parse_busctl_value(output: &str) -> Option<&str> {
+    let mut parts = output.trim().splitn(2, ' ');
+    parts.next()?;
+    parts.next().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// `NMMetered` values: `1` is yes and `3` is guessed yes.
+fn is_metered(value: &str) -> bool {
+    matches!(value, "1" | "3")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_pause_reason() {
+        let settings = PowerSettings::default();
+        assert_eq!(parse_busctl_value("b true\n"), Some("true"));
+        assert_eq!(parse_busctl_value("d 87.000000\n"), Some("87.000000"));
+        assert_eq!(parse_busctl_value("u\n"), None);
+        assert!(is_metered("1") && is_metered("3"));
+        assert!(!is_metered("0") && !is_metered("2") && !is_metered("4"));
+
+        let mut state = PowerState::default();
+        assert_eq!(state.pause_reason(&settings), None);
+        state.on_battery = true;
+        state.battery_level = Some(80);
+        assert_eq!(state.pause_reason(&settings), None);
+        state.battery_level = Some(15);
+        assert_eq!(state.pause_reason(&settings), Some("low battery"));
+        state.on_battery = false;
+        assert_eq!(state.pause_reason(&settings), None);
+        state.metered = true;
+        assert_eq!(state.pause_reason(&settings), Some("metered connection"));
+        assert_eq!(pause_reason(PowerSavingMode::Off, &state, &settings), None);
+        assert_eq!(
+            pause_reason(PowerSavingMode::On, &PowerState::default(), &settings),
+            Some("power saving enabled manually")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, kind, status, capacity) in [
+            ("AC", "Mains", None, None),
+            ("BAT0", "Battery", Some("Discharging"), Some("42")),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            std::fs::write(path.join("type"), format!("{}\n", kind)).unwrap();
+            if let Some(status) = status {
+                std::fs::write(path.join("status"), status).unwrap();
+            }
+            if let Some(capacity) = capacity {
+                std::fs::write(path.join("capacity"), capacity).unwrap();
+            }
+        }
+        let mut state = PowerState::default();
+        state.read_sysfs(dir.path());
+        assert!(state.on_battery);
+        assert_eq!(state.battery_level, Some(42));
+    }
+}
@@ -0,0 +1,598 @@
+/*
+ * meli - recording.rs
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Recording and replaying sessions, to reproduce UI bugs.
+ *
+ * `meli --record FILE` writes what drives the UI to `FILE`, one JSON object
+ * per line: the terminal input, the terminal size, the envelopes of each
+ * mailbox once it has loaded and the mailbox changes reported by the
+ * backends afterwards, each with the milliseconds since startup.
+ * `meli --replay FILE` feeds them back with the same timing. Mailboxes
+ * aren't watched during a replay, changes reported by the backends are
+ * ignored in favour of the recorded ones, and the recorded envelopes of each
+ * mailbox replace the ones it loads, so that the replay runs against the
+ * mailboxes as they were when the recording was made. Terminal input is
+ * ignored until the replay finishes.
+ */
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam::channel::Sender;
+use melib::{
+    backends::{AccountHash, MailboxHash, RefreshEvent, RefreshEventKind},
+    email::{Envelope, EnvelopeHash, Flag},
+    error::{Error, Result, ResultIntoError},
+    log,
+};
+use termion::event::Event as TermionEvent;
+
+use crate::{
+    state::Context,
+    terminal::Key,
+    types::{StatusEvent, ThreadEvent, UIEvent},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    /// A key press or mouse event, as the bytes read from the terminal.
+    Input { bytes: Vec<u8> },
+    /// Text pasted in bracketed paste mode.
+    Paste { text: String },
+    /// The terminal size, at startup and after every resize.
+    Resize { cols: usize, rows: usize },
+    /// A change in a mailbox reported by the account's backend.
+    Refresh {
+        account: String,
+        mailbox: String,
+        change: RecordedChange,
+    },
+    /// The envelopes of a mailbox when it finished loading.
+    Snapshot {
+        account: String,
+        mailbox: String,
+        #[serde(with = "bincode_hex")]
+        envelopes: Vec<Envelope>,
+    },
+}
+
+/// The mailbox changes that are recorded; failures and changes of the
+/// mailboxes themselves aren't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedChange {
+    Update {
+        old_hash: EnvelopeHash,
+        #[serde(with = "bincode_hex")]
+        envelope: Box<Envelope>,
+    },
+    Rename {
+        old_hash: EnvelopeHash,
+        new_hash: EnvelopeHash,
+    },
+    Create {
+        #[serde(with = "bincode_hex")]
+        envelope: Box<Envelope>,
+    },
+    Remove {
+        hash: EnvelopeHash,
+    },
+    NewFlags {
+        hash: EnvelopeHash,
+        flags: Flag,
+        tags: Vec<String>,
+    },
+    Rescan,
+}
+
+impl RecordedChange {
+    pub fn from_kind(kind: &RefreshEventKind) -> Option<Self> {
+        use RefreshEventKind::*;
+        Some(match kind {
+            Update(old_hash, envelope) => RecordedChange::Update {
+                old_hash: *old_hash,
+                envelope: envelope.clone(),
+            },
+            Rename(old_hash, new_hash) => RecordedChange::Rename {
+                old_hash: *old_hash,
+                new_hash: *new_hash,
+            },
+            Create(envelope) => RecordedChange::Create {
+                envelope: envelope.clone(),
+            },
+            Remove(hash) => RecordedChange::Remove { hash: *hash },
+            NewFlags(hash, (flags, tags)) => RecordedChange::NewFlags {
+                hash: *hash,
+                flags: *flags,
+                tags: tags.clone(),
+            },
+            Rescan => RecordedChange::Rescan,
+            Failure(_)
+            | MailboxCreate(_)
+            | MailboxDelete(_)
+            | MailboxRename { .. }
+            | MailboxSubscribe(_)
+            | MailboxUnsubscribe(_) => return None,
+        })
+    }
+
+    pub fn into_kind(self) -> RefreshEventKind {
+        match self {
+            RecordedChange::Update { old_hash, envelope } => {
+                RefreshEventKind::Update(old_hash, envelope)
+            }
+            RecordedChange::Rename { old_hash, new_hash } => {
+                RefreshEventKind::Rename(old_hash, new_hash)
+            }
+            RecordedChange::Create { envelope } => RefreshEventKind::Create(envelope),
+            RecordedChange::Remove { hash } => RefreshEventKind::Remove(hash),
+            RecordedChange::NewFlags { hash, flags, tags } => {
+                RefreshEventKind::NewFlags(hash, (flags, tags))
+            }
+            RecordedChange::Rescan => RefreshEventKind::Rescan,
+        }
+    }
+}
+
+/// Envelopes are recorded as hex encoded `bincode`, since their header maps
+/// can't be JSON objects.
+mod bincode_hex {
+    use bincode::Options;
+    use serde::{de, de::DeserializeOwned, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let bytes = bincode::config::DefaultOptions::new()
+            .serialize(value)
+            .map_err(ser::Error::custom)?;
+        serializer.serialize_str(
+            &bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+        )
+    }
+
+    pub fn deserialize<'de, T: DeserializeOwned, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| de::Error::custom("invalid hex encoded envelope"))?;
+        bincode::config::DefaultOptions::new()
+            .deserialize(&bytes)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// A line of a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// Milliseconds since the start of the recording.
+    ms: u64,
+    #[serde(flatten)]
+    event: RecordedEvent,
+}
+
+/// Writes the events of a session to a recording file. Recordings contain
+/// everything typed and the headers of new messages, so they are only
+/// readable by the user.
+#[derive(Debug)]
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    /// Mailboxes whose envelopes have been recorded.
+    snapshots: HashSet<(AccountHash, MailboxHash)>,
+}
+
+impl Recorder {
+    pub fn new(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .chain_err_summary(|| format!("Could not create recording `{}`", path.display()))?;
+        Ok(Recorder {
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+            snapshots: HashSet::default(),
+        })
+    }
+
+    /// Appends `event` to the recording. Every line is flushed, so that the
+    /// recording is complete even if meli crashes afterwards.
+    pub fn record(&mut self, event: RecordedEvent) {
+        let entry = Entry {
+            ms: self.start.elapsed().as_millis() as u64,
+            event,
+        };
+        let ret = serde_json::to_writer(&mut self.writer, &entry)
+            .map_err(|err| Error::new(err.to_string()))
+            .and_then(|()| writeln!(self.writer).map_err(Error::from))
+            .and_then(|()| self.writer.flush().map_err(Error::from));
+        if let Err(err) = ret {
+            log::error!("Could not write to recording: {}", err);
+        }
+    }
+
+    pub fn record_input(&mut self, key: &Key, bytes: &[u8]) {
+        self.record(match key {
+            Key::Paste(text) => RecordedEvent::Paste { text: text.clone() },
+            _ => RecordedEvent::Input {
+                bytes: bytes.to_vec(),
+            },
+        });
+    }
+
+    pub fn record_refresh(&mut self, context: &Context, event: &RefreshEvent) {
+        let account = match context.accounts.get(&event.account_hash) {
+            Some(account) => account,
+            None => return,
+        };
+        let mailbox = match account.mailbox_entries.get(&event.mailbox_hash) {
+            Some(entry) => entry.ref_mailbox.path().to_string(),
+            None => return,
+        };
+        if let Some(change) = RecordedChange::from_kind(&event.kind) {
+            self.record(RecordedEvent::Refresh {
+                account: account.name.clone(),
+                mailbox,
+                change,
+            });
+        }
+    }
+
+    /// Records the envelopes of the mailboxes that have finished loading since
+    /// the last call.
+    pub fn record_snapshots(&mut self, context: &Context) {
+        for account in context.accounts.values() {
+            for (mailbox_hash, entry) in account.mailbox_entries.iter() {
+                if !entry.status.is_available()
+                    || !self.snapshots.insert((account.hash(), *mailbox_hash))
+                {
+                    continue;
+                }
+                let envelopes = {
+                    let mailboxes = account.collection.mailboxes.read().unwrap();
+                    let envelopes = account.collection.envelopes.read().unwrap();
+                    mailboxes
+                        .get(mailbox_hash)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|env_hash| envelopes.get(env_hash).cloned())
+                        .collect::<Vec<Envelope>>()
+                };
+                self.record(RecordedEvent::Snapshot {
+                    account: account.name.clone(),
+                    mailbox: entry.ref_mailbox.path().to_string(),
+                    envelopes,
+                });
+            }
+        }
+    }
+}
+
+/// Reads a recording made with [`Recorder`].
+pub fn read_recording(path: &Path) -> Result<Vec<(Duration, RecordedEvent)>> {
+    let file = File::open(path)
+        .chain_err_summary(|| format!("Could not open recording `{}`", path.display()))?;
+    let mut ret = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line).map_err(|err| {
+            Error::new(format!(
+                "Could not parse line {} of `{}`: {}",
+                i + 1,
+                path.display(),
+                err
+            ))
+        })?;
+        ret.push((Duration::from_millis(entry.ms), entry.event));
+    }
+    Ok(ret)
+}
+
+/// Returns the key of the terminal input `bytes`.
+pub fn parse_input(bytes: &[u8]) -> Option<Key> {
+    /* termion reads a lone escape byte as Esc only when no other bytes are
+     * pending. */
+    if bytes == b"\x1b" {
+        return Some(Key::Esc);
+    }
+    let mut iter = bytes.iter().map(|b| Ok(*b));
+    let first = iter.next()?.ok()?;
+    match termion::event::parse_event(first, &mut iter) {
+        Ok(TermionEvent::Key(k)) => Some(Key::from(k)),
+        Ok(TermionEvent::Mouse(mev)) => Some(Key::Mouse(mev)),
+        _ => None,
+    }
+}
+
+/// Sends the events of a recording at their recorded times: input to the
+/// main event loop through `sender`, the rest to `replay_tx` for
+/// [`Replay::apply`]. Terminal input is dropped through `ignore_input` until
+/// the replay ends.
+pub fn spawn_replay(
+    events: Vec<(Duration, RecordedEvent)>,
+    sender: Sender<ThreadEvent>,
+    replay_tx: Sender<RecordedEvent>,
+    ignore_input: Arc<AtomicBool>,
+) -> Result<thread::JoinHandle<()>> {
+    ignore_input.store(true, Ordering::SeqCst);
+    Ok(thread::Builder::new()
+        .name("replay".to_string())
+        .spawn(move || {
+            /* Let the terminal through again however the replay ends. */
+            struct Restore(Arc<AtomicBool>);
+            impl Drop for Restore {
+                fn drop(&mut self) {
+                    self.0.store(false, Ordering::SeqCst);
+                }
+            }
+            let _restore = Restore(ignore_input);
+            let start = Instant::now();
+            for (at, event) in events {
+                if let Some(wait) = at.checked_sub(start.elapsed()) {
+                    thread::sleep(wait);
+                }
+                let ret = match event {
+                    RecordedEvent::Input { bytes } => match parse_input(&bytes) {
+                        Some(key) => sender.send(ThreadEvent::Input((key, bytes))).is_ok(),
+                        None => {
+                            log::warn!("replay: could not parse input {:?}", bytes);
+                            true
+                        }
+                    },
+                    RecordedEvent::Paste { text } => {
+                        let bytes = text.as_bytes().to_vec();
+                        sender
+                            .send(ThreadEvent::Input((Key::Paste(text), bytes)))
+                            .is_ok()
+                    }
+                    event => replay_tx.send(event).is_ok(),
+                };
+                if !ret {
+                    return;
+                }
+            }
+            let _ = sender.send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
+                StatusEvent::DisplayMessage("Replay finished.".to_string()),
+            )));
+        })?)
+}
+
+/// Applies the recorded sizes, envelopes and mailbox changes of a replay.
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// Events waiting for a snapshot ahead of them to be applied, in order.
+    pending: VecDeque<RecordedEvent>,
+}
+
+impl Replay {
+    pub fn apply(&mut self, state: &mut crate::State, event: RecordedEvent) {
+        self.pending.push_back(event);
+        self.apply_pending(state);
+    }
+
+    /// Applies the pending events in order, up to the first snapshot of a
+    /// mailbox that hasn't finished loading yet. Called again when jobs
+    /// finish.
+    pub fn apply_pending(&mut self, state: &mut crate::State) {
+        while let Some(event) = self.pending.front() {
+            if let RecordedEvent::Snapshot {
+                ref account,
+                ref mailbox,
+                ..
+            } = event
+            {
+                if let Some((account_hash, mailbox_hash)) = find_mailbox(state, account, mailbox) {
+                    if state.context.accounts[&account_hash]
+                        .load(mailbox_hash)
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            let event = self.pending.pop_front().unwrap();
+            apply(state, event);
+        }
+    }
+}
+
+fn find_mailbox(
+    state: &crate::State,
+    account: &str,
+    mailbox: &str,
+) -> Option<(AccountHash, MailboxHash)> {
+    let account_hash = AccountHash::from_bytes(account.as_bytes());
+    state
+        .context
+        .accounts
+        .get(&account_hash)
+        .and_then(|a| {
+            a.mailbox_entries
+                .iter()
+                .find(|(_, entry)| entry.ref_mailbox.path() == mailbox)
+        })
+        .map(|(hash, _)| (account_hash, *hash))
+}
+
+fn apply(state: &mut crate::State, event: RecordedEvent) {
+    match event {
+        RecordedEvent::Resize { cols, rows } => {
+            if let Ok((term_cols, term_rows)) = termion::terminal_size() {
+                if (term_cols as usize) < cols || (term_rows as usize) < rows {
+                    state.context.replies.push_back(UIEvent::StatusEvent(
+                        StatusEvent::DisplayMessage(format!(
+                            "The recording was made in a {}x{} terminal, larger than this {}x{} \
+                             one.",
+                            cols, rows, term_cols, term_rows
+                        )),
+                    ));
+                }
+            }
+            state.set_fixed_size(Some((cols, rows)));
+            state.update_size();
+            state.render();
+        }
+        RecordedEvent::Refresh {
+            account,
+            mailbox,
+            change,
+        } => match find_mailbox(state, &account, &mailbox) {
+            Some((account_hash, mailbox_hash)) => state.refresh_event(RefreshEvent {
+                account_hash,
+                mailbox_hash,
+                kind: change.into_kind(),
+            }),
+            None => log::warn!("replay: mailbox {}/{} not found", account, mailbox),
+        },
+        RecordedEvent::Snapshot {
+            account,
+            mailbox,
+            envelopes,
+        } => match find_mailbox(state, &account, &mailbox) {
+            Some((account_hash, mailbox_hash)) => {
+                let collection = &state.context.accounts[&account_hash].collection;
+                let env_hashes = envelopes.iter().map(Envelope::hash).collect::<Vec<_>>();
+                {
+                    let mut store = collection.envelopes.write().unwrap();
+                    for envelope in envelopes {
+                        store.insert(envelope.hash(), envelope);
+                    }
+                }
+                collection.set_mailbox_envelopes(mailbox_hash, &env_hashes);
+                state
+                    .context
+                    .replies
+                    .push_back(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+            }
+            None => log::warn!("replay: mailbox {}/{} not found", account, mailbox),
+        },
+        RecordedEvent::Input { .. } | RecordedEvent::Paste { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_parse_input() {
+        assert_eq!(parse_input(b"j"), Some(Key::Char('j')));
+        assert_eq!(parse_input(b"\r"), Some(Key::Char('\n')));
+        assert_eq!(parse_input(b"\x1b"), Some(Key::Esc));
+        assert_eq!(parse_input(b"\x1bj"), Some(Key::Alt('j')));
+        assert_eq!(parse_input(b"\x1b[A"), Some(Key::Up));
+        assert_eq!(parse_input(b"\x12"), Some(Key::Ctrl('r')));
+        assert_eq!(parse_input("ä".as_bytes()), Some(Key::Char('ä')));
+        assert_eq!(parse_input(b""), None);
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording");
+        let mut recorder = Recorder::new(&path).unwrap();
+        recorder.record(RecordedEvent::Resize { cols: 80, rows: 24 });
+        recorder.record_input(&Key::Char('j'), b"j");
+        recorder.record_input(&Key::Paste("pasted".to_string()), b"pasted");
+        let envelope = Envelope::from_bytes(
+            b"From: a@example.com\nSubject: Hello\nMessage-ID: <1@example.com>\n\nbody\n",
+            None,
+        )
+        .unwrap();
+        recorder.record(RecordedEvent::Refresh {
+            account: "personal".to_string(),
+            mailbox: "INBOX".to_string(),
+            change: RecordedChange::from_kind(&RefreshEventKind::Create(Box::new(
+                envelope.clone(),
+            )))
+            .unwrap(),
+        });
+        recorder.record(RecordedEvent::Snapshot {
+            account: "personal".to_string(),
+            mailbox: "INBOX".to_string(),
+            envelopes: vec![envelope.clone()],
+        });
+        drop(recorder);
+
+        let events = read_recording(&path).unwrap();
+        assert_eq!(events.len(), 5);
+        assert!(events.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(matches!(
+            events[0].1,
+            RecordedEvent::Resize { cols: 80, rows: 24 }
+        ));
+        assert!(matches!(events[1].1, RecordedEvent::Input { ref bytes } if bytes == b"j"));
+        assert!(matches!(events[2].1, RecordedEvent::Paste { ref text } if text == "pasted"));
+        match events[3].1.clone() {
+            RecordedEvent::Refresh {
+                account,
+                mailbox,
+                change,
+            } => {
+                assert_eq!(account, "personal");
+                assert_eq!(mailbox, "INBOX");
+                match change.into_kind() {
+                    RefreshEventKind::Create(env) => assert_eq!(env.subject(), "Hello"),
+                    other => panic!("unexpected change {:?}", other),
+                }
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        match events[4].1 {
+            RecordedEvent::Snapshot { ref envelopes, .. } => {
+                assert_eq!(envelopes.len(), 1);
+                assert_eq!(envelopes[0].hash(), envelope.hash());
+                assert_eq!(envelopes[0].subject(), "Hello");
+            }
+            ref other => panic!("unexpected event {:?}", other),
+        }
+        assert!(RecordedChange::from_kind(&RefreshEventKind::Failure(Error::new("x"))).is_none());
+    }
+}
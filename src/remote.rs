@@ -0,0 +1,261 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Forwarding the drafts of the `compose` subcommand to a running instance.
+//!
+//! A running instance listens on the `meli-compose` Unix socket in the
+//! runtime directory. `meli compose` sends its draft there as one line of
+//! JSON and the instance opens it in a new composer tab. If no instance
+//! listens, `meli compose` starts one itself.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+};
+
+use melib::{
+    error::{Error, Result, ResultIntoError},
+    log,
+};
+
+use crate::{
+    command::actions::{Action, TabAction},
+    components::mail::Composer,
+    types::{CallbackFn, NotificationType, ThreadEvent, UIEvent},
+    Context,
+};
+
+/// A new draft given to the `compose` subcommand.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComposeRequest {
+    /// The account to compose in, or the first one if `None`.
+    pub account: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    /// Absolute paths of the files to attach.
+    pub attachments: Vec<PathBuf>,
+}
+
+impl ComposeRequest {
+    /// Returns a composer with this draft.
+    pub fn composer(self, context: &Context) -> Result<Composer> {
+        let account_hash = match self.account {
+            Some(ref name) => context
+                .accounts
+                .values()
+                .find(|a| a.name == *name)
+                .map(|a| a.hash())
+                .ok_or_else(|| {
+                    Error::new(format!("Account `{}` not found in configuration.", name))
+                })?,
+            None => context
+                .accounts
+                .get_index(0)
+                .map(|(hash, _)| *hash)
+                .ok_or_else(|| Error::new("No accounts are configured."))?,
+        };
+        let attachments = self
+            .attachments
+            .iter()
+            .map(|path| {
+                melib::email::compose::attachment_from_file(path)
+                    .chain_err_summary(|| format!("Could not attach `{}`", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut composer = Composer::with_account(account_hash, context);
+        let draft = composer.draft_mut();
+        for (header, value) in self.headers {
+            draft.set_header(&header, value);
+        }
+        if let Some(body) = self.body {
+            draft.set_body(body);
+        }
+        draft.attachments_mut().extend(attachments);
+        Ok(composer)
+    }
+
+    /// Opens this draft in a new composer tab, or reports why it can't.
+    pub fn open(self, context: &mut Context) {
+        match self.composer(context) {
+            Ok(composer) => {
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Action::Tab(TabAction::New(Some(
+                        Box::new(composer),
+                    )))))
+            }
+            Err(err) => context.replies.push_back(UIEvent::Notification(
+                Some("Could not open forwarded draft".to_string()),
+                err.to_string(),
+                Some(NotificationType::Error(err.kind)),
+            )),
+        }
+    }
+}
+
+/// Returns the path of the socket running instances listen on.
+pub fn socket_path() -> Result<PathBuf> {
+    let base_dirs = xdg::BaseDirectories::new().map_err(|err| Error::new(err.to_string()))?;
+    Ok(base_dirs
+        .place_runtime_file("meli-compose")
+        .or_else(|_| base_dirs.place_cache_file("meli-compose"))?)
+}
+
+/// Sends `request` to the instance listening on `path`. Returns `false` if no
+/// instance listens there.
+pub fn forward(path: &Path, request: &ComposeRequest) -> Result<bool> {
+    let mut stream = match UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(err)
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false);
+        }
+        Err(err) => {
+            return Err(
+                Error::from(err).set_summary(format!("Could not connect to `{}`", path.display()))
+            )
+        }
+    };
+    let mut line = serde_json::to_string(request).map_err(|err| Error::new(err.to_string()))?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim_end().strip_prefix("ERR ") {
+        None if reply.trim_end() == "OK" => Ok(true),
+        Some(err) => Err(Error::new(err.to_string())),
+        None => Err(Error::new(format!(
+            "Unexpected reply from running instance: {:?}",
+            reply
+        ))),
+    }
+}
+
+/// Answers the requests sent to `listener` with [`forward`], passing each to
+/// `on_request`. Runs until `listener` fails.
+pub fn serve(listener: UnixListener, on_request: impl Fn(ComposeRequest)) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Could not accept forwarded draft: {}", err);
+                continue;
+            }
+        };
+        let mut line = String::new();
+        let reply = match stream
+            .try_clone()
+            .and_then(|stream| BufReader::new(stream).read_line(&mut line))
+            .map_err(Error::from)
+            .and_then(|_| {
+                serde_json::from_str::<ComposeRequest>(&line)
+                    .map_err(|err| Error::new(err.to_string()))
+            }) {
+            Ok(request) => {
+                on_request(request);
+                "OK\n".to_string()
+            }
+            Err(err) => format!("ERR {}\n", err.to_string().replace('\n', " ")),
+        };
+        let _ = stream.write_all(reply.as_bytes());
+    }
+}
+
+/// Listens for forwarded drafts and opens them, for as long as it is alive.
+#[derive(Debug)]
+pub struct ComposeListener {
+    path: PathBuf,
+}
+
+impl ComposeListener {
+    /// Starts listening, unless another instance already does.
+    pub fn new(sender: crossbeam::channel::Sender<ThreadEvent>) -> Result<Option<Self>> {
+        let path = socket_path()?;
+        if UnixStream::connect(&path).is_ok() {
+            return Ok(None);
+        }
+        /* Remove the socket of an instance that didn't exit cleanly. */
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .chain_err_summary(|| format!("Could not listen on `{}`", path.display()))?;
+        std::thread::Builder::new()
+            .name("compose-listener".to_string())
+            .spawn(move || {
+                serve(listener, move |request| {
+                    let _ = sender.send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+                        Box::new(move |context: &mut Context| request.open(context)),
+                    ))));
+                })
+            })?;
+        Ok(Some(ComposeListener { path }))
+    }
+}
+
+impl Drop for ComposeListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_forward() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meli-compose");
+        let request = ComposeRequest {
+            account: Some("work".to_string()),
+            headers: vec![
+                ("To".to_string(), "a@example.com".to_string()),
+                ("Subject".to_string(), "patch".to_string()),
+            ],
+            body: Some("body\nlines\n".to_string()),
+            attachments: vec![PathBuf::from("/tmp/0001.patch")],
+        };
+
+        /* Nothing listens yet, or anymore. */
+        assert!(!forward(&path, &request).unwrap());
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(!forward(&path, &request).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let listener = UnixListener::bind(&path).unwrap();
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        std::thread::spawn(move || serve(listener, move |request| sender.send(request).unwrap()));
+        assert!(forward(&path, &request).unwrap());
+        assert_eq!(receiver.recv().unwrap(), request);
+
+        /* Malformed requests are answered with an error. */
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"{\"headers\": 1}\n").unwrap();
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply).unwrap();
+        assert!(reply.starts_with("ERR "), "{}", reply);
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * meli - sd_notify.rs
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Service manager notifications, see `sd_notify(3)`.
+ *
+ * When meli is started by systemd with `Type=notify` the `NOTIFY_SOCKET`
+ * environment variable names a datagram socket that receives the readiness,
+ * status text and watchdog keep-alive messages. Without it every method is a
+ * no-op.
+ */
+use std::{
+    os::unix::net::UnixDatagram,
+    time::{Duration, Instant},
+};
+
+use melib::log;
+
+#[derive(Debug, Default)]
+pub struct SdNotify {
+    socket: Option<(UnixDatagram, String)>,
+    /// Keep-alive messages are sent at half the `WATCHDOG_USEC` interval.
+    watchdog_interval: Option<Duration>,
+    last_ping: Option<Instant>,
+    status: String,
+}
+
+impl SdNotify {
+    pub fn from_env() -> Self {
+        let path = match std::env::var("NOTIFY_SOCKET") {
+            Ok(path) if !path.is_empty() => path,
+            _ => return Self::default(),
+        };
+        if path.starts_with('@') {
+            log::warn!(
+                "NOTIFY_SOCKET {} is in the abstract namespace, which is not supported.",
+                path
+            );
+            return Self::default();
+        }
+        let socket = match UnixDatagram::unbound() {
+            Ok(socket) => socket,
+            Err(err) => {
+                log::warn!("Could not create socket for NOTIFY_SOCKET: {}", err);
+                return Self::default();
+            }
+        };
+        let watchdog_interval = watchdog_interval(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        SdNotify {
+            socket: Some((socket, path)),
+            watchdog_interval,
+            last_ping: None,
+            status: String::new(),
+        }
+    }
+
+    fn send(&self, message: &str) {
+        if let Some((ref socket, ref path)) = self.socket {
+            if let Err(err) = socket.send_to(message.as_bytes(), path) {
+                log::debug!("Could not notify service manager at {}: {}", path, err);
+            }
+        }
+    }
+
+    /// Tells the service manager that startup has finished.
+    pub fn ready(&mut self) {
+        self.send("READY=1");
+        self.last_ping = Some(Instant::now());
+    }
+
+    /// Tells the service manager that meli is shutting down.
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// Sets the status text shown by `systemctl status`, if it changed.
+    pub fn status(&mut self, status: &str) {
+        if self.socket.is_some() && self.status != status {
+            self.status = status.to_string();
+            self.send(&format!("STATUS={}", status.replace('\n', " ")));
+        }
+    }
+
+    /// Sends a watchdog keep-alive message if one is due.
+    pub fn watchdog(&mut self) {
+        let interval = match self.watchdog_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        if self
+            .last_ping
+            .map(|last| last.elapsed() >= interval)
+            .unwrap_or(true)
+        {
+            self.send("WATCHDOG=1");
+            self.last_ping = Some(Instant::now());
+        }
+    }
+}
+
+/// Returns half the watchdog timeout in `WATCHDOG_USEC`, if the watchdog is
+/// enabled for process `pid`.
+fn watchdog_interval(usec: Option<&str>, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    if let Some(watchdog_pid) = watchdog_pid {
+        if watchdog_pid.parse::<u32>().ok() != Some(pid) {
+            return None;
+        }
+    }
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sd_notify_watchdog_interval() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(Some("soon"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        let mut notify = SdNotify {
+            socket: Some((UnixDatagram::unbound().unwrap(), path.display().to_string())),
+            watchdog_interval: Some(Duration::from_secs(3600)),
+            last_ping: None,
+            status: String::new(),
+        };
+        let mut buf = [0; 64];
+        notify.ready();
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+        notify.status("2 accounts online");
+        notify.status("2 accounts online");
+        notify.watchdog();
+        notify.stopping();
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"STATUS=2 accounts online");
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"STOPPING=1");
+    }
+}
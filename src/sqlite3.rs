@@ -22,21 +22,25 @@
 /*! Use an sqlite3 database for fast searching.
  */
 use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
     path::PathBuf,
     sync::{Arc, RwLock},
 };
 
+use indexmap::IndexMap;
 use melib::{
     backends::{MailBackend, ResultFuture},
-    email::{Envelope, EnvelopeHash},
+    email::{Attachment, Envelope, EnvelopeHash},
     log,
     search::{
-        escape_double_quote,
+        escape_double_quote, FlagQuery,
         Query::{self, *},
     },
     sqlite3::{self as melib_sqlite3, rusqlite::params, DatabaseDescription},
+    text_processing::GlobMatch,
     thread::{SortField, SortOrder},
-    Error, Result,
+    EnvelopeStore, Envelopes, Error, MemoryStore, Result,
 };
 use smallvec::SmallVec;
 
@@ -61,7 +65,8 @@ const DB: DatabaseDescription = DatabaseDescription {
                     flags            INTEGER NOT NULL,
                     has_attachments  BOOLEAN NOT NULL,
                     body_text        TEXT NOT NULL,
-                    timestamp        BLOB NOT NULL
+                    timestamp        BLOB NOT NULL,
+                    attachment_text  TEXT NOT NULL DEFAULT ''
                    );
         CREATE TABLE IF NOT EXISTS folders (
                     id               INTEGER PRIMARY KEY,
@@ -112,7 +117,7 @@ CREATE TRIGGER IF NOT EXISTS envelopes_au AFTER UPDATE ON envelopes BEGIN
   INSERT INTO fts(rowid, subject, body_text) VALUES (new.id, new.subject, new.body_text);
 END; ",
     ),
-    version: 1,
+    version: 2,
 };
 
 pub fn db_path() -> Result<PathBuf> {
@@ -143,10 +148,93 @@ pub fn db_path() -> Result<PathBuf> {
 //}
 //
 //
+/// Returns the commands to extract attachment text with, if the account has
+/// `index_attachments` enabled.
+pub fn attachment_text_commands(
+    conf: &crate::conf::FileAccount,
+) -> Option<IndexMap<String, String>> {
+    if conf.index_attachments {
+        Some(conf.attachment_text_commands.clone())
+    } else {
+        None
+    }
+}
+
+/// Runs `command` with `input` on its standard input and returns its output.
+fn run_text_command(command: &str, input: &[u8]) -> Result<String> {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    /* Write from another thread, the command might not read all of its input
+     * before its output buffer fills up. */
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(Error::new(format!(
+            "`{}` exited with {}",
+            command, output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Extracts the searchable text of the attachments of a message: their file
+/// names, the contents of textual attachments and the output of the command in
+/// `commands` whose MIME type glob matches.
+pub fn attachment_text(body: &Attachment, commands: &IndexMap<String, String>) -> String {
+    let mut ret = String::new();
+    for a in body.attachments() {
+        let filename = a.filename();
+        if filename.is_none() && !a.content_disposition.kind.is_attachment() {
+            /* Inline parts are indexed as the body text. */
+            continue;
+        }
+        if let Some(filename) = filename {
+            ret.push_str(&filename);
+            ret.push('\n');
+        }
+        let mime_type = a.mime_type();
+        let text = if let Some((_, command)) = commands
+            .iter()
+            .find(|(glob, _)| mime_type.as_str().matches_glob(glob))
+        {
+            match run_text_command(command, &a.decode(Default::default())) {
+                Ok(text) => text,
+                Err(err) => {
+                    log::error!(
+                        "Failed to extract text from {} attachment: {err}",
+                        mime_type
+                    );
+                    continue;
+                }
+            }
+        } else if a.is_text() {
+            String::from_utf8_lossy(&a.decode(Default::default())).into_owned()
+        } else {
+            continue;
+        };
+        ret.push_str(&text);
+        ret.push('\n');
+    }
+    ret.replace('\0', "")
+}
+
 pub async fn insert(
     envelope: Envelope,
     backend: Arc<RwLock<Box<dyn MailBackend>>>,
     acc_name: String,
+    attachment_text_commands: Option<IndexMap<String, String>>,
 ) -> Result<()> {
     let db_path = db_path()?;
     if !db_path.exists() {
@@ -163,8 +251,14 @@ pub async fn insert(
         .operation(envelope.hash())?
         .as_bytes()?;
 
-    let body = match op.await.map(|bytes| envelope.body_bytes(&bytes)) {
-        Ok(body) => body.text(),
+    let (body, attachment_text) = match op.await.map(|bytes| envelope.body_bytes(&bytes)) {
+        Ok(body) => (
+            body.text(),
+            attachment_text_commands
+                .filter(|_| envelope.has_attachments())
+                .map(|commands| attachment_text(&body, &commands))
+                .unwrap_or_default(),
+        ),
         Err(err) => {
             debug!(
                 "{}",
@@ -213,8 +307,8 @@ pub async fn insert(
         .execute(
             "INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, \
              subject, message_id, in_reply_to, _references, flags, has_attachments, body_text, \
-             timestamp)
-              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+             timestamp, attachment_text)
+              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
             params![
                 account_id,
                 envelope.hash().to_be_bytes().to_vec(),
@@ -233,7 +327,8 @@ pub async fn insert(
                 i64::from(envelope.flags().bits()),
                 if envelope.has_attachments() { 1 } else { 0 },
                 body,
-                envelope.date().to_be_bytes().to_vec()
+                envelope.date().to_be_bytes().to_vec(),
+                attachment_text
             ],
         )
         .map_err(|e| Error::new(e.to_string()))
@@ -275,11 +370,128 @@ pub fn remove(env_hash: EnvelopeHash) -> Result<()> {
 
 pub fn index(context: &mut crate::state::Context, account_index: usize) -> ResultFuture<()> {
     let account = &context.accounts[account_index];
-    let (acc_name, acc_mutex, backend_mutex): (String, Arc<RwLock<_>>, Arc<_>) = (
+    index_envelopes(
         account.name().to_string(),
         account.collection.envelopes.clone(),
         account.backend.clone(),
-    );
+        attachment_text_commands(&account.settings.conf),
+    )
+}
+
+/// Returns the id of account `acc_name` in the index, adding it if needed.
+fn account_id(conn: &melib_sqlite3::Connection, acc_name: &str) -> Result<i32> {
+    conn.execute(
+        "INSERT OR IGNORE INTO accounts (name) VALUES (?1)",
+        params![acc_name],
+    )
+    .chain_err_summary(|| "Failed to update index:")?;
+    conn.query_row(
+        "SELECT id FROM accounts WHERE name = ?",
+        params![acc_name],
+        |row| row.get(0),
+    )
+    .chain_err_summary(|| "Failed to update index:")
+}
+
+/// Inserts or replaces the index entry of envelope `e` with raw bytes
+/// `bytes`, leaving out the attachment text.
+fn insert_row(
+    conn: &melib_sqlite3::Connection,
+    account_id: i32,
+    e: &Envelope,
+    bytes: &[u8],
+) -> Result<()> {
+    let body = e.body_bytes(bytes).text().replace('\0', "");
+    conn.execute(
+        "INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, cc, bcc, subject, \
+         message_id, in_reply_to, _references, flags, has_attachments, body_text, timestamp)
+              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        params![
+            account_id,
+            e.hash().to_be_bytes().to_vec(),
+            e.date_as_str(),
+            e.field_from_to_string(),
+            e.field_to_to_string(),
+            e.field_cc_to_string(),
+            e.field_bcc_to_string(),
+            e.subject().into_owned().trim_end_matches('\u{0}'),
+            e.message_id_display().to_string(),
+            e.in_reply_to_display()
+                .map(|f| f.to_string())
+                .unwrap_or_default(),
+            e.field_references_to_string(),
+            i64::from(e.flags().bits()),
+            if e.has_attachments() { 1 } else { 0 },
+            body,
+            e.date().to_be_bytes().to_vec()
+        ],
+    )
+    .chain_err_summary(|| format!("Failed to insert envelope {}", e.message_id_display()))?;
+    Ok(())
+}
+
+/// Adds the envelopes among `env_hashes` that aren't in the index of account
+/// `acc_name` yet. Run in the background after each mailbox is loaded, so
+/// that the index stays complete without a manual `reindex`.
+pub fn index_new_envelopes(
+    acc_name: String,
+    env_hashes: Vec<EnvelopeHash>,
+    acc_mutex: Envelopes,
+    backend_mutex: Arc<RwLock<Box<dyn MailBackend>>>,
+) -> ResultFuture<()> {
+    let conn = melib_sqlite3::open_or_create_db(&DB, None)?;
+    Ok(Box::pin(async move {
+        let account_id = account_id(&conn, &acc_name)?;
+        let indexed: HashSet<EnvelopeHash> = {
+            let mut stmt = conn
+                .prepare("SELECT hash FROM envelopes WHERE account_id = ?")
+                .chain_err_summary(|| "Failed to update index:")?;
+            let indexed = stmt
+                .query_map(params![account_id], |row| row.get::<_, EnvelopeHash>(0))
+                .chain_err_summary(|| "Failed to update index:")?
+                .filter_map(|hash| hash.ok())
+                .collect();
+            indexed
+        };
+        let env_hashes = env_hashes
+            .into_iter()
+            .filter(|env_hash| !indexed.contains(env_hash))
+            .collect::<Vec<_>>();
+        if env_hashes.is_empty() {
+            return Ok(());
+        }
+        log::trace!(
+            "Indexing {} new envelopes of account {}",
+            env_hashes.len(),
+            acc_name
+        );
+        for chunk in env_hashes.chunks(200) {
+            for env_hash in chunk {
+                let mut op = backend_mutex.read().unwrap().operation(*env_hash)?;
+                let bytes = op
+                    .as_bytes()?
+                    .await
+                    .chain_err_summary(|| format!("Failed to open envelope {}", env_hash))?;
+                let envelopes_lck = acc_mutex.read().unwrap();
+                if let Some(e) = envelopes_lck.get(env_hash) {
+                    insert_row(&conn, account_id, e, &bytes)?;
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        Ok(())
+    }))
+}
+
+/// Rebuilds the index of account `acc_name` from `acc_mutex`, the envelopes
+/// of the whole account. Entries of envelopes that no longer exist are
+/// removed.
+pub fn index_envelopes(
+    acc_name: String,
+    acc_mutex: Envelopes,
+    backend_mutex: Arc<RwLock<Box<dyn MailBackend>>>,
+    attachment_text_commands: Option<IndexMap<String, String>>,
+) -> ResultFuture<()> {
     let conn = melib_sqlite3::open_or_create_db(&DB, None)?;
     let env_hashes = acc_mutex
         .read()
@@ -290,23 +502,7 @@ pub fn index(context: &mut crate::state::Context, account_index: usize) -> Resul
 
     /* Sleep, index and repeat in order not to block the main process */
     Ok(Box::pin(async move {
-        conn.execute(
-            "INSERT OR REPLACE INTO accounts (name) VALUES (?1)",
-            params![acc_name.as_str(),],
-        )
-        .chain_err_summary(|| "Failed to update index:")?;
-        let account_id: i32 = {
-            let mut stmt = conn
-                .prepare("SELECT id FROM accounts WHERE name = ?")
-                .unwrap();
-            let x = stmt
-                .query_map(params![acc_name.as_str()], |row| row.get(0))
-                .unwrap()
-                .next()
-                .unwrap()
-                .unwrap();
-            x
-        };
+        let account_id = account_id(&conn, &acc_name)?;
         let mut ctr = 0;
         debug!(
             "{}",
@@ -327,44 +523,107 @@ pub fn index(context: &mut crate::state::Context, account_index: usize) -> Resul
                     .chain_err_summary(|| format!("Failed to open envelope {}", env_hash))?;
                 let envelopes_lck = acc_mutex.read().unwrap();
                 if let Some(e) = envelopes_lck.get(env_hash) {
-                    let body = e.body_bytes(&bytes).text().replace('\0', "");
+                    insert_row(&conn, account_id, e, &bytes)?;
+                }
+            }
+            let sleep_dur = std::time::Duration::from_millis(20);
+            std::thread::sleep(sleep_dur);
+        }
+        {
+            /* Remove envelopes that were deleted since the last indexing. */
+            let current: HashSet<EnvelopeHash> = env_hashes.iter().cloned().collect();
+            let mut stmt = conn
+                .prepare("SELECT hash FROM envelopes WHERE account_id = ?")
+                .chain_err_summary(|| "Failed to update index:")?;
+            let stale = stmt
+                .query_map(params![account_id], |row| row.get::<_, Vec<u8>>(0))
+                .chain_err_summary(|| "Failed to update index:")?
+                .filter_map(|hash| {
+                    let hash = EnvelopeHash(u64::from_be_bytes(hash.ok()?.try_into().ok()?));
+                    Some(hash).filter(|h| !current.contains(h))
+                })
+                .collect::<Vec<EnvelopeHash>>();
+            for env_hash in stale {
+                conn.execute(
+                    "DELETE FROM envelopes WHERE hash = ?",
+                    params![env_hash.to_be_bytes().to_vec()],
+                )
+                .chain_err_summary(|| format!("Failed to remove envelope {}", env_hash))?;
+            }
+        }
+        /* Extracting attachment text may run external commands, so do it after
+         * everything else is indexed and at a slower pace. */
+        if let Some(commands) = attachment_text_commands {
+            let env_hashes = env_hashes
+                .into_iter()
+                .filter(|env_hash| {
+                    acc_mutex
+                        .read()
+                        .unwrap()
+                        .get(env_hash)
+                        .map(|e| e.has_attachments())
+                        .unwrap_or(false)
+                })
+                .collect::<Vec<_>>();
+            for chunk in env_hashes.chunks(20) {
+                for env_hash in chunk {
+                    let mut op = backend_mutex.read().unwrap().operation(*env_hash)?;
+                    let bytes = op
+                        .as_bytes()?
+                        .await
+                        .chain_err_summary(|| format!("Failed to open envelope {}", env_hash))?;
+                    let text = match acc_mutex.read().unwrap().get(env_hash) {
+                        Some(e) => attachment_text(&e.body_bytes(&bytes), &commands),
+                        None => continue,
+                    };
                     conn.execute(
-                        "INSERT OR REPLACE INTO envelopes (account_id, hash, date, _from, _to, \
-                         cc, bcc, subject, message_id, in_reply_to, _references, flags, \
-                         has_attachments, body_text, timestamp)
-              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
-                        params![
-                            account_id,
-                            e.hash().to_be_bytes().to_vec(),
-                            e.date_as_str(),
-                            e.field_from_to_string(),
-                            e.field_to_to_string(),
-                            e.field_cc_to_string(),
-                            e.field_bcc_to_string(),
-                            e.subject().into_owned().trim_end_matches('\u{0}'),
-                            e.message_id_display().to_string(),
-                            e.in_reply_to_display()
-                                .map(|f| f.to_string())
-                                .unwrap_or_default(),
-                            e.field_references_to_string(),
-                            i64::from(e.flags().bits()),
-                            if e.has_attachments() { 1 } else { 0 },
-                            body,
-                            e.date().to_be_bytes().to_vec()
-                        ],
+                        "UPDATE envelopes SET attachment_text = ?1 WHERE hash = ?2",
+                        params![text, env_hash.to_be_bytes().to_vec()],
                     )
                     .chain_err_summary(|| {
-                        format!("Failed to insert envelope {}", e.message_id_display())
+                        format!("Failed to index attachments of envelope {}", env_hash)
                     })?;
                 }
+                std::thread::sleep(std::time::Duration::from_millis(200));
             }
-            let sleep_dur = std::time::Duration::from_millis(20);
-            std::thread::sleep(sleep_dur);
         }
         Ok(())
     }))
 }
 
+/// Rebuilds the index of an account without starting the user interface:
+/// every mailbox is fetched from the backend and then indexed. Returns the
+/// number of indexed envelopes.
+pub fn reindex_account(conf: &crate::conf::AccountConf) -> Result<usize> {
+    use futures::{executor::block_on, StreamExt};
+
+    let backends = melib::backends::Backends::new();
+    let backend = backends.get(&conf.account().format)(
+        conf.account(),
+        Box::new(|_: &str| true),
+        melib::backends::BackendEventConsumer::new(Arc::new(|_, _| {})),
+    )?;
+    let backend = Arc::new(RwLock::new(backend));
+    let mailboxes = block_on(backend.read().unwrap().mailboxes()?)?;
+    let mut envelopes = HashMap::default();
+    for mailbox_hash in mailboxes.keys() {
+        let mut stream = backend.write().unwrap().fetch(*mailbox_hash)?;
+        while let Some(batch) = block_on(stream.next()) {
+            envelopes.extend(batch?.into_iter().map(|e| (e.hash(), e)));
+        }
+    }
+    let total = envelopes.len();
+    block_on(index_envelopes(
+        conf.account().name.clone(),
+        Arc::new(RwLock::new(
+            Box::new(MemoryStore::from(envelopes)) as Box<dyn EnvelopeStore>
+        )),
+        backend,
+        attachment_text_commands(&conf.conf),
+    )?)?;
+    Ok(total)
+}
+
 pub fn search(
     query: &Query,
     (sort_field, sort_order): (SortField, SortOrder),
@@ -408,6 +667,15 @@ pub fn search(
     Ok(Box::pin(async { results }))
 }
 
+/// Matches envelopes whose body contains the words of `t`, the last one as a
+/// prefix, using the `fts` full text index instead of scanning every body.
+fn fts5_body_match(t: &str) -> String {
+    format!(
+        "id IN (SELECT rowid FROM fts WHERE body_text MATCH '\"{}\"*') ",
+        escape_double_quote(t).replace('\'', "''")
+    )
+}
+
 /// Translates a `Query` to an Sqlite3 expression in a `String`.
 pub fn query_to_sql(q: &Query) -> String {
     fn rec(q: &Query, s: &mut String) {
@@ -437,10 +705,15 @@ pub fn query_to_sql(q: &Query) -> String {
                 s.extend(escape_double_quote(t).chars());
                 s.push_str("%\" ");
             }
-            AllText(t) => {
-                s.push_str("body_text LIKE \"%");
-                s.extend(escape_double_quote(t).chars());
-                s.push_str("%\" ");
+            Body(t) | AllText(t) => {
+                s.push_str(&fts5_body_match(t));
+            }
+            /* Timestamps are stored as big endian blobs, which sqlite compares bytewise. */
+            Before(t) => {
+                s.push_str(&format!("timestamp < X'{:016x}' ", t));
+            }
+            After(t) => {
+                s.push_str(&format!("timestamp > X'{:016x}' ", t));
             }
             And(q1, q2) => {
                 s.push('(');
@@ -462,51 +735,32 @@ pub fn query_to_sql(q: &Query) -> String {
                 s.push_str(") ");
             }
             Flags(v) => {
-                let total = v.len();
-                if total > 1 {
+                /* Tags aren't stored in the index; `Account::search` evaluates queries with
+                 * tags locally instead. */
+                let parts = v
+                    .iter()
+                    .filter_map(|f| match FlagQuery::new(f) {
+                        FlagQuery::Set(flag) => Some(format!(" (flags & {} > 0) ", flag.bits())),
+                        FlagQuery::Unset(flag) => Some(format!(" (flags & {} == 0) ", flag.bits())),
+                        FlagQuery::Tag(_) => None,
+                    })
+                    .collect::<Vec<String>>();
+                if parts.len() > 1 {
                     s.push('(');
                 }
-                for (i, f) in v.iter().enumerate() {
-                    match f.as_str() {
-                        "draft" => {
-                            s.push_str(" (flags & 8 > 0) ");
-                        }
-                        "deleted" | "trashed" => {
-                            s.push_str(" (flags & 6 > 0) ");
-                        }
-                        "flagged" => {
-                            s.push_str(" (flags & 16 > 0) ");
-                        }
-                        "recent" => {
-                            s.push_str(" (flags & 4 == 0) ");
-                        }
-                        "seen" | "read" => {
-                            s.push_str(" (flags & 4 > 0) ");
-                        }
-                        "unseen" | "unread" => {
-                            s.push_str(" (flags & 4 == 0) ");
-                        }
-                        "answered" | "replied" => {
-                            s.push_str(" (flags & 2 > 0) ");
-                        }
-                        "unanswered" => {
-                            s.push_str(" (flags & 2 == 0) ");
-                        }
-                        _ => {
-                            continue;
-                        }
-                    }
-                    if total > 1 && i != total - 1 {
-                        s.push_str(" AND ");
-                    }
-                }
-                if total > 1 {
+                s.push_str(&parts.join(" AND "));
+                if parts.len() > 1 {
                     s.push_str(") ");
                 }
             }
             HasAttachment => {
                 s.push_str("has_attachments == 1 ");
             }
+            Attachment(t) => {
+                s.push_str("attachment_text LIKE \"%");
+                s.extend(escape_double_quote(t).chars());
+                s.push_str("%\" ");
+            }
             _ => {}
         }
     }
@@ -519,7 +773,8 @@ pub fn query_to_sql(q: &Query) -> String {
 fn test_query_to_sql() {
     use melib::{parsec::Parser, search::query};
     assert_eq!(
-        "(subject LIKE \"%test%\" ) AND (body_text LIKE \"%i%\" ) ",
+        "(subject LIKE \"%test%\" ) AND (id IN (SELECT rowid FROM fts WHERE body_text MATCH \
+         '\"i\"*') ) ",
         &query_to_sql(&query().parse_complete("subject: test and i").unwrap().1)
     );
     assert_eq!(
@@ -534,4 +789,29 @@ fn test_query_to_sql() {
                 .1
         )
     );
+    assert_eq!(
+        "(attachment_text LIKE \"%budget%\" ) AND (has_attachments == 1 ) ",
+        &query_to_sql(
+            &query()
+                .parse_complete("attachment:budget and has:attachment")
+                .unwrap()
+                .1
+        )
+    );
+    assert_eq!(
+        "( (flags & 32 > 0)  AND  (flags & 4 == 0) ) ",
+        &query_to_sql(&query().parse_complete("flags:flagged,unread").unwrap().1)
+    );
+    assert_eq!(
+        "timestamp < X'000000005e0a1f00' ",
+        &query_to_sql(&Before(1577721600))
+    );
+    assert_eq!(
+        "id IN (SELECT rowid FROM fts WHERE body_text MATCH '\"it''s \"\"done\"\"\"*') ",
+        &query_to_sql(&Body("it's \"done\"".to_string()))
+    );
+    assert_eq!(
+        " (flags & 2 > 0) ",
+        &query_to_sql(&query().parse_complete("is:answered").unwrap().1)
+    );
 }
@@ -28,7 +28,16 @@ The UI crate has an Box<dyn Component>-Component-System design. The System part,
 Input is received in the main loop from threads which listen on the stdin for user input, observe folders for file changes etc. The relevant struct is `ThreadEvent`.
 */
 
-use std::{env, os::unix::io::RawFd, sync::Arc, thread};
+use std::{
+    env,
+    os::unix::io::RawFd,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use indexmap::IndexMap;
@@ -37,7 +46,30 @@ use melib::backends::{AccountHash, BackendEventConsumer};
 use smallvec::SmallVec;
 
 use super::*;
-use crate::{jobs::JobExecutor, terminal::screen::Screen};
+use crate::{
+    conf::{
+        notifications::{in_dnd_schedule, DndMode},
+        ContactHighlight,
+    },
+    jobs::{JobExecutor, Timer},
+    power::{PowerSavingMode, PowerState},
+    terminal::screen::Screen,
+};
+
+/// How much more wall-clock time than monotonic time may pass between two
+/// account checks before it is assumed that the machine was suspended.
+const CLOCK_JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Whether the wall clock advanced by more than [`CLOCK_JUMP_THRESHOLD`]
+/// past `monotonic`, the time measured by the monotonic clock, which doesn't
+/// count time spent suspended.
+fn clock_jumped(monotonic: Duration, wall_before: SystemTime, wall_now: SystemTime) -> bool {
+    match wall_now.duration_since(wall_before) {
+        Ok(wall) => wall > monotonic + CLOCK_JUMP_THRESHOLD,
+        /* The wall clock was set back. */
+        Err(_) => false,
+    }
+}
 
 struct InputHandler {
     pipe: (RawFd, RawFd),
@@ -45,6 +77,8 @@ struct InputHandler {
     tx: Sender<InputCommand>,
     state_tx: Sender<ThreadEvent>,
     control: std::sync::Weak<()>,
+    /// While set, terminal input is read but dropped.
+    ignore: Arc<AtomicBool>,
 }
 
 impl InputHandler {
@@ -59,12 +93,15 @@ impl InputHandler {
         let rx = self.rx.clone();
         let pipe = self.pipe.0;
         let tx = self.state_tx.clone();
+        let ignore = self.ignore.clone();
         thread::Builder::new()
             .name("input-thread".to_string())
             .spawn(move || {
                 get_events(
                     |i| {
-                        tx.send(ThreadEvent::Input(i)).unwrap();
+                        if !ignore.load(Ordering::SeqCst) {
+                            tx.send(ThreadEvent::Input(i)).unwrap();
+                        }
                     },
                     &rx,
                     pipe,
@@ -106,6 +143,18 @@ pub struct Context {
     input_thread: InputHandler,
     pub job_executor: Arc<JobExecutor>,
     pub children: Vec<std::process::Child>,
+    /// The last detected power source and network state.
+    pub power_state: PowerState,
+    /// Manual override of the power saving decision.
+    pub power_saving: PowerSavingMode,
+    /// The workspace selected with the `workspace` command, if any.
+    pub workspace: Option<String>,
+    /// Manual override of the do not disturb schedule.
+    pub dnd: DndMode,
+    /// New mail notifications suppressed by do not disturb, per account.
+    pub dnd_suppressed: IndexMap<AccountHash, usize>,
+    /// Recent delete, move and flag actions that can be undone.
+    pub undo: crate::undo::UndoStack,
 
     pub temp_files: Vec<File>,
 }
@@ -115,6 +164,110 @@ impl Context {
         self.replies.drain(0..).collect()
     }
 
+    /// Pauses or resumes the background work of every account according to
+    /// `power_state`, the `power` settings and the `power-saving` override.
+    pub fn update_power_saving(&mut self) {
+        let reason =
+            crate::power::pause_reason(self.power_saving, &self.power_state, &self.settings.power);
+        let paused = reason.is_some();
+        if self
+            .accounts
+            .values()
+            .all(|a| a.background_paused == paused)
+        {
+            return;
+        }
+        for account in self.accounts.values_mut() {
+            account.background_paused = paused;
+        }
+        self.replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                match reason {
+                    Some(reason) => tr!("Background sync paused: {}.", reason),
+                    None => tr!("Background sync resumed."),
+                },
+            )));
+    }
+
+    /// Returns whether the account at `account_pos` is shown in the active
+    /// workspace. Accounts that don't belong to any workspace are always
+    /// shown.
+    pub fn is_account_visible(&self, account_pos: usize) -> bool {
+        match self.workspace {
+            None => true,
+            Some(ref workspace) => {
+                let workspaces = &self.accounts[account_pos].settings.conf.workspaces;
+                workspaces.is_empty() || workspaces.contains(workspace)
+            }
+        }
+    }
+
+    /// Returns whether new mail notifications of the account are suppressed,
+    /// either by the `dnd` command or by its `dnd_schedule` setting.
+    pub fn is_dnd_active(&self, account_hash: AccountHash) -> bool {
+        match self.dnd {
+            DndMode::On => true,
+            DndMode::Off => false,
+            DndMode::Auto => in_dnd_schedule(
+                account_settings!(self[account_hash].notifications.dnd_schedule),
+                melib::datetime::now(),
+            ),
+        }
+    }
+
+    /// Returns the color and label of `address`, from the `contacts`
+    /// settings or else from the `COLOR` and `LABEL` properties of its card
+    /// in the account's address book.
+    pub fn contact_highlight(
+        &self,
+        account_hash: AccountHash,
+        address: &Address,
+    ) -> Option<ContactHighlight> {
+        if let Some(highlight) = self.settings.contacts.find(address) {
+            return Some(highlight.clone());
+        }
+        let email = address.get_email();
+        if email.is_empty() {
+            return None;
+        }
+        self.accounts
+            .get(&account_hash)?
+            .address_book
+            .values()
+            .filter(|card| card.email().eq_ignore_ascii_case(&email))
+            .find_map(ContactHighlight::from_card)
+    }
+
+    /// Sends a summary notification for every account whose do not disturb
+    /// period has ended, and updates the status bar indicator.
+    pub fn update_dnd(&mut self) {
+        let ended = self
+            .dnd_suppressed
+            .keys()
+            .copied()
+            .filter(|h| !self.accounts.contains_key(h) || !self.is_dnd_active(*h))
+            .collect::<SmallVec<[AccountHash; 8]>>();
+        for account_hash in ended {
+            let count = self.dnd_suppressed.remove(&account_hash).unwrap_or(0);
+            if count == 0 || !self.accounts.contains_key(&account_hash) {
+                continue;
+            }
+            self.replies.push_back(UIEvent::Notification(
+                Some("do not disturb ended".to_string()),
+                format!(
+                    "{} new message{} in {}",
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    self.accounts[&account_hash].name()
+                ),
+                Some(NotificationType::NewMail),
+            ));
+        }
+        let active = self.accounts.keys().any(|h| self.is_dnd_active(*h));
+        self.replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::SetDnd(active)));
+    }
+
     pub fn input_kill(&self) {
         self.input_thread.kill();
     }
@@ -123,6 +276,12 @@ impl Context {
         self.input_thread.restore();
     }
 
+    /// Returns the flag that makes the input thread drop terminal input while
+    /// it is set, e.g. while a recording is replayed.
+    pub fn ignore_input_flag(&self) -> Arc<AtomicBool> {
+        self.input_thread.ignore.clone()
+    }
+
     pub fn is_online_idx(&mut self, account_pos: usize) -> Result<()> {
         let Context {
             ref mut accounts,
@@ -211,6 +370,12 @@ impl Context {
             temp_files: Vec::new(),
             job_executor,
             children: vec![],
+            power_state: PowerState::default(),
+            power_saving: PowerSavingMode::default(),
+            workspace: None,
+            dnd: DndMode::default(),
+            dnd_suppressed: IndexMap::default(),
+            undo: Default::default(),
 
             input_thread: InputHandler {
                 pipe: input_thread_pipe,
@@ -218,6 +383,7 @@ impl Context {
                 tx: input_thread.0,
                 control,
                 state_tx: sender.clone(),
+                ignore: Arc::new(AtomicBool::new(false)),
             },
             sender,
             receiver,
@@ -231,12 +397,22 @@ impl Context {
 pub struct State {
     screen: Box<Screen>,
     draw_rate_limit: RateLimit,
+    /// Fires every `power.check_interval` seconds to detect the power state.
+    power_timer: Option<Timer>,
+    /// Fires every minute to check whether do not disturb has started or
+    /// ended.
+    dnd_timer: Timer,
+    /// Fires every 30 seconds to send the messages scheduled with `send-at`
+    /// that are due.
+    scheduled_send_timer: Timer,
     child: Option<ForkType>,
     pub mode: UIMode,
     overlay: Vec<Box<dyn Component>>,
     components: Vec<Box<dyn Component>>,
     pub context: Box<Context>,
     timer: thread::JoinHandle<()>,
+    /// When accounts were last checked, to detect resumes from suspend.
+    last_check: (Instant, SystemTime),
 
     display_messages: SmallVec<[DisplayMessage; 8]>,
     display_messages_expiration_start: Option<UnixTimestamp>,
@@ -281,19 +457,15 @@ impl State {
         sender: Sender<ThreadEvent>,
         receiver: Receiver<ThreadEvent>,
     ) -> Result<Self> {
-        /*
-         * Create async channel to block the input-thread if we need to fork and stop
-         * it from reading stdin, see get_events() for details
-         */
-        let input_thread = unbounded();
-        let input_thread_pipe = nix::unistd::pipe()
-            .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send + Sync + 'static>)?;
         let backends = Backends::new();
         let settings = Box::new(if let Some(settings) = settings {
             settings
         } else {
             Settings::new()?
         });
+        if let Err(err) = crate::i18n::init(settings.terminal.locale.as_deref()) {
+            log::error!("Could not load user interface translation: {}", err);
+        }
         /*
         let mut plugin_manager = PluginManager::new();
         for (_, p) in settings.plugins.clone() {
@@ -310,8 +482,63 @@ impl State {
         */
 
         let termsize = termion::terminal_size()?;
-        let cols = termsize.0 as usize;
-        let rows = termsize.1 as usize;
+        let mut s = Self::new_inner(
+            settings,
+            &backends,
+            (termsize.0 as usize, termsize.1 as usize),
+            sender,
+            receiver,
+        )?;
+
+        s.screen.switch_to_alternate_screen(&s.context);
+        for i in 0..s.context.accounts.len() {
+            if !s.context.accounts[i].backend_capabilities.is_remote {
+                s.context.accounts[i].watch();
+            }
+            if s.context.is_online_idx(i).is_ok() && s.context.accounts[i].is_empty() {
+                //return Err(Error::new(format!(
+                //    "Account {} has no mailboxes configured.",
+                //    s.context.accounts[i].name()
+                //)));
+            }
+        }
+        s.context.restore_input();
+        Ok(s)
+    }
+
+    /// Creates a `State` that draws into its grid only, without a terminal
+    /// or an input thread, for tests that drive the UI with synthetic
+    /// events. See `crate::testing`.
+    #[cfg(test)]
+    pub fn new_headless(
+        settings: Settings,
+        backends: &Backends,
+        cols: usize,
+        rows: usize,
+    ) -> Result<Self> {
+        let (sender, receiver) =
+            crossbeam::channel::bounded(32 * ::std::mem::size_of::<ThreadEvent>());
+        let mut s = Self::new_inner(Box::new(settings), backends, (cols, rows), sender, receiver)?;
+        for i in 0..s.context.accounts.len() {
+            s.context.accounts[i].watch();
+        }
+        Ok(s)
+    }
+
+    fn new_inner(
+        settings: Box<Settings>,
+        backends: &Backends,
+        (cols, rows): (usize, usize),
+        sender: Sender<ThreadEvent>,
+        receiver: Receiver<ThreadEvent>,
+    ) -> Result<Self> {
+        /*
+         * Create async channel to block the input-thread if we need to fork and stop
+         * it from reading stdin, see get_events() for details
+         */
+        let input_thread = unbounded();
+        let input_thread_pipe = nix::unistd::pipe()
+            .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send + Sync + 'static>)?;
 
         let job_executor = Arc::new(JobExecutor::new(sender.clone()));
         let accounts = {
@@ -325,7 +552,7 @@ impl State {
                         account_hash,
                         n.to_string(),
                         a_s.clone(),
-                        &backends,
+                        backends,
                         job_executor.clone(),
                         sender.clone(),
                         BackendEventConsumer::new(Arc::new(
@@ -368,6 +595,7 @@ impl State {
                 grid: CellBuffer::new(cols, rows, Cell::with_char(' ')),
                 overlay_grid: CellBuffer::new(cols, rows, Cell::with_char(' ')),
                 mouse: settings.terminal.use_mouse.is_true(),
+                fixed_size: None,
                 stdout: None,
                 draw_horizontal_segment_fn: if settings.terminal.use_color() {
                     Screen::draw_horizontal_segment
@@ -380,7 +608,24 @@ impl State {
             components: Vec::with_capacity(8),
             overlay: Vec::new(),
             timer,
+            last_check: (Instant::now(), SystemTime::now()),
             draw_rate_limit: RateLimit::new(1, 3, job_executor.clone()),
+            dnd_timer: job_executor.clone().create_timer(
+                std::time::Duration::from_secs(60),
+                std::time::Duration::from_secs(1),
+            ),
+            scheduled_send_timer: job_executor.clone().create_timer(
+                std::time::Duration::from_secs(30),
+                std::time::Duration::from_secs(5),
+            ),
+            power_timer: Some(settings.power.check_interval)
+                .filter(|secs| *secs > 0)
+                .map(|secs| {
+                    job_executor.clone().create_timer(
+                        std::time::Duration::from_secs(secs),
+                        std::time::Duration::from_secs(1),
+                    )
+                }),
             display_messages: SmallVec::new(),
             display_messages_expiration_start: None,
             display_messages_pos: 0,
@@ -396,6 +641,12 @@ impl State {
                 temp_files: Vec::new(),
                 job_executor,
                 children: vec![],
+                power_state: PowerState::default(),
+                power_saving: PowerSavingMode::default(),
+                workspace: None,
+                dnd: DndMode::default(),
+                dnd_suppressed: IndexMap::default(),
+                undo: Default::default(),
 
                 input_thread: InputHandler {
                     pipe: input_thread_pipe,
@@ -403,6 +654,7 @@ impl State {
                     tx: input_thread.0,
                     control,
                     state_tx: sender.clone(),
+                    ignore: Arc::new(AtomicBool::new(false)),
                 },
                 sender,
                 receiver,
@@ -412,20 +664,6 @@ impl State {
             s.screen.grid.set_ascii_drawing(true);
             s.screen.overlay_grid.set_ascii_drawing(true);
         }
-
-        s.screen.switch_to_alternate_screen(&s.context);
-        for i in 0..s.context.accounts.len() {
-            if !s.context.accounts[i].backend_capabilities.is_remote {
-                s.context.accounts[i].watch();
-            }
-            if s.context.is_online_idx(i).is_ok() && s.context.accounts[i].is_empty() {
-                //return Err(Error::new(format!(
-                //    "Account {} has no mailboxes configured.",
-                //    s.context.accounts[i].name()
-                //)));
-            }
-        }
-        s.context.restore_input();
         Ok(s)
     }
 
@@ -455,7 +693,15 @@ impl State {
 
             if let Some(notification) = accounts[&account_hash].reload(event, mailbox_hash) {
                 if let UIEvent::Notification(_, _, _) = notification {
-                    self.rcv_event(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+                    /* Accounts outside the active workspace don't notify. */
+                    let account_pos = self.context.accounts.get_index_of(&account_hash).unwrap();
+                    if !self.context.is_account_visible(account_pos) {
+                        return;
+                    }
+                    if self.context.is_dnd_active(account_hash) {
+                        *self.context.dnd_suppressed.entry(account_hash).or_default() += 1;
+                        return;
+                    }
                 }
                 self.rcv_event(notification);
             }
@@ -476,6 +722,18 @@ impl State {
         self.context.restore_input();
     }
 
+    /// The size of the screen, in columns and rows.
+    pub fn size(&self) -> (usize, usize) {
+        (self.screen.cols, self.screen.rows)
+    }
+
+    /// Draws in a `cols`×`rows` screen regardless of the terminal's size, or
+    /// follows the terminal again if `None`. Takes effect on the next
+    /// `update_size`.
+    pub fn set_fixed_size(&mut self, size: Option<(usize, usize)>) {
+        self.screen.fixed_size = size;
+    }
+
     /// On `SIGWNICH` the `State` redraws itself according to the new terminal
     /// size.
     pub fn update_size(&mut self) {
@@ -491,7 +749,7 @@ impl State {
 
     /// Force a redraw for all dirty components.
     pub fn redraw(&mut self) {
-        if !self.draw_rate_limit.tick() {
+        if self.screen.stdout.is_some() && !self.draw_rate_limit.tick() {
             return;
         }
 
@@ -500,6 +758,32 @@ impl State {
         }
         let mut areas: smallvec::SmallVec<[Area; 8]> =
             self.context.dirty_areas.drain(0..).collect();
+        if self.screen.stdout.is_none() {
+            /* Headless, see `State::new_headless`: the grid is all there is, and
+             * an overlay is drawn on a copy of it. */
+            if !self.overlay.is_empty() {
+                let area = self.overlay_area();
+                let screen_area = (
+                    (0, 0),
+                    (
+                        self.screen.cols.saturating_sub(1),
+                        self.screen.rows.saturating_sub(1),
+                    ),
+                );
+                copy_area(
+                    &mut self.screen.overlay_grid,
+                    &self.screen.grid,
+                    screen_area,
+                    screen_area,
+                );
+                self.overlay.get_mut(0).unwrap().draw(
+                    &mut self.screen.overlay_grid,
+                    area,
+                    &mut self.context,
+                );
+            }
+            return;
+        }
         if self.display_messages_active {
             let now = melib::datetime::now();
             if self
@@ -664,12 +948,12 @@ impl State {
                     if self.display_messages.len() > 1 {
                         write_string_to_grid(
                             &if self.display_messages_pos == 0 {
-                                format!(
+                                tr!(
                                     "Next: {}",
                                     self.context.settings.shortcuts.general.info_message_next
                                 )
                             } else if self.display_messages_pos + 1 == self.display_messages.len() {
-                                format!(
+                                tr!(
                                     "Prev: {}",
                                     self.context
                                         .settings
@@ -678,7 +962,7 @@ impl State {
                                         .info_message_previous
                                 )
                             } else {
-                                format!(
+                                tr!(
                                     "Prev: {} Next: {}",
                                     self.context
                                         .settings
@@ -726,27 +1010,7 @@ impl State {
             self.display_messages_dirty = false;
         }
         if !self.overlay.is_empty() {
-            let area = center_area(
-                (
-                    (0, 0),
-                    (
-                        self.screen.cols.saturating_sub(1),
-                        self.screen.rows.saturating_sub(1),
-                    ),
-                ),
-                (
-                    if self.screen.cols / 3 > 30 {
-                        self.screen.cols / 3
-                    } else {
-                        self.screen.cols
-                    },
-                    if self.screen.rows / 5 > 10 {
-                        self.screen.rows / 5
-                    } else {
-                        self.screen.rows
-                    },
-                ),
-            );
+            let area = self.overlay_area();
             copy_area(&mut self.screen.overlay_grid, &self.screen.grid, area, area);
             self.overlay.get_mut(0).unwrap().draw(
                 &mut self.screen.overlay_grid,
@@ -766,6 +1030,31 @@ impl State {
         self.flush();
     }
 
+    /// The area of the screen the first overlay is drawn in.
+    fn overlay_area(&self) -> Area {
+        center_area(
+            (
+                (0, 0),
+                (
+                    self.screen.cols.saturating_sub(1),
+                    self.screen.rows.saturating_sub(1),
+                ),
+            ),
+            (
+                if self.screen.cols / 3 > 30 {
+                    self.screen.cols / 3
+                } else {
+                    self.screen.cols
+                },
+                if self.screen.rows / 5 > 10 {
+                    self.screen.rows / 5
+                } else {
+                    self.screen.rows
+                },
+            ),
+        )
+    }
+
     /// Draw the entire screen from scratch.
     pub fn render(&mut self) {
         self.screen.update_size();
@@ -801,6 +1090,85 @@ impl State {
         components.iter_mut().all(|c| c.can_quit_cleanly(context))
     }
 
+    /// Waits up to `timeout` for messages that are being sent, then logs out
+    /// of the accounts' servers. Called before exiting on `SIGTERM`.
+    pub fn shutdown(&mut self, timeout: std::time::Duration) {
+        use crate::conf::accounts::JobRequest;
+
+        let deadline = Instant::now() + timeout;
+        while self
+            .context
+            .accounts
+            .values()
+            .any(|a| a.active_jobs.values().any(JobRequest::is_send_message))
+        {
+            let now = Instant::now();
+            if now >= deadline {
+                log::warn!("Exiting while messages are still being sent.");
+                break;
+            }
+            match self.context.receiver.recv_timeout(deadline - now) {
+                Ok(ThreadEvent::JobFinished(id)) => {
+                    for account in self.context.accounts.values_mut() {
+                        if account.process_event(&id) {
+                            break;
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        for account in self.context.accounts.values() {
+            let logout = match account.backend.read().unwrap().logout() {
+                Ok(logout) => logout,
+                Err(err) => {
+                    log::debug!("{}: could not log out: {}", account.name, err);
+                    continue;
+                }
+            };
+            let remaining = deadline
+                .saturating_duration_since(Instant::now())
+                .max(std::time::Duration::from_secs(1));
+            if let Err(err) =
+                futures::executor::block_on(melib::connections::timeout(Some(remaining), logout))
+                    .and_then(|res| res)
+            {
+                log::debug!("{}: could not log out: {}", account.name, err);
+            }
+        }
+    }
+
+    /// A short summary of the state of the accounts, shown as the service
+    /// status by the service manager.
+    pub fn status_summary(&self) -> String {
+        let total = self.context.accounts.len();
+        let online = self
+            .context
+            .accounts
+            .values()
+            .filter(|a| a.is_online.is_ok())
+            .count();
+        let sending = self
+            .context
+            .accounts
+            .values()
+            .flat_map(|a| a.active_jobs.values())
+            .filter(|j| j.is_send_message())
+            .count();
+        if sending == 0 {
+            format!("{}/{} accounts online", online, total)
+        } else {
+            format!(
+                "{}/{} accounts online, sending {} message{}",
+                online,
+                total,
+                sending,
+                if sending == 1 { "" } else { "s" }
+            )
+        }
+    }
+
     pub fn register_component(&mut self, component: Box<dyn Component>) {
         self.components.push(component);
     }
@@ -832,7 +1200,7 @@ impl State {
                     }
                 } else {
                     self.context.replies.push_back(UIEvent::StatusEvent(
-                        StatusEvent::DisplayMessage(format!(
+                        StatusEvent::DisplayMessage(tr!(
                             "Account with name `{}` not found.",
                             account_name
                         )),
@@ -851,7 +1219,7 @@ impl State {
                 } else {
                     self.context.replies.push_back(UIEvent::Notification(
                         None,
-                        format!("Account {} was not found.", account_name),
+                        tr!("Account {} was not found.", account_name),
                         Some(NotificationType::Error(ErrorKind::None)),
                     ));
                     return;
@@ -864,7 +1232,7 @@ impl State {
                 {
                     self.context.replies.push_back(UIEvent::Notification(
                         None,
-                        format!(
+                        tr!(
                             "Account {} doesn't have an sqlite3 search backend.",
                             account_name
                         ),
@@ -886,13 +1254,13 @@ impl State {
                         );
                         self.context.replies.push_back(UIEvent::Notification(
                             None,
-                            "Message index rebuild started.".to_string(),
+                            tr!("Message index rebuild started."),
                             Some(NotificationType::Info),
                         ));
                     }
                     Err(err) => {
                         self.context.replies.push_back(UIEvent::Notification(
-                            Some("Message index rebuild failed".to_string()),
+                            Some(tr!("Message index rebuild failed")),
                             err.to_string(),
                             Some(NotificationType::Error(err.kind)),
                         ));
@@ -908,6 +1276,44 @@ impl State {
                     Some(NotificationType::Error(ErrorKind::None)),
                 ));
             }
+            #[cfg(feature = "carddav")]
+            AccountAction(ref account_name, SyncContacts) => {
+                if let Some(account) = self
+                    .context
+                    .accounts
+                    .values_mut()
+                    .find(|a| a.name() == account_name)
+                {
+                    match account.sync_contacts() {
+                        Ok(()) => {
+                            self.context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(tr!("Contacts sync started.")),
+                            ));
+                        }
+                        Err(err) => {
+                            self.context.replies.push_back(UIEvent::Notification(
+                                Some(tr!("Contacts sync failed")),
+                                err.to_string(),
+                                Some(NotificationType::Error(err.kind)),
+                            ));
+                        }
+                    }
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        tr!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                }
+            }
+            #[cfg(not(feature = "carddav"))]
+            AccountAction(_, SyncContacts) => {
+                self.context.replies.push_back(UIEvent::Notification(
+                    None,
+                    tr!("Contacts sync failed: meli is not built with CardDAV support."),
+                    Some(NotificationType::Error(ErrorKind::None)),
+                ));
+            }
             AccountAction(ref account_name, PrintAccountSetting(ref setting)) => {
                 let path = setting.split('.').collect::<SmallVec<[&str; 16]>>();
                 if let Some(pos) = self
@@ -927,7 +1333,7 @@ impl State {
                 } else {
                     self.context.replies.push_back(UIEvent::Notification(
                         None,
-                        format!("Account {} was not found.", account_name),
+                        tr!("Account {} was not found.", account_name),
                         Some(NotificationType::Error(ErrorKind::None)),
                     ));
                 }
@@ -943,6 +1349,48 @@ impl State {
                             .unwrap_or_else(|err| err.to_string()),
                     )));
             }
+            PowerSaving(mode) => {
+                self.context.power_saving = mode;
+                self.context.update_power_saving();
+                self.rcv_event(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    "Power saving: {} ({}).",
+                    mode, self.context.power_state
+                ))));
+            }
+            Workspace(name) => {
+                if let Some(ref name) = name {
+                    if !self
+                        .context
+                        .accounts
+                        .values()
+                        .any(|a| a.settings.conf.workspaces.contains(name))
+                    {
+                        self.context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(tr!(
+                                "No account belongs to workspace `{}`.",
+                                name
+                            )),
+                        ));
+                        return;
+                    }
+                }
+                self.context.workspace = name.clone();
+                self.rcv_event(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    match name {
+                        Some(ref name) => tr!("Workspace: {}.", name),
+                        None => tr!("Showing all accounts."),
+                    },
+                )));
+                self.rcv_event(UIEvent::Action(Workspace(name)));
+            }
+            Dnd(mode) => {
+                self.context.dnd = mode;
+                self.context.update_dnd();
+                self.rcv_event(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    "Do not disturb: {}.",
+                    mode
+                ))));
+            }
             ToggleMouse => {
                 self.screen.mouse = !self.screen.mouse;
                 self.screen.set_mouse(self.screen.mouse);
@@ -967,10 +1415,10 @@ impl State {
 
     /// The application's main loop sends `UIEvents` to state via this method.
     pub fn rcv_event(&mut self, mut event: UIEvent) {
-        if let UIEvent::Input(_) = event {
-            if self.display_messages_expiration_start.is_none() {
-                self.display_messages_expiration_start = Some(melib::datetime::now());
-            }
+        if matches!(event, UIEvent::Input(_) | UIEvent::Mouse(_))
+            && self.display_messages_expiration_start.is_none()
+        {
+            self.display_messages_expiration_start = Some(melib::datetime::now());
         }
 
         match event {
@@ -1033,7 +1481,7 @@ impl State {
                             }
                             Err(err) => {
                                 self.context.replies.push_back(UIEvent::StatusEvent(
-                                    StatusEvent::DisplayMessage(format!(
+                                    StatusEvent::DisplayMessage(tr!(
                                         "Could not load configuration: {}",
                                         err
                                     )),
@@ -1043,6 +1491,25 @@ impl State {
                     } else {
                         self.exec_command(action);
                     }
+                } else if let Some(commands) = self.context.settings.commands.expand(&cmd) {
+                    /* Aliases are made of built-in commands only; check them all before
+                     * running any. */
+                    if let Some(invalid) = commands
+                        .iter()
+                        .find(|c| parse_command(c.as_bytes()).is_err())
+                    {
+                        self.context.replies.push_back(UIEvent::StatusEvent(
+                            StatusEvent::DisplayMessage(format!(
+                                "{}: `{}` is not a valid command",
+                                cmd.trim(),
+                                invalid
+                            )),
+                        ));
+                    } else {
+                        for command in commands {
+                            self.rcv_event(UIEvent::Command(command));
+                        }
+                    }
                 } else {
                     self.context.replies.push_back(UIEvent::StatusEvent(
                         StatusEvent::DisplayMessage("invalid command".to_string()),
@@ -1112,6 +1579,109 @@ impl State {
                 self.redraw();
                 return;
             }
+            UIEvent::Timer(id) if id == self.dnd_timer.id() => {
+                self.context.update_dnd();
+                return;
+            }
+            UIEvent::Timer(id) if id == self.scheduled_send_timer.id() => {
+                crate::components::mail::scheduled::send_due(&mut self.context);
+                return;
+            }
+            UIEvent::Timer(id) if self.power_timer.as_ref().map(Timer::id) == Some(id) => {
+                let sender = self.context.sender.clone();
+                self.context.job_executor.spawn_blocking(async move {
+                    let power_state = PowerState::detect();
+                    sender
+                        .send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+                            Box::new(move |context: &mut Context| {
+                                context.power_state = power_state;
+                                context.update_power_saving();
+                            }),
+                        ))))
+                        .unwrap();
+                });
+                return;
+            }
+            UIEvent::Timer(id)
+                if self
+                    .context
+                    .accounts
+                    .values()
+                    .any(|a| a.refresh_timer_id() == Some(id)) =>
+            {
+                for account in self.context.accounts.values_mut() {
+                    if account.refresh_timer_id() != Some(id) {
+                        continue;
+                    }
+                    if let Err(err) = account.refresh_timer_fired() {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some(format!("{}: could not refresh", account.name)),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return;
+            }
+            UIEvent::Timer(id)
+                if self
+                    .context
+                    .accounts
+                    .values()
+                    .any(|a| a.snooze_timer_id() == id) =>
+            {
+                for account in self.context.accounts.values_mut() {
+                    if account.snooze_timer_id() != id {
+                        continue;
+                    }
+                    if let Err(err) = account.snooze_timer_fired() {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some(format!(
+                                "{}: could not resurface snoozed threads",
+                                account.name
+                            )),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return;
+            }
+            #[cfg(feature = "carddav")]
+            UIEvent::Timer(id)
+                if self
+                    .context
+                    .accounts
+                    .values()
+                    .any(|a| a.contacts_sync_timer_id() == Some(id)) =>
+            {
+                for account in self.context.accounts.values_mut() {
+                    if account.contacts_sync_timer_id() != Some(id) {
+                        continue;
+                    }
+                    if let Err(err) = account.contacts_sync_timer_fired() {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some(format!("{}: could not sync contacts", account.name)),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return;
+            }
+            UIEvent::Input(ref key)
+                if self.context.settings.commands.alias_for_key(key).is_some() =>
+            {
+                let alias = self
+                    .context
+                    .settings
+                    .commands
+                    .alias_for_key(key)
+                    .unwrap_or_default()
+                    .to_string();
+                self.rcv_event(UIEvent::Command(alias));
+                return;
+            }
             UIEvent::Input(ref key)
                 if *key
                     == self
@@ -1250,7 +1820,26 @@ impl State {
         self.screen.flush();
     }
 
+    /// The contents of the screen, as last drawn by `redraw`, with the
+    /// overlay if there is one.
+    #[cfg(test)]
+    pub fn grid(&self) -> &CellBuffer {
+        if self.overlay.is_empty() {
+            &self.screen.grid
+        } else {
+            &self.screen.overlay_grid
+        }
+    }
+
     pub fn check_accounts(&mut self) {
+        let (instant, wall) = (Instant::now(), SystemTime::now());
+        if clock_jumped(instant - self.last_check.0, self.last_check.1, wall) {
+            log::info!("Resumed from suspend, reconnecting accounts.");
+            for account in self.context.accounts.values_mut() {
+                account.resumed();
+            }
+        }
+        self.last_check = (instant, wall);
         let mut ctr = 0;
         for i in 0..self.context.accounts.len() {
             if self.context.is_online_idx(i).is_ok() {
@@ -1263,3 +1852,20 @@ impl State {
         self.context.input_thread.check();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_clock_jumped() {
+        let before = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let pulse = Duration::from_millis(300);
+        let hour = Duration::from_secs(3600);
+        assert!(!clock_jumped(pulse, before, before + pulse));
+        assert!(!clock_jumped(pulse, before, before + pulse * 30));
+        assert!(clock_jumped(pulse, before, before + hour));
+        assert!(!clock_jumped(hour, before, before + hour));
+        assert!(!clock_jumped(pulse, before, before - hour));
+    }
+}
@@ -476,6 +476,9 @@ pub mod screen {
         pub overlay_grid: CellBuffer,
         pub stdout: Option<StateStdout>,
         pub mouse: bool,
+        /// Size to draw in instead of the terminal's, when replaying a
+        /// recording.
+        pub fixed_size: Option<(usize, usize)>,
         pub draw_horizontal_segment_fn:
             fn(&mut CellBuffer, &mut StateStdout, usize, usize, usize) -> (),
     }
@@ -485,8 +488,12 @@ pub mod screen {
         /// sees before opening the application)
         pub fn switch_to_main_screen(&mut self) {
             let mouse = self.mouse;
+            let stdout = match self.stdout.as_mut() {
+                Some(stdout) => stdout,
+                None => return,
+            };
             write!(
-                self.stdout.as_mut().unwrap(),
+                stdout,
                 "{}{}{}{}{disable_sgr_mouse}{disable_mouse}",
                 termion::screen::ToMainScreen,
                 cursor::Show,
@@ -562,7 +569,10 @@ pub mod screen {
         /// On `SIGWNICH` the `State` redraws itself according to the new
         /// terminal size.
         pub fn update_size(&mut self) {
-            let termsize = termion::terminal_size().ok();
+            let termsize = self
+                .fixed_size
+                .map(|(cols, rows)| (cols as u16, rows as u16))
+                .or_else(|| termion::terminal_size().ok());
             let termcols = termsize.map(|(w, _)| w);
             let termrows = termsize.map(|(_, h)| h);
             if termcols.unwrap_or(72) as usize != self.cols
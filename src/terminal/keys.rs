@@ -72,6 +72,18 @@ pub enum Key {
 
 pub use termion::event::{MouseButton, MouseEvent};
 
+/// Returns the zero-based screen position of a mouse event; terminals report
+/// one-based coordinates.
+pub fn mouse_pos(event: &MouseEvent) -> Pos {
+    let (x, y) = match *event {
+        MouseEvent::Press(_, x, y) | MouseEvent::Release(x, y) | MouseEvent::Hold(x, y) => (x, y),
+    };
+    (
+        usize::from(x).saturating_sub(1),
+        usize::from(y).saturating_sub(1),
+    )
+}
+
 impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use crate::Key::*;
@@ -259,3 +259,19 @@ pub fn nth_row_area(area: Area, n: usize) -> Area {
     let y = std::cmp::min(max_y, get_y(upper_left) + n);
     (set_y(upper_left, y), set_y(bottom_right, y))
 }
+
+/// Check if `pos` lies inside `area`.
+///
+/// Example:
+/// ```no_run
+/// use meli::area_contains;
+///
+/// let area = ((2, 2), (4, 3));
+/// assert!(area_contains(area, (4, 2)));
+/// assert!(!area_contains(area, (1, 2)));
+/// ```
+pub fn area_contains(area: Area, pos: Pos) -> bool {
+    let (upper_left, bottom_right) = area;
+    (get_x(upper_left)..=get_x(bottom_right)).contains(&get_x(pos))
+        && (get_y(upper_left)..=get_y(bottom_right)).contains(&get_y(pos))
+}
@@ -0,0 +1,685 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Headless UI tests.
+ *
+ * A [`TestHarness`] owns a [`State`] without a terminal, whose only account
+ * is backed by a [`DummyServer`]. It feeds it synthetic key presses and
+ * commands, and runs the events that follow the same way the main event
+ * loop does, so that tests can assert on the text of the drawn screen.
+ *
+ * The harness never reads the user's configuration, and points the XDG base
+ * directories of the test process to a temporary directory, where each
+ * harness' account has a unique name.
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Once,
+    },
+    time::{Duration, Instant},
+};
+
+use melib::AccountHash;
+
+use crate::{
+    conf::{AccountConf, SearchBackend, Settings},
+    listing::Listing,
+    state::State,
+    terminal::Key,
+    types::{ThreadEvent, UIEvent, UIMode},
+    StatusBar, Tabbed,
+};
+
+pub mod backend;
+pub use self::backend::{DummyServer, DUMMY_FORMAT};
+
+/// The longest `process_events` waits for the jobs of the accounts to
+/// finish.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct TestHarness {
+    pub state: State,
+    pub server: DummyServer,
+    account_name: String,
+    enter_command_mode: Key,
+}
+
+/// Points the XDG base directories to a temporary directory, once per test
+/// process, so that accounts keep their data there.
+fn isolate_xdg_dirs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let dir = tempfile::Builder::new()
+            .prefix("meli-tests")
+            .tempdir()
+            .unwrap()
+            .into_path();
+        for (var, subdir) in [
+            ("XDG_CONFIG_HOME", "config"),
+            ("XDG_DATA_HOME", "data"),
+            ("XDG_CACHE_HOME", "cache"),
+            ("XDG_STATE_HOME", "state"),
+        ] {
+            std::env::set_var(var, dir.join(subdir));
+        }
+    });
+}
+
+/// Returns an account name no other harness of the test process uses.
+fn unique_account_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    format!("dummy-{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+impl TestHarness {
+    pub fn new(server: DummyServer) -> Self {
+        Self::with_size(server, 120, 40)
+    }
+
+    /// Creates a `cols`×`rows` screen with the main listing and processes
+    /// the startup events, so that the account is loaded on return.
+    pub fn with_size(server: DummyServer, cols: usize, rows: usize) -> Self {
+        Self::with_account_conf(server, cols, rows, |_| {})
+    }
+
+    /// Like [`TestHarness::with_size`], with the account configuration
+    /// changed by `f` first. `f` may set the account name to one of a
+    /// previous harness, to reuse its data.
+    pub fn with_account_conf(
+        server: DummyServer,
+        cols: usize,
+        rows: usize,
+        f: impl FnOnce(&mut AccountConf),
+    ) -> Self {
+        isolate_xdg_dirs();
+        let mut backends = melib::backends::Backends::new();
+        server.register(&mut backends);
+        let mut settings = Settings::default();
+        let mut account_conf = AccountConf::default();
+        account_conf.account.name = unique_account_name();
+        account_conf.account.identity = "user@example.com".to_string();
+        account_conf.account.format = DUMMY_FORMAT.to_string();
+        account_conf.conf.format = DUMMY_FORMAT.to_string();
+        account_conf.conf.search_backend = SearchBackend::None;
+        f(&mut account_conf);
+        let account_name = account_conf.account.name.clone();
+        settings.accounts.insert(account_name.clone(), account_conf);
+        let enter_command_mode = settings.shortcuts.general.enter_command_mode.clone();
+
+        let mut state = State::new_headless(settings, &backends, cols, rows).unwrap();
+        let listing = Box::new(Listing::new(&mut state.context));
+        let window = Box::new(Tabbed::new(vec![listing], &state.context));
+        let status_bar = Box::new(StatusBar::new(&state.context, window));
+        state.register_component(status_bar);
+        let mut ret = TestHarness {
+            state,
+            server,
+            account_name,
+            enter_command_mode,
+        };
+        ret.process_events();
+        ret
+    }
+
+    pub fn account_name(&self) -> &str {
+        &self.account_name
+    }
+
+    pub fn account_hash(&self) -> AccountHash {
+        AccountHash::from_bytes(self.account_name.as_bytes())
+    }
+
+    /// Whether an account has a job in progress, such as loading a mailbox,
+    /// whose completion will be sent to the event queue.
+    fn has_jobs(&self) -> bool {
+        self.state
+            .context
+            .accounts
+            .values()
+            .any(|account| account.active_jobs.values().any(|job| !job.is_watch()))
+    }
+
+    /// Handles the events of the state's queue, and the replies of the
+    /// components, until both are empty and no account has a job in
+    /// progress.
+    pub fn process_events(&mut self) {
+        let receiver = self.state.receiver();
+        let start = Instant::now();
+        while start.elapsed() < TIMEOUT {
+            for e in self.state.context.replies() {
+                self.state.rcv_event(e);
+            }
+            self.state.redraw();
+            let busy = !self.state.context.replies.is_empty() || self.has_jobs();
+            let event = if busy {
+                receiver.recv_timeout(Duration::from_millis(10)).ok()
+            } else {
+                receiver.try_recv().ok()
+            };
+            match event {
+                Some(ThreadEvent::Pulse) => {
+                    self.state.check_accounts();
+                }
+                Some(event) => self.handle_event(event),
+                None if busy => {}
+                None => break,
+            }
+        }
+        self.state.redraw();
+    }
+
+    /// What the main event loop does with events other than input.
+    fn handle_event(&mut self, event: ThreadEvent) {
+        match event {
+            ThreadEvent::Input((k, _)) => self.input(k),
+            ThreadEvent::RefreshMailbox(event) => {
+                self.state.refresh_event(*event);
+            }
+            ThreadEvent::UIEvent(UIEvent::ChangeMode(mode)) => {
+                self.state.mode = mode;
+            }
+            ThreadEvent::UIEvent(e) => {
+                self.state.rcv_event(e);
+            }
+            ThreadEvent::Pulse => {
+                self.state.check_accounts();
+            }
+            ThreadEvent::JobFinished(id) => {
+                for account in self.state.context.accounts.values_mut() {
+                    if account.process_event(&id) {
+                        break;
+                    }
+                }
+            }
+        }
+        self.state.redraw();
+    }
+
+    /// Handles a key press according to the current mode, like the main
+    /// event loop does.
+    fn input(&mut self, k: Key) {
+        match self.state.mode {
+            UIMode::Normal if k == self.enter_command_mode => {
+                self.state.mode = UIMode::Command;
+                self.state.rcv_event(UIEvent::ChangeMode(UIMode::Command));
+            }
+            UIMode::Normal => self.state.rcv_event(UIEvent::Input(k)),
+            UIMode::Insert if k == Key::Esc => {
+                self.state.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
+            }
+            UIMode::Insert => self.state.rcv_event(UIEvent::InsertInput(k)),
+            UIMode::Command if k == Key::Char('\n') => {
+                self.state.mode = UIMode::Normal;
+                self.state.rcv_event(UIEvent::ChangeMode(UIMode::Normal));
+            }
+            UIMode::Command => self.state.rcv_event(UIEvent::CmdInput(k)),
+            UIMode::Embed | UIMode::Fork => {
+                panic!("Embedded and forked processes can't be tested headless.")
+            }
+        }
+        self.state.redraw();
+    }
+
+    /// Presses `k` and processes the events that follow.
+    pub fn feed_key(&mut self, k: Key) {
+        self.input(k);
+        self.process_events();
+    }
+
+    /// Presses the keys of the characters of `keys` in order.
+    pub fn feed_keys(&mut self, keys: &str) {
+        for c in keys.chars() {
+            self.input(Key::Char(c));
+        }
+        self.process_events();
+    }
+
+    /// Types `command` in command mode and executes it.
+    pub fn command(&mut self, command: &str) {
+        let enter_command_mode = self.enter_command_mode.clone();
+        self.input(enter_command_mode);
+        self.process_events();
+        for c in command.chars() {
+            self.input(Key::Char(c));
+        }
+        self.input(Key::Char('\n'));
+        self.process_events();
+    }
+
+    /// Returns the text of row `y` of the screen, without trailing
+    /// whitespace.
+    pub fn row(&self, y: usize) -> String {
+        let grid = self.state.grid();
+        let mut ret = (0..grid.cols())
+            .map(|x| grid[(x, y)].ch())
+            .filter(|c| *c != '\0')
+            .collect::<String>();
+        ret.truncate(ret.trim_end().len());
+        ret
+    }
+
+    /// Returns the text of the screen, one line per row.
+    pub fn screen_text(&self) -> String {
+        (0..self.state.grid().rows())
+            .map(|y| self.row(y))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn contains(&self, needle: &str) -> bool {
+        self.screen_text().contains(needle)
+    }
+
+    #[track_caller]
+    pub fn assert_contains(&self, needle: &str) {
+        assert!(
+            self.contains(needle),
+            "{:?} not found on screen:\n{}",
+            needle,
+            self.screen_text()
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_not_contains(&self, needle: &str) {
+        assert!(
+            !self.contains(needle),
+            "{:?} found on screen:\n{}",
+            needle,
+            self.screen_text()
+        );
+    }
+}
+
+/// Returns a plain text message with `subject`.
+pub fn message(subject: &str, body: &str) -> Vec<u8> {
+    format!(
+        "From: Jane Doe <jane@example.com>\nTo: user@example.com\nSubject: {}\nDate: Mon, 2 Jan \
+         2023 10:00:00 +0000\nMessage-ID: <{}@example.com>\nContent-Type: text/plain; \
+         charset=utf-8\n\n{}\n",
+        subject,
+        subject.replace(' ', "-"),
+        body
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use melib::Flag;
+
+    use super::*;
+    use crate::conf::{FileMailboxConf, OpenEntryAction, OpenEntrySetting, SavedSearch};
+
+    #[test]
+    fn test_headless_listing_shows_messages() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        server.add_message("INBOX", &message("Lunch on Friday", "Pizza?"));
+        let h = TestHarness::new(server);
+        h.assert_contains("Quarterly report");
+        h.assert_contains("Lunch on Friday");
+        h.assert_contains("Jane Doe");
+    }
+
+    #[test]
+    fn test_headless_delivered_message_appears() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("First", "Hello."));
+        let mut h = TestHarness::new(server);
+        h.assert_not_contains("Second message");
+        h.server
+            .deliver("INBOX", &message("Second message", "Hello again."));
+        h.process_events();
+        h.assert_contains("First");
+        h.assert_contains("Second message");
+    }
+
+    #[test]
+    fn test_headless_command_sets_flags() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Unread message", "Body."));
+        let mut h = TestHarness::new(server);
+        assert_eq!(
+            h.server.flags("INBOX"),
+            vec![("Unread message".to_string(), Flag::default())]
+        );
+        h.command("set seen");
+        assert_eq!(
+            h.server.flags("INBOX"),
+            vec![("Unread message".to_string(), Flag::SEEN)]
+        );
+    }
+
+    #[test]
+    fn test_headless_virtual_mailbox() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        let mut h = TestHarness::with_account_conf(server, 120, 40, |conf| {
+            let mut unread = FileMailboxConf::default();
+            unread
+                .mailbox_conf
+                .extra
+                .insert("query".to_string(), "flags:unread".to_string());
+            conf.mailbox_confs.insert("Unread".to_string(), unread);
+        });
+        let account_hash = h.account_hash();
+        let mailbox_hash = h.state.context.accounts[&account_hash]
+            .mailbox_by_path("Unread")
+            .unwrap();
+        assert!(h.state.context.accounts[&account_hash]
+            .virtual_mailboxes
+            .contains_key(&mailbox_hash));
+        let count = |h: &TestHarness| {
+            h.state.context.accounts[&account_hash].mailbox_entries[&mailbox_hash]
+                .ref_mailbox
+                .count()
+                .unwrap()
+        };
+
+        h.command("go 1");
+        h.assert_contains("Quarterly report");
+        assert_eq!(count(&h), (1, 1));
+
+        h.server
+            .deliver("INBOX", &message("Lunch on Friday", "Pizza?"));
+        h.process_events();
+        h.assert_contains("Lunch on Friday");
+        assert_eq!(count(&h), (2, 2));
+
+        /* Flags are set in the message's actual mailbox, and the message
+         * leaves the virtual one. */
+        h.command("set seen");
+        assert_eq!(
+            h.server
+                .flags("INBOX")
+                .into_iter()
+                .filter(|(_, flags)| flags.is_seen())
+                .count(),
+            1
+        );
+        assert_eq!(count(&h), (1, 1));
+    }
+
+    #[test]
+    fn test_headless_command_alias() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        let mut h = TestHarness::new(server);
+        let commands = &mut h.state.context.settings.commands;
+        commands
+            .aliases
+            .insert("mark".to_string(), "set $1".to_string());
+        commands
+            .aliases
+            .insert("read-and-pin".to_string(), "set seen; pin".to_string());
+        commands
+            .shortcuts
+            .insert("read-and-pin".to_string(), Key::Char('R'));
+        let seen = |h: &TestHarness| {
+            h.server
+                .flags("INBOX")
+                .into_iter()
+                .filter(|(_, flags)| flags.is_seen())
+                .count()
+        };
+
+        h.command("mark seen");
+        assert_eq!(seen(&h), 1);
+        h.command("mark unseen");
+        assert_eq!(seen(&h), 0);
+        /* Nothing runs if one of the commands is invalid. */
+        h.state
+            .context
+            .settings
+            .commands
+            .aliases
+            .insert("broken".to_string(), "set seen; set nonsense".to_string());
+        h.command("broken");
+        assert_eq!(seen(&h), 0);
+        h.feed_key(Key::Char('R'));
+        assert_eq!(seen(&h), 1);
+    }
+
+    #[test]
+    fn test_headless_sidebar_order_and_collapse() {
+        let paths = ["INBOX", "Archive", "Drafts", "Sent"];
+        let conf = |conf: &mut AccountConf| {
+            conf.conf.mailbox_order = vec!["Sent".to_string(), "Drafts".to_string()];
+        };
+        let mut h = TestHarness::with_account_conf(DummyServer::new(&paths), 120, 40, conf);
+        let account_hash = h.account_hash();
+        let account = &h.state.context.accounts[&account_hash];
+        assert_eq!(
+            account
+                .mailboxes_order
+                .iter()
+                .map(|h| account.mailbox_entries[h].ref_mailbox.path())
+                .collect::<Vec<&str>>(),
+            vec!["Sent", "Drafts", "INBOX", "Archive"]
+        );
+
+        /* The collapse state outlives the account. */
+        let archive = h.server.mailbox_hash("Archive");
+        let account = &mut h.state.context.accounts[&account_hash];
+        assert!(!account.is_mailbox_collapsed(archive));
+        account.set_mailbox_collapsed(archive, true).unwrap();
+        assert!(account.is_mailbox_collapsed(archive));
+        let account_name = h.account_name().to_string();
+        let mut h = TestHarness::with_account_conf(DummyServer::new(&paths), 120, 40, |c| {
+            conf(c);
+            c.account.name = account_name;
+        });
+        let account = &mut h.state.context.accounts[&account_hash];
+        assert!(account.is_mailbox_collapsed(archive));
+    }
+
+    #[test]
+    fn test_headless_saved_search() {
+        let server = DummyServer::new(&["INBOX", "Archive"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        server.add_message("Archive", &message("Annual report", "More numbers."));
+        server.add_message("INBOX", &message("Lunch on Friday", "Pizza?"));
+        let mut h = TestHarness::with_account_conf(server, 120, 40, |conf| {
+            conf.conf.searches.insert(
+                "Reports".to_string(),
+                SavedSearch::WithShortcut {
+                    query: "subject:report".to_string(),
+                    shortcut: Some(Key::Char('R')),
+                },
+            );
+            conf.conf.searches.insert(
+                "A lunch".to_string(),
+                SavedSearch::Query("subject:lunch".to_string()),
+            );
+        });
+        let account_hash = h.account_hash();
+        let account = &h.state.context.accounts[&account_hash];
+        let reports = account.mailbox_by_path("Reports").unwrap();
+        /* Saved searches come after the mailboxes, in their order. */
+        assert_eq!(
+            account
+                .mailboxes_order
+                .iter()
+                .map(|h| account.mailbox_entries[h].ref_mailbox.path())
+                .collect::<Vec<&str>>(),
+            vec!["INBOX", "Archive", "Reports", "A lunch"]
+        );
+        assert!(account.virtual_mailboxes.contains_key(&reports));
+
+        h.assert_contains("Lunch on Friday");
+        h.feed_key(Key::Char('R'));
+        h.assert_contains("Quarterly report");
+        h.assert_contains("Annual report");
+        h.assert_not_contains("Lunch on Friday");
+
+        h.server
+            .deliver("INBOX", &message("Monthly report", "Even more numbers."));
+        h.process_events();
+        h.assert_contains("Monthly report");
+    }
+
+    #[test]
+    fn test_headless_flag_editor() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        server.add_message("INBOX", &message("Lunch on Friday", "Pizza?"));
+        let mut h = TestHarness::new(server);
+        h.command("set seen");
+        h.feed_keys("vjvE");
+        h.assert_contains("Edit flags of 2 messages");
+        h.assert_contains(" [-] seen (1 of 2)");
+        h.assert_contains(" [ ] flagged");
+        /* Unset seen, set flagged. */
+        h.feed_keys("  ");
+        h.feed_key(Key::Down);
+        h.feed_key(Key::Char(' '));
+        h.assert_contains("-[ ] seen");
+        h.assert_contains("+[x] flagged");
+        h.feed_key(Key::Char('\n'));
+        h.assert_not_contains("Edit flags of");
+        assert_eq!(
+            h.server
+                .flags("INBOX")
+                .into_iter()
+                .map(|(_, flags)| flags)
+                .collect::<Vec<Flag>>(),
+            vec![Flag::FLAGGED, Flag::FLAGGED]
+        );
+    }
+
+    #[test]
+    fn test_headless_open_entry_mark_read_and_advance() {
+        let server = DummyServer::new(&["INBOX"]);
+        server.add_message("INBOX", &message("Quarterly report", "Numbers."));
+        server.add_message("INBOX", &message("Lunch on Friday", "Pizza?"));
+        let mut h = TestHarness::new(server);
+        h.state.context.settings.listing.open_entry_action =
+            OpenEntrySetting::All(OpenEntryAction::MarkReadAndAdvance);
+        let seen = |h: &TestHarness| {
+            h.server
+                .flags("INBOX")
+                .into_iter()
+                .filter(|(_, flags)| flags.is_seen())
+                .count()
+        };
+
+        h.feed_key(Key::Char('\n'));
+        assert_eq!(seen(&h), 1);
+        h.assert_not_contains("Numbers.");
+        h.feed_key(Key::Char('\n'));
+        assert_eq!(seen(&h), 2);
+    }
+
+    #[test]
+    fn test_headless_help_filter() {
+        let server = DummyServer::new(&["INBOX"]);
+        let mut h = TestHarness::new(server);
+        h.feed_key(Key::Char('?'));
+        h.assert_contains("Search within list of e-mails.");
+        h.assert_contains(":search QUERY");
+        h.feed_keys("/quit");
+        h.assert_contains("filter: quit");
+        h.assert_contains("Quit meli.");
+        h.assert_contains(":quit");
+        h.assert_not_contains("Search within list of e-mails.");
+        h.feed_key(Key::Esc);
+        h.assert_contains("Search within list of e-mails.");
+        h.feed_key(Key::Char('?'));
+        h.assert_not_contains("Search within list of e-mails.");
+    }
+
+    #[test]
+    fn test_headless_open_forwarded_draft() {
+        use crate::remote::ComposeRequest;
+
+        let dir = tempfile::tempdir().unwrap();
+        let attachment = dir.path().join("notes.txt");
+        std::fs::write(&attachment, "notes").unwrap();
+        let mut h = TestHarness::new(DummyServer::new(&["INBOX"]));
+        let request = ComposeRequest {
+            account: None,
+            headers: vec![
+                ("To".to_string(), "a@example.com".to_string()),
+                ("Subject".to_string(), "Forwarded draft".to_string()),
+            ],
+            body: Some("Hello.".to_string()),
+            attachments: vec![attachment],
+        };
+
+        let mut composer = request.clone().composer(&h.state.context).unwrap();
+        let draft = composer.draft_mut();
+        assert_eq!(&draft.headers()["Subject"], "Forwarded draft");
+        assert_eq!(&draft.headers()["To"], "a@example.com");
+        assert_eq!(draft.body(), "Hello.");
+        assert_eq!(draft.attachments().len(), 1);
+        assert!(ComposeRequest {
+            account: Some("missing".to_string()),
+            ..request.clone()
+        }
+        .composer(&h.state.context)
+        .is_err());
+
+        request.open(&mut h.state.context);
+        h.process_events();
+        h.assert_contains("Forwarded draft");
+    }
+
+    #[test]
+    fn test_headless_periodic_refresh() {
+        let h = TestHarness::new(DummyServer::new(&["INBOX"]));
+        let account = &h.state.context.accounts[&h.account_hash()];
+        assert!(account.refresh_timer_id().is_none());
+        assert!(account.next_refresh.is_none());
+        h.assert_not_contains("next in");
+
+        /* Wide enough for the sidebar to fit the sync status. */
+        let mut h = TestHarness::with_account_conf(DummyServer::new(&["INBOX"]), 400, 40, |conf| {
+            conf.conf.refresh_interval = Some(300);
+        });
+        let account_hash = h.account_hash();
+        let account = &h.state.context.accounts[&account_hash];
+        assert!(account.refresh_timer_id().is_some());
+        assert!(account.last_synced.is_none());
+        let next_refresh = account.next_refresh.unwrap();
+        assert!(next_refresh > melib::datetime::now() + 290);
+        assert!(h.contains("next in 4m") || h.contains("next in 5m 00s"));
+
+        h.feed_key(Key::Char('S'));
+        h.process_events();
+        assert!(h.state.context.accounts[&account_hash]
+            .last_synced
+            .is_some());
+        h.assert_contains("synced ");
+
+        /* Each refresh is counted down from when the timer fired. */
+        let account = &mut h.state.context.accounts[&account_hash];
+        account.next_refresh = Some(0);
+        account.last_synced = None;
+        account.refresh_timer_fired().unwrap();
+        h.process_events();
+        let account = &h.state.context.accounts[&account_hash];
+        assert!(account.next_refresh.unwrap() >= next_refresh);
+        assert!(account.last_synced.is_some());
+    }
+}
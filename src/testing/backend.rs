@@ -0,0 +1,530 @@
+/*
+ * meli
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! An in-memory mail backend for tests. Its contents live in a
+//! [`DummyServer`] that the test keeps a handle of, to deliver messages and
+//! emit backend events as if they came from a real server.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use futures::stream::Stream;
+use melib::{
+    backends::*,
+    conf::AccountSettings,
+    email::{Envelope, EnvelopeHash, Flag},
+    error::{Error, ErrorKind, Result},
+    Collection,
+};
+use smallvec::SmallVec;
+
+/// The `format` of accounts that use the dummy backend.
+pub const DUMMY_FORMAT: &str = "dummy";
+
+#[derive(Debug, Clone)]
+pub struct DummyMailbox {
+    hash: MailboxHash,
+    name: String,
+    path: String,
+    usage: SpecialUsageMailbox,
+    is_subscribed: bool,
+    /// `(unseen, total)`, shared with the clones given to the account.
+    count: Arc<Mutex<(usize, usize)>>,
+}
+
+impl BackendMailbox for DummyMailbox {
+    fn hash(&self) -> MailboxHash {
+        self.hash
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn clone(&self) -> Mailbox {
+        Box::new(std::clone::Clone::clone(self))
+    }
+
+    fn children(&self) -> &[MailboxHash] {
+        &[]
+    }
+
+    fn parent(&self) -> Option<MailboxHash> {
+        None
+    }
+
+    fn is_subscribed(&self) -> bool {
+        self.is_subscribed
+    }
+
+    fn set_is_subscribed(&mut self, new_val: bool) -> Result<()> {
+        self.is_subscribed = new_val;
+        Ok(())
+    }
+
+    fn set_special_usage(&mut self, new_val: SpecialUsageMailbox) -> Result<()> {
+        self.usage = new_val;
+        Ok(())
+    }
+
+    fn special_usage(&self) -> SpecialUsageMailbox {
+        self.usage
+    }
+
+    fn permissions(&self) -> MailboxPermissions {
+        MailboxPermissions::default()
+    }
+
+    fn count(&self) -> Result<(usize, usize)> {
+        Ok(*self.count.lock()?)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DummyMessage {
+    mailbox_hash: MailboxHash,
+    bytes: Vec<u8>,
+    flags: Flag,
+}
+
+#[derive(Debug, Default)]
+struct DummyStore {
+    account_hash: AccountHash,
+    mailboxes: HashMap<MailboxHash, DummyMailbox>,
+    messages: HashMap<EnvelopeHash, DummyMessage>,
+    /// Messages stored so far, to give each one a unique hash.
+    counter: u64,
+    /// The consumer of the backend created last, if any.
+    event_consumer: Option<BackendEventConsumer>,
+}
+
+impl DummyStore {
+    fn update_counts(&self) {
+        for mailbox in self.mailboxes.values() {
+            let (mut unseen, mut total) = (0, 0);
+            for m in self
+                .messages
+                .values()
+                .filter(|m| m.mailbox_hash == mailbox.hash)
+            {
+                total += 1;
+                if !m.flags.is_seen() {
+                    unseen += 1;
+                }
+            }
+            *mailbox.count.lock().unwrap() = (unseen, total);
+        }
+    }
+
+    fn envelope(hash: EnvelopeHash, message: &DummyMessage) -> Result<Envelope> {
+        let mut env = Envelope::from_bytes(&message.bytes, Some(message.flags))?;
+        env.set_hash(hash);
+        Ok(env)
+    }
+
+    fn insert(&mut self, mailbox_hash: MailboxHash, bytes: Vec<u8>, flags: Flag) -> Result<()> {
+        if !self.mailboxes.contains_key(&mailbox_hash) {
+            return Err(Error::new(format!("Mailbox {} not found.", mailbox_hash)));
+        }
+        let message = DummyMessage {
+            mailbox_hash,
+            bytes,
+            flags,
+        };
+        self.counter += 1;
+        let hash = EnvelopeHash::from_bytes(&self.counter.to_be_bytes());
+        let env = Self::envelope(hash, &message)?;
+        self.messages.insert(hash, message);
+        self.update_counts();
+        self.emit(mailbox_hash, RefreshEventKind::Create(Box::new(env)));
+        Ok(())
+    }
+
+    fn emit(&self, mailbox_hash: MailboxHash, kind: RefreshEventKind) {
+        if let Some(ref consumer) = self.event_consumer {
+            (consumer)(
+                self.account_hash,
+                BackendEvent::Refresh(RefreshEvent {
+                    mailbox_hash,
+                    account_hash: self.account_hash,
+                    kind,
+                }),
+            );
+        }
+    }
+}
+
+/// The "server" side of the dummy backend: a handle to its mailboxes and
+/// messages, shared with the backends created by [`DummyServer::register`].
+#[derive(Debug, Clone, Default)]
+pub struct DummyServer(Arc<Mutex<DummyStore>>);
+
+impl DummyServer {
+    /// Creates a server with one mailbox for each of `paths`. The first one
+    /// is the inbox.
+    pub fn new(paths: &[&str]) -> Self {
+        let ret = DummyServer::default();
+        {
+            let mut store = ret.0.lock().unwrap();
+            for (i, path) in paths.iter().enumerate() {
+                let hash = MailboxHash::from_bytes(path.as_bytes());
+                store.mailboxes.insert(
+                    hash,
+                    DummyMailbox {
+                        hash,
+                        name: path.rsplit('/').next().unwrap_or(path).to_string(),
+                        path: path.to_string(),
+                        usage: if i == 0 {
+                            SpecialUsageMailbox::Inbox
+                        } else {
+                            SpecialUsageMailbox::detect_usage(path).unwrap_or_default()
+                        },
+                        is_subscribed: true,
+                        count: Arc::new(Mutex::new((0, 0))),
+                    },
+                );
+            }
+        }
+        ret
+    }
+
+    /// Registers the dummy backend as the [`DUMMY_FORMAT`] format. Accounts
+    /// of that format all share this server.
+    pub fn register(&self, backends: &mut Backends) {
+        let server = self.clone();
+        backends.register(
+            DUMMY_FORMAT.to_string(),
+            Backend {
+                create_fn: Box::new(move || {
+                    let server = server.clone();
+                    Box::new(move |settings, _is_subscribed, event_consumer| {
+                        DummyBackend::new(server.clone(), settings, event_consumer)
+                    })
+                }),
+                validate_conf_fn: Box::new(|_| Ok(())),
+            },
+        );
+    }
+
+    pub fn mailbox_hash(&self, path: &str) -> MailboxHash {
+        MailboxHash::from_bytes(path.as_bytes())
+    }
+
+    /// Stores a message in the mailbox `path` without telling the client,
+    /// as if it was there before it connected.
+    pub fn add_message(&self, path: &str, bytes: &[u8]) {
+        let mailbox_hash = self.mailbox_hash(path);
+        let mut store = self.0.lock().unwrap();
+        let consumer = store.event_consumer.take();
+        let ret = store.insert(mailbox_hash, bytes.to_vec(), Flag::default());
+        store.event_consumer = consumer;
+        ret.unwrap();
+    }
+
+    /// Stores a new message in the mailbox `path` and notifies the client.
+    pub fn deliver(&self, path: &str, bytes: &[u8]) {
+        let mailbox_hash = self.mailbox_hash(path);
+        self.0
+            .lock()
+            .unwrap()
+            .insert(mailbox_hash, bytes.to_vec(), Flag::default())
+            .unwrap();
+    }
+
+    /// Sends an arbitrary refresh event about the mailbox `path` to the
+    /// client, e.g. a `Failure`.
+    pub fn emit(&self, path: &str, kind: RefreshEventKind) {
+        let mailbox_hash = self.mailbox_hash(path);
+        self.0.lock().unwrap().emit(mailbox_hash, kind);
+    }
+
+    /// Returns the flags of the messages of the mailbox `path`, by subject.
+    pub fn flags(&self, path: &str) -> Vec<(String, Flag)> {
+        let mailbox_hash = self.mailbox_hash(path);
+        let store = self.0.lock().unwrap();
+        let mut ret = store
+            .messages
+            .iter()
+            .filter(|(_, m)| m.mailbox_hash == mailbox_hash)
+            .filter_map(|(h, m)| DummyStore::envelope(*h, m).ok())
+            .map(|env| (env.subject().to_string(), env.flags()))
+            .collect::<Vec<_>>();
+        ret.sort();
+        ret
+    }
+}
+
+#[derive(Debug)]
+pub struct DummyBackend {
+    server: DummyServer,
+    collection: Collection,
+}
+
+impl DummyBackend {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        server: DummyServer,
+        settings: &AccountSettings,
+        event_consumer: BackendEventConsumer,
+    ) -> Result<Box<dyn MailBackend>> {
+        {
+            let mut store = server.0.lock().unwrap();
+            store.account_hash = AccountHash::from_bytes(settings.name.as_bytes());
+            store.event_consumer = Some(event_consumer);
+        }
+        Ok(Box::new(DummyBackend {
+            server,
+            collection: Collection::default(),
+        }))
+    }
+
+    fn not_supported<T>(what: &str) -> ResultFuture<T> {
+        Err(
+            Error::new(format!("{} is not supported in the dummy backend.", what))
+                .set_kind(ErrorKind::NotSupported),
+        )
+    }
+}
+
+impl MailBackend for DummyBackend {
+    fn capabilities(&self) -> MailBackendCapabilities {
+        const CAPABILITIES: MailBackendCapabilities = MailBackendCapabilities {
+            is_async: false,
+            is_remote: false,
+            supports_search: false,
+            extensions: None,
+            supports_tags: false,
+            supports_submission: false,
+        };
+        CAPABILITIES
+    }
+
+    fn fetch(
+        &mut self,
+        mailbox_hash: MailboxHash,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<Vec<Envelope>>> + Send + 'static>>> {
+        let store = self.server.0.lock().unwrap();
+        let envelopes = store
+            .messages
+            .iter()
+            .filter(|(_, m)| m.mailbox_hash == mailbox_hash)
+            .map(|(h, m)| DummyStore::envelope(*h, m))
+            .collect::<Result<Vec<Envelope>>>();
+        Ok(Box::pin(futures::stream::iter(vec![envelopes])))
+    }
+
+    fn refresh(&mut self, _mailbox_hash: MailboxHash) -> ResultFuture<()> {
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn watch(&self) -> ResultFuture<()> {
+        Self::not_supported("Watching")
+    }
+
+    fn mailboxes(&self) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        let ret = self
+            .server
+            .0
+            .lock()
+            .unwrap()
+            .mailboxes
+            .iter()
+            .map(|(h, m)| (*h, BackendMailbox::clone(m)))
+            .collect();
+        Ok(Box::pin(async { Ok(ret) }))
+    }
+
+    fn operation(&self, hash: EnvelopeHash) -> Result<Box<dyn BackendOp>> {
+        let store = self.server.0.lock().unwrap();
+        let message = store
+            .messages
+            .get(&hash)
+            .ok_or_else(|| Error::new(format!("Message {} not found.", hash)))?;
+        Ok(Box::new(DummyOp {
+            bytes: message.bytes.clone(),
+            flags: message.flags,
+        }))
+    }
+
+    fn save(
+        &self,
+        bytes: Vec<u8>,
+        mailbox_hash: MailboxHash,
+        flags: Option<Flag>,
+    ) -> ResultFuture<()> {
+        self.server
+            .0
+            .lock()
+            .unwrap()
+            .insert(mailbox_hash, bytes, flags.unwrap_or_default())?;
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn copy_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        source_mailbox_hash: MailboxHash,
+        destination_mailbox_hash: MailboxHash,
+        move_: bool,
+    ) -> ResultFuture<()> {
+        let mut store = self.server.0.lock().unwrap();
+        for hash in env_hashes.iter() {
+            let message = match store.messages.get(&hash) {
+                Some(m) if m.mailbox_hash == source_mailbox_hash => m.clone(),
+                _ => continue,
+            };
+            store.insert(destination_mailbox_hash, message.bytes, message.flags)?;
+            if move_ {
+                store.messages.remove(&hash);
+                store.update_counts();
+                store.emit(source_mailbox_hash, RefreshEventKind::Remove(hash));
+            }
+        }
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn set_flags(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: SmallVec<[(std::result::Result<Flag, String>, bool); 8]>,
+    ) -> ResultFuture<()> {
+        let mut store = self.server.0.lock().unwrap();
+        for hash in env_hashes.iter() {
+            let new_flags = match store.messages.get_mut(&hash) {
+                Some(m) => {
+                    for (f, value) in flags.iter() {
+                        if let Ok(f) = f {
+                            m.flags.set(*f, *value);
+                        }
+                    }
+                    m.flags
+                }
+                None => continue,
+            };
+            store.emit(
+                mailbox_hash,
+                RefreshEventKind::NewFlags(hash, (new_flags, vec![])),
+            );
+        }
+        store.update_counts();
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn delete_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+    ) -> ResultFuture<()> {
+        let mut store = self.server.0.lock().unwrap();
+        for hash in env_hashes.iter() {
+            if store.messages.remove(&hash).is_some() {
+                store.emit(mailbox_hash, RefreshEventKind::Remove(hash));
+            }
+        }
+        store.update_counts();
+        Ok(Box::pin(async { Ok(()) }))
+    }
+
+    fn collection(&self) -> Collection {
+        self.collection.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn create_mailbox(
+        &mut self,
+        _path: String,
+    ) -> ResultFuture<(MailboxHash, HashMap<MailboxHash, Mailbox>)> {
+        Self::not_supported("Creating mailboxes")
+    }
+
+    fn delete_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+    ) -> ResultFuture<HashMap<MailboxHash, Mailbox>> {
+        Self::not_supported("Deleting mailboxes")
+    }
+
+    fn set_mailbox_subscription(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _val: bool,
+    ) -> ResultFuture<()> {
+        Self::not_supported("Mailbox subscriptions")
+    }
+
+    fn rename_mailbox(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _new_path: String,
+    ) -> ResultFuture<Mailbox> {
+        Self::not_supported("Renaming mailboxes")
+    }
+
+    fn set_mailbox_permissions(
+        &mut self,
+        _mailbox_hash: MailboxHash,
+        _val: MailboxPermissions,
+    ) -> ResultFuture<()> {
+        Self::not_supported("Mailbox permissions")
+    }
+
+    fn search(
+        &self,
+        _query: melib::search::Query,
+        _mailbox_hash: Option<MailboxHash>,
+    ) -> ResultFuture<SmallVec<[EnvelopeHash; 512]>> {
+        Self::not_supported("Searching")
+    }
+}
+
+#[derive(Debug)]
+struct DummyOp {
+    bytes: Vec<u8>,
+    flags: Flag,
+}
+
+impl BackendOp for DummyOp {
+    fn as_bytes(&mut self) -> ResultFuture<Vec<u8>> {
+        let bytes = self.bytes.clone();
+        Ok(Box::pin(async move { Ok(bytes) }))
+    }
+
+    fn fetch_flags(&self) -> ResultFuture<Flag> {
+        let flags = self.flags;
+        Ok(Box::pin(async move { Ok(flags) }))
+    }
+}
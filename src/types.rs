@@ -40,7 +40,7 @@ use std::{borrow::Cow, fmt, sync::Arc};
 use melib::{
     backends::{AccountHash, BackendEvent, MailboxHash},
     uuid::Uuid,
-    EnvelopeHash, RefreshEvent, ThreadHash,
+    EnvelopeHash, RefreshEvent, ThreadHash, ThreadUpdate,
 };
 use nix::unistd::Pid;
 
@@ -63,6 +63,8 @@ pub enum StatusEvent {
     JobFinished(JobId),
     JobCanceled(JobId),
     SetMouse(bool),
+    /// Whether do not disturb is active for any account.
+    SetDnd(bool),
     ScrollUpdate(ScrollUpdate),
 }
 
@@ -126,6 +128,8 @@ pub enum UIEvent {
     CmdInput(Key),
     InsertInput(Key),
     EmbedInput((Key, Vec<u8>)),
+    /// Mouse event, in one-based terminal coordinates. See [`mouse_pos`].
+    Mouse(MouseEvent),
     //Quit?
     Resize,
     /// Force redraw.
@@ -137,6 +141,9 @@ pub enum UIEvent {
     Action(Action),
     StatusEvent(StatusEvent),
     MailboxUpdate((AccountHash, MailboxHash)), // (account_idx, mailbox_idx)
+    /// A new envelope was inserted in a mailbox and only the threads in
+    /// [`ThreadUpdate`] changed.
+    ThreadUpdate((AccountHash, MailboxHash), ThreadUpdate),
     MailboxDelete((AccountHash, MailboxHash)),
     MailboxCreate((AccountHash, MailboxHash)),
     AccountStatusChange(AccountHash, Option<Cow<'static, str>>),
@@ -189,11 +196,11 @@ impl fmt::Display for UIMode {
             f,
             "{}",
             match *self {
-                UIMode::Normal => "NORMAL",
-                UIMode::Insert => "INSERT",
-                UIMode::Command => "COMMAND",
-                UIMode::Fork => "FORK",
-                UIMode::Embed => "EMBED",
+                UIMode::Normal => tr!("NORMAL"),
+                UIMode::Insert => tr!("INSERT"),
+                UIMode::Command => tr!("COMMAND"),
+                UIMode::Fork => tr!("FORK"),
+                UIMode::Embed => tr!("EMBED"),
             }
         )
     }
@@ -0,0 +1,405 @@
+/*
+ * meli - undo.rs
+ *
+ * Copyright 2023 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Undo of destructive mail actions.
+ *
+ * Deleting, moving and changing the flags of messages push an [`UndoEntry`]
+ * with the inverse operation to the [`UndoStack`] of the [`Context`]. The
+ * latest entry can be reverted with the `undo` listing shortcut until the
+ * `undo_grace_period` listing setting runs out.
+ */
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use melib::{
+    backends::{EnvelopeHashBatch, ResultFuture},
+    error::{Error, Result},
+    Collection, EnvelopeHash, Flag, LogLevel, MailboxHash, MessageID, TagHash,
+};
+use smallvec::SmallVec;
+
+use crate::{
+    conf::accounts::{Account, JobRequest},
+    types::{CallbackFn, StatusEvent, ThreadEvent, UIEvent},
+    Context,
+};
+
+pub type FlagOp = (std::result::Result<Flag, String>, bool);
+
+/// The inverse operation of an action.
+#[derive(Debug)]
+pub enum UndoKind {
+    /// Set each group of flags on the messages whose flags were changed
+    /// the same way.
+    SetFlags(Vec<(EnvelopeHashBatch, SmallVec<[FlagOp; 8]>)>),
+    /// Save the raw bytes of deleted messages back with their flags.
+    Delete(Vec<(Vec<u8>, Flag)>),
+    /// Move the messages with these Message-IDs back from `destination`.
+    Move {
+        destination: MailboxHash,
+        message_ids: Vec<MessageID>,
+    },
+}
+
+impl UndoKind {
+    /// The inverse of setting `ops` on `env_hashes`, for the messages whose
+    /// flags or tags would change.
+    ///
+    /// Messages that need the same inverse operations are grouped together,
+    /// so that reverting takes one backend job per group.
+    pub fn set_flags(
+        collection: &Collection,
+        env_hashes: &EnvelopeHashBatch,
+        ops: &[FlagOp],
+    ) -> Option<Self> {
+        let mut groups: Vec<(Vec<EnvelopeHash>, SmallVec<[FlagOp; 8]>)> = vec![];
+        for env_hash in env_hashes
+            .iter()
+            .filter(|env_hash| collection.contains_key(env_hash))
+        {
+            let env = collection.get_env(env_hash);
+            let inverse = ops
+                .iter()
+                .filter(|(flag, value)| {
+                    let current = match flag {
+                        Ok(flag) => env.flags().contains(*flag),
+                        Err(tag) => env.tags().contains(&TagHash::from_bytes(tag.as_bytes())),
+                    };
+                    current != *value
+                })
+                .map(|(flag, value)| (flag.clone(), !value))
+                .collect::<SmallVec<[FlagOp; 8]>>();
+            if inverse.is_empty() {
+                continue;
+            }
+            match groups.iter_mut().find(|(_, flags)| *flags == inverse) {
+                Some((group, _)) => group.push(env_hash),
+                None => groups.push((vec![env_hash], inverse)),
+            }
+        }
+        if groups.is_empty() {
+            return None;
+        }
+        Some(UndoKind::SetFlags(
+            groups
+                .into_iter()
+                .filter_map(|(group, flags)| {
+                    Some((EnvelopeHashBatch::try_from(group.as_slice()).ok()?, flags))
+                })
+                .collect(),
+        ))
+    }
+
+    /// The inverse of moving `env_hashes` to `destination`.
+    pub fn move_to(
+        collection: &Collection,
+        env_hashes: &EnvelopeHashBatch,
+        destination: MailboxHash,
+    ) -> Self {
+        UndoKind::Move {
+            destination,
+            message_ids: env_hashes
+                .iter()
+                .filter(|env_hash| collection.contains_key(env_hash))
+                .map(|env_hash| collection.get_env(env_hash).message_id().clone())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UndoEntry {
+    pub account_hash: melib::AccountHash,
+    /// The mailbox the action was performed in.
+    pub mailbox_hash: MailboxHash,
+    /// What was done, e.g. "3 messages deleted".
+    pub description: String,
+    pub expires: Instant,
+    pub kind: UndoKind,
+}
+
+impl UndoEntry {
+    pub fn new(
+        account_hash: melib::AccountHash,
+        mailbox_hash: MailboxHash,
+        description: String,
+        grace_period: u64,
+        kind: UndoKind,
+    ) -> Self {
+        UndoEntry {
+            account_hash,
+            mailbox_hash,
+            description,
+            expires: Instant::now() + Duration::from_secs(grace_period),
+            kind,
+        }
+    }
+
+    /// Adds the entry to the undo stack and tells the user how to undo it.
+    pub fn push(self, context: &mut Context) {
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                "{} — press {} to undo",
+                self.description, context.settings.shortcuts.listing.undo
+            ))));
+        context.undo.push(self);
+    }
+
+    /// Starts the inverse operation of the action.
+    pub fn revert(self, context: &mut Context) -> Result<()> {
+        let account = &mut context.accounts[&self.account_hash];
+        match self.kind {
+            UndoKind::SetFlags(groups) => {
+                for (env_hashes, flags) in groups {
+                    let job = account.backend.write().unwrap().set_flags(
+                        env_hashes.clone(),
+                        self.mailbox_hash,
+                        flags,
+                    )?;
+                    let handle = account.job_executor.spawn_specialized(job);
+                    account.insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                }
+            }
+            UndoKind::Delete(messages) => {
+                for (bytes, flags) in messages {
+                    account.save(&bytes, self.mailbox_hash, Some(flags))?;
+                }
+            }
+            UndoKind::Move {
+                destination,
+                message_ids,
+            } => {
+                let env_hashes = account
+                    .collection
+                    .get_mailbox(destination)
+                    .iter()
+                    .copied()
+                    .filter(|&env_hash| {
+                        message_ids.contains(account.collection.get_env(env_hash).message_id())
+                    })
+                    .collect::<Vec<EnvelopeHash>>();
+                let env_hashes = EnvelopeHashBatch::try_from(env_hashes.as_slice())
+                    .map_err(|()| Error::new("The moved messages were not found."))?;
+                let job = account.backend.write().unwrap().copy_messages(
+                    env_hashes,
+                    destination,
+                    self.mailbox_hash,
+                    /* move? */ true,
+                )?;
+                let handle = account.job_executor.spawn_specialized(job);
+                account.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: "undo message moving".into(),
+                        handle,
+                        on_finish: None,
+                        log_level: LogLevel::INFO,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reverts the latest undo entry, if its grace period hasn't run out.
+pub fn undo(context: &mut Context) {
+    let entry = match context.undo.pop(Instant::now()) {
+        Some(entry) => entry,
+        None => {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "Nothing to undo.".to_string(),
+                )));
+            return;
+        }
+    };
+    let description = entry.description.clone();
+    match entry.revert(context) {
+        Ok(()) => {
+            context
+                .replies
+                .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                    "Undone: {}",
+                    description
+                ))));
+        }
+        Err(err) => {
+            context.replies.push_back(UIEvent::Notification(
+                Some(format!("Could not undo: {}", description)),
+                err.to_string(),
+                Some(crate::types::NotificationType::Error(err.kind)),
+            ));
+        }
+    }
+}
+
+/// Wraps the job deleting `env_hashes` so that it first loads the messages,
+/// and once they are deleted pushes an undo entry that saves them back.
+pub fn undoable_delete(
+    account: &Account,
+    env_hashes: &EnvelopeHashBatch,
+    mailbox_hash: MailboxHash,
+    grace_period: u64,
+    delete: std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>>,
+) -> ResultFuture<()> {
+    let flags = env_hashes
+        .iter()
+        .map(|env_hash| {
+            if account.collection.contains_key(&env_hash) {
+                account.collection.get_env(env_hash).flags()
+            } else {
+                Flag::default()
+            }
+        })
+        .collect::<Vec<Flag>>();
+    let bytes = env_hashes
+        .iter()
+        .map(|env_hash| account.operation(env_hash).and_then(|mut op| op.as_bytes()))
+        .collect::<Result<Vec<_>>>()?;
+    let account_hash = account.hash();
+    let sender = account.sender.clone();
+    let len = flags.len();
+    Ok(Box::pin(async move {
+        let bytes = futures::future::try_join_all(bytes).await?;
+        delete.await?;
+        let entry = UndoEntry::new(
+            account_hash,
+            mailbox_hash,
+            format!("{} message{} deleted", len, if len == 1 { "" } else { "s" }),
+            grace_period,
+            UndoKind::Delete(bytes.into_iter().zip(flags).collect()),
+        );
+        let _ = sender.send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+            Box::new(move |context| entry.push(context)),
+        ))));
+        Ok(())
+    }))
+}
+
+/// Recent undoable actions, most recent last.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    entries: Vec<UndoEntry>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, entry: UndoEntry) {
+        self.prune(Instant::now());
+        self.entries.push(entry);
+    }
+
+    /// Removes and returns the latest entry whose grace period hasn't run
+    /// out.
+    pub fn pop(&mut self, now: Instant) -> Option<UndoEntry> {
+        self.prune(now);
+        self.entries.pop()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        self.entries.retain(|entry| entry.expires > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_stack() {
+        let entry = |description: &str, grace_period| {
+            UndoEntry::new(
+                melib::AccountHash::default(),
+                MailboxHash::default(),
+                description.to_string(),
+                grace_period,
+                UndoKind::Delete(vec![]),
+            )
+        };
+        let mut stack = UndoStack::default();
+        stack.push(entry("first", 60));
+        stack.push(entry("second", 60));
+        stack.push(entry("expired", 0));
+        let now = Instant::now();
+        assert_eq!(stack.pop(now).unwrap().description, "second");
+        assert_eq!(stack.pop(now).unwrap().description, "first");
+        assert!(stack.pop(now).is_none());
+
+        stack.push(entry("late", 60));
+        assert!(stack.pop(now + Duration::from_secs(61)).is_none());
+    }
+
+    #[test]
+    fn test_undo_set_flags() {
+        let collection = Collection::default();
+        for (i, flags) in [Flag::SEEN, Flag::empty(), Flag::SEEN | Flag::FLAGGED]
+            .iter()
+            .enumerate()
+        {
+            let mut env = melib::Envelope::new(EnvelopeHash(i as u64 + 1));
+            env.set_flags(*flags);
+            collection
+                .envelopes
+                .write()
+                .unwrap()
+                .insert(env.hash(), env);
+        }
+        let env_hashes =
+            EnvelopeHashBatch::try_from(&[EnvelopeHash(1), EnvelopeHash(2), EnvelopeHash(3)][..])
+                .unwrap();
+
+        /* Nothing changes. */
+        assert!(
+            UndoKind::set_flags(&collection, &env_hashes, &[(Ok(Flag::DRAFT), false)]).is_none()
+        );
+
+        let groups = match UndoKind::set_flags(
+            &collection,
+            &env_hashes,
+            &[(Ok(Flag::SEEN), true), (Ok(Flag::FLAGGED), true)],
+        ) {
+            Some(UndoKind::SetFlags(groups)) => groups,
+            other => panic!("{:?}", other),
+        };
+        let groups = groups
+            .into_iter()
+            .map(|(env_hashes, flags)| {
+                let mut env_hashes = env_hashes.iter().collect::<Vec<EnvelopeHash>>();
+                env_hashes.sort();
+                (env_hashes, flags.into_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (vec![EnvelopeHash(1)], vec![(Ok(Flag::FLAGGED), false)]),
+                (
+                    vec![EnvelopeHash(2)],
+                    vec![(Ok(Flag::SEEN), false), (Ok(Flag::FLAGGED), false)]
+                ),
+            ]
+        );
+    }
+}
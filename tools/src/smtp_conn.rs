@@ -17,6 +17,9 @@ fn main() -> Result<()> {
             auth_type: Default::default(),
         },
         envelope_from: String::new(),
+        proxy: None,
+        use_srv: false,
+        tls: Default::default(),
     };
     std::thread::spawn(move || {
         let ex = smol::Executor::new();